@@ -37,7 +37,7 @@ fn main() {
                 path.file_name().unwrap().to_str().unwrap(),
             ])
             .output()
-            .expect("Failed to run glslangValidator");
+            .expect("Failed to run glslangValidator, the SPIR-V features need it on the PATH");
 
         if !output.status.success() {
             panic!("{:?}", output)
@@ -65,10 +65,11 @@ fn main() {
     .collect::<Vec<_>>();
 
     println!("cargo:rerun-if-changed=src/rendering/postprocess/shaders");
+    println!("cargo:rerun-if-changed=src/rendering/shaders");
     internal_paths.iter().for_each(|path| {
         let output_fname = path.file_name().unwrap().to_str().unwrap().to_owned() + ".spv";
         let output = Command::new("glslangValidator")
-            .current_dir("src/rendering/postprocess/shaders")
+            .current_dir(path.parent().unwrap())
             .args(&[
                 "-G450",
                 "-e main",
@@ -78,7 +79,7 @@ fn main() {
                 path.file_name().unwrap().to_str().unwrap(),
             ])
             .output()
-            .expect("Failed to run glslangValidator");
+            .expect("Failed to run glslangValidator, the SPIR-V features need it on the PATH");
 
         if !output.status.success() {
             panic!("{:?}", output)
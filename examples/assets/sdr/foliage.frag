@@ -0,0 +1,475 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+const float EPSILON = 0.001;
+
+#include "pbs_lighting.glsl"
+
+const int RENDER_MODE_ALBEDO = 1;
+const int RENDER_MODE_METALLIC = 2;
+const int RENDER_MODE_ROUGHNESS = 3;
+const int RENDER_MODE_NORMALS = 4;
+const int RENDER_MODE_TANGENTS = 5;
+const int RENDER_MODE_UV = 6;
+const int RENDER_MODE_NDOTV = 7;
+const int RENDER_MODE_AO = 8;
+const int RENDER_MODE_SPECULAR_AO = 9;
+const int RENDER_MODE_HORIZON_SPECULAR_AO = 10;
+const int RENDER_MODE_DIFFUSE_AMBIENT = 11;
+const int RENDER_MODE_SPECULAR_AMBIENT = 12;
+const int RENDER_MODE_LIGHT_COUNT = 13;
+const int RENDER_MODE_OVERDRAW = 14;
+const int RENDER_MODE_SHADOW_VIEWS = 15;
+const int RENDER_MODE_MIP_LEVEL = 16;
+
+const int LIGHT_CULLING_GRID_CLUSTERS = 1;
+
+const int LIGHTMAP_MODE_IRRADIANCE = 1;
+const int LIGHTMAP_MODE_OCCLUSION = 2;
+
+layout(location = 0) in VsOut {
+    vec3 wViewDirection;
+    vec3 wNormal;
+    vec4 wTangent;
+    vec2 texcoord;
+    vec2 texcoord1;
+} fsIn;
+
+layout(std140, binding = 9) uniform LightCullingDebugBlock
+{
+    vec2 cullingViewportSize;
+    float cullingNear;
+    float cullingFar;
+    int cullingTileSize;
+    int cullingSlices;
+    int cullingGrid;
+    int cullingMaxLights;
+};
+
+layout(std140, binding = 4) uniform MaterialBlock
+{
+    vec4 baseColor;
+    float metallicScale;
+    float metallicBias;
+    float roughnessScale;
+    float roughnessBias;
+    float aoScale;
+    float aoBias;
+    // Parallax occlusion mapping parameters, read by pbs_pom.frag.
+    float pomMinLayers;
+    float pomMaxLayers;
+    float pomDisplacementScale;
+    int parallaxMappingMethod;
+    float lightmapIntensity;
+    // 0 without a lightmap.
+    int lightmapMode;
+};
+
+layout(std140, binding = 10) uniform FoliageBlock
+{
+    // xyz: direction the wind blows towards, w: strength.
+    vec4 wind;
+    // rgb: color of the light transmitted through leaves, w: translucency.
+    vec4 transmissionColor;
+    float windFrequency;
+    float flutterStrength;
+    float time;
+    float alphaCutoff;
+    float transmissionPower;
+    int alphaToCoverage;
+};
+
+layout(binding = 0) uniform sampler2D albedoMap;
+layout(binding = 1) uniform sampler2D normalMap;
+layout(binding = 2) uniform sampler2D m_r_aoMap;
+
+// Screen space ambient occlusion, sampled at the fragment position.
+layout(binding = 7) uniform sampler2D ssaoMap;
+
+// Externally baked lighting of static geometry, sampled with the second UV channel. Irradiance
+// in rgb and ambient occlusion in a, or ambient occlusion in r.
+layout(binding = 14) uniform sampler2D lightmap;
+
+// Reflection and irradiance probes, smallest volume first. Their cube maps are layers of the
+// probe arrays.
+struct LightProbe
+{
+    // w: intensity.
+    vec4 position;
+    // xyz: half extents of a box, x: radius of a sphere. w: blend distance.
+    vec4 extents;
+    // x: layer, y: shape.
+    ivec4 info;
+};
+
+const int PROBE_SHAPE_BOX = 0;
+const int PROBE_SHAPE_SPHERE = 1;
+
+layout(std430, binding = 5) readonly buffer LightProbeBlock
+{
+    ivec4 probeCount;
+    LightProbe probes[];
+};
+
+layout(binding = 12) uniform samplerCubeArray radianceProbes;
+layout(binding = 13) uniform samplerCubeArray irradianceProbes;
+
+layout(location = 0) out vec4 outColor;
+
+// Light probes--------
+// 1 inside the volume of the probe, fading to 0 over the blend distance towards its border.
+float LightProbeWeight(in LightProbe probe, in vec3 wPosition)
+{
+    vec3 local = wPosition - probe.position.xyz;
+    float blendDistance = max(probe.extents.w, 0.0001);
+
+    if (probe.info.y == PROBE_SHAPE_SPHERE) {
+        return clamp((probe.extents.x - length(local)) / blendDistance, 0.0, 1.0);
+    }
+
+    vec3 distanceToBorder = probe.extents.xyz - abs(local);
+    float distance = min(min(distanceToBorder.x, distanceToBorder.y), distanceToBorder.z);
+    return clamp(distance / blendDistance, 0.0, 1.0);
+}
+
+// Intersects the direction from the fragment with the volume of the probe and returns the
+// direction to the intersection from the capture point, so reflections line up with the
+// surroundings the probe captured.
+// Reference: https://seblagarde.wordpress.com/2012/09/29/image-based-lighting-approaches-and-parallax-corrected-cubemap/
+vec3 ParallaxCorrect(in LightProbe probe, in vec3 wPosition, in vec3 direction)
+{
+    vec3 local = wPosition - probe.position.xyz;
+    float distance;
+
+    if (probe.info.y == PROBE_SHAPE_SPHERE) {
+        float b = dot(local, direction);
+        float c = dot(local, local) - probe.extents.x * probe.extents.x;
+        distance = -b + sqrt(max(b * b - c, 0.0));
+    } else {
+        vec3 toMax = (probe.extents.xyz - local) / direction;
+        vec3 toMin = (-probe.extents.xyz - local) / direction;
+        vec3 furthest = max(toMax, toMin);
+        distance = min(min(furthest.x, furthest.y), furthest.z);
+    }
+
+    return local + direction * distance;
+}
+// --------------------
+
+// Whether the bounding sphere of a light overlaps a tile given in NDC and a range of view
+// depths. Conservative like a light culler: the box around the sphere is projected.
+bool LightOverlapsCell(in vec3 wCenter, in float radius, in vec2 ndcMin, in vec2 ndcMax, in float zMin, in float zMax)
+{
+    float depth = (viewProjection * vec4(wCenter, 1.0)).w;
+    if (depth + radius < zMin || depth - radius > zMax) {
+        return false;
+    }
+
+    vec2 boundsMin = vec2(1.0);
+    vec2 boundsMax = vec2(-1.0);
+    for (int i = 0; i < 8; ++i) {
+        vec3 corner = vec3(i & 1, (i >> 1) & 1, (i >> 2) & 1) * 2.0 - 1.0;
+        vec4 clip = viewProjection * vec4(wCenter + corner * radius, 1.0);
+
+        // Reaches behind the camera, the projection of the box covers the whole screen.
+        if (clip.w <= EPSILON) {
+            return true;
+        }
+
+        boundsMin = min(boundsMin, clip.xy / clip.w);
+        boundsMax = max(boundsMax, clip.xy / clip.w);
+    }
+
+    return all(lessThanEqual(boundsMin, ndcMax)) && all(greaterThanEqual(boundsMax, ndcMin));
+}
+
+// The lights a tiled or clustered culler would assign to the cell of the fragment.
+int LightCount()
+{
+    vec2 tile = floor(gl_FragCoord.xy / float(cullingTileSize));
+    vec2 ndcMin = tile * float(cullingTileSize) / cullingViewportSize * 2.0 - 1.0;
+    vec2 ndcMax = min((tile + 1.0) * float(cullingTileSize), cullingViewportSize) / cullingViewportSize * 2.0 - 1.0;
+
+    float zMin = cullingNear;
+    float zMax = cullingFar;
+    if (cullingGrid == LIGHT_CULLING_GRID_CLUSTERS) {
+        float depthRatio = cullingFar / cullingNear;
+        float slice = floor(log(1.0 / (gl_FragCoord.w * cullingNear)) / log(depthRatio) * float(cullingSlices));
+        zMin = cullingNear * pow(depthRatio, slice / float(cullingSlices));
+        zMax = cullingNear * pow(depthRatio, (slice + 1.0) / float(cullingSlices));
+    }
+
+    int count = 0;
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+
+        if (light.info.x == LIGHT_TYPE_DIRECTIONAL || LightOverlapsCell(light.position.xyz, light.direction.w, ndcMin, ndcMax, zMin, zMax)) {
+            ++count;
+        }
+    }
+
+    return count;
+}
+
+// Blue for few lights through green and yellow to red at the maximum.
+vec3 Heatmap(in float t)
+{
+    t = clamp(t, 0.0, 1.0);
+    return clamp(vec3(1.5 - abs(4.0 * t - 3.0), 1.5 - abs(4.0 * t - 2.0), 1.5 - abs(4.0 * t - 1.0)), 0.0, 1.0);
+}
+
+// A color per shadow view of the first shadowed light in range of the fragment, with a checker
+// of the atlas texels it covers. Grey where no shadow view covers the fragment.
+vec3 ShadowViewsDebugColor(in vec3 wPosition)
+{
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+        int shadowIndex = light.info.y;
+
+        if (shadowIndex < 0 || (light.info.x != LIGHT_TYPE_POINT && light.info.x != LIGHT_TYPE_SPOT)) {
+            continue;
+        }
+
+        vec3 d = wPosition - light.position.xyz;
+        if (length(d) > light.direction.w) {
+            continue;
+        }
+
+        int index = light.info.x == LIGHT_TYPE_POINT ? shadowIndex + CubeFace(d) : shadowIndex;
+        ShadowView view = shadowViews[index];
+
+        vec4 clipPosition = view.viewProjection * vec4(wPosition, 1.0);
+        vec3 ndc = clipPosition.xyz / clipPosition.w;
+        if (clipPosition.w <= 0.0 || any(greaterThan(abs(ndc), vec3(1.0)))) {
+            continue;
+        }
+
+        vec2 texel = floor((view.atlasRect.xy + (ndc.xy * 0.5 + 0.5) * view.atlasRect.zw) * vec2(textureSize(shadowAtlas, 0)));
+        float checker = mod(texel.x + texel.y, 2.0) == 0.0 ? 1.0 : 0.7;
+
+        return Heatmap(fract(float(index) * 0.618034)) * checker;
+    }
+
+    return vec3(0.2);
+}
+
+// Light through thin leaves lit from behind, wrapped around the back face and stronger when
+// looking towards the light.
+vec3 Transmission(in vec3 n, in vec3 l, in vec3 v, in vec3 albedo, in vec3 lightColor)
+{
+    float backLight = clamp(dot(-n, l) * 0.5 + 0.5, 0.0, 1.0);
+    float forwardScatter = pow(clamp(dot(v, -l), 0.0, 1.0), transmissionPower);
+
+    return transmissionColor.rgb * albedo * lightColor * transmissionColor.w * backLight * (1.0 + forwardScatter) * ONE_OVER_PI;
+}
+
+void main()
+{
+    // Two sided, back faces are lit with the flipped normal.
+    vec3 wGeometricNormal = normalize(fsIn.wNormal) * (gl_FrontFacing ? 1.0 : -1.0);
+
+    vec3 t = normalize(fsIn.wTangent.xyz);
+    mat3 tangentToWorldMat = CreateTangentToWorldMatrix(wGeometricNormal, t, fsIn.wTangent.w);
+
+    vec3 n = normalize(tangentToWorldMat * SampleNormalMap(normalMap, fsIn.texcoord, 1.0));
+
+    vec3 v = normalize(fsIn.wViewDirection);
+    vec3 r = reflect(-v, n);
+    vec3 wPosition = eyePosition.xyz - fsIn.wViewDirection;
+
+    float NdotV = clamp(dot(n, v), 0.0, 1.0);
+
+    vec4 albedo = texture(albedoMap, fsIn.texcoord) * baseColor;
+
+    // Sharpened to the cutoff over a pixel, so the coverage of the samples antialiases the edges.
+    // Reference: https://bgolus.medium.com/anti-aliased-alpha-test-the-esoteric-alpha-to-coverage-8b177335ae4f
+    float alpha = albedo.a;
+    if (alphaToCoverage == 1) {
+        alpha = clamp((alpha - alphaCutoff) / max(fwidth(alpha), 0.0001) + 0.5, 0.0, 1.0);
+    } else if (alpha < alphaCutoff) {
+        discard;
+    }
+
+    vec3 m_r_ao = texture(m_r_aoMap, fsIn.texcoord).rgb;
+    float metallic = clamp((m_r_ao.r + metallicBias) * metallicScale, 0.0, 1.0);
+    float perceptualRoughness = clamp((m_r_ao.g + roughnessBias) * roughnessScale, MIN_ROUGHNESS, 1.0) ;
+    float ao = clamp((m_r_ao.b + aoBias) * aoScale, 0.0, 1.0);
+
+    if (screenSpaceAO == 1) {
+        ao *= texture(ssaoMap, gl_FragCoord.xy / textureSize(ssaoMap, 0)).r;
+    }
+
+    float lod = PerceptualRoughnessToLod(perceptualRoughness);
+    vec3 specular_direction = GetSpecularDominantDirection(n, r, perceptualRoughness);
+
+    // Every probe takes its weight of what the smaller ones left, the environment maps the rest.
+    vec3 irradiance = vec3(0.0);
+    vec3 radiance = vec3(0.0);
+    float remainingWeight = 1.0;
+
+    for (int i = 0; i < probeCount.x && remainingWeight > 0.0; ++i) {
+        LightProbe probe = probes[i];
+        float weight = LightProbeWeight(probe, wPosition) * remainingWeight;
+
+        if (weight > 0.0) {
+            float layer = float(probe.info.x);
+            vec3 probeDirection = ParallaxCorrect(probe, wPosition, specular_direction);
+
+            irradiance += textureLod(irradianceProbes, vec4(n, layer), 0.0).rgb * weight * probe.position.w;
+            radiance += textureLod(radianceProbes, vec4(probeDirection, layer), lod).rgb * weight * probe.position.w;
+            remainingWeight -= weight;
+        }
+    }
+
+    irradiance += texture(irradianceMap, n).rgb * remainingWeight;
+    radiance += textureLod(radianceMap, specular_direction, lod).rgb * remainingWeight;
+
+    if (lightmapMode == LIGHTMAP_MODE_IRRADIANCE) {
+        vec4 bakedLight = texture(lightmap, fsIn.texcoord1);
+        irradiance = bakedLight.rgb * lightmapIntensity;
+        ao *= bakedLight.a;
+    } else if (lightmapMode == LIGHTMAP_MODE_OCCLUSION) {
+        ao *= texture(lightmap, fsIn.texcoord1).r;
+    }
+
+    vec2 lutSample = texture(brdfLUT, vec2(NdotV, perceptualRoughness)).rg;
+
+    vec3 F0 = mix(vec3(F0_DIELECTRIC), albedo.rgb, metallic);
+
+    mat3 worldToTangentMat = transpose(tangentToWorldMat);
+
+    vec3 analyticalLight = vec3(0.0);
+
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+
+        if (light.info.x == LIGHT_TYPE_RECT || light.info.x == LIGHT_TYPE_DISK) {
+            // The falloff with distance is part of the integral.
+            vec3 toLight = light.position.xyz - wPosition;
+            float window = RangeWindow(dot(toLight, toLight), light.direction.w);
+
+            analyticalLight += light.color.rgb * window * AreaLight(light, wPosition, n, v, NdotV, F0, albedo.rgb * (1.0 - metallic), perceptualRoughness);
+            continue;
+        }
+
+        vec3 l;
+        vec3 lightColor = light.color.rgb;
+
+        if (light.info.x == LIGHT_TYPE_DIRECTIONAL) {
+            l = normalize(light.direction.xyz);
+        } else {
+            vec3 toLight = light.position.xyz - wPosition;
+            float distanceSquared = dot(toLight, toLight);
+            l = toLight * inversesqrt(max(distanceSquared, 0.0001));
+
+            lightColor *= DistanceAttenuation(distanceSquared, light.direction.w);
+
+            if (light.info.x == LIGHT_TYPE_SPOT) {
+                lightColor *= AngleAttenuation(l, light.direction.xyz, light.shape.xy);
+                lightColor *= SpotLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            } else {
+                lightColor *= PointLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            }
+        }
+
+        // No early out for unlit fragments, the specular AA of the BRDF takes derivatives.
+        float NdotL = clamp(dot(n, l), 0.0, 1.0);
+        vec3 h = normalize(l + v);
+
+        analyticalLight += BRDF(
+            clamp(dot(n, h), 0.0, 1.0),
+            NdotV,
+            NdotL,
+            clamp(dot(h, v), 0.0, 1.0),
+            lightColor,
+            F0,
+            albedo.rgb,
+            metallic,
+            perceptualRoughness,
+            worldToTangentMat * h);
+
+        analyticalLight += Transmission(n, l, v, albedo.rgb, lightColor);
+    }
+
+    vec3 imageBasedLight = IBL(
+        NdotV,
+        F0,
+        albedo.rgb,
+        metallic,
+        perceptualRoughness,
+        ao,
+        lutSample,
+        irradiance,
+        radiance,
+        r,
+        n);
+
+    switch (renderMode) {
+        case RENDER_MODE_ALBEDO:
+            outColor = vec4(albedo.rgb, 1.0);
+            break;
+        case RENDER_MODE_METALLIC:
+            outColor = vec4(m_r_ao.rrr, 1.0);
+            break;
+        case RENDER_MODE_ROUGHNESS:
+            outColor = vec4(m_r_ao.ggg, 1.0);
+            break;
+        case RENDER_MODE_NORMALS:
+            outColor = vec4(n * 0.5 + 0.5, 1.0);
+            break;
+        case RENDER_MODE_TANGENTS:
+            outColor = vec4(t * 0.5 + 0.5, 1.0);
+            break;
+        case RENDER_MODE_UV:
+            outColor = vec4(fsIn.texcoord, 0.0, 1.0);
+            break;
+        case RENDER_MODE_NDOTV:
+            outColor = vec4(NdotV.xxx, 1.0);
+            break;
+        case RENDER_MODE_AO:
+            outColor = vec4(m_r_ao.bbb, 1.0);
+            break;
+        case RENDER_MODE_SPECULAR_AO:
+            outColor = vec4(so.xxx, 1.0);
+            break;
+        case RENDER_MODE_HORIZON_SPECULAR_AO:
+            outColor = vec4(horizonSo.xxx, 1.0);
+            break;
+        case RENDER_MODE_DIFFUSE_AMBIENT:
+            outColor = vec4(irradiance.rgb, 1.0);
+            break;
+        case RENDER_MODE_SPECULAR_AMBIENT:
+            outColor = vec4(radiance.rgb, 1.0);
+            break;
+        case RENDER_MODE_LIGHT_COUNT:
+            int count = LightCount();
+            vec3 heat = count == 0 ? vec3(0.0) : Heatmap(float(count) / float(cullingMaxLights));
+
+            // Over the luminance of the albedo, with the tile borders darkened.
+            vec2 tileUv = fract(gl_FragCoord.xy / float(cullingTileSize));
+            float border = any(lessThan(tileUv * float(cullingTileSize), vec2(1.0))) ? 0.5 : 1.0;
+            float luminance = dot(albedo.rgb, vec3(0.2126, 0.7152, 0.0722));
+            outColor = vec4(mix(vec3(luminance), heat, 0.75) * border, 1.0);
+            break;
+        case RENDER_MODE_OVERDRAW:
+            // Blended additively without a depth test, every layer adds the same amount.
+            outColor = vec4(0.1, 0.04, 0.02, 1.0);
+            break;
+        case RENDER_MODE_SHADOW_VIEWS:
+            outColor = vec4(ShadowViewsDebugColor(wPosition), 1.0);
+            break;
+        case RENDER_MODE_MIP_LEVEL:
+            float maxLevel = max(float(textureQueryLevels(albedoMap) - 1), 1.0);
+            vec3 level = Heatmap(textureQueryLod(albedoMap, fsIn.texcoord).y / maxLevel);
+            outColor = vec4(mix(vec3(dot(albedo.rgb, vec3(0.2126, 0.7152, 0.0722))), level, 0.75), 1.0);
+            break;
+        default:
+            vec3 finalColor = analyticalLight + imageBasedLight;
+            if (fogEnabled != 0) {
+                finalColor = mix(FogColor(), finalColor, FogTransmittance(eyePosition.xyz, wPosition));
+            }
+            outColor = vec4(finalColor, alpha);
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+const float TWO_PI = 6.28318530718;
+
+//Vertex attributes
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inTangent;
+layout(location = 3) in vec2 inTexcoord;
+// r: how much the vertex flutters, 0 for trunks and branches.
+layout(location = 4) in vec3 inColor;
+layout(location = 5) in vec2 inTexcoord1;
+
+//Instance attributes
+layout(location = 8) in mat4 inInstanceModel;
+layout(location = 12) in uint inInstanceMaterialIndex;
+
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+layout(std140, binding = 10) uniform FoliageBlock
+{
+    // xyz: direction the wind blows towards, w: strength.
+    vec4 wind;
+    // rgb: color of the light transmitted through leaves, w: translucency.
+    vec4 transmissionColor;
+    float windFrequency;
+    float flutterStrength;
+    float time;
+    float alphaCutoff;
+    float transmissionPower;
+    int alphaToCoverage;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// Varying variables
+// prefixes: w -> world space
+//           v -> view space
+//           t -> tangent space
+//           l -> local space
+layout(location = 0) out VsOut {
+    vec3 wViewDirection;
+    vec3 wNormal;
+    vec4 wTangent;
+    vec2 texcoord;
+    vec2 texcoord1;
+} vsOut;
+
+// A phase per instance from its position, so neighbouring plants do not sway in lockstep.
+float InstancePhase(in vec3 wOrigin)
+{
+    return fract(sin(dot(wOrigin.xz, vec2(12.9898, 78.233))) * 43758.5453) * TWO_PI;
+}
+
+void main()
+{
+    vec3 wOrigin = inInstanceModel[3].xyz;
+    float phase = InstancePhase(wOrigin);
+
+    //Transform vertex to world space.
+    vec4 lVertexPosition = vec4(inPosition, 1.0);
+    vec4 wVertexPosition = inInstanceModel * lVertexPosition;
+
+    mat3 normalMat = transpose(inverse(mat3(inInstanceModel)));
+    //Calculate the normal. Bring it to world space
+    vsOut.wNormal = normalMat * inNormal;
+
+    // The whole plant bends away from the wind, more the higher above its root. Two waves of
+    // different frequencies keep the sway from looking periodic.
+    float height = max(wVertexPosition.y - wOrigin.y, 0.0);
+    float sway = sin(time * windFrequency + phase) * 0.7 + sin(time * windFrequency * 2.3 + phase * 1.7) * 0.3;
+    wVertexPosition.xyz += wind.xyz * wind.w * height * height * (1.0 + 0.5 * sway);
+
+    // Leaves flutter along their normals, out of phase along the plant.
+    float flutter = sin(time * windFrequency * 6.0 + phase + dot(wVertexPosition.xyz, vec3(3.1, 1.7, 2.3)));
+    wVertexPosition.xyz += normalize(vsOut.wNormal) * flutter * flutterStrength * wind.w * inColor.r;
+
+    gl_Position = view_projection * wVertexPosition;
+
+    // Bring tangent to world space.
+    vsOut.wTangent = vec4(normalMat * inTangent.xyz, inTangent.w);
+
+    //Assign the view direction for output.
+    vsOut.wViewDirection = eyePosition.xyz - wVertexPosition.xyz;
+
+    //Assign texture coorinates for output.
+    vsOut.texcoord = inTexcoord;
+    vsOut.texcoord1 = inTexcoord1;
+}
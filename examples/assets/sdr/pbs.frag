@@ -1,12 +1,10 @@
 #version 450 core
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
 
 const float EPSILON = 0.001;
-const float F0_DIELECTRIC = 0.04;
-const float PI = 3.14159265359;
-const float ONE_OVER_PI = 0.318309886;
-const float MAX_REFLECTION_LOD = 5.0;
-const float MIN_ROUGHNESS = 0.023;
+
+#include "pbs_lighting.glsl"
 
 const int RENDER_MODE_ALBEDO = 1;
 const int RENDER_MODE_METALLIC = 2;
@@ -20,23 +18,33 @@ const int RENDER_MODE_SPECULAR_AO = 9;
 const int RENDER_MODE_HORIZON_SPECULAR_AO = 10;
 const int RENDER_MODE_DIFFUSE_AMBIENT = 11;
 const int RENDER_MODE_SPECULAR_AMBIENT = 12;
+const int RENDER_MODE_LIGHT_COUNT = 13;
+const int RENDER_MODE_OVERDRAW = 14;
+const int RENDER_MODE_SHADOW_VIEWS = 15;
+const int RENDER_MODE_MIP_LEVEL = 16;
+
+const int LIGHT_CULLING_GRID_CLUSTERS = 1;
+
+const int LIGHTMAP_MODE_IRRADIANCE = 1;
+const int LIGHTMAP_MODE_OCCLUSION = 2;
 
 layout(location = 0) in VsOut {
     vec3 wViewDirection;
     vec3 wNormal;
     vec4 wTangent;
     vec2 texcoord;
+    vec2 texcoord1;
 } fsIn;
 
-layout(std140, binding = 2) uniform PerFrameBlock
+layout(std140, binding = 9) uniform LightCullingDebugBlock
 {
-    vec4 wLightDirection;
-    vec4 lightColor;
-    vec2 ssVarianceAndThreshold;
-    int specularAA;
-    int specularAO;
-    int disneyGgxHotness;
-    int renderMode;
+    vec2 cullingViewportSize;
+    float cullingNear;
+    float cullingFar;
+    int cullingTileSize;
+    int cullingSlices;
+    int cullingGrid;
+    int cullingMaxLights;
 };
 
 layout(std140, binding = 4) uniform MaterialBlock
@@ -48,227 +56,188 @@ layout(std140, binding = 4) uniform MaterialBlock
     float roughnessBias;
     float aoScale;
     float aoBias;
+    // Parallax occlusion mapping parameters, read by pbs_pom.frag.
+    float pomMinLayers;
+    float pomMaxLayers;
+    float pomDisplacementScale;
+    int parallaxMappingMethod;
+    float lightmapIntensity;
+    // 0 without a lightmap.
+    int lightmapMode;
 };
 
 layout(binding = 0) uniform sampler2D albedoMap;
 layout(binding = 1) uniform sampler2D normalMap;
 layout(binding = 2) uniform sampler2D m_r_aoMap;
-layout(binding = 3) uniform sampler2D brdfLUT;
 
-layout(binding = 4) uniform samplerCube irradianceMap;
-layout(binding = 5) uniform samplerCube radianceMap;
-
-layout(location = 0) out vec4 outColor;
+// Screen space ambient occlusion, sampled at the fragment position.
+layout(binding = 7) uniform sampler2D ssaoMap;
 
-float so;
-float horizonSo;
+// Externally baked lighting of static geometry, sampled with the second UV channel. Irradiance
+// in rgb and ambient occlusion in a, or ambient occlusion in r.
+layout(binding = 14) uniform sampler2D lightmap;
 
-mat3 CreateTangentToWorldMatrix(in vec3 n, in vec3 t, in float tSign)
+// Reflection and irradiance probes, smallest volume first. Their cube maps are layers of the
+// probe arrays.
+struct LightProbe
 {
-    t = normalize(t - dot(t, n) * n);
-
-    //Calculate the binormal
-    vec3 b = normalize(cross(n, t) * tSign);
-
-    return mat3(t, b, n);
-}
+    // w: intensity.
+    vec4 position;
+    // xyz: half extents of a box, x: radius of a sphere. w: blend distance.
+    vec4 extents;
+    // x: layer, y: shape.
+    ivec4 info;
+};
 
-// PBS FUNCTIONS --------------------------------------------------
+const int PROBE_SHAPE_BOX = 0;
+const int PROBE_SHAPE_SPHERE = 1;
 
-// Analytical Lights---
-vec3 FresnelSchlick(in float cosTheta, in vec3 F0)
+layout(std430, binding = 5) readonly buffer LightProbeBlock
 {
-    vec3 F90 = vec3(1.0);
+    ivec4 probeCount;
+    LightProbe probes[];
+};
 
-    if (specularAO == 1) {
-        F90 = vec3(clamp(dot(F0, vec3(50.0 * 0.33)), 0.0, 1.0));
-    }
+layout(binding = 12) uniform samplerCubeArray radianceProbes;
+layout(binding = 13) uniform samplerCubeArray irradianceProbes;
 
-    return F0 + (F90 - F0) * pow(1.0 - cosTheta, 5.0);
-}
+layout(location = 0) out vec4 outColor;
 
-vec3 FresnelSchlickRoughness(in float NdotV, in vec3 F0, in float perceptualRoughness)
+// Light probes--------
+// 1 inside the volume of the probe, fading to 0 over the blend distance towards its border.
+float LightProbeWeight(in LightProbe probe, in vec3 wPosition)
 {
-    vec3 F90;
+    vec3 local = wPosition - probe.position.xyz;
+    float blendDistance = max(probe.extents.w, 0.0001);
 
-    if (specularAO == 1) {
-        F90 = vec3(clamp(dot(max(vec3(1.0 - perceptualRoughness), F0), vec3(50.0 * 0.33)), 0.0, 1.0));
+    if (probe.info.y == PROBE_SHAPE_SPHERE) {
+        return clamp((probe.extents.x - length(local)) / blendDistance, 0.0, 1.0);
     }
-    else {
-        F90 = max(vec3(1.0 - perceptualRoughness), F0);
-    }
-
-    return F0 + (F90 - F0) * pow(1.0 - NdotV, 5.0);
-}
-
-float DistributionGGX(in float NdotH, in float perceptualRoughness)
-{
-    float a = perceptualRoughness * perceptualRoughness;
-    float a2 = a * a;
-    float NdotH2 = NdotH * NdotH;
 
-    float num   = a2;
-    float denom = (NdotH2 * (a2 - 1.0) + 1.0);
-    denom = PI * denom * denom;
-
-    return num / denom;
+    vec3 distanceToBorder = probe.extents.xyz - abs(local);
+    float distance = min(min(distanceToBorder.x, distanceToBorder.y), distanceToBorder.z);
+    return clamp(distance / blendDistance, 0.0, 1.0);
 }
 
-// Reference: http://www.jp.square-enix.com/tech/library/pdf/ImprovedGeometricSpecularAA(slides).pdf
-// Reference: http://www.jp.square-enix.com/tech/library/pdf/ImprovedGeometricSpecularAA.pdf
-float BiasedAxisAlignedGeometricSpecularAA(in vec3 tHalfVector, in float perceptualRoughness)
+// Intersects the direction from the fragment with the volume of the probe and returns the
+// direction to the intersection from the capture point, so reflections line up with the
+// surroundings the probe captured.
+// Reference: https://seblagarde.wordpress.com/2012/09/29/image-based-lighting-approaches-and-parallax-corrected-cubemap/
+vec3 ParallaxCorrect(in LightProbe probe, in vec3 wPosition, in vec3 direction)
 {
-    float screenSpaceVariance = ssVarianceAndThreshold.x;
-    float clampingThreshold = ssVarianceAndThreshold.y;
-
-    float roughness = perceptualRoughness * perceptualRoughness;
-
-    vec2 halfVector2D = tHalfVector.xy;
-    vec2 deltaU = dFdx(halfVector2D);
-    vec2 deltaV = dFdy(halfVector2D);
-
-    vec2 boundingRectangle = abs(deltaU) + abs(deltaV);
-    vec2 variance = screenSpaceVariance * (boundingRectangle * boundingRectangle);
-    vec2 kernelRoughnessSquared = min(2.0 * variance, clampingThreshold);
-
-    return clamp(roughness + kernelRoughnessSquared, 0.0, 1.0).x;
-}
-
-float ComputeSpecularAO(float NoV, float ao, float roughness)
-{
-    return clamp(pow(NoV + ao, exp2(-16.0 * roughness - 1.0)) - 1.0 + ao, 0.0, 1.0);
-}
-
-float ComputeHorizonSpecularAO(vec3 r, vec3 n)
-{
-    return min(1.0 + dot(r, n), 1.0);
-}
-
-float DistributionGGXFiltered(in float NdotH, in float perceptualRoughness, in vec3 tHalfVector)
-{
-    float a = BiasedAxisAlignedGeometricSpecularAA(tHalfVector, perceptualRoughness);
-    float a2 = a * a;
-    float NdotH2 = NdotH * NdotH;
-
-    float num = a2;
-    float denom = (NdotH2 * (a2 - 1.0) + 1.0);
-    denom = PI * denom * denom;
+    vec3 local = wPosition - probe.position.xyz;
+    float distance;
+
+    if (probe.info.y == PROBE_SHAPE_SPHERE) {
+        float b = dot(local, direction);
+        float c = dot(local, local) - probe.extents.x * probe.extents.x;
+        distance = -b + sqrt(max(b * b - c, 0.0));
+    } else {
+        vec3 toMax = (probe.extents.xyz - local) / direction;
+        vec3 toMin = (-probe.extents.xyz - local) / direction;
+        vec3 furthest = max(toMax, toMin);
+        distance = min(min(furthest.x, furthest.y), furthest.z);
+    }
 
-    return num / denom;
+    return local + direction * distance;
 }
+// --------------------
 
-float GeometrySchlickGGX(in float NdotV, in float roughness)
+// Whether the bounding sphere of a light overlaps a tile given in NDC and a range of view
+// depths. Conservative like a light culler: the box around the sphere is projected.
+bool LightOverlapsCell(in vec3 wCenter, in float radius, in vec2 ndcMin, in vec2 ndcMax, in float zMin, in float zMax)
 {
-    float k = 0;
-
-    // Disney's roughness remapping to reduce "hotness" for punctual lights.
-    if (disneyGgxHotness == 1) {
-        float r = roughness + 1.0;
-        k = (r * r) * 0.125; // 1.0 / 8.0 = 0.125
-    } else { // Default "k" value
-        k = (roughness * roughness) * 0.5;
+    float depth = (viewProjection * vec4(wCenter, 1.0)).w;
+    if (depth + radius < zMin || depth - radius > zMax) {
+        return false;
     }
 
-    float num   = NdotV;
-    float denom = NdotV * (1.0 - k) + k;
+    vec2 boundsMin = vec2(1.0);
+    vec2 boundsMax = vec2(-1.0);
+    for (int i = 0; i < 8; ++i) {
+        vec3 corner = vec3(i & 1, (i >> 1) & 1, (i >> 2) & 1) * 2.0 - 1.0;
+        vec4 clip = viewProjection * vec4(wCenter + corner * radius, 1.0);
 
-    return num / denom;
-}
+        // Reaches behind the camera, the projection of the box covers the whole screen.
+        if (clip.w <= EPSILON) {
+            return true;
+        }
 
-float GeometrySmith(in float NdotV, in float NdotL, in float roughness)
-{
-    float ggx2  = GeometrySchlickGGX(NdotV, roughness);
-    float ggx1  = GeometrySchlickGGX(NdotL, roughness);
+        boundsMin = min(boundsMin, clip.xy / clip.w);
+        boundsMax = max(boundsMax, clip.xy / clip.w);
+    }
 
-    return ggx1 * ggx2;
+    return all(lessThanEqual(boundsMin, ndcMax)) && all(greaterThanEqual(boundsMax, ndcMin));
 }
 
-vec3 BRDF(in float NdotH, in float NdotV, in float NdotL, in float HdotV, in vec3 lightColor,
-in vec3 F0, in vec3 albedo, in float metallic, in float perceptualRoughness, in vec3 tHalfVector)
+// The lights a tiled or clustered culler would assign to the cell of the fragment.
+int LightCount()
 {
-    vec3 F = FresnelSchlick(HdotV, F0);
-    float NDF = 0.0;
-
-    if (specularAA == 1) {
-        NDF = DistributionGGXFiltered(NdotH, perceptualRoughness, tHalfVector);
+    vec2 tile = floor(gl_FragCoord.xy / float(cullingTileSize));
+    vec2 ndcMin = tile * float(cullingTileSize) / cullingViewportSize * 2.0 - 1.0;
+    vec2 ndcMax = min((tile + 1.0) * float(cullingTileSize), cullingViewportSize) / cullingViewportSize * 2.0 - 1.0;
+
+    float zMin = cullingNear;
+    float zMax = cullingFar;
+    if (cullingGrid == LIGHT_CULLING_GRID_CLUSTERS) {
+        float depthRatio = cullingFar / cullingNear;
+        float slice = floor(log(1.0 / (gl_FragCoord.w * cullingNear)) / log(depthRatio) * float(cullingSlices));
+        zMin = cullingNear * pow(depthRatio, slice / float(cullingSlices));
+        zMax = cullingNear * pow(depthRatio, (slice + 1.0) / float(cullingSlices));
     }
-    else {
-        NDF = DistributionGGX(NdotH, perceptualRoughness);
-    }
-
-    float G = GeometrySmith(NdotV, NdotL, perceptualRoughness);
-
-    vec3 numerator = NDF * G * F;
-    float denominator = 4.0 * NdotV * NdotL;
 
-    vec3 specular = numerator / max(denominator, EPSILON);
+    int count = 0;
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
 
-    //Energy conservation
-    vec3 kS = F;
-    vec3 kD = (vec3(1.0) - kS) * (1.0 - metallic);
+        if (light.info.x == LIGHT_TYPE_DIRECTIONAL || LightOverlapsCell(light.position.xyz, light.direction.w, ndcMin, ndcMax, zMin, zMax)) {
+            ++count;
+        }
+    }
 
-    return (kD * albedo * ONE_OVER_PI + specular) * lightColor * NdotL;
+    return count;
 }
 
-// --------------------
-
-// IBL-----------------
-vec3 EnvironmentBRDFApprox( vec3 F0, float roughness, float NoV )
+// Blue for few lights through green and yellow to red at the maximum.
+vec3 Heatmap(in float t)
 {
-    const vec4 c0 = vec4( -1, -0.0275, -0.572, 0.022 );
-    const vec4 c1 = vec4( 1, 0.0425, 1.04, -0.04 );
-    vec4 r = roughness * c0 + c1;
-    float a004 = min( r.x * r.x, exp2( -9.28 * NoV ) ) * r.x + r.y;
-    vec2 AB = vec2( -1.04, 1.04 ) * a004 + r.zw;
-    return F0 * AB.x + AB.y;
+    t = clamp(t, 0.0, 1.0);
+    return clamp(vec3(1.5 - abs(4.0 * t - 3.0), 1.5 - abs(4.0 * t - 2.0), 1.5 - abs(4.0 * t - 1.0)), 0.0, 1.0);
 }
 
-vec3 IBL(in float NdotV, in vec3 F0, in vec3 albedo, in float metallic, in float roughness, in float ao, in vec2 brdfLUT, in vec3 irradiance, in vec3 radiance, in vec3 r, in vec3 n)
+// A color per shadow view of the first shadowed light in range of the fragment, with a checker
+// of the atlas texels it covers. Grey where no shadow view covers the fragment.
+vec3 ShadowViewsDebugColor(in vec3 wPosition)
 {
-    vec3 F = FresnelSchlickRoughness(NdotV, F0, roughness);
-
-    vec3 kD = 1.0 - F;
-    kD *= 1.0 - metallic;
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+        int shadowIndex = light.info.y;
 
-    vec3 diffuse = irradiance * albedo;
-    vec3 specular = radiance * (F0 * brdfLUT.x + brdfLUT.y);
+        if (shadowIndex < 0 || (light.info.x != LIGHT_TYPE_POINT && light.info.x != LIGHT_TYPE_SPOT)) {
+            continue;
+        }
 
-    if (specularAO == 1) {
-        so = ComputeSpecularAO(NdotV, ao, roughness);
-        horizonSo = ComputeHorizonSpecularAO(r, n);
-        specular *= so;
-        specular *= horizonSo;
-        return kD * diffuse * ao + specular * so;
-    }
-
-    return (kD * diffuse + specular) * ao;
-}
+        vec3 d = wPosition - light.position.xyz;
+        if (length(d) > light.direction.w) {
+            continue;
+        }
 
-// reference: https://github.com/google/filament/blob/main/shaders/src/light_indirect.fs
-vec3 GetSpecularDominantDirection(const vec3 n, const vec3 r, in float perceptualRoughness)
-{
-    return mix(r, n, perceptualRoughness * perceptualRoughness);
-}
+        int index = light.info.x == LIGHT_TYPE_POINT ? shadowIndex + CubeFace(d) : shadowIndex;
+        ShadowView view = shadowViews[index];
 
-// reference: https://github.com/google/filament/blob/main/shaders/src/light_indirect.fs
-float PerceptualRoughnessToLod(in float perceptualRoughness)
-{
-    return MAX_REFLECTION_LOD * perceptualRoughness * (2.0 - perceptualRoughness);
-}
-// --------------------
+        vec4 clipPosition = view.viewProjection * vec4(wPosition, 1.0);
+        vec3 ndc = clipPosition.xyz / clipPosition.w;
+        if (clipPosition.w <= 0.0 || any(greaterThan(abs(ndc), vec3(1.0)))) {
+            continue;
+        }
 
-// END PBS FUNCTIONS ----------------------------------------------
+        vec2 texel = floor((view.atlasRect.xy + (ndc.xy * 0.5 + 0.5) * view.atlasRect.zw) * vec2(textureSize(shadowAtlas, 0)));
+        float checker = mod(texel.x + texel.y, 2.0) == 0.0 ? 1.0 : 0.7;
 
-float ConvertToGrayscale(in vec3 color)
-{
-    return dot(color, vec3(0.2125, 0.7154, 0.0721));
-}
+        return Heatmap(fract(float(index) * 0.618034)) * checker;
+    }
 
-vec3 SampleNormalMap(in sampler2D normalMap, in vec2 texcoords, in float strength)
-{
-    vec3 norm = texture(normalMap, texcoords).rgb * 2.0 - 1.0;
-    norm.xy *= strength;
-    return norm;
+    return vec3(0.2);
 }
 
 void main()
@@ -279,14 +248,10 @@ void main()
     vec3 n = normalize(tangentToWorldMat * SampleNormalMap(normalMap, fsIn.texcoord, 1.0));
 
     vec3 v = normalize(fsIn.wViewDirection);
-    vec3 l = normalize(wLightDirection).xyz;
-    vec3 h = normalize(l + v);
     vec3 r = reflect(-v, n);
+    vec3 wPosition = eyePosition.xyz - fsIn.wViewDirection;
 
-    float NdotH = clamp(dot(n, h), 0.0, 1.0);
     float NdotV = clamp(dot(n, v), 0.0, 1.0);
-    float NdotL = clamp(dot(n, l), 0.0, 1.0);
-    float HdotV = clamp(dot(h, v), 0.0, 1.0);
 
     vec4 albedo = texture(albedoMap, fsIn.texcoord) * vec4(baseColor.rgb, 1.0);
 
@@ -295,30 +260,100 @@ void main()
     float perceptualRoughness = clamp((m_r_ao.g + roughnessBias) * roughnessScale, MIN_ROUGHNESS, 1.0) ;
     float ao = clamp((m_r_ao.b + aoBias) * aoScale, 0.0, 1.0);
 
-    vec3 irradiance = texture(irradianceMap, n).rgb;
+    if (screenSpaceAO == 1) {
+        ao *= texture(ssaoMap, gl_FragCoord.xy / textureSize(ssaoMap, 0)).r;
+    }
 
     float lod = PerceptualRoughnessToLod(perceptualRoughness);
     vec3 specular_direction = GetSpecularDominantDirection(n, r, perceptualRoughness);
-    vec3 radiance = textureLod(radianceMap, specular_direction, lod).rgb;
+
+    // Every probe takes its weight of what the smaller ones left, the environment maps the rest.
+    vec3 irradiance = vec3(0.0);
+    vec3 radiance = vec3(0.0);
+    float remainingWeight = 1.0;
+
+    for (int i = 0; i < probeCount.x && remainingWeight > 0.0; ++i) {
+        LightProbe probe = probes[i];
+        float weight = LightProbeWeight(probe, wPosition) * remainingWeight;
+
+        if (weight > 0.0) {
+            float layer = float(probe.info.x);
+            vec3 probeDirection = ParallaxCorrect(probe, wPosition, specular_direction);
+
+            irradiance += textureLod(irradianceProbes, vec4(n, layer), 0.0).rgb * weight * probe.position.w;
+            radiance += textureLod(radianceProbes, vec4(probeDirection, layer), lod).rgb * weight * probe.position.w;
+            remainingWeight -= weight;
+        }
+    }
+
+    irradiance += texture(irradianceMap, n).rgb * remainingWeight;
+    radiance += textureLod(radianceMap, specular_direction, lod).rgb * remainingWeight;
+
+    if (lightmapMode == LIGHTMAP_MODE_IRRADIANCE) {
+        vec4 bakedLight = texture(lightmap, fsIn.texcoord1);
+        irradiance = bakedLight.rgb * lightmapIntensity;
+        ao *= bakedLight.a;
+    } else if (lightmapMode == LIGHTMAP_MODE_OCCLUSION) {
+        ao *= texture(lightmap, fsIn.texcoord1).r;
+    }
 
     vec2 lutSample = texture(brdfLUT, vec2(NdotV, perceptualRoughness)).rg;
 
     vec3 F0 = mix(vec3(F0_DIELECTRIC), albedo.rgb, metallic);
 
     mat3 worldToTangentMat = transpose(tangentToWorldMat);
-    vec3 tHalfVector = worldToTangentMat * h;
-
-    vec3 analyticalLight = BRDF(
-        NdotH,
-        NdotV,
-        NdotL,
-        HdotV,
-        lightColor.rgb,
-        F0,
-        albedo.rgb,
-        metallic,
-        perceptualRoughness,
-        tHalfVector);
+    vec3 wGeometricNormal = normalize(fsIn.wNormal);
+
+    vec3 analyticalLight = vec3(0.0);
+
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+
+        if (light.info.x == LIGHT_TYPE_RECT || light.info.x == LIGHT_TYPE_DISK) {
+            // The falloff with distance is part of the integral.
+            vec3 toLight = light.position.xyz - wPosition;
+            float window = RangeWindow(dot(toLight, toLight), light.direction.w);
+
+            analyticalLight += light.color.rgb * window * AreaLight(light, wPosition, n, v, NdotV, F0, albedo.rgb * (1.0 - metallic), perceptualRoughness);
+            continue;
+        }
+
+        vec3 l;
+        vec3 lightColor = light.color.rgb;
+
+        if (light.info.x == LIGHT_TYPE_DIRECTIONAL) {
+            l = normalize(light.direction.xyz);
+        } else {
+            vec3 toLight = light.position.xyz - wPosition;
+            float distanceSquared = dot(toLight, toLight);
+            l = toLight * inversesqrt(max(distanceSquared, 0.0001));
+
+            lightColor *= DistanceAttenuation(distanceSquared, light.direction.w);
+
+            if (light.info.x == LIGHT_TYPE_SPOT) {
+                lightColor *= AngleAttenuation(l, light.direction.xyz, light.shape.xy);
+                lightColor *= SpotLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            } else {
+                lightColor *= PointLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            }
+        }
+
+        // No early out for unlit fragments, the specular AA of the BRDF takes derivatives.
+        float NdotL = clamp(dot(n, l), 0.0, 1.0);
+        vec3 h = normalize(l + v);
+
+        analyticalLight += BRDF(
+            clamp(dot(n, h), 0.0, 1.0),
+            NdotV,
+            NdotL,
+            clamp(dot(h, v), 0.0, 1.0),
+            lightColor,
+            F0,
+            albedo.rgb,
+            metallic,
+            perceptualRoughness,
+            worldToTangentMat * h);
+    }
 
     vec3 imageBasedLight = IBL(
         NdotV,
@@ -370,8 +405,33 @@ void main()
         case RENDER_MODE_SPECULAR_AMBIENT:
             outColor = vec4(radiance.rgb, 1.0);
             break;
+        case RENDER_MODE_LIGHT_COUNT:
+            int count = LightCount();
+            vec3 heat = count == 0 ? vec3(0.0) : Heatmap(float(count) / float(cullingMaxLights));
+
+            // Over the luminance of the albedo, with the tile borders darkened.
+            vec2 tileUv = fract(gl_FragCoord.xy / float(cullingTileSize));
+            float border = any(lessThan(tileUv * float(cullingTileSize), vec2(1.0))) ? 0.5 : 1.0;
+            float luminance = dot(albedo.rgb, vec3(0.2126, 0.7152, 0.0722));
+            outColor = vec4(mix(vec3(luminance), heat, 0.75) * border, 1.0);
+            break;
+        case RENDER_MODE_OVERDRAW:
+            // Blended additively without a depth test, every layer adds the same amount.
+            outColor = vec4(0.1, 0.04, 0.02, 1.0);
+            break;
+        case RENDER_MODE_SHADOW_VIEWS:
+            outColor = vec4(ShadowViewsDebugColor(wPosition), 1.0);
+            break;
+        case RENDER_MODE_MIP_LEVEL:
+            float maxLevel = max(float(textureQueryLevels(albedoMap) - 1), 1.0);
+            vec3 level = Heatmap(textureQueryLod(albedoMap, fsIn.texcoord).y / maxLevel);
+            outColor = vec4(mix(vec3(dot(albedo.rgb, vec3(0.2126, 0.7152, 0.0722))), level, 0.75), 1.0);
+            break;
         default:
             vec3 finalColor = analyticalLight + imageBasedLight;
+            if (fogEnabled != 0) {
+                finalColor = mix(FogColor(), finalColor, FogTransmittance(eyePosition.xyz, wPosition));
+            }
             outColor = vec4(finalColor, 1.0);
     }
 
@@ -7,6 +7,7 @@ layout(location = 1) in vec3 inNormal;
 layout(location = 2) in vec4 inTangent;
 layout(location = 3) in vec2 inTexcoord;
 layout(location = 4) in vec3 inColor;
+layout(location = 5) in vec2 inTexcoord1;
 
 layout(std140, binding = 0) uniform PerFrameBlock
 {
@@ -34,6 +35,7 @@ layout(location = 0) out VsOut {
     vec3 wNormal;
     vec4 wTangent;
     vec2 texcoord;
+    vec2 texcoord1;
 } vsOut;
 
 void main()
@@ -55,4 +57,5 @@ void main()
 
     //Assign texture coorinates for output.
     vsOut.texcoord = inTexcoord;
+    vsOut.texcoord1 = inTexcoord1;
 }
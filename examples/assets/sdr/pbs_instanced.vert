@@ -0,0 +1,59 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+//Vertex attributes
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inTangent;
+layout(location = 3) in vec2 inTexcoord;
+layout(location = 4) in vec3 inColor;
+layout(location = 5) in vec2 inTexcoord1;
+
+//Instance attributes
+layout(location = 8) in mat4 inInstanceModel;
+layout(location = 12) in uint inInstanceMaterialIndex;
+
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// Varying variables
+// prefixes: w -> world space
+//           v -> view space
+//           t -> tangent space
+//           l -> local space
+layout(location = 0) out VsOut {
+    vec3 wViewDirection;
+    vec3 wNormal;
+    vec4 wTangent;
+    vec2 texcoord;
+    vec2 texcoord1;
+} vsOut;
+
+void main()
+{
+    //Transform vertex to clipspace.
+    vec4 lVertexPosition = vec4(inPosition, 1.0);
+    vec4 wVertexPosition = inInstanceModel * lVertexPosition;
+    gl_Position = view_projection * wVertexPosition;
+
+    mat3 normalMat = transpose(inverse(mat3(inInstanceModel)));
+    //Calculate the normal. Bring it to world space
+    vsOut.wNormal = normalMat * inNormal;
+
+    // Bring tangent to world space.
+    vsOut.wTangent = vec4(normalMat * inTangent.xyz, inTangent.w);
+
+    //Assign the view direction for output.
+    vsOut.wViewDirection = eyePosition.xyz - wVertexPosition.xyz;
+
+    //Assign texture coorinates for output.
+    vsOut.texcoord = inTexcoord;
+    vsOut.texcoord1 = inTexcoord1;
+}
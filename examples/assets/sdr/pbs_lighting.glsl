@@ -0,0 +1,612 @@
+// The lighting shared by the PBS shaders: the BRDF, image based lighting, punctual and area
+// lights, shadows and fog. Included after the shader defines EPSILON.
+
+const float F0_DIELECTRIC = 0.04;
+const float PI = 3.14159265359;
+const float ONE_OVER_PI = 0.318309886;
+const float MAX_REFLECTION_LOD = 5.0;
+const float MIN_ROUGHNESS = 0.023;
+
+const int LIGHT_TYPE_DIRECTIONAL = 0;
+const int LIGHT_TYPE_POINT = 1;
+const int LIGHT_TYPE_SPOT = 2;
+const int LIGHT_TYPE_RECT = 3;
+const int LIGHT_TYPE_DISK = 4;
+
+// Disks are integrated as octagons, scaled to the same area.
+const int AREA_LIGHT_MAX_VERTICES = 8;
+const float DISK_OCTAGON_SCALE = 1.0538844;
+const float LTC_LUT_SIZE = 64.0;
+
+layout(std140, binding = 0) uniform VertexPerFrameBlock
+{
+    mat4 viewProjection;
+    vec4 eyePosition;
+};
+
+layout(std140, binding = 2) uniform PerFrameBlock
+{
+    vec2 ssVarianceAndThreshold;
+    int specularAA;
+    int specularAO;
+    int disneyGgxHotness;
+    int renderMode;
+    int screenSpaceAO;
+};
+
+layout(std140, binding = 8) uniform FogBlock
+{
+    // w: 1 to tint the color with the average of the environment.
+    vec4 fogColor;
+    float fogDensity;
+    float fogHeightFalloff;
+    float fogBaseHeight;
+    float fogStartDistance;
+    float fogMaxOpacity;
+    int fogEnabled;
+};
+
+layout(binding = 3) uniform sampler2D brdfLUT;
+
+layout(binding = 4) uniform samplerCube irradianceMap;
+layout(binding = 5) uniform samplerCube radianceMap;
+
+// Colors are premultiplied with the intensity of the light, in lux for directional lights,
+// candela for point and spot lights and nits for area lights.
+struct Light
+{
+    vec4 position;
+    // Towards directional lights, along the cone of spot lights, first half axis of area lights.
+    // w: range.
+    vec4 direction;
+    vec4 color;
+    // Scale and offset of the cosine to the cone axis of spot lights, second half axis of area
+    // lights.
+    vec4 shape;
+    // x: type, y: first shadow view or -1, z: two sided.
+    ivec4 info;
+};
+
+layout(std430, binding = 4) readonly buffer LightBlock
+{
+    ivec4 lightCount;
+    Light lights[];
+};
+
+// Shadow maps of point and spot lights, tiles of a single atlas.
+struct ShadowView
+{
+    mat4 viewProjection;
+    // Offset and scale of the tile in the atlas.
+    vec4 atlasRect;
+    // x: depth bias, y: normal offset per unit of distance from the light.
+    vec4 params;
+    // x: size of the light on the near plane in tile coordinates, 0 for PCF. y, z: near and far.
+    vec4 pcss;
+};
+
+layout(std430, binding = 3) readonly buffer ShadowViewBlock
+{
+    ShadowView shadowViews[];
+};
+
+layout(binding = 9) uniform sampler2DShadow shadowAtlas;
+layout(binding = 15) uniform sampler2D shadowAtlasDepth;
+
+const int PCSS_SAMPLE_COUNT = 16;
+const float PCSS_MAX_FILTER_TEXELS = 32.0;
+const vec2 POISSON_DISK[PCSS_SAMPLE_COUNT] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+);
+
+// Linearly transformed cosines of the GGX BRDF for area lights. The inverse matrices and the
+// magnitude and Fresnel of the BRDF.
+layout(binding = 10) uniform sampler2D ltcMatrixLut;
+layout(binding = 11) uniform sampler2D ltcAmplitudeLut;
+
+float so;
+float horizonSo;
+
+mat3 CreateTangentToWorldMatrix(in vec3 n, in vec3 t, in float tSign)
+{
+    t = normalize(t - dot(t, n) * n);
+
+    //Calculate the binormal
+    vec3 b = normalize(cross(n, t) * tSign);
+
+    return mat3(t, b, n);
+}
+
+// PBS FUNCTIONS --------------------------------------------------
+
+// Analytical Lights---
+vec3 FresnelSchlick(in float cosTheta, in vec3 F0)
+{
+    vec3 F90 = vec3(1.0);
+
+    if (specularAO == 1) {
+        F90 = vec3(clamp(dot(F0, vec3(50.0 * 0.33)), 0.0, 1.0));
+    }
+
+    return F0 + (F90 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+vec3 FresnelSchlickRoughness(in float NdotV, in vec3 F0, in float perceptualRoughness)
+{
+    vec3 F90;
+
+    if (specularAO == 1) {
+        F90 = vec3(clamp(dot(max(vec3(1.0 - perceptualRoughness), F0), vec3(50.0 * 0.33)), 0.0, 1.0));
+    }
+    else {
+        F90 = max(vec3(1.0 - perceptualRoughness), F0);
+    }
+
+    return F0 + (F90 - F0) * pow(1.0 - NdotV, 5.0);
+}
+
+float DistributionGGX(in float NdotH, in float perceptualRoughness)
+{
+    float a = perceptualRoughness * perceptualRoughness;
+    float a2 = a * a;
+    float NdotH2 = NdotH * NdotH;
+
+    float num   = a2;
+    float denom = (NdotH2 * (a2 - 1.0) + 1.0);
+    denom = PI * denom * denom;
+
+    return num / denom;
+}
+
+// Reference: http://www.jp.square-enix.com/tech/library/pdf/ImprovedGeometricSpecularAA(slides).pdf
+// Reference: http://www.jp.square-enix.com/tech/library/pdf/ImprovedGeometricSpecularAA.pdf
+float BiasedAxisAlignedGeometricSpecularAA(in vec3 tHalfVector, in float perceptualRoughness)
+{
+    float screenSpaceVariance = ssVarianceAndThreshold.x;
+    float clampingThreshold = ssVarianceAndThreshold.y;
+
+    float roughness = perceptualRoughness * perceptualRoughness;
+
+    vec2 halfVector2D = tHalfVector.xy;
+    vec2 deltaU = dFdx(halfVector2D);
+    vec2 deltaV = dFdy(halfVector2D);
+
+    vec2 boundingRectangle = abs(deltaU) + abs(deltaV);
+    vec2 variance = screenSpaceVariance * (boundingRectangle * boundingRectangle);
+    vec2 kernelRoughnessSquared = min(2.0 * variance, clampingThreshold);
+
+    return clamp(roughness + kernelRoughnessSquared, 0.0, 1.0).x;
+}
+
+float ComputeSpecularAO(float NoV, float ao, float roughness)
+{
+    return clamp(pow(NoV + ao, exp2(-16.0 * roughness - 1.0)) - 1.0 + ao, 0.0, 1.0);
+}
+
+float ComputeHorizonSpecularAO(vec3 r, vec3 n)
+{
+    return min(1.0 + dot(r, n), 1.0);
+}
+
+float DistributionGGXFiltered(in float NdotH, in float perceptualRoughness, in vec3 tHalfVector)
+{
+    float a = BiasedAxisAlignedGeometricSpecularAA(tHalfVector, perceptualRoughness);
+    float a2 = a * a;
+    float NdotH2 = NdotH * NdotH;
+
+    float num = a2;
+    float denom = (NdotH2 * (a2 - 1.0) + 1.0);
+    denom = PI * denom * denom;
+
+    return num / denom;
+}
+
+float GeometrySchlickGGX(in float NdotV, in float roughness)
+{
+    float k = 0;
+
+    // Disney's roughness remapping to reduce "hotness" for punctual lights.
+    if (disneyGgxHotness == 1) {
+        float r = roughness + 1.0;
+        k = (r * r) * 0.125; // 1.0 / 8.0 = 0.125
+    } else { // Default "k" value
+        k = (roughness * roughness) * 0.5;
+    }
+
+    float num   = NdotV;
+    float denom = NdotV * (1.0 - k) + k;
+
+    return num / denom;
+}
+
+float GeometrySmith(in float NdotV, in float NdotL, in float roughness)
+{
+    float ggx2  = GeometrySchlickGGX(NdotV, roughness);
+    float ggx1  = GeometrySchlickGGX(NdotL, roughness);
+
+    return ggx1 * ggx2;
+}
+
+vec3 BRDF(in float NdotH, in float NdotV, in float NdotL, in float HdotV, in vec3 lightColor,
+in vec3 F0, in vec3 albedo, in float metallic, in float perceptualRoughness, in vec3 tHalfVector)
+{
+    vec3 F = FresnelSchlick(HdotV, F0);
+    float NDF = 0.0;
+
+    if (specularAA == 1) {
+        NDF = DistributionGGXFiltered(NdotH, perceptualRoughness, tHalfVector);
+    }
+    else {
+        NDF = DistributionGGX(NdotH, perceptualRoughness);
+    }
+
+    float G = GeometrySmith(NdotV, NdotL, perceptualRoughness);
+
+    vec3 numerator = NDF * G * F;
+    float denominator = 4.0 * NdotV * NdotL;
+
+    vec3 specular = numerator / max(denominator, EPSILON);
+
+    //Energy conservation
+    vec3 kS = F;
+    vec3 kD = (vec3(1.0) - kS) * (1.0 - metallic);
+
+    return (kD * albedo * ONE_OVER_PI + specular) * lightColor * NdotL;
+}
+
+// --------------------
+
+// IBL-----------------
+vec3 EnvironmentBRDFApprox( vec3 F0, float roughness, float NoV )
+{
+    const vec4 c0 = vec4( -1, -0.0275, -0.572, 0.022 );
+    const vec4 c1 = vec4( 1, 0.0425, 1.04, -0.04 );
+    vec4 r = roughness * c0 + c1;
+    float a004 = min( r.x * r.x, exp2( -9.28 * NoV ) ) * r.x + r.y;
+    vec2 AB = vec2( -1.04, 1.04 ) * a004 + r.zw;
+    return F0 * AB.x + AB.y;
+}
+
+vec3 IBL(in float NdotV, in vec3 F0, in vec3 albedo, in float metallic, in float roughness, in float ao, in vec2 brdfLUT, in vec3 irradiance, in vec3 radiance, in vec3 r, in vec3 n)
+{
+    vec3 F = FresnelSchlickRoughness(NdotV, F0, roughness);
+
+    vec3 kD = 1.0 - F;
+    kD *= 1.0 - metallic;
+
+    vec3 diffuse = irradiance * albedo;
+    vec3 specular = radiance * (F0 * brdfLUT.x + brdfLUT.y);
+
+    if (specularAO == 1) {
+        so = ComputeSpecularAO(NdotV, ao, roughness);
+        horizonSo = ComputeHorizonSpecularAO(r, n);
+        specular *= so;
+        specular *= horizonSo;
+        return kD * diffuse * ao + specular * so;
+    }
+
+    return (kD * diffuse + specular) * ao;
+}
+
+// reference: https://github.com/google/filament/blob/main/shaders/src/light_indirect.fs
+vec3 GetSpecularDominantDirection(const vec3 n, const vec3 r, in float perceptualRoughness)
+{
+    return mix(r, n, perceptualRoughness * perceptualRoughness);
+}
+
+// reference: https://github.com/google/filament/blob/main/shaders/src/light_indirect.fs
+float PerceptualRoughnessToLod(in float perceptualRoughness)
+{
+    return MAX_REFLECTION_LOD * perceptualRoughness * (2.0 - perceptualRoughness);
+}
+// --------------------
+
+// Punctual lights-----
+// Smoothly reaches 0 at the range. Real-Time Rendering 4th ed. 5.2.2
+float RangeWindow(in float distanceSquared, in float range)
+{
+    float factor = distanceSquared / (range * range);
+    float window = clamp(1.0 - factor * factor, 0.0, 1.0);
+    return window * window;
+}
+
+// Inverse square falloff, windowed to reach 0 at the range.
+float DistanceAttenuation(in float distanceSquared, in float range)
+{
+    return RangeWindow(distanceSquared, range) / max(distanceSquared, 0.0001);
+}
+
+float AngleAttenuation(in vec3 l, in vec3 spotDirection, in vec2 scaleOffset)
+{
+    float attenuation = clamp(dot(-l, spotDirection) * scaleOffset.x + scaleOffset.y, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+// --------------------
+
+// Area lights---------
+// Reference: https://eheitzresearch.wordpress.com/415-2/
+// Fitted theta / sin(theta) of the arc between the unit vectors, times their cross product.
+vec3 IntegrateEdge(in vec3 v1, in vec3 v2)
+{
+    float x = dot(v1, v2);
+    float y = abs(x);
+
+    float a = 0.8543985 + (0.4965155 + 0.0145206 * y) * y;
+    float b = 3.4175940 + (4.1616724 + y) * y;
+    float v = a / b;
+
+    float thetaOverSinTheta = x > 0.0 ? v : 0.5 * inversesqrt(max(1.0 - x * x, 1e-7)) - v;
+    return cross(v1, v2) * thetaOverSinTheta;
+}
+
+// Form factor of the polygon clipped to the upper hemisphere, the integral of the clamped cosine
+// over it divided by PI. The points are relative to the shading point.
+float IntegratePolygon(in vec3 points[AREA_LIGHT_MAX_VERTICES], in int count, in bool twoSided)
+{
+    float sum = 0.0;
+
+    // Where the polygon goes below the horizon and comes back, joined by an edge along it.
+    vec3 exitPoint = vec3(0.0);
+    vec3 entryPoint = vec3(0.0);
+    bool clipped = false;
+
+    for (int i = 0; i < count; ++i) {
+        vec3 a = points[i];
+        vec3 b = points[(i + 1) % count];
+
+        if (a.z > 0.0 && b.z > 0.0) {
+            sum += IntegrateEdge(normalize(a), normalize(b)).z;
+        } else if (a.z > 0.0) {
+            exitPoint = mix(a, b, a.z / (a.z - b.z));
+            sum += IntegrateEdge(normalize(a), normalize(exitPoint)).z;
+            clipped = true;
+        } else if (b.z > 0.0) {
+            entryPoint = mix(a, b, a.z / (a.z - b.z));
+            sum += IntegrateEdge(normalize(entryPoint), normalize(b)).z;
+        }
+    }
+
+    if (clipped) {
+        sum += IntegrateEdge(normalize(exitPoint), normalize(entryPoint)).z;
+    }
+
+    float formFactor = sum / (2.0 * PI);
+    return twoSided ? abs(formFactor) : max(formFactor, 0.0);
+}
+
+// The corners of the light relative to the shading point.
+int AreaLightPolygon(in Light light, in vec3 wPosition, out vec3 points[AREA_LIGHT_MAX_VERTICES])
+{
+    vec3 center = light.position.xyz - wPosition;
+    vec3 xAxis = light.direction.xyz;
+    vec3 yAxis = light.shape.xyz;
+
+    if (light.info.x == LIGHT_TYPE_RECT) {
+        points[0] = center - xAxis - yAxis;
+        points[1] = center + xAxis - yAxis;
+        points[2] = center + xAxis + yAxis;
+        points[3] = center - xAxis + yAxis;
+        return 4;
+    }
+
+    for (int i = 0; i < AREA_LIGHT_MAX_VERTICES; ++i) {
+        float angle = float(i) * (2.0 * PI / float(AREA_LIGHT_MAX_VERTICES));
+        points[i] = center + (xAxis * cos(angle) + yAxis * sin(angle)) * DISK_OCTAGON_SCALE;
+    }
+
+    return AREA_LIGHT_MAX_VERTICES;
+}
+
+// Diffuse and specular light of a rect or disk light of unit luminance.
+vec3 AreaLight(in Light light, in vec3 wPosition, in vec3 n, in vec3 v, in float NdotV, in vec3 F0, in vec3 diffuseColor, in float perceptualRoughness)
+{
+    vec3 points[AREA_LIGHT_MAX_VERTICES];
+    int count = AreaLightPolygon(light, wPosition, points);
+    bool twoSided = light.info.z != 0;
+
+    // The table is fitted with the view direction in the xz plane.
+    vec3 t = v - n * dot(v, n);
+    t = dot(t, t) > 1e-6 ? normalize(t) : normalize(cross(n, abs(n.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0)));
+    mat3 worldToLocal = transpose(mat3(t, cross(n, t), n));
+
+    vec2 uv = vec2(perceptualRoughness, sqrt(1.0 - NdotV));
+    uv = uv * ((LTC_LUT_SIZE - 1.0) / LTC_LUT_SIZE) + 0.5 / LTC_LUT_SIZE;
+
+    vec4 m = texture(ltcMatrixLut, uv);
+    vec2 amplitude = texture(ltcAmplitudeLut, uv).rg;
+    mat3 ltcInverse = mat3(vec3(m.x, 0.0, m.y), vec3(0.0, 1.0, 0.0), vec3(m.z, 0.0, m.w));
+
+    for (int i = 0; i < count; ++i) {
+        points[i] = worldToLocal * points[i];
+    }
+    float diffuse = IntegratePolygon(points, count, twoSided);
+
+    for (int i = 0; i < count; ++i) {
+        points[i] = ltcInverse * points[i];
+    }
+    float specular = IntegratePolygon(points, count, twoSided);
+
+    return diffuseColor * diffuse + (F0 * amplitude.x + (1.0 - F0) * amplitude.y) * specular;
+}
+// --------------------
+
+// Shadows-------------
+// Distance along the view direction of a depth of a perspective shadow view.
+float LinearizeShadowDepth(in float depth, in float near, in float far)
+{
+    float z = depth * 2.0 - 1.0;
+    return 2.0 * near * far / (far + near - z * (far - near));
+}
+
+float ShadowPcf(in vec2 uv, in float depth, in vec2 texelSize, in vec2 tileMin, in vec2 tileMax)
+{
+    float visibility = 0.0;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            vec2 tapUv = clamp(uv + vec2(x, y) * texelSize, tileMin, tileMax);
+            visibility += texture(shadowAtlas, vec3(tapUv, depth));
+        }
+    }
+
+    return visibility / 9.0;
+}
+
+// Percentage-closer soft shadows: the average depth of the blockers around the receiver gives
+// the width of the penumbra, which sizes the filter.
+float ShadowPcss(in ShadowView view, in vec2 uv, in float depth, in float receiverDistance,
+                 in vec2 texelSize, in vec2 tileMin, in vec2 tileMax)
+{
+    float lightSize = view.pcss.x;
+    float near = view.pcss.y;
+    float far = view.pcss.z;
+
+    // The part of the shadow map that sees the light from the receiver.
+    vec2 searchRadius = lightSize * (receiverDistance - near) / receiverDistance * view.atlasRect.zw;
+    searchRadius = min(searchRadius, texelSize * PCSS_MAX_FILTER_TEXELS);
+
+    float blockerDistance = 0.0;
+    int blockerCount = 0;
+    for (int i = 0; i < PCSS_SAMPLE_COUNT; ++i) {
+        vec2 tapUv = clamp(uv + POISSON_DISK[i] * searchRadius, tileMin, tileMax);
+        float tapDepth = textureLod(shadowAtlasDepth, tapUv, 0.0).r;
+        if (tapDepth < depth) {
+            blockerDistance += LinearizeShadowDepth(tapDepth, near, far);
+            ++blockerCount;
+        }
+    }
+
+    if (blockerCount == 0) {
+        return 1.0;
+    }
+
+    blockerDistance /= float(blockerCount);
+
+    // Similar triangles between the light, the blockers and the receiver.
+    float penumbra = (receiverDistance - blockerDistance) / blockerDistance;
+    vec2 filterRadius = penumbra * lightSize * near / receiverDistance * view.atlasRect.zw;
+    filterRadius = clamp(filterRadius, texelSize, texelSize * PCSS_MAX_FILTER_TEXELS);
+
+    float visibility = 0.0;
+    for (int i = 0; i < PCSS_SAMPLE_COUNT; ++i) {
+        vec2 tapUv = clamp(uv + POISSON_DISK[i] * filterRadius, tileMin, tileMax);
+        visibility += texture(shadowAtlas, vec3(tapUv, depth));
+    }
+
+    return visibility / float(PCSS_SAMPLE_COUNT);
+}
+
+float SampleShadowView(in int index, in vec3 wPosition, in vec3 wNormal, in float lightDistance)
+{
+    ShadowView view = shadowViews[index];
+
+    vec3 wOffsetPosition = wPosition + wNormal * view.params.y * lightDistance;
+    vec4 clipPosition = view.viewProjection * vec4(wOffsetPosition, 1.0);
+    vec3 ndc = clipPosition.xyz / clipPosition.w;
+    float depth = ndc.z * 0.5 + 0.5 - view.params.x;
+
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowAtlas, 0));
+    vec2 uv = view.atlasRect.xy + (ndc.xy * 0.5 + 0.5) * view.atlasRect.zw;
+
+    // The filter is kept within the tile, the neighbours belong to other lights.
+    vec2 tileMin = view.atlasRect.xy + texelSize * 0.5;
+    vec2 tileMax = view.atlasRect.xy + view.atlasRect.zw - texelSize * 0.5;
+
+    if (view.pcss.x > 0.0) {
+        return ShadowPcss(view, uv, depth, clipPosition.w, texelSize, tileMin, tileMax);
+    }
+
+    return ShadowPcf(uv, depth, texelSize, tileMin, tileMax);
+}
+
+// The cube face a direction from the light falls on, in the order +X, -X, +Y, -Y, +Z, -Z.
+int CubeFace(in vec3 d)
+{
+    vec3 a = abs(d);
+
+    if (a.x >= a.y && a.x >= a.z) {
+        return d.x > 0.0 ? 0 : 1;
+    } else if (a.y >= a.z) {
+        return d.y > 0.0 ? 2 : 3;
+    }
+
+    return d.z > 0.0 ? 4 : 5;
+}
+
+// shadowIndex is the first of the six cube face views of the light, -1 if it has no shadow.
+float PointLightShadow(in int shadowIndex, in vec3 wPosition, in vec3 wNormal, in vec3 wLightPosition)
+{
+    if (shadowIndex < 0) {
+        return 1.0;
+    }
+
+    vec3 d = wPosition - wLightPosition;
+
+    return SampleShadowView(shadowIndex + CubeFace(d), wPosition, wNormal, length(d));
+}
+
+float SpotLightShadow(in int shadowIndex, in vec3 wPosition, in vec3 wNormal, in vec3 wLightPosition)
+{
+    if (shadowIndex < 0) {
+        return 1.0;
+    }
+
+    return SampleShadowView(shadowIndex, wPosition, wNormal, length(wPosition - wLightPosition));
+}
+// --------------------
+
+float ConvertToGrayscale(in vec3 color)
+{
+    return dot(color, vec3(0.2125, 0.7154, 0.0721));
+}
+
+vec3 SampleNormalMap(in sampler2D normalMap, in vec2 texcoords, in float strength)
+{
+    vec3 norm = texture(normalMap, texcoords).rgb * 2.0 - 1.0;
+    norm.xy *= strength;
+    return norm;
+}
+
+// Fraction of the light that reaches the eye through exponential height fog. The density is
+// integrated analytically along the view ray, from the start distance to the surface.
+float FogTransmittance(in vec3 wEye, in vec3 wPosition)
+{
+    vec3 ray = wPosition - wEye;
+    float rayLength = length(ray);
+    float fogDistance = max(rayLength - fogStartDistance, 0.0);
+    vec3 direction = ray / max(rayLength, EPSILON);
+
+    float startHeight = wEye.y + direction.y * (rayLength - fogDistance);
+    float startDensity = fogDensity * exp(-fogHeightFalloff * (startHeight - fogBaseHeight));
+
+    // (1 - e^-x) / x, with its Taylor expansion where the ray is close to level.
+    float falloff = fogHeightFalloff * direction.y * fogDistance;
+    float integral = abs(falloff) > 0.01 ? (1.0 - exp(-falloff)) / falloff : 1.0 - 0.5 * falloff;
+
+    return max(exp(-startDensity * fogDistance * integral), 1.0 - fogMaxOpacity);
+}
+
+vec3 FogColor()
+{
+    if (fogColor.w == 0.0) {
+        return fogColor.rgb;
+    }
+
+    // The irradiance over the six axes averages the environment.
+    vec3 average = texture(irradianceMap, vec3(1.0, 0.0, 0.0)).rgb
+                 + texture(irradianceMap, vec3(-1.0, 0.0, 0.0)).rgb
+                 + texture(irradianceMap, vec3(0.0, 1.0, 0.0)).rgb
+                 + texture(irradianceMap, vec3(0.0, -1.0, 0.0)).rgb
+                 + texture(irradianceMap, vec3(0.0, 0.0, 1.0)).rgb
+                 + texture(irradianceMap, vec3(0.0, 0.0, -1.0)).rgb;
+
+    return fogColor.rgb * average / 6.0;
+}
+
+// END PBS FUNCTIONS ----------------------------------------------
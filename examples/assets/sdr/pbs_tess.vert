@@ -0,0 +1,43 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+//Vertex attributes
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inTangent;
+layout(location = 3) in vec2 inTexcoord;
+layout(location = 5) in vec2 inTexcoord1;
+
+layout(std140, binding = 1) uniform PerDrawBlock
+{
+    mat4 model;
+    mat4 normalMatrix;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// Varying variables
+// prefixes: w -> world space
+layout(location = 0) out VsOut {
+    vec3 wPosition;
+    vec3 wNormal;
+    vec4 wTangent;
+    vec2 texcoord;
+    vec2 texcoord1;
+} vsOut;
+
+void main()
+{
+    // Positions stay in world space. Projection happens after displacement in the evaluation stage.
+    vec4 wVertexPosition = model * vec4(inPosition, 1.0);
+    gl_Position = wVertexPosition;
+
+    mat3 normalMat = mat3(normalMatrix);
+    vsOut.wPosition = wVertexPosition.xyz;
+    vsOut.wNormal = normalMat * inNormal;
+    vsOut.wTangent = vec4(normalMat * inTangent.xyz, inTangent.w);
+    vsOut.texcoord = inTexcoord;
+    vsOut.texcoord1 = inTexcoord1;
+}
@@ -0,0 +1,292 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+const float EPSILON = 0.001;
+
+#include "pbs_lighting.glsl"
+
+const int RENDER_MODE_ALBEDO = 1;
+const int RENDER_MODE_METALLIC = 2;
+const int RENDER_MODE_ROUGHNESS = 3;
+const int RENDER_MODE_NORMALS = 4;
+const int RENDER_MODE_TANGENTS = 5;
+const int RENDER_MODE_UV = 6;
+const int RENDER_MODE_NDOTV = 7;
+const int RENDER_MODE_AO = 8;
+const int RENDER_MODE_SPECULAR_AO = 9;
+const int RENDER_MODE_HORIZON_SPECULAR_AO = 10;
+const int RENDER_MODE_DIFFUSE_AMBIENT = 11;
+const int RENDER_MODE_SPECULAR_AMBIENT = 12;
+
+const int MAX_LAYERS = 8;
+
+layout(location = 0) in VsOut {
+    vec3 wViewDirection;
+    // From 0 to 1 over the whole terrain.
+    vec2 texcoord;
+} fsIn;
+
+layout(std140, binding = 4) uniform TerrainMaterialBlock
+{
+    // x: tile size, y: 1 for triplanar projection, z: normal strength.
+    vec4 layers[MAX_LAYERS];
+    int layerCount;
+    float triplanarSharpness;
+};
+
+// The maps of the layers, a layer of the arrays each.
+layout(binding = 0) uniform sampler2DArray albedoMaps;
+layout(binding = 1) uniform sampler2DArray normalMaps;
+layout(binding = 2) uniform sampler2DArray m_r_aoMaps;
+
+// Screen space ambient occlusion, sampled at the fragment position.
+layout(binding = 7) uniform sampler2D ssaoMap;
+
+// World space normals of the heightmap samples.
+layout(binding = 8) uniform sampler2D terrainNormalMap;
+
+// The weights of layers 0 to 3 and 4 to 7 in their channels.
+layout(binding = 12) uniform sampler2D splatMap0;
+layout(binding = 13) uniform sampler2D splatMap1;
+
+layout(location = 0) out vec4 outColor;
+
+struct LayerSample
+{
+    vec3 albedo;
+    vec3 normal;
+    vec3 m_r_ao;
+};
+
+// Maps the tangent space normal of a top-down projection onto the surface.
+vec3 TopDownNormal(in vec3 tNormal, in vec3 n)
+{
+    vec3 t = normalize(vec3(1.0, 0.0, 0.0) - n * n.x);
+    vec3 b = normalize(vec3(0.0, 0.0, 1.0) - n * n.z);
+    return normalize(t * tNormal.x + b * tNormal.y + n * tNormal.z);
+}
+
+vec3 SampleNormalMapArray(in vec2 texcoords, in int layer, in float strength)
+{
+    vec3 norm = texture(normalMaps, vec3(texcoords, layer)).rgb * 2.0 - 1.0;
+    norm.xy *= strength;
+    return norm;
+}
+
+LayerSample SampleLayer(in int layer, in vec3 wPosition, in vec3 n)
+{
+    vec4 properties = layers[layer];
+    float tileSize = max(properties.x, EPSILON);
+    LayerSample s;
+
+    if (properties.y == 0.0) {
+        vec2 uv = wPosition.xz / tileSize;
+
+        s.albedo = texture(albedoMaps, vec3(uv, layer)).rgb;
+        s.normal = TopDownNormal(SampleNormalMapArray(uv, layer, properties.z), n);
+        s.m_r_ao = texture(m_r_aoMaps, vec3(uv, layer)).rgb;
+        return s;
+    }
+
+    // Projected along the three axes, weighted by the normal. The normals are combined with
+    // whiteout blending.
+    // Reference: https://bgolus.medium.com/normal-mapping-for-a-triplanar-shader-10bf39dca05a
+    vec3 weights = pow(abs(n), vec3(triplanarSharpness));
+    weights /= max(weights.x + weights.y + weights.z, EPSILON);
+
+    vec2 uvX = wPosition.zy / tileSize;
+    vec2 uvY = wPosition.xz / tileSize;
+    vec2 uvZ = wPosition.xy / tileSize;
+
+    s.albedo = texture(albedoMaps, vec3(uvX, layer)).rgb * weights.x
+             + texture(albedoMaps, vec3(uvY, layer)).rgb * weights.y
+             + texture(albedoMaps, vec3(uvZ, layer)).rgb * weights.z;
+
+    s.m_r_ao = texture(m_r_aoMaps, vec3(uvX, layer)).rgb * weights.x
+             + texture(m_r_aoMaps, vec3(uvY, layer)).rgb * weights.y
+             + texture(m_r_aoMaps, vec3(uvZ, layer)).rgb * weights.z;
+
+    vec3 tNormalX = SampleNormalMapArray(uvX, layer, properties.z);
+    vec3 tNormalY = SampleNormalMapArray(uvY, layer, properties.z);
+    vec3 tNormalZ = SampleNormalMapArray(uvZ, layer, properties.z);
+
+    tNormalX = vec3(tNormalX.xy + n.zy, abs(tNormalX.z) * n.x);
+    tNormalY = vec3(tNormalY.xy + n.xz, abs(tNormalY.z) * n.y);
+    tNormalZ = vec3(tNormalZ.xy + n.xy, abs(tNormalZ.z) * n.z);
+
+    s.normal = normalize(tNormalX.zyx * weights.x + tNormalY.xzy * weights.y + tNormalZ.xyz * weights.z);
+    return s;
+}
+
+void main()
+{
+    vec3 wPosition = eyePosition.xyz - fsIn.wViewDirection;
+
+    // Texel centers at the edges, as the heights of the vertices.
+    vec2 normalMapSize = vec2(textureSize(terrainNormalMap, 0));
+    vec2 normalMapUv = (fsIn.texcoord * (normalMapSize - 1.0) + 0.5) / normalMapSize;
+    vec3 wGeometricNormal = normalize(texture(terrainNormalMap, normalMapUv).xyz);
+
+    vec4 splat0 = texture(splatMap0, fsIn.texcoord);
+    vec4 splat1 = texture(splatMap1, fsIn.texcoord);
+    float splatWeights[MAX_LAYERS] = float[](splat0.r, splat0.g, splat0.b, splat0.a, splat1.r, splat1.g, splat1.b, splat1.a);
+
+    float totalWeight = 0.0;
+    for (int i = 0; i < layerCount; ++i) {
+        totalWeight += splatWeights[i];
+    }
+
+    // Every layer is sampled whatever its weight, the mip selection takes derivatives.
+    vec3 albedo = vec3(0.0);
+    vec3 blendedNormal = vec3(0.0);
+    vec3 m_r_ao = vec3(0.0);
+
+    for (int i = 0; i < layerCount; ++i) {
+        float weight = totalWeight > EPSILON ? splatWeights[i] / totalWeight : float(i == 0);
+        LayerSample s = SampleLayer(i, wPosition, wGeometricNormal);
+
+        albedo += s.albedo * weight;
+        blendedNormal += s.normal * weight;
+        m_r_ao += s.m_r_ao * weight;
+    }
+
+    vec3 n = normalize(blendedNormal);
+    // The maps of the top-down projection run along x and z.
+    vec3 t = normalize(vec3(1.0, 0.0, 0.0) - n * n.x);
+    mat3 tangentToWorldMat = CreateTangentToWorldMatrix(n, t, -1.0);
+
+    vec3 v = normalize(fsIn.wViewDirection);
+    vec3 r = reflect(-v, n);
+
+    float NdotV = clamp(dot(n, v), 0.0, 1.0);
+
+    float metallic = clamp(m_r_ao.r, 0.0, 1.0);
+    float perceptualRoughness = clamp(m_r_ao.g, MIN_ROUGHNESS, 1.0);
+    float ao = clamp(m_r_ao.b, 0.0, 1.0);
+
+    if (screenSpaceAO == 1) {
+        ao *= texture(ssaoMap, gl_FragCoord.xy / textureSize(ssaoMap, 0)).r;
+    }
+
+    float lod = PerceptualRoughnessToLod(perceptualRoughness);
+    vec3 specular_direction = GetSpecularDominantDirection(n, r, perceptualRoughness);
+
+    vec3 irradiance = texture(irradianceMap, n).rgb;
+    vec3 radiance = textureLod(radianceMap, specular_direction, lod).rgb;
+
+    vec2 lutSample = texture(brdfLUT, vec2(NdotV, perceptualRoughness)).rg;
+
+    vec3 F0 = mix(vec3(F0_DIELECTRIC), albedo, metallic);
+
+    mat3 worldToTangentMat = transpose(tangentToWorldMat);
+
+    vec3 analyticalLight = vec3(0.0);
+
+    for (int i = 0; i < lightCount.x; ++i) {
+        Light light = lights[i];
+
+        if (light.info.x == LIGHT_TYPE_RECT || light.info.x == LIGHT_TYPE_DISK) {
+            // The falloff with distance is part of the integral.
+            vec3 toLight = light.position.xyz - wPosition;
+            float window = RangeWindow(dot(toLight, toLight), light.direction.w);
+
+            analyticalLight += light.color.rgb * window * AreaLight(light, wPosition, n, v, NdotV, F0, albedo * (1.0 - metallic), perceptualRoughness);
+            continue;
+        }
+
+        vec3 l;
+        vec3 lightColor = light.color.rgb;
+
+        if (light.info.x == LIGHT_TYPE_DIRECTIONAL) {
+            l = normalize(light.direction.xyz);
+        } else {
+            vec3 toLight = light.position.xyz - wPosition;
+            float distanceSquared = dot(toLight, toLight);
+            l = toLight * inversesqrt(max(distanceSquared, 0.0001));
+
+            lightColor *= DistanceAttenuation(distanceSquared, light.direction.w);
+
+            if (light.info.x == LIGHT_TYPE_SPOT) {
+                lightColor *= AngleAttenuation(l, light.direction.xyz, light.shape.xy);
+                lightColor *= SpotLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            } else {
+                lightColor *= PointLightShadow(light.info.y, wPosition, wGeometricNormal, light.position.xyz);
+            }
+        }
+
+        // No early out for unlit fragments, the specular AA of the BRDF takes derivatives.
+        float NdotL = clamp(dot(n, l), 0.0, 1.0);
+        vec3 h = normalize(l + v);
+
+        analyticalLight += BRDF(
+            clamp(dot(n, h), 0.0, 1.0),
+            NdotV,
+            NdotL,
+            clamp(dot(h, v), 0.0, 1.0),
+            lightColor,
+            F0,
+            albedo,
+            metallic,
+            perceptualRoughness,
+            worldToTangentMat * h);
+    }
+
+    vec3 imageBasedLight = IBL(
+        NdotV,
+        F0,
+        albedo,
+        metallic,
+        perceptualRoughness,
+        ao,
+        lutSample,
+        irradiance,
+        radiance,
+        r,
+        n);
+
+    switch (renderMode) {
+        case RENDER_MODE_ALBEDO:
+            outColor = vec4(albedo, 1.0);
+            break;
+        case RENDER_MODE_METALLIC:
+            outColor = vec4(m_r_ao.rrr, 1.0);
+            break;
+        case RENDER_MODE_ROUGHNESS:
+            outColor = vec4(m_r_ao.ggg, 1.0);
+            break;
+        case RENDER_MODE_NORMALS:
+            outColor = vec4(n * 0.5 + 0.5, 1.0);
+            break;
+        case RENDER_MODE_TANGENTS:
+            outColor = vec4(t * 0.5 + 0.5, 1.0);
+            break;
+        case RENDER_MODE_UV:
+            outColor = vec4(fsIn.texcoord, 0.0, 1.0);
+            break;
+        case RENDER_MODE_NDOTV:
+            outColor = vec4(NdotV.xxx, 1.0);
+            break;
+        case RENDER_MODE_AO:
+            outColor = vec4(m_r_ao.bbb, 1.0);
+            break;
+        case RENDER_MODE_SPECULAR_AO:
+            outColor = vec4(so.xxx, 1.0);
+            break;
+        case RENDER_MODE_HORIZON_SPECULAR_AO:
+            outColor = vec4(horizonSo.xxx, 1.0);
+            break;
+        case RENDER_MODE_DIFFUSE_AMBIENT:
+            outColor = vec4(irradiance.rgb, 1.0);
+            break;
+        case RENDER_MODE_SPECULAR_AMBIENT:
+            outColor = vec4(radiance.rgb, 1.0);
+            break;
+        default:
+            vec3 finalColor = analyticalLight + imageBasedLight;
+            if (fogEnabled != 0) {
+                finalColor = mix(FogColor(), finalColor, FogTransmittance(eyePosition.xyz, wPosition));
+            }
+            outColor = vec4(finalColor, 1.0);
+    }
+}
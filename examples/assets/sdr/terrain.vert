@@ -0,0 +1,81 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_LODS = 16;
+
+//Vertex attributes
+// A flat grid from 0 to 1 along x and z, the patches place and scale it.
+layout(location = 0) in vec3 inPosition;
+
+//Instance attributes
+layout(location = 8) in mat4 inInstanceModel;
+// The level of detail of the patch.
+layout(location = 12) in uint inInstanceMaterialIndex;
+
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+uniform vec3 terrainOrigin;
+uniform vec3 terrainSize;
+// Quads along the side of the patch grid.
+uniform float gridResolution;
+// Distances between which the vertices of each level morph into the ones of the next.
+uniform vec2 morphRanges[MAX_LODS];
+
+layout(binding = 6) uniform sampler2D heightMap;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// Varying variables
+// prefixes: w -> world space
+//           v -> view space
+//           t -> tangent space
+//           l -> local space
+layout(location = 0) out VsOut {
+    vec3 wViewDirection;
+    // From 0 to 1 over the whole terrain.
+    vec2 texcoord;
+} vsOut;
+
+// Texel centers at the edges, so the heights match the samples of the heightmap.
+float SampleHeight(in vec2 wXZ)
+{
+    vec2 uv = (wXZ - terrainOrigin.xz) / terrainSize.xz;
+    vec2 size = vec2(textureSize(heightMap, 0));
+    return textureLod(heightMap, (uv * (size - 1.0) + 0.5) / size, 0.0).r;
+}
+
+// Moves the odd vertices of the grid onto the edges of the coarser grid of the next level.
+// Reference: https://github.com/fstrugar/CDLOD/blob/master/cdlod_paper_latest.pdf
+vec2 MorphVertex(in vec2 gridPosition, in float morph)
+{
+    vec2 fraction = fract(gridPosition * gridResolution * 0.5) * 2.0 / gridResolution;
+    return gridPosition - fraction * morph;
+}
+
+void main()
+{
+    vec2 morphRange = morphRanges[min(int(inInstanceMaterialIndex), MAX_LODS - 1)];
+
+    vec4 wVertexPosition = inInstanceModel * vec4(inPosition.x, 0.0, inPosition.z, 1.0);
+    wVertexPosition.y += SampleHeight(wVertexPosition.xz) * terrainSize.y;
+
+    float eyeDistance = distance(eyePosition.xyz, wVertexPosition.xyz);
+    float morph = clamp((eyeDistance - morphRange.x) / max(morphRange.y - morphRange.x, 0.0001), 0.0, 1.0);
+
+    vec2 lGridPosition = MorphVertex(inPosition.xz, morph);
+    wVertexPosition = inInstanceModel * vec4(lGridPosition.x, 0.0, lGridPosition.y, 1.0);
+    wVertexPosition.y += SampleHeight(wVertexPosition.xz) * terrainSize.y;
+
+    gl_Position = view_projection * wVertexPosition;
+
+    //Assign the view direction for output.
+    vsOut.wViewDirection = eyePosition.xyz - wVertexPosition.xyz;
+
+    vsOut.texcoord = (wVertexPosition.xz - terrainOrigin.xz) / terrainSize.xz;
+}
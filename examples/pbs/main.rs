@@ -4,30 +4,82 @@ mod pbs_scene;
 
 use crate::pbs_scene::PbsScene;
 use engine::application::Application;
-use engine::math::vector::{UVec2, Vec4};
-use engine::{Msaa, Settings, Version};
+use engine::config::EngineConfig;
+use engine::golden_image::GoldenImageTest;
+use engine::headless::HeadlessApplication;
+use engine::logging;
+use engine::{Settings, Version};
+use std::{env, process};
 
-fn main() {
-    Application::run(
-        Settings {
-            name: String::from("PBS-rs: Physically Based Shading demo using Rust"),
-            asset_path: "examples/assets".into(),
-            version: Version {
-                major: 0,
-                minor: 1,
-                patch: 0,
-            },
-            graphics_api_version: Version {
-                major: 4,
-                minor: 5,
-                patch: 0,
-            },
-            window_size: UVec2::new(1200, 720),
-            fullscreen: false,
-            msaa: Msaa::None,
-            vsync: true,
-            default_clear_color: Vec4::new(0.02, 0.02, 0.02, 1.0),
+fn settings(config: &EngineConfig) -> Settings {
+    Settings::from_config(
+        "PBS-rs: Physically Based Shading demo using Rust",
+        Version {
+            major: 0,
+            minor: 1,
+            patch: 0,
         },
-        |context| PbsScene::new(context),
+        config,
     )
 }
+
+// Renders the scene headless and compares it against the reference image in the golden
+// directory. Returns the exit code.
+fn golden_image_test() -> i32 {
+    // Independent of the configuration of the demo, so the image matches the reference.
+    let config = EngineConfig {
+        window_size: [640, 360],
+        asset_path: "examples/assets".into(),
+        ..Default::default()
+    };
+
+    let mut application = match HeadlessApplication::new(settings(&config)) {
+        Ok(application) => application,
+        Err(e) => {
+            println!("Failed to create a headless context: {}", e);
+            return 1;
+        }
+    };
+
+    let test = GoldenImageTest::new("cerberus", "examples/pbs/golden");
+
+    match test.run(&mut application, PbsScene::new) {
+        Ok(result) if result.passed => {
+            println!(
+                "Golden image test passed. Mean delta E {:.3}, max {:.3}.",
+                result.mean_difference, result.max_difference
+            );
+            0
+        }
+        Ok(result) => {
+            println!(
+                "Golden image test failed. {} of {} pixels differ, mean delta E {:.3}, max {:.3}.",
+                result.differing_pixels,
+                result.pixel_count,
+                result.mean_difference,
+                result.max_difference
+            );
+            1
+        }
+        Err(e) => {
+            println!("Golden image test failed: {}", e);
+            1
+        }
+    }
+}
+
+fn main() {
+    // Before reading the configuration, so its warnings are not lost.
+    logging::init(logging::default_level()).unwrap();
+
+    if env::args().any(|arg| arg == "--golden") {
+        process::exit(golden_image_test());
+    }
+
+    let config = EngineConfig::from_args("examples/pbs/config.ron").unwrap_or_else(|e| {
+        println!("{}", e);
+        process::exit(1)
+    });
+
+    Application::run(settings(&config), |context| PbsScene::new(context))
+}
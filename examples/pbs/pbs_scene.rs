@@ -1,42 +1,83 @@
 use std::{
-    borrow::Borrow,
-    mem,
-    {ops::RangeInclusive, rc::Rc},
+    cell::RefCell,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::Receiver,
 };
 
 use engine::{
-    camera::Camera,
+    asset::{
+        hot_reload::AssetChanged,
+        scene_file::{CameraDescription, LightDescription, SceneFile},
+        Asset, Handle,
+    },
+    camera::{
+        bookmarks::CameraBookmarks,
+        controller::{self, CameraController, OrbitController},
+        Camera,
+    },
     color::srgb_to_linear3f,
     imgui::*,
     math::{
-        inverse,
-        matrix::{perspective, Mat4},
-        transpose,
+        matrix::Mat4,
         vector::{UVec2, Vec2, Vec3, Vec4},
     },
+    platform::{
+        action::{ActionMap, Binding},
+        input::{Button, Input},
+    },
     rendering::{
         buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags},
+        command_buffer::{CommandRecorder, DrawResources},
+        debug_draw::DebugDraw,
+        debug_view::DebugView,
+        draw_list::{DrawItem, DrawList},
+        environment::HdrEnvironment,
+        fog::{HeightFog, FOG_BINDING},
+        frame_capture::FrameCapture,
+        frame_stats::FrameStats,
         framebuffer::{AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo},
-        material::{Material, PbsMetallicRoughnessMaterial},
-        mesh::{FullscreenMesh, Mesh, MeshUtilities},
+        gpu_capabilities::GpuCapabilities,
+        gpu_profiler::GpuProfiler,
+        graphics_settings::GraphicsSettings,
+        light::{DirectionalLight, DiskLight, LightBuffer, PointLight, RectLight, SpotLight},
+        light_culling_debug::{LightCullingDebug, LIGHT_CULLING_DEBUG_BINDING},
+        light_probe::LightProbes,
+        ltc::LtcLuts,
+        material::{Material, PbsMetallicRoughnessMaterial, PbsVertexVariant},
+        mesh::{FullscreenMesh, Mesh},
+        normal_visualizer::NormalVisualizer,
+        per_draw::PerDrawUniforms,
+        pipeline_statistics::PipelineStatistics,
         postprocess::{
-            bloom::BloomBuilder, tone_mapper::ToneMapper, PostprocessingStack,
-            PostprocessingStackBuilder,
+            bloom::{Bloom, BloomBuilder},
+            camera_imperfections::CameraImperfections,
+            fsr::FsrBuilder,
+            fxaa::{Fxaa, FxaaBuilder},
+            tone_mapper::ToneMapper,
+            PostprocessingEffect, PostprocessingStack, PostprocessingStackBuilder,
         },
-        program_pipeline::ProgramPipeline,
-        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
-        shader::{Shader, ShaderStage},
-        state::{DepthFunction, FaceCulling, FrontFace, StateManager},
+        sampler::{MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shadow::{LocalShadows, ShadowFilter},
+        sky::{SkyModel, SkyPass, SkySource},
+        sprite_batch::SpriteBatch,
+        ssao::Ssao,
+        state::{FrontFace, StateManager},
         texture::{SizedTextureFormat, TextureCube},
-        Draw,
+        viewport::{MultiView, View, ViewportRect, FRAGMENT_PER_FRAME_BINDING},
     },
     scene::Scene,
     scene::Transition,
     Context, Msaa,
 };
-use glutin::event::{
-    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
-};
+use glutin::event::{DeviceEvent, VirtualKeyCode, WindowEvent};
+
+const BINDINGS_PATH: &str = "examples/pbs/bindings.ron";
+const CAMERA_BOOKMARKS_PATH: &str = "examples/pbs/camera_bookmarks.ron";
+const QUIT: &str = "quit";
+const ENABLE_MSAA: &str = "enable_msaa";
+const DISABLE_MSAA: &str = "disable_msaa";
 
 struct EnvironmentMaps {
     skybox: TextureCube,
@@ -49,20 +90,56 @@ enum SkyboxType {
     Original,
     Radiance,
     Irradiance,
+    Procedural,
+    Atmosphere,
+    // Loaded at runtime, in the Environment window.
+    Hdr,
 }
 
 struct Environment {
     maps: [EnvironmentMaps; 2],
-    skybox_program_pipeline: ProgramPipeline,
-    skybox_mesh: Mesh,
+    sky: SkyPass,
+    hdr: HdrEnvironment,
     active_environment: usize,
     skybox_type: SkyboxType,
 }
 
+impl Environment {
+    fn sky_source(&self) -> SkySource {
+        let maps = &self.maps[self.active_environment];
+        match self.skybox_type {
+            SkyboxType::Original => SkySource::Cubemap(&maps.skybox),
+            SkyboxType::Radiance => SkySource::Cubemap(&maps.radiance),
+            SkyboxType::Irradiance => SkySource::Cubemap(&maps.irradiance),
+            SkyboxType::Procedural | SkyboxType::Atmosphere => SkySource::Procedural,
+            SkyboxType::Hdr => match self.hdr.maps() {
+                Some(hdr_maps) => SkySource::Cubemap(&hdr_maps.skybox),
+                None => SkySource::Cubemap(&maps.skybox),
+            },
+        }
+    }
+
+    // The irradiance and radiance maps of the image based lighting.
+    fn lighting_maps(&self) -> (&TextureCube, &TextureCube) {
+        let maps = &self.maps[self.active_environment];
+        match self.skybox_type {
+            SkyboxType::Procedural | SkyboxType::Atmosphere => {
+                (self.sky.irradiance(), self.sky.radiance())
+            }
+            SkyboxType::Hdr => match self.hdr.maps() {
+                Some(hdr_maps) => (&hdr_maps.irradiance, &hdr_maps.radiance),
+                None => (&maps.irradiance, &maps.radiance),
+            },
+            _ => (&maps.irradiance, &maps.radiance),
+        }
+    }
+}
+
 struct Lighting {
     light_direction: [f32; 3],
     light_color: [f32; 3],
     light_intensity: f32,
+    light_temperature: Option<f32>,
     disney_ggx_hotness: bool,
     geometric_specular_aa: bool,
     specular_ao: bool,
@@ -70,130 +147,159 @@ struct Lighting {
 }
 
 struct Model {
-    pub mesh: Rc<Mesh>,
+    pub mesh: Handle<Mesh>,
     pub transform: Mat4,
 }
 
-#[derive(Default)]
-struct Controls {
-    left_mouse_button_pressed: bool,
-    mouse_x: f32,
-    mouse_y: f32,
-    mouse_sensitivity: f32,
-    scroll: f32,
-    prev_x: f32,
-    prev_y: f32,
-    cursor_over_ui: bool,
-}
-
 #[repr(C)]
 struct VertexPerFrameUniforms {
     view_projection_matrix: Mat4,
     eye_position: Vec4,
 }
 
-#[repr(C)]
-struct VertexPerDrawUniforms {
-    model_matrix: Mat4,
-    normal_matrix: Mat4,
-}
-
 #[repr(C)]
 struct FragmentPerFrameUniforms {
-    light_direction: Vec4,
-    light_color: Vec4,
     ss_variance_and_threshold: Vec2,
     geometric_specular_aa: i32,
     specular_ao: i32,
     disney_ggx_hotness: i32,
     render_mode: i32,
-    _pad: Vec2,
-}
-
-#[repr(C)]
-struct SkyboxPerFrameUniforms {
-    view_projection_matrix: Mat4,
+    screen_space_ao: i32,
+    _pad: f32,
 }
 
 pub struct PbsScene {
     camera: Camera,
+    camera_controller: OrbitController,
+    // Takes over from the controller while its path plays.
+    camera_bookmarks: CameraBookmarks,
+    input: Input,
     model: Model,
-    material: PbsMetallicRoughnessMaterial,
+    material: Rc<RefCell<PbsMetallicRoughnessMaterial>>,
+    // The draws of the frame, recorded and submitted per view.
+    draw_list: DrawList,
+    draw_resources: DrawResources,
+    command_recorder: CommandRecorder,
     environment: Environment,
     framebuffer: Framebuffer,
     resolve_framebuffer: Framebuffer,
     sampler_linear: Sampler,
-    projection_matrix: Mat4,
     post_stack: PostprocessingStack,
-    controls: Controls,
+    // Edited in the Render Settings window, applied at the next update.
+    graphics_settings: GraphicsSettings,
+    applied_graphics_settings: GraphicsSettings,
+    normal_visualizer: NormalVisualizer,
+    debug_draw: DebugDraw,
+    sprite_batch: SpriteBatch,
+    show_crosshair: bool,
+    ssao: Ssao,
     lighting: Lighting,
-    render_mode: usize,
+    light_buffer: LightBuffer,
+    local_shadows: LocalShadows,
+    ltc_luts: LtcLuts,
+    light_probes: LightProbes,
+    // Baked in the next frame, once its lights are set up.
+    light_probes_dirty: bool,
+    fog: HeightFog,
+    light_culling_debug: LightCullingDebug,
+    gpu_profiler: GpuProfiler,
+    pipeline_statistics: PipelineStatistics,
+    gpu_capabilities: GpuCapabilities,
+    frame_capture: FrameCapture,
+    debug_view: DebugView,
+    // The main camera and a top down view, side by side in split screen.
+    views: MultiView,
+    split_screen: bool,
     vertex_per_frame_ubo: Buffer,
-    vertex_per_draw_ubo: Buffer,
+    per_draw_uniforms: PerDrawUniforms,
     fragment_per_frame_ubo: Buffer,
-    skybox_per_frame_ubo: Buffer,
+    asset_changes: Receiver<AssetChanged>,
+    scene_file: SceneFile,
+    scene_path: PathBuf,
     dt: f32,
 }
 
 impl PbsScene {
+    // The bindings of the file over the defaults of the demo and the camera controller.
+    fn create_input() -> Input {
+        let mut defaults = ActionMap::new();
+        defaults.bind(QUIT, Binding::Key(VirtualKeyCode::Escape));
+        defaults.bind(QUIT, Binding::GamepadButton(Button::Start));
+        defaults.bind(ENABLE_MSAA, Binding::Key(VirtualKeyCode::A));
+        defaults.bind(DISABLE_MSAA, Binding::Key(VirtualKeyCode::S));
+        controller::bind_default_actions(&mut defaults);
+
+        let mut input = Input::new();
+        let actions = input.actions_mut();
+        *actions = ActionMap::load(BINDINGS_PATH).unwrap_or_else(|e| {
+            println!("WARNING: {}", e);
+            ActionMap::new()
+        });
+        actions.merge(&defaults);
+
+        input
+    }
+
     pub fn new(context: Context) -> Self {
+        let window_size = context.window_size();
         let Context {
-            window,
             settings,
             asset_manager,
             ..
         } = context;
 
         let asset_path = settings.asset_path.as_path();
-        let camera = Camera::new(
-            Vec3::new(0.0, 0.0, -60.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            10.0,
-            30.0,
-            10.0,
-            200.0,
-            3.0,
-            4.0,
-        );
 
-        let skybox_prog = ProgramPipeline::new()
-            .add_shader(
-                &Shader::new(ShaderStage::Vertex, asset_path.join("sdr/skybox.vert")).unwrap(),
-            )
-            .add_shader(
-                &Shader::new(ShaderStage::Fragment, asset_path.join("sdr/skybox.frag")).unwrap(),
+        let scene_path = asset_path.join("scenes/cerberus.ron");
+        let scene_file = SceneFile::load(&scene_path, None).expect("Failed to load scene");
+        let camera = scene_file.camera.to_camera(window_size);
+        let camera_controller = scene_file.camera.to_orbit_controller(&camera);
+
+        let camera_bookmarks = if Path::new(CAMERA_BOOKMARKS_PATH).is_file() {
+            CameraBookmarks::load(CAMERA_BOOKMARKS_PATH).unwrap_or_else(|e| {
+                println!("WARNING: {}", e);
+                CameraBookmarks::new()
+            })
+        } else {
+            CameraBookmarks::new()
+        };
+
+        let entity = scene_file
+            .entities
+            .first()
+            .expect("The scene has no entities");
+        let mesh_path = entity.mesh.as_ref().expect("The entity has no mesh");
+        let model_transform = entity.transform.matrix();
+        let template = asset_manager
+            .load_material_template(
+                scene_file.resolve(
+                    entity
+                        .material
+                        .as_ref()
+                        .expect("The entity has no material"),
+                ),
             )
-            .build()
-            .unwrap();
+            .expect("Failed to load material template");
 
-        let mesh = asset_manager
-            .load_mesh(asset_path.join("models/cerberus/cerberus.glb"))
-            .expect("Failed to load mesh");
+        // Decode the mesh and textures in parallel.
+        let mesh = asset_manager.request_mesh(scene_file.resolve(mesh_path));
 
-        let skybox_mesh = MeshUtilities::generate_cube(1.0);
+        let mut request_map = |map: &Option<PathBuf>| {
+            asset_manager.request_texture(map.as_ref().expect("Incomplete material template"))
+        };
+        let albedo = request_map(&template.albedo);
+        let metallic_roughness_ao = request_map(&template.metallic_roughness_ao);
+        let normals = request_map(&template.normals);
 
+        let mesh = asset_manager.wait(&mesh).expect("Failed to load mesh");
         let albedo = asset_manager
-            .load_texture_2d(
-                asset_path.join("textures/cerberus/Cerberus_A.png"),
-                true,
-                true,
-            )
+            .wait(&albedo)
             .expect("Failed to load albedo texture");
-
         let metallic_roughness_ao = asset_manager
-            .load_texture_2d(
-                asset_path.join("textures/cerberus/Cerberus_M_R_AO.png"),
-                false,
-                true,
-            )
+            .wait(&metallic_roughness_ao)
             .expect("Failed to load metallic/roughness/ao texture");
-
         let normals = asset_manager
-            .load_texture_2d(
-                asset_path.join("textures/cerberus/Cerberus_N.png"),
-                false,
-                true,
-            )
+            .wait(&normals)
             .expect("Failed to load normals texture");
 
         let skybox_exterior =
@@ -236,49 +342,39 @@ impl PbsScene {
             },
         ];
 
-        let framebuffer = Framebuffer::new(
-            UVec2::new(window.inner_size().width, window.inner_size().height),
-            Msaa::X4,
-            vec![
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Rgba16f,
-                    AttachmentType::Texture,
-                ),
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Rgba16f,
-                    AttachmentType::Texture,
-                ),
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Depth16,
-                    AttachmentType::Renderbuffer,
-                ),
-            ],
-        )
-        .unwrap_or_else(|error| panic!("Framebuffer creation error: {}", error));
+        let graphics_settings = GraphicsSettings::from_preset(settings.quality);
 
-        let resolve_framebuffer = Framebuffer::new(
-            UVec2::new(window.inner_size().width, window.inner_size().height),
-            Msaa::None,
-            vec![
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Rgba16f,
-                    AttachmentType::Texture,
-                ),
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Rgba16f,
-                    AttachmentType::Texture,
-                ),
-                FramebufferAttachmentCreateInfo::new(
-                    SizedTextureFormat::Depth16,
-                    AttachmentType::Renderbuffer,
-                ),
-            ],
-        )
-        .unwrap_or_else(|error| panic!("Framebuffer creation error: {}", error));
+        let framebuffer = Self::scene_framebuffer(window_size, graphics_settings.msaa());
+        let resolve_framebuffer = Self::scene_framebuffer(window_size, Msaa::None);
 
         let post_stack = PostprocessingStackBuilder::new()
-            .with_effect(BloomBuilder::new(asset_path).build())
-            .with_effect(ToneMapper::new())
+            .with_effect(
+                FxaaBuilder::new()
+                    .enabled(graphics_settings.fxaa())
+                    .build()
+                    .unwrap_or_else(|error| panic!("FXAA creation error: {}", error)),
+            )
+            .with_effect(
+                FsrBuilder::new()
+                    .enabled(false)
+                    .build()
+                    .unwrap_or_else(|error| panic!("FSR creation error: {}", error)),
+            )
+            .with_effect(
+                BloomBuilder::new()
+                    .enabled(graphics_settings.bloom)
+                    .build()
+                    .unwrap_or_else(|error| panic!("Bloom creation error: {}", error)),
+            )
+            .with_effect(
+                CameraImperfections::new(scene_file.post_processing.clone()).unwrap_or_else(
+                    |error| panic!("Camera imperfections creation error: {}", error),
+                ),
+            )
+            .with_effect(
+                ToneMapper::new()
+                    .unwrap_or_else(|error| panic!("Tone mapper creation error: {}", error)),
+            )
             .build();
 
         let sampler_linear = Sampler::new(
@@ -288,24 +384,47 @@ impl PbsScene {
             WrappingMode::ClampToEdge,
             WrappingMode::ClampToEdge,
             Vec4::new(0.0, 0.0, 0.0, 0.0),
-            Anisotropy::X16,
+            graphics_settings.anisotropy,
         );
 
-        let projection = perspective(
-            window.inner_size().width,
-            window.inner_size().height,
-            60,
-            0.5,
-            500.0,
-        );
-
-        let material = PbsMetallicRoughnessMaterial::new(
+        let mut material = PbsMetallicRoughnessMaterial::new(
             asset_path,
             albedo,
             metallic_roughness_ao,
             normals,
             None,
-        );
+        )
+        .unwrap_or_else(|error| panic!("Material creation error: {}", error));
+        material.set_base_color(template.base_color);
+        material.set_metallic_scale(template.metallic_scale);
+        material.set_roughness_scale(template.roughness_scale);
+        material.set_anisotropy(graphics_settings.anisotropy);
+        material.set_vertex_variant(PbsVertexVariant::for_mesh(&mesh));
+
+        let (light_direction, light_color, light_intensity, light_temperature) = scene_file
+            .lights
+            .iter()
+            .find_map(|light| match light {
+                LightDescription::Directional {
+                    direction,
+                    color,
+                    intensity,
+                    temperature,
+                } => Some((*direction, *color, *intensity, *temperature)),
+                _ => None,
+            })
+            .unwrap_or(([0.4, 0.0, -1.0], [1.0, 1.0, 1.0], 5.0, None));
+
+        let mut light_probes = LightProbes::new(8)
+            .unwrap_or_else(|error| panic!("Light probes creation error: {}", error));
+        for probe in &scene_file.light_probes {
+            light_probes.add(probe.to_light_probe());
+        }
+
+        let hot_reload = asset_manager.hot_reload();
+        hot_reload.set_enabled(cfg!(debug_assertions));
+        material.watch(hot_reload);
+        let asset_changes = hot_reload.subscribe();
 
         let mut vertex_per_frame_ubo = Buffer::new(
             "Vertex Per Frame UBO",
@@ -316,14 +435,7 @@ impl PbsScene {
         vertex_per_frame_ubo.bind(0);
         vertex_per_frame_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
 
-        let mut vertex_per_draw_ubo = Buffer::new(
-            "Vertex Per Draw UBO",
-            mem::size_of::<VertexPerDrawUniforms>() as isize,
-            BufferTarget::Uniform,
-            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
-        );
-        vertex_per_draw_ubo.bind(1);
-        vertex_per_draw_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
+        let per_draw_uniforms = PerDrawUniforms::new(256);
 
         let mut fragment_per_frame_ubo = Buffer::new(
             "Fragment Per Frame UBO",
@@ -331,101 +443,448 @@ impl PbsScene {
             BufferTarget::Uniform,
             BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
         );
-        fragment_per_frame_ubo.bind(2);
+        fragment_per_frame_ubo.bind(FRAGMENT_PER_FRAME_BINDING);
         fragment_per_frame_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
 
-        let mut skybox_per_frame_ubo = Buffer::new(
-            "Skybox Matrices UBO",
-            mem::size_of::<SkyboxPerFrameUniforms>() as isize,
-            BufferTarget::Uniform,
-            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
-        );
-        skybox_per_frame_ubo.bind(5);
-        skybox_per_frame_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
+        let mut views = MultiView::new(2);
+        views.add(View::new("Main", camera.clone(), ViewportRect::full()));
+        views.add(View::new(
+            "Top",
+            Camera::perspective(45.0, 0.1, 100.0, window_size),
+            ViewportRect::full(),
+        ));
+
+        let mut ssao =
+            Ssao::new(window_size).unwrap_or_else(|error| panic!("SSAO creation error: {}", error));
+        ssao.set_enabled(graphics_settings.ssao);
 
         PbsScene {
             camera,
+            camera_controller,
+            camera_bookmarks,
+            input: Self::create_input(),
             model: Model {
                 mesh,
-                transform: Mat4::identity(),
+                transform: model_transform,
             },
-            material,
+            material: Rc::new(RefCell::new(material)),
+            draw_list: DrawList::new(),
+            draw_resources: DrawResources::new(),
+            command_recorder: CommandRecorder::new(),
             environment: Environment {
                 maps: environments,
-                skybox_program_pipeline: skybox_prog,
-                skybox_mesh,
+                sky: SkyPass::new()
+                    .unwrap_or_else(|error| panic!("Sky pass creation error: {}", error)),
+                hdr: HdrEnvironment::new()
+                    .unwrap_or_else(|error| panic!("HDR environment creation error: {}", error)),
                 active_environment: 1,
                 skybox_type: SkyboxType::Radiance,
             },
             framebuffer,
             resolve_framebuffer,
             sampler_linear,
-            projection_matrix: projection,
             post_stack,
-            controls: Controls {
-                mouse_sensitivity: 2.0,
-                ..Default::default()
-            },
+            graphics_settings,
+            applied_graphics_settings: graphics_settings,
+            normal_visualizer: NormalVisualizer::new()
+                .unwrap_or_else(|error| panic!("Normal visualizer creation error: {}", error)),
+            debug_draw: DebugDraw::new()
+                .unwrap_or_else(|error| panic!("Debug draw creation error: {}", error)),
+            sprite_batch: SpriteBatch::new()
+                .unwrap_or_else(|error| panic!("Sprite batch creation error: {}", error)),
+            show_crosshair: false,
+            ssao,
             lighting: Lighting {
-                light_direction: [0.4, 0.0, -1.0],
-                light_color: [1.0, 1.0, 1.0],
-                light_intensity: 5.0,
+                light_direction,
+                light_color,
+                light_intensity,
+                light_temperature,
                 disney_ggx_hotness: true,
                 geometric_specular_aa: true,
                 specular_ao: true,
                 ss_variance_and_threshold: Vec2::new(0.25, 0.18),
             },
-            render_mode: 0,
+            light_buffer: LightBuffer::new(64),
+            local_shadows: LocalShadows::new(graphics_settings.shadow_resolution)
+                .unwrap_or_else(|error| panic!("Local shadows creation error: {}", error)),
+            ltc_luts: LtcLuts::new(),
+            light_probes_dirty: !light_probes.is_empty(),
+            light_probes,
+            fog: HeightFog::new(scene_file.fog.clone()),
+            light_culling_debug: LightCullingDebug::new(),
+            gpu_profiler: GpuProfiler::new(),
+            pipeline_statistics: PipelineStatistics::new(),
+            gpu_capabilities: (*GpuCapabilities::current()).clone(),
+            frame_capture: FrameCapture::new(),
+            debug_view: DebugView::Lit,
+            views,
+            split_screen: false,
             vertex_per_frame_ubo,
-            vertex_per_draw_ubo,
+            per_draw_uniforms,
             fragment_per_frame_ubo,
-            skybox_per_frame_ubo,
+            asset_changes,
+            scene_file,
+            scene_path,
             dt: 0.0,
         }
     }
 
-    fn geometry_pass(&self) {
-        self.framebuffer.bind();
-        self.framebuffer.clear(&Vec4::new(0.0, 0.0, 0.0, 1.0));
+    // The HDR color, velocity and depth targets of the scene.
+    fn scene_framebuffer(size: UVec2, msaa: Msaa) -> Framebuffer {
+        Framebuffer::new(
+            size,
+            msaa,
+            vec![
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Rgba16f,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Rgba16f,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth16,
+                    AttachmentType::Renderbuffer,
+                ),
+            ],
+        )
+        .unwrap_or_else(|error| panic!("Framebuffer creation error: {}", error))
+    }
+
+    // Reconfigures the passes and targets the graphics settings changed.
+    fn apply_graphics_settings(&mut self) {
+        let settings = self.graphics_settings;
+        let applied = self.applied_graphics_settings;
+
+        if settings.msaa() != applied.msaa() {
+            self.framebuffer = Self::scene_framebuffer(self.framebuffer.size(), settings.msaa());
+        }
+
+        if let Some(fxaa) = self.post_stack.get_mut::<Fxaa>() {
+            set_effect_enabled(fxaa, settings.fxaa());
+        }
+
+        if let Some(bloom) = self.post_stack.get_mut::<Bloom>() {
+            set_effect_enabled(bloom, settings.bloom);
+        }
+
+        self.ssao.set_enabled(settings.ssao);
+        if let Err(e) = self
+            .local_shadows
+            .set_atlas_size(settings.shadow_resolution)
+        {
+            println!("WARNING: {}", e);
+        }
+        self.sampler_linear.set_anisotropy(settings.anisotropy);
+        self.material
+            .borrow_mut()
+            .set_anisotropy(settings.anisotropy);
+
+        self.applied_graphics_settings = settings;
+    }
+
+    // Writes the camera and light as they are edited in the UI back to the scene file.
+    // Orbits around the target from the current placement of the camera.
+    fn retarget_camera_controller(&mut self, target: Vec3) {
+        let controller = &self.camera_controller;
+
+        self.camera_controller = OrbitController::new(
+            &self.camera,
+            target,
+            controller.orbit_speed(),
+            controller.zoom_speed(),
+            controller.min_distance(),
+            controller.max_distance(),
+            controller.orbit_dampening(),
+            controller.zoom_dampening(),
+        );
+    }
+
+    fn save_scene(&mut self) {
+        self.scene_file.camera =
+            CameraDescription::from_camera(&self.camera, &self.camera_controller);
+
+        let light = LightDescription::Directional {
+            direction: self.lighting.light_direction,
+            color: self.lighting.light_color,
+            intensity: self.lighting.light_intensity,
+            temperature: self.lighting.light_temperature,
+        };
+
+        match self
+            .scene_file
+            .lights
+            .iter_mut()
+            .find(|light| matches!(light, LightDescription::Directional { .. }))
+        {
+            Some(directional) => *directional = light,
+            None => self.scene_file.lights.push(light),
+        }
+
+        self.scene_file.fog = self.fog.settings().clone();
 
+        if let Some(camera_imperfections) = self.post_stack.get::<CameraImperfections>() {
+            self.scene_file.post_processing = camera_imperfections.settings().clone();
+        }
+
+        if let Err(e) = self.scene_file.save(&self.scene_path) {
+            println!("WARNING: {}", e);
+        }
+    }
+
+    fn fill_vertex_per_frame_uniforms(&self) {
         let camera_pos = self.camera.position();
         let vertex_per_frame_uniforms = VertexPerFrameUniforms {
-            view_projection_matrix: self.projection_matrix.borrow() * self.camera.transform(),
+            view_projection_matrix: self.camera.view_projection_matrix(),
             eye_position: Vec4::new(camera_pos.x, camera_pos.y, camera_pos.z, 1.0),
         };
 
-        let vertex_per_draw_uniforms = VertexPerDrawUniforms {
-            model_matrix: self.model.transform.clone_owned(),
-            normal_matrix: transpose(&inverse(&self.model.transform)),
-        };
-
         self.vertex_per_frame_ubo
             .fill_mapped(0, &vertex_per_frame_uniforms);
+    }
+
+    fn directional_light(&self) -> DirectionalLight {
+        DirectionalLight {
+            direction: self.lighting.light_direction.into(),
+            color: srgb_to_linear3f(&self.lighting.light_color.into()),
+            temperature: self.lighting.light_temperature,
+            illuminance: self.lighting.light_intensity,
+        }
+    }
+
+    // The directional light of the UI, then the other lights of the scene file.
+    fn light_pass(&mut self) {
+        self.local_shadows.begin_frame();
+        self.light_buffer.clear();
+
+        self.light_buffer
+            .push_directional(&self.directional_light());
+
+        for light in &self.scene_file.lights {
+            match *light {
+                LightDescription::Point {
+                    position,
+                    color,
+                    intensity,
+                    range,
+                    temperature,
+                    shadow,
+                } => {
+                    let position = Vec3::from(position);
+                    let shadow = self
+                        .local_shadows
+                        .add_point_light(&position, range, &shadow);
+
+                    self.light_buffer.push_point(&PointLight {
+                        position,
+                        color: srgb_to_linear3f(&color.into()),
+                        temperature,
+                        luminous_power: intensity,
+                        range,
+                        shadow,
+                    })
+                }
+                LightDescription::Spot {
+                    position,
+                    direction,
+                    color,
+                    intensity,
+                    range,
+                    inner_angle,
+                    outer_angle,
+                    temperature,
+                    shadow,
+                } => {
+                    let position = Vec3::from(position);
+                    let direction = Vec3::from(direction);
+                    let shadow = self.local_shadows.add_spot_light(
+                        &position,
+                        &direction,
+                        outer_angle.to_radians(),
+                        range,
+                        &shadow,
+                    );
+
+                    self.light_buffer.push_spot(&SpotLight {
+                        position,
+                        direction,
+                        color: srgb_to_linear3f(&color.into()),
+                        temperature,
+                        luminous_power: intensity,
+                        range,
+                        inner_angle: inner_angle.to_radians(),
+                        outer_angle: outer_angle.to_radians(),
+                        shadow,
+                    })
+                }
+                LightDescription::Rect {
+                    position,
+                    direction,
+                    up,
+                    color,
+                    intensity,
+                    width,
+                    height,
+                    range,
+                    two_sided,
+                    temperature,
+                } => self.light_buffer.push_rect(&RectLight {
+                    position: position.into(),
+                    direction: direction.into(),
+                    up: up.into(),
+                    width,
+                    height,
+                    color: srgb_to_linear3f(&color.into()),
+                    temperature,
+                    luminous_power: intensity,
+                    range,
+                    two_sided,
+                }),
+                LightDescription::Disk {
+                    position,
+                    direction,
+                    color,
+                    intensity,
+                    radius,
+                    range,
+                    two_sided,
+                    temperature,
+                } => self.light_buffer.push_disk(&DiskLight {
+                    position: position.into(),
+                    direction: direction.into(),
+                    radius,
+                    color: srgb_to_linear3f(&color.into()),
+                    temperature,
+                    luminous_power: intensity,
+                    range,
+                    two_sided,
+                }),
+                LightDescription::Directional { .. } => {}
+            }
+        }
+
+        self.local_shadows
+            .render_draw_list(&self.draw_list, &mut self.per_draw_uniforms);
+
+        self.light_buffer.upload();
+    }
+
+    fn ssao_pass(&mut self) {
+        self.ssao
+            .prepass_draw_list(&self.draw_list, &mut self.per_draw_uniforms);
+
+        self.ssao.compute(&self.camera)
+    }
+
+    // Drawn over the post-processed image, with a dark outline to stay visible on bright areas.
+    fn crosshair(&mut self, window_size: UVec2) {
+        let center = Vec2::new(window_size.x as f32, window_size.y as f32) * 0.5;
+
+        for (size, color, layer) in [
+            (Vec2::new(3.0, 13.0), Vec4::new(0.0, 0.0, 0.0, 0.6), 0),
+            (Vec2::new(1.0, 11.0), Vec4::new(1.0, 1.0, 1.0, 0.9), 1),
+        ]
+        .iter()
+        {
+            self.sprite_batch
+                .quad(center - size * 0.5, *size, *color, *layer);
+            self.sprite_batch
+                .quad(center - size.yx() * 0.5, size.yx(), *color, *layer);
+        }
+    }
+
+    // The draws of the frame, shared by every pass that draws the model.
+    fn collect_draws(&mut self) {
+        self.draw_list.clear();
+        self.draw_resources.clear();
+
+        self.draw_list.push(DrawItem {
+            mesh: self.model.mesh.clone(),
+            material: self.material.clone(),
+            model: self.model.transform,
+            entity: None,
+        });
+
+        // The passes that draw the list directly submit it in the order of the main camera.
+        self.draw_list.sort(
+            &mut self.draw_resources,
+            &self.camera.view_projection_matrix(),
+        );
+    }
+
+    // The main view follows the camera, the top view looks down on the model from as far away.
+    fn update_views(&mut self) {
+        let layout = if self.split_screen {
+            ViewportRect::split_horizontal(2)
+        } else {
+            vec![ViewportRect::full()]
+        };
+        self.views.set_layout(&layout);
+
+        let distance = self.camera.position().norm();
+
+        self.views.view_mut(0).camera = self.camera.clone();
+        self.views
+            .view_mut(1)
+            .camera
+            .look_to(Vec3::new(0.0, distance, 0.0), Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    fn geometry_pass(&mut self) {
+        self.framebuffer.bind();
+        self.framebuffer.clear(&Vec4::new(0.0, 0.0, 0.0, 1.0));
 
-        self.vertex_per_draw_ubo
-            .fill_mapped(0, &vertex_per_draw_uniforms);
+        // The occlusion is computed for the main camera only.
+        self.bind_lighting(
+            self.debug_view.render_mode(),
+            self.ssao.is_enabled() && !self.split_screen,
+        );
+
+        let items = self.draw_list.record_items(&mut self.draw_resources);
+
+        let per_draw_uniforms = &mut self.per_draw_uniforms;
+        let command_recorder = &self.command_recorder;
+        let draw_resources = &self.draw_resources;
+        let model = &self.model;
+        let debug_view = &self.debug_view;
+        let normal_visualizer = &self.normal_visualizer;
+        let debug_draw = &mut self.debug_draw;
+
+        self.views.render(&self.framebuffer, false, |_, view| {
+            command_recorder
+                .record(items.clone(), &view.camera.view_projection_matrix())
+                .submit_with(draw_resources, per_draw_uniforms, || {
+                    debug_view.apply_state_override()
+                });
+
+            normal_visualizer.draw(&model.mesh);
 
-        self.material.bind();
+            debug_draw.axes(&Mat4::identity(), 1.0);
+            debug_draw.render();
+        });
 
-        let program_pipeline = self.material.program_pipeline();
+        // The passes outside of the views use the per frame uniforms of the main camera.
+        self.vertex_per_frame_ubo.bind(0);
+
+        self.framebuffer.unbind(false);
+
+        Framebuffer::blit(&self.framebuffer, &self.resolve_framebuffer);
+    }
 
-        let mut light_color: Vec3 = srgb_to_linear3f(&self.lighting.light_color.into());
-        light_color *= self.lighting.light_intensity;
+    // The per frame uniforms and lighting inputs of the material shader.
+    fn bind_lighting(&self, render_mode: i32, screen_space_ao: bool) {
+        let material = self.material.borrow();
+        let program_pipeline = material.program_pipeline();
 
         let fragment_per_frame_uniforms = FragmentPerFrameUniforms {
-            light_direction: Vec4::new(
-                self.lighting.light_direction[0],
-                self.lighting.light_direction[1],
-                self.lighting.light_direction[2],
-                1.0,
-            ),
-            light_color: Vec4::new(light_color.x, light_color.y, light_color.z, 0.0),
             ss_variance_and_threshold: self.lighting.ss_variance_and_threshold.clone_owned(),
             geometric_specular_aa: self.lighting.geometric_specular_aa as i32,
             specular_ao: self.lighting.specular_ao as i32,
             disney_ggx_hotness: self.lighting.disney_ggx_hotness as i32,
-            render_mode: self.render_mode as i32,
-            _pad: Vec2::new(0.0, 0.0),
+            render_mode,
+            screen_space_ao: screen_space_ao as i32,
+            _pad: 0.0,
         };
 
         self.fragment_per_frame_ubo
@@ -433,73 +892,140 @@ impl PbsScene {
 
         const IRRADIANCE_MAP_BINDING_INDEX: u32 = 4;
         const RADIANCE_MAP_BINDING_INDEX: u32 = 5;
+        let (irradiance, radiance) = self.environment.lighting_maps();
+
         program_pipeline
             .set_texture_cube(
                 IRRADIANCE_MAP_BINDING_INDEX,
-                &self.environment.maps[self.environment.active_environment].irradiance,
+                irradiance,
                 &self.sampler_linear,
             )
-            .set_texture_cube(
-                RADIANCE_MAP_BINDING_INDEX,
-                &self.environment.maps[self.environment.active_environment].radiance,
-                &self.sampler_linear,
-            );
+            .set_texture_cube(RADIANCE_MAP_BINDING_INDEX, radiance, &self.sampler_linear);
 
-        self.model.mesh.draw();
+        const SSAO_MAP_BINDING_INDEX: u32 = 7;
+        self.ssao
+            .bind_occlusion(program_pipeline, SSAO_MAP_BINDING_INDEX);
 
-        self.framebuffer.unbind(false);
-
-        Framebuffer::blit(&self.framebuffer, &self.resolve_framebuffer);
+        const SHADOW_ATLAS_BINDING_INDEX: u32 = 9;
+        self.local_shadows
+            .bind(program_pipeline, SHADOW_ATLAS_BINDING_INDEX);
+        const SHADOW_DEPTH_BINDING_INDEX: u32 = 15;
+        self.local_shadows
+            .bind_depth(program_pipeline, SHADOW_DEPTH_BINDING_INDEX);
+        self.light_buffer.bind();
 
-        self.material.unbind()
-    }
+        const LTC_LUTS_BINDING_INDEX: u32 = 10;
+        self.ltc_luts.bind(program_pipeline, LTC_LUTS_BINDING_INDEX);
 
-    fn skybox_pass(&self) {
-        StateManager::set_depth_function(DepthFunction::LessOrEqual);
-        StateManager::set_face_culling(FaceCulling::Front);
+        const LIGHT_PROBES_BINDING_INDEX: u32 = 12;
+        self.light_probes
+            .bind(program_pipeline, LIGHT_PROBES_BINDING_INDEX);
 
-        self.resolve_framebuffer.bind();
+        self.fog.bind(FOG_BINDING);
 
-        self.environment.skybox_program_pipeline.bind();
+        self.light_culling_debug
+            .bind(&self.camera, LIGHT_CULLING_DEBUG_BINDING);
+    }
 
-        let environment_map = match self.environment.skybox_type {
-            SkyboxType::Original => {
-                &self.environment.maps[self.environment.active_environment].skybox
-            }
-            SkyboxType::Radiance => {
-                &self.environment.maps[self.environment.active_environment].radiance
-            }
-            SkyboxType::Irradiance => {
-                &self.environment.maps[self.environment.active_environment].irradiance
-            }
-        };
+    // Captures the model and the sky into the light probes, lit by the lights of this frame.
+    fn bake_light_probes(&mut self) {
+        // The lighting inputs stay bound, every face only rebinds the material over the sky.
+        self.material.borrow().bind();
+        self.bind_lighting(0, false);
+        self.material.borrow().unbind();
+
+        let items = self.draw_list.record_items(&mut self.draw_resources);
+
+        let per_draw_uniforms = &mut self.per_draw_uniforms;
+        let command_recorder = &self.command_recorder;
+        let draw_resources = &self.draw_resources;
+        let vertex_per_frame_ubo = &self.vertex_per_frame_ubo;
+        let environment = &self.environment;
+
+        self.light_probes.bake_all(|probe_view| {
+            let position = probe_view.position;
+            vertex_per_frame_ubo.fill_mapped(
+                0,
+                &VertexPerFrameUniforms {
+                    view_projection_matrix: probe_view.view_projection,
+                    eye_position: Vec4::new(position.x, position.y, position.z, 1.0),
+                },
+            );
 
-        let mut view = self.camera.transform().clone_owned();
-        view.m14 = 0.0;
-        view.m24 = 0.0;
-        view.m34 = 0.0;
-        view.m44 = 1.0;
+            command_recorder
+                .record(items.clone(), &probe_view.view_projection)
+                .submit(draw_resources, per_draw_uniforms);
 
-        let skybox_per_frame_uniforms = SkyboxPerFrameUniforms {
-            view_projection_matrix: self.projection_matrix * &view,
-        };
+            environment.sky.render_view(
+                &probe_view.view,
+                &probe_view.projection,
+                environment.sky_source(),
+            );
+        });
 
-        self.skybox_per_frame_ubo
-            .fill_mapped(0, &skybox_per_frame_uniforms);
+        self.fill_vertex_per_frame_uniforms()
+    }
 
-        self.environment.skybox_program_pipeline.set_texture_cube(
-            0,
-            &environment_map,
-            &self.sampler_linear,
-        );
+    fn skybox_pass(&mut self) {
+        let environment = &self.environment;
 
-        self.environment.skybox_mesh.draw();
+        self.views
+            .render(&self.resolve_framebuffer, false, |_, view| {
+                environment
+                    .sky
+                    .render(&view.camera, environment.sky_source())
+            });
 
+        self.vertex_per_frame_ubo.bind(0);
         self.resolve_framebuffer.unbind(false);
-        self.environment.skybox_program_pipeline.unbind();
+    }
 
-        StateManager::set_depth_function(DepthFunction::Less);
-        StateManager::set_face_culling(FaceCulling::Back)
+    // Per light shadow bias of the point and spot lights of the scene file.
+    fn light_shadows_gui(&mut self, ui: &Ui) {
+        let default_bias = *self.local_shadows.bias();
+        let lights = &mut self.scene_file.lights;
+
+        imgui::TreeNode::new(im_str!("Light Shadows"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                ui.indent();
+
+                for (index, light) in lights.iter_mut().enumerate() {
+                    let (name, shadow) = match light {
+                        LightDescription::Point { shadow, .. } => ("Point Light", shadow),
+                        LightDescription::Spot { shadow, .. } => ("Spot Light", shadow),
+                        _ => continue,
+                    };
+
+                    let id = ui.push_id(index as i32);
+                    ui.text(format!("{} {}", name, index));
+
+                    let mut custom_bias = shadow.bias.is_some();
+                    if ui.checkbox(im_str!("Custom Bias"), &mut custom_bias) {
+                        shadow.bias = if custom_bias {
+                            Some(default_bias)
+                        } else {
+                            None
+                        };
+                    }
+                    if let Some(bias) = shadow.bias.as_mut() {
+                        bias.gui(ui)
+                    }
+
+                    if let ShadowFilter::Pcss { light_radius } = &mut shadow.filter {
+                        imgui::Slider::new(im_str!("Light Radius"))
+                            .range(RangeInclusive::new(0.01, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, light_radius);
+                    }
+                    id.pop(ui);
+                }
+
+                ui.unindent()
+            });
     }
 }
 
@@ -513,101 +1039,76 @@ impl Scene for PbsScene {
     fn resume(&mut self, _: Context) {}
 
     fn handle_event(&mut self, _: Context, event: WindowEvent) -> Transition {
-        match event {
-            WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                ..
-            } => self.controls.left_mouse_button_pressed = true,
-            WindowEvent::MouseInput {
-                state: ElementState::Released,
-                button: MouseButton::Left,
-                ..
-            } => {
-                self.controls.left_mouse_button_pressed = false;
-
-                self.controls.mouse_x = 0.0;
-                self.controls.mouse_y = 0.0;
-                self.controls.prev_x = 0.0;
-                self.controls.prev_y = 0.0;
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                if self.controls.left_mouse_button_pressed && !self.controls.cursor_over_ui {
-                    self.controls.mouse_x = position.x as f32;
-                    self.controls.mouse_y = position.y as f32;
-                }
-            }
-            WindowEvent::MouseWheel {
-                delta: MouseScrollDelta::LineDelta(_, y),
-                ..
-            } => {
-                if !self.controls.cursor_over_ui {
-                    self.controls.scroll = y;
-                }
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    },
-                ..
-            } => return Transition::Quit,
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::A),
-                        ..
-                    },
-                ..
-            } => {
-                use gl_bindings as gl;
-                unsafe { gl::Enable(gl::MULTISAMPLE) }
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::S),
-                        ..
-                    },
-                ..
-            } => {
-                use gl_bindings as gl;
-                unsafe { gl::Disable(gl::MULTISAMPLE) }
-            }
-            WindowEvent::Resized(size) => {
-                let x = size.width;
-                let y = size.height;
-                //TODO: Get this from the camera
-                self.projection_matrix = perspective(x, y, 60, 0.5, 500.0);
-                StateManager::set_viewport(0, 0, x as i32, y as i32)
-            }
-            _ => {}
+        self.input.handle_event(&event);
+
+        if let WindowEvent::Resized(size) = event {
+            let x = size.width;
+            let y = size.height;
+            self.camera.set_viewport_size(UVec2::new(x, y));
+            StateManager::set_viewport(0, 0, x as i32, y as i32)
         }
+
         Transition::None
     }
 
+    fn handle_device_event(&mut self, _: Context, event: &DeviceEvent) {
+        self.input.handle_device_event(event)
+    }
+
     fn update(&mut self, context: Context) -> Transition {
-        let Context { timer, .. } = context;
+        let Context {
+            window,
+            timer,
+            asset_manager,
+            ..
+        } = context;
+
+        self.dt = timer.delta_time();
 
-        self.dt = timer.get_delta();
+        for change in self.asset_changes.try_iter() {
+            self.material.borrow_mut().reload(&change, asset_manager);
+        }
+
+        self.input.poll_gamepads();
+
+        if let Some(window) = window {
+            if self.input.action_pressed(controller::CAPTURE_CURSOR) {
+                let captured = !self.input.cursor_captured();
 
-        let mut dx = 0.0;
-        let mut dy = 0.0;
+                if let Err(e) = self.input.set_cursor_captured(window, captured) {
+                    println!("WARNING: {}", e);
+                }
+            }
+
+            self.input.update_cursor(window);
+        }
+
+        if self.input.action_released(QUIT) {
+            return Transition::Quit;
+        }
 
-        if self.controls.prev_x != 0.0 || self.controls.prev_y != 0.0 {
-            dx = (self.controls.mouse_x - self.controls.prev_x) * self.controls.mouse_sensitivity;
-            dy = (self.controls.mouse_y - self.controls.prev_y) * self.controls.mouse_sensitivity;
+        if self.input.action_released(ENABLE_MSAA) {
+            use gl_bindings as gl;
+            unsafe { gl::Enable(gl::MULTISAMPLE) }
         }
 
-        self.controls.prev_x = self.controls.mouse_x;
-        self.controls.prev_y = self.controls.mouse_y;
+        if self.input.action_pressed(DISABLE_MSAA) {
+            use gl_bindings as gl;
+            unsafe { gl::Disable(gl::MULTISAMPLE) }
+        }
 
-        self.camera.update(dx, dy, self.controls.scroll, self.dt);
+        if self.graphics_settings != self.applied_graphics_settings {
+            self.apply_graphics_settings();
+        }
 
-        self.controls.scroll = 0.0;
+        match self.camera_bookmarks.update(&mut self.camera, self.dt) {
+            // The orbit continues from wherever the path leaves the camera.
+            Some(target) => self.retarget_camera_controller(target),
+            None => self
+                .camera_controller
+                .update(&mut self.camera, &self.input, self.dt),
+        }
+        self.input.end_frame();
 
         Transition::None
     }
@@ -615,6 +1116,7 @@ impl Scene for PbsScene {
     fn pre_draw(&mut self, _: Context) {}
 
     fn draw(&mut self, context: Context) {
+        let window_size = context.window_size();
         let Context {
             window,
             asset_manager,
@@ -622,53 +1124,110 @@ impl Scene for PbsScene {
             framebuffer_cache,
             settings,
         } = context;
+        self.gpu_profiler.begin_frame();
+        self.per_draw_uniforms.begin_frame();
+        self.views.begin_frame();
+        self.update_views();
+        self.collect_draws();
+
+        let sky_model = match self.environment.skybox_type {
+            SkyboxType::Procedural => Some(SkyModel::Preetham),
+            SkyboxType::Atmosphere => Some(SkyModel::Atmosphere),
+            _ => None,
+        };
+
+        if let Some(sky_model) = sky_model {
+            let sun_illuminance = self.directional_light().shader_color();
+
+            let sky = &mut self.environment.sky;
+            sky.set_model(sky_model);
+            sky.set_sun_direction(Vec3::from(self.lighting.light_direction).normalize());
+            sky.set_sun_illuminance(sun_illuminance);
+
+            self.gpu_profiler.begin_scope("Sky Environment");
+            sky.update_environment();
+            self.gpu_profiler.end_scope();
+        }
+
+        self.gpu_profiler.begin_scope("HDR Environment");
+        let hdr_baked = self.environment.hdr.update();
+        self.gpu_profiler.end_scope();
+
+        // The light probes captured the previous bake.
+        if hdr_baked && matches!(self.environment.skybox_type, SkyboxType::Hdr) {
+            self.light_probes_dirty = true;
+        }
+
+        self.fill_vertex_per_frame_uniforms();
+
+        self.gpu_profiler.begin_scope("Shadows");
+        self.light_pass();
+        self.gpu_profiler.end_scope();
+
+        if self.light_probes_dirty {
+            self.gpu_profiler.begin_scope("Light Probe Bake");
+            self.bake_light_probes();
+            self.gpu_profiler.end_scope();
+            self.light_probes_dirty = false;
+        }
+
+        self.gpu_profiler.begin_scope("SSAO");
+        self.ssao_pass();
+        self.gpu_profiler.end_scope();
+
+        self.gpu_profiler.begin_scope("Geometry");
+        self.pipeline_statistics.begin();
         self.geometry_pass();
-        self.skybox_pass();
+        self.pipeline_statistics.end();
+        self.gpu_profiler.end_scope();
+
+        if self.debug_view.draws_sky() {
+            self.gpu_profiler.begin_scope("Skybox");
+            self.skybox_pass();
+            self.gpu_profiler.end_scope();
+        }
+
+        self.per_draw_uniforms.end_frame();
+        self.views.end_frame();
 
         if let Some(tone_mapper) = self.post_stack.get_mut::<ToneMapper>() {
             tone_mapper.set_exposure(self.camera.exposure())
         }
 
+        self.gpu_profiler.begin_scope("Post Processing");
         self.post_stack.apply(
             &self.resolve_framebuffer,
             Context::new(window, asset_manager, timer, framebuffer_cache, settings),
         );
+        self.gpu_profiler.end_scope();
+
+        if self.show_crosshair {
+            self.crosshair(window_size);
+        }
+        self.sprite_batch
+            .render(Vec2::new(window_size.x as f32, window_size.y as f32));
+
+        self.gpu_profiler.end_frame();
+
+        self.frame_capture.capture(window_size)
     }
 
     fn gui(&mut self, ui: &Ui) {
-        imgui::Window::new(im_str!("Inspector"))
-            .size([358.0, 720.0], Condition::Appearing)
-            .position([2.0, 0.0], Condition::Always)
-            .mouse_inputs(true)
-            .resizable(true)
-            .movable(false)
+        // Movable windows, their layout is saved in the config directory. The initial positions
+        // only apply until then.
+        let display_size = ui.io().display_size;
+
+        imgui::Window::new(im_str!("Scene"))
+            .size([358.0, 520.0], Condition::FirstUseEver)
+            .position([2.0, 0.0], Condition::FirstUseEver)
             .build(ui, || {
-                ui.dummy([358.0, 0.0]);
-
-                imgui::ComboBox::new(im_str!("Render Mode"))
-                    .build_simple_string(
-                        ui,
-                        &mut self.render_mode,
-                        &[
-                            im_str!("Lit"),
-                            im_str!("Albedo"),
-                            im_str!("Metallic"),
-                            im_str!("Roughness"),
-                            im_str!("Normals"),
-                            im_str!("Tangents"),
-                            im_str!("UV"),
-                            im_str!("NdotV"),
-                            im_str!("AO"),
-                            im_str!("Specular AO"),
-                            im_str!("Horizon Specular AO"),
-                            im_str!("Diffuse Ambient"),
-                            im_str!("Specular Ambient")
-                        ]);
+                DebugView::combo(ui, &mut self.debug_view);
 
                 ui.spacing();
 
-                // Material
-                self.material.gui(ui);
+                if ui.button(im_str!("Save Scene"), [0.0, 0.0]) {
+                    self.save_scene();
+                }
 
                 ui.spacing();
 
@@ -708,10 +1267,24 @@ impl Scene for PbsScene {
                                         .alpha(false)
                                         .build(&ui);
                                     imgui::Slider::new(
-                                        im_str!("Light Intensity"))
+                                        im_str!("Illuminance (lux)"))
                                         .range(RangeInclusive::new(0.01, 300.0))
                                         .display_format(im_str!("%.1f"))
                                         .build(&ui, &mut self.lighting.light_intensity);
+
+                                    let mut use_temperature =
+                                        self.lighting.light_temperature.is_some();
+                                    if ui.checkbox(im_str!("Temperature"), &mut use_temperature) {
+                                        self.lighting.light_temperature =
+                                            if use_temperature { Some(6500.0) } else { None };
+                                    }
+
+                                    if let Some(temperature) = &mut self.lighting.light_temperature {
+                                        imgui::Slider::new(im_str!("Kelvin"))
+                                            .range(RangeInclusive::new(1000.0, 12000.0))
+                                            .display_format(im_str!("%.0f"))
+                                            .build(&ui, temperature);
+                                    }
                                 });
 
                             imgui::TreeNode::new(im_str!("BRDF"))
@@ -777,6 +1350,15 @@ impl Scene for PbsScene {
                         .build(ui, || {
                             ui.checkbox(im_str!("Specular AO"), &mut self.lighting.specular_ao);
 
+                            self.ssao.gui(ui);
+                            self.local_shadows.gui(ui);
+                            self.light_shadows_gui(ui);
+                            self.light_probes.gui(ui);
+                            if ui.button(im_str!("Bake Light Probes"), [0.0, 0.0]) {
+                                self.light_probes_dirty = true;
+                            }
+                            self.fog.gui(ui);
+
                             imgui::ComboBox::new(im_str!("Environment")).build_simple_string(
                                 ui,
                                 &mut self.environment.active_environment,
@@ -794,27 +1376,137 @@ impl Scene for PbsScene {
                                     im_str!("Original"),
                                     im_str!("Radiance"),
                                     im_str!("Irradiance"),
+                                    im_str!("Procedural"),
+                                    im_str!("Atmosphere"),
+                                    im_str!("HDR"),
                                 ],
                             );
+
+                            match self.environment.skybox_type {
+                                SkyboxType::Procedural | SkyboxType::Atmosphere => {
+                                    self.environment.sky.gui(ui)
+                                }
+                                _ => {}
+                            }
                         });
                 }
 
+                // Debug
+                if imgui::CollapsingHeader::new(im_str!("Debug"))
+                    .default_open(false)
+                    .open_on_arrow(true)
+                    .open_on_double_click(true)
+                    .build(ui)
+                {
+                    ui.spacing();
+                    self.normal_visualizer.gui(ui);
+                    self.debug_draw.gui(ui);
+                    ui.checkbox(im_str!("Crosshair"), &mut self.show_crosshair);
+                    self.light_culling_debug.gui(ui);
+                    self.gpu_capabilities.gui(ui);
+                    self.frame_capture.gui(ui);
+                    ui.checkbox(im_str!("Split Screen"), &mut self.split_screen);
+                    if self.split_screen {
+                        self.views.gui(ui);
+                    }
+                }
+
                 // Camera
                 self.camera.gui(ui);
+                self.camera_controller.gui(ui);
 
                 // Post processing
                 self.post_stack.gui(ui);
+            });
+
+        imgui::Window::new(im_str!("Material"))
+            .size([358.0, 200.0], Condition::FirstUseEver)
+            .position([2.0, 520.0], Condition::FirstUseEver)
+            .build(ui, || self.material.borrow_mut().gui(ui));
+
+        imgui::Window::new(im_str!("Render Settings"))
+            .size([280.0, 180.0], Condition::FirstUseEver)
+            .position([362.0, 0.0], Condition::FirstUseEver)
+            .build(ui, || self.graphics_settings.gui(ui));
+
+        imgui::Window::new(im_str!("Environment"))
+            .size([280.0, 150.0], Condition::FirstUseEver)
+            .position([362.0, 180.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let path = self.environment.hdr.path().map(Path::to_path_buf);
+                self.environment.hdr.gui(ui);
+
+                // Shows a newly loaded environment right away.
+                if self.environment.hdr.path() != path.as_deref() {
+                    self.environment.skybox_type = SkyboxType::Hdr;
+                }
+            });
+
+        imgui::Window::new(im_str!("Camera Bookmarks"))
+            .size([280.0, 300.0], Condition::FirstUseEver)
+            .position([362.0, 330.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let target = *self.camera_controller.target();
+                if let Some(target) = self.camera_bookmarks.gui(ui, &mut self.camera, &target) {
+                    self.retarget_camera_controller(target);
+                }
+
+                ui.separator();
+
+                if ui.button(im_str!("Save"), [0.0, 0.0]) {
+                    if let Err(e) = self.camera_bookmarks.save(CAMERA_BOOKMARKS_PATH) {
+                        println!("WARNING: {}", e);
+                    }
+                }
+            });
 
-                ui.dummy([358.0, 0.0]);
-                self.controls.cursor_over_ui = ui.is_window_focused() || ui.is_window_hovered();
+        imgui::Window::new(im_str!("Stats"))
+            .size([240.0, 160.0], Condition::FirstUseEver)
+            .position([display_size[0] - 242.0, 0.0], Condition::FirstUseEver)
+            .build(ui, || {
+                ui.text(format!("Frame: {:.2} ms", self.dt * 1000.0));
+                if self.gpu_profiler.is_enabled() {
+                    ui.text(format!(
+                        "GPU: {:.2} ms",
+                        self.gpu_profiler.frame_milliseconds()
+                    ));
+                }
+                ui.separator();
+                FrameStats::last().gui(ui);
             });
 
-        self.controls.cursor_over_ui = (self.controls.cursor_over_ui
-            || ui.is_any_item_hovered()
-            || ui.is_any_item_focused()
-            || ui.is_any_item_active())
-            && !ui.is_window_collapsed();
+        imgui::Window::new(im_str!("Profiler"))
+            .size([240.0, 360.0], Condition::FirstUseEver)
+            .position([display_size[0] - 242.0, 160.0], Condition::FirstUseEver)
+            .build(ui, || {
+                self.gpu_profiler.gui(ui);
+                self.pipeline_statistics.gui(ui);
+
+                // Fragment shader invocations per pixel of the geometry pass, 1 without overdraw.
+                if self.pipeline_statistics.is_enabled() {
+                    let viewport_size = self.camera.viewport_size();
+                    let pixels = (viewport_size.x * viewport_size.y).max(1) as f32;
+                    ui.text(format!(
+                        "Overdraw: {:.2}",
+                        self.pipeline_statistics
+                            .result()
+                            .fragment_shader_invocations as f32
+                            / pixels
+                    ));
+                }
+            });
+
+        self.input.set_mouse_blocked(ui.io().want_capture_mouse);
+        self.input.set_keyboard_blocked(ui.io().want_text_input);
     }
 
     fn post_draw(&mut self, _: Context) {}
 }
+
+fn set_effect_enabled<T: PostprocessingEffect>(effect: &mut T, enabled: bool) {
+    if enabled {
+        effect.enable()
+    } else {
+        effect.disable()
+    }
+}
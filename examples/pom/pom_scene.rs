@@ -1,4 +1,4 @@
-use std::{ops::RangeInclusive, rc::Rc};
+use std::ops::RangeInclusive;
 
 use engine::core::math::{inverse, transpose};
 use engine::math::Vec2;
@@ -7,15 +7,25 @@ use engine::rendering::postprocess::tone_mapper::ToneMapper;
 use engine::rendering::sampler::Anisotropy;
 use engine::{
     application::clear_default_framebuffer,
-    camera::Camera,
+    asset::Handle,
+    camera::{
+        controller::{self, CameraController, OrbitController},
+        Camera,
+    },
     color::srgb_to_linear3f,
     imgui::*,
     math::{
-        matrix::{perspective, Mat4},
+        matrix::Mat4,
         vector::{UVec2, Vec3, Vec4},
     },
+    platform::{
+        action::{ActionMap, Binding},
+        input::{Button, Input},
+    },
     rendering::{
         framebuffer::{AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo},
+        light::{DirectionalLight, LightBuffer},
+        ltc::LtcLuts,
         material::{Material, PbsMetallicRoughnessMaterial},
         mesh::{FullscreenMesh, Mesh, MeshUtilities},
         postprocess::{
@@ -25,17 +35,22 @@ use engine::{
         program_pipeline::ProgramPipeline,
         sampler::{MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
         shader::{Shader, ShaderStage},
+        sky::SKYBOX_MATRICES_BINDING,
         state::{DepthFunction, FaceCulling, FrontFace, StateManager},
         texture::{SizedTextureFormat, TextureCube},
+        viewport::FRAGMENT_PER_FRAME_BINDING,
         Draw,
     },
     scene::Scene,
     scene::Transition,
     Context, Msaa,
 };
-use glutin::event::{
-    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
-};
+use glutin::event::{DeviceEvent, VirtualKeyCode, WindowEvent};
+
+const BINDINGS_PATH: &str = "examples/pom/bindings.ron";
+const QUIT: &str = "quit";
+const ENABLE_MSAA: &str = "enable_msaa";
+const DISABLE_MSAA: &str = "disable_msaa";
 
 struct EnvironmentMaps {
     skybox: TextureCube,
@@ -53,7 +68,7 @@ enum SkyboxType {
 struct Environment {
     maps: [EnvironmentMaps; 2],
     skybox_program_pipeline: ProgramPipeline,
-    skybox_mesh: Rc<Mesh>,
+    skybox_mesh: Handle<Mesh>,
     active_environment: usize,
     skybox_type: SkyboxType,
 }
@@ -68,22 +83,10 @@ struct Lighting {
 }
 
 struct Model {
-    pub mesh: Rc<Mesh>,
+    pub mesh: Handle<Mesh>,
     pub transform: Mat4,
 }
 
-#[derive(Default)]
-struct Controls {
-    left_mouse_button_pressed: bool,
-    mouse_x: f32,
-    mouse_y: f32,
-    mouse_sensitivity: f32,
-    scroll: f32,
-    prev_x: f32,
-    prev_y: f32,
-    cursor_over_ui: bool,
-}
-
 #[repr(C)]
 struct VertexPerFrameUniforms {
     view_projection_matrix: Mat4,
@@ -98,11 +101,15 @@ struct VertexPerDrawUniforms {
 
 #[repr(C)]
 struct FragmentPerFrameUniforms {
-    light_direction: Vec4,
-    light_color: Vec4,
     ss_variance_and_threshold: Vec2,
     geometric_specular_aa: i32,
+    // The layout of the lighting shared with pbs.frag. The scene has no specular occlusion,
+    // debug views or SSAO.
+    specular_ao: i32,
     disney_ggx_hotness: i32,
+    render_mode: i32,
+    screen_space_ao: i32,
+    _pad: f32,
 }
 
 #[repr(C)]
@@ -112,36 +119,64 @@ struct SkyboxPerFrameUniforms {
 
 pub struct PomScene {
     camera: Camera,
+    camera_controller: OrbitController,
+    input: Input,
     model: Model,
     material: PbsMetallicRoughnessMaterial,
     environment: Environment,
     framebuffer: Framebuffer,
     resolve_framebuffer: Framebuffer,
     sampler_linear: Sampler,
-    projection_matrix: Mat4,
     post_stack: PostprocessingStack,
-    controls: Controls,
     lighting: Lighting,
     vertex_per_frame_ubo: Buffer,
     vertex_per_draw_ubo: Buffer,
     fragment_per_frame_ubo: Buffer,
     skybox_per_frame_ubo: Buffer,
+    light_buffer: LightBuffer,
+    ltc_luts: LtcLuts,
     dt: f32,
 }
 
 impl PomScene {
+    // The bindings of the file over the defaults of the demo and the camera controller.
+    fn create_input() -> Input {
+        let mut defaults = ActionMap::new();
+        defaults.bind(QUIT, Binding::Key(VirtualKeyCode::Escape));
+        defaults.bind(QUIT, Binding::GamepadButton(Button::Start));
+        defaults.bind(ENABLE_MSAA, Binding::Key(VirtualKeyCode::A));
+        defaults.bind(DISABLE_MSAA, Binding::Key(VirtualKeyCode::S));
+        controller::bind_default_actions(&mut defaults);
+
+        let mut input = Input::new();
+        let actions = input.actions_mut();
+        *actions = ActionMap::load(BINDINGS_PATH).unwrap_or_else(|e| {
+            println!("WARNING: {}", e);
+            ActionMap::new()
+        });
+        actions.merge(&defaults);
+
+        input
+    }
+
     pub fn new(context: Context) -> Self {
+        let window_size = context.window_size();
         let Context {
-            window,
             settings,
             asset_manager,
             ..
         } = context;
 
         let asset_path = settings.asset_path.as_path();
-        let camera = Camera::new(
+        let mut camera = Camera::perspective(60.0, 0.1, 500.0, window_size);
+        camera.look_at(
             Vec3::new(0.0, 0.0, -2.0),
             Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let camera_controller = OrbitController::new(
+            &camera,
+            Vec3::new(0.0, 0.0, 0.0),
             10.0,
             30.0,
             1.0,
@@ -160,9 +195,9 @@ impl PomScene {
             .build()
             .unwrap();
 
-        let mesh = Rc::new(MeshUtilities::generate_cube(1.0));
+        let mesh = Handle::new(MeshUtilities::generate_cube(1.0));
 
-        let skybox_mesh = Rc::clone(&mesh);
+        let skybox_mesh = mesh.clone();
 
         let albedo = asset_manager
             .load_texture_2d(
@@ -237,7 +272,7 @@ impl PomScene {
         ];
 
         let framebuffer = Framebuffer::new(
-            UVec2::new(window.inner_size().width, window.inner_size().height),
+            window_size,
             Msaa::X4,
             vec![
                 FramebufferAttachmentCreateInfo::new(
@@ -257,7 +292,7 @@ impl PomScene {
         .unwrap_or_else(|error| panic!("Framebuffer creation error: {}", error));
 
         let resolve_framebuffer = Framebuffer::new(
-            UVec2::new(window.inner_size().width, window.inner_size().height),
+            window_size,
             Msaa::None,
             vec![
                 FramebufferAttachmentCreateInfo::new(
@@ -277,8 +312,15 @@ impl PomScene {
         .unwrap_or_else(|error| panic!("Framebuffer creation error: {}", error));
 
         let post_stack = PostprocessingStackBuilder::new()
-            .with_effect(BloomBuilder::new(asset_path).build())
-            .with_effect(ToneMapper::new())
+            .with_effect(
+                BloomBuilder::new()
+                    .build()
+                    .unwrap_or_else(|error| panic!("Bloom creation error: {}", error)),
+            )
+            .with_effect(
+                ToneMapper::new()
+                    .unwrap_or_else(|error| panic!("Tone mapper creation error: {}", error)),
+            )
             .build();
 
         let sampler_linear = Sampler::new(
@@ -291,21 +333,14 @@ impl PomScene {
             Anisotropy::X4,
         );
 
-        let projection = perspective(
-            window.inner_size().width,
-            window.inner_size().height,
-            60,
-            0.1,
-            500.0,
-        );
-
         let material = PbsMetallicRoughnessMaterial::new(
             asset_path,
             albedo,
             metallic_roughness_ao,
             normals,
             Some(displacement),
-        );
+        )
+        .unwrap_or_else(|error| panic!("Material creation error: {}", error));
 
         let mut vertex_per_frame_ubo = Buffer::new(
             "Vertex Per Frame UBO",
@@ -331,7 +366,7 @@ impl PomScene {
             BufferTarget::Uniform,
             BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
         );
-        fragment_per_frame_ubo.bind(2);
+        fragment_per_frame_ubo.bind(FRAGMENT_PER_FRAME_BINDING);
         fragment_per_frame_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
 
         let mut skybox_per_frame_ubo = Buffer::new(
@@ -340,11 +375,13 @@ impl PomScene {
             BufferTarget::Uniform,
             BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
         );
-        skybox_per_frame_ubo.bind(5);
+        skybox_per_frame_ubo.bind(SKYBOX_MATRICES_BINDING);
         skybox_per_frame_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
 
         PomScene {
             camera,
+            camera_controller,
+            input: Self::create_input(),
             model: Model {
                 mesh,
                 transform: Mat4::identity(),
@@ -360,12 +397,7 @@ impl PomScene {
             framebuffer,
             resolve_framebuffer,
             sampler_linear,
-            projection_matrix: projection,
             post_stack,
-            controls: Controls {
-                mouse_sensitivity: 2.0,
-                ..Default::default()
-            },
             lighting: Lighting {
                 light_direction: [0.4, 0.0, -1.0],
                 light_color: [1.0, 1.0, 1.0],
@@ -378,16 +410,29 @@ impl PomScene {
             vertex_per_draw_ubo,
             fragment_per_frame_ubo,
             skybox_per_frame_ubo,
+            light_buffer: LightBuffer::new(1),
+            ltc_luts: LtcLuts::new(),
             dt: 0.0,
         }
     }
 
+    fn update_lights(&mut self) {
+        self.light_buffer.clear();
+        self.light_buffer.push_directional(&DirectionalLight {
+            direction: self.lighting.light_direction.into(),
+            color: srgb_to_linear3f(&self.lighting.light_color.into()),
+            temperature: None,
+            illuminance: self.lighting.light_intensity,
+        });
+        self.light_buffer.upload();
+    }
+
     fn geometry_pass(&self) {
         self.framebuffer.bind();
         self.framebuffer.clear(&Vec4::new(0.0, 0.0, 0.0, 1.0));
 
         let vertex_per_frame_uniforms = VertexPerFrameUniforms {
-            view_projection_matrix: self.projection_matrix * self.camera.transform(),
+            view_projection_matrix: self.camera.view_projection_matrix(),
             eye_position: Vec4::new(
                 self.camera.position().x,
                 self.camera.position().y,
@@ -411,25 +456,24 @@ impl PomScene {
 
         let program_pipeline = self.material.program_pipeline();
 
-        let mut light_color: Vec3 = srgb_to_linear3f(&self.lighting.light_color.into());
-        light_color *= self.lighting.light_intensity;
-
         let fragment_per_frame_uniforms = FragmentPerFrameUniforms {
-            light_direction: Vec4::new(
-                self.lighting.light_direction[0],
-                self.lighting.light_direction[1],
-                self.lighting.light_direction[2],
-                1.0,
-            ),
-            light_color: Vec4::new(light_color.x, light_color.y, light_color.z, 0.0),
             ss_variance_and_threshold: self.lighting.ss_variance_and_threshold.clone_owned(),
             geometric_specular_aa: self.lighting.geometric_specular_aa as i32,
+            specular_ao: 0,
             disney_ggx_hotness: self.lighting.disney_ggx_hotness as i32,
+            render_mode: 0,
+            screen_space_ao: 0,
+            _pad: 0.0,
         };
 
         self.fragment_per_frame_ubo
             .fill_mapped(0, &fragment_per_frame_uniforms);
 
+        self.light_buffer.bind();
+
+        const LTC_LUTS_BINDING_INDEX: u32 = 10;
+        self.ltc_luts.bind(program_pipeline, LTC_LUTS_BINDING_INDEX);
+
         const IRRADIANCE_MAP_BINDING_INDEX: u32 = 4;
         const RADIANCE_MAP_BINDING_INDEX: u32 = 5;
         program_pipeline
@@ -444,7 +488,9 @@ impl PomScene {
                 &self.sampler_linear,
             );
 
-        self.model.mesh.draw();
+        self.model
+            .mesh
+            .draw_with_primitive_mode(self.material.primitive_mode());
 
         self.framebuffer.unbind(false);
 
@@ -480,7 +526,7 @@ impl PomScene {
         view.m44 = 1.0;
 
         let skybox_per_frame_uniforms = SkyboxPerFrameUniforms {
-            view_projection_matrix: self.projection_matrix * view,
+            view_projection_matrix: self.camera.projection_matrix() * view,
         };
 
         self.skybox_per_frame_ubo
@@ -512,101 +558,60 @@ impl Scene for PomScene {
     fn resume(&mut self, _: Context) {}
 
     fn handle_event(&mut self, _: Context, event: WindowEvent) -> Transition {
-        match event {
-            WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                ..
-            } => self.controls.left_mouse_button_pressed = true,
-            WindowEvent::MouseInput {
-                state: ElementState::Released,
-                button: MouseButton::Left,
-                ..
-            } => {
-                self.controls.left_mouse_button_pressed = false;
-
-                self.controls.mouse_x = 0.0;
-                self.controls.mouse_y = 0.0;
-                self.controls.prev_x = 0.0;
-                self.controls.prev_y = 0.0;
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                if self.controls.left_mouse_button_pressed && !self.controls.cursor_over_ui {
-                    self.controls.mouse_x = position.x as f32;
-                    self.controls.mouse_y = position.y as f32;
-                }
-            }
-            WindowEvent::MouseWheel {
-                delta: MouseScrollDelta::LineDelta(_, y),
-                ..
-            } => {
-                if !self.controls.cursor_over_ui {
-                    self.controls.scroll = y;
-                }
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    },
-                ..
-            } => return Transition::Quit,
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::A),
-                        ..
-                    },
-                ..
-            } => {
-                use gl_bindings as gl;
-                unsafe { gl::Enable(gl::MULTISAMPLE) }
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::S),
-                        ..
-                    },
-                ..
-            } => {
-                use gl_bindings as gl;
-                unsafe { gl::Disable(gl::MULTISAMPLE) }
-            }
-            WindowEvent::Resized(size) => {
-                let x = size.width;
-                let y = size.height;
-                //TODO: Get this from the camera
-                self.projection_matrix = perspective(x, y, 60, 0.1, 500.0);
-                StateManager::set_viewport(0, 0, x as i32, y as i32)
-            }
-            _ => {}
+        self.input.handle_event(&event);
+
+        if let WindowEvent::Resized(size) = event {
+            let x = size.width;
+            let y = size.height;
+            self.camera.set_viewport_size(UVec2::new(x, y));
+            StateManager::set_viewport(0, 0, x as i32, y as i32)
         }
+
         Transition::None
     }
 
+    fn handle_device_event(&mut self, _: Context, event: &DeviceEvent) {
+        self.input.handle_device_event(event)
+    }
+
     fn update(&mut self, context: Context) -> Transition {
-        let Context { timer, .. } = context;
+        let Context { window, timer, .. } = context;
+
+        self.dt = timer.delta_time();
 
-        self.dt = timer.get_delta();
+        self.material.update();
 
-        let mut dx = 0.0;
-        let mut dy = 0.0;
+        self.input.poll_gamepads();
 
-        if self.controls.prev_x != 0.0 || self.controls.prev_y != 0.0 {
-            dx = (self.controls.mouse_x - self.controls.prev_x) * self.controls.mouse_sensitivity;
-            dy = (self.controls.mouse_y - self.controls.prev_y) * self.controls.mouse_sensitivity;
+        if let Some(window) = window {
+            if self.input.action_pressed(controller::CAPTURE_CURSOR) {
+                let captured = !self.input.cursor_captured();
+
+                if let Err(e) = self.input.set_cursor_captured(window, captured) {
+                    println!("WARNING: {}", e);
+                }
+            }
+
+            self.input.update_cursor(window);
         }
 
-        self.controls.prev_x = self.controls.mouse_x;
-        self.controls.prev_y = self.controls.mouse_y;
+        if self.input.action_released(QUIT) {
+            return Transition::Quit;
+        }
 
-        self.camera.update(dx, dy, self.controls.scroll, self.dt);
+        if self.input.action_released(ENABLE_MSAA) {
+            use gl_bindings as gl;
+            unsafe { gl::Enable(gl::MULTISAMPLE) }
+        }
+
+        if self.input.action_pressed(DISABLE_MSAA) {
+            use gl_bindings as gl;
+            unsafe { gl::Disable(gl::MULTISAMPLE) }
+        }
 
-        self.controls.scroll = 0.0;
+        self.camera_controller
+            .update(&mut self.camera, &self.input, self.dt);
+        self.input.end_frame();
 
         Transition::None
     }
@@ -622,6 +627,7 @@ impl Scene for PomScene {
             settings,
         } = context;
 
+        self.update_lights();
         self.geometry_pass();
         self.skybox_pass();
 
@@ -686,7 +692,7 @@ impl Scene for PomScene {
                                         .alpha(false)
                                         .build(&ui);
 
-                                    imgui::Slider::new(im_str!("Light Intensity"))
+                                    imgui::Slider::new(im_str!("Illuminance (lux)"))
                                         .range(RangeInclusive::new(0.01, 300.0))
                                         .display_format(im_str!("%.1f"))
                                         .build(&ui, &mut self.lighting.light_intensity);
@@ -776,19 +782,23 @@ impl Scene for PomScene {
 
                 // Camera
                 self.camera.gui(ui);
+                self.camera_controller.gui(ui);
 
                 // Post processing
                 self.post_stack.gui(ui);
 
                 ui.dummy([358.0, 0.0]);
-                self.controls.cursor_over_ui = ui.is_window_focused() || ui.is_window_hovered();
+                self.input
+                    .set_mouse_blocked(ui.is_window_focused() || ui.is_window_hovered());
             });
 
-        self.controls.cursor_over_ui = (self.controls.cursor_over_ui
+        let mouse_blocked = (self.input.mouse_blocked()
             || ui.is_any_item_hovered()
             || ui.is_any_item_focused()
             || ui.is_any_item_active())
             && !ui.is_window_collapsed();
+        self.input.set_mouse_blocked(mouse_blocked);
+        self.input.set_keyboard_blocked(ui.io().want_text_input);
     }
 
     fn post_draw(&mut self, _: Context) {}
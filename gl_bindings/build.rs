@@ -32,10 +32,11 @@ fn main() {
             "GL_ARB_gl_spirv",
             "GLX_ARB_create_context_no_error",
             "WGL_ARB_create_context_no_error",
-            "KHR_parallel_shader_compile",
+            "GL_KHR_parallel_shader_compile",
             "GL_ARB_polygon_offset_clamp",
             "GL_ARB_spirv_extensions",
             "GL_ARB_texture_filter_anisotropic",
+            "GL_ARB_pipeline_statistics_query",
         ],
     )
     .write_bindings(gl_generator::GlobalGenerator, &mut file)
@@ -0,0 +1,33 @@
+// Packs an asset directory into a single file that AssetManager::mount_pack can serve from.
+//
+// Usage: pack_assets <asset directory> <output pack> [--compress]
+use engine::asset::pack::{AssetPackWriter, Compression};
+use std::{env, process};
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let compress = args.iter().any(|arg| arg == "--compress");
+    let paths = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .collect::<Vec<_>>();
+
+    if paths.len() != 2 {
+        eprintln!("Usage: pack_assets <asset directory> <output pack> [--compress]");
+        process::exit(1);
+    }
+
+    let mut writer = AssetPackWriter::new().with_compression(if compress {
+        Compression::Zstd
+    } else {
+        Compression::None
+    });
+
+    if let Err(e) = writer
+        .add_directory(paths[0])
+        .and_then(|_| writer.write(paths[1]))
+    {
+        eprintln!("Failed to pack {}: {}", paths[0], e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod blend_tree;
+pub mod clip;
+pub mod curve;
+pub mod state_machine;
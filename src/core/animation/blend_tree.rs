@@ -0,0 +1,214 @@
+use crate::core::animation::clip::{ClipPlayer, Pose};
+use std::collections::{HashMap, HashSet};
+
+// The values the blend trees and transitions of an animation read, e.g. the speed of a
+// character, set by the game every frame. Unset parameters are 0.
+#[derive(Debug, Clone, Default)]
+pub struct Parameters {
+    values: HashMap<String, f32>,
+    triggers: HashSet<String>,
+}
+
+impl Parameters {
+    pub fn set(&mut self, name: &str, value: f32) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> f32 {
+        self.values.get(name).copied().unwrap_or(0.0)
+    }
+
+    // Held until a transition consumes it, e.g. to jump once.
+    pub fn trigger(&mut self, name: &str) {
+        self.triggers.insert(name.to_string());
+    }
+
+    pub fn triggered(&self, name: &str) -> bool {
+        self.triggers.contains(name)
+    }
+
+    pub(crate) fn consume(&mut self, name: &str) -> bool {
+        self.triggers.remove(name)
+    }
+}
+
+// How the clips of a state are mixed into its pose.
+#[derive(Debug, Clone)]
+pub enum BlendTree {
+    Clip(ClipPlayer),
+    // Blends the two children around the value of the parameter, e.g. idle, walk and run by
+    // the speed. Children are sorted by their threshold and play in sync, so the feet of a walk
+    // and a run land together.
+    Blend1D {
+        parameter: String,
+        children: Vec<(f32, BlendTree)>,
+    },
+    // Adds the difference of the additive tree to the reference pose on top of the base, e.g.
+    // a lean over a run.
+    Additive {
+        base: Box<BlendTree>,
+        additive: Box<BlendTree>,
+        reference: Pose,
+        weight: f32,
+    },
+}
+
+impl BlendTree {
+    pub fn clip(player: ClipPlayer) -> Self {
+        BlendTree::Clip(player)
+    }
+
+    pub fn blend_1d(parameter: &str, mut children: Vec<(f32, BlendTree)>) -> Self {
+        children.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        BlendTree::Blend1D {
+            parameter: parameter.to_string(),
+            children,
+        }
+    }
+
+    pub fn additive(base: BlendTree, additive: BlendTree, reference: Pose, weight: f32) -> Self {
+        BlendTree::Additive {
+            base: Box::new(base),
+            additive: Box::new(additive),
+            reference,
+            weight,
+        }
+    }
+
+    // Seconds one pass over the tree takes, the durations of blended children weighted.
+    pub fn duration(&self, parameters: &Parameters) -> f32 {
+        match self {
+            BlendTree::Clip(player) => player.duration(),
+            BlendTree::Blend1D {
+                parameter,
+                children,
+            } => Self::weights(children, parameters.get(parameter))
+                .map(|(i, weight)| children[i].1.duration(parameters) * weight)
+                .sum(),
+            BlendTree::Additive { base, .. } => base.duration(parameters),
+        }
+    }
+
+    // Whether every clip that does not loop reached its end.
+    pub fn finished(&self) -> bool {
+        match self {
+            BlendTree::Clip(player) => player.finished(),
+            BlendTree::Blend1D { children, .. } => {
+                children.iter().all(|(_, child)| child.finished())
+            }
+            BlendTree::Additive { base, .. } => base.finished(),
+        }
+    }
+
+    // Starts every clip over, when the state is entered.
+    pub fn reset(&mut self) {
+        self.set_normalized_time(0.0)
+    }
+
+    pub fn advance(&mut self, delta_time: f32, parameters: &Parameters) {
+        match self {
+            BlendTree::Clip(player) => player.advance(delta_time),
+            BlendTree::Blend1D { .. } => {
+                // Advances every child by the same fraction of its length.
+                let duration = self.duration(parameters);
+
+                if let BlendTree::Blend1D { children, .. } = self {
+                    for (_, child) in children {
+                        let child_duration = child.duration(parameters);
+                        let scale = if duration > 0.0 {
+                            child_duration / duration
+                        } else {
+                            1.0
+                        };
+
+                        child.advance(delta_time * scale, parameters)
+                    }
+                }
+            }
+            BlendTree::Additive { base, additive, .. } => {
+                base.advance(delta_time, parameters);
+                additive.advance(delta_time, parameters)
+            }
+        }
+    }
+
+    // Writes the pose of the tree, starting from the bind pose for the joints no clip animates.
+    pub fn evaluate(&self, parameters: &Parameters, bind_pose: &Pose, pose: &mut Pose) {
+        match self {
+            BlendTree::Clip(player) => {
+                pose.copy_from(bind_pose);
+                player.sample(pose)
+            }
+            BlendTree::Blend1D {
+                parameter,
+                children,
+            } => {
+                let mut weights = Self::weights(children, parameters.get(parameter));
+
+                match weights.next() {
+                    Some((first, _)) => children[first].1.evaluate(parameters, bind_pose, pose),
+                    None => {
+                        pose.copy_from(bind_pose);
+                        return;
+                    }
+                }
+
+                if let Some((second, weight)) = weights.next() {
+                    let mut other = bind_pose.clone();
+                    children[second]
+                        .1
+                        .evaluate(parameters, bind_pose, &mut other);
+                    pose.blend(&other, weight)
+                }
+            }
+            BlendTree::Additive {
+                base,
+                additive,
+                reference,
+                weight,
+            } => {
+                base.evaluate(parameters, bind_pose, pose);
+
+                let mut delta = bind_pose.clone();
+                additive.evaluate(parameters, bind_pose, &mut delta);
+                pose.add(&delta.relative_to(reference), *weight, None)
+            }
+        }
+    }
+
+    fn set_normalized_time(&mut self, normalized_time: f32) {
+        match self {
+            BlendTree::Clip(player) => player.set_normalized_time(normalized_time),
+            BlendTree::Blend1D { children, .. } => children
+                .iter_mut()
+                .for_each(|(_, child)| child.set_normalized_time(normalized_time)),
+            BlendTree::Additive { base, additive, .. } => {
+                base.set_normalized_time(normalized_time);
+                additive.set_normalized_time(normalized_time)
+            }
+        }
+    }
+
+    // The children around the value with their weights, the first one first. The second weight
+    // is how far to blend from the first child to the second one.
+    fn weights(children: &[(f32, BlendTree)], value: f32) -> impl Iterator<Item = (usize, f32)> {
+        let next = children
+            .iter()
+            .position(|(threshold, _)| *threshold > value);
+
+        let pair = match next {
+            None if children.is_empty() => vec![],
+            None => vec![(children.len() - 1, 1.0)],
+            Some(0) => vec![(0, 1.0)],
+            Some(next) => {
+                let (start, end) = (children[next - 1].0, children[next].0);
+                let t = (value - start) / (end - start);
+
+                vec![(next - 1, 1.0 - t), (next, t)]
+            }
+        };
+
+        pair.into_iter()
+    }
+}
@@ -0,0 +1,420 @@
+use crate::core::math::{quaternion, vec3_lerp, Mat4, Quat, Vec3};
+use nalgebra_glm as glm;
+use std::rc::Rc;
+
+// The local transform of a joint, relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl JointTransform {
+    pub fn lerp(a: &JointTransform, b: &JointTransform, t: f32) -> Self {
+        Self {
+            translation: vec3_lerp(&a.translation, &b.translation, t),
+            rotation: quaternion::slerp(&a.rotation, &b.rotation, t),
+            scale: vec3_lerp(&a.scale, &b.scale, t),
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        glm::translation(&self.translation)
+            * quaternion::to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+
+    // The difference to the reference, which add applies on top of another transform.
+    pub fn relative_to(&self, reference: &JointTransform) -> Self {
+        Self {
+            translation: self.translation - reference.translation,
+            rotation: glm::quat_normalize(
+                &(glm::quat_inverse(&reference.rotation) * self.rotation),
+            ),
+            scale: self.scale.component_div(&reference.scale),
+        }
+    }
+
+    // Applies the weighted difference of relative_to.
+    pub fn add(&mut self, delta: &JointTransform, weight: f32) {
+        self.translation += delta.translation * weight;
+        self.rotation = glm::quat_normalize(
+            &(self.rotation * quaternion::slerp(&Quat::identity(), &delta.rotation, weight)),
+        );
+        self.scale =
+            self.scale
+                .component_mul(&vec3_lerp(&Vec3::new(1.0, 1.0, 1.0), &delta.scale, weight));
+    }
+}
+
+// The local transforms of every joint of a skeleton.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    joints: Vec<JointTransform>,
+}
+
+impl Pose {
+    pub fn new(joints: Vec<JointTransform>) -> Self {
+        Self { joints }
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn joints(&self) -> &[JointTransform] {
+        &self.joints
+    }
+
+    pub fn joints_mut(&mut self) -> &mut [JointTransform] {
+        &mut self.joints
+    }
+
+    pub fn copy_from(&mut self, other: &Pose) {
+        self.joints.clone_from(&other.joints)
+    }
+
+    // Moves every joint towards the other pose, 0 keeps this pose and 1 takes the other one.
+    pub fn blend(&mut self, other: &Pose, weight: f32) {
+        for (joint, other) in self.joints.iter_mut().zip(&other.joints) {
+            *joint = JointTransform::lerp(joint, other, weight)
+        }
+    }
+
+    pub fn relative_to(&self, reference: &Pose) -> Pose {
+        Pose::new(
+            self.joints
+                .iter()
+                .zip(&reference.joints)
+                .map(|(joint, reference)| joint.relative_to(reference))
+                .collect(),
+        )
+    }
+
+    // Adds a pose made relative_to a reference, e.g. a breathing or leaning clip over the
+    // locomotion. The mask weighs the joints individually, None adds to every joint.
+    pub fn add(&mut self, delta: &Pose, weight: f32, mask: Option<&[f32]>) {
+        for (i, (joint, delta)) in self.joints.iter_mut().zip(&delta.joints).enumerate() {
+            let joint_weight = mask.map_or(1.0, |mask| mask.get(i).copied().unwrap_or(0.0));
+
+            if joint_weight > 0.0 {
+                joint.add(delta, weight * joint_weight)
+            }
+        }
+    }
+}
+
+// The hierarchy of the joints of a skin, in the order of its inverse bind matrices. Parents come
+// before their children.
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    parents: Vec<Option<usize>>,
+    bind_pose: Pose,
+}
+
+impl Skeleton {
+    pub fn new(parents: Vec<Option<usize>>, bind_pose: Pose) -> Result<Self, String> {
+        if parents.len() != bind_pose.joint_count() {
+            return Err(format!(
+                "The skeleton has {} joints but its bind pose {}",
+                parents.len(),
+                bind_pose.joint_count()
+            ));
+        }
+
+        if let Some(joint) = parents
+            .iter()
+            .enumerate()
+            .position(|(joint, parent)| parent.map_or(false, |parent| parent >= joint))
+        {
+            return Err(format!("Joint {} comes before its parent", joint));
+        }
+
+        Ok(Self { parents, bind_pose })
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn parent(&self, joint: usize) -> Option<usize> {
+        self.parents[joint]
+    }
+
+    pub fn bind_pose(&self) -> &Pose {
+        &self.bind_pose
+    }
+
+    // The transforms of the joints in the space of the mesh, for Skin::compute_palette.
+    pub fn joint_transforms(&self, pose: &Pose, transforms: &mut Vec<Mat4>) {
+        transforms.clear();
+
+        for (joint, local) in pose.joints.iter().enumerate() {
+            let transform = match self.parents[joint] {
+                Some(parent) => transforms[parent] * local.matrix(),
+                None => local.matrix(),
+            };
+
+            transforms.push(transform)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+// A value keyframes can hold.
+pub trait Keyframe: Copy {
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self;
+}
+
+impl Keyframe for Vec3 {
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self {
+        vec3_lerp(a, b, t)
+    }
+}
+
+impl Keyframe for Quat {
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self {
+        quaternion::slerp(a, b, t)
+    }
+}
+
+// Keyframes of one property, sorted by time in seconds. Holds the first and last values before
+// and after them.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    times: Vec<f32>,
+    values: Vec<T>,
+    interpolation: Interpolation,
+}
+
+impl<T: Keyframe> Track<T> {
+    pub fn new(
+        times: Vec<f32>,
+        values: Vec<T>,
+        interpolation: Interpolation,
+    ) -> Result<Self, String> {
+        if times.is_empty() || times.len() != values.len() {
+            return Err(format!(
+                "A track needs as many values as keyframes, got {} keyframes and {} values",
+                times.len(),
+                values.len()
+            ));
+        }
+
+        if times.windows(2).any(|pair| pair[1] < pair[0]) {
+            return Err("The keyframes of a track have to be sorted by time".to_string());
+        }
+
+        Ok(Self {
+            times,
+            values,
+            interpolation,
+        })
+    }
+
+    // The time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        *self.times.last().unwrap()
+    }
+
+    pub fn sample(&self, time: f32) -> T {
+        // The first keyframe after the time.
+        let next = self.times.partition_point(|&t| t <= time);
+
+        if next == 0 {
+            return self.values[0];
+        }
+
+        if next == self.times.len() {
+            return self.values[next - 1];
+        }
+
+        let (start, end) = (self.times[next - 1], self.times[next]);
+
+        match self.interpolation {
+            Interpolation::Step => self.values[next - 1],
+            Interpolation::Linear => T::interpolate(
+                &self.values[next - 1],
+                &self.values[next],
+                (time - start) / (end - start),
+            ),
+        }
+    }
+}
+
+// The animated properties of a joint. The others keep the pose they are sampled into.
+#[derive(Debug, Clone)]
+pub struct JointTracks {
+    pub joint: usize,
+    pub translation: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+}
+
+impl JointTracks {
+    fn duration(&self) -> f32 {
+        let translation = self.translation.as_ref().map_or(0.0, Track::duration);
+        let rotation = self.rotation.as_ref().map_or(0.0, Track::duration);
+        let scale = self.scale.as_ref().map_or(0.0, Track::duration);
+
+        translation.max(rotation).max(scale)
+    }
+
+    // Overwrites the animated properties of the transform.
+    pub fn sample(&self, time: f32, transform: &mut JointTransform) {
+        if let Some(translation) = &self.translation {
+            transform.translation = translation.sample(time)
+        }
+
+        if let Some(rotation) = &self.rotation {
+            transform.rotation = rotation.sample(time)
+        }
+
+        if let Some(scale) = &self.scale {
+            transform.scale = scale.sample(time)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    name: String,
+    duration: f32,
+    tracks: Vec<JointTracks>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, tracks: Vec<JointTracks>) -> Self {
+        let duration = tracks.iter().map(JointTracks::duration).fold(0.0, f32::max);
+
+        Self {
+            name: name.to_string(),
+            duration,
+            tracks,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // In seconds.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn tracks(&self) -> &[JointTracks] {
+        &self.tracks
+    }
+
+    // Overwrites the animated properties of the pose, e.g. one copied from the bind pose.
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for tracks in &self.tracks {
+            if let Some(joint) = pose.joints.get_mut(tracks.joint) {
+                tracks.sample(time, joint)
+            }
+        }
+    }
+}
+
+// Plays a clip, looping or holding its last frame.
+#[derive(Debug, Clone)]
+pub struct ClipPlayer {
+    clip: Rc<AnimationClip>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+}
+
+impl ClipPlayer {
+    pub fn new(clip: Rc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn clip(&self) -> &Rc<AnimationClip> {
+        &self.clip
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0).min(self.clip.duration)
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed
+    }
+
+    // From 0 at the start of the clip to 1 at its end.
+    pub fn normalized_time(&self) -> f32 {
+        if self.clip.duration > 0.0 {
+            self.time / self.clip.duration
+        } else {
+            1.0
+        }
+    }
+
+    pub fn set_normalized_time(&mut self, normalized_time: f32) {
+        self.set_time(normalized_time * self.clip.duration)
+    }
+
+    // Seconds one pass over the clip takes at the speed of the player.
+    pub fn duration(&self) -> f32 {
+        self.clip.duration / self.speed.abs().max(std::f32::EPSILON)
+    }
+
+    // A clip that does not loop holds its last frame once it is over.
+    pub fn finished(&self) -> bool {
+        !self.looping && self.time >= self.clip.duration
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        let duration = self.clip.duration;
+        let time = self.time + delta_time * self.speed;
+
+        self.time = if duration <= 0.0 {
+            0.0
+        } else if self.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.max(0.0).min(duration)
+        }
+    }
+
+    pub fn sample(&self, pose: &mut Pose) {
+        self.clip.sample(self.time, pose)
+    }
+}
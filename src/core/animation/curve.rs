@@ -0,0 +1,192 @@
+use crate::core::math::lerp_scalar;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CurveKey {
+    pub time: f32,
+    pub value: f32,
+}
+
+// A value over normalized time, e.g. the size of a particle over its lifetime. Keys are sorted by
+// time in [0, 1] and interpolated linearly, the first and last values hold before and after them.
+// There is always at least one key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    keys: Vec<CurveKey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: [f32; 3],
+}
+
+// A color over normalized time, e.g. the fog color over the day. Stops are sorted by position in
+// [0, 1] and interpolated linearly in the space they are stored in. There is always at least one
+// stop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    stops: Vec<GradientStop>,
+}
+
+impl Curve {
+    pub fn constant(value: f32) -> Self {
+        Self {
+            keys: vec![CurveKey { time: 0.0, value }],
+        }
+    }
+
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self {
+            keys: vec![
+                CurveKey {
+                    time: 0.0,
+                    value: start,
+                },
+                CurveKey {
+                    time: 1.0,
+                    value: end,
+                },
+            ],
+        }
+    }
+
+    pub fn keys(&self) -> &[CurveKey] {
+        &self.keys
+    }
+
+    // Returns the index the key was inserted at.
+    pub fn insert(&mut self, time: f32, value: f32) -> usize {
+        let time = time.max(0.0).min(1.0);
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(index, CurveKey { time, value });
+        index
+    }
+
+    // The last key is kept.
+    pub fn remove(&mut self, index: usize) {
+        if self.keys.len() > 1 && index < self.keys.len() {
+            self.keys.remove(index);
+        }
+    }
+
+    // The time is clamped between the neighbours of the key, so the keys keep their order.
+    pub fn set_key(&mut self, index: usize, time: f32, value: f32) {
+        let (min, max) = neighbour_range(index, self.keys.len(), |i| self.keys[i].time);
+        self.keys[index] = CurveKey {
+            time: time.max(min).min(max),
+            value,
+        };
+    }
+
+    pub fn sample(&self, time: f32) -> f32 {
+        let (previous, next, t) = segment(self.keys.len(), time, |i| self.keys[i].time);
+        lerp_scalar(self.keys[previous].value, self.keys[next].value, t)
+    }
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Curve::constant(1.0)
+    }
+}
+
+impl Gradient {
+    pub fn constant(color: [f32; 3]) -> Self {
+        Self {
+            stops: vec![GradientStop {
+                position: 0.0,
+                color,
+            }],
+        }
+    }
+
+    pub fn linear(start: [f32; 3], end: [f32; 3]) -> Self {
+        Self {
+            stops: vec![
+                GradientStop {
+                    position: 0.0,
+                    color: start,
+                },
+                GradientStop {
+                    position: 1.0,
+                    color: end,
+                },
+            ],
+        }
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    // Returns the index the stop was inserted at.
+    pub fn insert(&mut self, position: f32, color: [f32; 3]) -> usize {
+        let position = position.max(0.0).min(1.0);
+        let index = self.stops.partition_point(|stop| stop.position <= position);
+        self.stops.insert(index, GradientStop { position, color });
+        index
+    }
+
+    // The last stop is kept.
+    pub fn remove(&mut self, index: usize) {
+        if self.stops.len() > 1 && index < self.stops.len() {
+            self.stops.remove(index);
+        }
+    }
+
+    // The position is clamped between the neighbours of the stop, so the stops keep their order.
+    pub fn set_stop(&mut self, index: usize, position: f32, color: [f32; 3]) {
+        let (min, max) = neighbour_range(index, self.stops.len(), |i| self.stops[i].position);
+        self.stops[index] = GradientStop {
+            position: position.max(min).min(max),
+            color,
+        };
+    }
+
+    pub fn sample(&self, position: f32) -> [f32; 3] {
+        let (previous, next, t) = segment(self.stops.len(), position, |i| self.stops[i].position);
+        let (a, b) = (self.stops[previous].color, self.stops[next].color);
+
+        [
+            lerp_scalar(a[0], b[0], t),
+            lerp_scalar(a[1], b[1], t),
+            lerp_scalar(a[2], b[2], t),
+        ]
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::linear([0.0, 0.0, 0.0], [1.0, 1.0, 1.0])
+    }
+}
+
+// The keys around the time and how far it is from the first to the second of them.
+fn segment<F: Fn(usize) -> f32>(count: usize, time: f32, key_time: F) -> (usize, usize, f32) {
+    // The first key after the time.
+    let next = (0..count).position(|i| key_time(i) > time).unwrap_or(count);
+
+    if next == 0 {
+        return (0, 0, 0.0);
+    }
+
+    if next == count {
+        return (count - 1, count - 1, 0.0);
+    }
+
+    let (start, end) = (key_time(next - 1), key_time(next));
+    (next - 1, next, (time - start) / (end - start))
+}
+
+// The times the key can move between without passing its neighbours.
+fn neighbour_range<F: Fn(usize) -> f32>(index: usize, count: usize, key_time: F) -> (f32, f32) {
+    let min = if index > 0 { key_time(index - 1) } else { 0.0 };
+    let max = if index + 1 < count {
+        key_time(index + 1)
+    } else {
+        1.0
+    };
+
+    (min, max)
+}
@@ -0,0 +1,307 @@
+use crate::core::animation::{
+    blend_tree::{BlendTree, Parameters},
+    clip::{Pose, Skeleton},
+};
+use std::rc::Rc;
+
+// What has to hold for a transition to be taken. All conditions of a transition have to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Greater(String, f32),
+    Less(String, f32),
+    // Consumed by the transition.
+    Trigger(String),
+    // The clips of the state that do not loop reached their end.
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    name: String,
+    tree: BlendTree,
+}
+
+#[derive(Debug, Clone)]
+struct StateTransition {
+    // None for transitions from any state.
+    from: Option<usize>,
+    to: usize,
+    conditions: Vec<Condition>,
+    // Of the cross fade, in seconds.
+    duration: f32,
+}
+
+#[derive(Debug, Clone)]
+struct CrossFade {
+    from: usize,
+    elapsed: f32,
+    duration: f32,
+}
+
+// A blend tree added on top of the states, e.g. breathing or aiming. The mask weighs the joints
+// individually, None adds to every joint.
+#[derive(Debug, Clone)]
+pub struct AdditiveLayer {
+    pub name: String,
+    pub tree: BlendTree,
+    // The pose the tree is the difference to, usually its first frame.
+    pub reference: Pose,
+    pub weight: f32,
+    pub mask: Option<Vec<f32>>,
+}
+
+// Plays the blend tree of one state at a time and cross fades to the next state when the
+// conditions of a transition hold, e.g. from idle to locomotion once the speed parameter rises
+// and to a jump on a trigger. The first state added is the one it starts in. Additive layers are
+// applied over the result.
+#[derive(Debug, Clone)]
+pub struct AnimationStateMachine {
+    skeleton: Rc<Skeleton>,
+    states: Vec<State>,
+    transitions: Vec<StateTransition>,
+    layers: Vec<AdditiveLayer>,
+    parameters: Parameters,
+    current: usize,
+    fade: Option<CrossFade>,
+    pose: Pose,
+}
+
+impl AnimationStateMachine {
+    pub fn new(skeleton: Rc<Skeleton>) -> Self {
+        let pose = skeleton.bind_pose().clone();
+
+        Self {
+            skeleton,
+            states: vec![],
+            transitions: vec![],
+            layers: vec![],
+            parameters: Parameters::default(),
+            current: 0,
+            fade: None,
+            pose,
+        }
+    }
+
+    pub fn with_state(mut self, name: &str, tree: BlendTree) -> Self {
+        self.states.push(State {
+            name: name.to_string(),
+            tree,
+        });
+        self
+    }
+
+    // Panics for states that were not added.
+    pub fn with_transition(
+        mut self,
+        from: &str,
+        to: &str,
+        duration: f32,
+        conditions: Vec<Condition>,
+    ) -> Self {
+        let from = Some(self.state_index(from));
+        let to = self.state_index(to);
+
+        self.transitions.push(StateTransition {
+            from,
+            to,
+            conditions,
+            duration,
+        });
+        self
+    }
+
+    // Taken from every state but the one it leads to, e.g. to a hit reaction.
+    pub fn with_any_state_transition(
+        mut self,
+        to: &str,
+        duration: f32,
+        conditions: Vec<Condition>,
+    ) -> Self {
+        let to = self.state_index(to);
+
+        self.transitions.push(StateTransition {
+            from: None,
+            to,
+            conditions,
+            duration,
+        });
+        self
+    }
+
+    pub fn with_additive_layer(mut self, layer: AdditiveLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    pub fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+
+    pub fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.set(name, value)
+    }
+
+    pub fn trigger(&mut self, name: &str) {
+        self.parameters.trigger(name)
+    }
+
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut AdditiveLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    pub fn current_state(&self) -> &str {
+        &self.states[self.current].name
+    }
+
+    // Whether a cross fade is in progress.
+    pub fn in_transition(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    // Cross fades to the state regardless of the transitions. A fade of 0 switches at once.
+    pub fn play(&mut self, state: &str, fade_duration: f32) {
+        let to = self.state_index(state);
+        self.start_transition(to, fade_duration)
+    }
+
+    // Advances the clips, takes the transitions that hold and evaluates the pose.
+    pub fn update(&mut self, delta_time: f32) {
+        if self.states.is_empty() {
+            return;
+        }
+
+        if let Some(index) = self.next_transition() {
+            let transition = &self.transitions[index];
+
+            for condition in &transition.conditions {
+                if let Condition::Trigger(name) = condition {
+                    self.parameters.consume(name);
+                }
+            }
+
+            let (to, duration) = (transition.to, transition.duration);
+            self.start_transition(to, duration)
+        }
+
+        let parameters = &self.parameters;
+        self.states[self.current]
+            .tree
+            .advance(delta_time, parameters);
+
+        if let Some(fade) = &mut self.fade {
+            fade.elapsed += delta_time;
+            self.states[fade.from].tree.advance(delta_time, parameters);
+
+            if fade.elapsed >= fade.duration {
+                self.fade = None
+            }
+        }
+
+        for layer in &mut self.layers {
+            layer.tree.advance(delta_time, parameters)
+        }
+
+        self.evaluate()
+    }
+
+    // The pose of the last update, for Skeleton::joint_transforms.
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    pub fn skeleton(&self) -> &Rc<Skeleton> {
+        &self.skeleton
+    }
+
+    fn evaluate(&mut self) {
+        let bind_pose = self.skeleton.bind_pose();
+
+        self.states[self.current]
+            .tree
+            .evaluate(&self.parameters, bind_pose, &mut self.pose);
+
+        if let Some(fade) = &self.fade {
+            let mut from = bind_pose.clone();
+            self.states[fade.from]
+                .tree
+                .evaluate(&self.parameters, bind_pose, &mut from);
+
+            // The state faded out of weighs less over time.
+            from.blend(&self.pose, fade.elapsed / fade.duration);
+            self.pose = from
+        }
+
+        for layer in &self.layers {
+            if layer.weight <= 0.0 {
+                continue;
+            }
+
+            let mut delta = bind_pose.clone();
+            layer.tree.evaluate(&self.parameters, bind_pose, &mut delta);
+
+            self.pose.add(
+                &delta.relative_to(&layer.reference),
+                layer.weight,
+                layer.mask.as_deref(),
+            )
+        }
+    }
+
+    // The first transition out of the current state whose conditions hold. None during a fade,
+    // the state faded into is left once the fade completes.
+    fn next_transition(&self) -> Option<usize> {
+        if self.fade.is_some() {
+            return None;
+        }
+
+        let state = &self.states[self.current];
+
+        self.transitions.iter().position(|transition| {
+            let from_current = match transition.from {
+                Some(from) => from == self.current,
+                None => transition.to != self.current,
+            };
+
+            from_current
+                && transition
+                    .conditions
+                    .iter()
+                    .all(|condition| match condition {
+                        Condition::Greater(name, value) => self.parameters.get(name) > *value,
+                        Condition::Less(name, value) => self.parameters.get(name) < *value,
+                        Condition::Trigger(name) => self.parameters.triggered(name),
+                        Condition::Finished => state.tree.finished(),
+                    })
+        })
+    }
+
+    fn start_transition(&mut self, to: usize, duration: f32) {
+        if to == self.current && self.fade.is_none() {
+            return;
+        }
+
+        self.fade = if duration > 0.0 && to != self.current {
+            Some(CrossFade {
+                from: self.current,
+                elapsed: 0.0,
+                duration,
+            })
+        } else {
+            None
+        };
+
+        self.current = to;
+        self.states[to].tree.reset()
+    }
+
+    fn state_index(&self, name: &str) -> usize {
+        self.states
+            .iter()
+            .position(|state| state.name == name)
+            .unwrap_or_else(|| panic!("No animation state named '{}'", name))
+    }
+}
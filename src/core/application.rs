@@ -3,21 +3,30 @@ use gl_bindings as gl;
 
 use crate::core::{
     asset::AssetManager,
-    math::Vec4,
+    config, logging,
+    main_loop::MainLoop,
+    math::{UVec2, Vec4},
     scene::{Scene, SceneManager},
     timer::Timer,
     Context, Settings,
 };
 use crate::imgui::ImGui;
-use crate::rendering::framebuffer::TemporaryFramebufferPool;
+use crate::platform::{secondary_window, window};
+use crate::rendering::{
+    context_loss::ContextLoss, frame_stats::FrameStats, framebuffer::TemporaryFramebufferPool,
+    gpu_capabilities::GpuCapabilities, state::StateManager,
+};
 use glutin::{
-    dpi::LogicalSize,
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::{Fullscreen, Window, WindowBuilder},
-    Api, ContextBuilder, ContextWrapper, GlProfile, GlRequest, PossiblyCurrent,
+    event_loop::ControlFlow,
 };
-use std::{error::Error, ffi::CStr, ptr};
+use log::Level;
+use std::{ffi::CStr, ptr, time::Duration};
+
+// Time spent each frame uploading assets that finished loading in the background.
+const ASSET_UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+// Frames an unused temporary framebuffer is kept around for.
+const FRAMEBUFFER_KEEPALIVE_FRAMES: u8 = 3;
 
 pub struct Application;
 
@@ -25,17 +34,24 @@ impl Application {
     pub fn run<Cons, S>(settings: Settings, mut scene_constructor: Cons)
     where
         S: Scene + 'static,
-        Cons: FnMut(Context) -> S,
+        Cons: FnMut(Context) -> S + 'static,
     {
+        logging::init_default();
+
         let mut asset_manager = AssetManager::default();
+        config::mount_asset_packs(&mut asset_manager, &settings);
         let mut timer = Timer::new();
+        timer.pacing_mut().vsync = settings.vsync;
+        timer.pacing_mut().frame_rate_limit = settings.frame_rate_limit;
+        let mut main_loop = MainLoop::new(settings.vsync);
 
-        let (event_loop, windowed_context) = Self::create_windowed_context(&settings).unwrap();
+        let (event_loop, windowed_context) = window::create_windowed_context(&settings)
+            .unwrap_or_else(|e| panic!("Failed to initialize OpenGL: {}", e));
 
-        let mut framebuffer_cache = TemporaryFramebufferPool::new(3);
+        let mut framebuffer_cache = TemporaryFramebufferPool::new(FRAMEBUFFER_KEEPALIVE_FRAMES);
 
         let initial_scene = scene_constructor(Context::new(
-            windowed_context.window(),
+            Some(windowed_context.window()),
             &mut asset_manager,
             &mut timer,
             &mut framebuffer_cache,
@@ -44,18 +60,22 @@ impl Application {
 
         let mut scene_manager = SceneManager::new(initial_scene);
         scene_manager.initialize(Context::new(
-            windowed_context.window(),
+            Some(windowed_context.window()),
             &mut asset_manager,
             &mut timer,
             &mut framebuffer_cache,
             &settings,
         ));
 
-        let mut imgui = ImGui::new(windowed_context.window(), |s| {
-            windowed_context.get_proc_address(s)
-        });
+        let mut windowed_context = window::ContextSlot::new(windowed_context);
+
+        let mut imgui = ImGui::new(
+            windowed_context.window(),
+            settings.ui_layout_path.as_deref(),
+            |s| windowed_context.get_proc_address(s),
+        );
 
-        event_loop.run(move |event, _, control_flow| {
+        event_loop.run(move |event, target, control_flow| {
             *control_flow = ControlFlow::Poll;
 
             imgui
@@ -64,14 +84,27 @@ impl Application {
 
             match event {
                 Event::NewEvents(_) => {}
+                Event::WindowEvent { window_id, event }
+                    if window_id != windowed_context.window().id() =>
+                {
+                    secondary_window::handle_event(window_id, &event)
+                }
                 Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
                 } => *control_flow = ControlFlow::Exit,
                 Event::WindowEvent { event, .. } => {
+                    match &event {
+                        WindowEvent::Resized(size) => windowed_context.resize(*size),
+                        WindowEvent::ScaleFactorChanged { .. } => {
+                            imgui.rescale(|s| windowed_context.get_proc_address(s))
+                        }
+                        _ => {}
+                    }
+
                     scene_manager.handle_event(
                         Context::new(
-                            windowed_context.window(),
+                            Some(windowed_context.window()),
                             &mut asset_manager,
                             &mut timer,
                             &mut framebuffer_cache,
@@ -84,25 +117,100 @@ impl Application {
                         *control_flow = ControlFlow::Exit
                     }
                 }
-                Event::DeviceEvent { .. } => {}
+                Event::DeviceEvent { event, .. } => scene_manager.handle_device_event(
+                    Context::new(
+                        Some(windowed_context.window()),
+                        &mut asset_manager,
+                        &mut timer,
+                        &mut framebuffer_cache,
+                        &settings,
+                    ),
+                    &event,
+                ),
                 Event::UserEvent(_) => {}
                 Event::Suspended => scene_manager.pause(Context::new(
-                    windowed_context.window(),
+                    Some(windowed_context.window()),
                     &mut asset_manager,
                     &mut timer,
                     &mut framebuffer_cache,
                     &settings,
                 )),
                 Event::Resumed => scene_manager.resume(Context::new(
-                    windowed_context.window(),
+                    Some(windowed_context.window()),
                     &mut asset_manager,
                     &mut timer,
                     &mut framebuffer_cache,
                     &settings,
                 )),
                 Event::MainEventsCleared => {
+                    if let Some(cause) = ContextLoss::check() {
+                        log::error!("The OpenGL context was lost ({:?}), recreating it.", cause);
+
+                        // Everything of the lost context is released while it is still current,
+                        // the ids of its objects would delete the objects of the new one.
+                        scene_manager.stop(Context::new(
+                            Some(windowed_context.window()),
+                            &mut asset_manager,
+                            &mut timer,
+                            &mut framebuffer_cache,
+                            &settings,
+                        ));
+                        asset_manager.context_lost();
+                        framebuffer_cache =
+                            TemporaryFramebufferPool::new(FRAMEBUFFER_KEEPALIVE_FRAMES);
+                        imgui.context_lost();
+                        secondary_window::context_lost();
+                        ContextLoss::release_engine_objects();
+
+                        windowed_context = window::ContextSlot::new(
+                            window::recreate_window(target, &settings, windowed_context.window())
+                                .unwrap_or_else(|e| {
+                                    panic!("Failed to recreate the OpenGL context: {}", e)
+                                }),
+                        );
+                        ContextLoss::restored();
+                        main_loop = MainLoop::new(settings.vsync);
+
+                        asset_manager.restore_context();
+                        imgui.restore_context(windowed_context.window(), |s| {
+                            windowed_context.get_proc_address(s)
+                        });
+
+                        // The scenes start over, with the assets already uploaded.
+                        let scene = scene_constructor(Context::new(
+                            Some(windowed_context.window()),
+                            &mut asset_manager,
+                            &mut timer,
+                            &mut framebuffer_cache,
+                            &settings,
+                        ));
+                        scene_manager = SceneManager::new(scene);
+                        scene_manager.initialize(Context::new(
+                            Some(windowed_context.window()),
+                            &mut asset_manager,
+                            &mut timer,
+                            &mut framebuffer_cache,
+                            &settings,
+                        ));
+                    }
+
+                    secondary_window::open_pending(target, &windowed_context, &settings);
+                    asset_manager.update(ASSET_UPLOAD_BUDGET);
+
+                    let fixed_steps = main_loop.begin_frame(&mut timer);
+
+                    for _ in 0..fixed_steps {
+                        scene_manager.fixed_update(Context::new(
+                            Some(windowed_context.window()),
+                            &mut asset_manager,
+                            &mut timer,
+                            &mut framebuffer_cache,
+                            &settings,
+                        ));
+                    }
+
                     scene_manager.update(Context::new(
-                        windowed_context.window(),
+                        Some(windowed_context.window()),
                         &mut asset_manager,
                         &mut timer,
                         &mut framebuffer_cache,
@@ -115,7 +223,7 @@ impl Application {
                         .expect("Failed to prepare ImGui frame");
 
                     scene_manager.pre_draw(Context::new(
-                        windowed_context.window(),
+                        Some(windowed_context.window()),
                         &mut asset_manager,
                         &mut timer,
                         &mut framebuffer_cache,
@@ -124,9 +232,14 @@ impl Application {
 
                     windowed_context.window().request_redraw()
                 }
+                // Secondary windows are presented along with the main one.
+                Event::RedrawRequested(window_id)
+                    if window_id != windowed_context.window().id() => {}
                 Event::RedrawRequested(_) => {
+                    FrameStats::begin_frame();
+
                     scene_manager.draw(Context::new(
-                        windowed_context.window(),
+                        Some(windowed_context.window()),
                         &mut asset_manager,
                         &mut timer,
                         &mut framebuffer_cache,
@@ -139,13 +252,23 @@ impl Application {
                     imgui
                         .platform
                         .prepare_render(&ui, windowed_context.window());
-                    imgui.renderer.render(ui);
+                    // The frame borrows the UI context until it is rendered or dropped.
+                    match &imgui.renderer {
+                        Some(renderer) => renderer.render(ui),
+                        None => drop(ui),
+                    }
+                    imgui.update_ime_position(windowed_context.window());
+                    // The UI renderer binds its objects without the StateManager.
+                    StateManager::invalidate();
 
-                    windowed_context.swap_buffers().unwrap()
+                    windowed_context.swap_buffers().unwrap();
+                    secondary_window::present_all(&mut windowed_context);
+
+                    main_loop.end_frame(&timer, |s| windowed_context.get_proc_address(s))
                 }
                 Event::RedrawEventsCleared => {
                     scene_manager.post_draw(Context::new(
-                        windowed_context.window(),
+                        Some(windowed_context.window()),
                         &mut asset_manager,
                         &mut timer,
                         &mut framebuffer_cache,
@@ -155,7 +278,7 @@ impl Application {
                     framebuffer_cache.collect()
                 }
                 Event::LoopDestroyed => scene_manager.stop(Context::new(
-                    windowed_context.window(),
+                    Some(windowed_context.window()),
                     &mut asset_manager,
                     &mut timer,
                     &mut framebuffer_cache,
@@ -165,52 +288,15 @@ impl Application {
         });
     }
 
-    fn create_windowed_context(
-        settings: &Settings,
-    ) -> Result<(EventLoop<()>, ContextWrapper<PossiblyCurrent, Window>), Box<dyn Error>> {
-        assert!(
-            settings.graphics_api_version.major > 3 && settings.graphics_api_version.minor > 2,
-            "Only OpenGL version greater than 3.2 are supported"
-        );
-
-        assert!(
-            settings.graphics_api_version.major <= 4 && settings.graphics_api_version.minor <= 6,
-            "OpenGL versions greater than 4.6 are not supported"
-        );
-
-        let event_loop = EventLoop::new();
-        let mut window_builder = WindowBuilder::new()
-            .with_title(&settings.name)
-            .with_inner_size(LogicalSize::new(
-                settings.window_size.x,
-                settings.window_size.y,
-            ))
-            .with_resizable(false);
-
-        if settings.fullscreen {
-            let monitor = (&event_loop).available_monitors().next().unwrap();
-            let video_mode = monitor.video_modes().next().unwrap();
-            window_builder = window_builder.with_fullscreen(Some(Fullscreen::Exclusive(video_mode)))
-        }
-
-        let windowed_context = ContextBuilder::new()
-            .with_double_buffer(Some(true))
-            .with_gl_profile(GlProfile::Core)
-            .with_srgb(true)
-            .with_multisampling(settings.msaa as u16)
-            .with_vsync(settings.vsync)
-            .with_gl(GlRequest::Specific(
-                Api::OpenGl,
-                (
-                    settings.graphics_api_version.major as u8,
-                    settings.graphics_api_version.minor as u8,
-                ),
-            ))
-            .build_windowed(window_builder, &event_loop)?;
-
-        let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+    // Loads the functions of the current context and sets the state the renderer expects. The
+    // viewport covers the framebuffer of the window, in physical pixels.
+    pub(crate) fn initialize_gl<F>(viewport_size: UVec2, get_proc_address: F) -> Result<(), String>
+    where
+        F: FnMut(&'static str) -> *const GLvoid,
+    {
+        gl::load_with(get_proc_address);
 
-        gl::load_with(|s| windowed_context.get_proc_address(s) as *const _);
+        let capabilities = GpuCapabilities::initialize()?;
 
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
@@ -219,21 +305,21 @@ impl Application {
             gl::Enable(gl::FRAMEBUFFER_SRGB);
             gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
 
-            gl::Viewport(
-                0,
-                0,
-                settings.window_size.x as i32,
-                settings.window_size.y as i32,
-            );
+            gl::Viewport(0, 0, viewport_size.x as i32, viewport_size.y as i32);
 
             if cfg!(debug_assertions) {
                 gl::Enable(gl::DEBUG_OUTPUT);
                 gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
                 gl::DebugMessageCallback(Some(Self::debug_callback), ptr::null());
             }
+
+            if capabilities.parallel_shader_compile {
+                // Let the driver pick the number of compiler threads.
+                gl::MaxShaderCompilerThreadsKHR(0xFFFF_FFFF)
+            }
         }
 
-        Ok((event_loop, windowed_context))
+        Ok(())
     }
 
     extern "system" fn debug_callback(
@@ -247,11 +333,13 @@ impl Application {
     ) {
         let msg = unsafe { CStr::from_ptr(message) };
 
-        eprintln!(
-            "GL CALLBACK: type = {}, severity = {}, message = {:#?}",
+        log::log!(
+            target: "gl",
+            Self::severity_to_level(severity),
+            "{} ({}): {}",
             Self::message_type_to_str(message_type),
-            Self::severity_to_str(severity),
-            msg
+            id,
+            msg.to_string_lossy()
         )
     }
 
@@ -270,13 +358,12 @@ impl Application {
         }
     }
 
-    fn severity_to_str(severity: GLenum) -> &'static str {
+    fn severity_to_level(severity: GLenum) -> Level {
         match severity {
-            gl::DEBUG_SEVERITY_HIGH => "HIGH",
-            gl::DEBUG_SEVERITY_MEDIUM => "MEDIUM",
-            gl::DEBUG_SEVERITY_LOW => "LOW",
-            gl::DEBUG_SEVERITY_NOTIFICATION => "NOTIFICATION",
-            _ => "!UNDEFINED ENUM!",
+            gl::DEBUG_SEVERITY_HIGH => Level::Error,
+            gl::DEBUG_SEVERITY_MEDIUM => Level::Warn,
+            gl::DEBUG_SEVERITY_LOW => Level::Info,
+            _ => Level::Debug,
         }
     }
 }
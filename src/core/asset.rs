@@ -1,10 +1,37 @@
-use crate::rendering::mesh::Mesh;
-use crate::rendering::shader::{Shader, ShaderStage};
-use crate::rendering::texture::{Texture2D, Texture2DLoadConfig, TextureCube};
+use crate::geometry::MeshData;
+use crate::rendering::material::{LightmapMode, MaterialTemplate, PbsMetallicRoughnessMaterial};
+use crate::rendering::mesh::{Mesh, MeshImportSettings};
+use crate::rendering::shader::{expand_includes, Shader, ShaderStage};
+use crate::rendering::texture::{Texture2D, Texture2DLoadConfig, TextureCompression, TextureCube};
+use image::DynamicImage;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::path::Path;
-use std::rc::Rc;
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use self::embedded::EmbeddedAssets;
+use self::hot_reload::{AssetChanged, HotReload, ReloadStage};
+use self::loader::{Decoded, Loader, MeshKey, TextureKey};
+use self::pack::{AssetPack, AssetSource, MountedPack};
+
+pub use self::handle::Handle;
+pub use self::loader::{LoadProgress, PendingAsset};
+
+pub mod embedded;
+#[cfg(feature = "fbx")]
+pub mod fbx;
+pub mod gltf;
+mod handle;
+pub mod hot_reload;
+mod loader;
+pub mod meta;
+pub mod obj;
+pub mod pack;
+pub mod scene;
+pub mod scene_file;
 
 pub trait Asset {
     type Output;
@@ -17,114 +44,737 @@ pub trait Asset {
     ) -> Result<Self::Output, Self::Error>;
 }
 
+// Loads assets once per path (and load settings) and hands out shared handles to them.
 #[derive(Default)]
 pub struct AssetManager {
-    textures: HashMap<String, Rc<Texture2D>>,
-    cube_maps: HashMap<String, Rc<TextureCube>>,
-    meshes: HashMap<String, Rc<Mesh>>,
-    shaders: HashMap<String, Rc<Shader>>,
+    textures: HashMap<TextureKey, Handle<Texture2D>>,
+    cube_maps: HashMap<PathBuf, Handle<TextureCube>>,
+    meshes: HashMap<MeshKey, Handle<Mesh>>,
+    shaders: HashMap<(PathBuf, ShaderStage), Handle<Shader>>,
+    material_templates: HashMap<PathBuf, Handle<MaterialTemplate>>,
+    // Created on the first asynchronous request.
+    loader: Option<Loader>,
+    pending_textures: HashMap<TextureKey, Vec<PendingAsset<Texture2D>>>,
+    pending_meshes: HashMap<MeshKey, Vec<PendingAsset<Mesh>>>,
+    progress: LoadProgress,
+    packs: Vec<MountedPack>,
+    hot_reload: HotReload,
+    lost: LostAssets,
+}
+
+// The cache keys of the assets released with a lost GL context, uploaded again once the new one
+// is current.
+#[derive(Default)]
+struct LostAssets {
+    textures: Vec<TextureKey>,
+    cube_maps: Vec<PathBuf>,
+    meshes: Vec<MeshKey>,
+    shaders: Vec<(PathBuf, ShaderStage)>,
 }
 
 impl AssetManager {
+    // Serves the files of the pack in place of the files under mount_point. Packs mounted later
+    // take precedence. Cube maps are always read from loose files.
+    pub fn mount_pack<P: AsRef<Path>, M: AsRef<Path>>(
+        &mut self,
+        pack_path: P,
+        mount_point: M,
+    ) -> Result<(), String> {
+        let pack = AssetPack::open(pack_path)?;
+
+        self.packs.push(MountedPack {
+            mount_point: mount_point.as_ref().to_path_buf(),
+            pack: Arc::new(pack),
+        });
+
+        Ok(())
+    }
+
+    // Loads the texture with the settings of its .meta sidecar. Textures without one get
+    // mipmaps and are treated as linear.
+    pub fn load_texture<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<Texture2D>, String> {
+        let config = Texture2DLoadConfig::from_sidecar(path.as_ref(), Self::texture_defaults())?;
+
+        self.load_texture_2d_with_config(path, config)
+    }
+
     pub fn load_texture_2d<P: AsRef<Path>>(
         &mut self,
         path: P,
         is_srgb: bool,
         generate_mipmaps: bool,
-    ) -> Result<Rc<Texture2D>, String> {
-        match path.as_ref().file_name() {
-            Some(fname) => {
-                let texture = Rc::new(Texture2D::load(
-                    path.as_ref(),
-                    Some(Texture2DLoadConfig {
-                        is_srgb,
-                        generate_mipmap: generate_mipmaps,
-                    }),
-                )?);
-
-                self.textures
-                    .entry(String::from(fname.to_string_lossy()))
-                    .or_insert(Rc::clone(&texture));
+    ) -> Result<Handle<Texture2D>, String> {
+        self.load_texture_2d_with_config(
+            path,
+            Texture2DLoadConfig {
+                is_srgb,
+                generate_mipmap: generate_mipmaps,
+                compression: TextureCompression::None,
+            },
+        )
+    }
+
+    pub fn load_texture_2d_with_config<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: Texture2DLoadConfig,
+    ) -> Result<Handle<Texture2D>, String> {
+        let key = (Self::normalize(path.as_ref()), config);
+        let source = self.source(path.as_ref());
+
+        Self::load_cached(
+            &mut self.textures,
+            &mut self.hot_reload,
+            ReloadStage::Texture,
+            key,
+            || {
+                let texture = decode_image(&source)
+                    .and_then(|image| Texture2D::new_from_image_with_config(image, config))
+                    .map_err(|e| format!("Failed to load texture {:?}: {}", path.as_ref(), e))?;
+
+                texture.set_label(&path.as_ref().to_string_lossy());
+                log::debug!("Loaded texture {:?} ({:?}).", path.as_ref(), config);
 
                 Ok(texture)
-            }
-            None => Err(String::from("Invalid file path.")),
-        }
+            },
+        )
     }
 
     pub fn load_texture_cube<P: AsRef<Path>>(
         &mut self,
         path: P,
-    ) -> Result<Rc<TextureCube>, String> {
-        match path.as_ref().file_name() {
-            Some(fname) => {
-                let cubemap = Rc::new(TextureCube::new_from_file(path.as_ref())?);
+    ) -> Result<Handle<TextureCube>, String> {
+        let key = Self::normalize(path.as_ref());
 
-                self.cube_maps
-                    .entry(String::from(fname.to_string_lossy()))
-                    .or_insert_with(|| Rc::clone(&cubemap));
+        Self::load_cached(
+            &mut self.cube_maps,
+            &mut self.hot_reload,
+            ReloadStage::Texture,
+            key,
+            || TextureCube::new_from_file(path.as_ref()),
+        )
+    }
 
-                Ok(cubemap)
-            }
-            None => Err(String::from("Invalid file path.")),
+    // Loads the mesh with the settings of its .meta sidecar, if it has one.
+    pub fn load_mesh<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<Mesh>, String> {
+        let settings = MeshImportSettings::from_sidecar(path.as_ref(), Default::default())?;
+
+        self.load_mesh_with_settings(path, settings)
+    }
+
+    pub fn load_mesh_with_settings<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        settings: MeshImportSettings,
+    ) -> Result<Handle<Mesh>, String> {
+        let key = (Self::normalize(path.as_ref()), settings);
+        let source = self.source(path.as_ref());
+
+        Self::load_cached(
+            &mut self.meshes,
+            &mut self.hot_reload,
+            ReloadStage::Mesh,
+            key,
+            || Ok(gltf::process_mesh_data(read_mesh_data(&source)?, settings)),
+        )
+    }
+
+    // The vertices and indices of a mesh as stored, without import settings and not cached, e.g.
+    // for collision geometry.
+    pub fn read_mesh_data<P: AsRef<Path>>(&self, path: P) -> Result<MeshData, String> {
+        read_mesh_data(&self.source(path.as_ref()))
+    }
+
+    pub fn load_shader<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        stage: ShaderStage,
+    ) -> Result<Handle<Shader>, String> {
+        let key = (Self::normalize(path.as_ref()), stage);
+        let source = self.source(path.as_ref());
+
+        Self::load_cached(
+            &mut self.shaders,
+            &mut self.hot_reload,
+            ReloadStage::Shader,
+            key,
+            || load_shader(&source, stage),
+        )
+    }
+
+    // Asynchronous load_texture.
+    pub fn request_texture<P: AsRef<Path>>(&mut self, path: P) -> PendingAsset<Texture2D> {
+        match Texture2DLoadConfig::from_sidecar(path.as_ref(), Self::texture_defaults()) {
+            Ok(config) => self.request_texture_2d_with_config(path, config),
+            Err(e) => PendingAsset::ready(Err(e)),
+        }
+    }
+
+    pub fn request_texture_2d<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        is_srgb: bool,
+        generate_mipmaps: bool,
+    ) -> PendingAsset<Texture2D> {
+        self.request_texture_2d_with_config(
+            path,
+            Texture2DLoadConfig {
+                is_srgb,
+                generate_mipmap: generate_mipmaps,
+                compression: TextureCompression::None,
+            },
+        )
+    }
+
+    // Reads and decodes the texture on a loader thread. The GL upload happens on the main thread
+    // in update or wait.
+    pub fn request_texture_2d_with_config<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: Texture2DLoadConfig,
+    ) -> PendingAsset<Texture2D> {
+        let key = (Self::normalize(path.as_ref()), config);
+
+        if let Some(handle) = self.textures.get(&key) {
+            return PendingAsset::ready(Ok(handle.clone()));
         }
+
+        let pending = PendingAsset::new();
+        let waiting = self.pending_textures.entry(key.clone()).or_default();
+        waiting.push(pending.clone());
+
+        if waiting.len() == 1 {
+            let source = self.source(path.as_ref());
+
+            self.spawn(move || Decoded::Texture(key, decode_image(&source)));
+        }
+
+        pending
     }
 
-    pub fn load_mesh<P: AsRef<Path>>(&mut self, path: P) -> Result<Rc<Mesh>, String> {
-        match path.as_ref().file_name() {
-            Some(fname) => {
-                let mesh = Rc::new(Mesh::load(path.as_ref(), None)?);
+    // Asynchronous load_mesh.
+    pub fn request_mesh<P: AsRef<Path>>(&mut self, path: P) -> PendingAsset<Mesh> {
+        match MeshImportSettings::from_sidecar(path.as_ref(), Default::default()) {
+            Ok(settings) => self.request_mesh_with_settings(path, settings),
+            Err(e) => PendingAsset::ready(Err(e)),
+        }
+    }
 
-                self.meshes
-                    .entry(String::from(fname.to_string_lossy()))
-                    .or_insert_with(|| Rc::clone(&mesh));
+    // Reads and optimizes the mesh on a loader thread. The GL upload happens on the main thread
+    // in update or wait.
+    pub fn request_mesh_with_settings<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        settings: MeshImportSettings,
+    ) -> PendingAsset<Mesh> {
+        let key = (Self::normalize(path.as_ref()), settings);
 
-                Ok(mesh)
+        if let Some(handle) = self.meshes.get(&key) {
+            return PendingAsset::ready(Ok(handle.clone()));
+        }
+
+        let pending = PendingAsset::new();
+        let waiting = self.pending_meshes.entry(key.clone()).or_default();
+        waiting.push(pending.clone());
+
+        if waiting.len() == 1 {
+            let source = self.source(path.as_ref());
+
+            self.spawn(move || {
+                let mesh_data = read_mesh_data(&source).map(|mut mesh_data| {
+                    gltf::prepare_mesh_data(&mut mesh_data, settings);
+                    mesh_data
+                });
+                Decoded::Mesh(key, mesh_data)
+            });
+        }
+
+        pending
+    }
+
+    // Uploads the assets the loader threads have finished, until the time budget runs out. Call
+    // once per frame on the thread that owns the GL context.
+    pub fn update(&mut self, upload_budget: Duration) {
+        let start = Instant::now();
+
+        let changes = self.hot_reload.poll();
+
+        if !changes.is_empty() {
+            for change in &changes {
+                self.reload(change);
+            }
+
+            self.hot_reload.publish(&changes);
+        }
+
+        while start.elapsed() < upload_budget {
+            match self.loader.as_ref().and_then(Loader::try_recv) {
+                Some(decoded) => self.finalize(decoded),
+                None => break,
             }
-            None => Err(String::from("Invalid file path.")),
         }
     }
 
-    pub fn load_shader<P: AsRef<Path>>(
+    // Blocks until the asset is uploaded. Other loads that finish in the meantime are uploaded
+    // as well.
+    pub fn wait<T>(&mut self, pending: &PendingAsset<T>) -> Result<Handle<T>, String> {
+        loop {
+            if let Some(result) = pending.get() {
+                return result;
+            }
+
+            let decoded = self
+                .loader
+                .as_ref()
+                .expect("Waiting on an asset that was never requested")
+                .recv();
+
+            self.finalize(decoded);
+        }
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    // Files loaded through the manager are watched automatically. Enable it and subscribe to be
+    // notified after the manager reloaded a changed file.
+    pub fn hot_reload(&mut self) -> &mut HotReload {
+        &mut self.hot_reload
+    }
+
+    pub fn load_material_template<P: AsRef<Path>>(
         &mut self,
         path: P,
-        stage: ShaderStage,
-    ) -> Result<Rc<Shader>, String> {
-        match path.as_ref().file_name() {
-            Some(fname) => {
-                let shader = Rc::new(Shader::load(path.as_ref(), Some(stage))?);
+    ) -> Result<Handle<MaterialTemplate>, String> {
+        let key = Self::normalize(path.as_ref());
+        let source = self.source(path.as_ref());
+
+        Self::load_cached(
+            &mut self.material_templates,
+            &mut self.hot_reload,
+            ReloadStage::Material,
+            key,
+            || match &source {
+                AssetSource::File(path) => MaterialTemplate::load(path, None),
+                AssetSource::Packed(..) => MaterialTemplate::parse(
+                    &source.read_to_string()?,
+                    path.as_ref().parent().unwrap_or_else(|| Path::new("")),
+                ),
+            },
+        )
+    }
 
-                self.shaders
-                    .entry(String::from(fname.to_string_lossy()))
-                    .or_insert_with(|| Rc::clone(&shader));
+    // Creates a material from the template. Its textures are loaded through the manager and
+    // shared with every other material that uses them.
+    pub fn instantiate_material<P: AsRef<Path>>(
+        &mut self,
+        template: &Handle<MaterialTemplate>,
+        asset_path: P,
+    ) -> Result<PbsMetallicRoughnessMaterial, String> {
+        // The template decides the color space unless the texture's sidecar overrides it.
+        let mut load_map = |map: &Option<PathBuf>, is_srgb: bool, fallback: [u8; 3]| match map {
+            Some(path) => {
+                let defaults = Texture2DLoadConfig {
+                    is_srgb,
+                    ..Self::texture_defaults()
+                };
+                let config = Texture2DLoadConfig::from_sidecar(path, defaults)?;
 
-                Ok(shader)
+                self.load_texture_2d_with_config(path, config)
             }
-            None => Err(String::from("Invalid file path.")),
+            None => Ok(Handle::new(Texture2D::new_from_color(fallback))),
+        };
+
+        let albedo = load_map(&template.albedo, true, [255, 255, 255])?;
+        let metallic_roughness_ao =
+            load_map(&template.metallic_roughness_ao, false, [255, 255, 255])?;
+        let normals = load_map(&template.normals, false, [128, 128, 255])?;
+        // Baked irradiance is authored like a color, occlusion like the other data maps.
+        let lightmap = match &template.lightmap {
+            Some(_) => Some(load_map(
+                &template.lightmap,
+                template.lightmap_mode == LightmapMode::Irradiance,
+                [255, 255, 255],
+            )?),
+            None => None,
+        };
+        let displacement = match &template.displacement {
+            Some(path) => Some(self.load_texture(path)?),
+            None => None,
+        };
+
+        let mut material = PbsMetallicRoughnessMaterial::new(
+            asset_path,
+            albedo,
+            metallic_roughness_ao,
+            normals,
+            displacement,
+        )?;
+
+        material.set_base_color(template.base_color);
+        material.set_metallic_scale(template.metallic_scale);
+        material.set_roughness_scale(template.roughness_scale);
+        material.set_lightmap(lightmap, template.lightmap_mode);
+        material.set_lightmap_intensity(template.lightmap_intensity);
+
+        Ok(material)
+    }
+
+    pub fn get_texture_2d<P: AsRef<Path>>(&self, path: P) -> Option<Handle<Texture2D>> {
+        let path = Self::normalize(path.as_ref());
+
+        self.textures
+            .iter()
+            .find(|((texture_path, _), _)| *texture_path == path)
+            .map(|(_, handle)| handle.clone())
+    }
+
+    // Every loaded 2D texture and the path it was loaded from, once per load configuration.
+    pub fn textures(&self) -> impl Iterator<Item = (&Path, &Handle<Texture2D>)> {
+        self.textures
+            .iter()
+            .map(|((path, _), handle)| (path.as_path(), handle))
+    }
+
+    pub fn texture_cubes(&self) -> impl Iterator<Item = (&Path, &Handle<TextureCube>)> {
+        self.cube_maps
+            .iter()
+            .map(|(path, handle)| (path.as_path(), handle))
+    }
+
+    // Once per import settings.
+    pub fn meshes(&self) -> impl Iterator<Item = (&Path, &Handle<Mesh>)> {
+        self.meshes
+            .iter()
+            .map(|((path, _), handle)| (path.as_path(), handle))
+    }
+
+    pub fn shaders(&self) -> impl Iterator<Item = (&Path, &Handle<Shader>)> {
+        self.shaders
+            .iter()
+            .map(|((path, _), handle)| (path.as_path(), handle))
+    }
+
+    pub fn material_templates(&self) -> impl Iterator<Item = (&Path, &Handle<MaterialTemplate>)> {
+        self.material_templates
+            .iter()
+            .map(|(path, handle)| (path.as_path(), handle))
+    }
+
+    pub fn get_texture_cube<P: AsRef<Path>>(&self, path: P) -> Option<Handle<TextureCube>> {
+        self.cube_maps.get(&Self::normalize(path.as_ref())).cloned()
+    }
+
+    pub fn get_mesh<P: AsRef<Path>>(&self, path: P) -> Option<Handle<Mesh>> {
+        let path = Self::normalize(path.as_ref());
+
+        self.meshes
+            .iter()
+            .find(|((mesh_path, _), _)| *mesh_path == path)
+            .map(|(_, handle)| handle.clone())
+    }
+
+    // Drops the manager's reference to every asset loaded from the path. The asset itself is
+    // destroyed once the last outstanding handle is dropped.
+    pub fn unload<P: AsRef<Path>>(&mut self, path: P) {
+        let path = Self::normalize(path.as_ref());
+
+        self.textures.retain(|(p, _), _| *p != path);
+        self.cube_maps.retain(|p, _| *p != path);
+        self.meshes.retain(|(p, _), _| *p != path);
+        self.shaders.retain(|(p, _), _| *p != path);
+        self.material_templates.retain(|p, _| *p != path);
+
+        self.hot_reload.unwatch(&path);
+    }
+
+    // Destroys the assets nobody holds a handle to anymore. Returns the number of assets freed.
+    pub fn unload_unused(&mut self) -> usize {
+        Self::retain_used(&mut self.textures)
+            + Self::retain_used(&mut self.cube_maps)
+            + Self::retain_used(&mut self.meshes)
+            + Self::retain_used(&mut self.shaders)
+            + Self::retain_used(&mut self.material_templates)
+    }
+
+    // Releases the GPU assets of the lost context while it is still current. Handles held
+    // outside of the manager have to be dropped before, their objects would be deleted in the
+    // new context otherwise. Material templates are kept, they hold no GL objects.
+    pub fn context_lost(&mut self) {
+        self.lost = LostAssets {
+            textures: self.textures.drain().map(|(key, _)| key).collect(),
+            cube_maps: self.cube_maps.drain().map(|(key, _)| key).collect(),
+            meshes: self.meshes.drain().map(|(key, _)| key).collect(),
+            shaders: self.shaders.drain().map(|(key, _)| key).collect(),
+        };
+    }
+
+    // Uploads the assets released with the lost context to the new one, from their files or
+    // packs, and publishes them as changed to the hot reload subscribers.
+    pub fn restore_context(&mut self) {
+        let lost = std::mem::take(&mut self.lost);
+        let mut changes = Vec::new();
+
+        let mut restored =
+            |path: &Path, stage: ReloadStage, result: Result<(), String>| match result {
+                Ok(()) => changes.push(AssetChanged {
+                    path: path.to_path_buf(),
+                    stage,
+                }),
+                Err(e) => log::error!("Failed to restore {:?}: {}", path, e),
+            };
+
+        for (path, stage) in &lost.shaders {
+            let result = self.load_shader(path, *stage).map(|_| ());
+            restored(path, ReloadStage::Shader, result);
+        }
+
+        for (path, config) in &lost.textures {
+            let result = self.load_texture_2d_with_config(path, *config).map(|_| ());
+            restored(path, ReloadStage::Texture, result);
+        }
+
+        for path in &lost.cube_maps {
+            let result = self.load_texture_cube(path).map(|_| ());
+            restored(path, ReloadStage::Texture, result);
         }
+
+        for (path, settings) in &lost.meshes {
+            let result = self.load_mesh_with_settings(path, *settings).map(|_| ());
+            restored(path, ReloadStage::Mesh, result);
+        }
+
+        log::info!("Restored {} assets after losing the context", changes.len());
+
+        self.hot_reload.publish(&changes);
     }
 
-    pub fn get_texture_2d(&self, name: &str) -> Option<Rc<Texture2D>> {
-        if let Some(rc_tex) = self.textures.get(name) {
-            return Some(Rc::clone(rc_tex));
+    fn spawn<F: FnOnce() -> Decoded + Send + 'static>(&mut self, job: F) {
+        if self.progress.is_done() {
+            self.progress = LoadProgress::default();
         }
+        self.progress.requested += 1;
 
-        None
+        self.loader.get_or_insert_with(Loader::new).spawn(job);
     }
 
-    pub fn get_texture_cube(&self, name: &str) -> Option<Rc<TextureCube>> {
-        if let Some(rc_tex) = self.cube_maps.get(name) {
-            return Some(Rc::clone(rc_tex));
+    fn finalize(&mut self, decoded: Decoded) {
+        match decoded {
+            Decoded::Texture(key, image) => {
+                let config = key.1;
+
+                // A blocking load of the same texture may have finished first.
+                let result = match self.textures.get(&key) {
+                    Some(handle) => Ok(handle.clone()),
+                    None => image
+                        .and_then(|image| Texture2D::new_from_image_with_config(image, config))
+                        .map(|texture| Handle::with_path(texture, &key.0)),
+                };
+
+                self.complete(&key.0, &result);
+                self.hot_reload.watch(&key.0, ReloadStage::Texture);
+
+                if let Ok(handle) = &result {
+                    self.textures.insert(key.clone(), handle.clone());
+                }
+
+                for pending in self.pending_textures.remove(&key).unwrap_or_default() {
+                    pending.resolve(result.clone());
+                }
+            }
+            Decoded::Mesh(key, mesh_data) => {
+                let settings = key.1;
+
+                let result = match self.meshes.get(&key) {
+                    Some(handle) => Ok(handle.clone()),
+                    None => mesh_data
+                        .map(|mesh_data| gltf::upload_mesh_data(mesh_data, settings))
+                        .map(|mesh| Handle::with_path(mesh, &key.0)),
+                };
+
+                self.complete(&key.0, &result);
+                self.hot_reload.watch(&key.0, ReloadStage::Mesh);
+
+                if let Ok(handle) = &result {
+                    self.meshes.insert(key.clone(), handle.clone());
+                }
+
+                for pending in self.pending_meshes.remove(&key).unwrap_or_default() {
+                    pending.resolve(result.clone());
+                }
+            }
         }
+    }
 
-        None
+    fn complete<T>(&mut self, path: &Path, result: &Result<Handle<T>, String>) {
+        match result {
+            Ok(_) => self.progress.completed += 1,
+            Err(e) => {
+                log::error!("Failed to load {:?}: {}", path, e);
+                self.progress.failed += 1
+            }
+        }
     }
 
-    pub fn get_mesh(&self, name: &str) -> Option<Rc<Mesh>> {
-        if let Some(rc_mesh) = self.meshes.get(name) {
-            return Some(Rc::clone(&rc_mesh));
+    // Replaces the cached assets loaded from the changed file. Handles to the old assets stay
+    // valid; holders pick up the new ones through the published change.
+    fn reload(&mut self, change: &AssetChanged) {
+        let packs = &self.packs;
+        let source = |path: &Path| AssetSource::resolve(packs, path);
+
+        match change.stage {
+            ReloadStage::Shader => {
+                Self::reload_cached(&mut self.shaders, &change.path, |(path, stage)| {
+                    load_shader(&source(path), *stage)
+                })
+            }
+            ReloadStage::Texture => {
+                Self::reload_cached(&mut self.textures, &change.path, |(path, config)| {
+                    Texture2D::new_from_image_with_config(decode_image(&source(path))?, *config)
+                });
+                Self::reload_cached(&mut self.cube_maps, &change.path, |path| {
+                    TextureCube::new_from_file(path)
+                })
+            }
+            ReloadStage::Mesh => {
+                Self::reload_cached(&mut self.meshes, &change.path, |(path, settings)| {
+                    Ok(gltf::process_mesh_data(
+                        read_mesh_data(&source(path))?,
+                        *settings,
+                    ))
+                })
+            }
+            ReloadStage::Material => {
+                Self::reload_cached(&mut self.material_templates, &change.path, |path| {
+                    MaterialTemplate::load(path, None)
+                })
+            }
+        }
+    }
+
+    fn load_cached<K, T, F>(
+        cache: &mut HashMap<K, Handle<T>>,
+        hot_reload: &mut HotReload,
+        stage: ReloadStage,
+        key: K,
+        load: F,
+    ) -> Result<Handle<T>, String>
+    where
+        K: CacheKey,
+        F: FnOnce() -> Result<T, String>,
+    {
+        if let Some(handle) = cache.get(&key) {
+            return Ok(handle.clone());
+        }
+
+        hot_reload.watch(key.path(), stage);
+
+        let handle = Handle::with_path(load()?, key.path());
+        cache.insert(key, handle.clone());
+
+        Ok(handle)
+    }
+
+    fn reload_cached<K, T, F>(cache: &mut HashMap<K, Handle<T>>, path: &Path, mut load: F)
+    where
+        K: CacheKey,
+        F: FnMut(&K) -> Result<T, String>,
+    {
+        for (key, handle) in cache.iter_mut().filter(|(key, _)| key.path() == path) {
+            match load(key) {
+                Ok(asset) => *handle = Handle::with_path(asset, path),
+                Err(e) => log::error!("Failed to reload {:?}: {}", path, e),
+            }
+        }
+    }
+
+    fn retain_used<K: Eq + Hash, T>(cache: &mut HashMap<K, Handle<T>>) -> usize {
+        let count = cache.len();
+        cache.retain(|_, handle| handle.ref_count() > 1);
+
+        count - cache.len()
+    }
+
+    fn source(&self, path: &Path) -> AssetSource {
+        AssetSource::resolve(&self.packs, path)
+    }
+
+    // Different spellings of the same file share the cache entry.
+    fn texture_defaults() -> Texture2DLoadConfig {
+        Texture2DLoadConfig {
+            generate_mipmap: true,
+            ..Default::default()
+        }
+    }
+
+    fn normalize(path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+// Cache keys start with the normalized path of the file.
+trait CacheKey: Eq + Hash {
+    fn path(&self) -> &Path;
+}
+
+impl CacheKey for PathBuf {
+    fn path(&self) -> &Path {
+        self
+    }
+}
+
+impl<A: Eq + Hash> CacheKey for (PathBuf, A) {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl<A: Eq + Hash, B: Eq + Hash> CacheKey for (PathBuf, A, B) {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+fn decode_image(source: &AssetSource) -> Result<DynamicImage, String> {
+    match source {
+        AssetSource::File(path) => image::open(path).map_err(|e| e.to_string()),
+        AssetSource::Packed(..) => {
+            image::load_from_memory(&source.read()?).map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn read_mesh_data(source: &AssetSource) -> Result<MeshData, String> {
+    match source {
+        AssetSource::File(path) => Mesh::read_mesh_data(path),
+        AssetSource::Packed(pack, name) => Mesh::read_mesh_data_from_slice(&pack.read(name)?, name),
+    }
+}
+
+fn load_shader(source: &AssetSource, stage: ShaderStage) -> Result<Shader, String> {
+    match source {
+        AssetSource::File(path) => Shader::load(path, Some(stage)),
+        // Packs carry the compiled .spv next to the source, same as the loose files.
+        AssetSource::Packed(pack, name) if cfg!(feature = "use-spirv") => {
+            let spv_name = format!("{}.spv", name);
+            Shader::new_from_spirv_binary(stage, &spv_name, &pack.read(&spv_name)?)
+                .map_err(String::from)
         }
+        // Includes are read from the directory of the shader in the pack.
+        AssetSource::Packed(pack, name) => {
+            let directory = name.rfind('/').map_or("", |i| &name[..=i]);
+            let read_include = |include: &str| {
+                let packed = pack.read(&format!("{}{}", directory, include));
+
+                match packed {
+                    Ok(bytes) => String::from_utf8(bytes).map_err(|e| e.to_string()),
+                    Err(_) => EmbeddedAssets::shader_include(include),
+                }
+            };
+            let source = expand_includes(&source.read_to_string()?, &read_include)?;
 
-        None
+            Shader::new_from_source(stage, name, source).map_err(String::from)
+        }
     }
 }
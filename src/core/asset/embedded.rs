@@ -0,0 +1,357 @@
+use crate::core::asset::Handle;
+use crate::rendering::{
+    error::RendererError,
+    frame_stats::FrameStats,
+    program_pipeline::ProgramPipeline,
+    shader::{expand_includes, Shader, ShaderStage},
+    texture::{SizedTextureFormat, Texture2D},
+};
+use gl_bindings as gl;
+use std::{cell::RefCell, fmt::Debug, path::Path};
+
+const BRDF_LUT_SIZE: u32 = 512;
+
+// Shaders the engine can't work without, keyed by file name.
+const SHADERS: &[(&str, &str)] = &[
+    (
+        "pbs.vert",
+        include_str!("../../../examples/assets/sdr/pbs.vert"),
+    ),
+    (
+        "pbs.frag",
+        include_str!("../../../examples/assets/sdr/pbs.frag"),
+    ),
+    (
+        "pbs_lighting.glsl",
+        include_str!("../../../examples/assets/sdr/pbs_lighting.glsl"),
+    ),
+    (
+        "pbs_instanced.vert",
+        include_str!("../../../examples/assets/sdr/pbs_instanced.vert"),
+    ),
+    (
+        "pbs_skinned.vert",
+        include_str!("../../../examples/assets/sdr/pbs_skinned.vert"),
+    ),
+    (
+        "pbs_pom.vert",
+        include_str!("../../../examples/assets/sdr/pbs_pom.vert"),
+    ),
+    (
+        "pbs_pom.frag",
+        include_str!("../../../examples/assets/sdr/pbs_pom.frag"),
+    ),
+    (
+        "pbs_tess.vert",
+        include_str!("../../../examples/assets/sdr/pbs_tess.vert"),
+    ),
+    (
+        "pbs_tess.tesc",
+        include_str!("../../../examples/assets/sdr/pbs_tess.tesc"),
+    ),
+    (
+        "pbs_tess.tese",
+        include_str!("../../../examples/assets/sdr/pbs_tess.tese"),
+    ),
+    (
+        "terrain.vert",
+        include_str!("../../../examples/assets/sdr/terrain.vert"),
+    ),
+    (
+        "terrain.frag",
+        include_str!("../../../examples/assets/sdr/terrain.frag"),
+    ),
+    (
+        "foliage.vert",
+        include_str!("../../../examples/assets/sdr/foliage.vert"),
+    ),
+    (
+        "foliage.frag",
+        include_str!("../../../examples/assets/sdr/foliage.frag"),
+    ),
+    (
+        "gaussian_blur_horizontal.frag",
+        include_str!("../../../examples/assets/sdr/gaussian_blur_horizontal.frag"),
+    ),
+    (
+        "gaussian_blur_vertical.frag",
+        include_str!("../../../examples/assets/sdr/gaussian_blur_vertical.frag"),
+    ),
+    (
+        "fullscreen.vert",
+        include_str!("../../rendering/postprocess/shaders/fullscreen.vert"),
+    ),
+    (
+        "tonemap.frag",
+        include_str!("../../rendering/postprocess/shaders/tonemap.frag"),
+    ),
+    (
+        "fxaa.frag",
+        include_str!("../../rendering/postprocess/shaders/fxaa.frag"),
+    ),
+    (
+        "fsr_easu.frag",
+        include_str!("../../rendering/postprocess/shaders/fsr_easu.frag"),
+    ),
+    (
+        "fsr_rcas.frag",
+        include_str!("../../rendering/postprocess/shaders/fsr_rcas.frag"),
+    ),
+    (
+        "motion_blur_tile_max.frag",
+        include_str!("../../rendering/postprocess/shaders/motion_blur_tile_max.frag"),
+    ),
+    (
+        "motion_blur_neighbor_max.frag",
+        include_str!("../../rendering/postprocess/shaders/motion_blur_neighbor_max.frag"),
+    ),
+    (
+        "motion_blur.frag",
+        include_str!("../../rendering/postprocess/shaders/motion_blur.frag"),
+    ),
+    (
+        "camera_imperfections.frag",
+        include_str!("../../rendering/postprocess/shaders/camera_imperfections.frag"),
+    ),
+    (
+        "bloom_downsample.frag",
+        include_str!("../../rendering/postprocess/shaders/bloom_downsample.frag"),
+    ),
+    (
+        "bloom_upsample.frag",
+        include_str!("../../rendering/postprocess/shaders/bloom_upsample.frag"),
+    ),
+    (
+        "bloom_composite.frag",
+        include_str!("../../rendering/postprocess/shaders/bloom_composite.frag"),
+    ),
+    (
+        "luminance_histogram.comp",
+        include_str!("../../rendering/postprocess/shaders/luminance_histogram.comp"),
+    ),
+    (
+        "luminance_average.comp",
+        include_str!("../../rendering/postprocess/shaders/luminance_average.comp"),
+    ),
+    (
+        "debug_draw.vert",
+        include_str!("../../rendering/shaders/debug_draw.vert"),
+    ),
+    (
+        "debug_draw.frag",
+        include_str!("../../rendering/shaders/debug_draw.frag"),
+    ),
+    (
+        "fallback.vert",
+        include_str!("../../rendering/shaders/fallback.vert"),
+    ),
+    (
+        "fallback.frag",
+        include_str!("../../rendering/shaders/fallback.frag"),
+    ),
+    (
+        "normal_visualization.vert",
+        include_str!("../../rendering/shaders/normal_visualization.vert"),
+    ),
+    (
+        "normal_visualization.geom",
+        include_str!("../../rendering/shaders/normal_visualization.geom"),
+    ),
+    (
+        "normal_visualization.frag",
+        include_str!("../../rendering/shaders/normal_visualization.frag"),
+    ),
+    (
+        "brdf_lut.comp",
+        include_str!("../../rendering/shaders/brdf_lut.comp"),
+    ),
+    (
+        "hiz_copy.comp",
+        include_str!("../../rendering/shaders/hiz_copy.comp"),
+    ),
+    (
+        "hiz_copy_ms.comp",
+        include_str!("../../rendering/shaders/hiz_copy_ms.comp"),
+    ),
+    (
+        "hiz_downsample.comp",
+        include_str!("../../rendering/shaders/hiz_downsample.comp"),
+    ),
+    (
+        "picking.vert",
+        include_str!("../../rendering/shaders/picking.vert"),
+    ),
+    (
+        "picking.frag",
+        include_str!("../../rendering/shaders/picking.frag"),
+    ),
+    (
+        "ssao_prepass.vert",
+        include_str!("../../rendering/shaders/ssao_prepass.vert"),
+    ),
+    (
+        "ssao_prepass.frag",
+        include_str!("../../rendering/shaders/ssao_prepass.frag"),
+    ),
+    (
+        "ssao.frag",
+        include_str!("../../rendering/shaders/ssao.frag"),
+    ),
+    (
+        "ssao_blur.frag",
+        include_str!("../../rendering/shaders/ssao_blur.frag"),
+    ),
+    (
+        "refraction.vert",
+        include_str!("../../rendering/shaders/refraction.vert"),
+    ),
+    (
+        "refraction.frag",
+        include_str!("../../rendering/shaders/refraction.frag"),
+    ),
+    (
+        "velocity.vert",
+        include_str!("../../rendering/shaders/velocity.vert"),
+    ),
+    (
+        "velocity.frag",
+        include_str!("../../rendering/shaders/velocity.frag"),
+    ),
+    (
+        "sky.vert",
+        include_str!("../../rendering/shaders/sky.vert"),
+    ),
+    (
+        "sky.frag",
+        include_str!("../../rendering/shaders/sky.frag"),
+    ),
+    (
+        "sky_irradiance.frag",
+        include_str!("../../rendering/shaders/sky_irradiance.frag"),
+    ),
+    (
+        "equirectangular_to_cube.frag",
+        include_str!("../../rendering/shaders/equirectangular_to_cube.frag"),
+    ),
+    (
+        "environment_prefilter.frag",
+        include_str!("../../rendering/shaders/environment_prefilter.frag"),
+    ),
+    (
+        "atmosphere.frag",
+        include_str!("../../rendering/shaders/atmosphere.frag"),
+    ),
+    (
+        "shadow.vert",
+        include_str!("../../rendering/shaders/shadow.vert"),
+    ),
+    (
+        "shadow.frag",
+        include_str!("../../rendering/shaders/shadow.frag"),
+    ),
+    (
+        "sprite.vert",
+        include_str!("../../rendering/shaders/sprite.vert"),
+    ),
+    (
+        "sprite.frag",
+        include_str!("../../rendering/shaders/sprite.frag"),
+    ),
+];
+
+thread_local! {
+    static BRDF_LUT: RefCell<Option<Handle<Texture2D>>> = RefCell::new(None);
+}
+
+// Copies of the engine's own assets compiled into the binary. Loose files still take
+// precedence, so shaders can be edited (and hot reloaded) while working in the repository.
+pub struct EmbeddedAssets;
+
+impl EmbeddedAssets {
+    pub fn shader_source(file_name: &str) -> Option<&'static str> {
+        SHADERS
+            .iter()
+            .find(|(name, _)| *name == file_name)
+            .map(|(_, source)| *source)
+    }
+
+    // The embedded source of a shader include, for shader::expand_includes.
+    pub fn shader_include(name: &str) -> Result<String, String> {
+        Self::shader_source(name)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Shader include {} not found.", name))
+    }
+
+    // Loads the shader from the path if the file exists. Otherwise compiles the embedded shader
+    // with the same file name.
+    pub fn load_shader<P: AsRef<Path> + Debug>(
+        stage: ShaderStage,
+        path: P,
+    ) -> Result<Shader, RendererError> {
+        if path.as_ref().is_file() {
+            return Shader::new(stage, path.as_ref());
+        }
+
+        let source = path
+            .as_ref()
+            .file_name()
+            .and_then(|name| Self::shader_source(&name.to_string_lossy()))
+            .ok_or_else(|| RendererError::MissingAsset(path.as_ref().to_path_buf()))?;
+
+        let source = expand_includes(source, &Self::shader_include).map_err(|log| {
+            RendererError::Shader {
+                stage,
+                path: path.as_ref().to_path_buf(),
+                log,
+            }
+        })?;
+
+        Shader::new_from_source(stage, path.as_ref(), source)
+    }
+
+    // Split sum BRDF lookup table for image based lighting. Generated on the GPU the first time
+    // it is requested and shared afterwards.
+    pub fn brdf_lut() -> Result<Handle<Texture2D>, RendererError> {
+        BRDF_LUT.with(|lut| {
+            let mut lut = lut.borrow_mut();
+
+            if lut.is_none() {
+                *lut = Some(Handle::new(Self::generate_brdf_lut()?));
+            }
+
+            Ok(lut.as_ref().unwrap().clone())
+        })
+    }
+
+    // Drops the table of a lost context, the next request generates it again.
+    pub(crate) fn release_brdf_lut() {
+        BRDF_LUT.with(|lut| lut.borrow_mut().take());
+    }
+
+    fn generate_brdf_lut() -> Result<Texture2D, RendererError> {
+        let lut = Texture2D::new_empty(BRDF_LUT_SIZE, BRDF_LUT_SIZE, SizedTextureFormat::Rg16f, 1);
+
+        let pipeline = ProgramPipeline::new()
+            .add_shader(&Self::load_shader(
+                ShaderStage::Compute,
+                "src/rendering/shaders/brdf_lut.comp",
+            )?)
+            .build()?;
+
+        // Matches the 8x8 local size of the shader.
+        let group_count = (BRDF_LUT_SIZE + 7) / 8;
+
+        pipeline.bind();
+
+        unsafe {
+            gl::BindImageTexture(0, lut.get_id(), 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RG16F);
+            gl::DispatchCompute(group_count, group_count, 1);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+        }
+        FrameStats::record_dispatch();
+
+        pipeline.unbind();
+
+        Ok(lut)
+    }
+}
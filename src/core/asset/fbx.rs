@@ -0,0 +1,947 @@
+use crate::{
+    core::asset::{
+        gltf::process_mesh_data,
+        obj::{self, MtlMaterial},
+        scene::{self, SceneDescription, SceneMesh, SceneNode, ScenePrimitive},
+        Handle,
+    },
+    core::math::{Mat4, Vec2, Vec3, Vec4},
+    geometry::{tangents, MeshData},
+    rendering::mesh::{Mesh, MeshImportSettings, Vertex},
+};
+use nalgebra_glm as glm;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+const MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+const HEADER_SIZE: usize = 27;
+// Node records use 64 bit offsets from this version on.
+const WIDE_RECORD_VERSION: u32 = 7500;
+
+// A node record of a binary FBX file. The root node has no name and holds the top level records.
+#[derive(Debug, Clone, Default)]
+pub struct FbxNode {
+    pub name: String,
+    pub properties: Vec<FbxProperty>,
+    pub children: Vec<FbxNode>,
+}
+
+#[derive(Debug, Clone)]
+pub enum FbxProperty {
+    Bool(bool),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Raw(Vec<u8>),
+    BoolArray(Vec<bool>),
+    I32Array(Vec<i32>),
+    I64Array(Vec<i64>),
+    F32Array(Vec<f32>),
+    F64Array(Vec<f64>),
+}
+
+impl FbxNode {
+    pub fn child(&self, name: &str) -> Option<&FbxNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a FbxNode> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn property(&self, index: usize) -> Option<&FbxProperty> {
+        self.properties.get(index)
+    }
+
+    // Object records start with their id.
+    fn id(&self) -> Option<i64> {
+        self.property(0).and_then(FbxProperty::as_i64)
+    }
+
+    // Object names are stored as "Name\0\x01Class".
+    fn object_name(&self) -> String {
+        self.property(1)
+            .and_then(FbxProperty::as_str)
+            .map(|name| name.split("\u{0}\u{1}").next().unwrap_or_default())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn object_class(&self) -> &str {
+        self.property(2)
+            .and_then(FbxProperty::as_str)
+            .unwrap_or_default()
+    }
+
+    fn child_property(&self, name: &str) -> Option<&FbxProperty> {
+        self.child(name).and_then(|child| child.property(0))
+    }
+
+    // The values of an entry of the Properties70 list, e.g. "Lcl Translation". Each entry starts
+    // with its name, type, label and flags.
+    fn property70(&self, name: &str) -> Option<&[FbxProperty]> {
+        self.child("Properties70")?
+            .children_named("P")
+            .find(|p| p.property(0).and_then(FbxProperty::as_str) == Some(name))
+            .map(|p| &p.properties[p.properties.len().min(4)..])
+    }
+
+    fn property70_f64(&self, name: &str) -> Option<f64> {
+        self.property70(name)
+            .and_then(|values| values.first())
+            .and_then(FbxProperty::as_f64)
+    }
+
+    fn property70_vec3(&self, name: &str, default: Vec3) -> Vec3 {
+        let values = self.property70(name).unwrap_or_default();
+        let component = |i: usize| values.get(i).and_then(FbxProperty::as_f64);
+
+        match (component(0), component(1), component(2)) {
+            (Some(x), Some(y), Some(z)) => Vec3::new(x as f32, y as f32, z as f32),
+            _ => default,
+        }
+    }
+}
+
+impl FbxProperty {
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            FbxProperty::I16(value) => Some(value as i64),
+            FbxProperty::I32(value) => Some(value as i64),
+            FbxProperty::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            FbxProperty::F32(value) => Some(value as f64),
+            FbxProperty::F64(value) => Some(value),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FbxProperty::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        match self {
+            FbxProperty::F32Array(values) => Some(values.iter().map(|&v| v as f64).collect()),
+            FbxProperty::F64Array(values) => Some(values.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32_slice(&self) -> Option<&[i32]> {
+        match self {
+            FbxProperty::I32Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+// Loads a binary .fbx file. Meshes, the model hierarchy and Lambert/Phong materials are imported,
+// animation, skinning and cameras are ignored. asset_path is the engine asset directory that holds
+// the material shaders.
+pub fn import<P: AsRef<Path>, A: AsRef<Path>>(
+    path: P,
+    asset_path: A,
+    settings: MeshImportSettings,
+) -> Result<SceneDescription, String> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let data = fs::read(path).map_err(|e| format!("Failed to read FBX file {:?}: {}", path, e))?;
+    let document = parse_fbx(&data).map_err(|e| format!("FBX file {:?}: {}", path, e))?;
+    let objects = Objects::new(&document)?;
+
+    let materials = objects
+        .materials
+        .iter()
+        .map(|material| {
+            let mtl_material = read_material(material, &objects, directory);
+            obj::create_material(&mtl_material, directory, asset_path.as_ref())
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut nodes = objects
+        .models
+        .iter()
+        .map(|model| SceneNode {
+            name: model.object_name(),
+            local_transform: model_transform(model),
+            transform: Mat4::identity(),
+            mesh: None,
+            parent: None,
+            children: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut roots = Vec::new();
+    for (i, model) in objects.models.iter().enumerate() {
+        let parent = model.id().and_then(|id| {
+            objects
+                .parents(id)
+                .find_map(|parent| objects.model_index(parent))
+        });
+
+        match parent {
+            Some(parent) => nodes[parent].children.push(i),
+            None => roots.push(i),
+        }
+    }
+
+    // Converts the axes and units of the file to the engine's Y up, meter based space.
+    let correction = global_transform(&document);
+    for &root in &roots {
+        nodes[root].local_transform = correction * nodes[root].local_transform;
+    }
+
+    scene::resolve_hierarchy(&mut nodes, &roots);
+
+    // Geometry can be shared by several models, so each is split and uploaded only once.
+    let mut geometry_meshes: HashMap<i64, Vec<(usize, Handle<Mesh>)>> = HashMap::new();
+    let mut meshes = Vec::new();
+
+    for (i, model) in objects.models.iter().enumerate() {
+        let model_id = model.id().unwrap_or_default();
+
+        let geometry = match objects
+            .children(model_id)
+            .find_map(|id| objects.geometry(id))
+        {
+            Some(geometry) => geometry,
+            None => continue,
+        };
+        let geometry_id = geometry.id().unwrap_or_default();
+
+        if !geometry_meshes.contains_key(&geometry_id) {
+            let primitives = read_geometry(geometry)?
+                .into_iter()
+                .map(|(slot, mesh_data)| {
+                    (slot, Handle::new(process_mesh_data(mesh_data, settings)))
+                })
+                .collect();
+
+            geometry_meshes.insert(geometry_id, primitives);
+        }
+
+        // The material indices of the geometry refer to the materials of the model in the
+        // order they are connected.
+        let material_slots = objects
+            .children(model_id)
+            .filter_map(|id| objects.material_index(id))
+            .collect::<Vec<_>>();
+
+        let primitives = geometry_meshes[&geometry_id]
+            .iter()
+            .map(|(slot, mesh)| ScenePrimitive {
+                mesh: mesh.clone(),
+                material: material_slots.get(*slot).cloned(),
+            })
+            .collect();
+
+        nodes[i].mesh = Some(meshes.len());
+        meshes.push(SceneMesh {
+            name: nodes[i].name.clone(),
+            primitives,
+        });
+    }
+
+    Ok(SceneDescription {
+        nodes,
+        roots,
+        meshes,
+        materials,
+        animations: vec![],
+    })
+}
+
+// Merges every mesh of the file into a single mesh in the space of the file. Used when an FBX
+// file is loaded as a plain Mesh asset.
+pub(crate) fn read_mesh_data(data: &[u8]) -> Result<MeshData, String> {
+    let document = parse_fbx(data)?;
+    let objects = Objects::new(&document)?;
+
+    let mut mesh_data = MeshData::default();
+    for geometry in objects.geometries.values() {
+        for (_, primitive) in read_geometry(geometry)? {
+            mesh_data.append(&primitive);
+        }
+    }
+
+    Ok(mesh_data)
+}
+
+// The objects of the document that the importer understands, and how they are connected.
+struct Objects<'a> {
+    models: Vec<&'a FbxNode>,
+    materials: Vec<&'a FbxNode>,
+    geometries: HashMap<i64, &'a FbxNode>,
+    textures: HashMap<i64, &'a FbxNode>,
+    model_indices: HashMap<i64, usize>,
+    material_indices: HashMap<i64, usize>,
+    // (child, parent, property). Connections to a property name have one.
+    connections: Vec<(i64, i64, Option<&'a str>)>,
+}
+
+impl<'a> Objects<'a> {
+    fn new(document: &'a FbxNode) -> Result<Self, String> {
+        let root = document
+            .child("Objects")
+            .ok_or_else(|| "The file has no objects.".to_string())?;
+
+        let mut objects = Objects {
+            models: Vec::new(),
+            materials: Vec::new(),
+            geometries: HashMap::new(),
+            textures: HashMap::new(),
+            model_indices: HashMap::new(),
+            material_indices: HashMap::new(),
+            connections: Vec::new(),
+        };
+
+        for object in &root.children {
+            let id = match object.id() {
+                Some(id) => id,
+                None => continue,
+            };
+
+            match object.name.as_str() {
+                "Model" => {
+                    objects.model_indices.insert(id, objects.models.len());
+                    objects.models.push(object);
+                }
+                "Material" => {
+                    objects.material_indices.insert(id, objects.materials.len());
+                    objects.materials.push(object);
+                }
+                "Geometry" if object.object_class() == "Mesh" => {
+                    objects.geometries.insert(id, object);
+                }
+                "Texture" => {
+                    objects.textures.insert(id, object);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(connections) = document.child("Connections") {
+            for connection in connections.children_named("C") {
+                let child = connection.property(1).and_then(FbxProperty::as_i64);
+                let parent = connection.property(2).and_then(FbxProperty::as_i64);
+                let property = connection.property(3).and_then(FbxProperty::as_str);
+
+                if let (Some(child), Some(parent)) = (child, parent) {
+                    objects.connections.push((child, parent, property));
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    fn children(&self, parent: i64) -> impl Iterator<Item = i64> + '_ {
+        self.connections
+            .iter()
+            .filter(move |(_, p, property)| *p == parent && property.is_none())
+            .map(|(child, _, _)| *child)
+    }
+
+    fn parents(&self, child: i64) -> impl Iterator<Item = i64> + '_ {
+        self.connections
+            .iter()
+            .filter(move |(c, _, property)| *c == child && property.is_none())
+            .map(|(_, parent, _)| *parent)
+    }
+
+    // The texture connected to the given property of an object, e.g. "DiffuseColor".
+    fn texture(&self, parent: i64, property: &str) -> Option<&'a FbxNode> {
+        self.connections
+            .iter()
+            .filter(|(_, p, connection_property)| {
+                *p == parent && *connection_property == Some(property)
+            })
+            .find_map(|(child, _, _)| self.textures.get(child).cloned())
+    }
+
+    fn geometry(&self, id: i64) -> Option<&'a FbxNode> {
+        self.geometries.get(&id).cloned()
+    }
+
+    fn model_index(&self, id: i64) -> Option<usize> {
+        self.model_indices.get(&id).cloned()
+    }
+
+    fn material_index(&self, id: i64) -> Option<usize> {
+        self.material_indices.get(&id).cloned()
+    }
+}
+
+fn read_material(material: &FbxNode, objects: &Objects, directory: &Path) -> MtlMaterial {
+    let color = |name: &str, factor: &str, default: [f32; 3]| {
+        let color = material.property70_vec3(name, Vec3::new(default[0], default[1], default[2]));
+        let factor = material.property70_f64(factor).unwrap_or(1.0) as f32;
+        [color.x * factor, color.y * factor, color.z * factor]
+    };
+
+    let texture_path = |property: &str| {
+        let texture = objects.texture(material.id().unwrap_or_default(), property)?;
+        let relative = texture
+            .child_property("RelativeFilename")
+            .and_then(FbxProperty::as_str)
+            .filter(|path| !path.is_empty());
+        let absolute = texture
+            .child_property("FileName")
+            .and_then(FbxProperty::as_str)
+            .filter(|path| !path.is_empty());
+
+        // Absolute paths usually point to the machine the file was authored on. Fall back to
+        // looking for the file next to the FBX file.
+        match (relative, absolute) {
+            (Some(relative), _) => Some(relative.replace('\\', "/")),
+            (None, Some(absolute)) if Path::new(absolute).is_file() => Some(absolute.to_string()),
+            (None, Some(absolute)) => Path::new(&absolute.replace('\\', "/"))
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .filter(|name| directory.join(name).is_file()),
+            (None, None) => None,
+        }
+    };
+
+    MtlMaterial {
+        name: material.object_name(),
+        diffuse: color("DiffuseColor", "DiffuseFactor", [0.8, 0.8, 0.8]),
+        specular: color("SpecularColor", "SpecularFactor", [0.0, 0.0, 0.0]),
+        shininess: material
+            .property70_f64("ShininessExponent")
+            .or_else(|| material.property70_f64("Shininess"))
+            .unwrap_or(0.0) as f32,
+        dissolve: material.property70_f64("Opacity").unwrap_or(1.0) as f32,
+        roughness: None,
+        metallic: None,
+        diffuse_map: texture_path("DiffuseColor"),
+        normal_map: texture_path("NormalMap"),
+    }
+}
+
+// T * Rpre * R * Rpost^-1 * S. Pivots and offsets are ignored.
+fn model_transform(model: &FbxNode) -> Mat4 {
+    let translation = model.property70_vec3("Lcl Translation", Vec3::zeros());
+    let rotation = model.property70_vec3("Lcl Rotation", Vec3::zeros());
+    let scaling = model.property70_vec3("Lcl Scaling", Vec3::new(1.0, 1.0, 1.0));
+    let pre_rotation = model.property70_vec3("PreRotation", Vec3::zeros());
+    let post_rotation = model.property70_vec3("PostRotation", Vec3::zeros());
+    let rotation_order = model.property70_f64("RotationOrder").unwrap_or(0.0) as usize;
+
+    glm::translation(&translation)
+        * euler_rotation(&pre_rotation, 0)
+        * euler_rotation(&rotation, rotation_order)
+        * euler_rotation(&post_rotation, 0).transpose()
+        * glm::scaling(&scaling)
+}
+
+// Angles in degrees. The order names the axis that is applied first, e.g. 0 (XYZ) is Rz * Ry * Rx.
+fn euler_rotation(angles: &Vec3, order: usize) -> Mat4 {
+    let axis = |i: usize| {
+        let mut axis = Vec3::zeros();
+        axis[i] = 1.0;
+        glm::rotation(angles[i].to_radians(), &axis)
+    };
+
+    let [first, second, third] = match order {
+        1 => [0, 2, 1],
+        2 => [1, 2, 0],
+        3 => [1, 0, 2],
+        4 => [2, 0, 1],
+        5 => [2, 1, 0],
+        _ => [0, 1, 2],
+    };
+
+    axis(third) * axis(second) * axis(first)
+}
+
+// Maps the axis system of the file to the engine's (+X right, +Y up, +Z front) and its unit to
+// meters.
+fn global_transform(document: &FbxNode) -> Mat4 {
+    let settings = match document.child("GlobalSettings") {
+        Some(settings) => settings,
+        None => return Mat4::identity(),
+    };
+
+    let axis = |axis_name: &str, sign_name: &str, default: usize| {
+        let index = settings
+            .property70_f64(axis_name)
+            .map_or(default, |a| a as usize);
+        let sign = settings.property70_f64(sign_name).unwrap_or(1.0) as f32;
+        (index, sign)
+    };
+
+    let right = axis("CoordAxis", "CoordAxisSign", 0);
+    let up = axis("UpAxis", "UpAxisSign", 1);
+    let front = axis("FrontAxis", "FrontAxisSign", 2);
+
+    let mut rotation = Mat4::identity();
+    let mut used = [false; 3];
+
+    for (row, &(index, sign)) in [right, up, front].iter().enumerate() {
+        if index > 2 || used[index] {
+            log::warn!("Invalid FBX axis system. Keeping the axes of the file.");
+            rotation = Mat4::identity();
+            break;
+        }
+
+        used[index] = true;
+        rotation[(row, row)] = 0.0;
+        rotation[(row, index)] = sign.signum();
+    }
+
+    // The unit is given in centimeters.
+    let unit_scale = settings.property70_f64("UnitScaleFactor").unwrap_or(1.0) as f32 / 100.0;
+
+    glm::scaling(&Vec3::new(unit_scale, unit_scale, unit_scale)) * rotation
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mapping {
+    ByPolygonVertex,
+    ByControlPoint,
+    ByPolygon,
+    AllSame,
+}
+
+// One of the LayerElement* records of a geometry, e.g. the normals.
+struct LayerElement {
+    mapping: Mapping,
+    values: Vec<f64>,
+    indices: Option<Vec<i32>>,
+}
+
+impl LayerElement {
+    fn read(
+        geometry: &FbxNode,
+        element: &str,
+        values: &str,
+        indices: Option<&str>,
+    ) -> Result<Option<Self>, String> {
+        Self::read_layer(geometry, element, 0, values, indices)
+    }
+
+    // Geometries can have several records of an element, e.g. one per UV channel.
+    fn read_layer(
+        geometry: &FbxNode,
+        element: &str,
+        layer: usize,
+        values: &str,
+        indices: Option<&str>,
+    ) -> Result<Option<Self>, String> {
+        let element_node = match geometry.children_named(element).nth(layer) {
+            Some(element_node) => element_node,
+            None => return Ok(None),
+        };
+
+        let string = |name: &str| {
+            element_node
+                .child_property(name)
+                .and_then(FbxProperty::as_str)
+                .unwrap_or_default()
+        };
+
+        let mapping = match string("MappingInformationType") {
+            "ByPolygonVertex" => Mapping::ByPolygonVertex,
+            "ByVertice" | "ByVertex" | "ByControlPoint" => Mapping::ByControlPoint,
+            "ByPolygon" => Mapping::ByPolygon,
+            "AllSame" => Mapping::AllSame,
+            mapping => return Err(format!("Unsupported {} mapping '{}'.", element, mapping)),
+        };
+
+        let values = element_node
+            .child_property(values)
+            .and_then(|values| {
+                values.to_f64_vec().or_else(|| {
+                    values
+                        .as_i32_slice()
+                        .map(|values| values.iter().map(|&v| v as f64).collect())
+                })
+            })
+            .ok_or_else(|| format!("{} has no values.", element))?;
+
+        let indices = match string("ReferenceInformationType") {
+            "IndexToDirect" | "Index" => indices
+                .and_then(|indices| element_node.child_property(indices))
+                .and_then(FbxProperty::as_i32_slice)
+                .map(<[i32]>::to_vec),
+            _ => None,
+        };
+
+        Ok(Some(Self {
+            mapping,
+            values,
+            indices,
+        }))
+    }
+
+    // Index of the value that applies to a polygon corner.
+    fn index(&self, polygon_vertex: usize, control_point: usize, polygon: usize) -> Option<usize> {
+        let index = match self.mapping {
+            Mapping::ByPolygonVertex => polygon_vertex,
+            Mapping::ByControlPoint => control_point,
+            Mapping::ByPolygon => polygon,
+            Mapping::AllSame => 0,
+        };
+
+        match &self.indices {
+            Some(indices) => indices
+                .get(index)
+                .filter(|&&index| index >= 0)
+                .map(|&index| index as usize),
+            None => Some(index),
+        }
+    }
+
+    fn value<T, F: Fn(&[f64]) -> T>(&self, index: usize, size: usize, convert: F) -> Option<T> {
+        self.values
+            .get(index * size..(index + 1) * size)
+            .map(convert)
+    }
+}
+
+// Control point and the normal, UV, color and second UV indices of a polygon corner.
+type CornerKey = (
+    usize,
+    Option<usize>,
+    Option<usize>,
+    Option<usize>,
+    Option<usize>,
+);
+
+#[derive(Default)]
+struct PrimitiveBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    lookup: HashMap<CornerKey, u32>,
+}
+
+// Splits the geometry into one triangle list per material slot. Polygons are fan triangulated.
+fn read_geometry(geometry: &FbxNode) -> Result<BTreeMap<usize, MeshData>, String> {
+    let positions = geometry
+        .child_property("Vertices")
+        .and_then(FbxProperty::to_f64_vec)
+        .ok_or_else(|| "FBX geometry has no vertices.".to_string())?;
+    let polygon_vertices = geometry
+        .child_property("PolygonVertexIndex")
+        .and_then(FbxProperty::as_i32_slice)
+        .ok_or_else(|| "FBX geometry has no polygons.".to_string())?;
+
+    let normals = LayerElement::read(
+        geometry,
+        "LayerElementNormal",
+        "Normals",
+        Some("NormalsIndex"),
+    )?
+    .ok_or_else(|| "FBX geometry has no normals.".to_string())?;
+    let tex_coords = LayerElement::read(geometry, "LayerElementUV", "UV", Some("UVIndex"))?;
+    let tex_coords1 =
+        LayerElement::read_layer(geometry, "LayerElementUV", 1, "UV", Some("UVIndex"))?;
+    let colors = LayerElement::read(geometry, "LayerElementColor", "Colors", Some("ColorIndex"))?;
+    // The values of the material layer are the material slots themselves.
+    let materials = LayerElement::read(geometry, "LayerElementMaterial", "Materials", None)?;
+
+    let control_point_count = positions.len() / 3;
+    let mut builders: BTreeMap<usize, PrimitiveBuilder> = BTreeMap::new();
+    let mut polygon = Vec::new();
+    let mut polygon_index = 0;
+
+    for (polygon_vertex, &index) in polygon_vertices.iter().enumerate() {
+        // The last corner of a polygon is stored as -(index + 1).
+        let control_point = if index < 0 { !index } else { index } as usize;
+
+        if control_point >= control_point_count {
+            return Err(format!(
+                "FBX polygon references vertex {} of {}.",
+                control_point, control_point_count
+            ));
+        }
+
+        polygon.push((polygon_vertex, control_point));
+
+        if index >= 0 {
+            continue;
+        }
+
+        let slot = materials
+            .as_ref()
+            .and_then(|materials| {
+                let index = materials.index(polygon_vertex, control_point, polygon_index)?;
+                materials.value(index, 1, |v| v[0] as usize)
+            })
+            .unwrap_or(0);
+        let builder = builders.entry(slot).or_default();
+
+        let mut corners = Vec::with_capacity(polygon.len());
+        for &(polygon_vertex, control_point) in &polygon {
+            let normal = normals.index(polygon_vertex, control_point, polygon_index);
+            let tex_coord = tex_coords
+                .as_ref()
+                .and_then(|t| t.index(polygon_vertex, control_point, polygon_index));
+            let color = colors
+                .as_ref()
+                .and_then(|c| c.index(polygon_vertex, control_point, polygon_index));
+            let tex_coord1 = tex_coords1
+                .as_ref()
+                .and_then(|t| t.index(polygon_vertex, control_point, polygon_index));
+
+            let key = (control_point, normal, tex_coord, color, tex_coord1);
+            let next_index = builder.vertices.len() as u32;
+
+            if let Some(&index) = builder.lookup.get(&key) {
+                corners.push(index);
+                continue;
+            }
+
+            builder.vertices.push(Vertex {
+                position: Vec3::new(
+                    positions[control_point * 3] as f32,
+                    positions[control_point * 3 + 1] as f32,
+                    positions[control_point * 3 + 2] as f32,
+                ),
+                normal: normal
+                    .and_then(|n| {
+                        normals.value(n, 3, |v| Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32))
+                    })
+                    .unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0)),
+                tangent: Vec4::new(0.0, 0.0, 0.0, 1.0),
+                // FBX puts the texture origin at the bottom left, glTF and the engine at the top
+                // left.
+                tex_coord: tex_coord
+                    .and_then(|t| {
+                        let tex_coords = tex_coords.as_ref()?;
+                        tex_coords.value(t, 2, |v| Vec2::new(v[0] as f32, 1.0 - v[1] as f32))
+                    })
+                    .unwrap_or_else(|| Vec2::new(0.0, 0.0)),
+                color: color
+                    .and_then(|c| {
+                        let colors = colors.as_ref()?;
+                        colors.value(c, 4, |v| {
+                            Vec4::new(v[0] as f32, v[1] as f32, v[2] as f32, v[3] as f32)
+                        })
+                    })
+                    .unwrap_or_else(|| Vec4::new(1.0, 1.0, 1.0, 1.0)),
+                tex_coord1: tex_coord1
+                    .and_then(|t| {
+                        let tex_coords1 = tex_coords1.as_ref()?;
+                        tex_coords1.value(t, 2, |v| Vec2::new(v[0] as f32, 1.0 - v[1] as f32))
+                    })
+                    .unwrap_or_else(|| Vec2::new(0.0, 0.0)),
+            });
+            builder.lookup.insert(key, next_index);
+            corners.push(next_index);
+        }
+
+        for i in 1..corners.len().saturating_sub(1) {
+            builder
+                .indices
+                .extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+        }
+
+        polygon.clear();
+        polygon_index += 1;
+    }
+
+    Ok(builders
+        .into_iter()
+        .map(|(slot, builder)| {
+            let mut mesh_data = MeshData::new(builder.vertices, builder.indices);
+            tangents::generate_tangents(&mut mesh_data);
+
+            (slot, mesh_data)
+        })
+        .collect())
+}
+
+// Parses a binary FBX file into its node tree. ASCII FBX files are not supported.
+pub fn parse_fbx(data: &[u8]) -> Result<FbxNode, String> {
+    if data.len() < HEADER_SIZE || !data.starts_with(MAGIC) {
+        return Err("Not a binary FBX file. ASCII FBX files are not supported.".to_string());
+    }
+
+    let mut reader = Reader {
+        data,
+        position: MAGIC.len() + 2,
+    };
+    let version = reader.read_u32()?;
+    let wide = version >= WIDE_RECORD_VERSION;
+
+    let mut root = FbxNode::default();
+    while let Some(node) = read_node(&mut reader, wide)? {
+        root.children.push(node);
+    }
+
+    Ok(root)
+}
+
+// None for the null record that terminates a list of nodes.
+fn read_node(reader: &mut Reader, wide: bool) -> Result<Option<FbxNode>, String> {
+    if reader.position >= reader.data.len() {
+        return Ok(None);
+    }
+
+    let (end_offset, property_count) = if wide {
+        let end_offset = reader.read_u64()? as usize;
+        let property_count = reader.read_u64()? as usize;
+        reader.read_u64()?;
+        (end_offset, property_count)
+    } else {
+        let end_offset = reader.read_u32()? as usize;
+        let property_count = reader.read_u32()? as usize;
+        reader.read_u32()?;
+        (end_offset, property_count)
+    };
+    let name_length = reader.read_u8()? as usize;
+
+    if end_offset == 0 {
+        return Ok(None);
+    }
+
+    if end_offset > reader.data.len() {
+        return Err("FBX node record ends past the end of the file.".to_string());
+    }
+
+    let name = String::from_utf8_lossy(reader.read_bytes(name_length)?).into_owned();
+
+    let properties = (0..property_count)
+        .map(|_| read_property(reader))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut children = Vec::new();
+    while reader.position < end_offset {
+        match read_node(reader, wide)? {
+            Some(child) => children.push(child),
+            None => break,
+        }
+    }
+
+    reader.position = end_offset;
+
+    Ok(Some(FbxNode {
+        name,
+        properties,
+        children,
+    }))
+}
+
+fn read_property(reader: &mut Reader) -> Result<FbxProperty, String> {
+    let property = match reader.read_u8()? {
+        b'C' => FbxProperty::Bool(reader.read_u8()? != 0),
+        b'Y' => FbxProperty::I16(i16::from_le_bytes(reader.read_fixed()?)),
+        b'I' => FbxProperty::I32(i32::from_le_bytes(reader.read_fixed()?)),
+        b'L' => FbxProperty::I64(i64::from_le_bytes(reader.read_fixed()?)),
+        b'F' => FbxProperty::F32(f32::from_bits(reader.read_u32()?)),
+        b'D' => FbxProperty::F64(f64::from_bits(reader.read_u64()?)),
+        b'S' => {
+            let length = reader.read_u32()? as usize;
+            FbxProperty::String(String::from_utf8_lossy(reader.read_bytes(length)?).into_owned())
+        }
+        b'R' => {
+            let length = reader.read_u32()? as usize;
+            FbxProperty::Raw(reader.read_bytes(length)?.to_vec())
+        }
+        b'b' => FbxProperty::BoolArray(read_array(reader, 1, |b| b[0] != 0)?),
+        b'i' => FbxProperty::I32Array(read_array(reader, 4, |b| {
+            i32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        })?),
+        b'l' => FbxProperty::I64Array(read_array(reader, 8, |b| {
+            i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+        })?),
+        b'f' => FbxProperty::F32Array(read_array(reader, 4, |b| {
+            f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        })?),
+        b'd' => FbxProperty::F64Array(read_array(reader, 8, |b| {
+            f64::from_bits(u64::from_le_bytes([
+                b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+            ]))
+        })?),
+        code => return Err(format!("Unknown FBX property type '{}'.", code as char)),
+    };
+
+    Ok(property)
+}
+
+// Array properties are optionally zlib compressed.
+fn read_array<T, F: Fn(&[u8]) -> T>(
+    reader: &mut Reader,
+    element_size: usize,
+    convert: F,
+) -> Result<Vec<T>, String> {
+    let length = reader.read_u32()? as usize;
+    let encoding = reader.read_u32()?;
+    let size = reader.read_u32()? as usize;
+    let bytes = reader.read_bytes(size)?;
+
+    let decompressed;
+    let bytes = match encoding {
+        0 => bytes,
+        1 => {
+            decompressed = miniz_oxide::inflate::decompress_to_vec_zlib(bytes)
+                .map_err(|e| format!("Failed to decompress FBX array: {:?}", e))?;
+            &decompressed[..]
+        }
+        _ => return Err(format!("Unknown FBX array encoding {}.", encoding)),
+    };
+
+    if bytes.len() != length * element_size {
+        return Err("FBX array size doesn't match its length.".to_string());
+    }
+
+    Ok(bytes.chunks_exact(element_size).map(convert).collect())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| "Unexpected end of FBX file.".to_string())?;
+
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn read_fixed<A: Default + AsMut<[u8]>>(&mut self) -> Result<A, String> {
+        let mut array = A::default();
+        let size = array.as_mut().len();
+        array.as_mut().copy_from_slice(self.read_bytes(size)?);
+
+        Ok(array)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.read_fixed()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read_fixed()?))
+    }
+}
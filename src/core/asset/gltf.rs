@@ -0,0 +1,466 @@
+use crate::{
+    core::animation::clip::{AnimationClip, Interpolation, JointTracks, Track},
+    core::asset::{
+        scene::{self, SceneDescription, SceneMesh, SceneNode, ScenePrimitive},
+        Handle,
+    },
+    core::math::{Mat4, Quat, Vec2, Vec3, Vec4},
+    geometry::{optimize, tangents, MeshData},
+    rendering::{
+        material::PbsMetallicRoughnessMaterial,
+        mesh::{Mesh, MeshImportSettings, Vertex},
+        texture::Texture2D,
+    },
+};
+use ::gltf::{
+    animation::{util::ReadOutputs, Interpolation as GltfInterpolation},
+    buffer,
+    image::{Data as ImageData, Format},
+    Document, Primitive,
+};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+// Loads a .gltf or .glb file. asset_path is the engine asset directory that holds the material
+// shaders.
+pub fn import<P: AsRef<Path>, A: AsRef<Path>>(
+    path: P,
+    asset_path: A,
+    settings: MeshImportSettings,
+) -> Result<SceneDescription, String> {
+    let (document, buffers, images) = ::gltf::import(path.as_ref())
+        .map_err(|e| format!("Failed to load Gltf file {:?}: {}", path.as_ref(), e))?;
+
+    let meshes = document
+        .meshes()
+        .map(|mesh| {
+            let primitives = mesh
+                .primitives()
+                .map(|primitive| {
+                    let mesh_data = read_primitive(&primitive, &buffers)?;
+
+                    Ok(ScenePrimitive {
+                        mesh: Handle::new(process_mesh_data(mesh_data, settings)),
+                        material: primitive.material().index(),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+
+            Ok(SceneMesh {
+                name: mesh.name().unwrap_or_default().to_string(),
+                primitives,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let materials = import_materials(&document, &images, asset_path.as_ref())?;
+
+    let (nodes, roots) = import_nodes(&document);
+    let animations = import_animations(&document, &buffers)?;
+
+    Ok(SceneDescription {
+        nodes,
+        roots,
+        meshes,
+        materials,
+        animations,
+    })
+}
+
+// Reads the vertices and indices of a triangle list primitive. Tangents are generated when the
+// primitive has none.
+pub fn read_primitive(primitive: &Primitive, buffers: &[buffer::Data]) -> Result<MeshData, String> {
+    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+        return Err(format!(
+            "Unsupported primitive mode {:?}. Only triangle lists are supported.",
+            primitive.mode()
+        ));
+    }
+
+    let reader = primitive.reader(|buffer| {
+        buffers
+            .get(buffer.index())
+            .and_then(|buffer::Data(data)| data.as_slice().into())
+    });
+
+    let positions = reader
+        .read_positions()
+        .ok_or_else(|| "Mesh has no positions.".to_string())?;
+    let normals = reader
+        .read_normals()
+        .ok_or_else(|| "Mesh has no normals.".to_string())?;
+    let tangents = reader.read_tangents();
+    let has_tangents = tangents.is_some();
+    let mut tangents = tangents.into_iter().flatten();
+    let mut tex_coords = reader
+        .read_tex_coords(0)
+        .map(|tex_coords| tex_coords.into_f32())
+        .into_iter()
+        .flatten();
+    let mut tex_coords1 = reader
+        .read_tex_coords(1)
+        .map(|tex_coords| tex_coords.into_f32())
+        .into_iter()
+        .flatten();
+    let mut colors = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgba_f32())
+        .into_iter()
+        .flatten();
+
+    let vertices = positions
+        .zip(normals)
+        .map(|(v, n)| Vertex {
+            position: Vec3::new(v[0], v[1], v[2]),
+            normal: Vec3::new(n[0], n[1], n[2]),
+            tangent: tangents
+                .next()
+                .map_or(Vec4::new(0.0, 0.0, 0.0, 1.0), |t| t.into()),
+            tex_coord: tex_coords
+                .next()
+                .map_or(Vec2::new(0.0, 0.0), |tc| Vec2::new(tc[0], tc[1])),
+            color: colors
+                .next()
+                .map_or(Vec4::new(1.0, 1.0, 1.0, 1.0), |c| c.into()),
+            tex_coord1: tex_coords1
+                .next()
+                .map_or(Vec2::new(0.0, 0.0), |tc| Vec2::new(tc[0], tc[1])),
+        })
+        .collect::<Vec<_>>();
+
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    let mut mesh_data = MeshData::new(vertices, indices);
+
+    if !has_tangents {
+        log::info!("Mesh has no tangents. Generating...");
+        tangents::generate_tangents(&mut mesh_data);
+    }
+
+    Ok(mesh_data)
+}
+
+pub(crate) fn process_mesh_data(mut mesh_data: MeshData, settings: MeshImportSettings) -> Mesh {
+    prepare_mesh_data(&mut mesh_data, settings);
+    upload_mesh_data(mesh_data, settings)
+}
+
+// CPU side part of process_mesh_data.
+pub(crate) fn prepare_mesh_data(mesh_data: &mut MeshData, settings: MeshImportSettings) {
+    if settings.scale != 1.0 {
+        for vertex in &mut mesh_data.vertices {
+            vertex.position *= settings.scale;
+        }
+    }
+
+    if settings.optimize {
+        optimize::optimize(mesh_data);
+    }
+}
+
+pub(crate) fn upload_mesh_data(mesh_data: MeshData, settings: MeshImportSettings) -> Mesh {
+    if settings.quantize {
+        mesh_data.into_quantized_mesh()
+    } else {
+        mesh_data.into_mesh()
+    }
+}
+
+fn import_nodes(document: &Document) -> (Vec<SceneNode>, Vec<usize>) {
+    let mut nodes = document
+        .nodes()
+        .map(|node| SceneNode {
+            name: node.name().unwrap_or_default().to_string(),
+            local_transform: Mat4::from(node.transform().matrix()),
+            transform: Mat4::identity(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let roots = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|node| node.index()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    scene::resolve_hierarchy(&mut nodes, &roots);
+
+    (nodes, roots)
+}
+
+// Node transform animations. Morph target weights are not supported and are skipped. Cubic
+// spline keyframes are interpolated linearly between their values, ignoring the tangents.
+fn import_animations(
+    document: &Document,
+    buffers: &[buffer::Data],
+) -> Result<Vec<Rc<AnimationClip>>, String> {
+    document
+        .animations()
+        .map(|animation| {
+            let name = animation
+                .name()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Animation {}", animation.index()));
+
+            let mut tracks: Vec<JointTracks> = vec![];
+
+            for channel in animation.channels() {
+                let reader = channel.reader(|buffer| {
+                    buffers
+                        .get(buffer.index())
+                        .and_then(|buffer::Data(data)| data.as_slice().into())
+                });
+
+                let times = match reader.read_inputs() {
+                    Some(inputs) => inputs.collect::<Vec<_>>(),
+                    None => continue,
+                };
+
+                let (interpolation, stride, offset) = match channel.sampler().interpolation() {
+                    GltfInterpolation::Step => (Interpolation::Step, 1, 0),
+                    GltfInterpolation::Linear => (Interpolation::Linear, 1, 0),
+                    // In tangent, value, out tangent.
+                    GltfInterpolation::CubicSpline => (Interpolation::Linear, 3, 1),
+                };
+
+                let node = channel.target().node().index();
+
+                let index = match tracks.iter().position(|tracks| tracks.joint == node) {
+                    Some(index) => index,
+                    None => {
+                        tracks.push(JointTracks {
+                            joint: node,
+                            translation: None,
+                            rotation: None,
+                            scale: None,
+                        });
+                        tracks.len() - 1
+                    }
+                };
+
+                let error =
+                    |e: String| format!("Invalid track for node {} in '{}': {}", node, name, e);
+
+                match reader.read_outputs() {
+                    Some(ReadOutputs::Translations(translations)) => {
+                        let values = translations
+                            .skip(offset)
+                            .step_by(stride)
+                            .map(Vec3::from)
+                            .collect();
+
+                        tracks[index].translation =
+                            Some(Track::new(times, values, interpolation).map_err(error)?)
+                    }
+                    Some(ReadOutputs::Rotations(rotations)) => {
+                        let values = rotations
+                            .into_f32()
+                            .skip(offset)
+                            .step_by(stride)
+                            .map(|[x, y, z, w]| Quat::new(w, x, y, z))
+                            .collect();
+
+                        tracks[index].rotation =
+                            Some(Track::new(times, values, interpolation).map_err(error)?)
+                    }
+                    Some(ReadOutputs::Scales(scales)) => {
+                        let values = scales
+                            .skip(offset)
+                            .step_by(stride)
+                            .map(Vec3::from)
+                            .collect();
+
+                        tracks[index].scale =
+                            Some(Track::new(times, values, interpolation).map_err(error)?)
+                    }
+                    _ => {}
+                }
+            }
+
+            Ok(Rc::new(AnimationClip::new(&name, tracks)))
+        })
+        .collect()
+}
+
+fn import_materials(
+    document: &Document,
+    images: &[ImageData],
+    asset_path: &Path,
+) -> Result<Vec<PbsMetallicRoughnessMaterial>, String> {
+    let mut textures = TextureCache::new(images);
+
+    document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+
+            let albedo = pbr
+                .base_color_texture()
+                .and_then(|info| textures.get(info.texture().source().index(), true))
+                .unwrap_or_else(|| Handle::new(Texture2D::new_from_color([255, 255, 255])));
+
+            let normals = material
+                .normal_texture()
+                .and_then(|info| textures.get(info.texture().source().index(), false))
+                .unwrap_or_else(|| Handle::new(Texture2D::new_from_color([128, 128, 255])));
+
+            let metallic_roughness_ao = textures.metallic_roughness_ao(
+                pbr.metallic_roughness_texture()
+                    .map(|info| info.texture().source().index()),
+                material
+                    .occlusion_texture()
+                    .map(|info| info.texture().source().index()),
+            );
+
+            let mut pbs_material = PbsMetallicRoughnessMaterial::new(
+                asset_path,
+                albedo,
+                metallic_roughness_ao,
+                normals,
+                None,
+            )?;
+
+            pbs_material.set_base_color(pbr.base_color_factor().into());
+            pbs_material.set_metallic_scale(pbr.metallic_factor());
+            pbs_material.set_roughness_scale(pbr.roughness_factor());
+
+            Ok(pbs_material)
+        })
+        .collect()
+}
+
+// Uploads every image at most once per color space.
+struct TextureCache<'a> {
+    images: &'a [ImageData],
+    textures: HashMap<(usize, bool), Handle<Texture2D>>,
+    packed: HashMap<(Option<usize>, Option<usize>), Handle<Texture2D>>,
+}
+
+impl<'a> TextureCache<'a> {
+    fn new(images: &'a [ImageData]) -> Self {
+        Self {
+            images,
+            textures: HashMap::new(),
+            packed: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, image: usize, is_srgb: bool) -> Option<Handle<Texture2D>> {
+        if let Some(texture) = self.textures.get(&(image, is_srgb)) {
+            return Some(texture.clone());
+        }
+
+        let texture = match to_dynamic_image(&self.images[image])
+            .and_then(|dynamic_image| Texture2D::new_from_image(dynamic_image, true, is_srgb))
+        {
+            Ok(texture) => Handle::new(texture),
+            Err(e) => {
+                log::warn!("Failed to import Gltf image {}: {}", image, e);
+                return None;
+            }
+        };
+
+        self.textures.insert((image, is_srgb), texture.clone());
+
+        Some(texture)
+    }
+
+    // glTF stores roughness in G and metalness in B of one texture and occlusion in R of another
+    // (often the same) texture. The engine expects [Metalness (R), Roughness (G), AO (B)].
+    fn metallic_roughness_ao(
+        &mut self,
+        metallic_roughness: Option<usize>,
+        occlusion: Option<usize>,
+    ) -> Handle<Texture2D> {
+        if let Some(texture) = self.packed.get(&(metallic_roughness, occlusion)) {
+            return texture.clone();
+        }
+
+        let metallic_roughness_image = metallic_roughness.map(|i| &self.images[i]);
+        let occlusion_image = occlusion.map(|i| &self.images[i]);
+
+        let (width, height) = metallic_roughness_image
+            .or(occlusion_image)
+            .map_or((1, 1), |image| (image.width, image.height));
+
+        let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let metallic = metallic_roughness_image
+                    .and_then(|image| sample(image, x, y, width, height, 2))
+                    .unwrap_or(255);
+                let roughness = metallic_roughness_image
+                    .and_then(|image| sample(image, x, y, width, height, 1))
+                    .unwrap_or(255);
+                let ao = occlusion_image
+                    .and_then(|image| sample(image, x, y, width, height, 0))
+                    .unwrap_or(255);
+
+                pixels.extend_from_slice(&[metallic, roughness, ao]);
+            }
+        }
+
+        let image = DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, pixels).unwrap());
+        let texture = Handle::new(Texture2D::new_from_image(image, true, false).unwrap());
+
+        self.packed
+            .insert((metallic_roughness, occlusion), texture.clone());
+
+        texture
+    }
+}
+
+fn component_count(format: Format) -> Option<u32> {
+    match format {
+        Format::R8 => Some(1),
+        Format::R8G8 => Some(2),
+        Format::R8G8B8 => Some(3),
+        Format::R8G8B8A8 => Some(4),
+        _ => None,
+    }
+}
+
+// Nearest neighbour lookup of an 8-bit channel at the coordinates of an image with the given
+// size.
+fn sample(image: &ImageData, x: u32, y: u32, width: u32, height: u32, channel: u32) -> Option<u8> {
+    let components = component_count(image.format)?;
+
+    if channel >= components {
+        return None;
+    }
+
+    let sx = x * image.width / width;
+    let sy = y * image.height / height;
+
+    image
+        .pixels
+        .get(((sy * image.width + sx) * components + channel) as usize)
+        .copied()
+}
+
+fn to_dynamic_image(data: &ImageData) -> Result<DynamicImage, String> {
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let invalid = || "Invalid image data.".to_string();
+
+    match data.format {
+        Format::R8 => GrayImage::from_raw(width, height, pixels)
+            .map(DynamicImage::ImageLuma8)
+            .ok_or_else(invalid),
+        Format::R8G8 => GrayAlphaImage::from_raw(width, height, pixels)
+            .map(DynamicImage::ImageLumaA8)
+            .ok_or_else(invalid),
+        Format::R8G8B8 => RgbImage::from_raw(width, height, pixels)
+            .map(DynamicImage::ImageRgb8)
+            .ok_or_else(invalid),
+        Format::R8G8B8A8 => RgbaImage::from_raw(width, height, pixels)
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(invalid),
+        format => Err(format!("Unsupported image format {:?}.", format)),
+    }
+}
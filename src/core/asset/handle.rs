@@ -0,0 +1,73 @@
+use std::{fmt, ops::Deref, path::Path, rc::Rc};
+
+// Shared reference to a loaded asset. Handles handed out by the AssetManager for the same path
+// point to the same asset, which stays alive for as long as any handle does.
+pub struct Handle<T> {
+    asset: Rc<T>,
+    path: Option<Rc<Path>>,
+}
+
+impl<T> Handle<T> {
+    // Wraps an asset that is not owned by the AssetManager, e.g. a generated mesh.
+    pub fn new(asset: T) -> Self {
+        Self {
+            asset: Rc::new(asset),
+            path: None,
+        }
+    }
+
+    pub(crate) fn with_path(asset: T, path: &Path) -> Self {
+        Self {
+            asset: Rc::new(asset),
+            path: Some(Rc::from(path)),
+        }
+    }
+
+    // File the asset was loaded from. None for assets created in code.
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn ptr_eq(a: &Handle<T>, b: &Handle<T>) -> bool {
+        Rc::ptr_eq(&a.asset, &b.asset)
+    }
+
+    // Number of live handles, including the one held by the AssetManager.
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.asset)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            asset: Rc::clone(&self.asset),
+            path: self.path.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.asset
+    }
+}
+
+impl<T> AsRef<T> for Handle<T> {
+    fn as_ref(&self) -> &T {
+        &self.asset
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Handle<{}>({:p})",
+            std::any::type_name::<T>(),
+            Rc::as_ptr(&self.asset)
+        )
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime};
+
+// Order in which the changes of a single poll are published. Assets are reloaded before the
+// assets that are built from them, e.g. a material after its shaders and textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReloadStage {
+    Shader,
+    Texture,
+    Mesh,
+    Material,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetChanged {
+    pub path: PathBuf,
+    pub stage: ReloadStage,
+}
+
+struct WatchedFile {
+    stage: ReloadStage,
+    modified: Option<SystemTime>,
+}
+
+// Polls the modification time of watched files and publishes the changes to every subscriber.
+// Owned by the AssetManager, which reloads its own assets before the changes are published.
+pub struct HotReload {
+    enabled: bool,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    watched: HashMap<PathBuf, WatchedFile>,
+    // Maps a file to the files that have to be reloaded after it.
+    dependents: HashMap<PathBuf, HashSet<PathBuf>>,
+    subscribers: Vec<Sender<AssetChanged>>,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval: Duration::from_millis(500),
+            last_poll: None,
+            watched: HashMap::new(),
+            dependents: HashMap::new(),
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl HotReload {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval
+    }
+
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P, stage: ReloadStage) {
+        let modified = Self::modified(path.as_ref());
+
+        self.watched
+            .entry(path.as_ref().to_path_buf())
+            .or_insert(WatchedFile { stage, modified });
+    }
+
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) {
+        self.watched.remove(path.as_ref());
+        self.dependents.remove(path.as_ref());
+    }
+
+    // A change to dependency also publishes a change for dependent, after the dependency's.
+    pub fn add_dependency<D: AsRef<Path>, P: AsRef<Path>>(&mut self, dependent: D, dependency: P) {
+        self.dependents
+            .entry(dependency.as_ref().to_path_buf())
+            .or_default()
+            .insert(dependent.as_ref().to_path_buf());
+    }
+
+    // Events arrive in ReloadStage order.
+    pub fn subscribe(&mut self) -> Receiver<AssetChanged> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+
+        receiver
+    }
+
+    // Returns the files that changed since the last poll, together with their dependents,
+    // sorted by ReloadStage. Does nothing until the poll interval has passed.
+    pub fn poll(&mut self) -> Vec<AssetChanged> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.poll_interval {
+                return Vec::new();
+            }
+        }
+
+        self.last_poll = Some(now);
+
+        let mut changed = Vec::new();
+
+        for (path, watched) in &mut self.watched {
+            let modified = Self::modified(path);
+
+            if modified != watched.modified {
+                watched.modified = modified;
+                changed.push(AssetChanged {
+                    path: path.clone(),
+                    stage: watched.stage,
+                });
+            }
+        }
+
+        let mut published = changed
+            .iter()
+            .map(|c| c.path.clone())
+            .collect::<HashSet<_>>();
+
+        let mut i = 0;
+        while i < changed.len() {
+            if let Some(dependents) = self.dependents.get(&changed[i].path) {
+                let stage = changed[i].stage;
+
+                for dependent in dependents {
+                    if published.insert(dependent.clone()) {
+                        changed.push(AssetChanged {
+                            path: dependent.clone(),
+                            stage: self
+                                .watched
+                                .get(dependent)
+                                .map_or(stage, |watched| watched.stage.max(stage)),
+                        });
+                    }
+                }
+            }
+
+            i += 1;
+        }
+
+        // Stable, so dependents still follow their dependencies within a stage.
+        changed.sort_by_key(|c| c.stage);
+
+        changed
+    }
+
+    pub fn publish(&mut self, changes: &[AssetChanged]) {
+        self.subscribers.retain(|subscriber| {
+            changes
+                .iter()
+                .all(|change| subscriber.send(change.clone()).is_ok())
+        });
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
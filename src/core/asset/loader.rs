@@ -0,0 +1,163 @@
+use crate::core::asset::Handle;
+use crate::geometry::MeshData;
+use crate::rendering::mesh::MeshImportSettings;
+use crate::rendering::texture::Texture2DLoadConfig;
+use image::DynamicImage;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+pub(crate) type TextureKey = (PathBuf, Texture2DLoadConfig);
+pub(crate) type MeshKey = (PathBuf, MeshImportSettings);
+
+const LOADER_THREAD_COUNT: usize = 4;
+
+type Job = Box<dyn FnOnce() -> Decoded + Send>;
+
+// CPU side result of a load. Turned into GL objects on the main thread.
+pub(crate) enum Decoded {
+    Texture(TextureKey, Result<DynamicImage, String>),
+    Mesh(MeshKey, Result<MeshData, String>),
+}
+
+// Asset requested through AssetManager::request_*. Resolves once the AssetManager has uploaded
+// it, either in AssetManager::update or AssetManager::wait.
+pub struct PendingAsset<T> {
+    result: Rc<RefCell<Option<Result<Handle<T>, String>>>>,
+}
+
+impl<T> PendingAsset<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            result: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub(crate) fn ready(result: Result<Handle<T>, String>) -> Self {
+        let pending = Self::new();
+        pending.resolve(result);
+
+        pending
+    }
+
+    pub(crate) fn resolve(&self, result: Result<Handle<T>, String>) {
+        *self.result.borrow_mut() = Some(result);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.result.borrow().is_some()
+    }
+
+    pub fn get(&self) -> Option<Result<Handle<T>, String>> {
+        self.result.borrow().clone()
+    }
+}
+
+impl<T> Clone for PendingAsset<T> {
+    fn clone(&self) -> Self {
+        Self {
+            result: Rc::clone(&self.result),
+        }
+    }
+}
+
+// Counts the loads of the current batch. A batch starts with the first request made while no
+// other load is in flight, which lets a loading screen show a bar that only ever moves forward.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LoadProgress {
+    pub requested: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.completed + self.failed == self.requested
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.requested == 0 {
+            return 1.0;
+        }
+
+        (self.completed + self.failed) as f32 / self.requested as f32
+    }
+}
+
+// Worker threads for file IO and decoding. Results are queued until the main thread collects
+// them.
+pub(crate) struct Loader {
+    jobs: Option<Sender<Job>>,
+    results_sender: Sender<Decoded>,
+    results: Receiver<Decoded>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (results_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..LOADER_THREAD_COUNT)
+            .map(|i| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let results_sender = results_sender.clone();
+
+                thread::Builder::new()
+                    .name(format!("Asset Loader {}", i))
+                    .spawn(move || loop {
+                        let job = match job_receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        if results_sender.send(job()).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Failed to spawn asset loader thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(jobs),
+            results_sender,
+            results,
+            workers,
+        }
+    }
+
+    pub fn spawn<F: FnOnce() -> Decoded + Send + 'static>(&self, job: F) {
+        // Keep loading on the calling thread if the workers are gone.
+        if let Some(Err(mpsc::SendError(job))) =
+            self.jobs.as_ref().map(|jobs| jobs.send(Box::new(job)))
+        {
+            let _ = self.results_sender.send(job());
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<Decoded> {
+        self.results.try_recv().ok()
+    }
+
+    pub fn recv(&self) -> Decoded {
+        self.results
+            .recv()
+            .expect("Asset loader result channel closed")
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers once they finish their current job.
+        self.jobs = None;
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
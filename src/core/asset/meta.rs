@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Import settings authored next to an asset, e.g. textures/brick.png.meta:
+//
+//   # Albedo map
+//   srgb = true
+//   mipmaps = true
+//   compression = bptc
+//
+// One `key = value` pair per line, `#` starts a comment. Which keys are read is up to the
+// importer of the asset type.
+#[derive(Debug, Clone, Default)]
+pub struct AssetMeta {
+    path: PathBuf,
+    values: HashMap<String, String>,
+}
+
+impl AssetMeta {
+    pub fn sidecar_path<P: AsRef<Path>>(asset_path: P) -> PathBuf {
+        let mut path = OsString::from(asset_path.as_ref().as_os_str());
+        path.push(".meta");
+
+        PathBuf::from(path)
+    }
+
+    // None if the asset has no sidecar file.
+    pub fn load_for<P: AsRef<Path>>(asset_path: P) -> Result<Option<Self>, String> {
+        let path = Self::sidecar_path(asset_path);
+
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let source = fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+
+        Self::parse(&source, path).map(Some)
+    }
+
+    pub fn parse<P: AsRef<Path>>(source: &str, path: P) -> Result<Self, String> {
+        let mut values = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().map(str::trim).ok_or_else(|| {
+                format!(
+                    "{:?} line {}: Expected `key = value`.",
+                    path.as_ref(),
+                    line_number + 1
+                )
+            })?;
+
+            values.insert(key.to_string(), value.to_string());
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            values,
+        })
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, String> {
+        self.get(key)
+    }
+
+    pub fn get_f32(&self, key: &str) -> Result<Option<f32>, String> {
+        self.get(key)
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.get_str(key) {
+            Some(value) => value
+                .parse::<T>()
+                .map(Some)
+                .map_err(|_| format!("{:?}: Invalid value '{}' for {}.", self.path, value, key)),
+            None => Ok(None),
+        }
+    }
+
+    // Warns about keys the importer didn't recognize, which are usually typos.
+    pub fn check_keys(&self, known: &[&str]) {
+        for key in self.values.keys() {
+            if !known.contains(&key.as_str()) {
+                log::warn!("{:?}: Unknown key '{}'.", self.path, key);
+            }
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use crate::{
+    core::asset::Handle,
+    core::{
+        asset::{gltf::process_mesh_data, Asset},
+        math::{Vec2, Vec3, Vec4},
+    },
+    geometry::{tangents, MeshData},
+    rendering::{
+        material::PbsMetallicRoughnessMaterial,
+        mesh::{Mesh, MeshImportSettings, Vertex},
+        texture::{Texture2D, Texture2DLoadConfig},
+    },
+};
+use std::{collections::HashMap, fs, path::Path};
+
+pub struct ObjMesh {
+    pub name: String,
+    pub mesh: Handle<Mesh>,
+    // Index into ObjScene::materials. None uses the default material.
+    pub material: Option<usize>,
+}
+
+pub struct ObjScene {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<PbsMetallicRoughnessMaterial>,
+}
+
+// A mesh per object, group and material combination of the file.
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub mesh_data: MeshData,
+}
+
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    // Set by the PBR extension of the format (Pr/Pm).
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    pub diffuse_map: Option<String>,
+    pub normal_map: Option<String>,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            roughness: None,
+            metallic: None,
+            diffuse_map: None,
+            normal_map: None,
+        }
+    }
+
+    // Blinn-Phong exponent to perceptual GGX roughness, alpha = sqrt(2 / (Ns + 2)). Materials
+    // without a specular color are treated as fully rough.
+    pub fn perceptual_roughness(&self) -> f32 {
+        if let Some(roughness) = self.roughness {
+            return roughness.max(0.0).min(1.0);
+        }
+
+        let specular_intensity = self.specular.iter().cloned().fold(0.0, f32::max);
+
+        if specular_intensity <= 0.0 {
+            return 1.0;
+        }
+
+        (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt().sqrt()
+    }
+
+    pub fn metalness(&self) -> f32 {
+        self.metallic.unwrap_or(0.0).max(0.0).min(1.0)
+    }
+}
+
+// Loads an .obj file together with the .mtl libraries it references. asset_path is the engine
+// asset directory that holds the material shaders.
+pub fn import<P: AsRef<Path>, A: AsRef<Path>>(
+    path: P,
+    asset_path: A,
+    settings: MeshImportSettings,
+) -> Result<ObjScene, String> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read OBJ file {:?}: {}", path, e))?;
+
+    let (groups, libraries) = parse_obj(&source)?;
+
+    let mut mtl_materials = Vec::new();
+    for library in libraries {
+        match fs::read_to_string(directory.join(&library)) {
+            Ok(source) => mtl_materials.extend(parse_mtl(&source)?),
+            Err(e) => log::warn!("Failed to read material library {:?}: {}", library, e),
+        }
+    }
+
+    let materials = mtl_materials
+        .iter()
+        .map(|material| create_material(material, directory, asset_path.as_ref()))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let meshes = groups
+        .into_iter()
+        .map(|group| {
+            let material = group.material.as_ref().and_then(|name| {
+                let index = mtl_materials.iter().position(|m| &m.name == name);
+
+                if index.is_none() {
+                    log::warn!("OBJ material '{}' not found.", name);
+                }
+
+                index
+            });
+
+            ObjMesh {
+                name: group.name,
+                mesh: Handle::new(process_mesh_data(group.mesh_data, settings)),
+                material,
+            }
+        })
+        .collect();
+
+    Ok(ObjScene { meshes, materials })
+}
+
+// Also used for the Lambert/Phong materials of FBX files, which follow the same model.
+pub(crate) fn create_material(
+    material: &MtlMaterial,
+    directory: &Path,
+    asset_path: &Path,
+) -> Result<PbsMetallicRoughnessMaterial, String> {
+    let load_map = |map: &Option<String>, is_srgb: bool| {
+        map.as_ref().and_then(|map| {
+            let path = directory.join(map);
+            let defaults = Texture2DLoadConfig {
+                is_srgb,
+                generate_mipmap: true,
+                ..Default::default()
+            };
+
+            Texture2DLoadConfig::from_sidecar(&path, defaults)
+                .and_then(|config| Texture2D::load(&path, Some(config)))
+                .map_err(|e| log::warn!("Failed to load texture {:?}: {}", map, e))
+                .ok()
+        })
+    };
+
+    let albedo = load_map(&material.diffuse_map, true)
+        .unwrap_or_else(|| Texture2D::new_from_color([255, 255, 255]));
+    let normals = load_map(&material.normal_map, false)
+        .unwrap_or_else(|| Texture2D::new_from_color([128, 128, 255]));
+    let metallic_roughness_ao = Texture2D::new_from_color([255, 255, 255]);
+
+    let mut pbs_material = PbsMetallicRoughnessMaterial::new(
+        asset_path,
+        Handle::new(albedo),
+        Handle::new(metallic_roughness_ao),
+        Handle::new(normals),
+        None,
+    )?;
+
+    // The diffuse color tints the diffuse map, just like the base color factor of glTF.
+    let [r, g, b] = material.diffuse;
+    pbs_material.set_base_color(Vec4::new(r, g, b, material.dissolve));
+    pbs_material.set_metallic_scale(material.metalness());
+    pbs_material.set_roughness_scale(material.perceptual_roughness());
+
+    Ok(pbs_material)
+}
+
+struct GroupBuilder {
+    name: String,
+    material: Option<String>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    // Position index of each vertex, used to share generated normals.
+    positions: Vec<usize>,
+    has_normal: Vec<bool>,
+    lookup: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+}
+
+impl GroupBuilder {
+    fn new(name: String, material: Option<String>) -> Self {
+        Self {
+            name,
+            material,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            positions: Vec::new(),
+            has_normal: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        key: (usize, Option<usize>, Option<usize>),
+        positions: &[Vec3],
+        tex_coords: &[Vec2],
+        normals: &[Vec3],
+    ) -> u32 {
+        if let Some(&index) = self.lookup.get(&key) {
+            return index;
+        }
+
+        let (position, tex_coord, normal) = key;
+        let index = self.vertices.len() as u32;
+
+        self.vertices.push(Vertex {
+            position: positions[position],
+            normal: normal.map_or(Vec3::new(0.0, 0.0, 0.0), |n| normals[n]),
+            tangent: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            tex_coord: tex_coord.map_or(Vec2::new(0.0, 0.0), |t| tex_coords[t]),
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            // OBJ has a single UV channel.
+            tex_coord1: Vec2::new(0.0, 0.0),
+        });
+        self.positions.push(position);
+        self.has_normal.push(normal.is_some());
+        self.lookup.insert(key, index);
+
+        index
+    }
+
+    fn build(mut self) -> ObjGroup {
+        if self.has_normal.iter().any(|&has_normal| !has_normal) {
+            self.generate_normals();
+        }
+
+        let mut mesh_data = MeshData::new(self.vertices, self.indices);
+        tangents::generate_tangents(&mut mesh_data);
+
+        ObjGroup {
+            name: self.name,
+            material: self.material,
+            mesh_data,
+        }
+    }
+
+    // Smooth, area weighted normals for the vertices the file gives none. Vertices that share a
+    // position share the normal.
+    fn generate_normals(&mut self) {
+        let mut accumulated: HashMap<usize, Vec3> = HashMap::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+
+            // Not normalized, the length is twice the triangle area.
+            let face_normal = (p1 - p0).cross(&(p2 - p0));
+
+            for &index in triangle {
+                *accumulated
+                    .entry(self.positions[index as usize])
+                    .or_insert_with(Vec3::zeros) += face_normal;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            if self.has_normal[i] {
+                continue;
+            }
+
+            let normal = accumulated
+                .get(&self.positions[i])
+                .cloned()
+                .unwrap_or_else(Vec3::zeros);
+
+            vertex.normal = if normal.norm() > std::f32::EPSILON {
+                normal.normalize()
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+        }
+    }
+}
+
+// Returns the groups with faces and the material libraries referenced by the file.
+pub fn parse_obj(source: &str) -> Result<(Vec<ObjGroup>, Vec<String>), String> {
+    let mut positions = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut normals = Vec::new();
+    let mut libraries = Vec::new();
+
+    let mut groups = Vec::new();
+    let mut current = GroupBuilder::new("default".to_string(), None);
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("OBJ line {}: {}", line_number + 1, message);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(tokens, 3).ok_or_else(|| error("Invalid position."))?;
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let t =
+                    parse_floats(tokens, 1).ok_or_else(|| error("Invalid texture coordinate."))?;
+                // OBJ puts the texture origin at the bottom left, glTF and the engine at the top
+                // left.
+                tex_coords.push(Vec2::new(t[0], 1.0 - t.get(1).cloned().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let n = parse_floats(tokens, 3).ok_or_else(|| error("Invalid normal."))?;
+                normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|token| {
+                        parse_face_corner(token, positions.len(), tex_coords.len(), normals.len())
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| error("Invalid face index."))?;
+
+                if corners.len() < 3 {
+                    return Err(error("Face has less than 3 vertices."));
+                }
+
+                let corners = corners
+                    .into_iter()
+                    .map(|corner| current.vertex(corner, &positions, &tex_coords, &normals))
+                    .collect::<Vec<_>>();
+
+                // Fan triangulation. Fine for the convex polygons OBJ exporters write.
+                for i in 1..corners.len() - 1 {
+                    current
+                        .indices
+                        .extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = current.material.clone();
+                start_group(&mut groups, &mut current, name, material);
+            }
+            "usemtl" => {
+                let material = tokens.collect::<Vec<_>>().join(" ");
+                let name = current.name.clone();
+                start_group(&mut groups, &mut current, name, Some(material));
+            }
+            "mtllib" => libraries.extend(tokens.map(str::to_string)),
+            // Smoothing groups, lines, points and free-form geometry are ignored.
+            _ => {}
+        }
+    }
+
+    if !current.indices.is_empty() {
+        groups.push(current.build());
+    }
+
+    Ok((groups, libraries))
+}
+
+fn start_group(
+    groups: &mut Vec<ObjGroup>,
+    current: &mut GroupBuilder,
+    name: String,
+    material: Option<String>,
+) {
+    if current.indices.is_empty() {
+        current.name = name;
+        current.material = material;
+        return;
+    }
+
+    let finished = std::mem::replace(current, GroupBuilder::new(name, material));
+    groups.push(finished.build());
+}
+
+pub fn parse_mtl(source: &str) -> Result<Vec<MtlMaterial>, String> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("MTL line {}: {}", line_number + 1, message);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial::new(&tokens.collect::<Vec<_>>().join(" ")));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error("Material property before newmtl.")),
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(tokens).ok_or_else(|| error("Invalid Kd."))?,
+            "Ks" => material.specular = parse_color(tokens).ok_or_else(|| error("Invalid Ks."))?,
+            "Ns" => {
+                material.shininess = parse_scalar(tokens).ok_or_else(|| error("Invalid Ns."))?
+            }
+            "d" => material.dissolve = parse_scalar(tokens).ok_or_else(|| error("Invalid d."))?,
+            "Tr" => {
+                material.dissolve =
+                    1.0 - parse_scalar(tokens).ok_or_else(|| error("Invalid Tr."))?
+            }
+            "Pr" => material.roughness = parse_scalar(tokens),
+            "Pm" => material.metallic = parse_scalar(tokens),
+            // Map options (-bm, -o, ...) come before the file name.
+            "map_Kd" => material.diffuse_map = tokens.last().map(str::to_string),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_map = tokens.last().map(str::to_string)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_floats<'a, I: Iterator<Item = &'a str>>(tokens: I, min_count: usize) -> Option<Vec<f32>> {
+    let values = tokens
+        .map(|token| token.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if values.len() < min_count {
+        return None;
+    }
+
+    Some(values)
+}
+
+fn parse_scalar<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Option<f32> {
+    parse_floats(tokens, 1).map(|values| values[0])
+}
+
+fn parse_color<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Option<[f32; 3]> {
+    let values = parse_floats(tokens, 1)?;
+
+    // A single value sets all channels.
+    match values.len() {
+        1 | 2 => Some([values[0]; 3]),
+        _ => Some([values[0], values[1], values[2]]),
+    }
+}
+
+// Parses v, v/vt, v//vn or v/vt/vn into zero based indices. Negative indices count back from the
+// last element defined so far.
+fn parse_face_corner(
+    token: &str,
+    position_count: usize,
+    tex_coord_count: usize,
+    normal_count: usize,
+) -> Option<(usize, Option<usize>, Option<usize>)> {
+    let mut parts = token.split('/');
+
+    let position = resolve_index(parts.next()?, position_count)?;
+
+    let tex_coord = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, tex_coord_count)?),
+        _ => None,
+    };
+
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normal_count)?),
+        _ => None,
+    };
+
+    Some((position, tex_coord, normal))
+}
+
+fn resolve_index(token: &str, count: usize) -> Option<usize> {
+    let index = token.parse::<i64>().ok()?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as i64 + index
+    } else {
+        return None;
+    };
+
+    if resolved >= 0 && (resolved as usize) < count {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// Layout, all integers little endian:
+//   magic, version: u32, entry count: u32
+//   per entry: name length: u16, name (utf8, '/' separated), offset: u64, size: u64,
+//              uncompressed size: u64, compression: u8
+//   entry data
+const MAGIC: &[u8; 8] = b"PBSPACK\0";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = MAGIC.len() as u64 + 4 + 4;
+// An entry with an empty name.
+const MIN_ENTRY_SIZE: u64 = 2 + 8 * 3 + 1;
+
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 19;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None = 0,
+    Zstd = 1,
+}
+
+impl Compression {
+    fn from_u8(value: u8) -> Result<Self, String> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Zstd),
+            _ => Err(format!("Unknown asset pack compression {}.", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    size: u64,
+    uncompressed_size: u64,
+    compression: Compression,
+}
+
+// Read only view of a pack file. Entries are read on demand, so a pack can be shared between
+// the loader threads.
+#[derive(Debug)]
+pub struct AssetPack {
+    path: PathBuf,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl AssetPack {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("{:?}: {}", path.as_ref(), e);
+
+        let mut file = File::open(path.as_ref()).map_err(error)?;
+        let file_length = file.metadata().map_err(error)?.len();
+
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic).map_err(error)?;
+
+        if &magic != MAGIC {
+            return Err(format!("{:?} is not an asset pack.", path.as_ref()));
+        }
+
+        let version = read_u32(&mut file).map_err(error)?;
+
+        if version != VERSION {
+            return Err(format!(
+                "{:?}: Unsupported asset pack version {}.",
+                path.as_ref(),
+                version
+            ));
+        }
+
+        let entry_count = read_u32(&mut file).map_err(error)?;
+
+        // The counts and sizes are checked against the file before anything is allocated for
+        // them, so a corrupted pack fails to open instead of exhausting the memory.
+        if entry_count as u64 * MIN_ENTRY_SIZE > file_length.saturating_sub(HEADER_SIZE) {
+            return Err(format!(
+                "{:?}: {} entries do not fit in the asset pack.",
+                path.as_ref(),
+                entry_count
+            ));
+        }
+
+        let mut entries = HashMap::with_capacity(entry_count as usize);
+
+        for _ in 0..entry_count {
+            let mut name_length = [0u8; 2];
+            file.read_exact(&mut name_length).map_err(error)?;
+
+            let mut name = vec![0u8; u16::from_le_bytes(name_length) as usize];
+            file.read_exact(&mut name).map_err(error)?;
+            let name = String::from_utf8(name).map_err(|e| e.to_string())?;
+
+            let offset = read_u64(&mut file).map_err(error)?;
+            let size = read_u64(&mut file).map_err(error)?;
+            let uncompressed_size = read_u64(&mut file).map_err(error)?;
+
+            let mut compression = [0u8; 1];
+            file.read_exact(&mut compression).map_err(error)?;
+
+            if offset
+                .checked_add(size)
+                .map_or(true, |end| end > file_length)
+            {
+                return Err(format!(
+                    "{:?}: Entry {} is out of the bounds of the asset pack.",
+                    path.as_ref(),
+                    name
+                ));
+            }
+
+            entries.insert(
+                name,
+                PackEntry {
+                    offset,
+                    size,
+                    uncompressed_size,
+                    compression: Compression::from_u8(compression[0])?,
+                },
+            );
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn entry_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| format!("{:?} has no entry {}.", self.path, name))?;
+
+        let error = |e: std::io::Error| format!("{:?}: {}", self.path, e);
+
+        let mut file = File::open(&self.path).map_err(error)?;
+        file.seek(SeekFrom::Start(entry.offset)).map_err(error)?;
+
+        let mut data = vec![0u8; entry.size as usize];
+        file.read_exact(&mut data).map_err(error)?;
+
+        let data = match entry.compression {
+            Compression::None => data,
+            Compression::Zstd => decompress(&data)?,
+        };
+
+        if data.len() as u64 != entry.uncompressed_size {
+            return Err(format!("{:?}: Entry {} is corrupted.", self.path, name));
+        }
+
+        Ok(data)
+    }
+}
+
+// Collects files and writes them into a pack. Used by the pack_assets tool at build time.
+pub struct AssetPackWriter {
+    files: Vec<(String, PathBuf)>,
+    compression: Compression,
+}
+
+impl AssetPackWriter {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            compression: Compression::None,
+        }
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        if compression == Compression::Zstd && cfg!(not(feature = "zstd")) {
+            log::warn!("The zstd feature is disabled. Assets will be stored uncompressed.");
+            return self;
+        }
+
+        self.compression = compression;
+        self
+    }
+
+    pub fn add_file<P: AsRef<Path>>(&mut self, name: &str, path: P) {
+        self.files
+            .push((name.to_string(), path.as_ref().to_path_buf()));
+    }
+
+    // Adds every file under the directory, named by its path relative to the directory.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> Result<(), String> {
+        let mut directories = vec![directory.as_ref().to_path_buf()];
+
+        while let Some(current) = directories.pop() {
+            let read_dir = fs::read_dir(&current).map_err(|e| format!("{:?}: {}", current, e))?;
+
+            for entry in read_dir {
+                let path = entry.map_err(|e| e.to_string())?.path();
+
+                if path.is_dir() {
+                    directories.push(path);
+                } else if let Some(name) = entry_name(&path, directory.as_ref()) {
+                    self.add_file(&name, path);
+                }
+            }
+        }
+
+        // Keeps the pack identical between runs.
+        self.files.sort();
+
+        Ok(())
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut blobs = Vec::with_capacity(self.files.len());
+
+        for (name, file_path) in &self.files {
+            let data = fs::read(file_path).map_err(|e| format!("{:?}: {}", file_path, e))?;
+            let uncompressed_size = data.len() as u64;
+
+            let (data, compression) = match self.compression {
+                Compression::Zstd => {
+                    let compressed = compress(&data)?;
+
+                    // Already compressed formats (png, jpg, ktx2...) don't always shrink.
+                    if compressed.len() < data.len() {
+                        (compressed, Compression::Zstd)
+                    } else {
+                        (data, Compression::None)
+                    }
+                }
+                Compression::None => (data, Compression::None),
+            };
+
+            let name_length: u16 = name
+                .len()
+                .try_into()
+                .map_err(|_| format!("Asset pack entry name {} is too long.", name))?;
+
+            blobs.push((name, name_length, data, uncompressed_size, compression));
+        }
+
+        let table_size: u64 = blobs
+            .iter()
+            .map(|(_, name_length, ..)| MIN_ENTRY_SIZE + *name_length as u64)
+            .sum();
+
+        let mut offset = HEADER_SIZE + table_size;
+        let mut output = Vec::new();
+
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&VERSION.to_le_bytes());
+        output.extend_from_slice(&(blobs.len() as u32).to_le_bytes());
+
+        for (name, name_length, data, uncompressed_size, compression) in &blobs {
+            output.extend_from_slice(&name_length.to_le_bytes());
+            output.extend_from_slice(name.as_bytes());
+            output.extend_from_slice(&offset.to_le_bytes());
+            output.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            output.extend_from_slice(&uncompressed_size.to_le_bytes());
+            output.push(*compression as u8);
+
+            offset += data.len() as u64;
+        }
+
+        for (_, _, data, _, _) in &blobs {
+            output.extend_from_slice(data);
+        }
+
+        File::create(path.as_ref())
+            .and_then(|mut file| file.write_all(&output))
+            .map_err(|e| format!("{:?}: {}", path.as_ref(), e))
+    }
+}
+
+impl Default for AssetPackWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A pack standing in for the directory at mount_point.
+pub(crate) struct MountedPack {
+    pub mount_point: PathBuf,
+    pub pack: Arc<AssetPack>,
+}
+
+// Where an asset's bytes come from. Can be sent to the loader threads.
+#[derive(Debug, Clone)]
+pub(crate) enum AssetSource {
+    File(PathBuf),
+    Packed(Arc<AssetPack>, String),
+}
+
+impl AssetSource {
+    // The last mounted pack that contains the path wins. Falls back to the file system.
+    pub fn resolve(packs: &[MountedPack], path: &Path) -> Self {
+        packs
+            .iter()
+            .rev()
+            .filter_map(|mounted| {
+                let name = entry_name(path, &mounted.mount_point)?;
+
+                if mounted.pack.contains(&name) {
+                    Some(AssetSource::Packed(Arc::clone(&mounted.pack), name))
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or_else(|| AssetSource::File(path.to_path_buf()))
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match self {
+            AssetSource::File(path) => fs::read(path).map_err(|e| format!("{:?}: {}", path, e)),
+            AssetSource::Packed(pack, name) => pack.read(name),
+        }
+    }
+
+    pub fn read_to_string(&self) -> Result<String, String> {
+        String::from_utf8(self.read()?).map_err(|e| e.to_string())
+    }
+}
+
+// Pack entry name of a path below the root, e.g. "textures/cerberus/Cerberus_A.png".
+fn entry_name(path: &Path, root: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+
+    let components = relative
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(components.join("/"))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(feature = "zstd")]
+fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::stream::encode_all(data, ZSTD_LEVEL).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(data.to_vec())
+}
+
+#[cfg(feature = "zstd")]
+fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::stream::decode_all(data).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Asset pack entry is zstd compressed but the zstd feature is disabled.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetPack, AssetPackWriter, Compression, HEADER_SIZE, MAGIC, VERSION};
+    use std::{env, fs, path::PathBuf};
+
+    // A directory of its own for each test, since the tests run in parallel.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("pack_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        directory
+    }
+
+    fn write_pack(name: &str, compression: Compression) -> (PathBuf, AssetPack) {
+        let directory = test_directory(name);
+        let assets = directory.join("assets");
+
+        fs::create_dir_all(assets.join("sdr")).unwrap();
+        fs::write(assets.join("config.ron"), "(vsync: false)").unwrap();
+        fs::write(assets.join("sdr/pbs.frag"), "#version 450\n".repeat(100)).unwrap();
+        fs::write(assets.join("empty"), "").unwrap();
+
+        let mut writer = AssetPackWriter::new().with_compression(compression);
+        writer.add_directory(&assets).unwrap();
+        writer.write(directory.join("assets.pack")).unwrap();
+
+        let pack = AssetPack::open(directory.join("assets.pack")).unwrap();
+
+        (directory, pack)
+    }
+
+    fn assert_round_trips(name: &str, compression: Compression) {
+        let (directory, pack) = write_pack(name, compression);
+
+        let mut names = pack.entry_names().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, ["config.ron", "empty", "sdr/pbs.frag"]);
+        assert_eq!(pack.read("config.ron").unwrap(), b"(vsync: false)");
+        assert_eq!(
+            pack.read("sdr/pbs.frag").unwrap(),
+            "#version 450\n".repeat(100).as_bytes()
+        );
+        assert!(pack.read("empty").unwrap().is_empty());
+        assert!(pack.read("missing").is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn round_trips_files() {
+        assert_round_trips("uncompressed", Compression::None);
+    }
+
+    #[test]
+    fn round_trips_compressed_files() {
+        assert_round_trips("compressed", Compression::Zstd);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let directory = test_directory("other");
+        let path = directory.join("assets.pack");
+
+        fs::write(&path, "not an asset pack").unwrap();
+        assert!(AssetPack::open(&path).is_err());
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+
+        fs::write(&path, header).unwrap();
+        assert!(AssetPack::open(&path).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupted_sizes_before_allocating() {
+        let (directory, _) = write_pack("corrupted", Compression::None);
+        let path = directory.join("assets.pack");
+        let pack = fs::read(&path).unwrap();
+        let entry_count = MAGIC.len() + 4;
+
+        let mut corrupted = pack.clone();
+        corrupted[entry_count..entry_count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &corrupted).unwrap();
+
+        assert!(AssetPack::open(&path).is_err());
+
+        // The size of the first entry follows its name length, name and offset.
+        let name_length =
+            u16::from_le_bytes([pack[HEADER_SIZE as usize], pack[HEADER_SIZE as usize + 1]])
+                as usize;
+        let size = HEADER_SIZE as usize + 2 + name_length + 8;
+
+        let mut corrupted = pack.clone();
+        corrupted[size..size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, &corrupted).unwrap();
+
+        assert!(AssetPack::open(&path).is_err());
+
+        fs::write(&path, &pack[..pack.len() - 1]).unwrap();
+
+        assert!(AssetPack::open(&path).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
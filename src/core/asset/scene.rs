@@ -0,0 +1,83 @@
+use crate::{
+    core::animation::clip::AnimationClip,
+    core::asset::Handle,
+    core::math::Mat4,
+    rendering::{material::PbsMetallicRoughnessMaterial, mesh::Mesh},
+};
+use std::rc::Rc;
+
+pub struct SceneNode {
+    pub name: String,
+    pub local_transform: Mat4,
+    // Transform relative to the scene root.
+    pub transform: Mat4,
+    pub mesh: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+pub struct ScenePrimitive {
+    pub mesh: Handle<Mesh>,
+    // Index into SceneDescription::materials. None uses the default material.
+    pub material: Option<usize>,
+}
+
+pub struct SceneMesh {
+    pub name: String,
+    pub primitives: Vec<ScenePrimitive>,
+}
+
+// The format independent result of the scene importers (glTF, FBX).
+pub struct SceneDescription {
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+    pub meshes: Vec<SceneMesh>,
+    pub materials: Vec<PbsMetallicRoughnessMaterial>,
+    // Animations of the node transforms, e.g. a rotating fan. The joints of their tracks are
+    // indices into nodes.
+    pub animations: Vec<Rc<AnimationClip>>,
+}
+
+impl SceneDescription {
+    // Nodes that reference a mesh, together with their scene transform.
+    pub fn mesh_instances(&self) -> impl Iterator<Item = (&SceneNode, &SceneMesh)> {
+        self.nodes.iter().filter_map(move |node| {
+            node.mesh
+                .and_then(|mesh| self.meshes.get(mesh))
+                .map(|mesh| (node, mesh))
+        })
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Rc<AnimationClip>> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name() == name)
+    }
+}
+
+// Fills in the parent of every node and its transform relative to the scene root.
+pub(crate) fn resolve_hierarchy(nodes: &mut [SceneNode], roots: &[usize]) {
+    for i in 0..nodes.len() {
+        for child in nodes[i].children.clone() {
+            nodes[child].parent = Some(i);
+        }
+    }
+
+    // Parents are always visited before their children.
+    let mut stack = roots
+        .iter()
+        .map(|&root| (root, Mat4::identity()))
+        .collect::<Vec<_>>();
+
+    while let Some((index, parent_transform)) = stack.pop() {
+        let transform = parent_transform * nodes[index].local_transform;
+        nodes[index].transform = transform;
+
+        stack.extend(
+            nodes[index]
+                .children
+                .iter()
+                .map(|&child| (child, transform)),
+        );
+    }
+}
@@ -0,0 +1,475 @@
+use crate::{
+    core::asset::{Asset, AssetManager, Handle},
+    core::camera::{controller::OrbitController, Camera, Projection},
+    core::math::{Axes, Mat4, UVec2, Vec3},
+    rendering::{
+        fog::HeightFogSettings,
+        light_probe::{LightProbe, ProbeShape},
+        material::PbsMetallicRoughnessMaterial,
+        mesh::Mesh,
+        postprocess::camera_imperfections::PostprocessingSettings,
+        shadow::ShadowSettings,
+        texture::TextureCube,
+    },
+};
+use nalgebra_glm as glm;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// A scene authored as RON data, e.g. examples/assets/scenes/cerberus.ron. Asset paths are
+// relative to the directory of the scene file. Missing fields take their default values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub name: String,
+    pub camera: CameraDescription,
+    pub environment: Option<EnvironmentDescription>,
+    pub lights: Vec<LightDescription>,
+    pub light_probes: Vec<LightProbeDescription>,
+    pub entities: Vec<EntityDescription>,
+    pub fog: HeightFogSettings,
+    pub post_processing: PostprocessingSettings,
+    // Where the scene was loaded from. Paths are resolved against it.
+    #[serde(skip)]
+    directory: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub translation: [f32; 3],
+    // Euler angles in degrees, applied around X, then Y, then Z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityDescription {
+    pub name: String,
+    pub transform: TransformDescription,
+    pub mesh: Option<PathBuf>,
+    // A material template file. Entities with a mesh and no material use the default material.
+    pub material: Option<PathBuf>,
+    pub children: Vec<EntityDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    // Vertical, in degrees.
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    pub orbit_speed: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub orbit_dampening: f32,
+    pub zoom_dampening: f32,
+    pub aperture: f32,
+    pub shutter_speed: f32,
+    pub sensitivity: f32,
+}
+
+// Colors are sRGB. Intensities are in lux for directional lights and in lumens for the others.
+// The optional temperature in Kelvin tints the color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LightDescription {
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        #[serde(default)]
+        temperature: Option<f32>,
+    },
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        #[serde(default)]
+        temperature: Option<f32>,
+        #[serde(default)]
+        shadow: ShadowSettings,
+    },
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        // Half angles in degrees.
+        inner_angle: f32,
+        outer_angle: f32,
+        #[serde(default)]
+        temperature: Option<f32>,
+        #[serde(default)]
+        shadow: ShadowSettings,
+    },
+    Rect {
+        position: [f32; 3],
+        // Normal of the emitting side.
+        direction: [f32; 3],
+        up: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        width: f32,
+        height: f32,
+        range: f32,
+        #[serde(default)]
+        two_sided: bool,
+        #[serde(default)]
+        temperature: Option<f32>,
+    },
+    Disk {
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        radius: f32,
+        range: f32,
+        #[serde(default)]
+        two_sided: bool,
+        #[serde(default)]
+        temperature: Option<f32>,
+    },
+}
+
+// Prefiltered cube maps (.ktx/.dds) of the environment.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentDescription {
+    pub skybox: PathBuf,
+    pub irradiance: PathBuf,
+    pub radiance: PathBuf,
+}
+
+// A reflection and irradiance probe. The application bakes it, e.g. after loading the scene.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightProbeDescription {
+    // Where the probe captures from and the center of its volume.
+    pub position: [f32; 3],
+    pub shape: LightProbeShape,
+    pub blend_distance: f32,
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LightProbeShape {
+    Box { half_extents: [f32; 3] },
+    Sphere { radius: f32 },
+}
+
+// The result of SceneFile::instantiate. Entity transforms are relative to the scene root.
+pub struct LoadedScene {
+    pub name: String,
+    pub camera: Camera,
+    pub camera_controller: OrbitController,
+    pub environment: Option<LoadedEnvironment>,
+    pub lights: Vec<LightDescription>,
+    pub light_probes: Vec<LightProbeDescription>,
+    pub entities: Vec<LoadedEntity>,
+    pub fog: HeightFogSettings,
+    pub post_processing: PostprocessingSettings,
+}
+
+pub struct LoadedEnvironment {
+    pub skybox: Handle<TextureCube>,
+    pub irradiance: Handle<TextureCube>,
+    pub radiance: Handle<TextureCube>,
+}
+
+pub struct LoadedEntity {
+    pub name: String,
+    pub transform: Mat4,
+    pub mesh: Option<Handle<Mesh>>,
+    pub material: Option<PbsMetallicRoughnessMaterial>,
+    // Index into LoadedScene::entities.
+    pub parent: Option<usize>,
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self {
+            translation: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl TransformDescription {
+    pub fn matrix(&self) -> Mat4 {
+        let axis_rotation = |angle: f32, axis: Vec3| glm::rotation(angle.to_radians(), &axis);
+
+        glm::translation(&Vec3::from(self.translation))
+            * axis_rotation(self.rotation[2], Vec3::new(0.0, 0.0, 1.0))
+            * axis_rotation(self.rotation[1], Vec3::new(0.0, 1.0, 0.0))
+            * axis_rotation(self.rotation[0], Vec3::new(1.0, 0.0, 0.0))
+            * glm::scaling(&Vec3::from(self.scale))
+    }
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, -60.0],
+            target: [0.0, 0.0, 0.0],
+            fov_y: 60.0,
+            near: 0.5,
+            far: 500.0,
+            orbit_speed: 10.0,
+            zoom_speed: 30.0,
+            min_distance: 10.0,
+            max_distance: 200.0,
+            orbit_dampening: 3.0,
+            zoom_dampening: 4.0,
+            aperture: 1.4,
+            shutter_speed: 0.55,
+            sensitivity: 500.0,
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn from_camera(camera: &Camera, controller: &OrbitController) -> Self {
+        let position = camera.position();
+        let target = controller.target();
+
+        let (fov_y, near, far) = match *camera.projection() {
+            Projection::Perspective { fov_y, near, far } => (fov_y, near, far),
+            // Scene files only describe perspective cameras.
+            Projection::Orthographic { near, far, .. } => (Self::default().fov_y, near, far),
+        };
+
+        Self {
+            position: [position.x, position.y, position.z],
+            target: [target.x, target.y, target.z],
+            fov_y,
+            near,
+            far,
+            orbit_speed: controller.orbit_speed(),
+            zoom_speed: controller.zoom_speed(),
+            min_distance: controller.min_distance(),
+            max_distance: controller.max_distance(),
+            orbit_dampening: controller.orbit_dampening(),
+            zoom_dampening: controller.zoom_dampening(),
+            aperture: camera.aperture(),
+            shutter_speed: camera.shutter_speed(),
+            sensitivity: camera.sensitivity(),
+        }
+    }
+
+    pub fn to_camera(&self, viewport_size: UVec2) -> Camera {
+        let mut camera = Camera::perspective(self.fov_y, self.near, self.far, viewport_size);
+
+        camera.look_at(
+            Vec3::from(self.position),
+            Vec3::from(self.target),
+            Axes::up(),
+        );
+        camera.set_aperture(self.aperture);
+        camera.set_shutter_speed(self.shutter_speed);
+        camera.set_sensitivity(self.sensitivity);
+
+        camera
+    }
+
+    // Orbits the target, starting from the camera position.
+    pub fn to_orbit_controller(&self, camera: &Camera) -> OrbitController {
+        OrbitController::new(
+            camera,
+            Vec3::from(self.target),
+            self.orbit_speed,
+            self.zoom_speed,
+            self.min_distance,
+            self.max_distance,
+            self.orbit_dampening,
+            self.zoom_dampening,
+        )
+    }
+}
+
+impl Default for LightProbeDescription {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            shape: LightProbeShape::Box {
+                half_extents: [5.0, 5.0, 5.0],
+            },
+            blend_distance: 0.5,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl LightProbeDescription {
+    pub fn to_light_probe(&self) -> LightProbe {
+        let shape = match self.shape {
+            LightProbeShape::Box { half_extents } => ProbeShape::Box(Vec3::from(half_extents)),
+            LightProbeShape::Sphere { radius } => ProbeShape::Sphere(radius),
+        };
+
+        LightProbe {
+            position: Vec3::from(self.position),
+            shape,
+            blend_distance: self.blend_distance,
+            intensity: self.intensity,
+        }
+    }
+}
+
+impl SceneFile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // directory is used to resolve the asset paths of the scene.
+    pub fn from_ron<P: AsRef<Path>>(source: &str, directory: P) -> Result<Self, String> {
+        let mut scene: Self = ron::de::from_str(source).map_err(|e| e.to_string())?;
+        scene.directory = directory.as_ref().to_path_buf();
+
+        Ok(scene)
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())
+    }
+
+    // Paths are written as they are, so they stay relative to the directory the scene was
+    // loaded from.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let source = self.to_ron()?;
+
+        fs::write(path.as_ref(), source)
+            .map_err(|e| format!("Failed to save scene {:?}: {}", path.as_ref(), e))
+    }
+
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.directory.join(path)
+    }
+
+    // Loads the assets the scene references. asset_path is the engine asset directory that holds
+    // the material shaders. viewport_size sets the aspect ratio of the camera.
+    pub fn instantiate<A: AsRef<Path>>(
+        &self,
+        asset_manager: &mut AssetManager,
+        asset_path: A,
+        viewport_size: UVec2,
+    ) -> Result<LoadedScene, String> {
+        let environment = match &self.environment {
+            Some(environment) => Some(LoadedEnvironment {
+                skybox: asset_manager.load_texture_cube(self.resolve(&environment.skybox))?,
+                irradiance: asset_manager
+                    .load_texture_cube(self.resolve(&environment.irradiance))?,
+                radiance: asset_manager.load_texture_cube(self.resolve(&environment.radiance))?,
+            }),
+            None => None,
+        };
+
+        let mut entities = Vec::new();
+        for entity in &self.entities {
+            self.instantiate_entity(
+                entity,
+                Mat4::identity(),
+                None,
+                asset_manager,
+                asset_path.as_ref(),
+                &mut entities,
+            )?;
+        }
+
+        let camera = self.camera.to_camera(viewport_size);
+
+        Ok(LoadedScene {
+            name: self.name.clone(),
+            camera_controller: self.camera.to_orbit_controller(&camera),
+            camera,
+            environment,
+            lights: self.lights.clone(),
+            light_probes: self.light_probes.clone(),
+            entities,
+            fog: self.fog.clone(),
+            post_processing: self.post_processing.clone(),
+        })
+    }
+
+    fn instantiate_entity(
+        &self,
+        entity: &EntityDescription,
+        parent_transform: Mat4,
+        parent: Option<usize>,
+        asset_manager: &mut AssetManager,
+        asset_path: &Path,
+        entities: &mut Vec<LoadedEntity>,
+    ) -> Result<(), String> {
+        let transform = parent_transform * entity.transform.matrix();
+
+        let mesh = match &entity.mesh {
+            Some(mesh) => Some(asset_manager.load_mesh(self.resolve(mesh))?),
+            None => None,
+        };
+
+        let material = match &entity.material {
+            Some(material) => {
+                let template = asset_manager.load_material_template(self.resolve(material))?;
+                Some(asset_manager.instantiate_material(&template, asset_path)?)
+            }
+            None => None,
+        };
+
+        let index = entities.len();
+        entities.push(LoadedEntity {
+            name: entity.name.clone(),
+            transform,
+            mesh,
+            material,
+            parent,
+        });
+
+        for child in &entity.children {
+            self.instantiate_entity(
+                child,
+                transform,
+                Some(index),
+                asset_manager,
+                asset_path,
+                entities,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Asset for SceneFile {
+    type Output = Self;
+    type Error = String;
+    type LoadConfig = ();
+
+    fn load<P: AsRef<Path> + Debug>(
+        path: P,
+        _: Option<Self::LoadConfig>,
+    ) -> Result<Self::Output, Self::Error> {
+        let source = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read scene {:?}: {}", path, e))?;
+
+        Self::from_ron(
+            &source,
+            path.as_ref().parent().unwrap_or_else(|| Path::new("")),
+        )
+        .map_err(|e| format!("Failed to parse scene {:?}: {}", path, e))
+    }
+}
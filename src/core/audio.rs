@@ -0,0 +1,194 @@
+use crate::core::{
+    camera::Camera,
+    ecs::{components::Transform, Entity, World},
+    math::Vec3,
+};
+use rodio::{decoder::LoopedDecoder, Decoder, OutputStream, OutputStreamHandle, Sink, SpatialSink};
+use std::{collections::HashMap, fs, io::Cursor, path::Path, sync::Arc};
+
+// Distance between the ears of the listener, in world units.
+const EAR_DISTANCE: f32 = 0.2;
+
+// An encoded sound, decoded every time it plays. Wav, Vorbis, Flac and MP3. Cheap to clone.
+#[derive(Clone)]
+pub struct AudioClip {
+    data: Arc<[u8]>,
+}
+
+// Plays a clip from the position of the entity. Sources without a Transform are not heard.
+#[derive(Clone)]
+pub struct AudioSource {
+    pub clip: AudioClip,
+    pub volume: f32,
+    pub looping: bool,
+    // Set to start playing from the beginning, cleared to stop. Cleared by Audio::update when a
+    // clip that doesn't loop ends.
+    pub playing: bool,
+}
+
+impl AudioClip {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        fs::read(path.as_ref())
+            .map(Self::from_bytes)
+            .map_err(|e| format!("Failed to read {}: {}", path.as_ref().display(), e))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self { data: data.into() }
+    }
+
+    fn decode(&self) -> Result<Decoder<Cursor<Arc<[u8]>>>, String> {
+        Decoder::new(Cursor::new(self.data.clone())).map_err(|e| e.to_string())
+    }
+
+    fn decode_looped(&self) -> Result<LoopedDecoder<Cursor<Arc<[u8]>>>, String> {
+        Decoder::new_looped(Cursor::new(self.data.clone())).map_err(|e| e.to_string())
+    }
+}
+
+impl AudioSource {
+    pub fn new(clip: AudioClip) -> Self {
+        Self {
+            clip,
+            volume: 1.0,
+            looping: false,
+            playing: true,
+        }
+    }
+
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    // Starts stopped, set playing to start it.
+    pub fn paused(mut self) -> Self {
+        self.playing = false;
+        self
+    }
+}
+
+// Output to the default audio device, with the camera as the listener of the AudioSources of
+// the world. Sources are panned and attenuated by their position relative to the camera.
+pub struct Audio {
+    // Playback stops when the stream is dropped.
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sinks: HashMap<Entity, SpatialSink>,
+    master_volume: f32,
+}
+
+impl Audio {
+    // Fails without an audio device, scenes stay silent then.
+    pub fn new() -> Result<Self, String> {
+        let (stream, handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            _stream: stream,
+            handle,
+            sinks: HashMap::new(),
+            master_volume: 1.0,
+        })
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume
+    }
+
+    // Plays the clip once without a position, e.g. for UI sounds.
+    pub fn play(&self, clip: &AudioClip, volume: f32) {
+        let result = Sink::try_new(&self.handle)
+            .map_err(|e| e.to_string())
+            .and_then(|sink| {
+                sink.set_volume(volume * self.master_volume);
+                sink.append(clip.decode()?);
+                sink.detach();
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            log::warn!("Failed to play audio clip: {}", e)
+        }
+    }
+
+    // Starts and stops the sources of the world and moves them and the listener. Runs after
+    // systems::update_transforms, once per frame.
+    pub fn update(&mut self, world: &mut World, camera: &Camera) {
+        let half_ears = camera.right() * EAR_DISTANCE * 0.5;
+        let left_ear = to_array(&(camera.position() - half_ears));
+        let right_ear = to_array(&(camera.position() + half_ears));
+
+        self.sinks
+            .retain(|&entity, _| world.is_alive(entity) && world.has::<AudioSource>(entity));
+
+        let handle = &self.handle;
+        let sinks = &mut self.sinks;
+        let master_volume = self.master_volume;
+
+        world.for_each2_mut::<AudioSource, Transform, _>(|entity, source, transform| {
+            let position = to_array(&transform.world_position());
+
+            if !source.playing {
+                sinks.remove(&entity);
+                return;
+            }
+
+            if let Some(sink) = sinks.get(&entity) {
+                if sink.empty() {
+                    sinks.remove(&entity);
+                    source.playing = false;
+                    return;
+                }
+            } else {
+                match start(handle, source, position, left_ear, right_ear) {
+                    Ok(sink) => {
+                        sinks.insert(entity, sink);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to play the audio source of {:?}: {}", entity, e);
+                        source.playing = false;
+                        return;
+                    }
+                }
+            }
+
+            let sink = &sinks[&entity];
+            sink.set_emitter_position(position);
+            sink.set_left_ear_position(left_ear);
+            sink.set_right_ear_position(right_ear);
+            sink.set_volume(source.volume * master_volume);
+        });
+    }
+}
+
+fn start(
+    handle: &OutputStreamHandle,
+    source: &AudioSource,
+    position: [f32; 3],
+    left_ear: [f32; 3],
+    right_ear: [f32; 3],
+) -> Result<SpatialSink, String> {
+    let sink =
+        SpatialSink::try_new(handle, position, left_ear, right_ear).map_err(|e| e.to_string())?;
+
+    if source.looping {
+        sink.append(source.clip.decode_looped()?)
+    } else {
+        sink.append(source.clip.decode()?)
+    }
+
+    Ok(sink)
+}
+
+fn to_array(vector: &Vec3) -> [f32; 3] {
+    [vector.x, vector.y, vector.z]
+}
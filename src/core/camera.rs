@@ -1,160 +1,324 @@
-use crate::core::math::{clamp_scalar, rotate_vec3};
-use crate::core::{math, math::matrix, math::Axes, math::Mat4, math::Quat, math::Vec3};
+use crate::core::math::{self, Axes, Mat4, UVec2, Vec2, Vec3, Vec4};
+use crate::geometry::ray::Ray;
 use crate::imgui::{im_str, Gui, Ui};
-use crate::math::quaternion;
-use nalgebra_glm::{normalize, quat_normalize};
-use std::ops::RangeInclusive;
+use crate::rendering::{framebuffer::Framebuffer, state::StateManager};
+use nalgebra_glm as glm;
+use std::{ops::RangeInclusive, rc::Rc};
+
+pub mod bookmarks;
+pub mod controller;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // Vertical field of view in degrees.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    // Height of the view volume in world units. The width follows from the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> Mat4 {
+        match *self {
+            Projection::Perspective { fov_y, near, far } => {
+                glm::perspective(aspect, fov_y.to_radians(), near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height * 0.5;
+                let half_width = half_height * aspect;
+
+                glm::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        match *self {
+            Projection::Perspective { near, .. } | Projection::Orthographic { near, .. } => near,
+        }
+    }
+
+    pub fn far(&self) -> f32 {
+        match *self {
+            Projection::Perspective { far, .. } | Projection::Orthographic { far, .. } => far,
+        }
+    }
+}
+
+// Where a camera renders to.
+#[derive(Debug, Clone)]
+pub enum CameraTarget {
+    // The framebuffer the passes present into, see Framebuffer::default_id.
+    Screen,
+    // An offscreen framebuffer whose color attachment materials sample, e.g. for mirrors,
+    // in-world screens and minimaps.
+    Texture(Rc<Framebuffer>),
+}
 
+impl Default for CameraTarget {
+    fn default() -> Self {
+        CameraTarget::Screen
+    }
+}
+
+// A view and projection plus the physical exposure settings that drive the tone mapper. Movement
+// is left to the controllers in camera::controller.
+#[derive(Debug, Clone)]
 pub struct Camera {
     position: Vec3,
-    orientation: Quat,
-    transform: Mat4,
+    view: Mat4,
+    projection: Projection,
+    viewport_size: UVec2,
+    // Sub pixel offset of the projection in pixels, e.g. for temporal anti aliasing.
+    jitter: Vec2,
     aperture: f32,
     shutter_speed: f32,
     sensitivity: f32,
-    orbit_speed: f32,
-    zoom_speed: f32,
-    orbit_dampening: f32,
-    zoom_dampening: f32,
-    min_distance: f32,
-    max_distance: f32,
-    yaw: f32,
-    pitch: f32,
-    distance: f32,
-    prev_distance: f32,
+    motion_blur: bool,
+    target: CameraTarget,
 }
 
 impl Camera {
-    pub fn new(
-        position: Vec3,
-        target: Vec3,
-        orbit_speed: f32,
-        zoom_speed: f32,
-        min_distance: f32,
-        max_distance: f32,
-        orbit_dampening: f32,
-        zoom_dampening: f32,
-    ) -> Self {
-        let transform = math::look_at(&position, &target, &Axes::up());
-
-        let distance = (position - target).norm();
-
-        Camera {
-            position,
-            orientation: matrix::to_rotation_quat(&transform),
-            transform,
+    pub fn new(projection: Projection, viewport_size: UVec2) -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            view: Mat4::identity(),
+            projection,
+            viewport_size,
+            jitter: Vec2::new(0.0, 0.0),
             aperture: 1.4,
             shutter_speed: 0.55,
             sensitivity: 500.0,
-            orbit_speed,
-            zoom_speed,
-            min_distance,
-            max_distance,
-            orbit_dampening,
-            zoom_dampening,
-            yaw: 0.0,
-            pitch: 0.0,
-            distance,
-            prev_distance: distance,
+            motion_blur: true,
+            target: CameraTarget::Screen,
         }
     }
 
+    pub fn perspective(fov_y: f32, near: f32, far: f32, viewport_size: UVec2) -> Self {
+        Self::new(Projection::Perspective { fov_y, near, far }, viewport_size)
+    }
+
+    pub fn orthographic(height: f32, near: f32, far: f32, viewport_size: UVec2) -> Self {
+        Self::new(
+            Projection::Orthographic { height, near, far },
+            viewport_size,
+        )
+    }
+
     pub fn position(&self) -> &Vec3 {
         &self.position
     }
 
+    // The view matrix.
     pub fn transform(&self) -> &Mat4 {
-        &self.transform
+        &self.view
     }
 
-    pub fn orbit_speed(&self) -> f32 {
-        self.orbit_speed
+    pub fn look_at(&mut self, position: Vec3, target: Vec3, up: Vec3) {
+        self.position = position;
+        self.view = math::look_at(&position, &target, &up)
     }
 
-    pub fn zoom_speed(&self) -> f32 {
-        self.zoom_speed
-    }
+    // Looks along direction. Falls back to +Z as up when looking straight up or down.
+    pub fn look_to(&mut self, position: Vec3, direction: Vec3) {
+        let up = if direction.normalize().dot(&Axes::up()).abs() > 0.9999 {
+            Axes::forward()
+        } else {
+            Axes::up()
+        };
 
-    pub fn orbit_dampening(&self) -> f32 {
-        self.orbit_dampening
+        self.look_at(position, position + direction, up)
     }
 
-    pub fn zoom_dampening(&self) -> f32 {
-        self.zoom_dampening
+    pub fn forward(&self) -> Vec3 {
+        -Vec3::new(self.view.m31, self.view.m32, self.view.m33)
     }
 
-    pub fn look_at(&mut self, position: Vec3, target: Vec3, up: Vec3) {
-        self.transform = math::look_at(&position, &target, &up)
+    pub fn right(&self) -> Vec3 {
+        Vec3::new(self.view.m11, self.view.m12, self.view.m13)
     }
 
-    pub fn set_transform(&mut self, transform: Mat4) {
-        self.transform = transform
+    pub fn up(&self) -> Vec3 {
+        Vec3::new(self.view.m21, self.view.m22, self.view.m23)
     }
 
-    pub fn set_distance(&mut self, d: f32) {
-        self.distance = d;
-        self.prev_distance = self.distance;
+    pub fn projection(&self) -> &Projection {
+        &self.projection
     }
 
-    pub fn set_orbit_speed(&mut self, orbit_speed: f32) {
-        self.orbit_speed = orbit_speed
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection
     }
 
-    pub fn set_zoom_speed(&mut self, zoom_speed: f32) {
-        self.zoom_speed = zoom_speed
+    pub fn viewport_size(&self) -> UVec2 {
+        self.viewport_size
     }
 
-    pub fn set_orbit_dampening(&mut self, orbit_dampening: f32) {
-        self.orbit_dampening = orbit_dampening
+    // Call on resize to keep the aspect ratio in sync.
+    pub fn set_viewport_size(&mut self, viewport_size: UVec2) {
+        self.viewport_size = viewport_size
     }
 
-    pub fn set_zoom_dampening(&mut self, zoom_dampening: f32) {
-        self.zoom_dampening = zoom_dampening
+    pub fn target(&self) -> &CameraTarget {
+        &self.target
     }
 
-    pub fn update(&mut self, mouse_dx: f32, mouse_dy: f32, mouse_scroll: f32, dt: f32) {
-        const EPSILON: f32 = 0.00001;
+    // Texture targets also set the viewport size to their size.
+    pub fn set_target(&mut self, target: CameraTarget) {
+        if let CameraTarget::Texture(framebuffer) = &target {
+            self.viewport_size = framebuffer.size()
+        }
 
-        if mouse_dx < -EPSILON || mouse_dx > EPSILON || mouse_dy < -EPSILON || mouse_dy > EPSILON {
-            self.pitch += mouse_dy * self.orbit_speed * dt;
+        self.target = target
+    }
 
-            self.yaw += mouse_dx * self.orbit_speed * dt;
+    pub fn renders_to_texture(&self) -> bool {
+        match self.target {
+            CameraTarget::Texture(_) => true,
+            CameraTarget::Screen => false,
+        }
+    }
 
-            if self.yaw < 0.0 {
-                self.yaw += 360.0;
-            } else if self.yaw >= 360.0 {
-                self.yaw -= 360.0;
+    // Binds the target with a viewport of the viewport size, for the passes of the camera to
+    // render into.
+    pub fn bind_target(&self) {
+        match &self.target {
+            CameraTarget::Texture(framebuffer) => framebuffer.bind(),
+            CameraTarget::Screen => {
+                StateManager::bind_framebuffer(Framebuffer::default_id());
+                StateManager::set_viewport(
+                    0,
+                    0,
+                    self.viewport_size.x as i32,
+                    self.viewport_size.y as i32,
+                )
             }
-
-            self.pitch = clamp_scalar(self.pitch, -89.99, 89.99);
         }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.viewport_size.x.max(1) as f32 / self.viewport_size.y.max(1) as f32
+    }
 
-        if mouse_scroll != 0.0 {
-            let mut scroll_amount = mouse_scroll * self.zoom_speed;
-            scroll_amount *= self.distance * 0.3;
-            self.distance -= scroll_amount * dt;
+    pub fn jitter(&self) -> Vec2 {
+        self.jitter
+    }
+
+    pub fn set_jitter(&mut self, jitter: Vec2) {
+        self.jitter = jitter
+    }
+
+    // Halton(2, 3) offset in [-0.5, 0.5] pixels for the given frame.
+    pub fn halton_jitter(frame: u32, sequence_length: u32) -> Vec2 {
+        let index = frame % sequence_length.max(1) + 1;
+
+        Vec2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    // The projection including the jitter.
+    pub fn projection_matrix(&self) -> Mat4 {
+        let projection = self.unjittered_projection_matrix();
+
+        if self.jitter == Vec2::new(0.0, 0.0) {
+            return projection;
         }
 
-        self.distance =
-            math::lerp_scalar(self.prev_distance, self.distance, dt * self.zoom_dampening);
-        self.distance = clamp_scalar(self.distance, self.min_distance, self.max_distance);
-        self.prev_distance = self.distance;
+        // Offsetting in clip space after the projection shifts the image by a constant amount
+        // of NDC for perspective and orthographic projections alike.
+        let offset = Vec3::new(
+            2.0 * self.jitter.x / self.viewport_size.x.max(1) as f32,
+            2.0 * self.jitter.y / self.viewport_size.y.max(1) as f32,
+            0.0,
+        );
 
-        let dest = quat_normalize(&quaternion::from_euler(self.yaw, self.pitch, 0.0));
-        self.orientation = quaternion::slerp(&self.orientation, &dest, dt * self.orbit_dampening);
-        let direction = normalize(&rotate_vec3(&self.orientation, &Axes::forward()));
-        self.position = Vec3::new(0.0, 0.0, 0.0) - direction * self.distance;
+        glm::translation(&offset) * projection
+    }
 
-        self.look_at(self.position, Vec3::new(0.0, 0.0, 0.0), Axes::up());
+    pub fn unjittered_projection_matrix(&self) -> Mat4 {
+        self.projection.matrix(self.aspect())
     }
 
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view
+    }
+
+    // World space ray through a point of the viewport, in pixels from its top left corner, e.g.
+    // the mouse position. Starts on the near plane.
+    pub fn screen_ray(&self, screen_position: Vec2) -> Ray {
+        let viewport_size = self.viewport_size();
+        let x = screen_position.x / viewport_size.x.max(1) as f32 * 2.0 - 1.0;
+        let y = 1.0 - screen_position.y / viewport_size.y.max(1) as f32 * 2.0;
+
+        let inverse = glm::inverse(&(self.unjittered_projection_matrix() * self.view));
+        let unproject = |z: f32| {
+            let point = inverse * Vec4::new(x, y, z, 1.0);
+            point.xyz() / point.w
+        };
+
+        let near = unproject(-1.0);
+        Ray::new(near, unproject(1.0) - near)
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn shutter_speed(&self) -> f32 {
+        self.shutter_speed
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    pub fn set_aperture(&mut self, aperture: f32) {
+        self.aperture = aperture
+    }
+
+    pub fn set_shutter_speed(&mut self, shutter_speed: f32) {
+        self.shutter_speed = shutter_speed
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity
+    }
+
+    // Whether the motion blur post effect runs for the views of this camera.
+    pub fn motion_blur(&self) -> bool {
+        self.motion_blur
+    }
+
+    pub fn set_motion_blur(&mut self, motion_blur: bool) {
+        self.motion_blur = motion_blur
+    }
+
+    pub fn ev100(&self) -> f32 {
+        f32::log2(self.aperture * self.aperture / self.shutter_speed * 100.0 / self.sensitivity)
+    }
+
+    // Exposure multiplier for the tone mapper.
     pub fn exposure(&self) -> f32 {
-        let ev100 = f32::log2(
-            self.aperture * self.aperture / self.shutter_speed * 100.0 / self.sensitivity,
-        );
+        1.0 / 2.0f32.powf(self.ev100()) * 1.2
+    }
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
 
-        1.0 / 2.0f32.powf(ev100) * 1.2
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
     }
+
+    result
 }
 
 impl Gui for Camera {
@@ -167,7 +331,7 @@ impl Gui for Camera {
         {
             ui.spacing();
             ui.group(|| {
-                imgui::TreeNode::new(im_str!("Controls"))
+                imgui::TreeNode::new(im_str!("Exposure"))
                     .default_open(true)
                     .open_on_arrow(true)
                     .open_on_double_click(true)
@@ -200,44 +364,40 @@ impl Gui for Camera {
                             self.sensitivity = sensitivity;
                         }
 
-                        ui.spacing();
-                        ui.separator();
-                        ui.spacing();
-
-                        let mut orbit_speed = self.orbit_speed;
-                        if imgui::Slider::new(im_str!("Orbit Speed"))
-                            .range(RangeInclusive::new(1.0, 10.0))
-                            .display_format(im_str!("%.2f"))
-                            .build(&ui, &mut orbit_speed)
-                        {
-                            self.set_orbit_speed(orbit_speed)
-                        }
-
-                        let mut orbit_dampening = self.orbit_dampening();
-                        if imgui::Slider::new(im_str!("Orbit Dampening"))
-                            .range(RangeInclusive::new(1.0, 10.0))
-                            .display_format(im_str!("%.2f"))
-                            .build(&ui, &mut orbit_dampening)
-                        {
-                            self.set_orbit_dampening(orbit_dampening)
-                        }
+                        ui.checkbox(im_str!("Motion Blur"), &mut self.motion_blur);
+                    });
 
-                        let mut zoom_speed = self.zoom_speed();
-                        if imgui::Slider::new(im_str!("Zoom Speed"))
-                            .range(RangeInclusive::new(1.0, 40.0))
-                            .display_format(im_str!("%.2f"))
-                            .build(&ui, &mut zoom_speed)
-                        {
-                            self.set_zoom_speed(zoom_speed)
+                imgui::TreeNode::new(im_str!("Projection"))
+                    .default_open(false)
+                    .open_on_arrow(true)
+                    .open_on_double_click(true)
+                    .framed(false)
+                    .build(ui, || match &mut self.projection {
+                        Projection::Perspective { fov_y, near, far } => {
+                            imgui::Slider::new(im_str!("Field of View"))
+                                .range(RangeInclusive::new(10.0, 120.0))
+                                .display_format(im_str!("%.1f"))
+                                .build(ui, fov_y);
+                            imgui::Drag::new(im_str!("Near"))
+                                .range(RangeInclusive::new(0.001, *far))
+                                .speed(0.01)
+                                .build(ui, near);
+                            imgui::Drag::new(im_str!("Far"))
+                                .range(RangeInclusive::new(*near, 100_000.0))
+                                .build(ui, far);
                         }
-
-                        let mut zoom_dampening = self.zoom_dampening();
-                        if imgui::Slider::new(im_str!("Zoom Dampening"))
-                            .range(RangeInclusive::new(0.1, 10.0))
-                            .display_format(im_str!("%.2f"))
-                            .build(&ui, &mut zoom_dampening)
-                        {
-                            self.set_zoom_dampening(zoom_dampening)
+                        Projection::Orthographic { height, near, far } => {
+                            imgui::Drag::new(im_str!("Height"))
+                                .range(RangeInclusive::new(0.01, 100_000.0))
+                                .speed(0.1)
+                                .build(ui, height);
+                            imgui::Drag::new(im_str!("Near"))
+                                .range(RangeInclusive::new(-100_000.0, *far))
+                                .speed(0.01)
+                                .build(ui, near);
+                            imgui::Drag::new(im_str!("Far"))
+                                .range(RangeInclusive::new(*near, 100_000.0))
+                                .build(ui, far);
                         }
                     });
             });
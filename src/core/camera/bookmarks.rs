@@ -0,0 +1,420 @@
+use crate::core::camera::Camera;
+use crate::core::math::Vec3;
+use crate::imgui::{im_str, ImString, Ui};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{fs, ops::RangeInclusive, path::Path};
+
+// Seconds after the last key that keys added to the path get.
+const KEY_SPACING: f32 = 2.0;
+
+// A named placement of the camera.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPathKey {
+    // Seconds from the start of the path.
+    pub time: f32,
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+}
+
+// Placements of the camera over time. The positions and targets of the keys are interpolated
+// with a Catmull-Rom spline, which passes through all of them. Keys are sorted by time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraPath {
+    keys: Vec<CameraPathKey>,
+    // Starts over after the last key instead of stopping.
+    pub looping: bool,
+}
+
+impl CameraPath {
+    pub fn keys(&self) -> &[CameraPathKey] {
+        &self.keys
+    }
+
+    // The time of the last key.
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    // Returns the index the key was inserted at.
+    pub fn insert(&mut self, time: f32, position: &Vec3, target: &Vec3) -> usize {
+        let time = time.max(0.0);
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(
+            index,
+            CameraPathKey {
+                time,
+                position: [position.x, position.y, position.z],
+                target: [target.x, target.y, target.z],
+            },
+        );
+        index
+    }
+
+    // Inserts the key KEY_SPACING seconds after the last one, at the start of an empty path.
+    pub fn append(&mut self, position: &Vec3, target: &Vec3) -> usize {
+        let time = if self.keys.is_empty() {
+            0.0
+        } else {
+            self.duration() + KEY_SPACING
+        };
+
+        self.insert(time, position, target)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.keys.len() {
+            self.keys.remove(index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear()
+    }
+
+    // The time is clamped between the neighbours of the key, so the keys keep their order.
+    pub fn set_time(&mut self, index: usize, time: f32) {
+        let min = if index > 0 {
+            self.keys[index - 1].time
+        } else {
+            0.0
+        };
+        let max = self
+            .keys
+            .get(index + 1)
+            .map_or(std::f32::MAX, |key| key.time);
+
+        self.keys[index].time = time.max(min).min(max);
+    }
+
+    // The position and target at the time, None without keys.
+    pub fn sample(&self, time: f32) -> Option<(Vec3, Vec3)> {
+        let count = self.keys.len();
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+
+        // The first and last placements hold before and after the keys.
+        let next = match self.keys.iter().position(|key| key.time > time) {
+            Some(0) => return Some((Vec3::from(first.position), Vec3::from(first.target))),
+            Some(next) => next,
+            None => return Some((Vec3::from(last.position), Vec3::from(last.target))),
+        };
+
+        // The neighbours of the segment shape its tangents, the end keys stand in for missing
+        // ones.
+        let (p0, p1, p2, p3) = (
+            &self.keys[next.saturating_sub(2)],
+            &self.keys[next - 1],
+            &self.keys[next],
+            &self.keys[(next + 1).min(count - 1)],
+        );
+        let t = (time - p1.time) / (p2.time - p1.time).max(std::f32::EPSILON);
+
+        let spline = |a: [f32; 3], b: [f32; 3], c: [f32; 3], d: [f32; 3]| {
+            catmull_rom(
+                &Vec3::from(a),
+                &Vec3::from(b),
+                &Vec3::from(c),
+                &Vec3::from(d),
+                t,
+            )
+        };
+
+        Some((
+            spline(p0.position, p1.position, p2.position, p3.position),
+            spline(p0.target, p1.target, p2.target, p3.target),
+        ))
+    }
+}
+
+// Uniform Catmull-Rom spline through p1 at t = 0 and p2 at t = 1.
+fn catmull_rom(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+// Frames of the last complete playback, e.g. to compare benchmark runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackRun {
+    pub frames: u32,
+    pub seconds: f32,
+}
+
+impl PlaybackRun {
+    pub fn average_frame_time(&self) -> f32 {
+        self.seconds / self.frames.max(1) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Playback {
+    time: f32,
+    frames: u32,
+    seconds: f32,
+}
+
+// Bookmarks of camera placements and a path through them, saved together as RON. The path plays
+// back with the time step it is updated with, so a fixed time step flies the same frames every
+// run, e.g. for benchmarks and demo videos.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraBookmarks {
+    pub bookmarks: Vec<CameraBookmark>,
+    pub path: CameraPath,
+    // Of the playback.
+    pub speed: f32,
+    #[serde(skip)]
+    playback: Option<Playback>,
+    #[serde(skip)]
+    last_run: Option<PlaybackRun>,
+    #[serde(skip)]
+    name: ImString,
+}
+
+impl CameraBookmarks {
+    pub fn new() -> Self {
+        Self {
+            bookmarks: Vec::new(),
+            path: CameraPath::default(),
+            speed: 1.0,
+            playback: None,
+            last_run: None,
+            name: ImString::with_capacity(64),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read camera bookmarks {:?}: {}", path.as_ref(), e))?;
+        let bookmarks: Self = ron::de::from_str(&source).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            name: ImString::with_capacity(64),
+            ..bookmarks
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let source =
+            ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())?;
+
+        fs::write(path.as_ref(), source)
+            .map_err(|e| format!("Failed to save camera bookmarks {:?}: {}", path.as_ref(), e))
+    }
+
+    pub fn add(&mut self, name: &str, camera: &Camera, target: &Vec3) {
+        let position = camera.position();
+
+        self.bookmarks.push(CameraBookmark {
+            name: name.to_string(),
+            position: [position.x, position.y, position.z],
+            target: [target.x, target.y, target.z],
+        })
+    }
+
+    // Places the camera at the bookmark and returns its target, None if there is no such
+    // bookmark.
+    pub fn recall(&self, index: usize, camera: &mut Camera) -> Option<Vec3> {
+        let bookmark = self.bookmarks.get(index)?;
+        let target = Vec3::from(bookmark.target);
+        place(camera, &Vec3::from(bookmark.position), &target);
+
+        Some(target)
+    }
+
+    pub fn play(&mut self) {
+        self.playback = Some(Playback {
+            time: 0.0,
+            frames: 0,
+            seconds: 0.0,
+        })
+    }
+
+    pub fn stop(&mut self) {
+        self.playback = None
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn last_run(&self) -> Option<PlaybackRun> {
+        self.last_run
+    }
+
+    // Advances the playback and places the camera on the path. Returns the target the camera
+    // looks at while playing, None once the path has ended or while stopped, e.g. for a
+    // controller to take over from there.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) -> Option<Vec3> {
+        let mut playback = self.playback?;
+        let duration = self.path.duration();
+
+        playback.time += dt * self.speed;
+        playback.frames += 1;
+        playback.seconds += dt;
+
+        if playback.time > duration {
+            if self.path.looping && duration > 0.0 {
+                playback.time %= duration;
+            } else {
+                playback.time = duration;
+                self.playback = None;
+                self.last_run = Some(PlaybackRun {
+                    frames: playback.frames,
+                    seconds: playback.seconds,
+                });
+            }
+        }
+
+        let (position, target) = self.path.sample(playback.time)?;
+        place(camera, &position, &target);
+
+        if self.playback.is_some() {
+            self.playback = Some(playback);
+        }
+
+        Some(target)
+    }
+
+    // Edits the bookmarks and the path, with the current placement of the camera for the ones
+    // added. Returns the target of the camera if it was moved, e.g. to a recalled bookmark.
+    pub fn gui(&mut self, ui: &Ui, camera: &mut Camera, target: &Vec3) -> Option<Vec3> {
+        let mut moved = None;
+
+        imgui::TreeNode::new(im_str!("Bookmarks"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                ui.input_text(im_str!("Name"), &mut self.name).build();
+                ui.same_line(0.0);
+                if ui.button(im_str!("Add"), [0.0, 0.0]) {
+                    let name = match self.name.to_str() {
+                        "" => format!("Bookmark {}", self.bookmarks.len() + 1),
+                        name => name.to_string(),
+                    };
+                    self.add(&name, camera, target);
+                    self.name.clear();
+                }
+
+                let mut removed = None;
+                for (i, bookmark) in self.bookmarks.iter().enumerate() {
+                    if ui.button(&im_str!("Go##bookmark{}", i), [0.0, 0.0]) {
+                        moved = self.recall(i, camera);
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(&im_str!("X##bookmark{}", i), [0.0, 0.0]) {
+                        removed = Some(i);
+                    }
+                    ui.same_line(0.0);
+                    ui.text(&bookmark.name);
+                }
+
+                if let Some(i) = removed {
+                    self.bookmarks.remove(i);
+                }
+            });
+
+        imgui::TreeNode::new(im_str!("Path"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                if ui.button(im_str!("Add Key"), [0.0, 0.0]) {
+                    self.path.append(camera.position(), target);
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Add Bookmarks"), [0.0, 0.0]) {
+                    for bookmark in &self.bookmarks {
+                        self.path
+                            .append(&Vec3::from(bookmark.position), &Vec3::from(bookmark.target));
+                    }
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Clear"), [0.0, 0.0]) {
+                    self.stop();
+                    self.path.clear();
+                }
+
+                let mut removed = None;
+                for i in 0..self.path.keys().len() {
+                    let mut time = self.path.keys()[i].time;
+                    if imgui::Drag::new(&im_str!("##key{}", i))
+                        .range(RangeInclusive::new(0.0, 3600.0))
+                        .speed(0.01)
+                        .display_format(im_str!("%.2f s"))
+                        .build(ui, &mut time)
+                    {
+                        self.path.set_time(i, time);
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(&im_str!("X##key{}", i), [0.0, 0.0]) {
+                        removed = Some(i);
+                    }
+                }
+
+                if let Some(i) = removed {
+                    self.path.remove(i);
+                }
+
+                ui.checkbox(im_str!("Loop"), &mut self.path.looping);
+                imgui::Slider::new(im_str!("Speed"))
+                    .range(RangeInclusive::new(0.1, 4.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(ui, &mut self.speed);
+
+                match self.playback {
+                    Some(playback) => {
+                        if ui.button(im_str!("Stop"), [0.0, 0.0]) {
+                            self.stop();
+                        }
+                        ui.same_line(0.0);
+                        ui.text(format!(
+                            "{:.2} / {:.2} s",
+                            playback.time,
+                            self.path.duration()
+                        ));
+                    }
+                    None => {
+                        if ui.button(im_str!("Play"), [0.0, 0.0]) && !self.path.keys().is_empty() {
+                            self.play();
+                        }
+                    }
+                }
+
+                if let Some(run) = self.last_run {
+                    ui.text(format!(
+                        "Last run: {} frames, {:.2} ms average",
+                        run.frames,
+                        run.average_frame_time() * 1000.0
+                    ));
+                }
+            });
+
+        moved
+    }
+}
+
+impl Default for CameraBookmarks {
+    fn default() -> Self {
+        CameraBookmarks::new()
+    }
+}
+
+fn place(camera: &mut Camera, position: &Vec3, target: &Vec3) {
+    camera.look_to(*position, target - position)
+}
@@ -0,0 +1,400 @@
+use crate::core::camera::Camera;
+use crate::core::math::{self, clamp_scalar, quaternion, rotate_vec3, Axes, Quat, Vec2, Vec3};
+use crate::imgui::{im_str, Gui, Ui};
+use crate::platform::{
+    action::{ActionMap, AxisDirection, Binding},
+    input::{Axis, Button, Input},
+};
+use glutin::event::{MouseButton, VirtualKeyCode};
+use nalgebra_glm::{normalize, quat_normalize};
+use std::ops::RangeInclusive;
+
+// The actions and axes the controllers read. bind_default_actions binds them to the mouse,
+// WASD/Q/E and the sticks and triggers of gamepads.
+pub const ORBIT: &str = "camera_orbit";
+// For the application to toggle Input::set_cursor_captured with. The controllers turn with the
+// mouse without holding ORBIT or LOOK while the cursor is captured.
+pub const CAPTURE_CURSOR: &str = "camera_capture_cursor";
+pub const LOOK: &str = "camera_look";
+pub const BOOST: &str = "camera_boost";
+// Right, up and forward in camera space.
+pub const MOVE_X: &str = "camera_move_x";
+pub const MOVE_Y: &str = "camera_move_y";
+pub const MOVE_Z: &str = "camera_move_z";
+// Turns the camera without holding ORBIT or LOOK, for the right stick.
+pub const TURN_X: &str = "camera_turn_x";
+pub const TURN_Y: &str = "camera_turn_y";
+
+// Scales the cursor movement in pixels.
+const MOUSE_SENSITIVITY: f32 = 2.0;
+// The cursor movement in pixels a fully tilted stick is equivalent to per frame.
+const STICK_SENSITIVITY: f32 = 12.0;
+
+pub fn bind_default_actions(actions: &mut ActionMap) {
+    actions.bind(ORBIT, Binding::MouseButton(MouseButton::Left));
+    actions.bind(LOOK, Binding::MouseButton(MouseButton::Right));
+    actions.bind(CAPTURE_CURSOR, Binding::Key(VirtualKeyCode::Tab));
+    actions.bind(BOOST, Binding::Key(VirtualKeyCode::LShift));
+    actions.bind(BOOST, Binding::Key(VirtualKeyCode::RShift));
+    actions.bind(BOOST, Binding::GamepadButton(Button::LeftThumb));
+
+    actions.bind_axis(
+        MOVE_X,
+        Binding::Key(VirtualKeyCode::D),
+        Binding::Key(VirtualKeyCode::A),
+    );
+    actions.bind_gamepad_axis(MOVE_X, Axis::LeftStickX);
+    actions.bind_axis(
+        MOVE_Y,
+        Binding::Key(VirtualKeyCode::E),
+        Binding::Key(VirtualKeyCode::Q),
+    );
+    actions.bind_axis(
+        MOVE_Y,
+        Binding::GamepadAxis(Axis::RightZ, AxisDirection::Positive),
+        Binding::GamepadAxis(Axis::LeftZ, AxisDirection::Positive),
+    );
+    actions.bind_axis(
+        MOVE_Z,
+        Binding::Key(VirtualKeyCode::W),
+        Binding::Key(VirtualKeyCode::S),
+    );
+    actions.bind_gamepad_axis(MOVE_Z, Axis::LeftStickY);
+
+    actions.bind_gamepad_axis(TURN_X, Axis::RightStickX);
+    // Pushing the stick up looks up.
+    actions.bind_axis(
+        TURN_Y,
+        Binding::GamepadAxis(Axis::RightStickY, AxisDirection::Negative),
+        Binding::GamepadAxis(Axis::RightStickY, AxisDirection::Positive),
+    );
+}
+
+// Requested movement in camera space: x right, y up, z forward. Not normalized.
+fn movement(input: &Input) -> Vec3 {
+    Vec3::new(input.axis(MOVE_X), input.axis(MOVE_Y), input.axis(MOVE_Z))
+}
+
+// The rotation of the frame in cursor pixels, of the mouse while the action is held or the
+// cursor is captured and of the turn axes.
+fn turn_delta(input: &Input, action: &str) -> Vec2 {
+    let mouse_delta = if input.action_down(action) || input.cursor_captured() {
+        input.mouse_delta() * MOUSE_SENSITIVITY
+    } else {
+        Vec2::new(0.0, 0.0)
+    };
+
+    mouse_delta + Vec2::new(input.axis(TURN_X), input.axis(TURN_Y)) * STICK_SENSITIVITY
+}
+
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32);
+}
+
+// Orbits around a target while ORBIT is held or the turn axes are tilted and zooms with the
+// wheel.
+pub struct OrbitController {
+    target: Vec3,
+    orbit_speed: f32,
+    zoom_speed: f32,
+    orbit_dampening: f32,
+    zoom_dampening: f32,
+    min_distance: f32,
+    max_distance: f32,
+    yaw: f32,
+    pitch: f32,
+    orientation: Quat,
+    distance: f32,
+    prev_distance: f32,
+}
+
+impl OrbitController {
+    // Starts from the current placement of the camera, looking at target.
+    pub fn new(
+        camera: &Camera,
+        target: Vec3,
+        orbit_speed: f32,
+        zoom_speed: f32,
+        min_distance: f32,
+        max_distance: f32,
+        orbit_dampening: f32,
+        zoom_dampening: f32,
+    ) -> Self {
+        let offset = target - camera.position();
+        let distance = offset.norm();
+
+        let (yaw, pitch) = if distance > std::f32::EPSILON {
+            yaw_pitch(&(offset / distance))
+        } else {
+            (0.0, 0.0)
+        };
+
+        Self {
+            target,
+            orbit_speed,
+            zoom_speed,
+            orbit_dampening,
+            zoom_dampening,
+            min_distance,
+            max_distance,
+            yaw,
+            pitch,
+            orientation: quaternion::from_euler(yaw, pitch, 0.0),
+            distance,
+            prev_distance: distance,
+        }
+    }
+
+    pub fn target(&self) -> &Vec3 {
+        &self.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn orbit_speed(&self) -> f32 {
+        self.orbit_speed
+    }
+
+    pub fn zoom_speed(&self) -> f32 {
+        self.zoom_speed
+    }
+
+    pub fn orbit_dampening(&self) -> f32 {
+        self.orbit_dampening
+    }
+
+    pub fn zoom_dampening(&self) -> f32 {
+        self.zoom_dampening
+    }
+
+    pub fn min_distance(&self) -> f32 {
+        self.min_distance
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance;
+        self.prev_distance = self.distance;
+    }
+
+    pub fn set_orbit_speed(&mut self, orbit_speed: f32) {
+        self.orbit_speed = orbit_speed
+    }
+
+    pub fn set_zoom_speed(&mut self, zoom_speed: f32) {
+        self.zoom_speed = zoom_speed
+    }
+
+    pub fn set_orbit_dampening(&mut self, orbit_dampening: f32) {
+        self.orbit_dampening = orbit_dampening
+    }
+
+    pub fn set_zoom_dampening(&mut self, zoom_dampening: f32) {
+        self.zoom_dampening = zoom_dampening
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        const EPSILON: f32 = 0.00001;
+
+        let delta = turn_delta(input, ORBIT);
+
+        if delta.x.abs() > EPSILON || delta.y.abs() > EPSILON {
+            self.pitch += delta.y * self.orbit_speed * dt;
+            self.yaw = wrap_degrees(self.yaw + delta.x * self.orbit_speed * dt);
+            self.pitch = clamp_scalar(self.pitch, -89.99, 89.99);
+        }
+
+        if input.scroll().y != 0.0 {
+            let mut scroll_amount = input.scroll().y * self.zoom_speed;
+            scroll_amount *= self.distance * 0.3;
+            self.distance -= scroll_amount * dt;
+        }
+
+        self.distance =
+            math::lerp_scalar(self.prev_distance, self.distance, dt * self.zoom_dampening);
+        self.distance = clamp_scalar(self.distance, self.min_distance, self.max_distance);
+        self.prev_distance = self.distance;
+
+        let dest = quat_normalize(&quaternion::from_euler(self.yaw, self.pitch, 0.0));
+        self.orientation = quaternion::slerp(&self.orientation, &dest, dt * self.orbit_dampening);
+        let direction = normalize(&rotate_vec3(&self.orientation, &Axes::forward()));
+
+        camera.look_at(
+            self.target - direction * self.distance,
+            self.target,
+            Axes::up(),
+        );
+    }
+}
+
+impl Gui for OrbitController {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Orbit Controls"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                imgui::Slider::new(im_str!("Orbit Speed"))
+                    .range(RangeInclusive::new(1.0, 10.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(ui, &mut self.orbit_speed);
+
+                imgui::Slider::new(im_str!("Orbit Dampening"))
+                    .range(RangeInclusive::new(1.0, 10.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(ui, &mut self.orbit_dampening);
+
+                imgui::Slider::new(im_str!("Zoom Speed"))
+                    .range(RangeInclusive::new(1.0, 40.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(ui, &mut self.zoom_speed);
+
+                imgui::Slider::new(im_str!("Zoom Dampening"))
+                    .range(RangeInclusive::new(0.1, 10.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(ui, &mut self.zoom_dampening);
+            });
+    }
+}
+
+// Free flight. Looks around while LOOK is held, the cursor is captured or the turn axes are
+// tilted. Moves along the view direction with the MOVE_Z axis, sideways with MOVE_X and
+// vertically with MOVE_Y. BOOST speeds the movement up.
+pub struct FlyController {
+    pub move_speed: f32,
+    pub look_speed: f32,
+    pub boost_factor: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+// Like FlyController, but walks on the XZ plane regardless of the pitch.
+pub struct FpsController {
+    pub move_speed: f32,
+    pub look_speed: f32,
+    pub boost_factor: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FlyController {
+    // Starts from the current placement of the camera.
+    pub fn new(camera: &Camera, move_speed: f32, look_speed: f32) -> Self {
+        let (yaw, pitch) = yaw_pitch(&camera.forward());
+
+        Self {
+            move_speed,
+            look_speed,
+            boost_factor: 4.0,
+            yaw,
+            pitch,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        look(&mut self.yaw, &mut self.pitch, self.look_speed, input, dt);
+
+        let forward = direction(self.yaw, self.pitch);
+        let right = normalize(&forward.cross(&Axes::up()));
+        let up = right.cross(&forward);
+
+        let movement = movement(input);
+        let velocity = right * movement.x + up * movement.y + forward * movement.z;
+
+        let position = camera.position()
+            + scaled_velocity(velocity, self.move_speed, self.boost_factor, input) * dt;
+        camera.look_to(position, forward);
+    }
+}
+
+impl FpsController {
+    // Starts from the current placement of the camera.
+    pub fn new(camera: &Camera, move_speed: f32, look_speed: f32) -> Self {
+        let (yaw, pitch) = yaw_pitch(&camera.forward());
+
+        Self {
+            move_speed,
+            look_speed,
+            boost_factor: 2.0,
+            yaw,
+            pitch,
+        }
+    }
+}
+
+impl CameraController for FpsController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        look(&mut self.yaw, &mut self.pitch, self.look_speed, input, dt);
+
+        let forward = direction(self.yaw, self.pitch);
+        let walk_forward = direction(self.yaw, 0.0);
+        let walk_right = normalize(&walk_forward.cross(&Axes::up()));
+
+        let movement = movement(input);
+        let velocity =
+            walk_right * movement.x + Axes::up() * movement.y + walk_forward * movement.z;
+
+        let position = camera.position()
+            + scaled_velocity(velocity, self.move_speed, self.boost_factor, input) * dt;
+        camera.look_to(position, forward);
+    }
+}
+
+// The inverse of direction.
+fn yaw_pitch(direction: &Vec3) -> (f32, f32) {
+    let pitch = clamp_scalar(-direction.y, -1.0, 1.0).asin().to_degrees();
+    let yaw = wrap_degrees(direction.x.atan2(direction.z).to_degrees());
+
+    (yaw, clamp_scalar(pitch, -89.99, 89.99))
+}
+
+// Matches the orientation of quaternion::from_euler applied to +Z.
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+
+    Vec3::new(
+        pitch.cos() * yaw.sin(),
+        -pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+fn look(yaw: &mut f32, pitch: &mut f32, look_speed: f32, input: &Input, dt: f32) {
+    // Dragging right turns right, which is a negative rotation around +Y.
+    let delta = turn_delta(input, LOOK);
+    *yaw = wrap_degrees(*yaw - delta.x * look_speed * dt);
+    *pitch = clamp_scalar(*pitch + delta.y * look_speed * dt, -89.99, 89.99);
+}
+
+fn scaled_velocity(velocity: Vec3, speed: f32, boost_factor: f32, input: &Input) -> Vec3 {
+    if velocity.norm() <= std::f32::EPSILON {
+        return velocity;
+    }
+
+    let speed = if input.action_down(BOOST) {
+        speed * boost_factor
+    } else {
+        speed
+    };
+
+    // Full speed for keys held on two axes, partial for sticks partially tilted.
+    velocity / velocity.norm().max(1.0) * speed
+}
+
+fn wrap_degrees(angle: f32) -> f32 {
+    angle.rem_euclid(360.0)
+}
@@ -0,0 +1,434 @@
+use crate::core::{asset::AssetManager, Msaa, Settings};
+use crate::rendering::sampler::Anisotropy;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 4] = [
+        QualityPreset::Low,
+        QualityPreset::Medium,
+        QualityPreset::High,
+        QualityPreset::Ultra,
+    ];
+
+    pub fn shadow_atlas_size(self) -> u32 {
+        match self {
+            QualityPreset::Low => 1024,
+            QualityPreset::Medium => 2048,
+            QualityPreset::High => 4096,
+            QualityPreset::Ultra => 8192,
+        }
+    }
+
+    // Samples of the offscreen scene targets. The window itself uses the msaa of the settings.
+    pub fn msaa(self) -> Msaa {
+        match self {
+            QualityPreset::Low => Msaa::None,
+            QualityPreset::Medium => Msaa::X2,
+            QualityPreset::High => Msaa::X4,
+            QualityPreset::Ultra => Msaa::X8,
+        }
+    }
+
+    pub fn anisotropy(self) -> Anisotropy {
+        match self {
+            QualityPreset::Low => Anisotropy::X4,
+            QualityPreset::Medium => Anisotropy::X8,
+            QualityPreset::High | QualityPreset::Ultra => Anisotropy::X16,
+        }
+    }
+
+    pub fn ssao(self) -> bool {
+        self != QualityPreset::Low
+    }
+
+    pub fn bloom(self) -> bool {
+        self != QualityPreset::Low
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "low" => Some(QualityPreset::Low),
+            "medium" => Some(QualityPreset::Medium),
+            "high" => Some(QualityPreset::High),
+            "ultra" => Some(QualityPreset::Ultra),
+            _ => None,
+        }
+    }
+}
+
+impl Default for QualityPreset {
+    fn default() -> Self {
+        QualityPreset::High
+    }
+}
+
+// The startup configuration of the engine, read from a RON file and overridden on the command
+// line, e.g. examples/pbs/config.ron. Missing fields take their default values. Applications turn
+// it into their Settings with Settings::from_config.
+//
+// Command line overrides:
+//   --config <path>            reads the configuration from path instead
+//   --resolution <W>x<H>       window size
+//   --fullscreen, --windowed
+//   --vsync <on|off>
+//   --msaa <samples>           of the window
+//   --fps <limit|off>          frame rate limit
+//   --assets <path>            asset root
+//   --pack <path>              mounts an asset pack over the asset root, repeatable
+//   --quality <low|medium|high|ultra>
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub window_size: [u32; 2],
+    pub fullscreen: bool,
+    pub vsync: bool,
+    // Samples of the window, 1 without multisampling.
+    pub msaa: u32,
+    pub frame_rate_limit: Option<f32>,
+    pub asset_path: PathBuf,
+    // Mounted over the asset path in order, later packs take precedence.
+    pub asset_packs: Vec<PathBuf>,
+    pub quality: QualityPreset,
+    // The directory of the file the configuration was read from. Files the engine writes at
+    // runtime, like the UI layout, are kept next to it. None keeps them in memory only.
+    #[serde(skip)]
+    pub config_dir: Option<PathBuf>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            window_size: [1280, 720],
+            fullscreen: false,
+            vsync: true,
+            msaa: 1,
+            frame_rate_limit: None,
+            asset_path: PathBuf::from("assets"),
+            asset_packs: vec![],
+            quality: QualityPreset::default(),
+            config_dir: None,
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::de::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read config {:?}: {}", path.as_ref(), e))?;
+
+        let mut config = Self::from_ron(&source)
+            .map_err(|e| format!("Failed to parse config {:?}: {}", path.as_ref(), e))?;
+        config.config_dir = config_dir(path.as_ref());
+
+        Ok(config)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let source = self.to_ron()?;
+
+        fs::write(path.as_ref(), source)
+            .map_err(|e| format!("Failed to save config {:?}: {}", path.as_ref(), e))
+    }
+
+    // The configuration of the command line of the process: the file of --config, or the
+    // default path if it exists, or the defaults, with the overrides of the command line applied.
+    pub fn from_args<P: AsRef<Path>>(default_path: P) -> Result<Self, String> {
+        let args = env::args().skip(1).collect::<Vec<_>>();
+
+        let config_path = args
+            .iter()
+            .position(|arg| arg == "--config")
+            .map(|i| {
+                args.get(i + 1)
+                    .map(PathBuf::from)
+                    .ok_or_else(|| "--config expects a path.".to_string())
+            })
+            .transpose()?;
+
+        let mut config = match config_path {
+            Some(path) => Self::load(path)?,
+            None if default_path.as_ref().exists() => Self::load(default_path)?,
+            None => Self {
+                config_dir: config_dir(default_path.as_ref()),
+                ..Self::default()
+            },
+        };
+
+        config.apply_args(args)?;
+
+        Ok(config)
+    }
+
+    // Applies the overrides of the arguments. Arguments it does not know are left to the
+    // application.
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<(), String> {
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("{} expects a value.", name))
+            };
+
+            match arg.as_str() {
+                "--config" => {
+                    value("--config")?;
+                }
+                "--resolution" => {
+                    let resolution = value("--resolution")?;
+                    self.window_size = parse_resolution(&resolution).ok_or_else(|| {
+                        format!(
+                            "Invalid resolution '{}', expected e.g. 1920x1080.",
+                            resolution
+                        )
+                    })?
+                }
+                "--fullscreen" => self.fullscreen = true,
+                "--windowed" => self.fullscreen = false,
+                "--vsync" => {
+                    self.vsync = match value("--vsync")?.as_str() {
+                        "on" | "true" | "1" => true,
+                        "off" | "false" | "0" => false,
+                        other => {
+                            return Err(format!("Invalid vsync '{}', expected on or off.", other))
+                        }
+                    }
+                }
+                "--msaa" => {
+                    let samples = value("--msaa")?;
+                    self.msaa = samples
+                        .parse()
+                        .map_err(|_| format!("Invalid MSAA sample count '{}'.", samples))?
+                }
+                "--fps" => {
+                    let limit = value("--fps")?;
+                    self.frame_rate_limit = match limit.as_str() {
+                        "off" | "0" => None,
+                        _ => Some(
+                            limit
+                                .parse()
+                                .map_err(|_| format!("Invalid frame rate limit '{}'.", limit))?,
+                        ),
+                    }
+                }
+                "--assets" => self.asset_path = PathBuf::from(value("--assets")?),
+                "--pack" => self.asset_packs.push(PathBuf::from(value("--pack")?)),
+                "--quality" => {
+                    let quality = value("--quality")?;
+                    self.quality = QualityPreset::parse(&quality).ok_or_else(|| {
+                        format!(
+                            "Invalid quality '{}', expected low, medium, high or ultra.",
+                            quality
+                        )
+                    })?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Where the window layout of the UI is saved, imgui.ini in the config directory.
+    pub fn ui_layout_path(&self) -> Option<PathBuf> {
+        self.config_dir.as_ref().map(|dir| dir.join("imgui.ini"))
+    }
+
+    // The window multisampling of the sample count, the closest lower one if unsupported.
+    pub fn window_msaa(&self) -> Msaa {
+        let msaa = [Msaa::X16, Msaa::X8, Msaa::X4, Msaa::X2]
+            .iter()
+            .copied()
+            .find(|&msaa| msaa as u32 <= self.msaa)
+            .unwrap_or(Msaa::None);
+
+        if msaa as u32 != self.msaa.max(1) {
+            log::warn!(
+                "{} MSAA samples are not supported. Using {}.",
+                self.msaa,
+                msaa as u32
+            );
+        }
+
+        msaa
+    }
+}
+
+// Mounts the asset packs of the settings. Packs that fail to open are skipped.
+pub(crate) fn mount_asset_packs(asset_manager: &mut AssetManager, settings: &Settings) {
+    for pack in &settings.asset_packs {
+        if let Err(e) = asset_manager.mount_pack(pack, &settings.asset_path) {
+            log::warn!("Failed to mount asset pack {:?}: {}", pack, e);
+        }
+    }
+}
+
+fn config_dir(config_path: &Path) -> Option<PathBuf> {
+    // The parent of a bare file name is empty.
+    match config_path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Some(PathBuf::from(".")),
+        dir => dir.map(Path::to_path_buf),
+    }
+}
+
+fn parse_resolution(resolution: &str) -> Option<[u32; 2]> {
+    let mut parts = resolution.splitn(2, 'x');
+    let width = parts.next()?.trim().parse().ok()?;
+    let height = parts.next()?.trim().parse().ok()?;
+
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some([width, height])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_resolution, EngineConfig, QualityPreset};
+    use std::path::PathBuf;
+
+    fn apply(args: &[&str]) -> Result<EngineConfig, String> {
+        let mut config = EngineConfig::default();
+        config.apply_args(args.iter().map(|arg| arg.to_string()))?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let config = apply(&[
+            "--resolution",
+            "1920x1080",
+            "--fullscreen",
+            "--vsync",
+            "off",
+            "--msaa",
+            "4",
+            "--fps",
+            "144",
+            "--assets",
+            "data",
+            "--pack",
+            "base.pack",
+            "--pack",
+            "patch.pack",
+            "--quality",
+            "Ultra",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config,
+            EngineConfig {
+                window_size: [1920, 1080],
+                fullscreen: true,
+                vsync: false,
+                msaa: 4,
+                frame_rate_limit: Some(144.0),
+                asset_path: PathBuf::from("data"),
+                asset_packs: vec![PathBuf::from("base.pack"), PathBuf::from("patch.pack")],
+                quality: QualityPreset::Ultra,
+                config_dir: None,
+            }
+        );
+    }
+
+    #[test]
+    fn later_arguments_win() {
+        let config = apply(&["--fullscreen", "--windowed", "--fps", "60", "--fps", "off"]).unwrap();
+
+        assert!(!config.fullscreen);
+        assert_eq!(config.frame_rate_limit, None);
+    }
+
+    #[test]
+    fn leaves_unknown_arguments_to_the_application() {
+        let config =
+            apply(&["--scene", "sponza", "--config", "other.ron", "--vsync", "1"]).unwrap();
+
+        assert_eq!(
+            config,
+            EngineConfig {
+                vsync: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(apply(&["--resolution", "1920"]).is_err());
+        assert!(apply(&["--resolution", "0x1080"]).is_err());
+        assert!(apply(&["--vsync", "maybe"]).is_err());
+        assert!(apply(&["--msaa", "many"]).is_err());
+        assert!(apply(&["--fps", "fast"]).is_err());
+        assert!(apply(&["--quality", "epic"]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert!(apply(&["--resolution"]).is_err());
+        assert!(apply(&["--fullscreen", "--pack"]).is_err());
+        assert!(apply(&["--config"]).is_err());
+    }
+
+    #[test]
+    fn parses_resolutions() {
+        assert_eq!(parse_resolution("1280x720"), Some([1280, 720]));
+        assert_eq!(parse_resolution(" 800 x 600 "), Some([800, 600]));
+        assert_eq!(parse_resolution("1280x"), None);
+        assert_eq!(parse_resolution("x720"), None);
+        assert_eq!(parse_resolution("1280*720"), None);
+    }
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let config = EngineConfig::from_ron("(msaa: 8, quality: Low)").unwrap();
+
+        assert_eq!(
+            config,
+            EngineConfig {
+                msaa: 8,
+                quality: QualityPreset::Low,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let config = EngineConfig {
+            window_size: [640, 480],
+            frame_rate_limit: Some(30.0),
+            asset_packs: vec![PathBuf::from("assets.pack")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            EngineConfig::from_ron(&config.to_ron().unwrap()).unwrap(),
+            config
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::core::{AsAny, AsAnyMut};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+pub mod components;
+#[cfg(feature = "physics")]
+pub mod physics;
+pub mod raycast;
+pub mod systems;
+
+// Generational id of an entity. Ids of despawned entities are never valid again, even after
+// their slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// Components of one type, densely packed for iteration. The sparse array maps an entity index to
+// the position of its component.
+pub struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    entities: Vec<Entity>,
+    dense: Vec<T>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            dense: Vec::new(),
+        }
+    }
+}
+
+impl<T> SparseSet<T> {
+    // Returns the component the entity had before.
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if let Some(index) = self.dense_index(entity) {
+            return Some(std::mem::replace(&mut self.dense[index], component));
+        }
+
+        if self.sparse.len() <= entity.index() {
+            self.sparse.resize(entity.index() + 1, None);
+        }
+
+        // A stale component of a despawned entity in the same slot is replaced.
+        if let Some(index) = self.sparse[entity.index()] {
+            self.entities[index] = entity;
+            self.dense[index] = component;
+            return None;
+        }
+
+        self.sparse[entity.index()] = Some(self.dense.len());
+        self.entities.push(entity);
+        self.dense.push(component);
+
+        None
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let index = self.dense_index(entity)?;
+
+        self.sparse[entity.index()] = None;
+        self.entities.swap_remove(index);
+        let component = self.dense.swap_remove(index);
+
+        if let Some(moved) = self.entities.get(index) {
+            self.sparse[moved.index()] = Some(index);
+        }
+
+        Some(component)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense_index(entity)
+            .map(move |index| &self.dense[index])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let index = self.dense_index(entity)?;
+        Some(&mut self.dense[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().cloned().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().cloned().zip(self.dense.iter_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        self.sparse
+            .get(entity.index())
+            .cloned()
+            .flatten()
+            .filter(|&index| self.entities[index] == entity)
+    }
+}
+
+// Type erased SparseSet, so the world can drop the components of despawned entities.
+trait ComponentStorage: AsAny + AsAnyMut {
+    fn remove_entity(&mut self, entity: Entity);
+}
+
+impl<T: 'static> AsAny for SparseSet<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<T: 'static> AsAnyMut for SparseSet<T> {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl<T: 'static> ComponentStorage for SparseSet<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+}
+
+// Entities and their components. Any 'static type can be a component.
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn ComponentStorage>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+
+                Entity {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+
+                Entity {
+                    index: self.generations.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    // Removes the entity together with all of its components.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+
+        self.alive[entity.index()] = false;
+        self.generations[entity.index()] += 1;
+        self.free.push(entity.index);
+
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.alive.get(entity.index()).cloned().unwrap_or(false)
+            && self.generations[entity.index()] == entity.generation
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.generations.len() - self.free.len()
+    }
+
+    // Returns the component the entity had before. Components of dead entities are dropped.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        if !self.is_alive(entity) {
+            log::warn!("Inserting a component into a despawned entity.");
+            return None;
+        }
+
+        self.storage_or_default::<T>().insert(entity, component)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_mut::<T>()?.remove(entity)
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.storage::<T>()
+            .map_or(false, |storage| storage.contains(entity))
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.get(entity)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_mut::<T>()?.get_mut(entity)
+    }
+
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>().into_iter().flat_map(SparseSet::iter)
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storage_mut::<T>()
+            .into_iter()
+            .flat_map(SparseSet::iter_mut)
+    }
+
+    // Entities that have both components.
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        let b = self.storage::<B>();
+
+        self.query::<A>().filter_map(move |(entity, a)| {
+            b.and_then(|b| b.get(entity))
+                .map(|b_component| (entity, a, b_component))
+        })
+    }
+
+    // Like query2 with mutable access to A. A and B must be different types.
+    pub fn for_each2_mut<A: 'static, B: 'static, F: FnMut(Entity, &mut A, &B)>(
+        &mut self,
+        mut f: F,
+    ) {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "for_each2_mut needs two different component types."
+        );
+
+        // The storage of A is taken out of the map for the duration of the iteration so that B
+        // can be borrowed at the same time.
+        let mut a = match self.storages.remove(&TypeId::of::<A>()) {
+            Some(a) => a,
+            None => return,
+        };
+
+        if let Some(b) = self.storage::<B>() {
+            let a_storage = a
+                .as_any_mut()
+                .downcast_mut::<SparseSet<A>>()
+                .expect("Component storage type mismatch.");
+
+            for (entity, a_component) in a_storage.iter_mut() {
+                if let Some(b_component) = b.get(entity) {
+                    f(entity, a_component, b_component);
+                }
+            }
+        }
+
+        self.storages.insert(TypeId::of::<A>(), a);
+    }
+
+    pub fn storage<T: 'static>(&self) -> Option<&SparseSet<T>> {
+        self.storages.get(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .as_any()
+                .downcast_ref::<SparseSet<T>>()
+                .expect("Component storage type mismatch.")
+        })
+    }
+
+    pub fn storage_mut<T: 'static>(&mut self) -> Option<&mut SparseSet<T>> {
+        self.storages.get_mut(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()
+                .expect("Component storage type mismatch.")
+        })
+    }
+
+    fn storage_or_default<T: 'static>(&mut self) -> &mut SparseSet<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::<T>::default()))
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
+            .expect("Component storage type mismatch.")
+    }
+}
@@ -0,0 +1,396 @@
+use crate::{
+    core::animation::clip::{AnimationClip, ClipPlayer},
+    core::asset::Handle,
+    core::ecs::Entity,
+    core::math::{quaternion, Mat4, Quat, Vec3},
+    geometry::bvh::MeshBvh,
+    imgui::{im_str, Gui, Ui},
+    rendering::{material::Material, mesh::Mesh},
+};
+use nalgebra_glm as glm;
+use std::{cell::RefCell, ops::RangeInclusive, rc::Rc};
+
+// Materials are shared between renderers and stay editable through the UI.
+pub type SharedMaterial = Rc<RefCell<dyn Material>>;
+
+#[derive(Debug, Clone)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    // Relative to the world. Written by systems::update_transforms.
+    world: Mat4,
+}
+
+// Shown by the editor panels instead of the id of the entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name(pub String);
+
+// Makes the transform of the entity relative to the parent entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+// Plays a node animation of an imported scene, e.g. one of SceneDescription::animations, on
+// the Transforms of the entities of its nodes. The joints of the clip index into targets, nodes
+// without an entity are not animated. Independent of skinning.
+pub struct NodeAnimator {
+    pub player: ClipPlayer,
+    pub targets: Vec<Option<Entity>>,
+    pub playing: bool,
+}
+
+pub struct MeshRenderer {
+    pub mesh: Handle<Mesh>,
+    pub material: SharedMaterial,
+    pub visible: bool,
+}
+
+// Makes the entity hit by the ray queries of raycast::RaycastScene. The triangles are usually
+// those of the mesh of its MeshRenderer, see MeshBvh::new, and can be shared between entities.
+#[derive(Clone)]
+pub struct MeshCollider {
+    pub bvh: Rc<MeshBvh>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point {
+        range: f32,
+    },
+    // Half angles in degrees.
+    Spot {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    // Faces +Z, the height is along +Y.
+    Rect {
+        width: f32,
+        height: f32,
+        range: f32,
+        two_sided: bool,
+    },
+    Disk {
+        radius: f32,
+        range: f32,
+        two_sided: bool,
+    },
+}
+
+// Lights take their position and direction (+Z) from the Transform of the entity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    // Linear color.
+    pub color: Vec3,
+    // Lux for directional lights, lumens for the others.
+    pub intensity: f32,
+    // Color temperature in Kelvin that tints the color.
+    pub temperature: Option<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            world: Mat4::identity(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    // Matrices with shear lose it.
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let scale = Vec3::new(
+            matrix.column(0).xyz().norm(),
+            matrix.column(1).xyz().norm(),
+            matrix.column(2).xyz().norm(),
+        );
+
+        let mut basis = glm::mat4_to_mat3(matrix);
+        for i in 0..3 {
+            if scale[i] > std::f32::EPSILON {
+                let mut axis = basis.column_mut(i);
+                axis /= scale[i];
+            }
+        }
+
+        Self {
+            translation: matrix.column(3).xyz(),
+            rotation: glm::quat_normalize(&glm::mat3_to_quat(&basis)),
+            scale,
+            world: Mat4::identity(),
+        }
+    }
+
+    pub fn local_matrix(&self) -> Mat4 {
+        glm::translation(&self.translation)
+            * quaternion::to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+
+    pub fn world_matrix(&self) -> &Mat4 {
+        &self.world
+    }
+
+    pub fn world_position(&self) -> Vec3 {
+        self.world.column(3).xyz()
+    }
+
+    // The +Y axis of the transform in world space.
+    pub fn world_up(&self) -> Vec3 {
+        let up = self.world.column(1).xyz();
+
+        if up.norm() > std::f32::EPSILON {
+            up.normalize()
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        }
+    }
+
+    // The +Z axis of the transform in world space.
+    pub fn world_forward(&self) -> Vec3 {
+        let forward = self.world.column(2).xyz();
+
+        if forward.norm() > std::f32::EPSILON {
+            forward.normalize()
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        }
+    }
+
+    pub(crate) fn set_world_matrix(&mut self, world: Mat4) {
+        self.world = world;
+    }
+}
+
+impl NodeAnimator {
+    // The entities are in the order of the nodes of the scene, e.g. from
+    // systems::spawn_scene_nodes.
+    pub fn new(clip: Rc<AnimationClip>, targets: Vec<Option<Entity>>) -> Self {
+        Self {
+            player: ClipPlayer::new(clip),
+            targets,
+            playing: true,
+        }
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.player = self.player.looping(looping);
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.player = self.player.speed(speed);
+        self
+    }
+}
+
+impl MeshRenderer {
+    pub fn new(mesh: Handle<Mesh>, material: SharedMaterial) -> Self {
+        Self {
+            mesh,
+            material,
+            visible: true,
+        }
+    }
+}
+
+impl MeshCollider {
+    pub fn new(bvh: Rc<MeshBvh>) -> Self {
+        Self { bvh, enabled: true }
+    }
+}
+
+impl Light {
+    pub fn with_temperature(mut self, kelvin: f32) -> Self {
+        self.temperature = Some(kelvin);
+        self
+    }
+
+    pub fn directional(color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            color,
+            intensity,
+            temperature: None,
+        }
+    }
+
+    pub fn point(color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point { range },
+            color,
+            intensity,
+            temperature: None,
+        }
+    }
+
+    pub fn spot(
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+            temperature: None,
+        }
+    }
+
+    pub fn rect(color: Vec3, intensity: f32, width: f32, height: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Rect {
+                width,
+                height,
+                range,
+                two_sided: false,
+            },
+            color,
+            intensity,
+            temperature: None,
+        }
+    }
+
+    pub fn disk(color: Vec3, intensity: f32, radius: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Disk {
+                radius,
+                range,
+                two_sided: false,
+            },
+            color,
+            intensity,
+            temperature: None,
+        }
+    }
+}
+
+impl Gui for Transform {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::Drag::new(im_str!("Translation"))
+            .speed(0.01)
+            .display_format(im_str!("%.2f"))
+            .build_array(&ui, self.translation.as_mut_slice());
+
+        let (yaw, pitch, roll) = quaternion::to_euler(&self.rotation);
+        let mut angles = [yaw, pitch, roll];
+        if imgui::Drag::new(im_str!("Yaw Pitch Roll"))
+            .speed(0.5)
+            .display_format(im_str!("%.1f"))
+            .build_array(&ui, &mut angles)
+        {
+            self.rotation = quaternion::from_euler(angles[0], angles[1], angles[2]);
+        }
+
+        imgui::Drag::new(im_str!("Scale"))
+            .speed(0.01)
+            .display_format(im_str!("%.2f"))
+            .build_array(&ui, self.scale.as_mut_slice());
+    }
+}
+
+impl Gui for MeshRenderer {
+    fn gui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Visible"), &mut self.visible);
+        self.material.borrow_mut().gui(ui);
+    }
+}
+
+impl Gui for NodeAnimator {
+    fn gui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Playing"), &mut self.playing);
+    }
+}
+
+impl Gui for Light {
+    fn gui(&mut self, ui: &Ui) {
+        let mut color: [f32; 3] = self.color.into();
+        if imgui::ColorEdit::new(im_str!("Color"), &mut color).build(&ui) {
+            self.color = color.into();
+        }
+
+        imgui::Drag::new(im_str!("Intensity"))
+            .range(RangeInclusive::new(0.0, 1_000_000.0))
+            .display_format(im_str!("%.1f"))
+            .build(&ui, &mut self.intensity);
+
+        let mut use_temperature = self.temperature.is_some();
+        if ui.checkbox(im_str!("Temperature"), &mut use_temperature) {
+            self.temperature = if use_temperature { Some(6500.0) } else { None };
+        }
+
+        if let Some(temperature) = self.temperature.as_mut() {
+            imgui::Slider::new(im_str!("Kelvin"))
+                .range(RangeInclusive::new(1000.0, 40000.0))
+                .display_format(im_str!("%.0f"))
+                .build(&ui, temperature);
+        }
+
+        let distance = |label, value: &mut f32| {
+            imgui::Drag::new(label)
+                .range(RangeInclusive::new(0.0, 10_000.0))
+                .speed(0.05)
+                .display_format(im_str!("%.2f"))
+                .build(&ui, value);
+        };
+
+        match &mut self.kind {
+            LightKind::Directional => {}
+            LightKind::Point { range } => distance(im_str!("Range"), range),
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                distance(im_str!("Range"), range);
+                imgui::Slider::new(im_str!("Inner Angle"))
+                    .range(RangeInclusive::new(0.0, *outer_angle))
+                    .display_format(im_str!("%.1f"))
+                    .build(&ui, inner_angle);
+                imgui::Slider::new(im_str!("Outer Angle"))
+                    .range(RangeInclusive::new(*inner_angle, 90.0))
+                    .display_format(im_str!("%.1f"))
+                    .build(&ui, outer_angle);
+            }
+            LightKind::Rect {
+                width,
+                height,
+                range,
+                two_sided,
+            } => {
+                distance(im_str!("Width"), width);
+                distance(im_str!("Height"), height);
+                distance(im_str!("Range"), range);
+                ui.checkbox(im_str!("Two Sided"), two_sided);
+            }
+            LightKind::Disk {
+                radius,
+                range,
+                two_sided,
+            } => {
+                distance(im_str!("Radius"), radius);
+                distance(im_str!("Range"), range);
+                ui.checkbox(im_str!("Two Sided"), two_sided);
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use crate::{
+    core::ecs::{
+        components::{Parent, Transform},
+        Entity, World,
+    },
+    core::math::{Mat4, Quat, Vec3},
+    geometry::MeshData,
+};
+use nalgebra_glm as glm;
+use rapier3d::{
+    dynamics::{
+        BodyStatus, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle,
+        RigidBodySet,
+    },
+    geometry::{BroadPhase, ColliderBuilder, ColliderSet, NarrowPhase},
+    na::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3},
+    pipeline::PhysicsPipeline,
+};
+use std::collections::HashMap;
+
+// Accumulated time past this many steps is dropped, so a long frame doesn't stall the next ones.
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyKind {
+    // Moved by the simulation, writes its pose into the Transform.
+    Dynamic,
+    // Never moves.
+    Static,
+    // Follows its Transform and pushes dynamic bodies out of the way.
+    Kinematic,
+}
+
+// Simulates the entity in the PhysicsWorld. Only read when the body is created, remove and
+// insert it again to change it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RigidBody {
+    pub kind: BodyKind,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+}
+
+// Shapes are in the space of the entity, scaled by the scale of its world matrix when the body is
+// created.
+#[derive(Debug, Clone)]
+pub enum ColliderShape {
+    Cuboid {
+        half_extents: Vec3,
+    },
+    Ball {
+        radius: f32,
+    },
+    // Along Y.
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    ConvexHull {
+        points: Vec<Vec3>,
+    },
+    TriMesh {
+        vertices: Vec<Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+}
+
+// The shape of the RigidBody of the entity.
+#[derive(Debug, Clone)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub density: f32,
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+impl RigidBody {
+    pub fn dynamic() -> Self {
+        Self::new(BodyKind::Dynamic)
+    }
+
+    pub fn fixed() -> Self {
+        Self::new(BodyKind::Static)
+    }
+
+    pub fn kinematic() -> Self {
+        Self::new(BodyKind::Kinematic)
+    }
+
+    pub fn damping(mut self, linear: f32, angular: f32) -> Self {
+        self.linear_damping = linear;
+        self.angular_damping = angular;
+        self
+    }
+
+    fn new(kind: BodyKind) -> Self {
+        Self {
+            kind,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+        }
+    }
+}
+
+impl ColliderShape {
+    // The triangles of an imported mesh, e.g. from AssetManager::read_mesh_data. Suits static
+    // and kinematic bodies, triangle meshes have no volume to give dynamic bodies a mass.
+    pub fn trimesh(mesh_data: &MeshData) -> Self {
+        ColliderShape::TriMesh {
+            vertices: mesh_data.vertices.iter().map(|v| v.position).collect(),
+            indices: mesh_data
+                .indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        }
+    }
+
+    // The smallest convex volume around the vertices of an imported mesh. Suits dynamic bodies.
+    pub fn convex_hull(mesh_data: &MeshData) -> Self {
+        ColliderShape::ConvexHull {
+            points: mesh_data.vertices.iter().map(|v| v.position).collect(),
+        }
+    }
+}
+
+impl Collider {
+    pub fn new(shape: ColliderShape) -> Self {
+        Self {
+            shape,
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+
+    pub fn density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    // None for a convex hull of degenerate points.
+    fn build(&self, scale: &Vec3) -> Option<rapier3d::geometry::Collider> {
+        let point = |p: &Vec3| {
+            let p = p.component_mul(scale);
+            Point3::new(p.x, p.y, p.z)
+        };
+
+        let builder = match &self.shape {
+            ColliderShape::Cuboid { half_extents } => {
+                let half_extents = half_extents.component_mul(scale);
+                ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+            }
+            ColliderShape::Ball { radius } => ColliderBuilder::ball(radius * scale.max()),
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => ColliderBuilder::capsule_y(half_height * scale.y, radius * scale.x.max(scale.z)),
+            ColliderShape::ConvexHull { points } => {
+                ColliderBuilder::convex_hull(&points.iter().map(point).collect::<Vec<_>>())?
+            }
+            ColliderShape::TriMesh { vertices, indices } => {
+                ColliderBuilder::trimesh(vertices.iter().map(point).collect(), indices.clone())
+            }
+        };
+
+        Some(
+            builder
+                .density(self.density)
+                .friction(self.friction)
+                .restitution(self.restitution)
+                .build(),
+        )
+    }
+}
+
+// Rapier simulation of the entities with a RigidBody and a Transform, at a fixed time step.
+// Bodies are created from the world matrix of the entity, scale is baked into its Collider.
+pub struct PhysicsWorld {
+    gravity: Vec3,
+    time_step: f32,
+    accumulator: f32,
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    joints: JointSet,
+    handles: HashMap<Entity, RigidBodyHandle>,
+}
+
+impl PhysicsWorld {
+    pub fn new(time_step: f32) -> Self {
+        Self {
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            time_step,
+            accumulator: 0.0,
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters::default(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            joints: JointSet::new(),
+            handles: HashMap::new(),
+        }
+    }
+
+    pub fn gravity(&self) -> &Vec3 {
+        &self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) {
+        self.gravity = gravity
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    // The rapier body of the entity, e.g. to apply forces and impulses or read its velocity.
+    pub fn body(&self, entity: Entity) -> Option<&rapier3d::dynamics::RigidBody> {
+        self.handles
+            .get(&entity)
+            .and_then(|&handle| self.bodies.get(handle))
+    }
+
+    pub fn body_mut(&mut self, entity: Entity) -> Option<&mut rapier3d::dynamics::RigidBody> {
+        let handle = *self.handles.get(&entity)?;
+        self.bodies.get_mut(handle)
+    }
+
+    // Creates and removes bodies to match the world, runs as many fixed steps as fit in the
+    // accumulated time and writes the poses of the dynamic bodies into their Transforms. Runs
+    // before systems::update_transforms, new and kinematic bodies take the world matrices of the
+    // previous update.
+    pub fn step(&mut self, world: &mut World, delta_time: f32) {
+        self.sync_bodies(world);
+
+        self.accumulator =
+            (self.accumulator + delta_time).min(self.time_step * MAX_STEPS_PER_FRAME as f32);
+        self.integration_parameters.dt = self.time_step;
+
+        let gravity = Vector3::new(self.gravity.x, self.gravity.y, self.gravity.z);
+
+        while self.accumulator >= self.time_step {
+            self.pipeline.step(
+                &gravity,
+                &self.integration_parameters,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.joints,
+                None,
+                None,
+                &(),
+            );
+
+            self.accumulator -= self.time_step;
+        }
+
+        self.write_transforms(world);
+    }
+
+    fn sync_bodies(&mut self, world: &World) {
+        let removed = self
+            .handles
+            .iter()
+            .filter(|(&entity, _)| !world.is_alive(entity) || !world.has::<RigidBody>(entity))
+            .map(|(&entity, &handle)| (entity, handle))
+            .collect::<Vec<_>>();
+
+        for (entity, handle) in removed {
+            self.bodies
+                .remove(handle, &mut self.colliders, &mut self.joints);
+            self.handles.remove(&entity);
+        }
+
+        for (entity, body, transform) in world.query2::<RigidBody, Transform>() {
+            let pose = Transform::from_matrix(transform.world_matrix());
+            let isometry = to_isometry(&pose.translation, &pose.rotation);
+
+            if let Some(&handle) = self.handles.get(&entity) {
+                if body.kind == BodyKind::Kinematic {
+                    self.bodies[handle].set_next_kinematic_position(isometry);
+                }
+
+                continue;
+            }
+
+            let status = match body.kind {
+                BodyKind::Dynamic => BodyStatus::Dynamic,
+                BodyKind::Static => BodyStatus::Static,
+                BodyKind::Kinematic => BodyStatus::Kinematic,
+            };
+
+            let handle = self.bodies.insert(
+                RigidBodyBuilder::new(status)
+                    .position(isometry)
+                    .linear_damping(body.linear_damping)
+                    .angular_damping(body.angular_damping)
+                    .build(),
+            );
+
+            match world.get::<Collider>(entity).map(|c| c.build(&pose.scale)) {
+                Some(Some(collider)) => {
+                    self.colliders.insert(collider, handle, &mut self.bodies);
+                }
+                Some(None) => log::warn!("Degenerate convex hull collider of {:?}", entity),
+                None => {}
+            }
+
+            self.handles.insert(entity, handle);
+        }
+    }
+
+    fn write_transforms(&self, world: &mut World) {
+        for (&entity, &handle) in &self.handles {
+            let body = &self.bodies[handle];
+
+            if !body.is_dynamic() || body.is_sleeping() {
+                continue;
+            }
+
+            let (translation, rotation) = from_isometry(body.position());
+
+            // Bodies are simulated in world space, children are posed relative to their parent.
+            let parent_world = world
+                .get::<Parent>(entity)
+                .and_then(|parent| world.get::<Transform>(parent.0))
+                .map(|parent| *parent.world_matrix());
+
+            let (translation, rotation) = match parent_world {
+                Some(parent_world) => {
+                    let world_pose: Mat4 =
+                        glm::translation(&translation) * glm::quat_to_mat4(&rotation);
+                    let local = Transform::from_matrix(&(glm::inverse(&parent_world) * world_pose));
+                    (local.translation, local.rotation)
+                }
+                None => (translation, rotation),
+            };
+
+            if let Some(transform) = world.get_mut::<Transform>(entity) {
+                transform.translation = translation;
+                transform.rotation = rotation;
+            }
+        }
+    }
+}
+
+fn to_isometry(translation: &Vec3, rotation: &Quat) -> Isometry3<f32> {
+    Isometry3::from_parts(
+        Translation3::new(translation.x, translation.y, translation.z),
+        UnitQuaternion::from_quaternion(Quaternion::new(
+            rotation.w, rotation.i, rotation.j, rotation.k,
+        )),
+    )
+}
+
+fn from_isometry(isometry: &Isometry3<f32>) -> (Vec3, Quat) {
+    let translation = &isometry.translation.vector;
+    let rotation = &isometry.rotation;
+
+    (
+        Vec3::new(translation.x, translation.y, translation.z),
+        Quat::new(rotation.w, rotation.i, rotation.j, rotation.k),
+    )
+}
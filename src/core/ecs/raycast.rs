@@ -0,0 +1,103 @@
+use crate::{
+    core::ecs::{
+        components::{MeshCollider, Transform},
+        Entity, World,
+    },
+    core::math::{Mat4, Vec2, Vec3},
+    geometry::{
+        bounds::Aabb,
+        bvh::{Bvh, MeshBvh},
+        ray::Ray,
+    },
+};
+use nalgebra_glm as glm;
+use std::rc::Rc;
+
+// Where a ray hit a collider, in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub entity: Entity,
+    pub distance: f32,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+struct ColliderInstance {
+    entity: Entity,
+    bvh: Rc<MeshBvh>,
+    world: Mat4,
+    inverse: Mat4,
+}
+
+// Ray queries against the enabled MeshColliders of a world, on the CPU, for picking and simple
+// gameplay interaction without a physics engine. A snapshot of the colliders and their
+// transforms, build it again after they change, e.g. once per frame after
+// systems::update_transforms.
+pub struct RaycastScene {
+    instances: Vec<ColliderInstance>,
+    bvh: Bvh,
+}
+
+impl RaycastScene {
+    pub fn build(world: &World) -> Self {
+        let instances = world
+            .query2::<MeshCollider, Transform>()
+            .filter(|(_, collider, _)| collider.enabled)
+            .map(|(entity, collider, transform)| ColliderInstance {
+                entity,
+                bvh: Rc::clone(&collider.bvh),
+                world: *transform.world_matrix(),
+                inverse: glm::inverse(transform.world_matrix()),
+            })
+            .collect::<Vec<_>>();
+
+        let bounds = instances
+            .iter()
+            .map(|instance| instance.bvh.bounds().transform(&instance.world))
+            .collect::<Vec<_>>();
+
+        Self {
+            instances,
+            bvh: Bvh::new(&bounds),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        self.raycast_within(ray, std::f32::INFINITY)
+    }
+
+    // The closest hit within max_distance along the ray. Triangles are hit from either side.
+    pub fn raycast_within(&self, ray: &Ray, max_distance: f32) -> Option<Hit> {
+        let ray = Ray::new(ray.origin, ray.direction);
+        let mut closest = None;
+
+        let intersect = |index: usize, max_distance: f32| {
+            let instance = &self.instances[index];
+
+            // Distances along the transformed ray stay world space distances.
+            let local_ray = ray.transform(&instance.inverse);
+            let hit = instance.bvh.raycast(&local_ray, max_distance)?;
+
+            let normal = glm::mat4_to_mat3(&instance.inverse).transpose() * hit.normal;
+
+            closest = Some(Hit {
+                entity: instance.entity,
+                distance: hit.distance,
+                position: ray.at(hit.distance),
+                normal: normal.normalize(),
+                uv: hit.uv,
+            });
+
+            Some(hit.distance)
+        };
+
+        self.bvh.closest_hit(&ray, max_distance, intersect)?;
+
+        closest
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    core::animation::clip::JointTransform,
+    core::asset::scene::SceneDescription,
+    core::camera::Camera,
+    core::ecs::{
+        components::{Light, MeshRenderer, Name, NodeAnimator, Parent, Transform},
+        Entity, World,
+    },
+    core::math::{Mat4, Vec3},
+    rendering::draw_list::{DrawItem, DrawList},
+};
+use std::collections::HashMap;
+
+// A light with the position and direction of its entity.
+#[derive(Debug, Clone, Copy)]
+pub struct LightInstance {
+    pub entity: Entity,
+    pub light: Light,
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub up: Vec3,
+}
+
+// Spawns an entity with a Transform for every node of the scene, with a Parent for the nodes
+// that have one and a Name for the named ones. Returns the entities in the order of the nodes, e.g. as the targets of a
+// NodeAnimator. Meshes are left to the caller, see SceneDescription::mesh_instances.
+pub fn spawn_scene_nodes(world: &mut World, scene: &SceneDescription) -> Vec<Entity> {
+    let entities = scene
+        .nodes
+        .iter()
+        .map(|node| {
+            let entity = world.spawn();
+            world.insert(entity, Transform::from_matrix(&node.local_transform));
+
+            if !node.name.is_empty() {
+                world.insert(entity, Name(node.name.clone()));
+            }
+
+            entity
+        })
+        .collect::<Vec<_>>();
+
+    for (node, &entity) in scene.nodes.iter().zip(&entities) {
+        if let Some(parent) = node.parent {
+            world.insert(entity, Parent(entities[parent]));
+        }
+    }
+
+    entities
+}
+
+// Advances the playing NodeAnimators and writes their tracks into the Transforms of the target
+// entities. Runs before update_transforms.
+pub fn animate_nodes(world: &mut World, delta_time: f32) {
+    let animations = world
+        .query_mut::<NodeAnimator>()
+        .map(|(_, animator)| {
+            if animator.playing {
+                animator.player.advance(delta_time)
+            }
+
+            (
+                animator.player.clip().clone(),
+                animator.player.time(),
+                animator.targets.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for (clip, time, targets) in animations {
+        for tracks in clip.tracks() {
+            let entity = match targets.get(tracks.joint) {
+                Some(Some(entity)) => *entity,
+                _ => continue,
+            };
+
+            if let Some(transform) = world.get_mut::<Transform>(entity) {
+                let mut joint = JointTransform {
+                    translation: transform.translation,
+                    rotation: transform.rotation,
+                    scale: transform.scale,
+                };
+
+                tracks.sample(time, &mut joint);
+
+                transform.translation = joint.translation;
+                transform.rotation = joint.rotation;
+                transform.scale = joint.scale;
+            }
+        }
+    }
+}
+
+// Computes the world matrix of every Transform. Entities whose parent has no Transform, or that
+// are part of a parent cycle, are treated as roots.
+pub fn update_transforms(world: &mut World) {
+    let locals = world
+        .query::<Transform>()
+        .map(|(entity, transform)| {
+            let parent = world.get::<Parent>(entity).map(|parent| parent.0);
+            (entity, (transform.local_matrix(), parent))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut worlds: HashMap<Entity, Mat4> = HashMap::with_capacity(locals.len());
+
+    for &entity in locals.keys() {
+        if worlds.contains_key(&entity) {
+            continue;
+        }
+
+        let mut chain = vec![entity];
+
+        // Walk up until an entity with a known world matrix or a root.
+        let mut parent_world = Mat4::identity();
+        while let Some(&current) = chain.last() {
+            let parent = match locals[&current].1 {
+                Some(parent) if locals.contains_key(&parent) && !chain.contains(&parent) => parent,
+                _ => break,
+            };
+
+            if let Some(world) = worlds.get(&parent) {
+                parent_world = *world;
+                break;
+            }
+
+            chain.push(parent);
+        }
+
+        for &current in chain.iter().rev() {
+            parent_world *= locals[&current].0;
+            worlds.insert(current, parent_world);
+        }
+    }
+
+    for (entity, transform) in world.query_mut::<Transform>() {
+        transform.set_world_matrix(worlds[&entity]);
+    }
+}
+
+// Adds a draw for every visible MeshRenderer that has a Transform.
+pub fn collect_draws(world: &World, draw_list: &mut DrawList) {
+    for (entity, renderer, transform) in world.query2::<MeshRenderer, Transform>() {
+        if !renderer.visible {
+            continue;
+        }
+
+        draw_list.push(DrawItem {
+            mesh: renderer.mesh.clone(),
+            material: renderer.material.clone(),
+            model: *transform.world_matrix(),
+            entity: Some(entity),
+        });
+    }
+}
+
+// Lights without a Transform sit at the origin and point along +Z.
+pub fn collect_lights(world: &World) -> Vec<LightInstance> {
+    world
+        .query::<Light>()
+        .map(|(entity, light)| {
+            let transform = world.get::<Transform>(entity);
+
+            LightInstance {
+                entity,
+                light: *light,
+                position: transform.map_or(Vec3::new(0.0, 0.0, 0.0), Transform::world_position),
+                direction: transform.map_or(Vec3::new(0.0, 0.0, 1.0), Transform::world_forward),
+                up: transform.map_or(Vec3::new(0.0, 1.0, 0.0), Transform::world_up),
+            }
+        })
+        .collect()
+}
+
+// The first entity with a Camera.
+pub fn active_camera(world: &World) -> Option<(Entity, &Camera)> {
+    world.query::<Camera>().next()
+}
@@ -0,0 +1,221 @@
+use crate::core::{headless::HeadlessApplication, scene::Scene, Context};
+use image::{Rgba, RgbaImage};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// Set to overwrite the reference images with the rendered ones, e.g. after an intended change
+// of the shading.
+pub const UPDATE_GOLDEN_IMAGES_VARIABLE: &str = "UPDATE_GOLDEN_IMAGES";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoldenImageResult {
+    pub pixel_count: usize,
+    // Pixels whose color difference exceeds the pixel tolerance.
+    pub differing_pixels: usize,
+    pub mean_difference: f32,
+    pub max_difference: f32,
+    pub passed: bool,
+    // The rendered image was stored as the reference instead of being compared.
+    pub updated_reference: bool,
+}
+
+// Renders a scene headless and compares the last frame against a stored reference image, so
+// changes to the shaders can be validated automatically. Pixels are compared by their
+// perceptual color difference, CIE76 delta E in CIELAB, which tolerates the last bit noise
+// drivers produce. Missing references are written on the first run. Failing runs write the
+// rendered image and a map of the differing pixels next to the reference.
+pub struct GoldenImageTest {
+    name: String,
+    reference_directory: PathBuf,
+    frames: u32,
+    // Delta E up to which pixels count as equal. 2.3 is about the smallest difference people
+    // notice.
+    pixel_tolerance: f32,
+    // Share of the pixels allowed to differ.
+    max_differing_pixels: f32,
+}
+
+impl GoldenImageTest {
+    pub fn new<P: AsRef<Path>>(name: &str, reference_directory: P) -> Self {
+        Self {
+            name: name.to_string(),
+            reference_directory: reference_directory.as_ref().to_path_buf(),
+            frames: 8,
+            pixel_tolerance: 2.3,
+            max_differing_pixels: 0.001,
+        }
+    }
+
+    // Frames rendered before the last one is compared, e.g. for temporal effects to settle.
+    pub fn frames(mut self, frames: u32) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    pub fn pixel_tolerance(mut self, pixel_tolerance: f32) -> Self {
+        self.pixel_tolerance = pixel_tolerance.max(0.0);
+        self
+    }
+
+    pub fn max_differing_pixels(mut self, max_differing_pixels: f32) -> Self {
+        self.max_differing_pixels = max_differing_pixels.max(0.0).min(1.0);
+        self
+    }
+
+    pub fn reference_path(&self) -> PathBuf {
+        self.reference_directory.join(format!("{}.png", self.name))
+    }
+
+    // Renders the scene at the window size of the settings of the application and compares it.
+    pub fn run<Cons, S>(
+        &self,
+        application: &mut HeadlessApplication,
+        scene_constructor: Cons,
+    ) -> Result<GoldenImageResult, String>
+    where
+        S: Scene + 'static,
+        Cons: FnOnce(Context) -> S,
+    {
+        let rendered = application.render(scene_constructor, self.frames)?;
+        let reference_path = self.reference_path();
+
+        if !reference_path.exists() || env::var_os(UPDATE_GOLDEN_IMAGES_VARIABLE).is_some() {
+            if !reference_path.exists() {
+                log::warn!(
+                    "No reference image for golden image test '{}'. Storing the rendered image as {}.",
+                    self.name,
+                    reference_path.display()
+                );
+            }
+
+            fs::create_dir_all(&self.reference_directory).map_err(|e| e.to_string())?;
+            rendered.save(&reference_path).map_err(|e| e.to_string())?;
+
+            return Ok(GoldenImageResult {
+                pixel_count: (rendered.width() * rendered.height()) as usize,
+                differing_pixels: 0,
+                mean_difference: 0.0,
+                max_difference: 0.0,
+                passed: true,
+                updated_reference: true,
+            });
+        }
+
+        let reference = image::open(&reference_path)
+            .map_err(|e| format!("Failed to load {}: {}", reference_path.display(), e))?
+            .to_rgba();
+
+        if reference.dimensions() != rendered.dimensions() {
+            return Err(format!(
+                "Golden image test '{}' rendered {:?} pixels, the reference has {:?}.",
+                self.name,
+                rendered.dimensions(),
+                reference.dimensions()
+            ));
+        }
+
+        let (result, difference_map) = self.compare(&rendered, &reference);
+
+        if !result.passed {
+            let actual_path = self
+                .reference_directory
+                .join(format!("{}.actual.png", self.name));
+            let difference_path = self
+                .reference_directory
+                .join(format!("{}.difference.png", self.name));
+
+            rendered.save(&actual_path).map_err(|e| e.to_string())?;
+            difference_map
+                .save(&difference_path)
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(result)
+    }
+
+    // The result and a map of the differences, the reference in grey with the differing pixels
+    // in red.
+    fn compare(
+        &self,
+        rendered: &RgbaImage,
+        reference: &RgbaImage,
+    ) -> (GoldenImageResult, RgbaImage) {
+        let mut difference_map = RgbaImage::new(reference.width(), reference.height());
+        let mut differing_pixels = 0;
+        let mut difference_sum = 0.0;
+        let mut max_difference: f32 = 0.0;
+
+        for ((rendered, reference), output) in rendered
+            .pixels()
+            .zip(reference.pixels())
+            .zip(difference_map.pixels_mut())
+        {
+            let difference = delta_e(rendered, reference);
+
+            difference_sum += difference as f64;
+            max_difference = max_difference.max(difference);
+
+            *output = if difference > self.pixel_tolerance {
+                differing_pixels += 1;
+                Rgba([255, 0, 0, 255])
+            } else {
+                let grey = (lab(reference)[0] * 2.55 * 0.5) as u8;
+                Rgba([grey, grey, grey, 255])
+            };
+        }
+
+        let pixel_count = (reference.width() * reference.height()) as usize;
+
+        let result = GoldenImageResult {
+            pixel_count,
+            differing_pixels,
+            mean_difference: (difference_sum / pixel_count.max(1) as f64) as f32,
+            max_difference,
+            passed: differing_pixels as f32 <= self.max_differing_pixels * pixel_count as f32,
+            updated_reference: false,
+        };
+
+        (result, difference_map)
+    }
+}
+
+fn delta_e(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let a = lab(a);
+    let b = lab(b);
+
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+// CIELAB of an sRGB color, for the D65 white point. Alpha is ignored.
+fn lab(color: &Rgba<u8>) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    let r = linear(color[0]);
+    let g = linear(color[1]);
+    let b = linear(color[2]);
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.950_47;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.088_83;
+
+    let f = |t: f32| {
+        if t > 0.008_856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
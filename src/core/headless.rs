@@ -0,0 +1,273 @@
+use crate::core::{
+    application::Application,
+    asset::AssetManager,
+    config, logging,
+    main_loop::MainLoop,
+    scene::{Scene, SceneManager},
+    timer::Timer,
+    Context, Msaa, Settings,
+};
+use crate::platform::window;
+use crate::rendering::{
+    frame_stats::FrameStats,
+    framebuffer::{
+        AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo, TemporaryFramebufferPool,
+    },
+    state::StateManager,
+    texture::SizedTextureFormat,
+};
+use glutin::{
+    dpi::PhysicalSize,
+    event_loop::EventLoop,
+    window::{Window, WindowBuilder},
+    ContextWrapper, PossiblyCurrent,
+};
+use image::RgbaImage;
+use std::{ffi::c_void, thread, time::Duration};
+
+const ASSET_UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+// Every frame advances the time of the scene by the same step, however long it took to render.
+const FIXED_TIME_STEP: f32 = 1.0 / 60.0;
+
+// A GL context without a window where the platform allows it, with a window nobody sees
+// otherwise.
+enum HeadlessContext {
+    Windowless(glutin::Context<PossiblyCurrent>),
+    HiddenWindow(ContextWrapper<PossiblyCurrent, Window>),
+}
+
+impl HeadlessContext {
+    fn create(settings: &Settings, event_loop: &EventLoop<()>) -> Result<Self, String> {
+        let error = match Self::create_windowless(settings, event_loop) {
+            Ok(context) => return Ok(HeadlessContext::Windowless(context)),
+            Err(error) => error,
+        };
+
+        log::warn!(
+            "Failed to create a windowless context ({}). Rendering into a hidden window.",
+            error
+        );
+
+        let window_builder = WindowBuilder::new()
+            .with_title(&settings.name)
+            .with_inner_size(PhysicalSize::new(
+                settings.window_size.x,
+                settings.window_size.y,
+            ))
+            .with_visible(false);
+
+        let context = window::context_builder(settings)
+            .build_windowed(window_builder, event_loop)
+            .map_err(|e| e.to_string())?;
+
+        let context = unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())?;
+
+        Ok(HeadlessContext::HiddenWindow(context))
+    }
+
+    // EGL without any surface first, then whatever headless context the platform offers, e.g. a
+    // pbuffer.
+    #[cfg(target_os = "linux")]
+    fn create_windowless(
+        settings: &Settings,
+        event_loop: &EventLoop<()>,
+    ) -> Result<glutin::Context<PossiblyCurrent>, String> {
+        use glutin::platform::unix::HeadlessContextExt;
+
+        let context = window::context_builder(settings)
+            .build_surfaceless(event_loop)
+            .or_else(|_| {
+                window::context_builder(settings).build_headless(
+                    event_loop,
+                    PhysicalSize::new(settings.window_size.x, settings.window_size.y),
+                )
+            })
+            .map_err(|e| e.to_string())?;
+
+        unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_windowless(
+        settings: &Settings,
+        event_loop: &EventLoop<()>,
+    ) -> Result<glutin::Context<PossiblyCurrent>, String> {
+        let context = window::context_builder(settings)
+            .build_headless(
+                event_loop,
+                PhysicalSize::new(settings.window_size.x, settings.window_size.y),
+            )
+            .map_err(|e| e.to_string())?;
+
+        unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const c_void {
+        match self {
+            HeadlessContext::Windowless(context) => context.get_proc_address(name),
+            HeadlessContext::HiddenWindow(context) => context.get_proc_address(name),
+        }
+    }
+
+    fn window(&self) -> Option<&Window> {
+        match self {
+            HeadlessContext::Windowless(_) => None,
+            HeadlessContext::HiddenWindow(context) => Some(context.window()),
+        }
+    }
+}
+
+// Runs scenes without a visible window, e.g. for image regression tests or to generate
+// thumbnails on CI machines. An offscreen framebuffer of the window size of the settings stands
+// in for the framebuffer of the window, and is read back after the last frame. The multisampling
+// of the settings is ignored, it is always single sampled.
+pub struct HeadlessApplication {
+    settings: Settings,
+    asset_manager: AssetManager,
+    timer: Timer,
+    framebuffer_cache: TemporaryFramebufferPool,
+    target: Framebuffer,
+    // Dropped after everything above, their GL objects live in it.
+    gl_context: HeadlessContext,
+    _event_loop: EventLoop<()>,
+}
+
+impl HeadlessApplication {
+    pub fn new(settings: Settings) -> Result<Self, String> {
+        logging::init_default();
+
+        let event_loop = Self::create_event_loop();
+        let gl_context = HeadlessContext::create(&settings, &event_loop)?;
+
+        Application::initialize_gl(settings.window_size, |s| {
+            gl_context.get_proc_address(s) as *const _
+        })?;
+
+        let target = Framebuffer::new(
+            settings.window_size,
+            Msaa::None,
+            vec![
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Srgb8A8,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth24Stencil8,
+                    AttachmentType::Renderbuffer,
+                ),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut asset_manager = AssetManager::default();
+        config::mount_asset_packs(&mut asset_manager, &settings);
+
+        Ok(Self {
+            settings,
+            asset_manager,
+            timer: Timer::fixed_step(FIXED_TIME_STEP),
+            framebuffer_cache: TemporaryFramebufferPool::new(3),
+            target,
+            gl_context,
+            _event_loop: event_loop,
+        })
+    }
+
+    // Tests do not run on the main thread.
+    #[cfg(target_os = "linux")]
+    fn create_event_loop() -> EventLoop<()> {
+        use glutin::platform::unix::EventLoopExtUnix;
+
+        EventLoop::new_any_thread()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_event_loop() -> EventLoop<()> {
+        EventLoop::new()
+    }
+
+    // For loading assets or creating GL objects outside of a scene.
+    pub fn context(&mut self) -> Context {
+        Context::new(
+            self.gl_context.window(),
+            &mut self.asset_manager,
+            &mut self.timer,
+            &mut self.framebuffer_cache,
+            &self.settings,
+        )
+    }
+
+    // The framebuffer standing in for the window's, with the last rendered frame.
+    pub fn target(&self) -> &Framebuffer {
+        &self.target
+    }
+
+    // Constructs the scene, runs it for the number of frames and returns the last one. Time
+    // starts at zero and advances by a fixed step, and every frame waits for the assets loading
+    // in the background, so the same scene renders the same frames on every run.
+    pub fn render<Cons, S>(
+        &mut self,
+        scene_constructor: Cons,
+        frames: u32,
+    ) -> Result<RgbaImage, String>
+    where
+        S: Scene + 'static,
+        Cons: FnOnce(Context) -> S,
+    {
+        self.timer = Timer::fixed_step(FIXED_TIME_STEP);
+        let mut main_loop = MainLoop::new(false);
+
+        let scene = scene_constructor(self.context());
+
+        let mut scene_manager = SceneManager::new(scene);
+        scene_manager.initialize(self.context());
+
+        Framebuffer::set_default_id(self.target.id());
+
+        for _ in 0..frames.max(1) {
+            self.upload_pending_assets();
+
+            for _ in 0..main_loop.begin_frame(&mut self.timer) {
+                scene_manager.fixed_update(self.context());
+            }
+
+            scene_manager.update(self.context());
+            if !scene_manager.is_running() {
+                break;
+            }
+
+            scene_manager.pre_draw(self.context());
+
+            FrameStats::begin_frame();
+
+            self.target.bind();
+            self.target.clear(&self.settings.default_clear_color);
+
+            scene_manager.draw(self.context());
+            scene_manager.post_draw(self.context());
+
+            self.framebuffer_cache.collect()
+        }
+
+        let image = self.target.read_pixels(0);
+
+        scene_manager.stop(self.context());
+
+        Framebuffer::set_default_id(0);
+        StateManager::bind_framebuffer(0);
+
+        image
+    }
+
+    fn upload_pending_assets(&mut self) {
+        loop {
+            self.asset_manager.update(ASSET_UPLOAD_BUDGET);
+
+            if self.asset_manager.progress().is_done() {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(1))
+        }
+    }
+}
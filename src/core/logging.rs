@@ -0,0 +1,64 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::io::{self, Write};
+
+// The logger the engine installs when the application has not installed its own. Warnings and
+// errors go to stderr, the rest to stdout, as "[LEVEL target] message".
+//
+// Applications that want a different sink, e.g. env_logger, a file or a tracing subscriber
+// through tracing-log, install it with the log crate before running the application. The engine
+// then leaves it in place.
+pub struct ConsoleLogger;
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        // A closed pipe is no reason to take the application down.
+        let _ = match record.level() {
+            Level::Error | Level::Warn => writeln!(io::stderr(), "{}", line),
+            _ => writeln!(io::stdout(), "{}", line),
+        };
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+    }
+}
+
+// Installs the console logger with the maximum level. Fails if a logger is already installed.
+pub fn init(level: LevelFilter) -> Result<(), String> {
+    log::set_logger(&LOGGER).map_err(|e| e.to_string())?;
+    log::set_max_level(level);
+
+    Ok(())
+}
+
+// Debug messages in debug builds, info in release builds.
+pub fn default_level() -> LevelFilter {
+    if cfg!(debug_assertions) {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    }
+}
+
+// Called at startup. Keeps the logger the application installed, if any.
+pub(crate) fn init_default() {
+    let _ = init(default_level());
+}
@@ -0,0 +1,138 @@
+use crate::core::timer::Timer;
+use std::{
+    ffi::c_void,
+    thread,
+    time::{Duration, Instant},
+};
+
+// The last part of a frame limited wait spins, sleeping is too coarse to hit the frame time.
+const SPIN_DURATION: Duration = Duration::from_millis(1);
+
+// How the main loop paces its frames. Scenes change it through Timer::pacing_mut.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FramePacing {
+    // Changes after the context was created only apply on Windows. Elsewhere the context keeps
+    // the vsync of the settings.
+    pub vsync: bool,
+    // Frames per second the loop waits down to. None renders as fast as it can, or as vsync
+    // allows.
+    pub frame_rate_limit: Option<f32>,
+    pub fixed_time_step: f32,
+    // Fixed updates per frame at most. Frames that took longer drop the rest of their time
+    // rather than running ever more updates to catch up.
+    pub max_fixed_steps: u32,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            frame_rate_limit: None,
+            fixed_time_step: 1.0 / 60.0,
+            max_fixed_steps: 5,
+        }
+    }
+}
+
+// Frame timing of the application. Every frame starts with begin_frame, which advances the timer
+// and returns the fixed updates due, and ends with end_frame after the buffers are swapped.
+pub struct MainLoop {
+    accumulator: f32,
+    frame_start: Instant,
+    // The swap interval the context has, None if unknown.
+    vsync: Option<bool>,
+    // Set once changing the swap interval failed, so that it is reported only once.
+    swap_interval_unsupported: bool,
+}
+
+impl MainLoop {
+    // The context was created with vsync set or not.
+    pub fn new(vsync: bool) -> Self {
+        Self {
+            accumulator: 0.0,
+            frame_start: Instant::now(),
+            vsync: Some(vsync),
+            swap_interval_unsupported: false,
+        }
+    }
+
+    // The number of fixed updates to run before the frame's update.
+    pub fn begin_frame(&mut self, timer: &mut Timer) -> u32 {
+        self.frame_start = Instant::now();
+
+        timer.tick();
+
+        let pacing = *timer.pacing();
+        let step = pacing.fixed_time_step.max(0.0001);
+
+        self.accumulator += timer.delta_time();
+
+        let mut steps = (self.accumulator / step) as u32;
+        self.accumulator -= steps as f32 * step;
+
+        if steps > pacing.max_fixed_steps {
+            steps = pacing.max_fixed_steps;
+            self.accumulator = 0.0;
+        }
+
+        timer.set_interpolation((self.accumulator / step).min(1.0));
+
+        steps
+    }
+
+    // Applies changes of the swap interval and waits out the frame rate limit.
+    pub fn end_frame<F>(&mut self, timer: &Timer, get_proc_address: F)
+    where
+        F: Fn(&str) -> *const c_void,
+    {
+        let pacing = timer.pacing();
+
+        if self.vsync != Some(pacing.vsync) {
+            if !set_swap_interval(get_proc_address, if pacing.vsync { 1 } else { 0 })
+                && !self.swap_interval_unsupported
+            {
+                self.swap_interval_unsupported = true;
+                log::warn!(
+                    "Changing the swap interval is not supported on this platform. Set the vsync \
+                     of the settings instead."
+                );
+            }
+
+            // Not retried every frame when it failed.
+            self.vsync = Some(pacing.vsync)
+        }
+
+        if let Some(frame_rate_limit) = pacing.frame_rate_limit.filter(|&limit| limit > 0.0) {
+            let frame_end = self.frame_start + Duration::from_secs_f32(1.0 / frame_rate_limit);
+            let now = Instant::now();
+
+            if frame_end > now + SPIN_DURATION {
+                thread::sleep(frame_end - now - SPIN_DURATION)
+            }
+
+            while Instant::now() < frame_end {
+                thread::yield_now()
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_swap_interval<F: Fn(&str) -> *const c_void>(get_proc_address: F, interval: i32) -> bool {
+    let swap_interval = get_proc_address("wglSwapIntervalEXT");
+
+    if swap_interval.is_null() {
+        return false;
+    }
+
+    let swap_interval: extern "system" fn(i32) -> i32 =
+        unsafe { std::mem::transmute(swap_interval) };
+
+    swap_interval(interval) != 0
+}
+
+// GLX and EGL need the display and the surface, which glutin does not expose.
+#[cfg(not(target_os = "windows"))]
+fn set_swap_interval<F: Fn(&str) -> *const c_void>(_get_proc_address: F, _interval: i32) -> bool {
+    false
+}
@@ -57,6 +57,17 @@ pub mod quaternion {
         glm::quat_normalize(&(y * p * r))
     }
 
+    // Inverse of from_euler, (yaw, pitch, roll) in degrees.
+    pub fn to_euler(quat: &Quat) -> (f32, f32, f32) {
+        let m = glm::quat_to_mat3(quat);
+
+        let yaw = m[(0, 2)].atan2(m[(2, 2)]);
+        let pitch = (-m[(1, 2)]).max(-1.0).min(1.0).asin();
+        let roll = m[(1, 0)].atan2(m[(1, 1)]);
+
+        (yaw.to_degrees(), pitch.to_degrees(), roll.to_degrees())
+    }
+
     pub fn quat_look_at(target: &Vec3, up: &Vec3) -> Quat {
         glm::quat_look_at(target, up)
     }
@@ -1,12 +1,22 @@
+pub mod animation;
 pub mod application;
 pub mod asset;
+#[cfg(feature = "audio")]
+pub mod audio;
 pub mod camera;
+pub mod config;
+pub mod ecs;
 pub mod entity;
+pub mod golden_image;
+pub mod headless;
+pub mod logging;
+pub mod main_loop;
 pub mod math;
 pub mod scene;
 pub mod timer;
 
-use self::math::{UVec2, Vec4};
+use self::config::{EngineConfig, QualityPreset};
+use self::math::{UVec2, Vec2, Vec4};
 use crate::asset::AssetManager;
 use crate::rendering::framebuffer::TemporaryFramebufferPool;
 use crate::timer::Timer;
@@ -21,7 +31,7 @@ pub struct Version {
     pub patch: u32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u32)]
 pub enum Msaa {
     None = 1,
@@ -41,10 +51,40 @@ pub struct Settings {
     pub fullscreen: bool,
     pub msaa: Msaa,
     pub vsync: bool,
+    pub frame_rate_limit: Option<f32>,
+    // Mounted over the asset path at startup.
+    pub asset_packs: Vec<PathBuf>,
+    pub quality: QualityPreset,
     pub default_clear_color: Vec4,
+    // The window layout of the UI is read from and saved to it. None doesn't persist it.
+    pub ui_layout_path: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Settings {
+    pub fn from_config(name: &str, version: Version, config: &EngineConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            asset_path: config.asset_path.clone(),
+            version,
+            graphics_api_version: Version {
+                major: 4,
+                minor: 5,
+                patch: 0,
+            },
+            window_size: UVec2::new(config.window_size[0], config.window_size[1]),
+            fullscreen: config.fullscreen,
+            msaa: config.window_msaa(),
+            vsync: config.vsync,
+            frame_rate_limit: config.frame_rate_limit,
+            asset_packs: config.asset_packs.clone(),
+            quality: config.quality,
+            default_clear_color: Vec4::new(0.02, 0.02, 0.02, 1.0),
+            ui_layout_path: config.ui_layout_path(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rectangle {
     pub x: i32,
     pub y: i32,
@@ -64,7 +104,8 @@ impl Rectangle {
 }
 
 pub struct Context<'a> {
-    pub window: &'a Window,
+    // None when rendering headless without a window.
+    pub window: Option<&'a Window>,
     pub asset_manager: &'a mut AssetManager,
     pub timer: &'a mut Timer,
     pub framebuffer_cache: &'a mut TemporaryFramebufferPool,
@@ -73,7 +114,7 @@ pub struct Context<'a> {
 
 impl<'a> Context<'a> {
     pub fn new(
-        window: &'a Window,
+        window: Option<&'a Window>,
         asset_manager: &'a mut AssetManager,
         timer: &'a mut Timer,
         framebuffer_cache: &'a mut TemporaryFramebufferPool,
@@ -87,6 +128,26 @@ impl<'a> Context<'a> {
             settings,
         }
     }
+
+    // Size of the framebuffer of the window in physical pixels, or of the offscreen one standing
+    // in for it when rendering headless.
+    pub fn window_size(&self) -> UVec2 {
+        self.window.map_or(self.settings.window_size, |window| {
+            UVec2::new(window.inner_size().width, window.inner_size().height)
+        })
+    }
+
+    // Physical pixels per logical unit of the monitor of the window, 1 when rendering headless.
+    pub fn scale_factor(&self) -> f32 {
+        self.window
+            .map_or(1.0, |window| window.scale_factor() as f32)
+    }
+
+    // The window size in logical units, e.g. for laying out UI independent of the DPI.
+    pub fn logical_window_size(&self) -> Vec2 {
+        let size = self.window_size();
+        Vec2::new(size.x as f32, size.y as f32) / self.scale_factor()
+    }
 }
 
 pub trait AsAny {
@@ -1,5 +1,5 @@
 use crate::core::Context;
-use glutin::event::WindowEvent;
+use glutin::event::{DeviceEvent, WindowEvent};
 use imgui::Ui;
 
 pub enum Transition {
@@ -18,6 +18,12 @@ pub trait Scene {
     fn handle_event(&mut self, context: Context, event: WindowEvent) -> Transition {
         Transition::None
     }
+    // Raw input of the devices, e.g. the relative mouse motion while the cursor is captured.
+    fn handle_device_event(&mut self, context: Context, event: &DeviceEvent) {}
+    // Runs at the fixed time step of the timer, zero or more times per frame before update.
+    fn fixed_update(&mut self, context: Context) -> Transition {
+        Transition::None
+    }
     fn update(&mut self, context: Context) -> Transition {
         Transition::None
     }
@@ -70,6 +76,42 @@ impl SceneManager {
         }
     }
 
+    pub(crate) fn handle_device_event(&mut self, context: Context, event: &DeviceEvent) {
+        if self.is_running {
+            if let Some(scene) = self.scenes.last_mut() {
+                scene.handle_device_event(context, event)
+            }
+        }
+    }
+
+    pub(crate) fn fixed_update(&mut self, context: Context) {
+        let Context {
+            window,
+            asset_manager,
+            timer,
+            framebuffer_cache,
+            settings,
+        } = context;
+
+        if self.is_running {
+            let transition = match self.scenes.last_mut() {
+                Some(scene) => scene.fixed_update(Context::new(
+                    window,
+                    asset_manager,
+                    timer,
+                    framebuffer_cache,
+                    settings,
+                )),
+                None => Transition::None,
+            };
+
+            self.handle_transition(
+                transition,
+                Context::new(window, asset_manager, timer, framebuffer_cache, settings),
+            )
+        }
+    }
+
     pub(crate) fn update(&mut self, context: Context) {
         let Context {
             window,
@@ -1,8 +1,19 @@
+use crate::core::main_loop::FramePacing;
 use std::time::Instant;
 
 pub struct Timer {
     start: Instant,
-    prev_time: f32
+    prev_time: f32,
+    // Time advances by this step every tick instead of with the clock, so frames do not depend
+    // on how fast they render, e.g. for reproducible headless renders.
+    fixed_step: Option<f32>,
+    fixed_time: f32,
+    // Of the frame, set once per tick.
+    delta_time: f32,
+    total_time: f32,
+    frame_count: u64,
+    interpolation: f32,
+    pacing: FramePacing,
 }
 
 impl Timer {
@@ -12,11 +23,47 @@ impl Timer {
 
         Timer {
             start: now,
-            prev_time: now.elapsed().as_secs() as f32 + now.elapsed().subsec_nanos() as f32 * 0.000000001
+            prev_time: now.elapsed().as_secs() as f32 + now.elapsed().subsec_nanos() as f32 * 0.000000001,
+            fixed_step: None,
+            fixed_time: 0.0,
+            delta_time: 0.0,
+            total_time: 0.0,
+            frame_count: 0,
+            interpolation: 0.0,
+            pacing: FramePacing::default(),
         }
     }
 
+    pub fn fixed_step(step: f32) -> Self {
+        Timer {
+            fixed_step: Some(step),
+            prev_time: 0.0,
+            pacing: FramePacing {
+                fixed_time_step: step,
+                ..FramePacing::default()
+            },
+            ..Timer::new()
+        }
+    }
+
+    // Starts a frame. A fixed step timer advances by its step, a clock timer to the current time.
+    pub fn tick(&mut self) {
+        if let Some(step) = self.fixed_step {
+            self.fixed_time += step
+        }
+
+        let total_time = self.get_elapsed_time();
+
+        self.delta_time = total_time - self.total_time;
+        self.total_time = total_time;
+        self.frame_count += 1
+    }
+
     pub fn get_elapsed_time(&self) -> f32 {
+        if self.fixed_step.is_some() {
+            return self.fixed_time;
+        }
+
         self.start.elapsed().as_secs() as f32 + self.start.elapsed().subsec_nanos() as f32 * 0.000000001
     }
 
@@ -28,4 +75,42 @@ impl Timer {
         delta
     }
 
+    // Seconds between the last two frames.
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    // Seconds from the start to the current frame. Unlike get_elapsed_time, the same throughout
+    // the frame.
+    pub fn total_time(&self) -> f32 {
+        self.total_time
+    }
+
+    // Seconds every fixed update advances by.
+    pub fn fixed_time_step(&self) -> f32 {
+        self.pacing.fixed_time_step
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // How far the frame is between the last fixed update and the next, in [0, 1). Rendering
+    // blends the last two fixed update states by it for smooth motion at any frame rate.
+    pub fn interpolation(&self) -> f32 {
+        self.interpolation
+    }
+
+    pub(crate) fn set_interpolation(&mut self, interpolation: f32) {
+        self.interpolation = interpolation
+    }
+
+    pub fn pacing(&self) -> &FramePacing {
+        &self.pacing
+    }
+
+    // Changes take effect at the next frame.
+    pub fn pacing_mut(&mut self) -> &mut FramePacing {
+        &mut self.pacing
+    }
 }
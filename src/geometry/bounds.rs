@@ -0,0 +1,91 @@
+use crate::core::math::{Mat4, Vec3, Vec4};
+
+// Axis aligned bounding box. An empty box has min > max.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::repeat(std::f32::MAX),
+            max: Vec3::repeat(std::f32::MIN),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        let mut aabb = Self::empty();
+
+        for point in points {
+            aabb.grow(&point);
+        }
+
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, point: &Vec3) {
+        self.min = self.min.inf(point);
+        self.max = self.max.sup(point);
+    }
+
+    // Grows the box to enclose the other one.
+    pub fn merge(&mut self, other: &Aabb) {
+        if !other.is_empty() {
+            self.grow(&other.min);
+            self.grow(&other.max);
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // Half of the size along each axis.
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // The box that encloses this one after the transformation.
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        Self::from_points(
+            self.corners()
+                .iter()
+                .map(|corner| (matrix * Vec4::new(corner.x, corner.y, corner.z, 1.0)).xyz()),
+        )
+    }
+}
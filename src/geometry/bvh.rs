@@ -0,0 +1,383 @@
+use crate::{
+    core::math::{Vec2, Vec3},
+    geometry::{bounds::Aabb, ray::Ray, MeshData},
+};
+use std::cmp::Ordering;
+
+const MAX_LEAF_SIZE: u32 = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bounds: Aabb,
+    // The first primitive of leaves, the first child of inner nodes. The second child follows it.
+    first: u32,
+    // 0 for inner nodes.
+    count: u32,
+}
+
+// Bounding volume hierarchy over primitives given by their bounds. Nodes are split at the
+// median of the centers of their primitives along the axis they spread the most.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // Indices into the bounds the hierarchy was built from, ordered by leaf.
+    primitives: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: vec![BvhNode {
+                bounds: Aabb::empty(),
+                first: 0,
+                count: bounds.len() as u32,
+            }],
+            primitives: (0..bounds.len() as u32).collect(),
+        };
+
+        if !bounds.is_empty() {
+            bvh.subdivide(0, bounds);
+        }
+
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    // The closest primitive the ray hits within max_distance, and the distance of the hit. hit
+    // intersects the ray with the primitive of the index, given the closest distance so far.
+    // Nodes are visited near to far and skipped once they are farther than the closest hit.
+    pub fn closest_hit<F>(&self, ray: &Ray, max_distance: f32, mut hit: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        let mut closest = None;
+        let mut max_distance = max_distance;
+
+        let mut stack = match ray.intersect_aabb(&self.nodes[0].bounds, max_distance) {
+            Some(distance) => vec![(0, distance)],
+            None => return None,
+        };
+
+        while let Some((index, entry_distance)) = stack.pop() {
+            if entry_distance > max_distance {
+                continue;
+            }
+
+            let node = &self.nodes[index];
+
+            if node.count > 0 {
+                let primitives = &self.primitives[node.first as usize..][..node.count as usize];
+
+                for &primitive in primitives {
+                    if let Some(distance) = hit(primitive as usize, max_distance) {
+                        if distance < max_distance {
+                            max_distance = distance;
+                            closest = Some((primitive as usize, distance));
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            let children = [node.first as usize, node.first as usize + 1];
+            let mut entries = children
+                .iter()
+                .filter_map(|&child| {
+                    ray.intersect_aabb(&self.nodes[child].bounds, max_distance)
+                        .map(|distance| (child, distance))
+                })
+                .collect::<Vec<_>>();
+
+            // The nearer child is popped first.
+            entries.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            stack.extend(entries);
+        }
+
+        closest
+    }
+
+    fn subdivide(&mut self, index: usize, bounds: &[Aabb]) {
+        let BvhNode { first, count, .. } = self.nodes[index];
+        let range = first as usize..(first + count) as usize;
+
+        let mut node_bounds = Aabb::empty();
+        let mut centers = Aabb::empty();
+
+        for &primitive in &self.primitives[range.clone()] {
+            node_bounds.merge(&bounds[primitive as usize]);
+            centers.grow(&bounds[primitive as usize].center());
+        }
+
+        self.nodes[index].bounds = node_bounds;
+
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let spread = centers.max - centers.min;
+        let axis = spread.imax();
+
+        // Coinciding centers can't be told apart.
+        if spread[axis] <= 0.0 {
+            return;
+        }
+
+        self.primitives[range].sort_unstable_by(|&a, &b| {
+            let a = bounds[a as usize].center()[axis];
+            let b = bounds[b as usize].center()[axis];
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        });
+
+        let half = count / 2;
+        let first_child = self.nodes.len();
+
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first,
+            count: half,
+        });
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first: first + half,
+            count: count - half,
+        });
+
+        self.nodes[index].first = first_child as u32;
+        self.nodes[index].count = 0;
+
+        self.subdivide(first_child, bounds);
+        self.subdivide(first_child + 1, bounds);
+    }
+}
+
+// Where a ray hit a mesh, in the space of the mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub distance: f32,
+    pub triangle: usize,
+    pub position: Vec3,
+    // Interpolated from the vertex normals, the face normal where they cancel out.
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+// The triangles of a mesh for ray queries on the CPU, e.g. mouse picking or placing objects on
+// surfaces. Keeps a copy of the positions, normals and UVs of the vertices.
+#[derive(Debug, Clone)]
+pub struct MeshBvh {
+    bvh: Bvh,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    tex_coords: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl MeshBvh {
+    pub fn new(mesh_data: &MeshData) -> Self {
+        let positions = mesh_data
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+
+        let triangle_bounds = mesh_data
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| Aabb::from_points(triangle.iter().map(|&i| positions[i as usize])))
+            .collect::<Vec<_>>();
+
+        Self {
+            bvh: Bvh::new(&triangle_bounds),
+            normals: mesh_data.vertices.iter().map(|v| v.normal).collect(),
+            tex_coords: mesh_data.vertices.iter().map(|v| v.tex_coord).collect(),
+            positions,
+            indices: mesh_data.indices.clone(),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // The closest triangle the ray hits within max_distance, from either side.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        let mut barycentrics = (0.0, 0.0);
+
+        let intersect = |triangle: usize, closest: f32| {
+            let [a, b, c] = self.triangle(triangle);
+
+            ray.intersect_triangle(&self.positions[a], &self.positions[b], &self.positions[c])
+                .filter(|&(distance, _, _)| distance < closest)
+                .map(|(distance, u, v)| {
+                    barycentrics = (u, v);
+                    distance
+                })
+        };
+
+        let (triangle, distance) = self.bvh.closest_hit(ray, max_distance, intersect)?;
+
+        let [a, b, c] = self.triangle(triangle);
+        let (u, v) = barycentrics;
+        let w = 1.0 - u - v;
+
+        let normal = self.normals[a] * w + self.normals[b] * u + self.normals[c] * v;
+        let normal = normal.try_normalize(std::f32::EPSILON).unwrap_or_else(|| {
+            (self.positions[b] - self.positions[a])
+                .cross(&(self.positions[c] - self.positions[a]))
+                .normalize()
+        });
+
+        Some(TriangleHit {
+            distance,
+            triangle,
+            position: ray.at(distance),
+            normal,
+            uv: self.tex_coords[a] * w + self.tex_coords[b] * u + self.tex_coords[c] * v,
+        })
+    }
+
+    fn triangle(&self, triangle: usize) -> [usize; 3] {
+        let indices = &self.indices[triangle * 3..triangle * 3 + 3];
+
+        [
+            indices[0] as usize,
+            indices[1] as usize,
+            indices[2] as usize,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bvh, MeshBvh};
+    use crate::{
+        core::math::{Vec2, Vec3},
+        geometry::{bounds::Aabb, ray::Ray, shapes},
+    };
+
+    fn unit_box(min: Vec3) -> Aabb {
+        Aabb::new(min, min + Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    fn closest_box(bvh: &Bvh, bounds: &[Aabb], ray: &Ray, max_distance: f32) -> Option<usize> {
+        bvh.closest_hit(ray, max_distance, |i, closest| {
+            ray.intersect_aabb(&bounds[i], closest)
+        })
+        .map(|(i, _)| i)
+    }
+
+    #[test]
+    fn empty_hierarchy_has_no_hits() {
+        let bvh = Bvh::new(&[]);
+
+        assert!(bvh.is_empty());
+        assert!(bvh
+            .closest_hit(&Ray::new(Vec3::zeros(), Vec3::x()), 100.0, |_, _| Some(0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn bounds_contain_every_primitive() {
+        let bounds = (0..10)
+            .map(|i| unit_box(Vec3::new(i as f32 * 2.0, -(i as f32), 0.5)))
+            .collect::<Vec<_>>();
+
+        let bvh = Bvh::new(&bounds);
+
+        assert_eq!(bvh.bounds().min, Vec3::new(0.0, -9.0, 0.5));
+        assert_eq!(bvh.bounds().max, Vec3::new(19.0, 1.0, 1.5));
+    }
+
+    #[test]
+    fn finds_the_closest_primitive() {
+        let bounds = (0..100)
+            .map(|i| unit_box(Vec3::new(i as f32 * 2.0, 0.0, 0.0)))
+            .collect::<Vec<_>>();
+
+        let bvh = Bvh::new(&bounds);
+        let forward = Ray::new(Vec3::new(-5.0, 0.5, 0.5), Vec3::x());
+        let backward = Ray::new(Vec3::new(500.0, 0.5, 0.5), -Vec3::x());
+
+        assert_eq!(
+            bvh.closest_hit(&forward, 1000.0, |i, closest| {
+                forward.intersect_aabb(&bounds[i], closest)
+            }),
+            Some((0, 5.0))
+        );
+        assert_eq!(closest_box(&bvh, &bounds, &backward, 1000.0), Some(99));
+        assert_eq!(closest_box(&bvh, &bounds, &forward, 4.0), None);
+    }
+
+    #[test]
+    fn matches_testing_every_primitive() {
+        // A fixed linear congruential sequence keeps the test deterministic.
+        let mut seed = 12345u32;
+        let mut random = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+
+        let bounds = (0..200)
+            .map(|_| unit_box(Vec3::new(random(), random(), random()) * 20.0))
+            .collect::<Vec<_>>();
+
+        let bvh = Bvh::new(&bounds);
+
+        for _ in 0..200 {
+            let origin = Vec3::new(random(), random(), random()) * 40.0 - Vec3::repeat(10.0);
+            let direction = Vec3::new(random(), random(), random()) - Vec3::repeat(0.5);
+            let ray = Ray::new(origin, direction);
+
+            let expected = bounds
+                .iter()
+                .filter_map(|aabb| ray.intersect_aabb(aabb, 100.0))
+                .fold(None, |closest: Option<f32>, distance| {
+                    Some(closest.map_or(distance, |closest| closest.min(distance)))
+                });
+
+            let hit = bvh.closest_hit(&ray, 100.0, |i, closest| {
+                ray.intersect_aabb(&bounds[i], closest)
+            });
+
+            assert_eq!(hit.map(|(_, distance)| distance), expected);
+        }
+    }
+
+    #[test]
+    fn raycasts_meshes_from_both_sides() {
+        let bvh = MeshBvh::new(&shapes::plane(2.0, 2.0, 4));
+
+        assert_eq!(bvh.triangle_count(), 32);
+
+        let hit = bvh
+            .raycast(&Ray::new(Vec3::new(0.25, 1.0, 0.25), -Vec3::y()), 10.0)
+            .unwrap();
+
+        assert!((hit.distance - 1.0).abs() < 1e-5);
+        assert!((hit.position - Vec3::new(0.25, 0.0, 0.25)).norm() < 1e-5);
+        assert!((hit.normal - Vec3::y()).norm() < 1e-5);
+        assert!((hit.uv - Vec2::new(0.625, 0.375)).norm() < 1e-5);
+
+        let below = bvh.raycast(&Ray::new(Vec3::new(0.25, -2.0, 0.25), Vec3::y()), 10.0);
+
+        assert!((below.unwrap().distance - 2.0).abs() < 1e-5);
+        assert!(bvh
+            .raycast(&Ray::new(Vec3::new(3.0, 1.0, 0.0), -Vec3::y()), 10.0)
+            .is_none());
+        assert!(bvh
+            .raycast(&Ray::new(Vec3::new(0.0, 1.0, 0.0), -Vec3::y()), 0.5)
+            .is_none());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::rendering::{
+    indirect::DrawElementsIndirectCommand,
+    mesh::{Mesh, QuantizedVertex, Vertex},
+};
+use std::{mem, slice};
+
+pub mod bounds;
+pub mod bvh;
+pub mod optimize;
+pub mod quantize;
+pub mod ray;
+pub mod shapes;
+#[cfg(feature = "meshopt")]
+pub mod simplify;
+pub mod tangents;
+
+// CPU side mesh data. Kept separate from Mesh so it can be processed before the upload.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self { vertices, indices }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // Appends another mesh for batched drawing and returns the command that draws it from the
+    // combined buffers.
+    pub fn append(&mut self, other: &MeshData) -> DrawElementsIndirectCommand {
+        let command = DrawElementsIndirectCommand {
+            count: other.indices.len() as u32,
+            instance_count: 1,
+            first_index: self.indices.len() as u32,
+            base_vertex: self.vertices.len() as i32,
+            base_instance: 0,
+        };
+
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend_from_slice(&other.indices);
+
+        command
+    }
+
+    pub fn into_mesh(self) -> Mesh {
+        Mesh::new(self.vertices, self.indices)
+    }
+
+    // Uploads the mesh with compressed normals, tangents, UVs and colors.
+    pub fn into_quantized_mesh(self) -> Mesh {
+        Mesh::new_with_layout(
+            &quantize::quantize_vertices(&self.vertices),
+            &self.indices,
+            QuantizedVertex::layout(),
+        )
+    }
+}
+
+pub(crate) fn vertex_bytes(vertices: &[Vertex]) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(
+            vertices.as_ptr() as *const u8,
+            vertices.len() * mem::size_of::<Vertex>(),
+        )
+    }
+}
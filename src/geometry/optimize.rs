@@ -0,0 +1,63 @@
+use crate::geometry::MeshData;
+
+// Overdraw optimization may degrade the vertex cache efficiency by up to this factor.
+#[cfg(feature = "meshopt")]
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+// Reorders the triangles for post-transform cache efficiency and reduced overdraw, then the
+// vertices for fetch locality. Expects an indexed triangle list. The rendered result is unchanged
+// but vertices that are not referenced by any triangle are dropped.
+pub fn optimize(mesh_data: &mut MeshData) {
+    if mesh_data.indices.is_empty() || mesh_data.indices.len() % 3 != 0 {
+        return;
+    }
+
+    optimize_vertex_cache(mesh_data);
+    optimize_vertex_fetch(mesh_data);
+}
+
+#[cfg(feature = "meshopt")]
+fn optimize_vertex_cache(mesh_data: &mut MeshData) {
+    use crate::{geometry::vertex_bytes, rendering::mesh::Vertex};
+    use std::mem;
+
+    let mut indices = meshopt::optimize_vertex_cache(&mesh_data.indices, mesh_data.vertices.len());
+
+    let adapter = meshopt::VertexDataAdapter::new(
+        vertex_bytes(&mesh_data.vertices),
+        mem::size_of::<Vertex>(),
+        // position is the first field of the #[repr(C)] Vertex.
+        0,
+    )
+    .expect("Failed to create meshopt vertex adapter.");
+
+    meshopt::optimize_overdraw_in_place(&mut indices, &adapter, OVERDRAW_THRESHOLD);
+
+    mesh_data.indices = indices;
+}
+
+#[cfg(not(feature = "meshopt"))]
+fn optimize_vertex_cache(_: &mut MeshData) {
+    log::warn!("Vertex cache and overdraw optimization requires the meshopt feature. Skipping.");
+}
+
+// Renumbers the vertices in the order the index buffer first references them.
+pub fn optimize_vertex_fetch(mesh_data: &mut MeshData) {
+    const UNUSED: u32 = u32::MAX;
+
+    let mut remap = vec![UNUSED; mesh_data.vertices.len()];
+    let mut vertices = Vec::with_capacity(mesh_data.vertices.len());
+
+    for index in mesh_data.indices.iter_mut() {
+        let old_index = *index as usize;
+
+        if remap[old_index] == UNUSED {
+            remap[old_index] = vertices.len() as u32;
+            vertices.push(mesh_data.vertices[old_index]);
+        }
+
+        *index = remap[old_index];
+    }
+
+    mesh_data.vertices = vertices;
+}
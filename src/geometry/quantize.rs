@@ -0,0 +1,91 @@
+use crate::rendering::mesh::{QuantizedVertex, Vertex};
+
+pub fn quantize_vertices(vertices: &[Vertex]) -> Vec<QuantizedVertex> {
+    vertices
+        .iter()
+        .map(|v| QuantizedVertex {
+            position: [v.position.x, v.position.y, v.position.z],
+            normal: [
+                snorm8(v.normal.x),
+                snorm8(v.normal.y),
+                snorm8(v.normal.z),
+                0,
+            ],
+            tangent: [
+                snorm8(v.tangent.x),
+                snorm8(v.tangent.y),
+                snorm8(v.tangent.z),
+                snorm8(v.tangent.w),
+            ],
+            tex_coord: [half(v.tex_coord.x), half(v.tex_coord.y)],
+            color: [
+                unorm8(v.color.x),
+                unorm8(v.color.y),
+                unorm8(v.color.z),
+                unorm8(v.color.w),
+            ],
+            tex_coord1: [half(v.tex_coord1.x), half(v.tex_coord1.y)],
+        })
+        .collect()
+}
+
+pub fn snorm8(value: f32) -> i8 {
+    (value.max(-1.0).min(1.0) * 127.0).round() as i8
+}
+
+pub fn unorm8(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+// IEEE 754 binary16 conversion with round to nearest even.
+pub fn half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    // NaN and infinity.
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+
+    // Too large, clamp to infinity.
+    if half_exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    // Subnormal or zero.
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+
+        let rounded = if remainder > halfway || (remainder == halfway && half_mantissa & 1 != 0) {
+            half_mantissa + 1
+        } else {
+            half_mantissa
+        };
+
+        return sign | rounded as u16;
+    }
+
+    let half_mantissa = mantissa >> 13;
+    let remainder = mantissa & 0x1FFF;
+
+    let mut result = ((half_exponent as u32) << 10) | half_mantissa;
+
+    // Carries into the exponent are intended and correctly round up to infinity.
+    if remainder > 0x1000 || (remainder == 0x1000 && half_mantissa & 1 != 0) {
+        result += 1;
+    }
+
+    sign | result as u16
+}
@@ -0,0 +1,105 @@
+use crate::{
+    core::math::{Mat4, Vec3, Vec4},
+    geometry::bounds::Aabb,
+};
+
+// Distances along the ray are in units of the length of its direction, world units for rays
+// with a normalized direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    // The direction is normalized.
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    // Keeps the length of the transformed direction, so distances along the transformed ray are
+    // the distances along this one.
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let origin = matrix * Vec4::new(self.origin.x, self.origin.y, self.origin.z, 1.0);
+        let direction =
+            matrix * Vec4::new(self.direction.x, self.direction.y, self.direction.z, 0.0);
+
+        Self {
+            origin: origin.xyz() / origin.w,
+            direction: direction.xyz(),
+        }
+    }
+
+    // Distance to where the ray enters the box, 0 from inside of it. Slab test.
+    pub fn intersect_aabb(&self, aabb: &Aabb, max_distance: f32) -> Option<f32> {
+        if aabb.is_empty() {
+            return None;
+        }
+
+        let mut near = 0.0f32;
+        let mut far = max_distance;
+
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inverse_direction;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inverse_direction;
+
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // NaNs of rays parallel to a slab through its border are ignored by min and max.
+            near = near.max(t0);
+            far = far.min(t1);
+
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    // Distance and barycentric coordinates of b and c of the hit, both sides of the triangle.
+    // Reference: Möller and Trumbore, Fast, Minimum Storage Ray/Triangle Intersection.
+    pub fn intersect_triangle(&self, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<(f32, f32, f32)> {
+        let ab = b - a;
+        let ac = c - a;
+
+        let p = self.direction.cross(&ac);
+        let determinant = ab.dot(&p);
+
+        if determinant.abs() < std::f32::EPSILON {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - a;
+
+        let u = to_origin.dot(&p) * inverse_determinant;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+
+        let q = to_origin.cross(&ab);
+        let v = self.direction.dot(&q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = ac.dot(&q) * inverse_determinant;
+
+        if distance >= 0.0 {
+            Some((distance, u, v))
+        } else {
+            None
+        }
+    }
+}
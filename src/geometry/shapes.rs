@@ -0,0 +1,511 @@
+use crate::{
+    core::math::{Vec2, Vec3, Vec4},
+    geometry::MeshData,
+    rendering::mesh::Vertex,
+};
+use nalgebra_glm as glm;
+use std::{collections::HashMap, f32::consts::PI};
+
+// All shapes are centered at the origin, use counter clockwise winding for front faces and
+// follow the glTF tangent convention: bitangent = cross(normal, tangent.xyz) * tangent.w.
+
+pub fn cube(size: f32) -> MeshData {
+    cuboid(Vec3::new(size, size, size))
+}
+
+pub fn cuboid(dimensions: Vec3) -> MeshData {
+    // (normal, tangent) per face. The bitangent is cross(normal, tangent).
+    const FACES: [([f32; 3], [f32; 3]); 6] = [
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+
+    let half_dimensions = dimensions * 0.5;
+
+    let mut mesh_data = MeshData::default();
+
+    for (normal, tangent) in FACES.iter() {
+        let (normal, tangent) = (Vec3::from(*normal), Vec3::from(*tangent));
+        let bitangent = glm::cross(&normal, &tangent);
+        let first_vertex = mesh_data.vertices.len() as u32;
+
+        for &v in [0.0, 1.0].iter() {
+            for &u in [0.0, 1.0].iter() {
+                let position = (normal + tangent * (u * 2.0 - 1.0) + bitangent * (v * 2.0 - 1.0))
+                    .component_mul(&half_dimensions);
+
+                mesh_data
+                    .vertices
+                    .push(vertex(position, normal, tangent, Vec2::new(u, v)));
+            }
+        }
+
+        grid_indices(1, 1, first_vertex, &mut mesh_data.indices);
+    }
+
+    mesh_data
+}
+
+// A plane facing +Y.
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> MeshData {
+    let segments = subdivisions.max(1);
+    let mut mesh_data = MeshData::default();
+
+    for row in 0..=segments {
+        let v = row as f32 / segments as f32;
+
+        for column in 0..=segments {
+            let u = column as f32 / segments as f32;
+
+            mesh_data.vertices.push(vertex(
+                Vec3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec2::new(u, v),
+            ));
+        }
+    }
+
+    grid_indices(segments, segments, 0, &mut mesh_data.indices);
+
+    mesh_data
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+    let mut mesh_data = MeshData::default();
+
+    // Rows go from the south to the north pole so that v increases with the row.
+    for row in 0..=rings {
+        let v = row as f32 / rings as f32;
+        let theta = PI * (1.0 - v);
+
+        for column in 0..=segments {
+            let u = column as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+
+            let normal = spherical_direction(theta, phi);
+
+            mesh_data.vertices.push(vertex(
+                normal * radius,
+                normal,
+                longitude_tangent(phi),
+                Vec2::new(u, v),
+            ));
+        }
+    }
+
+    grid_indices(segments, rings, 0, &mut mesh_data.indices);
+
+    mesh_data
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5.0f32.sqrt()) * 0.5;
+
+    let mut positions: Vec<Vec3> = [
+        Vec3::new(-1.0, t, 0.0),
+        Vec3::new(1.0, t, 0.0),
+        Vec3::new(-1.0, -t, 0.0),
+        Vec3::new(1.0, -t, 0.0),
+        Vec3::new(0.0, -1.0, t),
+        Vec3::new(0.0, 1.0, t),
+        Vec3::new(0.0, -1.0, -t),
+        Vec3::new(0.0, 1.0, -t),
+        Vec3::new(t, 0.0, -1.0),
+        Vec3::new(t, 0.0, 1.0),
+        Vec3::new(-t, 0.0, -1.0),
+        Vec3::new(-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(glm::normalize)
+    .collect();
+
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vec3>| {
+            let key = (a.min(b), a.max(b));
+
+            *midpoints.entry(key).or_insert_with(|| {
+                let midpoint = positions[a as usize] + positions[b as usize];
+                positions.push(glm::normalize(&midpoint));
+                positions.len() as u32 - 1
+            })
+        };
+
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut mesh_data = MeshData::default();
+
+    mesh_data.vertices = positions
+        .iter()
+        .map(|normal| {
+            let theta = normal.y.max(-1.0).min(1.0).acos();
+            let mut phi = (-normal.z).atan2(normal.x);
+            if phi < 0.0 {
+                phi += 2.0 * PI;
+            }
+
+            vertex(
+                normal * radius,
+                *normal,
+                longitude_tangent(phi),
+                Vec2::new(phi / (2.0 * PI), 1.0 - theta / PI),
+            )
+        })
+        .collect();
+
+    // Triangles crossing the u = 0/1 seam would interpolate across the whole texture. Give them
+    // their own copies of the vertices on the u = 0 side, shifted by 1.
+    let mut seam_vertices: HashMap<u32, u32> = HashMap::new();
+
+    for triangle in triangles.iter_mut() {
+        let u = |i: u32, vertices: &Vec<Vertex>| vertices[i as usize].tex_coord.x;
+
+        let max_u = triangle
+            .iter()
+            .map(|&i| u(i, &mesh_data.vertices))
+            .fold(0.0f32, f32::max);
+
+        if max_u < 0.75 {
+            continue;
+        }
+
+        for index in triangle.iter_mut() {
+            if u(*index, &mesh_data.vertices) < 0.25 {
+                let vertices = &mut mesh_data.vertices;
+
+                *index = *seam_vertices.entry(*index).or_insert_with(|| {
+                    let mut seam_vertex = vertices[*index as usize];
+                    seam_vertex.tex_coord.x += 1.0;
+                    vertices.push(seam_vertex);
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+
+    mesh_data.indices = triangles.iter().flatten().copied().collect();
+
+    mesh_data
+}
+
+// A torus around the Y axis.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> MeshData {
+    let major_segments = major_segments.max(3);
+    let minor_segments = minor_segments.max(3);
+    let mut mesh_data = MeshData::default();
+
+    for row in 0..=minor_segments {
+        let v = row as f32 / minor_segments as f32;
+        let psi = v * 2.0 * PI;
+
+        for column in 0..=major_segments {
+            let u = column as f32 / major_segments as f32;
+            let phi = u * 2.0 * PI;
+
+            let normal = Vec3::new(psi.cos() * phi.cos(), psi.sin(), -psi.cos() * phi.sin());
+            let center = Vec3::new(major_radius * phi.cos(), 0.0, -major_radius * phi.sin());
+
+            mesh_data.vertices.push(vertex(
+                center + normal * minor_radius,
+                normal,
+                longitude_tangent(phi),
+                Vec2::new(u, v),
+            ));
+        }
+    }
+
+    grid_indices(major_segments, minor_segments, 0, &mut mesh_data.indices);
+
+    mesh_data
+}
+
+// A capsule along the Y axis. The total height is height + 2 * radius.
+pub fn capsule(radius: f32, height: f32, segments: u32, hemisphere_rings: u32) -> MeshData {
+    let segments = segments.max(3);
+    let hemisphere_rings = hemisphere_rings.max(1);
+    let half_height = height * 0.5;
+    let total_height = height + 2.0 * radius;
+    let mut mesh_data = MeshData::default();
+
+    // Bottom hemisphere rows followed by top hemisphere rows. The quads between the two
+    // equators form the cylinder.
+    let rows = (0..=hemisphere_rings)
+        .map(|ring| {
+            let theta = PI - 0.5 * PI * ring as f32 / hemisphere_rings as f32;
+            (theta, -half_height)
+        })
+        .chain((0..=hemisphere_rings).map(|ring| {
+            let theta = 0.5 * PI - 0.5 * PI * ring as f32 / hemisphere_rings as f32;
+            (theta, half_height)
+        }));
+
+    for (theta, y_offset) in rows {
+        for column in 0..=segments {
+            let u = column as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+
+            let normal = spherical_direction(theta, phi);
+            let position = normal * radius + Vec3::new(0.0, y_offset, 0.0);
+            let v = (position.y + total_height * 0.5) / total_height;
+
+            mesh_data.vertices.push(vertex(
+                position,
+                normal,
+                longitude_tangent(phi),
+                Vec2::new(u, v),
+            ));
+        }
+    }
+
+    grid_indices(
+        segments,
+        2 * hemisphere_rings + 1,
+        0,
+        &mut mesh_data.indices,
+    );
+
+    mesh_data
+}
+
+// A single triangle covering the [-1, 1] clip space square. Positions are in clip space.
+pub fn fullscreen_triangle() -> MeshData {
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+    let tangent = Vec3::new(1.0, 0.0, 0.0);
+
+    MeshData::new(
+        vec![
+            vertex(
+                Vec3::new(-1.0, -1.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(0.0, 0.0),
+            ),
+            vertex(
+                Vec3::new(3.0, -1.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(2.0, 0.0),
+            ),
+            vertex(
+                Vec3::new(-1.0, 3.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(0.0, 2.0),
+            ),
+        ],
+        vec![0, 1, 2],
+    )
+}
+
+// Indices for a (columns + 1) x (rows + 1) vertex grid stored row by row, where u increases
+// with the column and v with the row.
+fn grid_indices(columns: u32, rows: u32, first_vertex: u32, indices: &mut Vec<u32>) {
+    let stride = columns + 1;
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = first_vertex + row * stride + column;
+            let b = a + 1;
+            let c = b + stride;
+            let d = a + stride;
+
+            indices.extend_from_slice(&[a, b, c, a, c, d]);
+        }
+    }
+}
+
+// theta is measured from +Y. phi increases clockwise when looking down the Y axis so that u
+// increases to the right when looking at the surface from outside.
+fn spherical_direction(theta: f32, phi: f32) -> Vec3 {
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        -theta.sin() * phi.sin(),
+    )
+}
+
+fn longitude_tangent(phi: f32) -> Vec3 {
+    Vec3::new(-phi.sin(), 0.0, -phi.cos())
+}
+
+fn vertex(position: Vec3, normal: Vec3, tangent: Vec3, uv: Vec2) -> Vertex {
+    Vertex {
+        position,
+        normal,
+        tangent: Vec4::new(tangent.x, tangent.y, tangent.z, 1.0),
+        tex_coord: uv,
+        color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        tex_coord1: uv,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_shapes() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("cube", cube(2.0)),
+            ("cuboid", cuboid(Vec3::new(1.0, 2.0, 3.0))),
+            ("plane", plane(2.0, 3.0, 4)),
+            ("uv_sphere", uv_sphere(1.5, 16, 8)),
+            ("icosphere", icosphere(1.5, 2)),
+            ("torus", torus(2.0, 0.5, 16, 8)),
+            ("capsule", capsule(0.5, 2.0, 16, 4)),
+        ]
+    }
+
+    #[test]
+    fn indices_form_triangles_of_existing_vertices() {
+        for (name, mesh_data) in all_shapes() {
+            assert_eq!(mesh_data.indices.len() % 3, 0, "{}", name);
+            assert!(
+                mesh_data
+                    .indices
+                    .iter()
+                    .all(|&i| (i as usize) < mesh_data.vertices.len()),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn tangent_frames_are_orthonormal() {
+        for (name, mesh_data) in all_shapes() {
+            for vertex in &mesh_data.vertices {
+                let tangent = vertex.tangent.xyz();
+
+                assert!((glm::length(&vertex.normal) - 1.0).abs() < 1e-4, "{}", name);
+                assert!((glm::length(&tangent) - 1.0).abs() < 1e-4, "{}", name);
+                assert!(glm::dot(&vertex.normal, &tangent).abs() < 1e-4, "{}", name);
+                assert_eq!(vertex.tangent.w, 1.0, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn front_faces_wind_counter_clockwise() {
+        for (name, mesh_data) in all_shapes() {
+            for triangle in mesh_data.indices.chunks_exact(3) {
+                let [a, b, c] = [
+                    &mesh_data.vertices[triangle[0] as usize],
+                    &mesh_data.vertices[triangle[1] as usize],
+                    &mesh_data.vertices[triangle[2] as usize],
+                ];
+
+                let face_normal =
+                    glm::cross(&(b.position - a.position), &(c.position - a.position));
+
+                // The triangles touching the poles of the spheres have no area.
+                if glm::length(&face_normal) < 1e-6 {
+                    continue;
+                }
+
+                let vertex_normal = a.normal + b.normal + c.normal;
+
+                assert!(glm::dot(&face_normal, &vertex_normal) > 0.0, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn normals_point_away_from_the_center_of_spheres() {
+        for mesh_data in [uv_sphere(1.5, 16, 8), icosphere(1.5, 2)].iter() {
+            for vertex in &mesh_data.vertices {
+                assert!((glm::length(&vertex.position) - 1.5).abs() < 1e-4);
+                assert!((vertex.position / 1.5 - vertex.normal).norm() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_have_the_requested_extents() {
+        let extents = |mesh_data: &MeshData| {
+            mesh_data
+                .vertices
+                .iter()
+                .fold(Vec3::zeros(), |extents, vertex| {
+                    glm::max2(&extents, &glm::abs(&vertex.position))
+                })
+        };
+
+        assert!(
+            (extents(&cuboid(Vec3::new(1.0, 2.0, 3.0))) - Vec3::new(0.5, 1.0, 1.5)).norm() < 1e-5
+        );
+        assert!((extents(&plane(2.0, 3.0, 4)) - Vec3::new(1.0, 0.0, 1.5)).norm() < 1e-5);
+        assert!((extents(&torus(2.0, 0.5, 16, 8)) - Vec3::new(2.5, 0.5, 2.5)).norm() < 1e-5);
+        assert!((extents(&capsule(0.5, 2.0, 16, 4)).y - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn icosphere_seam_triangles_do_not_wrap_the_texture() {
+        let mesh_data = icosphere(1.0, 2);
+
+        for triangle in mesh_data.indices.chunks_exact(3) {
+            // u is undefined at the poles.
+            let touches_pole = triangle
+                .iter()
+                .any(|&i| mesh_data.vertices[i as usize].normal.y.abs() > 0.9999);
+
+            if touches_pole {
+                continue;
+            }
+
+            let u = triangle
+                .iter()
+                .map(|&i| mesh_data.vertices[i as usize].tex_coord.x)
+                .collect::<Vec<_>>();
+
+            let min_u = u.iter().copied().fold(f32::MAX, f32::min);
+            let max_u = u.iter().copied().fold(f32::MIN, f32::max);
+
+            assert!(max_u - min_u < 0.5);
+        }
+    }
+}
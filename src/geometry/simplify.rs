@@ -0,0 +1,30 @@
+use crate::{
+    geometry::{vertex_bytes, MeshData},
+    rendering::mesh::Vertex,
+};
+use std::mem;
+
+// Reduces the triangle count to roughly target_ratio of the original while keeping the
+// geometric error below target_error (relative to the mesh extents). Vertices are left
+// untouched, unreferenced ones are simply no longer indexed.
+pub fn simplify(mesh_data: &MeshData, target_ratio: f32, target_error: f32) -> MeshData {
+    let target_ratio = target_ratio.max(0.0).min(1.0);
+    let target_index_count = ((mesh_data.indices.len() as f32 * target_ratio) as usize / 3) * 3;
+
+    let adapter = meshopt::VertexDataAdapter::new(
+        vertex_bytes(&mesh_data.vertices),
+        mem::size_of::<Vertex>(),
+        // position is the first field of the #[repr(C)] Vertex.
+        0,
+    )
+    .expect("Failed to create meshopt vertex adapter.");
+
+    let indices = meshopt::simplify(
+        &mesh_data.indices,
+        &adapter,
+        target_index_count,
+        target_error,
+    );
+
+    MeshData::new(mesh_data.vertices.clone(), indices)
+}
@@ -0,0 +1,196 @@
+use crate::{
+    core::math::{Vec3, Vec4},
+    geometry::MeshData,
+};
+use nalgebra_glm as glm;
+
+// Per vertex tangent generation following the MikkTSpace conventions: tangents point along +u,
+// are orthogonal to the normal and tangent.w stores the handedness so that
+// bitangent = cross(normal, tangent.xyz) * tangent.w.
+// Face contributions are projected onto the tangent plane of each vertex and weighted by the
+// corner angle, which matches MikkTSpace for meshes that don't split vertices on UV seams.
+pub fn generate_tangents(mesh_data: &mut MeshData) {
+    let vertex_count = mesh_data.vertices.len();
+    let mut tangents = vec![Vec3::zeros(); vertex_count];
+    let mut bitangents = vec![Vec3::zeros(); vertex_count];
+
+    for triangle in mesh_data.indices.chunks_exact(3) {
+        let corners = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+
+        let position = |i: usize| mesh_data.vertices[corners[i]].position;
+        let uv = |i: usize| mesh_data.vertices[corners[i]].tex_coord;
+
+        let e1 = position(1) - position(0);
+        let e2 = position(2) - position(0);
+        let (du1, dv1) = (uv(1).x - uv(0).x, uv(1).y - uv(0).y);
+        let (du2, dv2) = (uv(2).x - uv(0).x, uv(2).y - uv(0).y);
+
+        let determinant = du1 * dv2 - du2 * dv1;
+
+        // Degenerate UVs carry no tangent information.
+        if determinant.abs() <= std::f32::EPSILON {
+            continue;
+        }
+
+        let r = 1.0 / determinant;
+        let face_tangent = (e1 * dv2 - e2 * dv1) * r;
+        let face_bitangent = (e2 * du1 - e1 * du2) * r;
+
+        for i in 0..3 {
+            let vertex = corners[i];
+            let normal = normalize(&mesh_data.vertices[vertex].normal);
+
+            let to_next = position((i + 1) % 3) - position(i);
+            let to_previous = position((i + 2) % 3) - position(i);
+            let angle = glm::dot(&normalize(&to_next), &normalize(&to_previous))
+                .max(-1.0)
+                .min(1.0)
+                .acos();
+
+            tangents[vertex] += normalize(&project_on_plane(&face_tangent, &normal)) * angle;
+            bitangents[vertex] += normalize(&project_on_plane(&face_bitangent, &normal)) * angle;
+        }
+    }
+
+    for (i, vertex) in mesh_data.vertices.iter_mut().enumerate() {
+        let normal = normalize(&vertex.normal);
+
+        let mut tangent = normalize(&project_on_plane(&tangents[i], &normal));
+
+        // No usable UVs touch this vertex. Any vector on the tangent plane will do.
+        if tangent == Vec3::zeros() {
+            let axis = if normal.x.abs() < 0.9 {
+                Vec3::new(1.0, 0.0, 0.0)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+            tangent = normalize(&project_on_plane(&axis, &normal));
+        }
+
+        let handedness = if glm::dot(&glm::cross(&normal, &tangent), &bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        vertex.tangent = Vec4::new(tangent.x, tangent.y, tangent.z, handedness);
+    }
+}
+
+fn project_on_plane(v: &Vec3, normal: &Vec3) -> Vec3 {
+    v - normal * glm::dot(v, normal)
+}
+
+// glm::normalize divides by zero for zero length input. This returns the zero vector instead.
+fn normalize(v: &Vec3) -> Vec3 {
+    if glm::length(v) <= std::f32::EPSILON {
+        return Vec3::zeros();
+    }
+
+    glm::normalize(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_tangents;
+    use crate::{
+        core::math::{Vec2, Vec3, Vec4},
+        geometry::{shapes, MeshData},
+        rendering::mesh::Vertex,
+    };
+    use nalgebra_glm as glm;
+
+    // The shapes come with analytic tangents that follow the same conventions.
+    fn assert_regenerates_tangents(mesh_data: MeshData) {
+        let mut generated = mesh_data.clone();
+        generate_tangents(&mut generated);
+
+        for (expected, vertex) in mesh_data.vertices.iter().zip(&generated.vertices) {
+            assert!(glm::dot(&expected.tangent.xyz(), &vertex.tangent.xyz()) > 0.99);
+            assert_eq!(vertex.tangent.w, expected.tangent.w);
+        }
+    }
+
+    fn triangle(uvs: [Vec2; 3]) -> MeshData {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+
+        let vertices = positions
+            .iter()
+            .zip(uvs.iter())
+            .map(|(&position, &tex_coord)| Vertex {
+                position,
+                normal: Vec3::z(),
+                tangent: Vec4::zeros(),
+                tex_coord,
+                color: Vec4::zeros(),
+                tex_coord1: tex_coord,
+            })
+            .collect();
+
+        MeshData::new(vertices, vec![0, 1, 2])
+    }
+
+    #[test]
+    fn regenerates_the_tangents_of_shapes() {
+        assert_regenerates_tangents(shapes::cube(1.0));
+        assert_regenerates_tangents(shapes::plane(2.0, 2.0, 4));
+        assert_regenerates_tangents(shapes::torus(2.0, 0.5, 32, 16));
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut mesh_data = triangle([
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        ]);
+        generate_tangents(&mut mesh_data);
+
+        for vertex in &mesh_data.vertices {
+            assert!((vertex.tangent.xyz() - Vec3::y()).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_the_handedness() {
+        let mut mesh_data = triangle([
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+        ]);
+        let mut mirrored = triangle([
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+        ]);
+
+        generate_tangents(&mut mesh_data);
+        generate_tangents(&mut mirrored);
+
+        for (vertex, mirrored) in mesh_data.vertices.iter().zip(&mirrored.vertices) {
+            assert_eq!(vertex.tangent.w, 1.0);
+            assert_eq!(mirrored.tangent.w, -1.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_still_give_a_tangent_on_the_plane() {
+        let mut mesh_data = triangle([Vec2::zeros(); 3]);
+        generate_tangents(&mut mesh_data);
+
+        for vertex in &mesh_data.vertices {
+            let tangent = vertex.tangent.xyz();
+
+            assert!((glm::length(&tangent) - 1.0).abs() < 1e-5);
+            assert!(glm::dot(&tangent, &vertex.normal).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    core::asset::{AssetManager, Handle},
+    imgui::{drag_drop, im_str, ComboBox, ImString, Image, Selectable, Ui},
+    rendering::texture::Texture2D,
+};
+use gl::types::GLuint;
+use gl_bindings as gl;
+use image::GenericImageView;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+const THUMBNAIL_SIZE: f32 = 64.0;
+// The face of the cube maps shown as their thumbnail, +Z.
+const CUBE_MAP_THUMBNAIL_FACE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AssetKind {
+    Texture,
+    CubeMap,
+    Mesh,
+    Shader,
+    Material,
+}
+
+struct Entry<'a> {
+    kind: AssetKind,
+    path: &'a Path,
+    // GL name of the thumbnail, if the asset has one.
+    thumbnail: Option<GLuint>,
+    // Width over height of the thumbnail.
+    aspect: f32,
+    texture: Option<&'a Handle<Texture2D>>,
+}
+
+// Lists the assets loaded through the AssetManager with thumbnails of the textures and cube maps,
+// searchable by path and filterable by type. Textures can be dragged onto texture slots, e.g. the
+// maps of a material.
+pub struct AssetBrowser {
+    search: ImString,
+    // Index into the kinds, after All.
+    filter: usize,
+    // Mip levels of the cube maps, copied the first time they are shown. Kept with the GL name of
+    // the cube map they were copied from, so reloaded cube maps are copied again.
+    cube_map_thumbnails: HashMap<PathBuf, (GLuint, Texture2D)>,
+}
+
+impl AssetBrowser {
+    pub fn new() -> Self {
+        Self {
+            search: ImString::with_capacity(64),
+            filter: 0,
+            cube_map_thumbnails: HashMap::new(),
+        }
+    }
+
+    pub fn gui(&mut self, ui: &Ui, asset_manager: &AssetManager) {
+        ui.input_text(im_str!("Search"), &mut self.search).build();
+        ComboBox::new(im_str!("Type")).build_simple_string(
+            ui,
+            &mut self.filter,
+            &[
+                im_str!("All"),
+                im_str!("Textures"),
+                im_str!("Cube Maps"),
+                im_str!("Meshes"),
+                im_str!("Shaders"),
+                im_str!("Materials"),
+            ],
+        );
+        ui.separator();
+
+        self.update_cube_map_thumbnails(asset_manager);
+
+        let search = self.search.to_str().to_lowercase();
+        let kind = match self.filter {
+            1 => Some(AssetKind::Texture),
+            2 => Some(AssetKind::CubeMap),
+            3 => Some(AssetKind::Mesh),
+            4 => Some(AssetKind::Shader),
+            5 => Some(AssetKind::Material),
+            _ => None,
+        };
+
+        let mut entries = self
+            .entries(asset_manager)
+            .into_iter()
+            .filter(|entry| kind.map_or(true, |kind| entry.kind == kind))
+            .filter(|entry| {
+                entry
+                    .path
+                    .to_string_lossy()
+                    .to_lowercase()
+                    .contains(&search)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.path.cmp(b.path));
+
+        if entries.is_empty() {
+            ui.text_disabled("No assets found");
+        }
+
+        for (i, entry) in entries.into_iter().enumerate() {
+            let label = entry.path.display().to_string();
+
+            match entry.thumbnail {
+                Some(thumbnail) => {
+                    // Fitted into the square, keeping the aspect ratio.
+                    let size = if entry.aspect >= 1.0 {
+                        [THUMBNAIL_SIZE, THUMBNAIL_SIZE / entry.aspect]
+                    } else {
+                        [THUMBNAIL_SIZE * entry.aspect, THUMBNAIL_SIZE]
+                    };
+                    Image::new((thumbnail as usize).into(), size).build(ui)
+                }
+                None => ui.dummy([THUMBNAIL_SIZE, THUMBNAIL_SIZE]),
+            }
+
+            ui.same_line(0.0);
+            ui.group(|| {
+                Selectable::new(&ImString::new(format!("{}##asset{}", label, i))).build(ui);
+                if let Some(texture) = entry.texture {
+                    drag_drop::texture_source(ui, texture, &label);
+                }
+                ui.text_disabled(kind_name(entry.kind));
+            });
+        }
+    }
+
+    fn update_cube_map_thumbnails(&mut self, asset_manager: &AssetManager) {
+        let mut loaded = HashMap::new();
+
+        for (path, cube_map) in asset_manager.texture_cubes() {
+            let thumbnail = match self.cube_map_thumbnails.remove(path) {
+                Some((id, thumbnail)) if id == cube_map.get_id() => thumbnail,
+                _ => cube_map.face_thumbnail(CUBE_MAP_THUMBNAIL_FACE, THUMBNAIL_SIZE as u32),
+            };
+
+            loaded.insert(path.to_path_buf(), (cube_map.get_id(), thumbnail));
+        }
+
+        // The thumbnails of unloaded cube maps are dropped with the old map.
+        self.cube_map_thumbnails = loaded;
+    }
+
+    fn entries<'a>(&'a self, asset_manager: &'a AssetManager) -> Vec<Entry<'a>> {
+        let entry = |kind: AssetKind, path: &'a Path| Entry {
+            kind,
+            path,
+            thumbnail: None,
+            aspect: 1.0,
+            texture: None,
+        };
+
+        let textures = asset_manager.textures().map(|(path, texture)| {
+            let image = texture.get_image();
+            Entry {
+                thumbnail: Some(texture.get_id()),
+                aspect: if image.height() > 0 {
+                    image.width() as f32 / image.height() as f32
+                } else {
+                    1.0
+                },
+                texture: Some(texture),
+                ..entry(AssetKind::Texture, path)
+            }
+        });
+
+        let cube_maps = self
+            .cube_map_thumbnails
+            .iter()
+            .map(|(path, (_, thumbnail))| Entry {
+                thumbnail: Some(thumbnail.get_id()),
+                ..entry(AssetKind::CubeMap, path.as_path())
+            });
+
+        textures
+            .chain(cube_maps)
+            .chain(
+                asset_manager
+                    .meshes()
+                    .map(|(path, _)| entry(AssetKind::Mesh, path)),
+            )
+            .chain(
+                asset_manager
+                    .shaders()
+                    .map(|(path, _)| entry(AssetKind::Shader, path)),
+            )
+            .chain(
+                asset_manager
+                    .material_templates()
+                    .map(|(path, _)| entry(AssetKind::Material, path)),
+            )
+            .collect()
+    }
+}
+
+impl Default for AssetBrowser {
+    fn default() -> Self {
+        AssetBrowser::new()
+    }
+}
+
+fn kind_name(kind: AssetKind) -> &'static str {
+    match kind {
+        AssetKind::Texture => "Texture",
+        AssetKind::CubeMap => "Cube Map",
+        AssetKind::Mesh => "Mesh",
+        AssetKind::Shader => "Shader",
+        AssetKind::Material => "Material",
+    }
+}
@@ -0,0 +1,30 @@
+use copypasta::{ClipboardContext, ClipboardProvider};
+use imgui::{ClipboardBackend, ImStr, ImString};
+
+// The system clipboard, for copying and pasting in the text fields of the UI.
+pub(crate) struct Clipboard(ClipboardContext);
+
+impl Clipboard {
+    // None where the clipboard is not available, e.g. without a display server.
+    pub(crate) fn new() -> Option<Self> {
+        match ClipboardContext::new() {
+            Ok(context) => Some(Self(context)),
+            Err(e) => {
+                log::warn!("The clipboard is not available: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl ClipboardBackend for Clipboard {
+    fn get(&mut self) -> Option<ImString> {
+        self.0.get_contents().ok().map(ImString::new)
+    }
+
+    fn set(&mut self, value: &ImStr) {
+        if let Err(e) = self.0.set_contents(value.to_str().to_owned()) {
+            log::warn!("Failed to copy to the clipboard: {}", e)
+        }
+    }
+}
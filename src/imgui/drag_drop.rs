@@ -0,0 +1,48 @@
+use crate::{
+    core::asset::Handle,
+    imgui::{im_str, DragDropFlags, DragDropSource, DragDropTarget, ImStr, Ui},
+    rendering::texture::Texture2D,
+};
+use std::cell::RefCell;
+
+// Tooltip preview size of a dragged texture, in pixels.
+const PREVIEW_SIZE: f32 = 64.0;
+
+thread_local! {
+    // Dear ImGui copies payloads as plain bytes, so the handle of the dragged texture waits here.
+    static DRAGGED_TEXTURE: RefCell<Option<Handle<Texture2D>>> = RefCell::new(None);
+}
+
+fn texture_payload() -> &'static ImStr {
+    im_str!("TEXTURE_2D")
+}
+
+// Makes the last item a drag source of the texture, e.g. an entry of the asset browser.
+pub fn texture_source(ui: &Ui, texture: &Handle<Texture2D>, label: &str) {
+    if let Some(tooltip) = DragDropSource::new(texture_payload()).begin(ui) {
+        DRAGGED_TEXTURE.with(|dragged| *dragged.borrow_mut() = Some(texture.clone()));
+
+        imgui::Image::new(
+            (texture.get_id() as usize).into(),
+            [PREVIEW_SIZE, PREVIEW_SIZE],
+        )
+        .build(ui);
+        ui.text(label);
+
+        tooltip.end();
+    }
+}
+
+// Makes the last item a drop target for textures, e.g. a texture slot of a material. Returns the
+// texture dropped on it this frame.
+pub fn texture_target(ui: &Ui) -> Option<Handle<Texture2D>> {
+    let target = DragDropTarget::new(ui)?;
+
+    let dropped = target
+        .accept_payload_empty(texture_payload(), DragDropFlags::empty())
+        .and_then(|_| DRAGGED_TEXTURE.with(|dragged| dragged.borrow_mut().take()));
+
+    target.pop();
+
+    dropped
+}
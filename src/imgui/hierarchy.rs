@@ -0,0 +1,120 @@
+use crate::{
+    core::ecs::{
+        components::{Name, Parent, Transform},
+        Entity, World,
+    },
+    imgui::{im_str, ImString, MouseButton, TreeNode, Ui},
+};
+use std::collections::BTreeMap;
+
+// Tree of the entities with a Transform, nested by their Parent, with a single selection. The
+// filter lists the entities whose name contains it instead of the tree.
+pub struct SceneHierarchy {
+    selected: Option<Entity>,
+    filter: ImString,
+}
+
+impl SceneHierarchy {
+    pub fn new() -> Self {
+        Self {
+            selected: None,
+            filter: ImString::with_capacity(64),
+        }
+    }
+
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, selected: Option<Entity>) {
+        self.selected = selected
+    }
+
+    pub fn gui(&mut self, ui: &Ui, world: &World) {
+        if self
+            .selected
+            .map_or(false, |entity| !world.is_alive(entity))
+        {
+            self.selected = None
+        }
+
+        ui.input_text(im_str!("Filter"), &mut self.filter).build();
+        ui.separator();
+
+        let filter = self.filter.to_str().to_lowercase();
+
+        if !filter.is_empty() {
+            for (entity, _) in world.query::<Transform>() {
+                if name(world, entity).to_lowercase().contains(&filter) {
+                    self.node(ui, world, entity, &BTreeMap::new());
+                }
+            }
+
+            return;
+        }
+
+        let mut roots = vec![];
+        let mut children: BTreeMap<Entity, Vec<Entity>> = BTreeMap::new();
+
+        for (entity, _) in world.query::<Transform>() {
+            match world.get::<Parent>(entity) {
+                Some(Parent(parent)) if world.has::<Transform>(*parent) => {
+                    children.entry(*parent).or_default().push(entity)
+                }
+                _ => roots.push(entity),
+            }
+        }
+
+        roots.sort();
+        for siblings in children.values_mut() {
+            siblings.sort();
+        }
+
+        for root in roots {
+            self.node(ui, world, root, &children);
+        }
+    }
+
+    fn node(
+        &mut self,
+        ui: &Ui,
+        world: &World,
+        entity: Entity,
+        children: &BTreeMap<Entity, Vec<Entity>>,
+    ) {
+        let entity_children = children.get(&entity).map_or(&[][..], Vec::as_slice);
+        let label = ImString::new(format!("{}##{}", name(world, entity), entity.index()));
+
+        let token = TreeNode::new(&label)
+            .leaf(entity_children.is_empty())
+            .selected(self.selected == Some(entity))
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .push(ui);
+
+        if ui.is_item_clicked(MouseButton::Left) {
+            self.selected = Some(entity)
+        }
+
+        if let Some(token) = token {
+            for &child in entity_children {
+                self.node(ui, world, child, children);
+            }
+
+            token.pop(ui);
+        }
+    }
+}
+
+impl Default for SceneHierarchy {
+    fn default() -> Self {
+        SceneHierarchy::new()
+    }
+}
+
+pub(crate) fn name(world: &World, entity: Entity) -> String {
+    world.get::<Name>(entity).map_or_else(
+        || format!("Entity {}", entity.index()),
+        |name| name.0.clone(),
+    )
+}
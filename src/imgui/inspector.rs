@@ -0,0 +1,76 @@
+use crate::{
+    core::ecs::{
+        components::{Light, MeshRenderer, NodeAnimator, Transform},
+        Entity, World,
+    },
+    imgui::{hierarchy, CollapsingHeader, Gui, ImStr, ImString, Ui},
+};
+
+struct ComponentPanel {
+    name: ImString,
+    // Draws the component under a header named after it, if the entity has one.
+    gui: fn(&mut World, Entity, &Ui, &ImStr),
+}
+
+// The components of an entity through their Gui impls, one collapsing header each. Only
+// registered component types are shown, in the order they were registered.
+pub struct Inspector {
+    panels: Vec<ComponentPanel>,
+}
+
+impl Inspector {
+    // With the engine components that have a Gui.
+    pub fn new() -> Self {
+        Self { panels: vec![] }
+            .with_component::<Transform>("Transform")
+            .with_component::<MeshRenderer>("Mesh Renderer")
+            .with_component::<Light>("Light")
+            .with_component::<NodeAnimator>("Node Animator")
+    }
+
+    pub fn with_component<T: Gui + 'static>(mut self, name: &str) -> Self {
+        self.panels.push(ComponentPanel {
+            name: ImString::new(name),
+            gui: component_gui::<T>,
+        });
+        self
+    }
+
+    pub fn gui(&self, ui: &Ui, world: &mut World, entity: Option<Entity>) {
+        let entity = match entity {
+            Some(entity) if world.is_alive(entity) => entity,
+            _ => {
+                ui.text_disabled("Nothing selected");
+                return;
+            }
+        };
+
+        ui.text(hierarchy::name(world, entity));
+        ui.separator();
+
+        for panel in &self.panels {
+            let id = ui.push_id(panel.name.to_str());
+            (panel.gui)(world, entity, ui, &panel.name);
+            id.pop(ui);
+        }
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Inspector::new()
+    }
+}
+
+fn component_gui<T: Gui + 'static>(world: &mut World, entity: Entity, ui: &Ui, name: &ImStr) {
+    if let Some(component) = world.get_mut::<T>(entity) {
+        if CollapsingHeader::new(name)
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            component.gui(ui);
+        }
+    }
+}
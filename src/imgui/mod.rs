@@ -1,32 +1,121 @@
-use glutin::window::Window;
+pub mod asset_browser;
+mod clipboard;
+pub mod drag_drop;
+pub mod hierarchy;
+pub mod inspector;
+pub mod widgets;
+
+use self::clipboard::Clipboard;
+use glutin::{dpi::LogicalPosition, window::Window};
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
+use std::{cell::Cell, os::raw::c_int, path::Path};
 
 pub use ::imgui::*;
 
+// In logical units.
+const FONT_SIZE: f64 = 13.0;
+
+thread_local! {
+    // Where the focused text field wants the composition window of the input method, in logical
+    // units. Set by Dear ImGui at the end of a frame when it moves.
+    static IME_POSITION: Cell<Option<[f32; 2]>> = Cell::new(None);
+}
+
+extern "C" fn set_ime_position(x: c_int, y: c_int) {
+    IME_POSITION.with(|position| position.set(Some([x as f32, y as f32])))
+}
+
 pub(crate) struct ImGui {
     pub(crate) context: imgui::Context,
     pub(crate) platform: imgui_winit_support::WinitPlatform,
-    pub(crate) renderer: imgui_opengl_renderer::Renderer,
+    // None while the GL context is lost.
+    pub(crate) renderer: Option<imgui_opengl_renderer::Renderer>,
 }
 
 impl ImGui {
-    pub(crate) fn new<F>(window: &Window, load_fn: F) -> Self
+    // The window layout is read from and saved to the layout path, if any.
+    pub(crate) fn new<F>(window: &Window, layout_path: Option<&Path>, load_fn: F) -> Self
     where
         F: FnMut(&'static str) -> *const ::std::os::raw::c_void,
     {
         let mut context = imgui::Context::create();
-        context.set_ini_filename(None);
+        context.set_ini_filename(layout_path.map(Path::to_path_buf));
+
+        if let Some(clipboard) = Clipboard::new() {
+            context.set_clipboard_backend(Box::new(clipboard));
+        }
+
+        // Replaces the Win32 default of Dear ImGui, winit places the window on every platform.
+        unsafe { (*sys::igGetIO()).ImeSetInputScreenPosFn = Some(set_ime_position) }
+
         let mut platform = WinitPlatform::init(&mut context);
         platform.attach_window(context.io_mut(), window, HiDpiMode::Default);
+        Self::add_fonts(&mut context, platform.hidpi_factor());
 
         let renderer = imgui_opengl_renderer::Renderer::new(&mut context, load_fn);
 
         Self {
             context,
             platform,
-            renderer,
+            renderer: Some(renderer),
         }
     }
+
+    // Rasterizes the font for the new scale factor of the window, so text stays sharp on high DPI
+    // monitors. The platform has to have handled the ScaleFactorChanged event first.
+    pub(crate) fn rescale<F>(&mut self, load_fn: F)
+    where
+        F: FnMut(&'static str) -> *const ::std::os::raw::c_void,
+    {
+        Self::add_fonts(&mut self.context, self.platform.hidpi_factor());
+        // The renderer uploads the font atlas when created. The old one is deleted first, in the
+        // context it was created in.
+        self.renderer = None;
+        self.renderer = Some(imgui_opengl_renderer::Renderer::new(
+            &mut self.context,
+            load_fn,
+        ));
+    }
+
+    // Releases the renderer with the lost context current.
+    pub(crate) fn context_lost(&mut self) {
+        self.renderer = None
+    }
+
+    // Renders into the window that replaced the one of the lost context.
+    pub(crate) fn restore_context<F>(&mut self, window: &Window, load_fn: F)
+    where
+        F: FnMut(&'static str) -> *const ::std::os::raw::c_void,
+    {
+        self.platform
+            .attach_window(self.context.io_mut(), window, HiDpiMode::Default);
+        self.rescale(load_fn)
+    }
+
+    // Moves the composition window of the input method, e.g. for CJK input, next to the text
+    // cursor of the focused field. Once a frame, after the UI rendered. The composed text arrives
+    // as ReceivedCharacter events the platform forwards.
+    pub(crate) fn update_ime_position(&self, window: &Window) {
+        if let Some([x, y]) = IME_POSITION.with(Cell::take) {
+            window.set_ime_position(LogicalPosition::new(x, y))
+        }
+    }
+
+    // At the physical size, scaled back down to the logical one.
+    fn add_fonts(context: &mut imgui::Context, scale_factor: f64) {
+        {
+            let mut fonts = context.fonts();
+            fonts.clear_fonts();
+            fonts.add_font(&[FontSource::DefaultFontData {
+                config: Some(FontConfig {
+                    size_pixels: (FONT_SIZE * scale_factor) as f32,
+                    ..FontConfig::default()
+                }),
+            }]);
+        }
+
+        context.io_mut().font_global_scale = (1.0 / scale_factor) as f32;
+    }
 }
 
 pub trait Gui {
@@ -0,0 +1,280 @@
+use crate::{
+    core::{
+        animation::curve::{Curve, Gradient},
+        math::lerp_scalar,
+    },
+    imgui::{im_str, ColorPicker, ImStr, MouseButton, Ui},
+};
+use std::{cell::Cell, iter, ops::RangeInclusive};
+
+const CURVE_HEIGHT: f32 = 100.0;
+const GRADIENT_HEIGHT: f32 = 20.0;
+const KEY_RADIUS: f32 = 4.0;
+// Half the width of the markers of the gradient stops.
+const MARKER_SIZE: f32 = 6.0;
+// How close to a key a click has to be to pick it, in pixels.
+const PICK_DISTANCE: f32 = 6.0;
+
+const BACKGROUND_COLOR: [f32; 4] = [0.1, 0.1, 0.12, 1.0];
+const GRID_COLOR: [f32; 4] = [0.3, 0.3, 0.32, 1.0];
+const LINE_COLOR: [f32; 4] = [0.9, 0.7, 0.2, 1.0];
+const KEY_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const HIGHLIGHT_COLOR: [f32; 4] = [0.3, 0.7, 1.0, 1.0];
+
+thread_local! {
+    // The key or stop dragged in the active editor. Dear ImGui has a single active item, so one
+    // slot serves all of them.
+    static DRAGGED_KEY: Cell<Option<usize>> = Cell::new(None);
+    // The stop of the open color popup of a gradient editor.
+    static EDITED_STOP: Cell<Option<usize>> = Cell::new(None);
+}
+
+// Edits the curve as a graph over the width of the window, with the range on the vertical axis.
+// Click to add a key, drag keys to move them and right click them to remove them. Returns whether
+// the curve changed.
+pub fn curve_editor(ui: &Ui, label: &ImStr, curve: &mut Curve, range: RangeInclusive<f32>) -> bool {
+    let (min, max) = (*range.start(), *range.end());
+
+    ui.text(visible_label(label));
+
+    let origin = ui.cursor_screen_pos();
+    let size = [ui.content_region_avail()[0], CURVE_HEIGHT];
+
+    let to_screen = |time: f32, value: f32| {
+        [
+            origin[0] + time * size[0],
+            origin[1] + (1.0 - (value - min) / (max - min)) * size[1],
+        ]
+    };
+    let from_screen = |position: [f32; 2]| {
+        let time = (position[0] - origin[0]) / size[0];
+        let value = 1.0 - (position[1] - origin[1]) / size[1];
+        (time, lerp_scalar(min, max, value.max(0.0).min(1.0)))
+    };
+
+    ui.invisible_button(label, size);
+
+    let mouse = ui.io().mouse_pos;
+    let hovered_key = curve.keys().iter().position(|key| {
+        let position = to_screen(key.time, key.value);
+        (position[0] - mouse[0]).hypot(position[1] - mouse[1]) <= PICK_DISTANCE
+    });
+
+    let mut changed = false;
+
+    if ui.is_item_hovered() && ui.is_mouse_clicked(MouseButton::Right) {
+        if let Some(index) = hovered_key {
+            curve.remove(index);
+            changed = true
+        }
+    } else if ui.is_item_activated() {
+        let index = hovered_key.unwrap_or_else(|| {
+            let (time, value) = from_screen(mouse);
+            changed = true;
+            curve.insert(time, value)
+        });
+        DRAGGED_KEY.with(|key| key.set(Some(index)))
+    } else if ui.is_item_active() && ui.io().mouse_delta != [0.0, 0.0] {
+        let dragged = DRAGGED_KEY.with(Cell::get);
+
+        // The key may have been removed with a right click while dragged.
+        if let Some(index) = dragged.filter(|&i| i < curve.keys().len()) {
+            let (time, value) = from_screen(mouse);
+            curve.set_key(index, time, value);
+            changed = true
+        }
+    }
+
+    let dragged_key = if ui.is_item_active() {
+        DRAGGED_KEY.with(Cell::get)
+    } else {
+        None
+    };
+
+    if let Some(key) = dragged_key
+        .or(hovered_key)
+        .and_then(|i| curve.keys().get(i))
+    {
+        ui.tooltip_text(format!("{:.2}: {:.3}", key.time, key.value))
+    }
+
+    let draw_list = ui.get_window_draw_list();
+    let end = [origin[0] + size[0], origin[1] + size[1]];
+
+    draw_list
+        .add_rect(origin, end, BACKGROUND_COLOR)
+        .filled(true)
+        .build();
+
+    for i in 1..4 {
+        let x = origin[0] + size[0] * i as f32 / 4.0;
+        let y = origin[1] + size[1] * i as f32 / 4.0;
+        draw_list
+            .add_line([x, origin[1]], [x, end[1]], GRID_COLOR)
+            .build();
+        draw_list
+            .add_line([origin[0], y], [end[0], y], GRID_COLOR)
+            .build();
+    }
+
+    // The first and last values hold before and after the keys.
+    let keys = curve.keys();
+    let (first, last) = (keys[0], keys[keys.len() - 1]);
+    let points = iter::once(to_screen(0.0, first.value))
+        .chain(keys.iter().map(|key| to_screen(key.time, key.value)))
+        .chain(iter::once(to_screen(1.0, last.value)))
+        .collect::<Vec<_>>();
+
+    for segment in points.windows(2) {
+        draw_list
+            .add_line(segment[0], segment[1], LINE_COLOR)
+            .thickness(2.0)
+            .build();
+    }
+
+    for (i, key) in keys.iter().enumerate() {
+        let color = if Some(i) == dragged_key.or(hovered_key) {
+            HIGHLIGHT_COLOR
+        } else {
+            KEY_COLOR
+        };
+
+        draw_list
+            .add_circle(to_screen(key.time, key.value), KEY_RADIUS, color)
+            .filled(true)
+            .build();
+    }
+
+    changed
+}
+
+// Edits the gradient as a bar over the width of the window with the stops marked under it. Click
+// to add a stop, drag stops to move them, double click them to pick their color and right click
+// them to remove them. Returns whether the gradient changed.
+pub fn gradient_editor(ui: &Ui, label: &ImStr, gradient: &mut Gradient) -> bool {
+    ui.text(visible_label(label));
+
+    let origin = ui.cursor_screen_pos();
+    let size = [
+        ui.content_region_avail()[0],
+        GRADIENT_HEIGHT + MARKER_SIZE * 2.0,
+    ];
+
+    let to_x = |position: f32| origin[0] + position * size[0];
+    let from_x = |x: f32| (x - origin[0]) / size[0];
+
+    ui.invisible_button(label, size);
+
+    let mouse = ui.io().mouse_pos;
+    let hovered_stop = if mouse[1] >= origin[1] + GRADIENT_HEIGHT {
+        gradient
+            .stops()
+            .iter()
+            .position(|stop| (to_x(stop.position) - mouse[0]).abs() <= MARKER_SIZE)
+    } else {
+        None
+    };
+
+    let dragged_stop = if ui.is_item_active() {
+        DRAGGED_KEY.with(Cell::get)
+    } else {
+        None
+    };
+
+    let mut changed = false;
+    // Of the popup, which is looked up relative to the ID stack.
+    let id = ui.push_id(label);
+
+    if ui.is_item_hovered() && ui.is_mouse_clicked(MouseButton::Right) {
+        if let Some(index) = hovered_stop {
+            gradient.remove(index);
+            changed = true
+        }
+    } else if ui.is_item_hovered() && ui.is_mouse_double_clicked(MouseButton::Left) {
+        if hovered_stop.is_some() {
+            EDITED_STOP.with(|stop| stop.set(hovered_stop));
+            ui.open_popup(im_str!("Stop"));
+        }
+    } else if ui.is_item_activated() {
+        let index = hovered_stop.unwrap_or_else(|| {
+            let position = from_x(mouse[0]);
+            changed = true;
+            gradient.insert(position, gradient.sample(position))
+        });
+        DRAGGED_KEY.with(|key| key.set(Some(index)))
+    } else if ui.is_item_active() && ui.io().mouse_delta[0] != 0.0 {
+        let dragged = DRAGGED_KEY.with(Cell::get);
+
+        if let Some(index) = dragged.filter(|&i| i < gradient.stops().len()) {
+            let color = gradient.stops()[index].color;
+            gradient.set_stop(index, from_x(mouse[0]), color);
+            changed = true
+        }
+    }
+
+    ui.popup(im_str!("Stop"), || {
+        let index = EDITED_STOP.with(Cell::get);
+
+        if let Some(mut stop) = index.and_then(|i| gradient.stops().get(i).copied()) {
+            if ColorPicker::new(im_str!("##color"), &mut stop.color).build(ui) {
+                gradient.set_stop(index.unwrap(), stop.position, stop.color);
+                changed = true
+            }
+        }
+    });
+
+    id.pop(ui);
+
+    let draw_list = ui.get_window_draw_list();
+    let stops = gradient.stops();
+    let bottom = origin[1] + GRADIENT_HEIGHT;
+
+    // The first and last colors hold before and after the stops.
+    let bands = iter::once((0.0, stops[0].color))
+        .chain(stops.iter().map(|stop| (stop.position, stop.color)))
+        .chain(iter::once((1.0, stops[stops.len() - 1].color)))
+        .collect::<Vec<_>>();
+
+    for band in bands.windows(2) {
+        let ((start, start_color), (end, end_color)) = (band[0], band[1]);
+        let (start_color, end_color) = (opaque(start_color), opaque(end_color));
+
+        draw_list.add_rect_filled_multicolor(
+            [to_x(start), origin[1]],
+            [to_x(end), bottom],
+            start_color,
+            end_color,
+            end_color,
+            start_color,
+        );
+    }
+
+    for (i, stop) in stops.iter().enumerate() {
+        let x = to_x(stop.position);
+        let tip = [x, bottom];
+        let left = [x - MARKER_SIZE, bottom + MARKER_SIZE * 2.0];
+        let right = [x + MARKER_SIZE, bottom + MARKER_SIZE * 2.0];
+        let outline = if Some(i) == dragged_stop.or(hovered_stop) {
+            HIGHLIGHT_COLOR
+        } else {
+            KEY_COLOR
+        };
+
+        draw_list
+            .add_triangle(tip, left, right, opaque(stop.color))
+            .filled(true)
+            .build();
+        draw_list.add_triangle(tip, left, right, outline).build();
+    }
+
+    changed
+}
+
+// The label up to the ## that hides the rest from the UI.
+fn visible_label(label: &ImStr) -> &str {
+    label.to_str().split("##").next().unwrap_or_default()
+}
+
+fn opaque(color: [f32; 3]) -> [f32; 4] {
+    [color[0], color[1], color[2], 1.0]
+}
@@ -16,7 +16,9 @@ macro_rules! impl_as_any {
 }
 
 pub mod core;
+pub mod geometry;
 pub mod imgui;
+pub mod platform;
 pub mod rendering;
 
 pub use crate::core::*;
@@ -25,9 +27,6 @@ pub use crate::rendering::*;
 #[macro_use]
 extern crate bitflags;
 
-#[macro_use]
-extern crate lazy_static;
-
 pub mod capabilities {
     use crate::shader;
 
@@ -51,4 +50,33 @@ pub mod color {
     pub fn srgb_to_linear3f(color: &Vec3) -> Vec3 {
         pow(&color, &Vec3::new(2.2, 2.2, 2.2))
     }
+
+    // Linear color of a black body at the temperature in Kelvin, brightest channel at 1. After
+    // Tanner Helland's fit, valid from 1000K to 40000K.
+    pub fn temperature_to_linear(kelvin: f32) -> Vec3 {
+        let t = kelvin.max(1000.0).min(40000.0) / 100.0;
+
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698_73 * (t - 60.0).powf(-0.133_204_76)
+        };
+
+        let green = if t <= 66.0 {
+            99.470_8 * t.ln() - 161.119_57
+        } else {
+            288.122_17 * (t - 60.0).powf(-0.075_514_85)
+        };
+
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (t - 10.0).ln() - 305.044_8
+        };
+
+        let srgb = Vec3::new(red, green, blue) / 255.0;
+        srgb_to_linear3f(&srgb.map(|channel| channel.max(0.0).min(1.0)))
+    }
 }
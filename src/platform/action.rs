@@ -0,0 +1,297 @@
+use crate::platform::input::{Axis, Button, Input};
+use glutin::event::{MouseButton, VirtualKeyCode};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+// Axis bindings past it count as held.
+pub(crate) const PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+// An input an action or an axis is bound to. Gamepad bindings read every connected gamepad.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    MouseButton(MouseButton),
+    GamepadButton(Button),
+    // The half of the axis in the direction, from 0 to 1.
+    GamepadAxis(Axis, AxisDirection),
+}
+
+impl Binding {
+    // From 0 to 1, past the deadzones of the gamepads.
+    fn value(&self, input: &Input) -> f32 {
+        match *self {
+            Binding::Key(key) => input.key_down(key) as i32 as f32,
+            Binding::MouseButton(button) => input.mouse_button_down(button) as i32 as f32,
+            Binding::GamepadButton(button) => input
+                .gamepads()
+                .iter()
+                .any(|gamepad| gamepad.button_down(button))
+                as i32 as f32,
+            Binding::GamepadAxis(axis, direction) => input
+                .gamepads()
+                .iter()
+                .map(|gamepad| direction.apply(gamepad.axis(axis)))
+                .fold(0.0, f32::max),
+        }
+    }
+
+    fn pressed(&self, input: &Input) -> bool {
+        match *self {
+            Binding::Key(key) => input.key_pressed(key),
+            Binding::MouseButton(button) => input.mouse_button_pressed(button),
+            Binding::GamepadButton(button) => input
+                .gamepads()
+                .iter()
+                .any(|gamepad| gamepad.button_pressed(button)),
+            Binding::GamepadAxis(axis, direction) => input.gamepads().iter().any(|gamepad| {
+                direction.apply(gamepad.axis(axis)) >= PRESS_THRESHOLD
+                    && direction.apply(gamepad.previous_axis(axis)) < PRESS_THRESHOLD
+            }),
+        }
+    }
+
+    // Axes cross the threshold at most once a frame.
+    fn press_count(&self, input: &Input) -> u32 {
+        match *self {
+            Binding::Key(key) => input.key_press_count(key),
+            Binding::MouseButton(button) => input.mouse_button_press_count(button),
+            Binding::GamepadButton(button) => input
+                .gamepads()
+                .iter()
+                .map(|gamepad| gamepad.button_press_count(button))
+                .sum(),
+            Binding::GamepadAxis(..) => self.pressed(input) as u32,
+        }
+    }
+
+    fn released(&self, input: &Input) -> bool {
+        match *self {
+            Binding::Key(key) => input.key_released(key),
+            Binding::MouseButton(button) => input.mouse_button_released(button),
+            Binding::GamepadButton(button) => input
+                .gamepads()
+                .iter()
+                .any(|gamepad| gamepad.button_released(button)),
+            Binding::GamepadAxis(axis, direction) => input.gamepads().iter().any(|gamepad| {
+                direction.apply(gamepad.axis(axis)) < PRESS_THRESHOLD
+                    && direction.apply(gamepad.previous_axis(axis)) >= PRESS_THRESHOLD
+            }),
+        }
+    }
+}
+
+impl AxisDirection {
+    fn apply(self, value: f32) -> f32 {
+        match self {
+            AxisDirection::Positive => value.max(0.0),
+            AxisDirection::Negative => (-value).max(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisBindings {
+    pub positive: Vec<Binding>,
+    pub negative: Vec<Binding>,
+}
+
+// Named actions and axes bound to the keyboard, the mouse and the gamepads, so the keyboard and
+// a gamepad drive the same code. An action is held while any of its bindings is, an axis is its
+// strongest positive binding minus its strongest negative one. Unknown names are never held.
+// Saved to and loaded from RON bindings files, e.g. examples/pbs/bindings.ron, so players can
+// rebind them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionMap {
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, AxisBindings>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::de::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read bindings {:?}: {}", path.as_ref(), e))?;
+
+        Self::from_ron(&source)
+            .map_err(|e| format!("Failed to parse bindings {:?}: {}", path.as_ref(), e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let source = self.to_ron()?;
+
+        fs::write(path.as_ref(), source)
+            .map_err(|e| format!("Failed to save bindings {:?}: {}", path.as_ref(), e))
+    }
+
+    // Adds the bindings of the other map, e.g. the defaults of the camera controllers under the
+    // bindings of a file. Actions and axes the map already binds are kept as they are.
+    pub fn merge(&mut self, other: &ActionMap) {
+        for (action, bindings) in &other.actions {
+            self.actions
+                .entry(action.clone())
+                .or_insert_with(|| bindings.clone());
+        }
+
+        for (axis, bindings) in &other.axes {
+            self.axes
+                .entry(axis.clone())
+                .or_insert_with(|| bindings.clone());
+        }
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding)
+        }
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, positive: Binding, negative: Binding) {
+        let bindings = self.axes.entry(axis.to_string()).or_default();
+
+        if !bindings.positive.contains(&positive) {
+            bindings.positive.push(positive)
+        }
+
+        if !bindings.negative.contains(&negative) {
+            bindings.negative.push(negative)
+        }
+    }
+
+    // Binds both halves of the gamepad axis.
+    pub fn bind_gamepad_axis(&mut self, axis: &str, gamepad_axis: Axis) {
+        self.bind_axis(
+            axis,
+            Binding::GamepadAxis(gamepad_axis, AxisDirection::Positive),
+            Binding::GamepadAxis(gamepad_axis, AxisDirection::Negative),
+        )
+    }
+
+    // Replaces the bindings of the action.
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_string(), bindings);
+    }
+
+    pub fn set_axis_bindings(&mut self, axis: &str, bindings: AxisBindings) {
+        self.axes.insert(axis.to_string(), bindings);
+    }
+
+    // Replaces the binding of the action or axis, e.g. with the one Input::pressed_binding
+    // captured. Returns false if it was not bound to it.
+    pub fn rebind(&mut self, name: &str, old: Binding, new: Binding) -> bool {
+        let replace = |bindings: &mut Vec<Binding>| {
+            let bound = bindings.contains(&old);
+
+            if bound && old != new {
+                bindings.retain(|b| *b != new);
+                bindings
+                    .iter_mut()
+                    .filter(|b| **b == old)
+                    .for_each(|b| *b = new);
+            }
+
+            bound
+        };
+
+        let action = self.actions.get_mut(name).map_or(false, replace);
+        let axis = self.axes.get_mut(name).map_or(false, |bindings| {
+            replace(&mut bindings.positive) | replace(&mut bindings.negative)
+        });
+
+        action || axis
+    }
+
+    pub fn unbind(&mut self, name: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(name) {
+            bindings.retain(|b| *b != binding)
+        }
+
+        if let Some(bindings) = self.axes.get_mut(name) {
+            bindings.positive.retain(|b| *b != binding);
+            bindings.negative.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = (&str, &[Binding])> {
+        self.actions
+            .iter()
+            .map(|(action, bindings)| (action.as_str(), bindings.as_slice()))
+    }
+
+    pub fn axes(&self) -> impl Iterator<Item = (&str, &AxisBindings)> {
+        self.axes
+            .iter()
+            .map(|(axis, bindings)| (axis.as_str(), bindings))
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> Option<&AxisBindings> {
+        self.axes.get(axis)
+    }
+
+    pub fn down(&self, input: &Input, action: &str) -> bool {
+        self.value(input, action) >= PRESS_THRESHOLD
+    }
+
+    pub fn pressed(&self, input: &Input, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| binding.pressed(input))
+    }
+
+    // How many times the action was pressed since the last frame, e.g. to step once per tap.
+    pub fn press_count(&self, input: &Input, action: &str) -> u32 {
+        self.action_bindings(action)
+            .iter()
+            .map(|binding| binding.press_count(input))
+            .sum()
+    }
+
+    pub fn released(&self, input: &Input, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| binding.released(input))
+    }
+
+    // From 0 to 1, analog for gamepad axes.
+    pub fn value(&self, input: &Input, action: &str) -> f32 {
+        Self::strongest(input, self.action_bindings(action))
+    }
+
+    pub fn axis(&self, input: &Input, axis: &str) -> f32 {
+        self.axes.get(axis).map_or(0.0, |bindings| {
+            Self::strongest(input, &bindings.positive) - Self::strongest(input, &bindings.negative)
+        })
+    }
+
+    fn strongest(input: &Input, bindings: &[Binding]) -> f32 {
+        bindings
+            .iter()
+            .map(|binding| binding.value(input))
+            .fold(0.0, f32::max)
+    }
+}
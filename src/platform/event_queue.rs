@@ -0,0 +1,98 @@
+use crate::core::math::Vec2;
+use crate::platform::input::{Axis, Button};
+use gilrs::GamepadId;
+use glutin::event::{MouseButton, VirtualKeyCode};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEventKind {
+    Key {
+        key: VirtualKeyCode,
+        pressed: bool,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    // In physical pixels, the raw motion of the mouse while the cursor is captured.
+    MouseMotion(Vec2),
+    // In lines.
+    Scroll(Vec2),
+    GamepadButton {
+        gamepad: GamepadId,
+        button: Button,
+        pressed: bool,
+    },
+    // Before the deadzones.
+    GamepadAxis {
+        gamepad: GamepadId,
+        axis: Axis,
+        value: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputEvent {
+    // When the event was received, or when the gamepad reported it.
+    pub time: Instant,
+    pub kind: InputEventKind,
+}
+
+// The input events of the frame in the order they happened, so a frame sees every press and
+// release even when a key is tapped several times between two frames. Only events that changed
+// the input are queued, key repeats and input blocked for the UI are not.
+#[derive(Debug)]
+pub struct EventQueue {
+    events: Vec<InputEvent>,
+    frame_start: Instant,
+    frame_duration: Duration,
+}
+
+impl EventQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            events: vec![],
+            frame_start: Instant::now(),
+            frame_duration: Duration::from_secs(0),
+        }
+    }
+
+    // Gamepad events are polled after the window events that followed them, so events are
+    // inserted by time.
+    pub(crate) fn push(&mut self, time: Instant, kind: InputEventKind) {
+        let index = self
+            .events
+            .iter()
+            .rposition(|event| event.time <= time)
+            .map_or(0, |index| index + 1);
+
+        self.events.insert(index, InputEvent { time, kind })
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    // The events since the time, e.g. the ones after a press to tell a tap from a hold.
+    pub fn since(&self, time: Instant) -> impl Iterator<Item = &InputEvent> {
+        self.events.iter().filter(move |event| event.time >= time)
+    }
+
+    // When the events of the frame started to be queued.
+    pub fn frame_start(&self) -> Instant {
+        self.frame_start
+    }
+
+    // The time the events of the last frame were queued over.
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        let now = Instant::now();
+
+        self.frame_duration = now - self.frame_start;
+        self.frame_start = now;
+        self.events.clear()
+    }
+}
@@ -0,0 +1,789 @@
+use crate::core::math::Vec2;
+use crate::platform::action::{ActionMap, AxisDirection, Binding, PRESS_THRESHOLD};
+use crate::platform::event_queue::{EventQueue, InputEvent, InputEventKind};
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks},
+    EventType, GamepadId, Gilrs,
+};
+use glutin::{
+    dpi::PhysicalPosition,
+    event::{
+        DeviceEvent, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        VirtualKeyCode, WindowEvent,
+    },
+    window::Window,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub use gilrs::{Axis, Button};
+
+// Pixels scrolled per line of the wheel, for touchpads that report pixels.
+const PIXELS_PER_LINE: f32 = 20.0;
+const DEFAULT_STICK_DEADZONE: f32 = 0.15;
+const DEFAULT_TRIGGER_DEADZONE: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+// The buttons of a device held down, with how many times they were pressed and released since
+// the last frame.
+#[derive(Debug)]
+struct ButtonStates<T> {
+    down: HashSet<T>,
+    pressed: HashMap<T, u32>,
+    released: HashMap<T, u32>,
+}
+
+impl<T: Copy + Eq + std::hash::Hash> ButtonStates<T> {
+    fn new() -> Self {
+        Self {
+            down: HashSet::new(),
+            pressed: HashMap::new(),
+            released: HashMap::new(),
+        }
+    }
+
+    // Returns whether the state of the button changed.
+    fn set(&mut self, button: T, down: bool) -> bool {
+        if down {
+            // Key repeat reports held keys again.
+            if self.down.insert(button) {
+                *self.pressed.entry(button).or_default() += 1;
+                return true;
+            }
+        } else if self.down.remove(&button) {
+            *self.released.entry(button).or_default() += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn press_count(&self, button: T) -> u32 {
+        self.pressed.get(&button).copied().unwrap_or(0)
+    }
+
+    // Returns the buttons that were down.
+    fn release_all(&mut self) -> Vec<T> {
+        let released: Vec<T> = self.down.drain().collect();
+
+        for button in &released {
+            *self.released.entry(*button).or_default() += 1;
+        }
+
+        released
+    }
+
+    fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+pub struct GamepadState {
+    id: GamepadId,
+    name: String,
+    force_feedback: bool,
+    buttons: ButtonStates<Button>,
+    axes: HashMap<Axis, f32>,
+    // The axes at the end of the last frame.
+    previous_axes: HashMap<Axis, f32>,
+    stick_deadzone: f32,
+    trigger_deadzone: f32,
+}
+
+impl GamepadState {
+    fn new(gamepad: gilrs::Gamepad, stick_deadzone: f32, trigger_deadzone: f32) -> Self {
+        Self {
+            id: gamepad.id(),
+            name: gamepad.name().to_string(),
+            force_feedback: gamepad.is_ff_supported(),
+            buttons: ButtonStates::new(),
+            axes: HashMap::new(),
+            previous_axes: HashMap::new(),
+            stick_deadzone,
+            trigger_deadzone,
+        }
+    }
+
+    pub fn id(&self) -> GamepadId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Whether the gamepad can rumble.
+    pub fn force_feedback(&self) -> bool {
+        self.force_feedback
+    }
+
+    pub fn button_down(&self, button: Button) -> bool {
+        self.buttons.down.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: Button) -> bool {
+        self.buttons.pressed.contains_key(&button)
+    }
+
+    pub fn button_released(&self, button: Button) -> bool {
+        self.buttons.released.contains_key(&button)
+    }
+
+    // How many times the button was pressed since the last frame.
+    pub fn button_press_count(&self, button: Button) -> u32 {
+        self.buttons.press_count(button)
+    }
+
+    // From -1 to 1 for sticks and 0 to 1 for triggers, 0 for axes the gamepad does not have.
+    // Sticks and triggers are past their deadzone and rescaled to the full range.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.filtered_axis(&self.axes, axis)
+    }
+
+    // The axis at the end of the last frame.
+    pub fn previous_axis(&self, axis: Axis) -> f32 {
+        self.filtered_axis(&self.previous_axes, axis)
+    }
+
+    // How much the axis moved since the last frame.
+    pub fn axis_delta(&self, axis: Axis) -> f32 {
+        self.axis(axis) - self.previous_axis(axis)
+    }
+
+    // The position of the stick with a radial deadzone, so diagonals are not snapped to the axes.
+    pub fn stick(&self, stick: Stick) -> Vec2 {
+        self.filtered_stick(&self.axes, stick)
+    }
+
+    fn filtered_axis(&self, axes: &HashMap<Axis, f32>, axis: Axis) -> f32 {
+        match axis {
+            Axis::LeftStickX => self.filtered_stick(axes, Stick::Left).x,
+            Axis::LeftStickY => self.filtered_stick(axes, Stick::Left).y,
+            Axis::RightStickX => self.filtered_stick(axes, Stick::Right).x,
+            Axis::RightStickY => self.filtered_stick(axes, Stick::Right).y,
+            Axis::LeftZ | Axis::RightZ => {
+                let value = Self::raw_axis(axes, axis);
+
+                if value < self.trigger_deadzone {
+                    0.0
+                } else {
+                    ((value - self.trigger_deadzone) / (1.0 - self.trigger_deadzone)).min(1.0)
+                }
+            }
+            _ => Self::raw_axis(axes, axis),
+        }
+    }
+
+    fn filtered_stick(&self, axes: &HashMap<Axis, f32>, stick: Stick) -> Vec2 {
+        let (x, y) = match stick {
+            Stick::Left => (Axis::LeftStickX, Axis::LeftStickY),
+            Stick::Right => (Axis::RightStickX, Axis::RightStickY),
+        };
+
+        let position = Vec2::new(Self::raw_axis(axes, x), Self::raw_axis(axes, y));
+        let length = position.norm();
+
+        if length <= self.stick_deadzone {
+            return Vec2::new(0.0, 0.0);
+        }
+
+        position / length * ((length.min(1.0) - self.stick_deadzone) / (1.0 - self.stick_deadzone))
+    }
+
+    fn raw_axis(axes: &HashMap<Axis, f32>, axis: Axis) -> f32 {
+        axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+// A snapshot of the keyboard, the mouse and the gamepads, accumulated from the events of a
+// frame. Pressed and released hold for the frame they happened in, until end_frame. The events
+// themselves are queued with their time for the frame, see events.
+pub struct Input {
+    keys: ButtonStates<VirtualKeyCode>,
+    mouse_buttons: ButtonStates<MouseButton>,
+    modifiers: ModifiersState,
+    cursor: Option<Vec2>,
+    mouse_delta: Vec2,
+    scroll: Vec2,
+    focused: bool,
+    mouse_blocked: bool,
+    keyboard_blocked: bool,
+    // Hidden and held in the window, the mouse delta is the raw motion of the mouse.
+    cursor_captured: bool,
+    // Escape or losing the focus releases the capture with the next update_cursor.
+    release_capture: bool,
+    // None where gamepads are not supported.
+    gilrs: Option<Gilrs>,
+    gamepads: Vec<GamepadState>,
+    stick_deadzone: f32,
+    trigger_deadzone: f32,
+    // Effects stop once dropped, kept until they are over.
+    rumbles: Vec<(Instant, Effect)>,
+    actions: ActionMap,
+    events: EventQueue,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepads are not available: {}", e);
+                None
+            }
+        };
+
+        let gamepads = gilrs.as_ref().map_or(vec![], |gilrs| {
+            gilrs
+                .gamepads()
+                .map(|(_, gamepad)| {
+                    GamepadState::new(gamepad, DEFAULT_STICK_DEADZONE, DEFAULT_TRIGGER_DEADZONE)
+                })
+                .collect()
+        });
+
+        Self {
+            keys: ButtonStates::new(),
+            mouse_buttons: ButtonStates::new(),
+            modifiers: ModifiersState::empty(),
+            cursor: None,
+            mouse_delta: Vec2::new(0.0, 0.0),
+            scroll: Vec2::new(0.0, 0.0),
+            focused: true,
+            mouse_blocked: false,
+            keyboard_blocked: false,
+            cursor_captured: false,
+            release_capture: false,
+            gilrs,
+            gamepads,
+            stick_deadzone: DEFAULT_STICK_DEADZONE,
+            trigger_deadzone: DEFAULT_TRIGGER_DEADZONE,
+            rumbles: vec![],
+            actions: ActionMap::new(),
+            events: EventQueue::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let now = Instant::now();
+
+        match event {
+            // Escape gets the cursor back and is not seen by the application, so it does not quit
+            // at the same time.
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Escape),
+                        ..
+                    },
+                ..
+            } if self.cursor_captured => self.release_capture = true,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+
+                if !(pressed && self.keyboard_blocked) && self.keys.set(*key, pressed) {
+                    self.events
+                        .push(now, InputEventKind::Key { key: *key, pressed })
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            // Releases pass while blocked, so no button stays held.
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+
+                if !(pressed && self.mouse_blocked) && self.mouse_buttons.set(*button, pressed) {
+                    self.events.push(
+                        now,
+                        InputEventKind::MouseButton {
+                            button: *button,
+                            pressed,
+                        },
+                    )
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = Vec2::new(position.x as f32, position.y as f32);
+
+                if let Some(cursor) = self.cursor {
+                    if !self.mouse_blocked && !self.cursor_captured {
+                        self.mouse_delta += position - cursor;
+                        self.events
+                            .push(now, InputEventKind::MouseMotion(position - cursor));
+                    }
+                }
+
+                self.cursor = Some(position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { .. } if self.mouse_blocked => {}
+            WindowEvent::MouseWheel { delta, .. } => {
+                let scroll = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        Vec2::new(position.x as f32, position.y as f32) / PIXELS_PER_LINE
+                    }
+                };
+
+                self.scroll += scroll;
+                self.events.push(now, InputEventKind::Scroll(scroll))
+            }
+            // The releases happen in another window.
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+
+                if !focused {
+                    self.release_capture = self.cursor_captured;
+
+                    for key in self.keys.release_all() {
+                        self.events.push(
+                            now,
+                            InputEventKind::Key {
+                                key,
+                                pressed: false,
+                            },
+                        )
+                    }
+
+                    for button in self.mouse_buttons.release_all() {
+                        self.events.push(
+                            now,
+                            InputEventKind::MouseButton {
+                                button,
+                                pressed: false,
+                            },
+                        )
+                    }
+
+                    self.modifiers = ModifiersState::empty();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            if self.cursor_captured && self.focused {
+                let delta = Vec2::new(delta.0 as f32, delta.1 as f32);
+
+                self.mouse_delta += delta;
+                self.events
+                    .push(Instant::now(), InputEventKind::MouseMotion(delta))
+            }
+        }
+    }
+
+    // Hides the cursor and holds it in the window, for looking around with the mouse without
+    // the cursor stopping at the edges of the window. Escape releases it.
+    pub fn set_cursor_captured(&mut self, window: &Window, captured: bool) -> Result<(), String> {
+        window
+            .set_cursor_grab(captured)
+            .map_err(|e| format!("Failed to capture the cursor: {}", e))?;
+        window.set_cursor_visible(!captured);
+
+        self.cursor_captured = captured;
+        self.release_capture = false;
+
+        if captured {
+            Self::center_cursor(window);
+        }
+
+        Ok(())
+    }
+
+    pub fn cursor_captured(&self) -> bool {
+        self.cursor_captured
+    }
+
+    // Once per frame. Releases the captured cursor after Escape or losing the focus, and moves
+    // it back to the middle of the window where grabbing only confines it.
+    pub fn update_cursor(&mut self, window: &Window) {
+        if self.release_capture {
+            if let Err(e) = self.set_cursor_captured(window, false) {
+                log::warn!("{}", e);
+            }
+        }
+
+        if self.cursor_captured {
+            Self::center_cursor(window);
+        }
+    }
+
+    fn center_cursor(window: &Window) {
+        let size = window.inner_size();
+
+        // Not supported everywhere, grabbing already locks the cursor there.
+        window
+            .set_cursor_position(PhysicalPosition::new(size.width / 2, size.height / 2))
+            .ok();
+    }
+
+    // Applies the events of the gamepads since the last call. Once per frame, before reading
+    // the input.
+    pub fn poll_gamepads(&mut self) {
+        let gilrs = match self.gilrs.as_mut() {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+
+        let now = Instant::now();
+        self.rumbles.retain(|(end, _)| *end > now);
+
+        while let Some(gilrs::Event { id, event, time }) = gilrs.next_event() {
+            match event {
+                EventType::Connected => {
+                    log::info!("Gamepad connected: {}", gilrs.gamepad(id).name());
+
+                    if !self.gamepads.iter().any(|gamepad| gamepad.id == id) {
+                        self.gamepads.push(GamepadState::new(
+                            gilrs.gamepad(id),
+                            self.stick_deadzone,
+                            self.trigger_deadzone,
+                        ));
+                    }
+                }
+                EventType::Disconnected => {
+                    log::info!("Gamepad disconnected: {}", gilrs.gamepad(id).name());
+                    self.gamepads.retain(|gamepad| gamepad.id != id);
+                }
+                _ => {}
+            }
+
+            let gamepad = match self.gamepads.iter_mut().find(|gamepad| gamepad.id == id) {
+                Some(gamepad) => gamepad,
+                None => continue,
+            };
+
+            // Gilrs times events with the system clock.
+            let time = SystemTime::now()
+                .duration_since(time)
+                .ok()
+                .and_then(|age| now.checked_sub(age))
+                .unwrap_or(now);
+
+            let axis = match event {
+                EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+                    let pressed = matches!(event, EventType::ButtonPressed(..));
+
+                    if gamepad.buttons.set(button, pressed) {
+                        self.events.push(
+                            time,
+                            InputEventKind::GamepadButton {
+                                gamepad: id,
+                                button,
+                                pressed,
+                            },
+                        )
+                    }
+
+                    None
+                }
+                EventType::AxisChanged(axis, value, _) => Some((axis, value)),
+                // Analog triggers also report as axes.
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    Some((Axis::LeftZ, value))
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    Some((Axis::RightZ, value))
+                }
+                _ => None,
+            };
+
+            if let Some((axis, value)) = axis {
+                gamepad.axes.insert(axis, value);
+                self.events.push(
+                    time,
+                    InputEventKind::GamepadAxis {
+                        gamepad: id,
+                        axis,
+                        value,
+                    },
+                )
+            }
+        }
+    }
+
+    // Clears what happened during the frame. Call after the frame has read the input.
+    pub fn end_frame(&mut self) {
+        self.events.end_frame();
+        self.keys.end_frame();
+        self.mouse_buttons.end_frame();
+        self.mouse_delta = Vec2::new(0.0, 0.0);
+        self.scroll = Vec2::new(0.0, 0.0);
+
+        for gamepad in &mut self.gamepads {
+            gamepad.buttons.end_frame();
+            gamepad.previous_axes.clone_from(&gamepad.axes);
+        }
+    }
+
+    // Set while the cursor is over the UI, to drop the mouse input meant for it.
+    pub fn set_mouse_blocked(&mut self, blocked: bool) {
+        self.mouse_blocked = blocked
+    }
+
+    pub fn mouse_blocked(&self) -> bool {
+        self.mouse_blocked
+    }
+
+    // Set while a text field of the UI has the focus, so typing does not trigger actions.
+    pub fn set_keyboard_blocked(&mut self, blocked: bool) {
+        self.keyboard_blocked = blocked
+    }
+
+    pub fn keyboard_blocked(&self) -> bool {
+        self.keyboard_blocked
+    }
+
+    // Deadzones of the sticks and triggers of every gamepad, from 0 to 1.
+    pub fn set_deadzones(&mut self, stick: f32, trigger: f32) {
+        self.stick_deadzone = stick.max(0.0).min(0.99);
+        self.trigger_deadzone = trigger.max(0.0).min(0.99);
+
+        for gamepad in &mut self.gamepads {
+            gamepad.stick_deadzone = self.stick_deadzone;
+            gamepad.trigger_deadzone = self.trigger_deadzone;
+        }
+    }
+
+    // Rumbles the gamepad with the strong, low frequency, and the weak, high frequency, motors
+    // at magnitudes from 0 to 1 for the duration.
+    pub fn rumble(
+        &mut self,
+        gamepad: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), String> {
+        let gilrs = self
+            .gilrs
+            .as_mut()
+            .ok_or_else(|| "Gamepads are not available.".to_string())?;
+
+        if !self
+            .gamepads
+            .iter()
+            .any(|state| state.id == gamepad && state.force_feedback)
+        {
+            return Err(format!("Gamepad {:?} does not support rumble.", gamepad));
+        }
+
+        let ticks = Ticks::from_ms(duration.as_millis() as u32);
+        let magnitude = |value: f32| (value.max(0.0).min(1.0) * u16::MAX as f32) as u16;
+        let motor = |kind| BaseEffect {
+            kind,
+            scheduling: Replay {
+                play_for: ticks,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let effect = EffectBuilder::new()
+            .add_effect(motor(BaseEffectType::Strong {
+                magnitude: magnitude(strong),
+            }))
+            .add_effect(motor(BaseEffectType::Weak {
+                magnitude: magnitude(weak),
+            }))
+            .repeat(Repeat::For(ticks))
+            .gamepads(&[gamepad])
+            .finish(gilrs)
+            .map_err(|e| format!("Failed to create rumble effect: {}", e))?;
+
+        effect
+            .play()
+            .map_err(|e| format!("Failed to play rumble effect: {}", e))?;
+
+        self.rumbles.push((Instant::now() + duration, effect));
+
+        Ok(())
+    }
+
+    // The bindings of the actions and axes queried below.
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
+    }
+
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions.down(self, action)
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions.pressed(self, action)
+    }
+
+    pub fn action_press_count(&self, action: &str) -> u32 {
+        self.actions.press_count(self, action)
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions.released(self, action)
+    }
+
+    // From -1 to 1.
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.actions.axis(self, axis)
+    }
+
+    // The input events of the frame with their time, in the order they happened.
+    pub fn events(&self) -> &[InputEvent] {
+        self.events.events()
+    }
+
+    pub fn event_queue(&self) -> &EventQueue {
+        &self.events
+    }
+
+    // The time the input of the last frame was gathered over, for turning per-frame deltas into
+    // rates.
+    pub fn frame_duration(&self) -> Duration {
+        self.events.frame_duration()
+    }
+
+    // A binding pressed this frame, for capturing the new binding of an action while rebinding.
+    pub fn pressed_binding(&self) -> Option<Binding> {
+        let key = self
+            .keys
+            .pressed
+            .keys()
+            .next()
+            .map(|&key| Binding::Key(key));
+        let mouse_button = || {
+            self.mouse_buttons
+                .pressed
+                .keys()
+                .next()
+                .map(|&button| Binding::MouseButton(button))
+        };
+        let gamepad_button = || {
+            self.gamepads.iter().find_map(|gamepad| {
+                gamepad
+                    .buttons
+                    .pressed
+                    .keys()
+                    .next()
+                    .map(|&button| Binding::GamepadButton(button))
+            })
+        };
+        let gamepad_axis = || {
+            self.gamepads.iter().find_map(|gamepad| {
+                gamepad.axes.keys().find_map(|&axis| {
+                    let (value, previous) = (gamepad.axis(axis), gamepad.previous_axis(axis));
+
+                    if value.abs() >= PRESS_THRESHOLD && previous.abs() < PRESS_THRESHOLD {
+                        let direction = if value > 0.0 {
+                            AxisDirection::Positive
+                        } else {
+                            AxisDirection::Negative
+                        };
+
+                        Some(Binding::GamepadAxis(axis, direction))
+                    } else {
+                        None
+                    }
+                })
+            })
+        };
+
+        key.or_else(mouse_button)
+            .or_else(gamepad_button)
+            .or_else(gamepad_axis)
+    }
+
+    pub fn key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys.down.contains(&key)
+    }
+
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.pressed.contains_key(&key)
+    }
+
+    pub fn key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys.released.contains_key(&key)
+    }
+
+    // How many times the key was pressed since the last frame, taps shorter than a frame count.
+    pub fn key_press_count(&self, key: VirtualKeyCode) -> u32 {
+        self.keys.press_count(key)
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.down.contains(&button)
+    }
+
+    pub fn mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.pressed.contains_key(&button)
+    }
+
+    pub fn mouse_button_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.released.contains_key(&button)
+    }
+
+    pub fn mouse_button_press_count(&self, button: MouseButton) -> u32 {
+        self.mouse_buttons.press_count(button)
+    }
+
+    // In physical pixels from the top left corner of the window, None outside of it.
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.cursor
+    }
+
+    // Cursor movement since the last frame in physical pixels.
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    // In lines since the last frame, y positive away from the user.
+    pub fn scroll(&self) -> Vec2 {
+        self.scroll
+    }
+
+    pub fn focused(&self) -> bool {
+        self.focused
+    }
+
+    // The connected gamepads in the order they were connected.
+    pub fn gamepads(&self) -> &[GamepadState] {
+        &self.gamepads
+    }
+
+    pub fn gamepad(&self, index: usize) -> Option<&GamepadState> {
+        self.gamepads.get(index)
+    }
+
+    pub fn gamepad_by_id(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.iter().find(|gamepad| gamepad.id == id)
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,117 @@
+pub mod action;
+pub mod event_queue;
+pub mod input;
+pub mod secondary_window;
+pub mod window;
+
+use self::input::Input;
+use self::window::WindowSize;
+use crate::core::{
+    application::Application,
+    scene::{Scene, Transition},
+    Context, Settings,
+};
+use glutin::event::{DeviceEvent, WindowEvent};
+use imgui::Ui;
+
+// An application driven by the event loop of the engine, for samples that need a window, input
+// and a frame loop but not a stack of scenes. The input is up to date with the events of the
+// frame in update and its pressed and released states hold until the next one.
+pub trait App {
+    fn start(&mut self, context: Context) {}
+    fn on_event(&mut self, context: Context, event: &WindowEvent) -> Transition {
+        Transition::None
+    }
+    // The window was resized or moved to a monitor of another scale factor.
+    fn resize(&mut self, context: Context, size: WindowSize) {}
+    fn update(&mut self, context: Context, input: &mut Input) -> Transition {
+        Transition::None
+    }
+    fn render(&mut self, context: Context) {}
+    fn gui(&mut self, ui: &Ui) {}
+}
+
+// Creates the window of the settings and runs the app constructed in it until the window closes
+// or the app quits.
+pub fn run<Cons, A>(settings: Settings, mut app_constructor: Cons)
+where
+    A: App + 'static,
+    Cons: FnMut(Context) -> A + 'static,
+{
+    Application::run(settings, move |context| AppScene {
+        app: app_constructor(context),
+        input: Input::new(),
+    })
+}
+
+// Runs an app as the only scene of the application.
+struct AppScene<A: App> {
+    app: A,
+    input: Input,
+}
+
+impl<A: App> Scene for AppScene<A> {
+    fn start(&mut self, context: Context) {
+        self.app.start(context)
+    }
+
+    fn handle_event(&mut self, context: Context, event: WindowEvent) -> Transition {
+        self.input.handle_event(&event);
+
+        let Context {
+            window,
+            asset_manager,
+            timer,
+            framebuffer_cache,
+            settings,
+        } = context;
+
+        let size = match &event {
+            WindowEvent::Resized(size) => {
+                window.map(|window| WindowSize::new(*size, window.scale_factor()))
+            }
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => Some(WindowSize::new(**new_inner_size, *scale_factor)),
+            _ => None,
+        };
+
+        if let Some(size) = size {
+            self.app.resize(
+                Context::new(window, asset_manager, timer, framebuffer_cache, settings),
+                size,
+            )
+        }
+
+        self.app.on_event(
+            Context::new(window, asset_manager, timer, framebuffer_cache, settings),
+            &event,
+        )
+    }
+
+    fn handle_device_event(&mut self, _: Context, event: &DeviceEvent) {
+        self.input.handle_device_event(event)
+    }
+
+    fn update(&mut self, context: Context) -> Transition {
+        self.input.poll_gamepads();
+
+        if let Some(window) = context.window {
+            self.input.update_cursor(window);
+        }
+
+        let transition = self.app.update(context, &mut self.input);
+        self.input.end_frame();
+
+        transition
+    }
+
+    fn draw(&mut self, context: Context) {
+        self.app.render(context)
+    }
+
+    fn gui(&mut self, ui: &Ui) {
+        self.app.gui(ui)
+    }
+}
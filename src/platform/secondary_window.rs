@@ -0,0 +1,298 @@
+use crate::core::{math::UVec2, Settings};
+use crate::platform::window::{self, ContextSlot, WindowSize};
+use crate::rendering::{render_texture::RenderTexture, state::StateManager};
+use gl::types::*;
+use gl_bindings as gl;
+use glutin::{
+    dpi::LogicalSize,
+    event::WindowEvent,
+    event_loop::EventLoopWindowTarget,
+    window::{WindowBuilder, WindowId},
+};
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+thread_local! {
+    // Every secondary window that has not been dropped, opened or waiting to be.
+    static WINDOWS: RefCell<Vec<Weak<RefCell<WindowState>>>> = RefCell::new(vec![]);
+}
+
+enum Content {
+    Empty,
+    Texture(RenderTexture),
+    Draw(Box<dyn FnMut(WindowSize)>),
+}
+
+struct WindowState {
+    title: String,
+    // Logical.
+    size: UVec2,
+    // None until the next frame opens it and after it closes.
+    context: Option<ContextSlot>,
+    closed: bool,
+    content: Content,
+    // Reads the texture for the blit. Created in the context of the window, framebuffers are
+    // not shared between contexts. Destroyed along with the context instead of deleted in
+    // whatever context is current when the window is dropped.
+    read_framebuffer: GLuint,
+}
+
+// A window besides the main one, e.g. for a material preview or a profiling view. It opens with
+// the next frame and is presented after the main window every frame until closed or dropped.
+// Its GL context shares the textures, buffers, shaders and programs of the main context, so it
+// can show a RenderTexture the main view rendered into, or draw with the same textures and
+// vertex buffers. Vertex arrays, framebuffers and program pipelines are not shared, a draw
+// callback has to create its own.
+pub struct SecondaryWindow {
+    state: Rc<RefCell<WindowState>>,
+}
+
+impl SecondaryWindow {
+    // The size is logical.
+    pub fn new(title: &str, size: UVec2) -> Self {
+        let state = Rc::new(RefCell::new(WindowState {
+            title: title.to_string(),
+            size,
+            context: None,
+            closed: false,
+            content: Content::Empty,
+            read_framebuffer: 0,
+        }));
+
+        WINDOWS.with(|windows| windows.borrow_mut().push(Rc::downgrade(&state)));
+
+        Self { state }
+    }
+
+    // Until the user or close closes it. Windows waiting to open are open.
+    pub fn is_open(&self) -> bool {
+        !self.state.borrow().closed
+    }
+
+    // None until the window opens.
+    pub fn size(&self) -> Option<WindowSize> {
+        self.state
+            .borrow()
+            .context
+            .as_ref()
+            .map(|context| WindowSize::of(context.window()))
+    }
+
+    // Shows the texture scaled to fit the window.
+    pub fn show(&self, texture: &RenderTexture) {
+        self.state.borrow_mut().content = Content::Texture(texture.clone())
+    }
+
+    // Draws into the window every frame with its context current and its framebuffer bound.
+    pub fn set_draw<F>(&self, draw: F)
+    where
+        F: FnMut(WindowSize) + 'static,
+    {
+        self.state.borrow_mut().content = Content::Draw(Box::new(draw))
+    }
+
+    pub fn close(&self) {
+        let mut state = self.state.borrow_mut();
+        state.closed = true;
+        state.context = None
+    }
+}
+
+fn windows() -> Vec<Rc<RefCell<WindowState>>> {
+    WINDOWS.with(|windows| {
+        let mut windows = windows.borrow_mut();
+        windows.retain(|window| window.strong_count() > 0);
+        windows.iter().filter_map(Weak::upgrade).collect()
+    })
+}
+
+// Opens the windows created since the last frame, sharing the objects of the main context.
+pub(crate) fn open_pending(
+    target: &EventLoopWindowTarget<()>,
+    main_context: &ContextSlot,
+    settings: &Settings,
+) {
+    for window in windows() {
+        let mut state = window.borrow_mut();
+
+        if state.closed || state.context.is_some() {
+            continue;
+        }
+
+        let window_builder = WindowBuilder::new()
+            .with_title(&state.title)
+            .with_inner_size(LogicalSize::new(state.size.x, state.size.y));
+
+        // Without vsync, the main window already waits for it.
+        match window::context_builder(settings)
+            .with_shared_lists(main_context.context())
+            .with_double_buffer(Some(true))
+            .with_srgb(true)
+            .with_vsync(false)
+            .build_windowed(window_builder, target)
+        {
+            Ok(context) => {
+                log::debug!("Opened window '{}'", state.title);
+                // Made current before every use.
+                state.context = Some(ContextSlot::new(unsafe { context.treat_as_current() }))
+            }
+            Err(e) => {
+                log::error!("Failed to open window '{}': {}", state.title, e);
+                state.closed = true
+            }
+        }
+    }
+}
+
+// The events of the secondary windows, the ones of the main window go to the scenes.
+pub(crate) fn handle_event(window_id: WindowId, event: &WindowEvent) {
+    for window in windows() {
+        let mut state = window.borrow_mut();
+
+        let matches = state
+            .context
+            .as_ref()
+            .map_or(false, |context| context.window().id() == window_id);
+
+        if !matches {
+            continue;
+        }
+
+        match event {
+            WindowEvent::CloseRequested => {
+                state.closed = true;
+                state.context = None
+            }
+            WindowEvent::Resized(size) => {
+                if let Some(context) = &state.context {
+                    context.resize(*size)
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The contexts of the windows share the objects of the lost main context and are lost with it.
+// Their windows open again with the next frame, sharing the new main context. Textures shown
+// from the lost context are dropped, draw callbacks have to create their objects again, see
+// ContextLoss::generation.
+pub(crate) fn context_lost() {
+    for window in windows() {
+        let mut state = window.borrow_mut();
+
+        state.context = None;
+        state.read_framebuffer = 0;
+
+        if let Content::Texture(_) = state.content {
+            state.content = Content::Empty
+        }
+    }
+}
+
+// Renders every open window and makes the main context current again.
+pub(crate) fn present_all(main_context: &mut ContextSlot) {
+    let mut presented = false;
+
+    for window in windows() {
+        let mut state = window.borrow_mut();
+        let title = state.title.clone();
+
+        let context = match state.context.as_mut() {
+            Some(context) => context,
+            None => continue,
+        };
+
+        if let Err(e) = context.make_current() {
+            log::error!("Failed to render window '{}': {}", title, e);
+            continue;
+        }
+
+        presented = true;
+        // The state manager tracks the bindings of the context that was current.
+        StateManager::invalidate();
+
+        let size = WindowSize::of(context.window());
+
+        unsafe {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, size.physical.x as i32, size.physical.y as i32);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        let mut content = std::mem::replace(&mut state.content, Content::Empty);
+
+        if let Content::Texture(texture) = &content {
+            if state.read_framebuffer == 0 {
+                unsafe { gl::CreateFramebuffers(1, &mut state.read_framebuffer) }
+            }
+
+            blit_to_fit(state.read_framebuffer, texture, size.physical)
+        }
+
+        // Without the borrow, so the callback can use the window.
+        drop(state);
+
+        if let Content::Draw(draw) = &mut content {
+            draw(size)
+        }
+
+        let mut state = window.borrow_mut();
+
+        if let Content::Empty = state.content {
+            state.content = content
+        }
+
+        if let Some(context) = &state.context {
+            if let Err(e) = context.swap_buffers() {
+                log::error!("Failed to present window '{}': {}", state.title, e)
+            }
+        }
+    }
+
+    if presented {
+        main_context
+            .make_current()
+            .expect("Failed to make the main context current");
+        StateManager::invalidate();
+    }
+}
+
+// Scales the texture to the largest size that fits the window, keeping its aspect ratio.
+fn blit_to_fit(read_framebuffer: GLuint, texture: &RenderTexture, window_size: UVec2) {
+    let texture_size = texture.size();
+
+    let scale = (window_size.x as f32 / texture_size.x as f32)
+        .min(window_size.y as f32 / texture_size.y as f32);
+    let width = (texture_size.x as f32 * scale) as i32;
+    let height = (texture_size.y as f32 * scale) as i32;
+    let x = (window_size.x as i32 - width) / 2;
+    let y = (window_size.y as i32 - height) / 2;
+
+    unsafe {
+        gl::NamedFramebufferTexture(
+            read_framebuffer,
+            gl::COLOR_ATTACHMENT0,
+            texture.texture_id(),
+            0,
+        );
+        gl::BlitNamedFramebuffer(
+            read_framebuffer,
+            0,
+            0,
+            0,
+            texture_size.x as i32,
+            texture_size.y as i32,
+            x,
+            y,
+            x + width,
+            y + height,
+            gl::COLOR_BUFFER_BIT,
+            gl::LINEAR,
+        );
+    }
+}
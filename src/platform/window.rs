@@ -0,0 +1,285 @@
+use crate::core::{
+    application::Application,
+    math::{IVec2, UVec2, Vec2},
+    Settings,
+};
+use glutin::{
+    dpi::{LogicalSize, PhysicalSize},
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowBuilder},
+    Api, ContextBuilder, ContextWrapper, GlProfile, GlRequest, NotCurrent, PossiblyCurrent,
+    Robustness,
+};
+use std::{error::Error, ops::Deref};
+
+pub use glutin::monitor::VideoMode;
+
+// A window with its current GL context.
+pub type GlWindow = ContextWrapper<PossiblyCurrent, Window>;
+
+// A window with a GL context that can be made current again after other contexts were, e.g.
+// the main window after rendering a secondary one.
+pub struct ContextSlot {
+    // Only None while switching.
+    context: Option<GlWindow>,
+}
+
+impl ContextSlot {
+    pub fn new(context: GlWindow) -> Self {
+        Self {
+            context: Some(context),
+        }
+    }
+
+    pub fn make_current(&mut self) -> Result<(), String> {
+        let context = self.context.take().expect("Context lost while switching");
+
+        match unsafe { context.make_current() } {
+            Ok(context) => {
+                self.context = Some(context);
+                Ok(())
+            }
+            Err((context, e)) => {
+                self.context = Some(context);
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+impl Deref for ContextSlot {
+    type Target = GlWindow;
+
+    fn deref(&self) -> &GlWindow {
+        self.context.as_ref().expect("Context lost while switching")
+    }
+}
+
+// The size of a window in logical units, which UI and text are laid out in, and of its
+// framebuffer in physical pixels, which everything is rendered at. They differ by the scale
+// factor of the monitor, e.g. 2 on most high DPI displays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSize {
+    pub physical: UVec2,
+    pub logical: Vec2,
+    pub scale_factor: f32,
+}
+
+impl WindowSize {
+    pub fn new(physical: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        let logical = physical.to_logical::<f32>(scale_factor);
+
+        Self {
+            physical: UVec2::new(physical.width, physical.height),
+            logical: Vec2::new(logical.width, logical.height),
+            scale_factor: scale_factor as f32,
+        }
+    }
+
+    pub fn of(window: &Window) -> Self {
+        Self::new(window.inner_size(), window.scale_factor())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    handle: MonitorHandle,
+}
+
+impl Monitor {
+    pub fn name(&self) -> Option<String> {
+        self.handle.name()
+    }
+
+    // Of the top left corner on the desktop, in physical pixels.
+    pub fn position(&self) -> IVec2 {
+        let position = self.handle.position();
+        IVec2::new(position.x, position.y)
+    }
+
+    // In physical pixels.
+    pub fn size(&self) -> UVec2 {
+        let size = self.handle.size();
+        UVec2::new(size.width, size.height)
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.handle.scale_factor() as f32
+    }
+
+    // The modes exclusive fullscreen can switch it to, largest and fastest first.
+    pub fn video_modes(&self) -> Vec<VideoMode> {
+        let mut video_modes = self.handle.video_modes().collect::<Vec<_>>();
+        video_modes.sort_by_key(|mode| {
+            std::cmp::Reverse((
+                mode.size().width * mode.size().height,
+                mode.refresh_rate(),
+                mode.bit_depth(),
+            ))
+        });
+        video_modes
+    }
+
+    // The fastest mode of the size, or the largest mode if the monitor does not support it.
+    pub fn video_mode(&self, size: UVec2) -> Option<VideoMode> {
+        let video_modes = self.video_modes();
+        let matching = video_modes
+            .iter()
+            .find(|mode| mode.size() == PhysicalSize::new(size.x, size.y))
+            .cloned();
+
+        matching.or_else(|| video_modes.into_iter().next())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FullscreenMode {
+    Windowed,
+    // Covers the monitor without changing its video mode. None for the monitor of the window.
+    Borderless(Option<Monitor>),
+    Exclusive(VideoMode),
+}
+
+// The monitors connected to the desktop of the window.
+pub fn monitors(window: &Window) -> Vec<Monitor> {
+    window
+        .available_monitors()
+        .map(|handle| Monitor { handle })
+        .collect()
+}
+
+pub fn primary_monitor(window: &Window) -> Option<Monitor> {
+    window.primary_monitor().map(|handle| Monitor { handle })
+}
+
+// The monitor the window is mostly on.
+pub fn current_monitor(window: &Window) -> Option<Monitor> {
+    window.current_monitor().map(|handle| Monitor { handle })
+}
+
+pub fn fullscreen_mode(window: &Window) -> FullscreenMode {
+    match window.fullscreen() {
+        None => FullscreenMode::Windowed,
+        Some(Fullscreen::Borderless(handle)) => {
+            FullscreenMode::Borderless(handle.map(|handle| Monitor { handle }))
+        }
+        Some(Fullscreen::Exclusive(video_mode)) => FullscreenMode::Exclusive(video_mode),
+    }
+}
+
+// Switches the window at runtime. The framebuffer is resized along with the window, scenes get
+// the Resized event.
+pub fn set_fullscreen_mode(window: &Window, mode: FullscreenMode) {
+    window.set_fullscreen(match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless(monitor) => Some(Fullscreen::Borderless(
+            monitor.map(|monitor| monitor.handle),
+        )),
+        FullscreenMode::Exclusive(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+    })
+}
+
+// Creates the event loop along with the window, see create_window.
+pub fn create_windowed_context(
+    settings: &Settings,
+) -> Result<(EventLoop<()>, GlWindow), Box<dyn Error>> {
+    let event_loop = EventLoop::new();
+    let windowed_context = create_window(&event_loop, settings)?;
+
+    Ok((event_loop, windowed_context))
+}
+
+// Creates the window of the settings with a current GL context, loads the GL functions and
+// sets the state the renderer expects. The window size of the settings is logical, the
+// framebuffer is larger on high DPI monitors.
+pub fn create_window(
+    target: &EventLoopWindowTarget<()>,
+    settings: &Settings,
+) -> Result<GlWindow, Box<dyn Error>> {
+    let mut window_builder = WindowBuilder::new()
+        .with_title(&settings.name)
+        .with_inner_size(LogicalSize::new(
+            settings.window_size.x,
+            settings.window_size.y,
+        ))
+        .with_resizable(false);
+
+    if settings.fullscreen {
+        let monitor = target
+            .primary_monitor()
+            .or_else(|| target.available_monitors().next())
+            .map(|handle| Monitor { handle })
+            .ok_or("No monitor to go fullscreen on")?;
+        let size = settings.window_size * monitor.scale_factor().round() as u32;
+        let video_mode = monitor
+            .video_mode(size)
+            .ok_or("The monitor has no video modes")?;
+        window_builder = window_builder.with_fullscreen(Some(Fullscreen::Exclusive(video_mode)))
+    }
+
+    let windowed_context = context_builder(settings)
+        .with_double_buffer(Some(true))
+        .with_srgb(true)
+        .with_multisampling(settings.msaa as u16)
+        .with_vsync(settings.vsync)
+        .build_windowed(window_builder, target)?;
+
+    let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+
+    let size = WindowSize::of(windowed_context.window());
+    Application::initialize_gl(size.physical, |s| {
+        windowed_context.get_proc_address(s) as *const _
+    })?;
+
+    Ok(windowed_context)
+}
+
+// Replaces a window whose context was lost with a new one at the same place, of the same size
+// and in the same fullscreen mode. The old window closes once dropped.
+pub(crate) fn recreate_window(
+    target: &EventLoopWindowTarget<()>,
+    settings: &Settings,
+    old_window: &Window,
+) -> Result<GlWindow, Box<dyn Error>> {
+    let windowed_context = create_window(target, settings)?;
+    let window = windowed_context.window();
+
+    window.set_fullscreen(old_window.fullscreen());
+
+    if old_window.fullscreen().is_none() {
+        window.set_inner_size(old_window.inner_size());
+
+        if let Ok(position) = old_window.outer_position() {
+            window.set_outer_position(position)
+        }
+    }
+
+    Ok(windowed_context)
+}
+
+// The GL version and profile every context of the engine is created with.
+pub fn context_builder(settings: &Settings) -> ContextBuilder<'static, NotCurrent> {
+    assert!(
+        settings.graphics_api_version.major > 3 && settings.graphics_api_version.minor > 2,
+        "Only OpenGL version greater than 3.2 are supported"
+    );
+
+    assert!(
+        settings.graphics_api_version.major <= 4 && settings.graphics_api_version.minor <= 6,
+        "OpenGL versions greater than 4.6 are not supported"
+    );
+
+    // Where supported, a reset of the driver loses the context instead of leaving it undefined,
+    // see ContextLoss.
+    ContextBuilder::new()
+        .with_gl_robustness(Robustness::TryRobustLoseContextOnReset)
+        .with_gl_profile(GlProfile::Core)
+        .with_gl(GlRequest::Specific(
+            Api::OpenGl,
+            (
+                settings.graphics_api_version.major as u8,
+                settings.graphics_api_version.minor as u8,
+            ),
+        ))
+}
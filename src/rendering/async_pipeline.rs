@@ -0,0 +1,175 @@
+use crate::core::asset::embedded::EmbeddedAssets;
+use crate::rendering::{
+    error::RendererError,
+    program_pipeline::{PipelineError, ProgramPipeline},
+    shader::{Shader, ShaderStage},
+};
+use std::{cell::RefCell, mem, path::PathBuf, rc::Rc};
+
+thread_local! {
+    // Shared by every material waiting on its shaders, built with the first one.
+    static FALLBACK_PIPELINE: RefCell<Option<Rc<ProgramPipeline>>> = RefCell::new(None);
+}
+
+pub fn fallback_pipeline() -> Result<Rc<ProgramPipeline>, RendererError> {
+    FALLBACK_PIPELINE.with(|fallback| {
+        if let Some(pipeline) = fallback.borrow().as_ref() {
+            return Ok(Rc::clone(pipeline));
+        }
+
+        let pipeline = Rc::new(
+            ProgramPipeline::new()
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Vertex,
+                    "src/rendering/shaders/fallback.vert",
+                )?)
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Fragment,
+                    "src/rendering/shaders/fallback.frag",
+                )?)
+                .build()?,
+        );
+
+        *fallback.borrow_mut() = Some(Rc::clone(&pipeline));
+
+        Ok(pipeline)
+    })
+}
+
+// Drops the pipeline of a lost context, the next material builds it again.
+pub(crate) fn release_fallback_pipeline() {
+    FALLBACK_PIPELINE.with(|fallback| fallback.borrow_mut().take());
+}
+
+enum AsyncPipelineState {
+    Compiling(Vec<Shader>),
+    Linking(ProgramPipeline),
+    Ready(ProgramPipeline),
+    Failed(PipelineError),
+    Polling,
+}
+
+// Compiles and links a program pipeline without stalling the frame. When the driver
+// exposes GL_KHR_parallel_shader_compile the work happens on driver threads, otherwise
+// everything resolves on the first poll.
+pub struct AsyncProgramPipeline {
+    state: AsyncPipelineState,
+}
+
+impl AsyncProgramPipeline {
+    pub fn new(shaders: &[(ShaderStage, PathBuf)]) -> Self {
+        let shaders = shaders
+            .iter()
+            .map(|(stage, path)| Shader::new_async(*stage, path))
+            .collect();
+
+        Self {
+            state: AsyncPipelineState::Compiling(shaders),
+        }
+    }
+
+    // An already built pipeline, e.g. one rebuilt synchronously on hot reload.
+    pub fn from_pipeline(pipeline: ProgramPipeline) -> Self {
+        Self {
+            state: AsyncPipelineState::Ready(pipeline),
+        }
+    }
+
+    // Advances the compilation. Should be called once per frame. Returns true once
+    // the pipeline is ready.
+    pub fn poll(&mut self) -> bool {
+        self.state = match mem::replace(&mut self.state, AsyncPipelineState::Polling) {
+            AsyncPipelineState::Compiling(shaders) => {
+                if shaders.iter().all(Shader::is_compile_complete) {
+                    Self::link(&shaders)
+                } else {
+                    AsyncPipelineState::Compiling(shaders)
+                }
+            }
+            AsyncPipelineState::Linking(pipeline) => {
+                if pipeline.is_link_complete() {
+                    Self::finish_link(pipeline)
+                } else {
+                    AsyncPipelineState::Linking(pipeline)
+                }
+            }
+            state => state,
+        };
+
+        // Without the extension linking completes immediately, no need to wait for another poll.
+        if let AsyncPipelineState::Linking(ref pipeline) = self.state {
+            if pipeline.is_link_complete() {
+                return self.poll();
+            }
+        }
+
+        self.is_ready()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        match self.state {
+            AsyncPipelineState::Ready(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        match self.state {
+            AsyncPipelineState::Compiling(_) | AsyncPipelineState::Linking(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn pipeline(&self) -> Option<&ProgramPipeline> {
+        match self.state {
+            AsyncPipelineState::Ready(ref pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&PipelineError> {
+        match self.state {
+            AsyncPipelineState::Failed(ref error) => Some(error),
+            _ => None,
+        }
+    }
+
+    // Returns the compiled pipeline, or the fallback while it is pending or if it failed.
+    pub fn pipeline_or<'a>(&'a self, fallback: &'a ProgramPipeline) -> &'a ProgramPipeline {
+        self.pipeline().unwrap_or(fallback)
+    }
+
+    fn link(shaders: &[Shader]) -> AsyncPipelineState {
+        for shader in shaders {
+            if let Err(error) = shader.check_compile_status() {
+                log::error!("{}", error);
+
+                return AsyncPipelineState::Failed(error);
+            }
+        }
+
+        let pipeline = shaders
+            .iter()
+            .fold(ProgramPipeline::new(), |pipeline, shader| {
+                pipeline.add_shader(shader)
+            });
+
+        match pipeline.link() {
+            Ok(pipeline) => AsyncPipelineState::Linking(pipeline),
+            Err(error) => {
+                log::error!("{}", error);
+                AsyncPipelineState::Failed(error)
+            }
+        }
+    }
+
+    fn finish_link(pipeline: ProgramPipeline) -> AsyncPipelineState {
+        match pipeline.finish_link() {
+            Ok(pipeline) => AsyncPipelineState::Ready(pipeline),
+            Err(error) => {
+                log::error!("{}", error);
+                AsyncPipelineState::Failed(error)
+            }
+        }
+    }
+}
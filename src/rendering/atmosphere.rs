@@ -0,0 +1,282 @@
+use crate::{
+    core::math::{UVec2, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        error::RendererError,
+        framebuffer::{
+            AttachmentType, Framebuffer, FramebufferAttachment, FramebufferAttachmentCreateInfo,
+        },
+        postprocess::fullscreen_pass::FullscreenPass,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        state::{DepthStencilState, FixedFunctionState, RasterizerState, StateManager},
+        texture::SizedTextureFormat,
+    },
+    Msaa,
+};
+use std::ops::RangeInclusive;
+
+const TRANSMITTANCE_LUT: i32 = 0;
+const MULTIPLE_SCATTERING_LUT: i32 = 1;
+const SKY_VIEW_LUT: i32 = 2;
+
+// Physical description of the planet and its atmosphere. Distances are in km and the
+// coefficients in 1/km, the defaults are the ones of the earth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtmosphereSettings {
+    pub bottom_radius: f32,
+    pub top_radius: f32,
+    pub rayleigh_scattering: Vec3,
+    // Height at which the density of the air falls to 1/e.
+    pub rayleigh_density_height: f32,
+    pub mie_scattering: f32,
+    pub mie_absorption: f32,
+    pub mie_density_height: f32,
+    // Anisotropy of the aerosol phase function.
+    pub mie_g: f32,
+    // Peaks at 25km and fades out over 15km to either side.
+    pub ozone_absorption: Vec3,
+    pub ground_albedo: Vec3,
+    // Height of the viewer above the ground. The scene is small enough for it to be constant.
+    pub altitude: f32,
+}
+
+impl Default for AtmosphereSettings {
+    fn default() -> Self {
+        Self {
+            bottom_radius: 6360.0,
+            top_radius: 6460.0,
+            rayleigh_scattering: Vec3::new(5.802e-3, 13.558e-3, 33.1e-3),
+            rayleigh_density_height: 8.0,
+            mie_scattering: 3.996e-3,
+            mie_absorption: 0.444e-3,
+            mie_density_height: 1.2,
+            mie_g: 0.8,
+            ozone_absorption: Vec3::new(0.650e-3, 1.881e-3, 0.085e-3),
+            ground_albedo: Vec3::new(0.3, 0.3, 0.3),
+            altitude: 0.2,
+        }
+    }
+}
+
+// The look up tables of Hillaire's sky model (A Scalable and Production Ready Sky and
+// Atmosphere Rendering Technique, 2020). Transmittance and multiple scattering only depend on
+// the atmosphere, the sky view also on the sun and is what the sky is drawn from.
+pub struct Atmosphere {
+    settings: AtmosphereSettings,
+    pass: FullscreenPass,
+    sampler_linear: Sampler,
+    transmittance_lut: Framebuffer,
+    multiple_scattering_lut: Framebuffer,
+    sky_view_lut: Framebuffer,
+    // What the look up tables were last rendered with.
+    rendered_settings: Option<AtmosphereSettings>,
+    rendered_sun_direction: Option<Vec3>,
+}
+
+impl Atmosphere {
+    pub fn new(settings: AtmosphereSettings) -> Result<Self, RendererError> {
+        Ok(Self {
+            settings,
+            pass: FullscreenPass::new("src/rendering/shaders/atmosphere.frag")?,
+            sampler_linear: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            transmittance_lut: Self::create_lut(UVec2::new(256, 64))?,
+            multiple_scattering_lut: Self::create_lut(UVec2::new(32, 32))?,
+            sky_view_lut: Self::create_lut(UVec2::new(192, 108))?,
+            rendered_settings: None,
+            rendered_sun_direction: None,
+        })
+    }
+
+    // Renders the look up tables that are out of date. Returns whether any of them changed.
+    pub fn update(&mut self, sun_direction: &Vec3) -> bool {
+        let settings_changed = self.rendered_settings.as_ref() != Some(&self.settings);
+        let sun_changed = self.rendered_sun_direction.as_ref() != Some(sun_direction);
+
+        if !settings_changed && !sun_changed {
+            return false;
+        }
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        self.pass.bind();
+        self.set_uniforms(sun_direction);
+
+        if settings_changed {
+            self.render_lut(&self.transmittance_lut, TRANSMITTANCE_LUT);
+            self.pass.set_texture(
+                "transmittanceLut",
+                self.transmittance_lut.texture_attachments()[0].id(),
+                &self.sampler_linear,
+            );
+            self.render_lut(&self.multiple_scattering_lut, MULTIPLE_SCATTERING_LUT);
+        }
+
+        self.pass
+            .set_texture(
+                "transmittanceLut",
+                self.transmittance_lut.texture_attachments()[0].id(),
+                &self.sampler_linear,
+            )
+            .set_texture(
+                "multipleScatteringLut",
+                self.multiple_scattering_lut.texture_attachments()[0].id(),
+                &self.sampler_linear,
+            );
+        self.render_lut(&self.sky_view_lut, SKY_VIEW_LUT);
+
+        self.pass.unbind();
+
+        StateManager::apply(&FixedFunctionState::default());
+
+        self.rendered_settings = Some(self.settings.clone());
+        self.rendered_sun_direction = Some(*sun_direction);
+        true
+    }
+
+    // Transmittance to the top of the atmosphere by height and view zenith.
+    pub fn transmittance_lut(&self) -> FramebufferAttachment {
+        self.transmittance_lut.texture_attachments()[0]
+    }
+
+    // Sky luminance for a unit sun illuminance by view zenith and azimuth from the sun.
+    pub fn sky_view_lut(&self) -> FramebufferAttachment {
+        self.sky_view_lut.texture_attachments()[0]
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler_linear
+    }
+
+    pub fn settings(&self) -> &AtmosphereSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut AtmosphereSettings {
+        &mut self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: AtmosphereSettings) {
+        self.settings = settings
+    }
+
+    // Height of the viewer from the center of the planet.
+    pub fn view_height(&self) -> f32 {
+        self.settings.bottom_radius + self.settings.altitude.max(0.001)
+    }
+
+    fn set_uniforms(&self, sun_direction: &Vec3) {
+        let settings = &self.settings;
+
+        self.pass
+            .pipeline()
+            .set_float_all_stages("bottomRadius", settings.bottom_radius)
+            .set_float_all_stages("topRadius", settings.top_radius)
+            .set_vec3_all_stages("rayleighScattering", &settings.rayleigh_scattering)
+            .set_float_all_stages("rayleighDensityHeight", settings.rayleigh_density_height)
+            .set_float_all_stages("mieScattering", settings.mie_scattering)
+            .set_float_all_stages("mieAbsorption", settings.mie_absorption)
+            .set_float_all_stages("mieDensityHeight", settings.mie_density_height)
+            .set_float_all_stages("mieG", settings.mie_g)
+            .set_vec3_all_stages("ozoneAbsorption", &settings.ozone_absorption)
+            .set_vec3_all_stages("groundAlbedo", &settings.ground_albedo)
+            .set_float_all_stages("viewHeight", self.view_height())
+            .set_vec3_all_stages("sunDirection", sun_direction);
+    }
+
+    fn render_lut(&self, lut: &Framebuffer, index: i32) {
+        lut.bind();
+        self.pass.pipeline().set_int_all_stages("lut", index);
+        self.pass.draw();
+        lut.unbind(false);
+    }
+
+    fn create_lut(size: UVec2) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![FramebufferAttachmentCreateInfo::new(
+                SizedTextureFormat::Rgba16f,
+                AttachmentType::Texture,
+            )],
+        )
+        .map_err(RendererError::from)
+    }
+}
+
+impl Gui for Atmosphere {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Atmosphere"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                let settings = &mut self.settings;
+
+                imgui::Slider::new(im_str!("Altitude (km)"))
+                    .range(RangeInclusive::new(0.0, 50.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut settings.altitude);
+                imgui::Drag::new(im_str!("Rayleigh Scattering"))
+                    .range(RangeInclusive::new(0.0, 0.1))
+                    .speed(0.0001)
+                    .display_format(im_str!("%.4f"))
+                    .build_array(&ui, settings.rayleigh_scattering.as_mut_slice());
+                imgui::Slider::new(im_str!("Rayleigh Height (km)"))
+                    .range(RangeInclusive::new(1.0, 20.0))
+                    .display_format(im_str!("%.1f"))
+                    .build(&ui, &mut settings.rayleigh_density_height);
+                imgui::Drag::new(im_str!("Mie Scattering"))
+                    .range(RangeInclusive::new(0.0, 0.1))
+                    .speed(0.0001)
+                    .display_format(im_str!("%.4f"))
+                    .build(&ui, &mut settings.mie_scattering);
+                imgui::Drag::new(im_str!("Mie Absorption"))
+                    .range(RangeInclusive::new(0.0, 0.1))
+                    .speed(0.0001)
+                    .display_format(im_str!("%.4f"))
+                    .build(&ui, &mut settings.mie_absorption);
+                imgui::Slider::new(im_str!("Mie Height (km)"))
+                    .range(RangeInclusive::new(0.1, 10.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut settings.mie_density_height);
+                imgui::Slider::new(im_str!("Mie Anisotropy"))
+                    .range(RangeInclusive::new(0.0, 0.99))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut settings.mie_g);
+                imgui::Drag::new(im_str!("Ozone Absorption"))
+                    .range(RangeInclusive::new(0.0, 0.01))
+                    .speed(0.0001)
+                    .display_format(im_str!("%.4f"))
+                    .build_array(&ui, settings.ozone_absorption.as_mut_slice());
+
+                let mut ground_albedo: [f32; 3] = settings.ground_albedo.into();
+                if imgui::ColorEdit::new(im_str!("Ground Albedo"), &mut ground_albedo).build(&ui) {
+                    settings.ground_albedo = ground_albedo.into()
+                }
+
+                if ui.button(im_str!("Reset"), [0.0, 0.0]) {
+                    *settings = AtmosphereSettings::default()
+                }
+            });
+    }
+}
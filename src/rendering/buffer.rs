@@ -1,7 +1,13 @@
-use crate::rendering::format::{BufferInternalFormat, DataFormat, DataType};
+use crate::rendering::{
+    fence::{FenceWaitResult, GpuFence},
+    format::{BufferInternalFormat, DataFormat, DataType},
+    gpu_capabilities::GpuCapabilities,
+    object_label::set_object_label,
+    state::StateManager,
+};
 use gl::types::*;
 use gl_bindings as gl;
-use std::ffi::CString;
+use std::cell::RefCell;
 use std::{mem, ptr};
 
 bitflags! {
@@ -67,6 +73,8 @@ pub struct Buffer {
     mapped_ptr: *mut GLvoid,
     storage_flags: BufferStorageFlags,
     current_bound_target: BufferTarget,
+    // Signals once the GPU is done with the commands that read the mapped memory.
+    fence: RefCell<Option<GpuFence>>,
 }
 
 impl Buffer {
@@ -80,11 +88,10 @@ impl Buffer {
         unsafe {
             gl::CreateBuffers(1, &mut id);
             gl::NamedBufferStorage(id, size, ptr::null(), buffer_storage_flags.bits());
-
-            let label = CString::new(name).unwrap();
-            gl::ObjectLabel(gl::BUFFER, id, name.len() as i32 + 1, label.as_ptr())
         }
 
+        set_object_label(gl::BUFFER, id, name);
+
         Self {
             _name: name.to_string(),
             id,
@@ -92,6 +99,7 @@ impl Buffer {
             mapped_ptr: ptr::null_mut(),
             storage_flags: buffer_storage_flags,
             current_bound_target: buffer_target,
+            fence: RefCell::new(None),
         }
     }
 
@@ -111,11 +119,10 @@ impl Buffer {
                 data as *const T as *const GLvoid,
                 buffer_storage_flags.bits(),
             );
-
-            let label = CString::new(name).unwrap();
-            gl::ObjectLabel(gl::BUFFER, id, name.len() as i32 + 1, label.as_ptr())
         }
 
+        set_object_label(gl::BUFFER, id, name);
+
         Self {
             _name: name.to_string(),
             id,
@@ -123,6 +130,7 @@ impl Buffer {
             mapped_ptr: ptr::null_mut(),
             storage_flags: buffer_storage_flags,
             current_bound_target: buffer_target,
+            fence: RefCell::new(None),
         }
     }
 
@@ -142,11 +150,10 @@ impl Buffer {
                 data.as_ptr() as *const GLvoid,
                 buffer_storage_flags.bits(),
             );
-
-            let label = CString::new(name).unwrap();
-            gl::ObjectLabel(gl::BUFFER, id, name.len() as i32 + 1, label.as_ptr())
         }
 
+        set_object_label(gl::BUFFER, id, name);
+
         Self {
             _name: name.to_string(),
             id,
@@ -154,6 +161,7 @@ impl Buffer {
             mapped_ptr: ptr::null_mut(),
             storage_flags: buffer_storage_flags,
             current_bound_target: buffer_target,
+            fence: RefCell::new(None),
         }
     }
 
@@ -169,6 +177,14 @@ impl Buffer {
                 || self.current_bound_target == BufferTarget::TransformFeedback,
             "Cannot bind buffer range. Buffer target is not one of [ShaderStorage|AtomicCounter|Uniform|TransformFeedback]."
         );
+        assert_eq!(
+            offset % Self::offset_alignment(self.current_bound_target),
+            0,
+            "Buffer bind offset {} is not aligned to the offset alignment ({}) of the buffer target. \
+                Hint: Use bind_block(...) or Buffer::align_offset(...)",
+            offset,
+            Self::offset_alignment(self.current_bound_target)
+        );
         assert!(
             offset + size <= self.size,
             "Buffer bind operation out of buffer range. Buffer size: {}, Requested bind offset: {}, Requested bind size: {}",
@@ -176,17 +192,80 @@ impl Buffer {
             offset,
             size
         );
-        unsafe {
-            gl::BindBufferRange(
-                self.current_bound_target as u32,
-                binding_index,
-                self.id,
-                offset,
-                size,
-            )
+        StateManager::bind_buffer_range(
+            self.current_bound_target as u32,
+            binding_index,
+            self.id,
+            offset,
+            size,
+        )
+    }
+
+    // Binds the block at the given index of an array of T blocks, each starting at an offset
+    // aligned to the requirements of the buffer target. Lets many per-object blocks share a
+    // single buffer created with new_block_array.
+    pub fn bind_block<T: Sized>(&self, binding_index: u32, index: usize) {
+        let stride = Self::block_stride::<T>(self.current_bound_target);
+
+        self.bind_range(
+            binding_index,
+            index as isize * stride,
+            mem::size_of::<T>() as isize,
+        )
+    }
+
+    pub fn new_block_array<T: Sized>(
+        name: &str,
+        count: usize,
+        buffer_target: BufferTarget,
+        buffer_storage_flags: BufferStorageFlags,
+    ) -> Self {
+        Self::new(
+            name,
+            count as isize * Self::block_stride::<T>(buffer_target),
+            buffer_target,
+            buffer_storage_flags,
+        )
+    }
+
+    // Writes the block at the given index of a buffer created with new_block_array. Mapped
+    // buffers are written through the mapping.
+    pub fn fill_block<T: Sized>(&self, index: usize, data: &T) {
+        let offset = index as isize * Self::block_stride::<T>(self.current_bound_target);
+
+        if self.is_mapped() {
+            self.fill_mapped_slice(offset, std::slice::from_ref(data))
+        } else {
+            self.fill_slice(offset, std::slice::from_ref(data))
         }
     }
 
+    // Minimum alignment of offsets passed to bind_range for the given target.
+    pub fn offset_alignment(target: BufferTarget) -> isize {
+        let capabilities = GpuCapabilities::current();
+
+        let alignment = match target {
+            BufferTarget::Uniform => capabilities.uniform_buffer_offset_alignment,
+            BufferTarget::ShaderStorage => capabilities.shader_storage_buffer_offset_alignment,
+            // Atomic counter and transform feedback offsets only need to be multiples of 4.
+            BufferTarget::AtomicCounter | BufferTarget::TransformFeedback => return 4,
+            // Enough for any vertex or index type.
+            _ => return 16,
+        };
+
+        alignment as isize
+    }
+
+    // Rounds the offset up to the next valid bind offset of the target.
+    pub fn align_offset(offset: isize, target: BufferTarget) -> isize {
+        let alignment = Self::offset_alignment(target);
+        (offset + alignment - 1) / alignment * alignment
+    }
+
+    fn block_stride<T: Sized>(target: BufferTarget) -> isize {
+        Self::align_offset(mem::size_of::<T>() as isize, target)
+    }
+
     pub fn map(&mut self, map_mode: MapModeFlags) {
         self.map_range(0, self.size, map_mode)
     }
@@ -229,7 +308,7 @@ impl Buffer {
                 self.mapped_ptr = gl::MapNamedBufferRange(self.id, offset, length, map_mode.bits())
             }
         } else {
-            println!("WARNING: Buffer already mapped. This call has no effect.")
+            log::warn!("Buffer already mapped. This call has no effect.")
         }
     }
 
@@ -260,6 +339,21 @@ impl Buffer {
         }
     }
 
+    pub fn fill_slice<T>(&self, offset: isize, data: &[T]) {
+        assert!(
+            self.storage_flags.intersects(BufferStorageFlags::DYNAMIC),
+            "Cannot fill non-mapped buffer. \n \
+                Reason: Not able to call glBufferSubData(...).\n\
+                Hint: Create the buffer using BufferStorageFlags::DYNAMIC \
+                for non-mapped data updates."
+        );
+
+        let size = (data.len() * mem::size_of::<T>()) as isize;
+        assert!(offset >= 0 && offset + size <= self.size);
+
+        unsafe { gl::NamedBufferSubData(self.id, offset, size, data.as_ptr() as *const GLvoid) }
+    }
+
     pub fn fill_mapped<T: Sized>(&self, offset: isize, data: &T) {
         assert_ne!(
             self.mapped_ptr,
@@ -278,15 +372,76 @@ impl Buffer {
         assert_eq!(self.size, mem::size_of::<T>() as isize);
         assert!(offset + std::mem::size_of::<T>() as isize <= self.size);
 
+        self.wait_for_gpu();
+
         let source = unsafe { (data as *const T).offset(offset) };
 
         unsafe { ptr::copy_nonoverlapping(source, self.mapped_ptr as *mut T, 1) }
     }
 
+    pub fn fill_mapped_slice<T: Sized>(&self, offset: isize, data: &[T]) {
+        assert_ne!(
+            self.mapped_ptr,
+            ptr::null_mut(),
+            "Attempting to fill unmapped buffer. Please map the buffer first by calling \
+                   map(&mut self, buffer_access: BufferAccess)"
+        );
+
+        assert!(
+            self.storage_flags.intersects(BufferStorageFlags::MAP_WRITE),
+            "Cannot fill mapped buffer.\n\
+                Reason: Buffer not created using the flag BufferStorageFlags::MAP_WRITE.\n\
+                Hint: Create the buffer using BufferStorageFlags::MAP_WRITE"
+        );
+
+        let size = (data.len() * mem::size_of::<T>()) as isize;
+        assert!(offset >= 0 && offset + size <= self.size);
+
+        self.wait_for_gpu();
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                (self.mapped_ptr as *mut u8).offset(offset),
+                size as usize,
+            )
+        }
+    }
+
+    // Call after the last command that reads the mapped memory. The next fill of a persistently
+    // mapped buffer waits until the GPU has executed those commands instead of overwriting data
+    // that is still in use.
+    pub fn fence(&self) {
+        if self
+            .storage_flags
+            .intersects(BufferStorageFlags::MAP_PERSISTENT)
+        {
+            *self.fence.borrow_mut() = Some(GpuFence::new())
+        }
+    }
+
+    // Orphans the buffer contents. The driver may hand out fresh memory instead of waiting for
+    // pending reads.
+    pub fn invalidate(&self) {
+        unsafe { gl::InvalidateBufferData(self.id) }
+    }
+
+    fn wait_for_gpu(&self) {
+        if let Some(fence) = self.fence.borrow_mut().take() {
+            if fence.wait() == FenceWaitResult::Failed {
+                log::error!("Waiting on buffer fence failed.")
+            }
+        }
+    }
+
     pub fn get_id(&self) -> GLuint {
         self.id
     }
 
+    pub fn get_target(&self) -> BufferTarget {
+        self.current_bound_target
+    }
+
     pub fn get_size(&self) -> isize {
         self.size
     }
@@ -399,6 +554,7 @@ impl Drop for Buffer {
         if self.is_mapped() {
             self.unmap()
         }
+        StateManager::buffer_deleted(self.id);
         unsafe { gl::DeleteBuffers(1, &mut self.id) }
     }
 }
@@ -0,0 +1,262 @@
+use crate::rendering::state::StateManager;
+use crate::rendering::texture::{SizedTextureFormat, TextureFormat};
+use gl::types::*;
+use gl_bindings as gl;
+use image::GenericImageView;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const MIN_SIZE: u32 = 2;
+const MAX_SIZE: u32 = 256;
+
+// A 3D color lookup table for color grading. Maps sRGB encoded colors to sRGB encoded colors,
+// the way LUTs are authored in image editors and grading tools.
+pub struct ColorLut {
+    id: GLuint,
+    size: u32,
+    path: PathBuf,
+}
+
+impl ColorLut {
+    // Loads a .cube file or a strip image, picked by the extension.
+    pub fn new_from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let is_cube = path
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("cube"));
+
+        let mut lut = if is_cube {
+            Self::new_from_cube(path)?
+        } else {
+            Self::new_from_strip(path)?
+        };
+
+        lut.path = path.to_path_buf();
+        Ok(lut)
+    }
+
+    // A strip image of size square slices side by side, e.g. 1024x32 for a 32^3 LUT. Red grows
+    // to the right within a slice, green downwards and blue from slice to slice.
+    pub fn new_from_strip<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let image = image::open(path.as_ref()).map_err(|e| e.to_string())?;
+        let (width, height) = image.dimensions();
+
+        if width != height * height {
+            return Err(format!(
+                "A {}x{} image is not a LUT strip, expected a width of {}.",
+                width,
+                height,
+                height * height
+            ));
+        }
+
+        let size = height;
+        Self::validate_size(size)?;
+
+        let strip = image.to_rgba();
+        let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    texels.extend_from_slice(&strip.get_pixel(blue * size + red, green).0);
+                }
+            }
+        }
+
+        Ok(Self::new_from_texels(
+            size,
+            SizedTextureFormat::Rgba8,
+            TextureFormat::Rgba,
+            gl::UNSIGNED_BYTE,
+            texels.as_ptr() as *const _,
+            path.as_ref(),
+        ))
+    }
+
+    // An Adobe/Resolve .cube file with a LUT_3D_SIZE table.
+    pub fn new_from_cube<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = fs::read_to_string(path.as_ref()).map_err(|e| e.to_string())?;
+
+        let mut size = None;
+        let mut texels: Vec<f32> = vec![];
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap();
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err("1D .cube LUTs are not supported.".to_string()),
+                "LUT_3D_SIZE" => {
+                    let value = tokens
+                        .next()
+                        .and_then(|value| value.parse::<u32>().ok())
+                        .ok_or_else(|| format!("Invalid LUT_3D_SIZE on line {}.", index + 1))?;
+
+                    Self::validate_size(value)?;
+                    texels.reserve((value * value * value * 3) as usize);
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let default = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let is_default = tokens
+                        .map(|value| value.parse::<f32>())
+                        .all(|value| value == Ok(default));
+
+                    if !is_default {
+                        log::warn!(
+                            "The {} of {:?} is ignored, the LUT is sampled in [0, 1].",
+                            keyword,
+                            path.as_ref()
+                        );
+                    }
+                }
+                _ => {
+                    let values = line
+                        .split_whitespace()
+                        .map(|value| value.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| format!("Unexpected '{}' on line {}.", line, index + 1))?;
+
+                    if values.len() != 3 {
+                        return Err(format!("Expected 3 values on line {}.", index + 1));
+                    }
+
+                    texels.extend_from_slice(&values);
+                }
+            }
+        }
+
+        let size = size.ok_or_else(|| "The .cube file has no LUT_3D_SIZE.".to_string())?;
+        let expected = (size * size * size * 3) as usize;
+
+        if texels.len() != expected {
+            return Err(format!(
+                "Expected {} entries in the .cube file, found {}.",
+                expected / 3,
+                texels.len() / 3
+            ));
+        }
+
+        // Entries are listed with red changing fastest, which is the texel order of GL too.
+        Ok(Self::new_from_texels(
+            size,
+            SizedTextureFormat::Rgb32f,
+            TextureFormat::Rgb,
+            gl::FLOAT,
+            texels.as_ptr() as *const _,
+            path.as_ref(),
+        ))
+    }
+
+    // A LUT that leaves colors as they are, a starting point for strips exported for grading.
+    pub fn new_identity(size: u32) -> Self {
+        let size = size.max(MIN_SIZE).min(MAX_SIZE);
+        let scale = 255.0 / (size - 1) as f32;
+        let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    texels.extend_from_slice(&[
+                        (red as f32 * scale).round() as u8,
+                        (green as f32 * scale).round() as u8,
+                        (blue as f32 * scale).round() as u8,
+                        255,
+                    ]);
+                }
+            }
+        }
+
+        Self::new_from_texels(
+            size,
+            SizedTextureFormat::Rgba8,
+            TextureFormat::Rgba,
+            gl::UNSIGNED_BYTE,
+            texels.as_ptr() as *const _,
+            Path::new(""),
+        )
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+
+    // Texels along each axis.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // The file the LUT was loaded from, empty for generated ones.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn validate_size(size: u32) -> Result<(), String> {
+        if size < MIN_SIZE || size > MAX_SIZE {
+            return Err(format!(
+                "LUT size {} is outside of [{}, {}].",
+                size, MIN_SIZE, MAX_SIZE
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn new_from_texels(
+        size: u32,
+        internal_format: SizedTextureFormat,
+        format: TextureFormat,
+        data_type: GLenum,
+        texels: *const GLvoid,
+        path: &Path,
+    ) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_3D, 1, &mut id);
+            gl::TextureStorage3D(
+                id,
+                1,
+                internal_format as u32,
+                size as i32,
+                size as i32,
+                size as i32,
+            );
+            gl::TextureSubImage3D(
+                id,
+                0,
+                0,
+                0,
+                0,
+                size as i32,
+                size as i32,
+                size as i32,
+                format as u32,
+                data_type,
+                texels,
+            );
+        }
+
+        Self {
+            id,
+            size,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Drop for ColorLut {
+    fn drop(&mut self) {
+        StateManager::texture_deleted(self.id);
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
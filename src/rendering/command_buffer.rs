@@ -0,0 +1,466 @@
+use crate::{
+    core::asset::Handle,
+    core::ecs::{components::SharedMaterial, Entity},
+    core::math::{Mat4, Vec4},
+    geometry::bounds::Aabb,
+    rendering::{
+        mesh::Mesh,
+        per_draw::{PerDrawData, PerDrawUniforms},
+        sort_key::{RenderLayer, SortKey},
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    ops::Range,
+    rc::Rc,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const RECORDER_THREAD_COUNT: usize = 4;
+// Fewer items are recorded on the calling thread, handing them to a worker costs more than it
+// saves.
+const MIN_ITEMS_PER_JOB: usize = 256;
+// How often the calling thread checks that the workers are alive while waiting for their chunks.
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The meshes and materials of a frame. Commands refer to them by index, so they can be recorded
+// on threads that cannot touch the GL objects or the reference counted handles.
+#[derive(Default)]
+pub struct DrawResources {
+    meshes: Vec<Handle<Mesh>>,
+    materials: Vec<SharedMaterial>,
+    mesh_indices: HashMap<usize, u32>,
+    material_indices: HashMap<usize, u32>,
+    // Program pipeline -> index, in the order of first use.
+    pipeline_indices: HashMap<GLuint, u32>,
+}
+
+impl DrawResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The index of the mesh, added on first use.
+    pub fn mesh_index(&mut self, mesh: &Handle<Mesh>) -> u32 {
+        let meshes = &mut self.meshes;
+
+        *self
+            .mesh_indices
+            .entry(&**mesh as *const Mesh as usize)
+            .or_insert_with(|| {
+                meshes.push(mesh.clone());
+                meshes.len() as u32 - 1
+            })
+    }
+
+    // The index of the material, added on first use. Updates the material once per frame, before
+    // its program pipeline is sorted on.
+    pub fn material_index(&mut self, material: &SharedMaterial) -> u32 {
+        let materials = &mut self.materials;
+
+        *self
+            .material_indices
+            .entry(Rc::as_ptr(material) as *const u8 as usize)
+            .or_insert_with(|| {
+                material.borrow_mut().update();
+                materials.push(material.clone());
+                materials.len() as u32 - 1
+            })
+    }
+
+    // The index of the program pipeline of the material, for the sort keys.
+    pub fn pipeline_index(&mut self, material: &SharedMaterial) -> u32 {
+        let id = material.borrow().program_pipeline().id();
+        let count = self.pipeline_indices.len() as u32;
+
+        *self.pipeline_indices.entry(id).or_insert(count)
+    }
+
+    pub fn mesh(&self, index: u32) -> &Handle<Mesh> {
+        &self.meshes[index as usize]
+    }
+
+    pub fn material(&self, index: u32) -> &SharedMaterial {
+        &self.materials[index as usize]
+    }
+
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+        self.materials.clear();
+        self.mesh_indices.clear();
+        self.material_indices.clear();
+        self.pipeline_indices.clear()
+    }
+}
+
+// A renderable as the recorder sees it, plain data that can be sent to the worker threads.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordItem {
+    pub mesh: u32,
+    pub material: u32,
+    pub pipeline: u32,
+    pub layer: RenderLayer,
+    pub model: Mat4,
+    // Object space bounds of the mesh.
+    pub bounds: Aabb,
+    pub entity: Option<Entity>,
+}
+
+impl RecordItem {
+    pub fn new(
+        resources: &mut DrawResources,
+        mesh: &Handle<Mesh>,
+        material: &SharedMaterial,
+        model: Mat4,
+        entity: Option<Entity>,
+    ) -> Self {
+        Self {
+            mesh: resources.mesh_index(mesh),
+            material: resources.material_index(material),
+            pipeline: resources.pipeline_index(material),
+            layer: material.borrow().render_layer(),
+            model,
+            bounds: *mesh.bounds(),
+            entity,
+        }
+    }
+
+    // The key the item is submitted in order of, seen through the view projection.
+    pub fn sort_key(&self, view_projection: &Mat4) -> SortKey {
+        SortKey::new(
+            self.layer,
+            self.pipeline,
+            self.material,
+            depth(&self.bounds, &(view_projection * self.model)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrawCommand {
+    // Submission order, ascending.
+    pub sort_key: SortKey,
+    pub mesh: u32,
+    pub material: u32,
+    pub per_draw: PerDrawData,
+    pub entity: Option<Entity>,
+}
+
+// Draws recorded for submission. Recording only computes visibility, sort keys and per draw
+// data, which can happen on any thread. Submission binds and draws on the thread that owns the
+// GL context.
+#[derive(Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<DrawCommand>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command)
+    }
+
+    // Moves the commands of the other buffer to the end of this one.
+    pub fn append(&mut self, other: &mut CommandBuffer) {
+        self.commands.append(&mut other.commands)
+    }
+
+    // Records a command for every item inside the view frustum.
+    pub fn record(&mut self, items: &[RecordItem], view_projection: &Mat4) {
+        for item in items {
+            let model_view_projection = view_projection * item.model;
+
+            if !is_in_frustum(&item.bounds, &model_view_projection) {
+                continue;
+            }
+
+            self.commands.push(DrawCommand {
+                sort_key: item.sort_key(view_projection),
+                mesh: item.mesh,
+                material: item.material,
+                per_draw: PerDrawData::new(item.model, 0),
+                entity: item.entity,
+            })
+        }
+    }
+
+    // Stable, equal keys keep the recording order.
+    pub fn sort(&mut self) {
+        self.commands.sort_by_key(|command| command.sort_key)
+    }
+
+    // Merges buffers that are sorted already. Equal keys keep the order of the buffers, as if
+    // they were appended in order and sorted.
+    pub fn merge(buffers: Vec<CommandBuffer>) -> CommandBuffer {
+        let len = buffers.iter().map(CommandBuffer::len).sum();
+        let mut commands = Vec::with_capacity(len);
+        let mut sources = buffers
+            .into_iter()
+            .map(|buffer| buffer.commands.into_iter().peekable())
+            .collect::<Vec<_>>();
+
+        // A handful of buffers, one per recorder thread, so a linear scan for the smallest key
+        // is enough.
+        loop {
+            let next = sources
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, source)| source.peek().map(|command| (command.sort_key, i)))
+                .min();
+
+            match next {
+                Some((_, i)) => commands.extend(sources[i].next()),
+                None => break,
+            }
+        }
+
+        CommandBuffer { commands }
+    }
+
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear()
+    }
+
+    // Binds each material once per run of commands that use it.
+    pub fn submit(&self, resources: &DrawResources, per_draw_uniforms: &mut PerDrawUniforms) {
+        self.submit_with(resources, per_draw_uniforms, || {})
+    }
+
+    // Like submit, calls after_bind whenever a material was bound, e.g. to override its fixed
+    // function state.
+    pub fn submit_with<F>(
+        &self,
+        resources: &DrawResources,
+        per_draw_uniforms: &mut PerDrawUniforms,
+        after_bind: F,
+    ) where
+        F: Fn(),
+    {
+        let mut bound: Option<u32> = None;
+
+        for command in &self.commands {
+            let material = resources.material(command.material).borrow();
+
+            if bound != Some(command.material) {
+                if let Some(previous) = bound {
+                    resources.material(previous).borrow().unbind();
+                }
+
+                material.bind();
+                after_bind();
+                bound = Some(command.material);
+            }
+
+            if per_draw_uniforms.push_and_bind(&command.per_draw).is_none() {
+                break;
+            }
+
+            resources
+                .mesh(command.mesh)
+                .draw_with_primitive_mode(material.primitive_mode());
+        }
+
+        if let Some(bound) = bound {
+            resources.material(bound).borrow().unbind();
+        }
+    }
+}
+
+// The depth of the center of the bounds in [0, 1], 0 at the near plane. Nonlinear for
+// perspective projections, which keeps the order.
+fn depth(bounds: &Aabb, model_view_projection: &Mat4) -> f32 {
+    let center = if bounds.is_empty() {
+        Vec4::new(0.0, 0.0, 0.0, 1.0)
+    } else {
+        let center = (bounds.min + bounds.max) * 0.5;
+        Vec4::new(center.x, center.y, center.z, 1.0)
+    };
+
+    let clip = model_view_projection * center;
+
+    if clip.w <= 0.0 {
+        return 0.0;
+    }
+
+    clip.z / clip.w * 0.5 + 0.5
+}
+
+// False if all corners of the bounds are outside of the same clip plane. Empty bounds, of meshes
+// without positions, are always visible.
+fn is_in_frustum(bounds: &Aabb, model_view_projection: &Mat4) -> bool {
+    if bounds.is_empty() {
+        return true;
+    }
+
+    let corners = bounds
+        .corners()
+        .iter()
+        .map(|corner| model_view_projection * Vec4::new(corner.x, corner.y, corner.z, 1.0))
+        .collect::<Vec<_>>();
+
+    for axis in 0..3 {
+        if corners.iter().all(|c| c[axis] < -c.w) || corners.iter().all(|c| c[axis] > c.w) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn record_sorted(items: &[RecordItem], view_projection: &Mat4) -> CommandBuffer {
+    let mut command_buffer = CommandBuffer::new();
+    command_buffer.record(items, view_projection);
+    command_buffer.sort();
+    command_buffer
+}
+
+struct RecordJob {
+    items: Arc<Vec<RecordItem>>,
+    range: Range<usize>,
+    view_projection: Mat4,
+    chunk: usize,
+}
+
+// Records command buffers on worker threads. The items are split into chunks that the workers
+// cull, record and sort in parallel, the sorted chunks are merged on the calling thread.
+pub struct CommandRecorder {
+    jobs: Option<Sender<RecordJob>>,
+    results: Receiver<(usize, CommandBuffer)>,
+    workers: Vec<JoinHandle<()>>,
+    // Set once a worker panicked. Everything is recorded on the calling thread afterwards.
+    workers_lost: Cell<bool>,
+}
+
+impl CommandRecorder {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<RecordJob>();
+        let (results_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..RECORDER_THREAD_COUNT)
+            .map(|i| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let results_sender = results_sender.clone();
+
+                thread::Builder::new()
+                    .name(format!("Command Recorder {}", i))
+                    .spawn(move || loop {
+                        let job = match job_receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        let command_buffer =
+                            record_sorted(&job.items[job.range], &job.view_projection);
+
+                        if results_sender.send((job.chunk, command_buffer)).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Failed to spawn command recorder thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            workers,
+            workers_lost: Cell::new(false),
+        }
+    }
+
+    // The sorted commands of the items inside the view frustum.
+    pub fn record(&self, items: Vec<RecordItem>, view_projection: &Mat4) -> CommandBuffer {
+        let jobs = match self.jobs.as_ref() {
+            Some(jobs) if items.len() >= MIN_ITEMS_PER_JOB * 2 && !self.workers_lost.get() => jobs,
+            _ => return record_sorted(&items, view_projection),
+        };
+
+        let item_count = items.len();
+        let chunk_size = ((item_count + RECORDER_THREAD_COUNT - 1) / RECORDER_THREAD_COUNT)
+            .max(MIN_ITEMS_PER_JOB);
+        let items = Arc::new(items);
+
+        let ranges = (0..item_count)
+            .step_by(chunk_size)
+            .map(|start| start..(start + chunk_size).min(item_count))
+            .collect::<Vec<_>>();
+        let mut chunks: Vec<Option<CommandBuffer>> = ranges.iter().map(|_| None).collect();
+
+        for (chunk, range) in ranges.iter().enumerate() {
+            // Record on this thread if the workers are gone.
+            if let Err(mpsc::SendError(job)) = jobs.send(RecordJob {
+                items: Arc::clone(&items),
+                range: range.clone(),
+                view_projection: *view_projection,
+                chunk,
+            }) {
+                chunks[chunk] = Some(record_sorted(&items[job.range], view_projection));
+            }
+        }
+
+        while chunks.iter().any(Option::is_none) {
+            match self.results.recv_timeout(WORKER_POLL_INTERVAL) {
+                Ok((chunk, chunk_buffer)) => chunks[chunk] = Some(chunk_buffer),
+                Err(RecvTimeoutError::Timeout)
+                    if !self.workers.iter().any(JoinHandle::is_finished) => {}
+                // A worker panicked and its chunk will never arrive. Results of the other
+                // workers that are still in flight are never read.
+                Err(_) => {
+                    log::error!("Command recorder thread stopped, recording on the calling thread");
+                    self.workers_lost.set(true);
+
+                    for (chunk, range) in ranges.iter().enumerate() {
+                        if chunks[chunk].is_none() {
+                            chunks[chunk] =
+                                Some(record_sorted(&items[range.clone()], view_projection));
+                        }
+                    }
+                }
+            }
+        }
+
+        // In chunk order, so equal keys keep the order of the items.
+        CommandBuffer::merge(chunks.into_iter().flatten().collect())
+    }
+}
+
+impl Default for CommandRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for CommandRecorder {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers once they finish their current job.
+        self.jobs = None;
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
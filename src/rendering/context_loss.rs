@@ -0,0 +1,70 @@
+use crate::core::asset::embedded::EmbeddedAssets;
+use crate::rendering::{
+    async_pipeline::release_fallback_pipeline, mesh::FullscreenMesh,
+    postprocess::release_fullscreen_vertex_shader, state::StateManager,
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::cell::Cell;
+
+thread_local! {
+    // How many times the context of the thread was lost and replaced.
+    static GENERATION: Cell<u32> = Cell::new(0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResetCause {
+    // The application caused the reset, e.g. with a draw that ran for too long.
+    Guilty,
+    // Another application or the driver itself did.
+    Innocent,
+    Unknown,
+}
+
+// Detects a lost context after a driver reset or a GPU hang, through the robustness of GL 4.5.
+// A lost context ignores every call, so the application replaces it: the engine objects of the
+// old one are released while it is still current, and the assets are uploaded again to the new
+// one, see Application::run. Contexts created without robustness are never reported lost.
+pub struct ContextLoss;
+
+impl ContextLoss {
+    // Whether the context current on the thread reports resets.
+    pub fn supported() -> bool {
+        let mut strategy: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::RESET_NOTIFICATION_STRATEGY, &mut strategy) }
+
+        strategy as GLenum == gl::LOSE_CONTEXT_ON_RESET
+    }
+
+    // The cause of the reset once the context current on the thread was lost. Once per frame, it
+    // is cheap but not free.
+    pub fn check() -> Option<ResetCause> {
+        match unsafe { gl::GetGraphicsResetStatus() } {
+            gl::NO_ERROR => None,
+            gl::GUILTY_CONTEXT_RESET => Some(ResetCause::Guilty),
+            gl::INNOCENT_CONTEXT_RESET => Some(ResetCause::Innocent),
+            _ => Some(ResetCause::Unknown),
+        }
+    }
+
+    // Changes whenever the context was replaced. Code that keeps GL objects outside of the
+    // assets and scenes compares it to tell when to create them again.
+    pub fn generation() -> u32 {
+        GENERATION.with(Cell::get)
+    }
+
+    // Forgets the objects the engine shares between scenes, with the lost context current.
+    pub(crate) fn release_engine_objects() {
+        FullscreenMesh::forget();
+        release_fullscreen_vertex_shader();
+        EmbeddedAssets::release_brdf_lut();
+        release_fallback_pipeline();
+        StateManager::invalidate();
+    }
+
+    // The new context is current and initialized.
+    pub(crate) fn restored() {
+        GENERATION.with(|generation| generation.set(generation.get() + 1));
+        StateManager::invalidate();
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Mat4, Vec2, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        buffer::BufferTarget,
+        error::RendererError,
+        frame_stats::FrameStats,
+        mesh::PrimitiveMode,
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        shader::ShaderStage,
+        state::{BlendState, DepthFunction, DepthStencilState, RasterizerState, StateManager},
+        streaming_buffer::StreamingBuffer,
+        vertex_layout::{VertexAttribute, VertexFormat, VertexLayout},
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::{f32::consts::PI, mem};
+
+// Per frame vertex budget. Primitives past it are dropped with a warning.
+const FRAME_BUFFER_SIZE: isize = 1024 * 1024;
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct DebugVertex {
+    pub position: Vec3,
+    pub color: Vec4,
+}
+
+impl DebugVertex {
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at_offset(
+                VertexAttribute::Position,
+                VertexFormat::Float3,
+                offset_of!(DebugVertex, position) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Color,
+                VertexFormat::Float4,
+                offset_of!(DebugVertex, color) as u32,
+            )
+            .with_stride(mem::size_of::<DebugVertex>() as u32)
+    }
+}
+
+struct DebugText {
+    position: Vec3,
+    text: String,
+    color: [f32; 4],
+}
+
+// Immediate mode debug primitives. Everything added during a frame is drawn by render() and
+// discarded afterwards, text is drawn through imgui by draw_text().
+pub struct DebugDraw {
+    pipeline_state: PipelineState,
+    vertex_buffer: StreamingBuffer,
+    vao: GLuint,
+    depth_tested_lines: Vec<DebugVertex>,
+    overlay_lines: Vec<DebugVertex>,
+    texts: Vec<DebugText>,
+    depth_test: bool,
+    enabled: bool,
+}
+
+impl DebugDraw {
+    pub fn new() -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/debug_draw.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/debug_draw.frag",
+            )?)
+            .build()?;
+
+        let pipeline_state = PipelineStateBuilder::new(program_pipeline)
+            .depth_stencil(DepthStencilState {
+                depth_write: false,
+                depth_function: DepthFunction::LessOrEqual,
+                ..Default::default()
+            })
+            .blend(Some(BlendState::alpha_blending()))
+            .rasterizer(RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            })
+            .build();
+
+        let vertex_buffer =
+            StreamingBuffer::new("Debug Draw Buffer", FRAME_BUFFER_SIZE, BufferTarget::Array);
+
+        let mut vao: GLuint = 0;
+        unsafe { gl::CreateVertexArrays(1, &mut vao) }
+        DebugVertex::layout().apply(vao, 0);
+
+        Ok(Self {
+            pipeline_state,
+            vertex_buffer,
+            vao,
+            depth_tested_lines: Vec::new(),
+            overlay_lines: Vec::new(),
+            texts: Vec::new(),
+            depth_test: true,
+            enabled: false,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled
+    }
+
+    // Applies to the primitives added afterwards. Overlay primitives are drawn on top of the
+    // scene.
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+
+        let lines = if self.depth_test {
+            &mut self.depth_tested_lines
+        } else {
+            &mut self.overlay_lines
+        };
+
+        lines.push(DebugVertex {
+            position: from,
+            color,
+        });
+        lines.push(DebugVertex {
+            position: to,
+            color,
+        });
+    }
+
+    // Axis aligned box.
+    pub fn wire_box(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        self.wire_box_transformed(&Mat4::identity(), min, max, color)
+    }
+
+    pub fn wire_box_transformed(&mut self, transform: &Mat4, min: Vec3, max: Vec3, color: Vec4) {
+        let corners = [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+        ];
+
+        let mut transformed = [Vec3::zeros(); 8];
+        for (i, corner) in corners.iter().enumerate() {
+            transformed[i] = transform_point(transform, corner);
+        }
+
+        self.box_edges(&transformed, color)
+    }
+
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        let axes = [
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0)),
+        ];
+
+        for (u, v) in axes.iter() {
+            self.circle(center, u * radius, v * radius, color)
+        }
+    }
+
+    // Red, green and blue lines for the x, y and z axes of the transform.
+    pub fn axes(&mut self, transform: &Mat4, size: f32) {
+        let origin = transform_point(transform, &Vec3::zeros());
+
+        let axes = [
+            (Vec3::new(size, 0.0, 0.0), Vec4::new(1.0, 0.0, 0.0, 1.0)),
+            (Vec3::new(0.0, size, 0.0), Vec4::new(0.0, 1.0, 0.0, 1.0)),
+            (Vec3::new(0.0, 0.0, size), Vec4::new(0.0, 0.0, 1.0, 1.0)),
+        ];
+
+        for (axis, color) in axes.iter() {
+            self.line(origin, transform_point(transform, axis), *color)
+        }
+    }
+
+    // Draws the volume of a camera, light or shadow cascade given its view projection matrix.
+    pub fn frustum(&mut self, view_projection: &Mat4, color: Vec4) {
+        let inverse_view_projection = match view_projection.try_inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let mut corners = [Vec3::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let x = if i & 1 == 0 { -1.0 } else { 1.0 };
+            let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+            let z = if i & 4 == 0 { -1.0 } else { 1.0 };
+
+            *corner = transform_point(&inverse_view_projection, &Vec3::new(x, y, z));
+        }
+
+        // Reorder to match the winding of box_edges.
+        self.box_edges(
+            &[
+                corners[0], corners[1], corners[3], corners[2], corners[4], corners[5], corners[7],
+                corners[6],
+            ],
+            color,
+        )
+    }
+
+    // Text is anchored at a world space position and always faces the camera.
+    pub fn text(&mut self, position: Vec3, text: &str, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+
+        self.texts.push(DebugText {
+            position,
+            text: text.to_string(),
+            color: [color.x, color.y, color.z, color.w],
+        })
+    }
+
+    // Draws the lines added this frame. Expects the per frame block (binding 0) to be filled.
+    pub fn render(&mut self) {
+        let vertex_count = self.depth_tested_lines.len() + self.overlay_lines.len();
+
+        if !self.enabled || vertex_count == 0 {
+            self.depth_tested_lines.clear();
+            self.overlay_lines.clear();
+            return;
+        }
+
+        self.vertex_buffer.begin_frame();
+
+        let depth_tested = self.vertex_buffer.push_slice(&self.depth_tested_lines);
+        let overlay = self.vertex_buffer.push_slice(&self.overlay_lines);
+
+        self.pipeline_state.bind();
+
+        StateManager::bind_vertex_array(self.vao);
+
+        if let Some(allocation) = depth_tested {
+            self.draw_lines(allocation.offset, self.depth_tested_lines.len());
+        }
+
+        if let Some(allocation) = overlay {
+            self.pipeline_state
+                .fixed_function_state_mut()
+                .depth_stencil
+                .depth_test = false;
+            self.pipeline_state.bind();
+
+            self.draw_lines(allocation.offset, self.overlay_lines.len());
+
+            self.pipeline_state
+                .fixed_function_state_mut()
+                .depth_stencil
+                .depth_test = true;
+        }
+
+        self.pipeline_state.unbind();
+        self.vertex_buffer.end_frame();
+
+        self.depth_tested_lines.clear();
+        self.overlay_lines.clear();
+    }
+
+    // Draws the text added this frame on top of everything.
+    pub fn draw_text(&mut self, ui: &Ui, view_projection: &Mat4, viewport_size: Vec2) {
+        let draw_list = ui.get_foreground_draw_list();
+
+        for text in self.texts.drain(..) {
+            let clip =
+                view_projection * Vec4::new(text.position.x, text.position.y, text.position.z, 1.0);
+
+            // Behind the camera.
+            if clip.w <= 0.0 {
+                continue;
+            }
+
+            let ndc = Vec2::new(clip.x / clip.w, clip.y / clip.w);
+            let screen = [
+                (ndc.x * 0.5 + 0.5) * viewport_size.x,
+                (0.5 - ndc.y * 0.5) * viewport_size.y,
+            ];
+
+            draw_list.add_text(screen, text.color, &text.text);
+        }
+    }
+
+    fn draw_lines(&self, offset: isize, vertex_count: usize) {
+        if vertex_count == 0 {
+            return;
+        }
+
+        unsafe {
+            gl::VertexArrayVertexBuffer(
+                self.vao,
+                0,
+                self.vertex_buffer.get_buffer().get_id(),
+                offset,
+                mem::size_of::<DebugVertex>() as i32,
+            );
+            gl::DrawArrays(gl::LINES, 0, vertex_count as i32);
+        }
+
+        FrameStats::record_draw(PrimitiveMode::Lines, vertex_count, 1)
+    }
+
+    // Ellipse around the center through center + u and center + v.
+    pub fn circle(&mut self, center: Vec3, u: Vec3, v: Vec3, color: Vec4) {
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+            center + u * angle.cos() + v * angle.sin()
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color)
+        }
+    }
+
+    // Corners 0-3 form the near face and 4-7 the far face, both in the same winding.
+    fn box_edges(&mut self, corners: &[Vec3; 8], color: Vec4) {
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+
+            self.line(corners[i], corners[next], color);
+            self.line(corners[i + 4], corners[next + 4], color);
+            self.line(corners[i], corners[i + 4], color);
+        }
+    }
+}
+
+impl Drop for DebugDraw {
+    fn drop(&mut self) {
+        StateManager::vertex_array_deleted(self.vao);
+        unsafe { gl::DeleteVertexArrays(1, &self.vao) }
+    }
+}
+
+impl Gui for DebugDraw {
+    fn gui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Debug Draw"), &mut self.enabled);
+    }
+}
+
+fn transform_point(transform: &Mat4, point: &Vec3) -> Vec3 {
+    let p = transform * Vec4::new(point.x, point.y, point.z, 1.0);
+    Vec3::new(p.x, p.y, p.z) / p.w
+}
@@ -0,0 +1,147 @@
+use crate::{
+    imgui::{im_str, ImStr, Ui},
+    rendering::state::{BlendState, PolygonMode, StateManager},
+};
+
+// What the lighting shaders output instead of the lit image. Every view maps to a render mode
+// of the shaders, some also override the fixed function state of the draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    Lit,
+    Albedo,
+    Metallic,
+    Roughness,
+    Normals,
+    Tangents,
+    Uv,
+    NdotV,
+    Ao,
+    SpecularAo,
+    HorizonSpecularAo,
+    DiffuseAmbient,
+    SpecularAmbient,
+    LightCount,
+    // Every layer of geometry adds to the color, hot spots are drawn many times over.
+    Overdraw,
+    Wireframe,
+    // The shadow map view of the first shadowed light that reaches the fragment, with a checker
+    // of its texels.
+    ShadowViews,
+    // The mip level the albedo map is sampled at, blue for the full resolution.
+    MipLevel,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 18] = [
+        DebugView::Lit,
+        DebugView::Albedo,
+        DebugView::Metallic,
+        DebugView::Roughness,
+        DebugView::Normals,
+        DebugView::Tangents,
+        DebugView::Uv,
+        DebugView::NdotV,
+        DebugView::Ao,
+        DebugView::SpecularAo,
+        DebugView::HorizonSpecularAo,
+        DebugView::DiffuseAmbient,
+        DebugView::SpecularAmbient,
+        DebugView::LightCount,
+        DebugView::Overdraw,
+        DebugView::Wireframe,
+        DebugView::ShadowViews,
+        DebugView::MipLevel,
+    ];
+
+    pub fn name(&self) -> &'static ImStr {
+        match self {
+            DebugView::Lit => im_str!("Lit"),
+            DebugView::Albedo => im_str!("Albedo"),
+            DebugView::Metallic => im_str!("Metallic"),
+            DebugView::Roughness => im_str!("Roughness"),
+            DebugView::Normals => im_str!("Normals"),
+            DebugView::Tangents => im_str!("Tangents"),
+            DebugView::Uv => im_str!("UV"),
+            DebugView::NdotV => im_str!("NdotV"),
+            DebugView::Ao => im_str!("AO"),
+            DebugView::SpecularAo => im_str!("Specular AO"),
+            DebugView::HorizonSpecularAo => im_str!("Horizon Specular AO"),
+            DebugView::DiffuseAmbient => im_str!("Diffuse Ambient"),
+            DebugView::SpecularAmbient => im_str!("Specular Ambient"),
+            DebugView::LightCount => im_str!("Light Count"),
+            DebugView::Overdraw => im_str!("Overdraw"),
+            DebugView::Wireframe => im_str!("Wireframe"),
+            DebugView::ShadowViews => im_str!("Shadow Views"),
+            DebugView::MipLevel => im_str!("Mip Level"),
+        }
+    }
+
+    // The renderMode of the lighting shaders.
+    pub fn render_mode(&self) -> i32 {
+        match self {
+            DebugView::Lit | DebugView::Wireframe => 0,
+            DebugView::Albedo => 1,
+            DebugView::Metallic => 2,
+            DebugView::Roughness => 3,
+            DebugView::Normals => 4,
+            DebugView::Tangents => 5,
+            DebugView::Uv => 6,
+            DebugView::NdotV => 7,
+            DebugView::Ao => 8,
+            DebugView::SpecularAo => 9,
+            DebugView::HorizonSpecularAo => 10,
+            DebugView::DiffuseAmbient => 11,
+            DebugView::SpecularAmbient => 12,
+            DebugView::LightCount => 13,
+            DebugView::Overdraw => 14,
+            DebugView::ShadowViews => 15,
+            DebugView::MipLevel => 16,
+        }
+    }
+
+    // The sky would cover everything while overdraw leaves the depth buffer empty.
+    pub fn draws_sky(&self) -> bool {
+        *self != DebugView::Overdraw
+    }
+
+    // Overrides the fixed function state a material just bound, for the views that need to.
+    pub fn apply_state_override(&self) {
+        let mut state = match StateManager::current_state() {
+            Some(state) => state,
+            None => return,
+        };
+
+        match self {
+            DebugView::Overdraw => {
+                state.depth_stencil.depth_test = false;
+                state.depth_stencil.depth_write = false;
+                state.blend = Some(BlendState::additive());
+            }
+            DebugView::Wireframe => {
+                state.rasterizer.polygon_mode = PolygonMode::Line;
+                state.rasterizer.face_culling = None;
+            }
+            _ => return,
+        }
+
+        StateManager::apply(&state)
+    }
+
+    // A combo box over every view. True if the selection changed.
+    pub fn combo(ui: &Ui, view: &mut DebugView) -> bool {
+        let mut index = DebugView::ALL.iter().position(|v| v == view).unwrap_or(0);
+        let names: Vec<&ImStr> = DebugView::ALL.iter().map(|v| v.name()).collect();
+
+        let changed =
+            imgui::ComboBox::new(im_str!("Debug View")).build_simple_string(ui, &mut index, &names);
+
+        *view = DebugView::ALL[index];
+        changed
+    }
+}
+
+impl Default for DebugView {
+    fn default() -> Self {
+        DebugView::Lit
+    }
+}
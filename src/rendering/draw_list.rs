@@ -0,0 +1,93 @@
+use crate::{
+    core::asset::Handle,
+    core::ecs::{components::SharedMaterial, Entity},
+    core::math::Mat4,
+    rendering::{
+        command_buffer::{DrawResources, RecordItem},
+        hiz::HiZBuffer,
+        mesh::Mesh,
+    },
+};
+
+pub struct DrawItem {
+    pub mesh: Handle<Mesh>,
+    pub material: SharedMaterial,
+    pub model: Mat4,
+    // Set for draws that can be picked.
+    pub entity: Option<Entity>,
+}
+
+// The draws of a frame. Filled by systems::collect_draws or by hand.
+#[derive(Default)]
+pub struct DrawList {
+    items: Vec<DrawItem>,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, item: DrawItem) {
+        self.items.push(item)
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear()
+    }
+
+    pub fn items(&self) -> &[DrawItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Orders the draws by their sort keys, as a recorded command buffer would submit them.
+    // Their meshes and materials are added to the resources.
+    pub fn sort(&mut self, resources: &mut DrawResources, view_projection: &Mat4) {
+        let keys = self
+            .record_items(resources)
+            .iter()
+            .map(|item| item.sort_key(view_projection))
+            .collect::<Vec<_>>();
+
+        let mut items = self.items.drain(..).zip(keys).collect::<Vec<_>>();
+        items.sort_by_key(|(_, key)| *key);
+
+        self.items = items.into_iter().map(|(item, _)| item).collect();
+    }
+
+    // Drops the draws whose bounds are hidden behind the depth of the Hi-Z buffer. Returns the
+    // number of culled draws.
+    pub fn cull_occluded(&mut self, hiz: &HiZBuffer) -> usize {
+        let count = self.items.len();
+
+        self.items
+            .retain(|item| hiz.is_visible(&item.mesh.bounds().transform(&item.model)));
+
+        count - self.items.len()
+    }
+
+    // The draws as plain data for a CommandRecorder. Their meshes and materials are added to the
+    // resources the recorded commands are submitted with.
+    pub fn record_items(&self, resources: &mut DrawResources) -> Vec<RecordItem> {
+        self.items
+            .iter()
+            .map(|item| {
+                RecordItem::new(
+                    resources,
+                    &item.mesh,
+                    &item.material,
+                    item.model,
+                    item.entity,
+                )
+            })
+            .collect()
+    }
+}
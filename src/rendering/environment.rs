@@ -0,0 +1,373 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Mat4, Vec3, Vec4},
+    imgui::{im_str, Gui, ImString, Ui},
+    rendering::{
+        error::RendererError,
+        framebuffer::Framebuffer,
+        mesh::FULLSCREEN_MESH,
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{DepthStencilState, FixedFunctionState, RasterizerState, StateManager},
+        texture::{SizedTextureFormat, Texture2D, TextureCube},
+        Draw,
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use nalgebra_glm as glm;
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+const MIN_SKYBOX_SIZE: u32 = 256;
+const MAX_SKYBOX_SIZE: u32 = 1024;
+const RADIANCE_SIZE: u32 = 128;
+// Levels 0 to MAX_REFLECTION_LOD of the roughness lookup of the PBS shaders.
+const RADIANCE_LEVELS: i32 = 6;
+const IRRADIANCE_SIZE: u32 = 32;
+
+// The +X, -X, +Y, -Y, +Z, -Z faces of the GL cube map layout.
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentSettings {
+    // Of the panorama around the vertical axis, in degrees.
+    pub rotation: f32,
+    // Scales the radiance of the panorama to the units of the scene.
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+// The cube maps baked from the panorama.
+pub struct EnvironmentMaps {
+    pub skybox: TextureCube,
+    // GGX prefiltered per level, for the specular IBL term.
+    pub radiance: TextureCube,
+    // For the diffuse IBL term.
+    pub irradiance: TextureCube,
+}
+
+// An equirectangular HDR panorama loaded at runtime, baked into the skybox and the image based
+// lighting maps. The maps are baked again whenever the settings change, so the sky and the
+// materials sampling them update live.
+pub struct HdrEnvironment {
+    equirectangular_pipeline_state: PipelineState,
+    prefilter_pipeline_state: PipelineState,
+    irradiance_pipeline_state: PipelineState,
+    sampler_equirectangular: Sampler,
+    sampler_linear: Sampler,
+    path: Option<PathBuf>,
+    panorama: Option<Texture2D>,
+    maps: Option<EnvironmentMaps>,
+    settings: EnvironmentSettings,
+    // The settings the maps were baked with.
+    baked: Option<EnvironmentSettings>,
+    capture_framebuffer: GLuint,
+    path_input: ImString,
+    load_error: Option<String>,
+}
+
+impl HdrEnvironment {
+    pub fn new() -> Result<Self, RendererError> {
+        let load = |fragment_shader: &str| -> Result<PipelineState, RendererError> {
+            let program_pipeline = ProgramPipeline::new()
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Vertex,
+                    "src/rendering/shaders/sky.vert",
+                )?)
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Fragment,
+                    fragment_shader,
+                )?)
+                .build()?;
+
+            Ok(PipelineStateBuilder::new(program_pipeline)
+                .depth_stencil(DepthStencilState {
+                    depth_test: false,
+                    depth_write: false,
+                    ..Default::default()
+                })
+                .rasterizer(RasterizerState {
+                    face_culling: None,
+                    ..Default::default()
+                })
+                .build())
+        };
+
+        let equirectangular_pipeline_state =
+            load("src/rendering/shaders/equirectangular_to_cube.frag")?;
+        let prefilter_pipeline_state = load("src/rendering/shaders/environment_prefilter.frag")?;
+        let irradiance_pipeline_state = load("src/rendering/shaders/sky_irradiance.frag")?;
+
+        let mut capture_framebuffer: GLuint = 0;
+        unsafe { gl::CreateFramebuffers(1, &mut capture_framebuffer) }
+
+        Ok(Self {
+            equirectangular_pipeline_state,
+            prefilter_pipeline_state,
+            irradiance_pipeline_state,
+            // Wraps around the vertical axis, the seam of the panorama.
+            sampler_equirectangular: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::Repeat,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            sampler_linear: Sampler::new(
+                MinificationFilter::LinearMipmapLinear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            path: None,
+            panorama: None,
+            maps: None,
+            settings: EnvironmentSettings::default(),
+            baked: None,
+            capture_framebuffer,
+            path_input: ImString::with_capacity(256),
+            load_error: None,
+        })
+    }
+
+    // Loads an equirectangular Radiance HDR file. Its maps are baked at the next update, the
+    // current ones are kept if it fails to load.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let panorama = Texture2D::new_from_hdr(path.as_ref())?;
+        panorama.set_label(&path.as_ref().display().to_string());
+
+        let mut width: GLint = 0;
+        unsafe {
+            gl::GetTextureLevelParameteriv(panorama.get_id(), 0, gl::TEXTURE_WIDTH, &mut width)
+        }
+
+        // A face covers a quarter of the panorama around the horizon.
+        let skybox_size = (width as u32 / 4)
+            .next_power_of_two()
+            .max(MIN_SKYBOX_SIZE)
+            .min(MAX_SKYBOX_SIZE);
+        // Mipmapped, the prefiltering of rough levels samples the lower mips.
+        let skybox_levels = (skybox_size as f32).log2() as i32 + 1;
+
+        self.maps = Some(EnvironmentMaps {
+            skybox: TextureCube::new_empty(skybox_size, SizedTextureFormat::Rgba16f, skybox_levels),
+            radiance: TextureCube::new_empty(
+                RADIANCE_SIZE,
+                SizedTextureFormat::Rgba16f,
+                RADIANCE_LEVELS,
+            ),
+            irradiance: TextureCube::new_empty(IRRADIANCE_SIZE, SizedTextureFormat::Rgba16f, 1),
+        });
+        self.panorama = Some(panorama);
+        self.path = Some(path.as_ref().to_path_buf());
+        self.baked = None;
+
+        log::info!("Loaded HDR environment {:?}.", path.as_ref());
+
+        Ok(())
+    }
+
+    // Bakes the maps if the settings changed since they were baked or a rebake was requested.
+    // Call it before the maps are drawn or used for lighting. Returns whether they were baked.
+    pub fn update(&mut self) -> bool {
+        let (panorama, maps) = match (&self.panorama, &self.maps) {
+            (Some(panorama), Some(maps)) => (panorama, maps),
+            _ => return false,
+        };
+
+        if self.baked == Some(self.settings) {
+            return false;
+        }
+
+        self.bake_skybox(panorama, &maps.skybox);
+        self.bake_radiance(&maps.skybox, &maps.radiance);
+        self.bake_irradiance(&maps.skybox, &maps.irradiance);
+
+        StateManager::apply(&FixedFunctionState::default());
+        self.baked = Some(self.settings);
+
+        true
+    }
+
+    // Bakes the maps again at the next update, e.g. after the baking shaders were edited.
+    pub fn rebake(&mut self) {
+        self.baked = None
+    }
+
+    // None until a panorama was loaded.
+    pub fn maps(&self) -> Option<&EnvironmentMaps> {
+        self.maps.as_ref()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn settings(&self) -> &EnvironmentSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: EnvironmentSettings) {
+        self.settings = settings
+    }
+
+    fn bake_skybox(&self, panorama: &Texture2D, skybox: &TextureCube) {
+        let pipeline_state = &self.equirectangular_pipeline_state;
+
+        pipeline_state.bind();
+        pipeline_state
+            .program_pipeline()
+            .set_texture_2d(0, panorama, &self.sampler_equirectangular)
+            .set_float_all_stages("rotation", self.settings.rotation.to_radians())
+            .set_float_all_stages("intensity", self.settings.intensity);
+        self.capture(skybox, 0, pipeline_state);
+        pipeline_state.unbind();
+
+        unsafe { gl::GenerateTextureMipmap(skybox.get_id()) }
+    }
+
+    fn bake_radiance(&self, skybox: &TextureCube, radiance: &TextureCube) {
+        let pipeline_state = &self.prefilter_pipeline_state;
+
+        pipeline_state.bind();
+        pipeline_state
+            .program_pipeline()
+            .set_texture_cube(0, skybox, &self.sampler_linear)
+            .set_float_all_stages("environmentSize", Self::size(skybox, 0) as f32);
+
+        for level in 0..RADIANCE_LEVELS {
+            // Inverse of the roughness lookup of the PBS shaders,
+            // lod = MAX_REFLECTION_LOD * roughness * (2 - roughness).
+            let lod = level as f32 / (RADIANCE_LEVELS - 1) as f32;
+            pipeline_state
+                .program_pipeline()
+                .set_float_all_stages("perceptualRoughness", 1.0 - (1.0 - lod).sqrt());
+            self.capture(radiance, level, pipeline_state);
+        }
+
+        pipeline_state.unbind();
+    }
+
+    fn bake_irradiance(&self, skybox: &TextureCube, irradiance: &TextureCube) {
+        let pipeline_state = &self.irradiance_pipeline_state;
+
+        // The level of the skybox about the size of the irradiance map, so the convolution
+        // doesn't skip over its detail.
+        let sample_lod = (Self::size(skybox, 0) as f32 / IRRADIANCE_SIZE as f32).log2();
+
+        pipeline_state.bind();
+        pipeline_state
+            .program_pipeline()
+            .set_texture_cube(0, skybox, &self.sampler_linear)
+            .set_float_all_stages("sampleLod", sample_lod.max(0.0));
+        self.capture(irradiance, 0, pipeline_state);
+        pipeline_state.unbind();
+    }
+
+    // Renders every face of the level of the cube map with the matrix of the face.
+    fn capture(&self, target: &TextureCube, level: i32, pipeline_state: &PipelineState) {
+        let projection = glm::perspective(1.0, 90.0f32.to_radians(), 0.1, 10.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let size = Self::size(target, level);
+
+        StateManager::bind_framebuffer(self.capture_framebuffer);
+        StateManager::set_viewport(0, 0, size, size);
+
+        for (face, (direction, up)) in FACES.iter().enumerate() {
+            let view = glm::look_at(&origin, &Vec3::from(*direction), &Vec3::from(*up));
+            let inverse_view_projection = (projection * view)
+                .try_inverse()
+                .unwrap_or_else(Mat4::identity);
+
+            unsafe {
+                gl::NamedFramebufferTextureLayer(
+                    self.capture_framebuffer,
+                    gl::COLOR_ATTACHMENT0,
+                    target.get_id(),
+                    level,
+                    face as i32,
+                );
+            }
+
+            pipeline_state
+                .program_pipeline()
+                .set_mat4_all_stages("inverseViewProjection", &inverse_view_projection);
+            FULLSCREEN_MESH.draw();
+        }
+
+        StateManager::bind_framebuffer(Framebuffer::default_id());
+    }
+
+    fn size(texture: &TextureCube, level: i32) -> i32 {
+        let mut size: GLint = 0;
+        unsafe {
+            gl::GetTextureLevelParameteriv(texture.get_id(), level, gl::TEXTURE_WIDTH, &mut size)
+        }
+        size
+    }
+}
+
+impl Drop for HdrEnvironment {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.capture_framebuffer) }
+    }
+}
+
+impl Gui for HdrEnvironment {
+    fn gui(&mut self, ui: &Ui) {
+        ui.input_text(im_str!("HDR"), &mut self.path_input).build();
+
+        if ui.button(im_str!("Load"), [0.0, 0.0]) {
+            let path = self.path_input.to_str().to_string();
+            self.load_error = self.load(&path).err();
+        }
+
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("Rebake"), [0.0, 0.0]) {
+            self.rebake()
+        }
+
+        if let Some(error) = &self.load_error {
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+        } else if let Some(path) = &self.path {
+            ui.text(path.display().to_string());
+        } else {
+            ui.text_disabled("No environment loaded");
+        }
+
+        imgui::Slider::new(im_str!("Rotation"))
+            .range(RangeInclusive::new(-180.0, 180.0))
+            .display_format(im_str!("%.1f deg"))
+            .build(&ui, &mut self.settings.rotation);
+        imgui::Slider::new(im_str!("Intensity"))
+            .range(RangeInclusive::new(0.0, 4.0))
+            .display_format(im_str!("%.2f"))
+            .build(&ui, &mut self.settings.intensity);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::rendering::{
+    framebuffer::FramebufferError, program_pipeline::PipelineError, shader::ShaderStage,
+};
+use std::{error::Error, fmt, path::PathBuf};
+
+// The errors of the renderer. Wraps the errors of the GL object it failed to create, so
+// applications can tell a missing asset from a broken shader and fall back instead of exiting.
+// Converts into the String errors of the asset loaders.
+#[derive(Debug)]
+pub enum RendererError {
+    // Neither a file nor embedded in the engine.
+    MissingAsset(PathBuf),
+    Shader {
+        stage: ShaderStage,
+        path: PathBuf,
+        log: String,
+    },
+    Pipeline(PipelineError),
+    Framebuffer(FramebufferError),
+    MissingAttachment {
+        framebuffer: String,
+        index: usize,
+    },
+    TerrainLayerCount {
+        count: usize,
+        max: usize,
+    },
+    // An image that failed to load or to turn into a texture.
+    Texture {
+        name: String,
+        log: String,
+    },
+}
+
+impl Error for RendererError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RendererError::Pipeline(e) => Some(e),
+            RendererError::Framebuffer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::MissingAsset(path) => {
+                write!(f, "{:?} doesn't exist and has no embedded copy.", path)
+            }
+            RendererError::Shader { stage, path, log } => {
+                write!(
+                    f,
+                    "Failed to create {:?} shader {:?}:\n{}",
+                    stage, path, log
+                )
+            }
+            RendererError::Pipeline(e) => write!(f, "{}", e),
+            RendererError::Framebuffer(e) => write!(f, "{}", e),
+            RendererError::MissingAttachment { framebuffer, index } => write!(
+                f,
+                "Framebuffer {} has no texture attachment {}.",
+                framebuffer, index
+            ),
+            RendererError::TerrainLayerCount { count, max } => write!(
+                f,
+                "A terrain material needs 1 to {} layers, got {}.",
+                max, count
+            ),
+            RendererError::Texture { name, log } => {
+                write!(f, "Failed to create texture {}: {}", name, log)
+            }
+        }
+    }
+}
+
+impl From<PipelineError> for RendererError {
+    fn from(e: PipelineError) -> Self {
+        RendererError::Pipeline(e)
+    }
+}
+
+impl From<FramebufferError> for RendererError {
+    fn from(e: FramebufferError) -> Self {
+        RendererError::Framebuffer(e)
+    }
+}
+
+impl From<RendererError> for String {
+    fn from(e: RendererError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,75 @@
+use gl::types::*;
+use gl_bindings as gl;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FenceWaitResult {
+    Signaled,
+    TimedOut,
+    Failed,
+}
+
+// Signals once every GPU command issued before its creation has completed.
+pub struct GpuFence {
+    sync: GLsync,
+}
+
+impl GpuFence {
+    pub fn new() -> Self {
+        Self {
+            sync: unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) },
+        }
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        let mut status: GLint = 0;
+
+        unsafe {
+            gl::GetSynciv(
+                self.sync,
+                gl::SYNC_STATUS,
+                1,
+                std::ptr::null_mut(),
+                &mut status,
+            )
+        }
+
+        status == gl::SIGNALED as i32
+    }
+
+    // Blocks the CPU for at most timeout_ns nanoseconds. Pending commands are flushed so the
+    // fence is guaranteed to eventually signal.
+    pub fn client_wait(&self, timeout_ns: u64) -> FenceWaitResult {
+        match unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, timeout_ns) } {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => FenceWaitResult::Signaled,
+            gl::TIMEOUT_EXPIRED => FenceWaitResult::TimedOut,
+            _ => FenceWaitResult::Failed,
+        }
+    }
+
+    // Blocks until the fence signals.
+    pub fn wait(&self) -> FenceWaitResult {
+        loop {
+            match self.client_wait(1_000_000_000) {
+                FenceWaitResult::TimedOut => continue,
+                result => return result,
+            }
+        }
+    }
+
+    // Makes the GPU wait for the fence before executing further commands. Does not block the CPU.
+    pub fn gpu_wait(&self) {
+        unsafe { gl::WaitSync(self.sync, 0, gl::TIMEOUT_IGNORED) }
+    }
+}
+
+impl Default for GpuFence {
+    fn default() -> Self {
+        GpuFence::new()
+    }
+}
+
+impl Drop for GpuFence {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSync(self.sync) }
+    }
+}
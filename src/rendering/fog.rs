@@ -0,0 +1,161 @@
+use crate::{
+    color::srgb_to_linear3f,
+    core::math::{Vec2, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::buffer::{Buffer, BufferStorageFlags, BufferTarget},
+};
+use serde::{Deserialize, Serialize};
+use std::{mem, ops::RangeInclusive};
+
+// Uniform block binding of the FogBlock of the lighting shaders.
+pub const FOG_BINDING: u32 = 8;
+
+// Parameters of the fog of a scene, saved along with the scene file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightFogSettings {
+    pub enabled: bool,
+    // sRGB.
+    pub color: [f32; 3],
+    // Multiplies the color by the average of the environment, so the fog picks up the light of
+    // the sky.
+    pub environment_tint: bool,
+    // Extinction per world unit at the base height.
+    pub density: f32,
+    // How fast the density falls off above the base height, 0 is uniform distance fog.
+    pub height_falloff: f32,
+    pub base_height: f32,
+    // No fog closer than this to the camera.
+    pub start_distance: f32,
+    // Distant geometry stays this visible at least.
+    pub max_opacity: f32,
+}
+
+impl Default for HeightFogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: [1.0, 1.0, 1.0],
+            environment_tint: true,
+            density: 0.02,
+            height_falloff: 0.2,
+            base_height: 0.0,
+            start_distance: 0.0,
+            max_opacity: 1.0,
+        }
+    }
+}
+
+// Layout of the FogBlock of the lighting shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct FogUniforms {
+    // w: 1 to tint the color with the environment.
+    color: Vec4,
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    start_distance: f32,
+    max_opacity: f32,
+    enabled: i32,
+    _pad: Vec2,
+}
+
+// Exponential height fog. The lighting shaders integrate the density along the view ray
+// analytically and blend what they shade towards the fog color.
+pub struct HeightFog {
+    settings: HeightFogSettings,
+    buffer: Buffer,
+}
+
+impl HeightFog {
+    pub fn new(settings: HeightFogSettings) -> Self {
+        Self {
+            settings,
+            buffer: Buffer::new(
+                "Fog UBO",
+                mem::size_of::<FogUniforms>() as isize,
+                BufferTarget::Uniform,
+                BufferStorageFlags::DYNAMIC,
+            ),
+        }
+    }
+
+    pub fn settings(&self) -> &HeightFogSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut HeightFogSettings {
+        &mut self.settings
+    }
+
+    // Uploads the settings and binds them to the uniform block binding.
+    pub fn bind(&self, binding: u32) {
+        let settings = &self.settings;
+        let color = srgb_to_linear3f(&Vec3::from(settings.color));
+
+        self.buffer.fill(
+            0,
+            &FogUniforms {
+                color: Vec4::new(
+                    color.x,
+                    color.y,
+                    color.z,
+                    settings.environment_tint as i32 as f32,
+                ),
+                density: settings.density,
+                height_falloff: settings.height_falloff,
+                base_height: settings.base_height,
+                start_distance: settings.start_distance,
+                max_opacity: settings.max_opacity,
+                enabled: settings.enabled as i32,
+                _pad: Vec2::new(0.0, 0.0),
+            },
+        );
+        self.buffer.bind(binding)
+    }
+}
+
+impl Gui for HeightFog {
+    fn gui(&mut self, ui: &Ui) {
+        let settings = &mut self.settings;
+
+        ui.group(|| {
+            ui.checkbox(im_str!("##height_fog"), &mut settings.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Height Fog"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    imgui::ColorEdit::new(im_str!("Color##fog"), &mut settings.color).build(&ui);
+                    ui.checkbox(im_str!("Environment Tint"), &mut settings.environment_tint);
+                    imgui::Slider::new(im_str!("Density"))
+                        .range(RangeInclusive::new(0.0, 0.5))
+                        .display_format(im_str!("%.4f"))
+                        .build(&ui, &mut settings.density);
+                    imgui::Slider::new(im_str!("Height Falloff"))
+                        .range(RangeInclusive::new(0.0, 2.0))
+                        .display_format(im_str!("%.3f"))
+                        .build(&ui, &mut settings.height_falloff);
+                    imgui::Slider::new(im_str!("Base Height"))
+                        .range(RangeInclusive::new(-20.0, 20.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut settings.base_height);
+                    imgui::Slider::new(im_str!("Start Distance"))
+                        .range(RangeInclusive::new(0.0, 100.0))
+                        .display_format(im_str!("%.1f"))
+                        .build(&ui, &mut settings.start_distance);
+                    imgui::Slider::new(im_str!("Max Opacity"))
+                        .range(RangeInclusive::new(0.0, 1.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut settings.max_opacity);
+
+                    ui.unindent()
+                });
+        });
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    core::math::UVec2,
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        fence::GpuFence,
+        framebuffer::Framebuffer,
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use image::RgbaImage;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    ptr,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Frames copied into pixel buffers that have not been read back yet. Capturing waits for the
+// oldest one only when the GPU falls this many frames behind.
+const READBACKS_IN_FLIGHT: usize = 3;
+
+struct EncodeJob {
+    path: PathBuf,
+    image: RgbaImage,
+}
+
+// A frame copied into a pixel pack buffer by the GPU, on its way to the encoder.
+struct Readback {
+    buffer: Buffer,
+    size: UVec2,
+    path: PathBuf,
+    fence: GpuFence,
+}
+
+struct Recording {
+    directory: PathBuf,
+    frame: u32,
+}
+
+// Saves frames of the framebuffer standing in for the window as PNG files, single screenshots
+// or numbered sequences. The pixels are copied into pixel buffers the frame they are captured
+// and read back a few frames later, once the GPU is done with them. A worker thread encodes
+// the files, so neither stalls the render loop.
+pub struct FrameCapture {
+    readbacks: Vec<Readback>,
+    // Pixel buffers of finished readbacks, reused while the frame size does not change.
+    free_buffers: Vec<Buffer>,
+    screenshot: Option<PathBuf>,
+    recording: Option<Recording>,
+    // Where the gui saves screenshots and sequences.
+    output_directory: PathBuf,
+    jobs: Option<Sender<EncodeJob>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<EncodeJob>();
+
+        let worker = thread::Builder::new()
+            .name("Frame Capture Encoder".to_string())
+            .spawn(move || {
+                for job in job_receiver {
+                    if let Err(e) = job.image.save(&job.path) {
+                        log::error!("Failed to save frame capture {}: {}", job.path.display(), e)
+                    }
+                }
+            })
+            .expect("Failed to spawn frame capture encoder thread");
+
+        Self {
+            readbacks: vec![],
+            free_buffers: vec![],
+            screenshot: None,
+            recording: None,
+            output_directory: PathBuf::from("captures"),
+            jobs: Some(jobs),
+            worker: Some(worker),
+        }
+    }
+
+    // Saves the next captured frame to the path.
+    pub fn capture_frame<P: AsRef<Path>>(&mut self, path: P) {
+        self.screenshot = Some(path.as_ref().to_path_buf())
+    }
+
+    // Saves every captured frame into the directory as frame_00000.png, frame_00001.png, ...
+    pub fn start_recording<P: AsRef<Path>>(&mut self, directory: P) -> Result<(), String> {
+        fs::create_dir_all(directory.as_ref()).map_err(|e| e.to_string())?;
+
+        self.recording = Some(Recording {
+            directory: directory.as_ref().to_path_buf(),
+            frame: 0,
+        });
+
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recording = None
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn output_directory(&self) -> &Path {
+        &self.output_directory
+    }
+
+    pub fn set_output_directory<P: AsRef<Path>>(&mut self, directory: P) {
+        self.output_directory = directory.as_ref().to_path_buf()
+    }
+
+    // Call once per frame, after the frame is drawn and before the UI, with the size of the
+    // framebuffer standing in for the window. Hands the finished readbacks to the encoder and
+    // copies this frame if it was requested.
+    pub fn capture(&mut self, size: UVec2) {
+        self.collect(false);
+
+        let path = match (self.screenshot.take(), self.recording.as_mut()) {
+            (Some(path), _) => Some(path),
+            (None, Some(recording)) => {
+                recording.frame += 1;
+                Some(
+                    recording
+                        .directory
+                        .join(format!("frame_{:05}.png", recording.frame - 1)),
+                )
+            }
+            (None, None) => None,
+        };
+
+        if let Some(path) = path {
+            if self.readbacks.len() == READBACKS_IN_FLIGHT {
+                self.readbacks[0].fence.wait();
+                self.collect(false);
+            }
+
+            self.read_back(size, path)
+        }
+    }
+
+    fn read_back(&mut self, size: UVec2, path: PathBuf) {
+        let byte_size = (size.x * size.y * 4) as isize;
+
+        let buffer = match self
+            .free_buffers
+            .iter()
+            .position(|buffer| buffer.get_size() == byte_size)
+        {
+            Some(index) => self.free_buffers.swap_remove(index),
+            None => Buffer::new(
+                "Frame Capture PBO",
+                byte_size,
+                BufferTarget::PixelPack,
+                BufferStorageFlags::CLIENT_STORAGE,
+            ),
+        };
+
+        unsafe {
+            let mut previous_read_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous_read_framebuffer);
+
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, Framebuffer::default_id());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer.get_id());
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+            // With a pixel pack buffer bound the pointer is an offset into it.
+            gl::ReadnPixels(
+                0,
+                0,
+                size.x as i32,
+                size.y as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                byte_size as i32,
+                ptr::null_mut(),
+            );
+
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read_framebuffer as GLuint)
+        }
+
+        self.readbacks.push(Readback {
+            buffer,
+            size,
+            path,
+            fence: GpuFence::new(),
+        })
+    }
+
+    // Sends the readbacks the GPU finished to the encoder, every one of them if wait is set.
+    fn collect(&mut self, wait: bool) {
+        while !self.readbacks.is_empty() {
+            if wait {
+                self.readbacks[0].fence.wait();
+            } else if !self.readbacks[0].fence.is_signaled() {
+                break;
+            }
+
+            let readback = self.readbacks.remove(0);
+            let width = readback.size.x as usize;
+            let mut pixels = vec![0u8; width * readback.size.y as usize * 4];
+
+            unsafe {
+                gl::GetNamedBufferSubData(
+                    readback.buffer.get_id(),
+                    0,
+                    pixels.len() as isize,
+                    pixels.as_mut_ptr() as *mut GLvoid,
+                )
+            }
+
+            self.free_buffers.push(readback.buffer);
+
+            // GL stores the bottom row first.
+            let rows = pixels
+                .chunks(width * 4)
+                .rev()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>();
+
+            let image = match RgbaImage::from_raw(readback.size.x, readback.size.y, rows) {
+                Some(image) => image,
+                None => continue,
+            };
+
+            if let Some(jobs) = self.jobs.as_ref() {
+                let _ = jobs.send(EncodeJob {
+                    path: readback.path,
+                    image,
+                });
+            }
+        }
+    }
+}
+
+impl Default for FrameCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.collect(true);
+
+        // Closing the job channel stops the encoder once it saved every queued frame.
+        self.jobs = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Gui for FrameCapture {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Frame Capture"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                ui.indent();
+
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs());
+
+                if ui.button(im_str!("Screenshot"), [0.0, 0.0]) {
+                    let path = self
+                        .output_directory
+                        .join(format!("screenshot_{}.png", timestamp));
+
+                    match fs::create_dir_all(&self.output_directory) {
+                        Ok(_) => self.capture_frame(path),
+                        Err(e) => log::error!("Failed to create capture directory: {}", e),
+                    }
+                }
+
+                ui.same_line(0.0);
+
+                let mut recording = self.is_recording();
+                if ui.checkbox(im_str!("Record Sequence"), &mut recording) {
+                    if recording {
+                        let directory = self
+                            .output_directory
+                            .join(format!("sequence_{}", timestamp));
+
+                        if let Err(e) = self.start_recording(directory) {
+                            log::error!("Failed to start recording: {}", e)
+                        }
+                    } else {
+                        self.stop_recording()
+                    }
+                }
+
+                if let Some(recording) = self.recording.as_ref() {
+                    ui.text(format!(
+                        "{} frames to {}",
+                        recording.frame,
+                        recording.directory.display()
+                    ));
+                }
+
+                ui.unindent()
+            });
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    imgui::{Gui, Ui},
+    rendering::mesh::PrimitiveMode,
+};
+use std::cell::Cell;
+
+thread_local! {
+    // Counters of the frame being recorded and of the last complete frame.
+    static CURRENT_STATS: Cell<FrameStats> = Cell::new(FrameStats::default());
+    static LAST_STATS: Cell<FrameStats> = Cell::new(FrameStats::default());
+}
+
+// What the renderer submitted during a frame. The draw and bind paths of the engine count
+// themselves, the application starts every frame with FrameStats::begin_frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    // Summed over every draw call, 1 for non instanced draws.
+    pub instances: u32,
+    // Of every instance. Indirect draws count what the CPU recorded, patches count before
+    // tessellation.
+    pub triangles: u64,
+    pub texture_binds: u32,
+    pub pipeline_binds: u32,
+    // Depth stencil, blend and rasterizer states the StateManager actually changed.
+    pub state_changes: u32,
+    // Binds the StateManager skipped, the object was bound already.
+    pub redundant_binds: u32,
+    pub compute_dispatches: u32,
+}
+
+impl FrameStats {
+    // Makes the counters of the frame so far the last frame and starts counting from zero.
+    pub fn begin_frame() {
+        let stats = CURRENT_STATS.with(|current| current.replace(FrameStats::default()));
+        LAST_STATS.with(|last| last.set(stats))
+    }
+
+    // The last complete frame.
+    pub fn last() -> FrameStats {
+        LAST_STATS.with(|last| last.get())
+    }
+
+    // The frame being recorded, up to now.
+    pub fn current() -> FrameStats {
+        CURRENT_STATS.with(|current| current.get())
+    }
+
+    pub(crate) fn record_draw(primitive_mode: PrimitiveMode, vertex_count: usize, instances: u32) {
+        Self::update(|stats| {
+            stats.draw_calls += 1;
+            stats.instances += instances;
+            stats.triangles += triangle_count(primitive_mode, vertex_count) * instances as u64;
+        })
+    }
+
+    // A multi draw counts as a single call.
+    pub(crate) fn record_multi_draw<I: Iterator<Item = (usize, u32)>>(
+        primitive_mode: PrimitiveMode,
+        draws: I,
+    ) {
+        Self::update(|stats| {
+            stats.draw_calls += 1;
+
+            for (vertex_count, instances) in draws {
+                stats.instances += instances;
+                stats.triangles += triangle_count(primitive_mode, vertex_count) * instances as u64;
+            }
+        })
+    }
+
+    pub(crate) fn record_texture_bind() {
+        Self::update(|stats| stats.texture_binds += 1)
+    }
+
+    pub(crate) fn record_pipeline_bind() {
+        Self::update(|stats| stats.pipeline_binds += 1)
+    }
+
+    pub(crate) fn record_state_change() {
+        Self::update(|stats| stats.state_changes += 1)
+    }
+
+    pub(crate) fn record_redundant_bind() {
+        Self::update(|stats| stats.redundant_binds += 1)
+    }
+
+    pub(crate) fn record_dispatch() {
+        Self::update(|stats| stats.compute_dispatches += 1)
+    }
+
+    fn update<F: FnOnce(&mut FrameStats)>(f: F) {
+        CURRENT_STATS.with(|current| {
+            let mut stats = current.get();
+            f(&mut stats);
+            current.set(stats)
+        })
+    }
+}
+
+// Patches are assumed to be triangles, as in the tessellated PBS shaders.
+fn triangle_count(primitive_mode: PrimitiveMode, vertex_count: usize) -> u64 {
+    let vertex_count = vertex_count as u64;
+
+    match primitive_mode {
+        PrimitiveMode::Triangles | PrimitiveMode::Patches => vertex_count / 3,
+        PrimitiveMode::TriangleStrip => vertex_count.saturating_sub(2),
+        PrimitiveMode::Points | PrimitiveMode::Lines | PrimitiveMode::LineStrip => 0,
+    }
+}
+
+impl Gui for FrameStats {
+    fn gui(&mut self, ui: &Ui) {
+        ui.text(format!("Draw Calls: {}", self.draw_calls));
+        ui.text(format!("Instances: {}", self.instances));
+        ui.text(format!("Triangles: {}", self.triangles));
+        ui.text(format!("Texture Binds: {}", self.texture_binds));
+        ui.text(format!("Pipeline Binds: {}", self.pipeline_binds));
+        ui.text(format!("State Changes: {}", self.state_changes));
+        ui.text(format!("Redundant Binds Skipped: {}", self.redundant_binds));
+        ui.text(format!("Dispatches: {}", self.compute_dispatches));
+    }
+}
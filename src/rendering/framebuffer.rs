@@ -4,12 +4,22 @@ use std::fmt;
 
 use crate::core::math;
 use crate::core::math::{UVec2, Vec4};
+use crate::rendering::error::RendererError;
+use crate::rendering::gpu_capabilities::GpuCapabilities;
+use crate::rendering::object_label::set_object_label;
 use crate::rendering::state::StateManager;
 use crate::rendering::texture::SizedTextureFormat;
 use crate::Msaa;
+use image::RgbaImage;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+thread_local! {
+    // What stands in for the framebuffer of the window, 0 unless rendering headless.
+    static DEFAULT_FRAMEBUFFER: Cell<GLuint> = Cell::new(0);
+}
+
 #[repr(u32)]
 #[derive(Debug, Clone, Copy)]
 pub enum TextureFilter {
@@ -139,6 +149,8 @@ impl FramebufferAttachment {
 #[derive(Debug)]
 pub struct Framebuffer {
     id: GLuint,
+    // Identifies the framebuffer in messages, its id unless named.
+    name: String,
     size: UVec2,
     texture_attachments: Vec<FramebufferAttachment>,
     renderbuffer_attachments: Vec<FramebufferAttachment>,
@@ -153,6 +165,7 @@ impl Framebuffer {
         msaa: Msaa,
         attachment_create_infos: Vec<FramebufferAttachmentCreateInfo>,
     ) -> Result<Self, FramebufferError> {
+        let msaa = Self::supported_msaa(msaa);
         let mut framebuffer_id: GLuint = 0;
 
         unsafe {
@@ -361,8 +374,18 @@ impl Framebuffer {
         if let Err(e) = Self::check_status(framebuffer_id) {
             Err(e)
         } else {
+            log::debug!(
+                "Created framebuffer {} ({}x{}, {} samples, {} attachments).",
+                framebuffer_id,
+                size.x,
+                size.y,
+                msaa as u32,
+                attachment_create_infos.len()
+            );
+
             Ok(Framebuffer {
                 id: framebuffer_id,
+                name: framebuffer_id.to_string(),
                 size,
                 texture_attachments,
                 renderbuffer_attachments,
@@ -408,11 +431,33 @@ impl Framebuffer {
             });
     }
 
-    pub fn bind(&self) {
-        unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
-            StateManager::set_viewport(0, 0, self.size.x as i32, self.size.y as i32);
+    // The most samples up to the requested the GPU supports.
+    fn supported_msaa(msaa: Msaa) -> Msaa {
+        let max_samples = GpuCapabilities::current().max_samples;
+
+        if msaa as u32 <= max_samples {
+            return msaa;
         }
+
+        let supported = [Msaa::X16, Msaa::X8, Msaa::X4, Msaa::X2]
+            .iter()
+            .copied()
+            .find(|&samples| samples as u32 <= max_samples)
+            .unwrap_or(Msaa::None);
+
+        log::warn!(
+            "{} samples requested, the GPU supports up to {}. Using {}.",
+            msaa as u32,
+            max_samples,
+            supported as u32
+        );
+
+        supported
+    }
+
+    pub fn bind(&self) {
+        StateManager::bind_framebuffer(self.id);
+        StateManager::set_viewport(0, 0, self.size.x as i32, self.size.y as i32);
     }
 
     pub fn unbind(&self, invalidate: bool) {
@@ -420,7 +465,84 @@ impl Framebuffer {
             self.invalidate()
         }
 
-        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) }
+        StateManager::bind_framebuffer(Self::default_id())
+    }
+
+    // The framebuffer passes bind when they are done and present into. The window's, 0, unless
+    // an offscreen framebuffer stands in for it.
+    pub fn default_id() -> GLuint {
+        DEFAULT_FRAMEBUFFER.with(|default| default.get())
+    }
+
+    pub(crate) fn set_default_id(id: GLuint) {
+        DEFAULT_FRAMEBUFFER.with(|default| default.set(id))
+    }
+
+    // Reads a color attachment back as 8 bit RGBA, top row first. Multisampled framebuffers
+    // have to be blitted to a single sampled one first.
+    pub fn read_pixels(&self, index: usize) -> Result<RgbaImage, String> {
+        if self.samples > 1 {
+            return Err(format!(
+                "Cannot read back the pixels of multisampled framebuffer {}.",
+                self.name
+            ));
+        }
+
+        let output_location = *self.output_locations.get(index).ok_or_else(|| {
+            format!(
+                "Framebuffer {} has no color attachment {}.",
+                self.name, index
+            )
+        })?;
+
+        let width = self.size.x as usize;
+        let height = self.size.y as usize;
+        let mut pixels = vec![0u8; width * height * 4];
+
+        unsafe {
+            let mut previous_read_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous_read_framebuffer);
+
+            gl::NamedFramebufferReadBuffer(self.id, output_location);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadnPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.len() as i32,
+                pixels.as_mut_ptr() as *mut GLvoid,
+            );
+
+            gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read_framebuffer as GLuint)
+        }
+
+        // GL stores the bottom row first.
+        let rows = pixels
+            .chunks(width * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        RgbaImage::from_raw(self.size.x, self.size.y, rows)
+            .ok_or_else(|| "Failed to create an image from the framebuffer pixels.".to_string())
+    }
+
+    // Restricts drawing to the first count color attachments, e.g. for passes that only write
+    // the first one. None restores all of them.
+    pub fn set_draw_buffer_count(&self, count: Option<usize>) {
+        let count = count.map_or(self.output_locations.len(), |count| {
+            count.min(self.output_locations.len())
+        });
+
+        unsafe {
+            gl::NamedFramebufferDrawBuffers(self.id, count as i32, self.output_locations.as_ptr())
+        }
     }
 
     pub fn invalidate(&self) {
@@ -446,12 +568,13 @@ impl Framebuffer {
         }
     }
 
-    pub fn texture_attachment(&self, index: usize) -> FramebufferAttachment {
-        assert!(
-            index < self.texture_attachments.len(),
-            "Index out of bounds."
-        );
-        self.texture_attachments[index]
+    pub fn texture_attachment(&self, index: usize) -> Result<FramebufferAttachment, RendererError> {
+        self.texture_attachments.get(index).copied().ok_or_else(|| {
+            RendererError::MissingAttachment {
+                framebuffer: self.name.clone(),
+                index,
+            }
+        })
     }
 
     pub fn texture_attachments(&self) -> &Vec<FramebufferAttachment> {
@@ -466,6 +589,38 @@ impl Framebuffer {
         self.id
     }
 
+    // Labels the framebuffer and its attachments for debuggers, e.g. "Scene" labels the
+    // attachments "Scene color 0" and "Scene depth".
+    pub fn with_name(mut self, name: &str) -> Self {
+        set_object_label(gl::FRAMEBUFFER, self.id, name);
+
+        self.texture_attachments
+            .iter()
+            .chain(self.renderbuffer_attachments.iter())
+            .for_each(|attachment| {
+                let identifier = match attachment.attachment_type {
+                    AttachmentType::Renderbuffer => gl::RENDERBUFFER,
+                    _ => gl::TEXTURE,
+                };
+
+                let label = match attachment.attachment_bind_point {
+                    AttachmentBindPoint::Color(_, i) => format!("{} color {}", name, i),
+                    AttachmentBindPoint::Depth(_) => format!("{} depth", name),
+                    AttachmentBindPoint::DepthStencil(_) => format!("{} depth stencil", name),
+                    AttachmentBindPoint::Stencil(_) => format!("{} stencil", name),
+                };
+
+                set_object_label(identifier, attachment.id, &label)
+            });
+
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn size(&self) -> UVec2 {
         self.size
     }
@@ -536,7 +691,7 @@ impl Framebuffer {
         unsafe {
             gl::BlitNamedFramebuffer(
                 source.id(),
-                0,
+                Self::default_id(),
                 0,
                 0,
                 source.size().x as i32,
@@ -592,6 +747,7 @@ impl Framebuffer {
 
 impl Drop for Framebuffer {
     fn drop(&mut self) {
+        StateManager::framebuffer_deleted(self.id);
         unsafe { gl::DeleteFramebuffers(1, &self.id) }
     }
 }
@@ -692,9 +848,11 @@ impl TemporaryFramebufferPool {
             })
         }
 
-        Rc::new(
-            Framebuffer::new(size, Msaa::None, attachment_create_infos)
-                .expect("Failed to create framebuffer!"),
-        )
+        let name = format!("Temporary {}x{} {:?}", size.x, size.y, format);
+
+        let framebuffer = Framebuffer::new(size, Msaa::None, attachment_create_infos)
+            .unwrap_or_else(|e| panic!("Failed to create framebuffer {}: {}", name, e));
+
+        Rc::new(framebuffer.with_name(&name))
     }
 }
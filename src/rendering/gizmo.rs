@@ -0,0 +1,439 @@
+use crate::{
+    core::camera::{Camera, Projection},
+    core::ecs::components::Transform,
+    core::math::{Mat4, Vec3, Vec4},
+    geometry::ray::Ray,
+    imgui::{im_str, Gui, Ui},
+    platform::input::Input,
+    rendering::debug_draw::DebugDraw,
+};
+use glutin::event::MouseButton;
+use nalgebra_glm as glm;
+use std::ops::RangeInclusive;
+
+// Length of the handles as a fraction of the view height, they keep their size on screen.
+const SCREEN_SIZE: f32 = 0.15;
+// How close the cursor has to get to a handle, as a fraction of its length.
+const PICK_TOLERANCE: f32 = 0.08;
+const MIN_SCALE: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+// The axes of the handles. Scale handles always follow the axes of the object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoSpace {
+    World,
+    Local,
+}
+
+struct Handles {
+    origin: Vec3,
+    axes: [Vec3; 3],
+    length: f32,
+}
+
+struct Drag {
+    axis: usize,
+    start: Transform,
+    // Turns world space offsets and axes into the space of the transform.
+    parent_inverse: Mat4,
+    origin: Vec3,
+    direction: Vec3,
+    // Translate and scale: where the axis was grabbed, along the axis.
+    anchor: f32,
+    // Rotate: the angle turned so far and the last point on the plane of the ring.
+    angle: f32,
+    previous: Vec3,
+}
+
+// Translate, rotate and scale handles of the editor overlay, drawn through the debug draw.
+// Dragging an axis with the left mouse button moves the Transform along it, snapped to steps
+// while snapping is on. Holding Ctrl inverts snapping.
+pub struct Gizmo {
+    mode: GizmoMode,
+    space: GizmoSpace,
+    snapping: bool,
+    translation_step: f32,
+    // In degrees.
+    rotation_step: f32,
+    scale_step: f32,
+    hovered: Option<usize>,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn new() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snapping: false,
+            translation_step: 0.5,
+            rotation_step: 15.0,
+            scale_step: 0.1,
+            hovered: None,
+            drag: None,
+        }
+    }
+
+    pub fn mode(&self) -> GizmoMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: GizmoMode) {
+        self.mode = mode;
+        self.drag = None;
+    }
+
+    pub fn space(&self) -> GizmoSpace {
+        self.space
+    }
+
+    pub fn set_space(&mut self, space: GizmoSpace) {
+        self.space = space
+    }
+
+    pub fn snapping(&self) -> bool {
+        self.snapping
+    }
+
+    pub fn set_snapping(&mut self, snapping: bool) {
+        self.snapping = snapping
+    }
+
+    // Rotation in degrees.
+    pub fn set_snap_steps(&mut self, translation: f32, rotation: f32, scale: f32) {
+        self.translation_step = translation;
+        self.rotation_step = rotation;
+        self.scale_step = scale;
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    // Hovers and drags the handles of the transform, its world matrix has to be up to date.
+    // Returns whether the gizmo has the mouse, clicks should only pick objects when it hasn't.
+    pub fn manipulate(
+        &mut self,
+        camera: &Camera,
+        input: &Input,
+        transform: &mut Transform,
+    ) -> bool {
+        let cursor = match input.cursor() {
+            Some(cursor) if !input.mouse_blocked() => cursor,
+            _ => {
+                self.hovered = None;
+                self.drag = None;
+                return false;
+            }
+        };
+
+        let ray = camera.screen_ray(cursor);
+        let snapping = self.snapping != input.modifiers().ctrl();
+
+        if let Some(mut drag) = self.drag.take() {
+            if input.mouse_button_down(MouseButton::Left) {
+                self.drag_to(&mut drag, &ray, snapping, transform);
+                self.drag = Some(drag);
+                return true;
+            }
+        }
+
+        let handles = self.handles(camera, transform);
+        self.hovered = self.pick(&handles, &ray);
+
+        if input.mouse_button_pressed(MouseButton::Left) {
+            if let Some(axis) = self.hovered {
+                self.drag = self.begin_drag(&handles, axis, &ray, transform);
+            }
+        }
+
+        self.hovered.is_some()
+    }
+
+    // Draws the handles over the scene. The debug draw has to be enabled.
+    pub fn draw(&self, camera: &Camera, transform: &Transform, debug_draw: &mut DebugDraw) {
+        let Handles {
+            origin,
+            axes,
+            length,
+        } = self.handles(camera, transform);
+
+        let active = self.drag.as_ref().map(|drag| drag.axis).or(self.hovered);
+
+        debug_draw.set_depth_test(false);
+
+        for (axis, direction) in axes.iter().enumerate() {
+            let color = if active == Some(axis) {
+                Vec4::new(1.0, 0.9, 0.1, 1.0)
+            } else {
+                let mut color = Vec4::new(0.0, 0.0, 0.0, 1.0);
+                color[axis] = 1.0;
+                color
+            };
+
+            let u = axes[(axis + 1) % 3];
+            let v = axes[(axis + 2) % 3];
+            let end = origin + direction * length;
+
+            match self.mode {
+                GizmoMode::Translate => {
+                    debug_draw.line(origin, end, color);
+
+                    let base = end - direction * length * 0.2;
+                    for side in [u, -u, v, -v].iter() {
+                        debug_draw.line(end, base + side * length * 0.06, color)
+                    }
+                }
+                GizmoMode::Rotate => debug_draw.circle(origin, u * length, v * length, color),
+                GizmoMode::Scale => {
+                    debug_draw.line(origin, end, color);
+
+                    let half_size = Vec3::new(1.0, 1.0, 1.0) * length * 0.05;
+                    debug_draw.wire_box(end - half_size, end + half_size, color)
+                }
+            }
+        }
+
+        debug_draw.set_depth_test(true);
+    }
+
+    fn handles(&self, camera: &Camera, transform: &Transform) -> Handles {
+        let world = transform.world_matrix();
+        let origin = world.column(3).xyz();
+
+        let axis = |i: usize| {
+            let mut unit = Vec3::zeros();
+            unit[i] = 1.0;
+
+            if self.space == GizmoSpace::Local || self.mode == GizmoMode::Scale {
+                world
+                    .column(i)
+                    .xyz()
+                    .try_normalize(std::f32::EPSILON)
+                    .unwrap_or(unit)
+            } else {
+                unit
+            }
+        };
+
+        let view_height = match *camera.projection() {
+            Projection::Perspective { fov_y, .. } => {
+                glm::distance(camera.position(), &origin) * (fov_y.to_radians() * 0.5).tan() * 2.0
+            }
+            Projection::Orthographic { height, .. } => height,
+        };
+
+        Handles {
+            origin,
+            axes: [axis(0), axis(1), axis(2)],
+            length: view_height * SCREEN_SIZE,
+        }
+    }
+
+    // The axis of the handle under the cursor nearest to the camera.
+    fn pick(&self, handles: &Handles, ray: &Ray) -> Option<usize> {
+        let tolerance = handles.length * PICK_TOLERANCE;
+        let mut closest: Option<(usize, f32)> = None;
+
+        for (axis, direction) in handles.axes.iter().enumerate() {
+            let distance = match self.mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    closest_points(ray, &handles.origin, direction).and_then(|(t, s)| {
+                        let on_axis = handles.origin + direction * s;
+                        let hit = t >= 0.0
+                            && s >= 0.0
+                            && s <= handles.length
+                            && glm::distance(&ray.at(t), &on_axis) < tolerance;
+
+                        if hit {
+                            Some(t)
+                        } else {
+                            None
+                        }
+                    })
+                }
+                GizmoMode::Rotate => {
+                    intersect_plane(ray, &handles.origin, direction).filter(|&t| {
+                        (glm::distance(&ray.at(t), &handles.origin) - handles.length).abs()
+                            < tolerance
+                    })
+                }
+            };
+
+            if let Some(distance) = distance {
+                if closest.map_or(true, |(_, closest)| distance < closest) {
+                    closest = Some((axis, distance))
+                }
+            }
+        }
+
+        closest.map(|(axis, _)| axis)
+    }
+
+    fn begin_drag(
+        &self,
+        handles: &Handles,
+        axis: usize,
+        ray: &Ray,
+        transform: &Transform,
+    ) -> Option<Drag> {
+        let direction = handles.axes[axis];
+        let parent_world = transform.world_matrix() * glm::inverse(&transform.local_matrix());
+
+        let mut drag = Drag {
+            axis,
+            start: transform.clone(),
+            parent_inverse: glm::inverse(&parent_world),
+            origin: handles.origin,
+            direction,
+            anchor: 0.0,
+            angle: 0.0,
+            previous: Vec3::zeros(),
+        };
+
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                drag.anchor = closest_points(ray, &handles.origin, &direction)?.1
+            }
+            GizmoMode::Rotate => {
+                drag.previous =
+                    ray.at(intersect_plane(ray, &handles.origin, &direction)?) - handles.origin
+            }
+        }
+
+        Some(drag)
+    }
+
+    fn drag_to(&self, drag: &mut Drag, ray: &Ray, snapping: bool, transform: &mut Transform) {
+        let parent_inverse = drag.parent_inverse;
+        let to_parent =
+            |vector: &Vec3| (parent_inverse * Vec4::new(vector.x, vector.y, vector.z, 0.0)).xyz();
+
+        match self.mode {
+            GizmoMode::Translate => {
+                if let Some((_, s)) = closest_points(ray, &drag.origin, &drag.direction) {
+                    let distance = snap(s - drag.anchor, self.translation_step, snapping);
+                    transform.translation =
+                        drag.start.translation + to_parent(&(drag.direction * distance));
+                }
+            }
+            GizmoMode::Rotate => {
+                if let Some(t) = intersect_plane(ray, &drag.origin, &drag.direction) {
+                    let current = ray.at(t) - drag.origin;
+
+                    // Accumulated frame to frame, so turns past half a circle keep their sign.
+                    drag.angle += drag
+                        .previous
+                        .cross(&current)
+                        .dot(&drag.direction)
+                        .atan2(drag.previous.dot(&current));
+                    drag.previous = current;
+
+                    let angle = snap(drag.angle, self.rotation_step.to_radians(), snapping);
+                    let axis = to_parent(&drag.direction)
+                        .try_normalize(std::f32::EPSILON)
+                        .unwrap_or(drag.direction);
+
+                    transform.rotation = glm::quat_angle_axis(angle, &axis) * drag.start.rotation;
+                }
+            }
+            GizmoMode::Scale => {
+                if let Some((_, s)) = closest_points(ray, &drag.origin, &drag.direction) {
+                    if drag.anchor.abs() > std::f32::EPSILON {
+                        let scale = drag.start.scale[drag.axis] * s / drag.anchor;
+                        transform.scale[drag.axis] =
+                            snap(scale, self.scale_step, snapping).max(MIN_SCALE);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Gizmo::new()
+    }
+}
+
+impl Gui for Gizmo {
+    fn gui(&mut self, ui: &Ui) {
+        let mut mode = self.mode as usize;
+        if imgui::ComboBox::new(im_str!("Gizmo")).build_simple_string(
+            &ui,
+            &mut mode,
+            &[im_str!("Translate"), im_str!("Rotate"), im_str!("Scale")],
+        ) {
+            self.set_mode(match mode {
+                0 => GizmoMode::Translate,
+                1 => GizmoMode::Rotate,
+                _ => GizmoMode::Scale,
+            })
+        }
+
+        let mut local = self.space == GizmoSpace::Local;
+        if ui.checkbox(im_str!("Local Axes"), &mut local) {
+            self.space = if local {
+                GizmoSpace::Local
+            } else {
+                GizmoSpace::World
+            }
+        }
+
+        ui.checkbox(im_str!("Snapping"), &mut self.snapping);
+        imgui::Drag::new(im_str!("Translation Step"))
+            .range(RangeInclusive::new(0.01, 10.0))
+            .speed(0.01)
+            .display_format(im_str!("%.2f"))
+            .build(&ui, &mut self.translation_step);
+        imgui::Drag::new(im_str!("Rotation Step"))
+            .range(RangeInclusive::new(1.0, 90.0))
+            .display_format(im_str!("%.0f deg"))
+            .build(&ui, &mut self.rotation_step);
+        imgui::Drag::new(im_str!("Scale Step"))
+            .range(RangeInclusive::new(0.01, 1.0))
+            .speed(0.01)
+            .display_format(im_str!("%.2f"))
+            .build(&ui, &mut self.scale_step);
+    }
+}
+
+fn snap(value: f32, step: f32, snapping: bool) -> f32 {
+    if snapping && step > 0.0 {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
+
+// Distances along the ray and along the line through origin in the unit direction of their
+// closest points. None when they are parallel.
+fn closest_points(ray: &Ray, origin: &Vec3, direction: &Vec3) -> Option<(f32, f32)> {
+    let to_ray = ray.origin - origin;
+    let b = ray.direction.dot(direction);
+    let d = ray.direction.dot(&to_ray);
+    let e = direction.dot(&to_ray);
+    let denominator = 1.0 - b * b;
+
+    if denominator < 1e-6 {
+        return None;
+    }
+
+    Some(((b * e - d) / denominator, (e - b * d) / denominator))
+}
+
+fn intersect_plane(ray: &Ray, origin: &Vec3, normal: &Vec3) -> Option<f32> {
+    let denominator = normal.dot(&ray.direction);
+
+    if denominator.abs() < 1e-6 {
+        return None;
+    }
+
+    Some(normal.dot(&(origin - ray.origin)) / denominator).filter(|&t| t >= 0.0)
+}
@@ -0,0 +1,242 @@
+use crate::imgui::{im_str, Gui, Ui};
+use gl::types::*;
+use gl_bindings as gl;
+use std::{cell::RefCell, collections::HashSet, ffi::CStr, rc::Rc};
+
+thread_local! {
+    // Of the context current on the thread, detected when it is initialized.
+    static CAPABILITIES: RefCell<Option<Rc<GpuCapabilities>>> = RefCell::new(None);
+}
+
+// Limits and optional features of the GL implementation, detected once when the context is
+// created. Subsystems query them instead of assuming what the driver supports, and fall back
+// where a feature is missing.
+#[derive(Debug, Clone)]
+pub struct GpuCapabilities {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub major_version: u32,
+    pub minor_version: u32,
+    extensions: HashSet<String>,
+    pub max_texture_size: u32,
+    pub max_3d_texture_size: u32,
+    pub max_cube_map_texture_size: u32,
+    pub max_array_texture_layers: u32,
+    // Texture units of all shader stages together.
+    pub max_texture_units: u32,
+    pub max_color_attachments: u32,
+    pub max_samples: u32,
+    // 1 without anisotropic filtering.
+    pub max_anisotropy: f32,
+    pub max_uniform_block_size: u32,
+    pub uniform_buffer_offset_alignment: u32,
+    pub shader_storage_buffer_offset_alignment: u32,
+    pub max_compute_work_group_invocations: u32,
+    pub direct_state_access: bool,
+    pub buffer_storage: bool,
+    pub separate_shader_objects: bool,
+    pub compute_shaders: bool,
+    pub anisotropic_filtering: bool,
+    pub bindless_textures: bool,
+    pub sparse_textures: bool,
+    pub sparse_buffers: bool,
+    pub parallel_shader_compile: bool,
+    pub spirv: bool,
+    pub pipeline_statistics_query: bool,
+}
+
+impl GpuCapabilities {
+    // Queries the context current on the thread.
+    pub fn detect() -> Self {
+        let mut extension_count: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count) }
+
+        let extensions = (0..extension_count.max(0) as GLuint)
+            .filter_map(|i| unsafe { Self::string(gl::GetStringi(gl::EXTENSIONS, i)) })
+            .collect::<HashSet<_>>();
+
+        let major_version = Self::integer(gl::MAJOR_VERSION);
+        let minor_version = Self::integer(gl::MINOR_VERSION);
+
+        let supports = |major: u32, minor: u32, extension: &str| {
+            (major_version, minor_version) >= (major, minor) || extensions.contains(extension)
+        };
+
+        let direct_state_access = supports(4, 5, "GL_ARB_direct_state_access");
+        let buffer_storage = supports(4, 4, "GL_ARB_buffer_storage");
+        let separate_shader_objects = supports(4, 1, "GL_ARB_separate_shader_objects");
+        let compute_shaders = supports(4, 3, "GL_ARB_compute_shader");
+        let anisotropic_filtering = supports(4, 6, "GL_ARB_texture_filter_anisotropic")
+            || extensions.contains("GL_EXT_texture_filter_anisotropic");
+        let parallel_shader_compile = extensions.contains("GL_KHR_parallel_shader_compile");
+        let spirv = supports(4, 6, "GL_ARB_gl_spirv");
+        let pipeline_statistics_query = supports(4, 6, "GL_ARB_pipeline_statistics_query");
+
+        let max_anisotropy = if anisotropic_filtering {
+            let mut max_anisotropy: GLfloat = 1.0;
+            unsafe { gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy) }
+            max_anisotropy.max(1.0)
+        } else {
+            1.0
+        };
+
+        Self {
+            vendor: unsafe { Self::string(gl::GetString(gl::VENDOR)) }.unwrap_or_default(),
+            renderer: unsafe { Self::string(gl::GetString(gl::RENDERER)) }.unwrap_or_default(),
+            version: unsafe { Self::string(gl::GetString(gl::VERSION)) }.unwrap_or_default(),
+            major_version,
+            minor_version,
+            max_texture_size: Self::integer(gl::MAX_TEXTURE_SIZE),
+            max_3d_texture_size: Self::integer(gl::MAX_3D_TEXTURE_SIZE),
+            max_cube_map_texture_size: Self::integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE),
+            max_array_texture_layers: Self::integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+            max_texture_units: Self::integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_color_attachments: Self::integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_samples: Self::integer(gl::MAX_SAMPLES).max(1),
+            max_anisotropy,
+            max_uniform_block_size: Self::integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            uniform_buffer_offset_alignment: Self::integer(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
+                .max(1),
+            shader_storage_buffer_offset_alignment: Self::integer(
+                gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT,
+            )
+            .max(1),
+            max_compute_work_group_invocations: if compute_shaders {
+                Self::integer(gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS)
+            } else {
+                0
+            },
+            direct_state_access,
+            buffer_storage,
+            separate_shader_objects,
+            compute_shaders,
+            anisotropic_filtering,
+            bindless_textures: extensions.contains("GL_ARB_bindless_texture"),
+            sparse_textures: extensions.contains("GL_ARB_sparse_texture"),
+            sparse_buffers: extensions.contains("GL_ARB_sparse_buffer"),
+            parallel_shader_compile,
+            spirv,
+            pipeline_statistics_query,
+            extensions,
+        }
+    }
+
+    // Detects the capabilities of the new context current on the thread. Fails with every
+    // missing feature the renderer cannot do without.
+    pub(crate) fn initialize() -> Result<Rc<GpuCapabilities>, String> {
+        let capabilities = Rc::new(Self::detect());
+
+        CAPABILITIES.with(|current| *current.borrow_mut() = Some(Rc::clone(&capabilities)));
+
+        capabilities.validate()?;
+
+        Ok(capabilities)
+    }
+
+    // The capabilities of the context current on the thread.
+    pub fn current() -> Rc<GpuCapabilities> {
+        CAPABILITIES.with(|current| {
+            Rc::clone(
+                current
+                    .borrow_mut()
+                    .get_or_insert_with(|| Rc::new(Self::detect())),
+            )
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let missing = [
+            (self.direct_state_access, "direct state access"),
+            (self.buffer_storage, "immutable buffer storage"),
+            (self.separate_shader_objects, "separate shader objects"),
+            (self.compute_shaders, "compute shaders"),
+        ]
+        .iter()
+        .filter(|(supported, _)| !supported)
+        .map(|(_, feature)| *feature)
+        .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "{} ({}, OpenGL {}) does not support {}. OpenGL 4.5 or the equivalent extensions are required.",
+            self.renderer,
+            self.vendor,
+            self.version,
+            missing.join(", ")
+        ))
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.extensions.iter().map(String::as_str)
+    }
+
+    pub fn supports_version(&self, major: u32, minor: u32) -> bool {
+        (self.major_version, self.minor_version) >= (major, minor)
+    }
+
+    fn integer(parameter: GLenum) -> u32 {
+        let mut value: GLint = 0;
+        unsafe { gl::GetIntegerv(parameter, &mut value) }
+
+        value.max(0) as u32
+    }
+
+    unsafe fn string(string: *const GLubyte) -> Option<String> {
+        if string.is_null() {
+            return None;
+        }
+
+        Some(
+            CStr::from_ptr(string as *const GLchar)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+impl Gui for GpuCapabilities {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("GPU Capabilities"))
+            .default_open(false)
+            .build(ui, || {
+                ui.text(format!("{} ({})", self.renderer, self.vendor));
+                ui.text(format!("OpenGL {}", self.version));
+                ui.separator();
+                ui.text(format!("Max Texture Size: {}", self.max_texture_size));
+                ui.text(format!("Max Samples: {}", self.max_samples));
+                ui.text(format!("Max Anisotropy: {}", self.max_anisotropy));
+                ui.text(format!(
+                    "UBO Offset Alignment: {}",
+                    self.uniform_buffer_offset_alignment
+                ));
+                ui.separator();
+
+                for (supported, feature) in [
+                    (self.bindless_textures, "Bindless Textures"),
+                    (self.sparse_textures, "Sparse Textures"),
+                    (self.sparse_buffers, "Sparse Buffers"),
+                    (self.parallel_shader_compile, "Parallel Shader Compile"),
+                    (self.spirv, "SPIR-V"),
+                    (self.pipeline_statistics_query, "Pipeline Statistics"),
+                ]
+                .iter()
+                {
+                    ui.text(format!(
+                        "{}: {}",
+                        feature,
+                        if *supported { "Yes" } else { "No" }
+                    ));
+                }
+
+                ui.text(format!("Extensions: {}", self.extensions.len()));
+            });
+    }
+}
@@ -0,0 +1,287 @@
+use crate::imgui::{im_str, Gui, Ui};
+use gl::types::*;
+use gl_bindings as gl;
+use std::collections::HashMap;
+
+// Results are read this many frames later, when the GPU has long finished them, so reading
+// them never stalls.
+const FRAMES_IN_FLIGHT: usize = 4;
+// Weight of the newest frame in the averages shown by the gui.
+const AVERAGE_WEIGHT: f32 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuScopeTiming {
+    pub name: &'static str,
+    // Nesting level, 0 for top level scopes.
+    pub depth: usize,
+    pub milliseconds: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingScope {
+    name: &'static str,
+    depth: usize,
+    // Indices of the begin and end timestamps in the queries of the frame.
+    begin: usize,
+    end: usize,
+}
+
+// The timestamp queries of one frame in flight. Queries are created on demand and reused.
+#[derive(Default)]
+struct FrameQueries {
+    queries: Vec<GLuint>,
+    used: usize,
+    scopes: Vec<PendingScope>,
+}
+
+impl FrameQueries {
+    fn timestamp(&mut self) -> usize {
+        if self.used == self.queries.len() {
+            let mut query: GLuint = 0;
+            unsafe { gl::CreateQueries(gl::TIMESTAMP, 1, &mut query) }
+            self.queries.push(query);
+        }
+
+        unsafe { gl::QueryCounter(self.queries[self.used], gl::TIMESTAMP) }
+
+        self.used += 1;
+        self.used - 1
+    }
+
+    fn is_available(&self) -> bool {
+        if self.used == 0 {
+            return false;
+        }
+
+        let mut available: GLint = 0;
+        unsafe {
+            gl::GetQueryObjectiv(
+                self.queries[self.used - 1],
+                gl::QUERY_RESULT_AVAILABLE,
+                &mut available,
+            )
+        }
+
+        available != 0
+    }
+
+    fn result(&self, index: usize) -> u64 {
+        let mut nanoseconds: GLuint64 = 0;
+        unsafe { gl::GetQueryObjectui64v(self.queries[index], gl::QUERY_RESULT, &mut nanoseconds) }
+
+        nanoseconds
+    }
+
+    fn reset(&mut self) {
+        self.used = 0;
+        self.scopes.clear()
+    }
+}
+
+// Measures how long the GPU spends in named, nestable scopes of a frame with timestamp
+// queries, since timing the CPU around draw calls only measures how long they take to submit.
+// Timings arrive a few frames late.
+pub struct GpuProfiler {
+    frames: Vec<FrameQueries>,
+    frame: usize,
+    // Open scopes of the current frame, by index into its scopes.
+    stack: Vec<usize>,
+    timings: Vec<GpuScopeTiming>,
+    averages: HashMap<&'static str, f32>,
+    in_frame: bool,
+    enabled: bool,
+}
+
+impl GpuProfiler {
+    pub fn new() -> Self {
+        Self {
+            frames: (0..FRAMES_IN_FLIGHT)
+                .map(|_| FrameQueries::default())
+                .collect(),
+            frame: 0,
+            stack: vec![],
+            timings: vec![],
+            averages: HashMap::new(),
+            in_frame: false,
+            enabled: true,
+        }
+    }
+
+    // Collects the results of the oldest frame in flight and starts recording into its queries.
+    pub fn begin_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        let frame = &mut self.frames[self.frame];
+        if frame.is_available() {
+            self.timings = frame
+                .scopes
+                .iter()
+                .map(|scope| GpuScopeTiming {
+                    name: scope.name,
+                    depth: scope.depth,
+                    milliseconds: frame
+                        .result(scope.end)
+                        .saturating_sub(frame.result(scope.begin))
+                        as f32
+                        / 1_000_000.0,
+                })
+                .collect();
+
+            for timing in &self.timings {
+                let average = self
+                    .averages
+                    .entry(timing.name)
+                    .or_insert(timing.milliseconds);
+                *average += (timing.milliseconds - *average) * AVERAGE_WEIGHT;
+            }
+        }
+
+        frame.reset();
+        self.stack.clear();
+        self.in_frame = true
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.in_frame {
+            return;
+        }
+
+        while !self.stack.is_empty() {
+            log::warn!(
+                "GPU profiler scope '{}' was not ended.",
+                self.frames[self.frame].scopes[*self.stack.last().unwrap()].name
+            );
+            self.end_scope();
+        }
+
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        self.in_frame = false
+    }
+
+    // Scopes outside of begin_frame and end_frame are ignored.
+    pub fn begin_scope(&mut self, name: &'static str) {
+        if !self.in_frame {
+            return;
+        }
+
+        let depth = self.stack.len();
+        let frame = &mut self.frames[self.frame];
+        let begin = frame.timestamp();
+
+        frame.scopes.push(PendingScope {
+            name,
+            depth,
+            begin,
+            end: begin,
+        });
+        self.stack.push(frame.scopes.len() - 1)
+    }
+
+    pub fn end_scope(&mut self) {
+        if !self.in_frame {
+            return;
+        }
+
+        match self.stack.pop() {
+            Some(scope) => {
+                let frame = &mut self.frames[self.frame];
+                frame.scopes[scope].end = frame.timestamp();
+            }
+            None => log::warn!("GPU profiler scope ended without being begun."),
+        }
+    }
+
+    // Measures the GPU work issued by f.
+    pub fn scope<T, F: FnOnce() -> T>(&mut self, name: &'static str, f: F) -> T {
+        self.begin_scope(name);
+        let result = f();
+        self.end_scope();
+        result
+    }
+
+    // Timings of the last frame with available results, in the order the scopes began.
+    pub fn timings(&self) -> &[GpuScopeTiming] {
+        &self.timings
+    }
+
+    // Exponential moving average of the scope, None if it was never measured.
+    pub fn average(&self, name: &str) -> Option<f32> {
+        self.averages.get(name).copied()
+    }
+
+    // Sum of the top level scopes.
+    pub fn frame_milliseconds(&self) -> f32 {
+        self.timings
+            .iter()
+            .filter(|timing| timing.depth == 0)
+            .map(|timing| timing.milliseconds)
+            .sum()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.end_frame();
+            self.timings.clear();
+            self.averages.clear();
+        }
+
+        self.enabled = enabled
+    }
+}
+
+impl Default for GpuProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GpuProfiler {
+    fn drop(&mut self) {
+        for frame in &self.frames {
+            unsafe { gl::DeleteQueries(frame.queries.len() as i32, frame.queries.as_ptr()) }
+        }
+    }
+}
+
+impl Gui for GpuProfiler {
+    fn gui(&mut self, ui: &Ui) {
+        let mut enabled = self.enabled;
+
+        ui.group(|| {
+            if ui.checkbox(im_str!("##gpu_profiler"), &mut enabled) {
+                self.set_enabled(enabled)
+            }
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("GPU Profiler"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    for timing in &self.timings {
+                        let average = self.averages.get(timing.name).copied().unwrap_or(0.0);
+                        ui.text(format!(
+                            "{:indent$}{}: {:.3} ms",
+                            "",
+                            timing.name,
+                            average,
+                            indent = timing.depth * 2
+                        ));
+                    }
+
+                    ui.separator();
+                    ui.text(format!("Total: {:.3} ms", self.frame_milliseconds()));
+
+                    ui.unindent()
+                });
+        });
+    }
+}
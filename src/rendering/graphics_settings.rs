@@ -0,0 +1,165 @@
+use crate::core::{config::QualityPreset, Msaa};
+use crate::imgui::{im_str, ComboBox, Gui, Ui};
+use crate::rendering::sampler::Anisotropy;
+
+const SHADOW_RESOLUTIONS: [u32; 4] = [1024, 2048, 4096, 8192];
+
+const ANTI_ALIASING: [AntiAliasing; 5] = [
+    AntiAliasing::None,
+    AntiAliasing::Fxaa,
+    AntiAliasing::Msaa(Msaa::X2),
+    AntiAliasing::Msaa(Msaa::X4),
+    AntiAliasing::Msaa(Msaa::X8),
+];
+
+const ANISOTROPY: [Anisotropy; 5] = [
+    Anisotropy::None,
+    Anisotropy::X2,
+    Anisotropy::X4,
+    Anisotropy::X8,
+    Anisotropy::X16,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AntiAliasing {
+    None,
+    // Post process, the cheapest but blurs detail.
+    Fxaa,
+    // Samples of the scene targets.
+    Msaa(Msaa),
+}
+
+// The rendering features that trade quality for speed. Scenes keep the settings they applied and
+// reconfigure their passes and targets when these change, e.g. through the Gui.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphicsSettings {
+    // Size of the shadow atlas of the local lights.
+    pub shadow_resolution: u32,
+    pub anti_aliasing: AntiAliasing,
+    pub ssao: bool,
+    pub bloom: bool,
+    // Of the material textures.
+    pub anisotropy: Anisotropy,
+}
+
+impl GraphicsSettings {
+    pub fn from_preset(preset: QualityPreset) -> Self {
+        Self {
+            shadow_resolution: preset.shadow_atlas_size(),
+            anti_aliasing: match preset.msaa() {
+                Msaa::None => AntiAliasing::Fxaa,
+                msaa => AntiAliasing::Msaa(msaa),
+            },
+            ssao: preset.ssao(),
+            bloom: preset.bloom(),
+            anisotropy: preset.anisotropy(),
+        }
+    }
+
+    // The preset the settings are, None once any of them was changed.
+    pub fn preset(&self) -> Option<QualityPreset> {
+        QualityPreset::ALL
+            .iter()
+            .copied()
+            .find(|&preset| Self::from_preset(preset) == *self)
+    }
+
+    // Samples of the scene targets.
+    pub fn msaa(&self) -> Msaa {
+        match self.anti_aliasing {
+            AntiAliasing::Msaa(msaa) => msaa,
+            _ => Msaa::None,
+        }
+    }
+
+    pub fn fxaa(&self) -> bool {
+        self.anti_aliasing == AntiAliasing::Fxaa
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings::from_preset(QualityPreset::default())
+    }
+}
+
+impl Gui for GraphicsSettings {
+    fn gui(&mut self, ui: &Ui) {
+        let mut preset = self
+            .preset()
+            .map_or(QualityPreset::ALL.len(), |p| p as usize);
+        if ComboBox::new(im_str!("Preset")).build_simple_string(
+            ui,
+            &mut preset,
+            &[
+                im_str!("Low"),
+                im_str!("Medium"),
+                im_str!("High"),
+                im_str!("Ultra"),
+                im_str!("Custom"),
+            ],
+        ) {
+            if let Some(&preset) = QualityPreset::ALL.get(preset) {
+                *self = Self::from_preset(preset)
+            }
+        }
+
+        ui.separator();
+
+        let mut shadow_resolution = SHADOW_RESOLUTIONS
+            .iter()
+            .position(|&resolution| resolution == self.shadow_resolution)
+            .unwrap_or(0);
+        if ComboBox::new(im_str!("Shadow Resolution")).build_simple_string(
+            ui,
+            &mut shadow_resolution,
+            &[
+                im_str!("1024"),
+                im_str!("2048"),
+                im_str!("4096"),
+                im_str!("8192"),
+            ],
+        ) {
+            self.shadow_resolution = SHADOW_RESOLUTIONS[shadow_resolution]
+        }
+
+        let mut anti_aliasing = ANTI_ALIASING
+            .iter()
+            .position(|&anti_aliasing| anti_aliasing == self.anti_aliasing)
+            .unwrap_or(0);
+        if ComboBox::new(im_str!("Anti-Aliasing")).build_simple_string(
+            ui,
+            &mut anti_aliasing,
+            &[
+                im_str!("None"),
+                im_str!("FXAA"),
+                im_str!("MSAA 2x"),
+                im_str!("MSAA 4x"),
+                im_str!("MSAA 8x"),
+            ],
+        ) {
+            self.anti_aliasing = ANTI_ALIASING[anti_aliasing]
+        }
+
+        let mut anisotropy = ANISOTROPY
+            .iter()
+            .position(|&anisotropy| anisotropy == self.anisotropy)
+            .unwrap_or(0);
+        if ComboBox::new(im_str!("Anisotropy")).build_simple_string(
+            ui,
+            &mut anisotropy,
+            &[
+                im_str!("Off"),
+                im_str!("2x"),
+                im_str!("4x"),
+                im_str!("8x"),
+                im_str!("16x"),
+            ],
+        ) {
+            self.anisotropy = ANISOTROPY[anisotropy]
+        }
+
+        ui.checkbox(im_str!("SSAO"), &mut self.ssao);
+        ui.checkbox(im_str!("Bloom"), &mut self.bloom);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Mat4, UVec2, Vec2, Vec4},
+    geometry::bounds::Aabb,
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        error::RendererError,
+        fence::GpuFence,
+        frame_stats::FrameStats,
+        framebuffer::{AttachmentType, FramebufferAttachment},
+        program_pipeline::ProgramPipeline,
+        shader::ShaderStage,
+        state::StateManager,
+        texture::{SizedTextureFormat, Texture2D},
+    },
+};
+use gl_bindings as gl;
+use std::{mem, ptr};
+
+// Largest dimension of the pyramid level that is read back to the CPU.
+const MAX_READBACK_SIZE: u32 = 256;
+// Readbacks in flight. The CPU tests against a pyramid that is this many frames old at most.
+const READBACK_COUNT: usize = 3;
+// Occlusion tests look at no more than this many texels per axis.
+const MAX_TEST_TEXELS: u32 = 4;
+
+struct Readback {
+    buffer: Buffer,
+    fence: Option<GpuFence>,
+    view_projection: Mat4,
+}
+
+struct HiZLevel {
+    size: UVec2,
+    depths: Vec<f32>,
+}
+
+// Hierarchical depth buffer for occlusion culling. Each level stores the farthest depth of the
+// texels below it. A coarse level is read back asynchronously, so objects are tested against
+// the depth and the view projection of a frame that already finished on the GPU. Fast camera
+// moves can therefore cull objects that just came into view for a frame or two.
+pub struct HiZBuffer {
+    pyramid: Texture2D,
+    size: UVec2,
+    level_count: u32,
+    copy_pipeline: ProgramPipeline,
+    copy_pipeline_multisample: ProgramPipeline,
+    downsample_pipeline: ProgramPipeline,
+    readback_level: u32,
+    readbacks: Vec<Readback>,
+    next_readback: usize,
+    levels: Vec<HiZLevel>,
+    view_projection: Mat4,
+    enabled: bool,
+}
+
+impl HiZBuffer {
+    // size must match the depth buffer the pyramid is built from.
+    pub fn new(size: UVec2) -> Result<Self, RendererError> {
+        let load = |path: &str| -> Result<ProgramPipeline, RendererError> {
+            let program_pipeline = ProgramPipeline::new()
+                .add_shader(&EmbeddedAssets::load_shader(ShaderStage::Compute, path)?)
+                .build()?;
+
+            Ok(program_pipeline)
+        };
+
+        let level_count = Self::level_count_for(size);
+        let readback_level = Self::readback_level_for(size, level_count);
+
+        Ok(Self {
+            pyramid: Texture2D::new_empty(
+                size.x,
+                size.y,
+                SizedTextureFormat::R32f,
+                level_count as i32,
+            ),
+            size,
+            level_count,
+            copy_pipeline: load("src/rendering/shaders/hiz_copy.comp")?,
+            copy_pipeline_multisample: load("src/rendering/shaders/hiz_copy_ms.comp")?,
+            downsample_pipeline: load("src/rendering/shaders/hiz_downsample.comp")?,
+            readback_level,
+            readbacks: Self::create_readbacks(Self::level_size(size, readback_level)),
+            next_readback: 0,
+            levels: vec![],
+            view_projection: Mat4::identity(),
+            enabled: true,
+        })
+    }
+
+    // Recreates the pyramid. Occlusion tests pass until the next readback arrives.
+    pub fn resize(&mut self, size: UVec2) {
+        if size == self.size {
+            return;
+        }
+
+        self.level_count = Self::level_count_for(size);
+        self.readback_level = Self::readback_level_for(size, self.level_count);
+        self.pyramid = Texture2D::new_empty(
+            size.x,
+            size.y,
+            SizedTextureFormat::R32f,
+            self.level_count as i32,
+        );
+        self.readbacks = Self::create_readbacks(Self::level_size(size, self.readback_level));
+        self.next_readback = 0;
+        self.levels.clear();
+        self.size = size;
+    }
+
+    // Builds the pyramid from a depth texture that was rendered with view_projection and
+    // queues its readback. Call after the depth pre pass or the opaque geometry.
+    pub fn build(&mut self, depth: &FramebufferAttachment, samples: u32, view_projection: &Mat4) {
+        if !self.enabled {
+            return;
+        }
+
+        assert!(
+            depth.is_depth_stencil() && depth.attachment_type() == AttachmentType::Texture,
+            "The Hi-Z buffer is built from a depth texture attachment."
+        );
+
+        self.fetch_readbacks();
+
+        let copy_pipeline = if samples > 1 {
+            &self.copy_pipeline_multisample
+        } else {
+            &self.copy_pipeline
+        };
+
+        copy_pipeline.bind();
+
+        unsafe {
+            StateManager::bind_texture_unit(0, depth.id());
+            gl::BindImageTexture(
+                0,
+                self.pyramid.get_id(),
+                0,
+                gl::FALSE,
+                0,
+                gl::WRITE_ONLY,
+                gl::R32F,
+            );
+            Self::dispatch(self.size);
+            StateManager::bind_texture_unit(0, 0);
+        }
+
+        copy_pipeline.unbind();
+
+        self.downsample_pipeline.bind();
+
+        for level in 1..self.level_count {
+            unsafe {
+                gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+                gl::BindImageTexture(
+                    0,
+                    self.pyramid.get_id(),
+                    level as i32 - 1,
+                    gl::FALSE,
+                    0,
+                    gl::READ_ONLY,
+                    gl::R32F,
+                );
+                gl::BindImageTexture(
+                    1,
+                    self.pyramid.get_id(),
+                    level as i32,
+                    gl::FALSE,
+                    0,
+                    gl::WRITE_ONLY,
+                    gl::R32F,
+                );
+                Self::dispatch(Self::level_size(self.size, level));
+            }
+        }
+
+        self.downsample_pipeline.unbind();
+
+        let readback = &mut self.readbacks[self.next_readback];
+
+        // A readback that never arrived is overwritten.
+        readback.view_projection = *view_projection;
+        readback.fence = None;
+
+        unsafe {
+            gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, readback.buffer.get_id());
+            gl::GetTextureImage(
+                self.pyramid.get_id(),
+                self.readback_level as i32,
+                gl::RED,
+                gl::FLOAT,
+                readback.buffer.get_size() as i32,
+                ptr::null_mut(),
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        readback.fence = Some(GpuFence::new());
+        self.next_readback = (self.next_readback + 1) % READBACK_COUNT;
+    }
+
+    // True when the world space bounds may be visible. Bounds that cross the near plane or
+    // leave the screen are always visible, those are left to frustum culling.
+    pub fn is_visible(&self, bounds: &Aabb) -> bool {
+        !self.enabled || self.levels.is_empty() || !self.is_occluded(bounds)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Disabling drops the pyramid, so stale depth isn't used once enabled again.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.levels.clear();
+        }
+
+        self.enabled = enabled
+    }
+
+    // The GPU pyramid, e.g. to visualize it.
+    pub fn texture(&self) -> &Texture2D {
+        &self.pyramid
+    }
+
+    pub fn level_count(&self) -> u32 {
+        self.level_count
+    }
+
+    fn is_occluded(&self, bounds: &Aabb) -> bool {
+        if bounds.is_empty() {
+            return false;
+        }
+
+        let mut min_uv = Vec2::repeat(std::f32::MAX);
+        let mut max_uv = Vec2::repeat(std::f32::MIN);
+        let mut nearest = std::f32::MAX;
+
+        for corner in bounds.corners().iter() {
+            let clip = self.view_projection * Vec4::new(corner.x, corner.y, corner.z, 1.0);
+
+            if clip.w <= std::f32::EPSILON {
+                return false;
+            }
+
+            let ndc = clip.xyz() / clip.w;
+            let uv = ndc.xy() * 0.5 + Vec2::new(0.5, 0.5);
+
+            min_uv = min_uv.inf(&uv);
+            max_uv = max_uv.sup(&uv);
+            nearest = nearest.min(ndc.z * 0.5 + 0.5);
+        }
+
+        if min_uv.x < 0.0 || min_uv.y < 0.0 || max_uv.x > 1.0 || max_uv.y > 1.0 {
+            return false;
+        }
+
+        // Texel rectangle in the finest level, then the coarsest level that covers it with a
+        // few texels.
+        let base = &self.levels[0];
+        let to_texel = |uv: f32, size: u32| ((uv * size as f32) as u32).min(size - 1);
+        let (x0, x1) = (
+            to_texel(min_uv.x, base.size.x),
+            to_texel(max_uv.x, base.size.x),
+        );
+        let (y0, y1) = (
+            to_texel(min_uv.y, base.size.y),
+            to_texel(max_uv.y, base.size.y),
+        );
+
+        let mut level = 0;
+        while level + 1 < self.levels.len()
+            && ((x1 >> level) - (x0 >> level) >= MAX_TEST_TEXELS
+                || (y1 >> level) - (y0 >> level) >= MAX_TEST_TEXELS)
+        {
+            level += 1;
+        }
+
+        let HiZLevel { size, depths } = &self.levels[level];
+        let to_level = |texel: u32, size: u32| (texel >> level).min(size - 1);
+
+        let mut farthest: f32 = 0.0;
+        for y in to_level(y0, size.y)..=to_level(y1, size.y) {
+            for x in to_level(x0, size.x)..=to_level(x1, size.x) {
+                farthest = farthest.max(depths[(y * size.x + x) as usize]);
+            }
+        }
+
+        nearest > farthest
+    }
+
+    // Takes the newest readback that has arrived and builds the coarser levels from it.
+    fn fetch_readbacks(&mut self) {
+        let mut arrived = None;
+
+        for offset in 0..READBACK_COUNT {
+            let index = (self.next_readback + offset) % READBACK_COUNT;
+            let readback = &mut self.readbacks[index];
+
+            if readback.fence.as_ref().map_or(false, GpuFence::is_signaled) {
+                readback.fence = None;
+                arrived = Some(index);
+            }
+        }
+
+        let readback = match arrived {
+            Some(index) => &self.readbacks[index],
+            None => return,
+        };
+
+        let size = Self::level_size(self.size, self.readback_level);
+        let mut depths = vec![0.0f32; (size.x * size.y) as usize];
+
+        unsafe {
+            gl::GetNamedBufferSubData(
+                readback.buffer.get_id(),
+                0,
+                (depths.len() * mem::size_of::<f32>()) as isize,
+                depths.as_mut_ptr() as *mut _,
+            );
+        }
+
+        self.view_projection = readback.view_projection;
+        self.levels.clear();
+        self.levels.push(HiZLevel { size, depths });
+
+        while let Some(level) = self.levels.last().and_then(downsample) {
+            self.levels.push(level);
+        }
+    }
+
+    fn create_readbacks(size: UVec2) -> Vec<Readback> {
+        (0..READBACK_COUNT)
+            .map(|_| Readback {
+                buffer: Buffer::new(
+                    "Hi-Z Readback Buffer",
+                    (size.x * size.y) as isize * mem::size_of::<f32>() as isize,
+                    BufferTarget::PixelPack,
+                    BufferStorageFlags::CLIENT_STORAGE,
+                ),
+                fence: None,
+                view_projection: Mat4::identity(),
+            })
+            .collect()
+    }
+
+    unsafe fn dispatch(size: UVec2) {
+        // Matches the 8x8 local size of the shaders.
+        gl::DispatchCompute((size.x + 7) / 8, (size.y + 7) / 8, 1);
+        FrameStats::record_dispatch();
+    }
+
+    fn level_count_for(size: UVec2) -> u32 {
+        32 - size.x.max(size.y).max(1).leading_zeros()
+    }
+
+    fn level_size(size: UVec2, level: u32) -> UVec2 {
+        UVec2::new((size.x >> level).max(1), (size.y >> level).max(1))
+    }
+
+    fn readback_level_for(size: UVec2, level_count: u32) -> u32 {
+        (0..level_count)
+            .find(|&level| {
+                let size = Self::level_size(size, level);
+                size.x.max(size.y) <= MAX_READBACK_SIZE
+            })
+            .unwrap_or(level_count - 1)
+    }
+}
+
+// The CPU equivalent of hiz_downsample.comp. None once the level is 1x1.
+fn downsample(level: &HiZLevel) -> Option<HiZLevel> {
+    if level.size.x == 1 && level.size.y == 1 {
+        return None;
+    }
+
+    let size = UVec2::new((level.size.x / 2).max(1), (level.size.y / 2).max(1));
+    let mut depths = Vec::with_capacity((size.x * size.y) as usize);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let extent = |texel: u32, size: u32, previous_size: u32| {
+                if texel == size - 1 {
+                    2 + previous_size % 2
+                } else {
+                    2
+                }
+            };
+
+            let mut depth: f32 = 0.0;
+            for sy in 0..extent(y, size.y, level.size.y) {
+                for sx in 0..extent(x, size.x, level.size.x) {
+                    let source_x = (x * 2 + sx).min(level.size.x - 1);
+                    let source_y = (y * 2 + sy).min(level.size.y - 1);
+                    depth = depth.max(level.depths[(source_y * level.size.x + source_x) as usize]);
+                }
+            }
+
+            depths.push(depth);
+        }
+    }
+
+    Some(HiZLevel { size, depths })
+}
@@ -0,0 +1,76 @@
+use crate::rendering::buffer::{Buffer, BufferStorageFlags, BufferTarget};
+use std::mem;
+
+// Matches the layout glMultiDrawElementsIndirect expects.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct DrawElementsIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+// Commands are recorded on the CPU and uploaded in one go before drawing.
+pub struct IndirectDrawBuffer {
+    buffer: Buffer,
+    capacity: usize,
+    commands: Vec<DrawElementsIndirectCommand>,
+    uploaded_count: usize,
+}
+
+impl IndirectDrawBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let buffer = Buffer::new(
+            "Indirect Draw Buffer",
+            (capacity * mem::size_of::<DrawElementsIndirectCommand>()) as isize,
+            BufferTarget::DrawIndirect,
+            BufferStorageFlags::DYNAMIC,
+        );
+
+        Self {
+            buffer,
+            capacity,
+            commands: Vec::with_capacity(capacity),
+            uploaded_count: 0,
+        }
+    }
+
+    pub fn push(&mut self, command: DrawElementsIndirectCommand) {
+        assert!(
+            self.commands.len() < self.capacity,
+            "Indirect draw buffer capacity ({}) exceeded.",
+            self.capacity
+        );
+
+        self.commands.push(command)
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.uploaded_count = 0;
+    }
+
+    pub fn upload(&mut self) {
+        self.buffer.fill_slice(0, &self.commands);
+        self.uploaded_count = self.commands.len();
+    }
+
+    pub fn commands(&self) -> &[DrawElementsIndirectCommand] {
+        &self.commands
+    }
+
+    // Number of commands the GPU will execute.
+    pub fn draw_count(&self) -> usize {
+        self.uploaded_count
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    core::math::Mat4,
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        vertex_layout::{VertexAttribute, VertexFormat, VertexLayout},
+    },
+};
+use std::mem;
+
+// Vertex buffer binding used for the per instance data. Binding 0 holds the vertices.
+pub const INSTANCE_BUFFER_BINDING_INDEX: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct InstanceData {
+    pub model: Mat4,
+    pub material_index: u32,
+}
+
+impl InstanceData {
+    pub fn new(model: Mat4, material_index: u32) -> Self {
+        Self {
+            model,
+            material_index,
+        }
+    }
+
+    pub fn layout() -> VertexLayout {
+        let column_size = VertexFormat::Float4.size();
+
+        VertexLayout::new()
+            .attribute_at_offset(VertexAttribute::InstanceModel0, VertexFormat::Float4, 0)
+            .attribute_at_offset(
+                VertexAttribute::InstanceModel1,
+                VertexFormat::Float4,
+                column_size,
+            )
+            .attribute_at_offset(
+                VertexAttribute::InstanceModel2,
+                VertexFormat::Float4,
+                column_size * 2,
+            )
+            .attribute_at_offset(
+                VertexAttribute::InstanceModel3,
+                VertexFormat::Float4,
+                column_size * 3,
+            )
+            .attribute_at_offset(
+                VertexAttribute::InstanceMaterialIndex,
+                VertexFormat::UnsignedInt,
+                offset_of!(InstanceData, material_index) as u32,
+            )
+            .with_stride(mem::size_of::<InstanceData>() as u32)
+    }
+}
+
+pub struct InstanceBuffer {
+    buffer: Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let buffer = Buffer::new(
+            "Instance Buffer",
+            (capacity * mem::size_of::<InstanceData>()) as isize,
+            BufferTarget::Array,
+            BufferStorageFlags::DYNAMIC,
+        );
+
+        Self {
+            buffer,
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub fn from_slice(instances: &[InstanceData]) -> Self {
+        let mut instance_buffer = Self::new(instances.len());
+        instance_buffer.update(instances);
+        instance_buffer
+    }
+
+    pub fn update(&mut self, instances: &[InstanceData]) {
+        assert!(
+            instances.len() <= self.capacity,
+            "Instance buffer capacity exceeded. Capacity: {}, Requested: {}",
+            self.capacity,
+            instances.len()
+        );
+
+        self.buffer.fill_slice(0, instances);
+        self.len = instances.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
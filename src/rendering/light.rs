@@ -1,9 +1,415 @@
-use crate::math::Vec3;
+use crate::{
+    color::temperature_to_linear,
+    core::ecs::{components::LightKind, systems::LightInstance},
+    core::math::{Vec3, Vec4},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        shadow::ShadowHandle,
+    },
+};
+use std::{f32::consts::PI, mem};
 
+// Storage block binding of the lights, read by the PBS shaders.
+pub const LIGHTS_BINDING: u32 = 4;
+
+const LIGHT_TYPE_DIRECTIONAL: i32 = 0;
+const LIGHT_TYPE_POINT: i32 = 1;
+const LIGHT_TYPE_SPOT: i32 = 2;
+const LIGHT_TYPE_RECT: i32 = 3;
+const LIGHT_TYPE_DISK: i32 = 4;
+
+// Light from far away, e.g. the sun.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    // Towards the light.
+    pub direction: Vec3,
+    // Linear color, tinted by the temperature if there is one.
+    pub color: Vec3,
+    // In Kelvin, e.g. 5500 for noon daylight.
+    pub temperature: Option<f32>,
+    // In lux.
+    pub illuminance: f32,
+}
+
+// Light emitted from a point in every direction, e.g. a light bulb.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub temperature: Option<f32>,
+    // In lumens.
+    pub luminous_power: f32,
+    // Distance at which the light fades out completely.
+    pub range: f32,
+    pub shadow: Option<ShadowHandle>,
+}
+
+// A point light limited to a cone, e.g. a flashlight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vec3,
+    // Along the axis of the cone, away from the light.
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub temperature: Option<f32>,
+    // In lumens. Narrowing the cone doesn't make the light brighter.
+    pub luminous_power: f32,
+    pub range: f32,
+    // Half angles in radians. The light fades out between them.
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub shadow: Option<ShadowHandle>,
+}
+
+// A lit rectangle, e.g. a window or a panel light. Area lights are shaded with linearly
+// transformed cosines, see ltc.rs, and cast no shadows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RectLight {
+    // Center of the rectangle.
+    pub position: Vec3,
+    // Normal of the emitting side.
+    pub direction: Vec3,
+    // Along the height of the rectangle.
+    pub up: Vec3,
+    pub width: f32,
+    pub height: f32,
+    pub color: Vec3,
+    pub temperature: Option<f32>,
+    // In lumens, split between both sides of two sided lights.
+    pub luminous_power: f32,
+    pub range: f32,
+    pub two_sided: bool,
+}
+
+// A lit disk, e.g. a ceiling light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub radius: f32,
+    pub color: Vec3,
+    pub temperature: Option<f32>,
+    pub luminous_power: f32,
+    pub range: f32,
+    pub two_sided: bool,
+}
+
+impl DirectionalLight {
+    // The color the BRDF is multiplied with, the illuminance of a surface facing the light.
+    pub fn shader_color(&self) -> Vec3 {
+        tint(&self.color, self.temperature) * self.illuminance
+    }
+}
+
+impl PointLight {
+    // Luminous intensity in candela. The power is spread over the whole sphere.
+    pub fn shader_color(&self) -> Vec3 {
+        tint(&self.color, self.temperature) * (self.luminous_power / (4.0 * PI))
+    }
+}
+
+impl SpotLight {
+    // Luminous intensity in candela. As with a point light whose light outside the cone is
+    // absorbed, so the cone angle can change without changing the brightness.
+    pub fn shader_color(&self) -> Vec3 {
+        tint(&self.color, self.temperature) * (self.luminous_power / PI)
+    }
+}
+
+impl RectLight {
+    // Luminance in nits of a Lambertian emitter of the size of the rectangle.
+    pub fn shader_color(&self) -> Vec3 {
+        area_light_color(
+            &self.color,
+            self.temperature,
+            self.luminous_power,
+            self.width * self.height,
+            self.two_sided,
+        )
+    }
+}
+
+impl DiskLight {
+    pub fn shader_color(&self) -> Vec3 {
+        area_light_color(
+            &self.color,
+            self.temperature,
+            self.luminous_power,
+            PI * self.radius * self.radius,
+            self.two_sided,
+        )
+    }
+}
+
+fn area_light_color(
+    color: &Vec3,
+    temperature: Option<f32>,
+    luminous_power: f32,
+    area: f32,
+    two_sided: bool,
+) -> Vec3 {
+    let sides = if two_sided { 2.0 } else { 1.0 };
+    tint(color, temperature) * (luminous_power / (sides * PI * area.max(0.0001)))
+}
+
+fn tint(color: &Vec3, temperature: Option<f32>) -> Vec3 {
+    match temperature {
+        Some(kelvin) => color.component_mul(&temperature_to_linear(kelvin)),
+        None => *color,
+    }
+}
+
+// Layout of the Light struct of the PBS shaders.
 #[repr(C)]
-#[derive(Debug)]
-pub enum Light {
-    Directional { direction: Vec3, temperature: u32 },
-    Point,
-    Spotlight,
+#[derive(Debug, Clone, Copy)]
+struct LightData {
+    // w unused.
+    position: Vec4,
+    // Towards directional lights, along the cone of spot lights and the first half axis of area
+    // lights. w: range.
+    direction: Vec4,
+    color: Vec4,
+    // Scale and offset of the cosine of the angle to the cone axis for spot lights, the second
+    // half axis of area lights.
+    shape: Vec4,
+    // x: type, y: first shadow view or -1, z: two sided.
+    info: [i32; 4],
+}
+
+// The lights of a frame, uploaded to a storage buffer at LIGHTS_BINDING.
+pub struct LightBuffer {
+    buffer: Buffer,
+    lights: Vec<LightData>,
+    capacity: usize,
+}
+
+impl LightBuffer {
+    pub fn new(capacity: usize) -> Self {
+        // The light count is padded to 16 bytes, as the array that follows it in the block.
+        let size = 16 + capacity * mem::size_of::<LightData>();
+
+        Self {
+            buffer: Buffer::new(
+                "Lights",
+                size as isize,
+                BufferTarget::ShaderStorage,
+                BufferStorageFlags::DYNAMIC,
+            ),
+            lights: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear()
+    }
+
+    pub fn push_directional(&mut self, light: &DirectionalLight) {
+        let direction = light.direction.normalize();
+        let color = light.shader_color();
+
+        self.push(LightData {
+            position: Vec4::new(0.0, 0.0, 0.0, 0.0),
+            direction: Vec4::new(direction.x, direction.y, direction.z, 0.0),
+            color: Vec4::new(color.x, color.y, color.z, 0.0),
+            shape: Vec4::new(0.0, 0.0, 0.0, 0.0),
+            info: [LIGHT_TYPE_DIRECTIONAL, -1, 0, 0],
+        })
+    }
+
+    pub fn push_point(&mut self, light: &PointLight) {
+        let color = light.shader_color();
+
+        self.push(LightData {
+            position: Vec4::new(light.position.x, light.position.y, light.position.z, 0.0),
+            direction: Vec4::new(0.0, 0.0, 0.0, light.range),
+            color: Vec4::new(color.x, color.y, color.z, 0.0),
+            shape: Vec4::new(0.0, 0.0, 0.0, 0.0),
+            info: [LIGHT_TYPE_POINT, shadow_index(light.shadow), 0, 0],
+        })
+    }
+
+    pub fn push_spot(&mut self, light: &SpotLight) {
+        let direction = light.direction.normalize();
+        let color = light.shader_color();
+
+        let cos_outer = light.outer_angle.cos();
+        let cos_inner = light.inner_angle.min(light.outer_angle).cos();
+        let scale = 1.0 / (cos_inner - cos_outer).max(0.0001);
+
+        self.push(LightData {
+            position: Vec4::new(light.position.x, light.position.y, light.position.z, 0.0),
+            direction: Vec4::new(direction.x, direction.y, direction.z, light.range),
+            color: Vec4::new(color.x, color.y, color.z, 0.0),
+            shape: Vec4::new(scale, -cos_outer * scale, 0.0, 0.0),
+            info: [LIGHT_TYPE_SPOT, shadow_index(light.shadow), 0, 0],
+        })
+    }
+
+    pub fn push_rect(&mut self, light: &RectLight) {
+        let (x_axis, y_axis) = area_light_axes(&light.direction, &light.up);
+        self.push(area_light_data(
+            LIGHT_TYPE_RECT,
+            &light.position,
+            (x_axis * (light.width * 0.5), y_axis * (light.height * 0.5)),
+            &light.shader_color(),
+            light.range,
+            light.two_sided,
+        ))
+    }
+
+    pub fn push_disk(&mut self, light: &DiskLight) {
+        let (x_axis, y_axis) = area_light_axes(&light.direction, &Vec3::new(0.0, 1.0, 0.0));
+        self.push(area_light_data(
+            LIGHT_TYPE_DISK,
+            &light.position,
+            (x_axis * light.radius, y_axis * light.radius),
+            &light.shader_color(),
+            light.range,
+            light.two_sided,
+        ))
+    }
+
+    // Pushes a light component with the position and direction of its entity.
+    pub fn push_instance(&mut self, instance: &LightInstance, shadow: Option<ShadowHandle>) {
+        let light = &instance.light;
+
+        match light.kind {
+            LightKind::Directional => self.push_directional(&DirectionalLight {
+                direction: -instance.direction,
+                color: light.color,
+                temperature: light.temperature,
+                illuminance: light.intensity,
+            }),
+            LightKind::Point { range } => self.push_point(&PointLight {
+                position: instance.position,
+                color: light.color,
+                temperature: light.temperature,
+                luminous_power: light.intensity,
+                range,
+                shadow,
+            }),
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            } => self.push_spot(&SpotLight {
+                position: instance.position,
+                direction: instance.direction,
+                color: light.color,
+                temperature: light.temperature,
+                luminous_power: light.intensity,
+                range,
+                inner_angle: inner_angle.to_radians(),
+                outer_angle: outer_angle.to_radians(),
+                shadow,
+            }),
+            LightKind::Rect {
+                width,
+                height,
+                range,
+                two_sided,
+            } => self.push_rect(&RectLight {
+                position: instance.position,
+                direction: instance.direction,
+                up: instance.up,
+                width,
+                height,
+                color: light.color,
+                temperature: light.temperature,
+                luminous_power: light.intensity,
+                range,
+                two_sided,
+            }),
+            LightKind::Disk {
+                radius,
+                range,
+                two_sided,
+            } => self.push_disk(&DiskLight {
+                position: instance.position,
+                direction: instance.direction,
+                radius,
+                color: light.color,
+                temperature: light.temperature,
+                luminous_power: light.intensity,
+                range,
+                two_sided,
+            }),
+        }
+    }
+
+    // Writes the lights pushed since clear to the buffer.
+    pub fn upload(&self) {
+        self.buffer.fill(0, &[self.lights.len() as u32, 0, 0, 0]);
+
+        if !self.lights.is_empty() {
+            self.buffer.fill_slice(16, &self.lights);
+        }
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind(LIGHTS_BINDING)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn push(&mut self, light: LightData) {
+        if self.lights.len() == self.capacity {
+            log::warn!("Light capacity exceeded. Capacity: {}", self.capacity);
+            return;
+        }
+
+        self.lights.push(light)
+    }
+}
+
+// The half axes span the light from its center.
+fn area_light_data(
+    light_type: i32,
+    position: &Vec3,
+    (x_axis, y_axis): (Vec3, Vec3),
+    color: &Vec3,
+    range: f32,
+    two_sided: bool,
+) -> LightData {
+    LightData {
+        position: Vec4::new(position.x, position.y, position.z, 0.0),
+        direction: Vec4::new(x_axis.x, x_axis.y, x_axis.z, range),
+        color: Vec4::new(color.x, color.y, color.z, 0.0),
+        shape: Vec4::new(y_axis.x, y_axis.y, y_axis.z, 0.0),
+        info: [light_type, -1, two_sided as i32, 0],
+    }
+}
+
+// Unit axes of the plane of an area light. The shaders light the side x_axis.cross(y_axis) points
+// away from, so the winding of the polygon faces the direction.
+fn area_light_axes(direction: &Vec3, up: &Vec3) -> (Vec3, Vec3) {
+    let direction = direction.normalize();
+    let mut y_axis = up - direction * up.dot(&direction);
+
+    if y_axis.norm() < 0.0001 {
+        y_axis = if direction.y.abs() > 0.99 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        y_axis -= direction * y_axis.dot(&direction);
+    }
+
+    let y_axis = y_axis.normalize();
+    (direction.cross(&y_axis), y_axis)
+}
+
+fn shadow_index(shadow: Option<ShadowHandle>) -> i32 {
+    shadow.map_or(-1, |shadow| shadow.index() as i32)
 }
@@ -0,0 +1,166 @@
+use crate::{
+    core::camera::Camera,
+    imgui::{im_str, Gui, Ui},
+    rendering::buffer::{Buffer, BufferStorageFlags, BufferTarget},
+};
+use std::{mem, ops::RangeInclusive};
+
+// Uniform block binding of the LightCullingDebugBlock of the lighting shaders.
+pub const LIGHT_CULLING_DEBUG_BINDING: u32 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightCullingGrid {
+    // Screen tiles over the whole depth range.
+    Tiles = 0,
+    // Screen tiles split further into depth slices, exponentially distributed between the near
+    // and far planes of the camera.
+    Clusters = 1,
+}
+
+// Layout of the LightCullingDebugBlock of the lighting shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LightCullingDebugUniforms {
+    viewport_size: [f32; 2],
+    near: f32,
+    far: f32,
+    tile_size: i32,
+    slices: i32,
+    grid: i32,
+    // Lights at which the heatmap saturates.
+    max_lights: i32,
+}
+
+// Settings of the light count render mode of the lighting shaders. Every fragment counts the
+// lights whose bounds overlap its tile or cluster, with the same sphere tests a tiled or
+// clustered light culler would use, and shows the count as a heatmap over the scene.
+pub struct LightCullingDebug {
+    grid: LightCullingGrid,
+    // In pixels.
+    tile_size: u32,
+    slices: u32,
+    max_lights: u32,
+    buffer: Buffer,
+}
+
+impl LightCullingDebug {
+    pub fn new() -> Self {
+        Self {
+            grid: LightCullingGrid::Tiles,
+            tile_size: 16,
+            slices: 24,
+            max_lights: 16,
+            buffer: Buffer::new(
+                "Light Culling Debug UBO",
+                mem::size_of::<LightCullingDebugUniforms>() as isize,
+                BufferTarget::Uniform,
+                BufferStorageFlags::DYNAMIC,
+            ),
+        }
+    }
+
+    pub fn grid(&self) -> LightCullingGrid {
+        self.grid
+    }
+
+    pub fn set_grid(&mut self, grid: LightCullingGrid) {
+        self.grid = grid
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: u32) {
+        self.tile_size = tile_size.max(1)
+    }
+
+    pub fn slices(&self) -> u32 {
+        self.slices
+    }
+
+    pub fn set_slices(&mut self, slices: u32) {
+        self.slices = slices.max(1)
+    }
+
+    // Uploads the grid of the camera and binds it to the uniform block binding.
+    pub fn bind(&self, camera: &Camera, binding: u32) {
+        let viewport_size = camera.viewport_size();
+        let projection = camera.projection();
+
+        self.buffer.fill(
+            0,
+            &LightCullingDebugUniforms {
+                viewport_size: [viewport_size.x as f32, viewport_size.y as f32],
+                near: projection.near(),
+                far: projection.far(),
+                tile_size: self.tile_size as i32,
+                slices: self.slices as i32,
+                grid: self.grid as i32,
+                max_lights: self.max_lights as i32,
+            },
+        );
+        self.buffer.bind(binding)
+    }
+}
+
+impl Default for LightCullingDebug {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gui for LightCullingDebug {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Light Culling"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                ui.indent();
+
+                let mut grid = self.grid as usize;
+                if imgui::ComboBox::new(im_str!("Grid")).build_simple_string(
+                    ui,
+                    &mut grid,
+                    &[im_str!("Tiles"), im_str!("Clusters")],
+                ) {
+                    self.grid = if grid == 0 {
+                        LightCullingGrid::Tiles
+                    } else {
+                        LightCullingGrid::Clusters
+                    };
+                }
+
+                let mut tile_size = self.tile_size as i32;
+                if imgui::Slider::new(im_str!("Tile Size"))
+                    .range(RangeInclusive::new(8, 128))
+                    .build(&ui, &mut tile_size)
+                {
+                    self.set_tile_size(tile_size as u32)
+                }
+
+                if self.grid == LightCullingGrid::Clusters {
+                    let mut slices = self.slices as i32;
+                    if imgui::Slider::new(im_str!("Depth Slices"))
+                        .range(RangeInclusive::new(1, 64))
+                        .build(&ui, &mut slices)
+                    {
+                        self.set_slices(slices as u32)
+                    }
+                }
+
+                let mut max_lights = self.max_lights as i32;
+                if imgui::Slider::new(im_str!("Heatmap Max"))
+                    .range(RangeInclusive::new(1, 64))
+                    .build(&ui, &mut max_lights)
+                {
+                    self.max_lights = max_lights.max(1) as u32
+                }
+                ui.text(im_str!("Shown with the Light Count render mode."));
+
+                ui.unindent()
+            });
+    }
+}
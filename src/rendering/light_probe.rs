@@ -0,0 +1,547 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Mat4, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        error::RendererError,
+        fence::GpuFence,
+        framebuffer::Framebuffer,
+        mesh::FULLSCREEN_MESH,
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{DepthStencilState, FixedFunctionState, RasterizerState, StateManager},
+        texture::{SizedTextureFormat, TextureCube, TextureCubeArray},
+        Draw,
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use nalgebra_glm as glm;
+use std::{f32::consts::PI, mem, ops::RangeInclusive};
+
+// Storage block binding of the light probes, read by the PBS shaders.
+pub const LIGHT_PROBES_BINDING: u32 = 5;
+
+const RADIANCE_SIZE: u32 = 128;
+// The same levels as the radiance of the sky, for the roughness lookup of the specular term.
+const RADIANCE_LEVELS: i32 = 8;
+const IRRADIANCE_SIZE: u32 = 32;
+const IRRADIANCE_SAMPLE_LOD: f32 = 2.0;
+const NEAR_PLANE: f32 = 0.05;
+const FAR_PLANE: f32 = 1000.0;
+
+const PROBE_SHAPE_BOX: i32 = 0;
+const PROBE_SHAPE_SPHERE: i32 = 1;
+
+// The +X, -X, +Y, -Y, +Z, -Z faces of the GL cube map layout.
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+// The volume a probe lights, centered on the probe. Reflections are projected onto it, so it
+// should match the surroundings, e.g. a box for the walls of a room.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeShape {
+    // Half extents of a world axis aligned box.
+    Box(Vec3),
+    Sphere(f32),
+}
+
+// Cube maps of the surroundings captured at a point. Inside its volume they replace the
+// environment maps for image based lighting, e.g. so a room doesn't reflect the sky.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightProbe {
+    pub position: Vec3,
+    pub shape: ProbeShape,
+    // Distance inside the border of the volume over which the probe fades into what is behind.
+    pub blend_distance: f32,
+    pub intensity: f32,
+}
+
+impl LightProbe {
+    pub fn new(position: Vec3, shape: ProbeShape) -> Self {
+        Self {
+            position,
+            shape,
+            blend_distance: 0.5,
+            intensity: 1.0,
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        match self.shape {
+            ProbeShape::Box(half_extents) => 8.0 * half_extents.x * half_extents.y * half_extents.z,
+            ProbeShape::Sphere(radius) => 4.0 / 3.0 * PI * radius * radius * radius,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightProbeHandle(usize);
+
+// A cube face of the probe being baked.
+pub struct ProbeView {
+    pub position: Vec3,
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+}
+
+// Layout of the LightProbe struct of the PBS shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LightProbeData {
+    // w: intensity.
+    position: Vec4,
+    // xyz: half extents of a box, x: radius of a sphere. w: blend distance.
+    extents: Vec4,
+    // x: layer in the cube map arrays, y: shape.
+    info: [i32; 4],
+}
+
+struct ProbeSlot {
+    probe: LightProbe,
+    baked: bool,
+}
+
+// Reflection and irradiance probes, baked into layers of two cube map arrays so any number of
+// them takes two texture units. The shaders blend the probes covering a pixel, smaller ones
+// first, and fall back to the environment maps for what they leave uncovered.
+pub struct LightProbes {
+    // Indexed by the layer of the probe.
+    slots: Vec<Option<ProbeSlot>>,
+    radiance: TextureCubeArray,
+    irradiance: TextureCubeArray,
+    capture: TextureCube,
+    capture_depth: GLuint,
+    capture_framebuffer: GLuint,
+    irradiance_pipeline_state: PipelineState,
+    sampler_linear: Sampler,
+    probes_buffer: Buffer,
+    enabled: bool,
+}
+
+impl LightProbes {
+    pub fn new(capacity: usize) -> Result<Self, RendererError> {
+        let irradiance_program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/sky.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/sky_irradiance.frag",
+            )?)
+            .build()?;
+
+        let mut capture_depth: GLuint = 0;
+        let mut capture_framebuffer: GLuint = 0;
+        unsafe {
+            gl::CreateRenderbuffers(1, &mut capture_depth);
+            gl::NamedRenderbufferStorage(
+                capture_depth,
+                SizedTextureFormat::Depth24 as u32,
+                RADIANCE_SIZE as i32,
+                RADIANCE_SIZE as i32,
+            );
+
+            gl::CreateFramebuffers(1, &mut capture_framebuffer);
+            gl::NamedFramebufferRenderbuffer(
+                capture_framebuffer,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                capture_depth,
+            );
+        }
+
+        // The probe count is padded to 16 bytes, as the array that follows it in the block.
+        let size = 16 + capacity * mem::size_of::<LightProbeData>();
+
+        let probes = Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            radiance: TextureCubeArray::new_empty(
+                RADIANCE_SIZE,
+                capacity as u32,
+                SizedTextureFormat::Rgba16f,
+                RADIANCE_LEVELS,
+            ),
+            irradiance: TextureCubeArray::new_empty(
+                IRRADIANCE_SIZE,
+                capacity as u32,
+                SizedTextureFormat::Rgba16f,
+                1,
+            ),
+            capture: TextureCube::new_empty(
+                RADIANCE_SIZE,
+                SizedTextureFormat::Rgba16f,
+                RADIANCE_LEVELS,
+            ),
+            capture_depth,
+            capture_framebuffer,
+            irradiance_pipeline_state: PipelineStateBuilder::new(irradiance_program_pipeline)
+                .depth_stencil(DepthStencilState {
+                    depth_test: false,
+                    depth_write: false,
+                    ..Default::default()
+                })
+                .rasterizer(RasterizerState {
+                    face_culling: None,
+                    ..Default::default()
+                })
+                .build(),
+            sampler_linear: Sampler::new(
+                MinificationFilter::LinearMipmapLinear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            probes_buffer: Buffer::new(
+                "Light Probes",
+                size as isize,
+                BufferTarget::ShaderStorage,
+                BufferStorageFlags::DYNAMIC,
+            ),
+            enabled: true,
+        };
+
+        probes.upload();
+
+        Ok(probes)
+    }
+
+    // None once every layer is taken. The probe lights nothing until it is baked.
+    pub fn add(&mut self, probe: LightProbe) -> Option<LightProbeHandle> {
+        let index = match self.slots.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                log::warn!(
+                    "Light probe capacity exceeded. Capacity: {}",
+                    self.slots.len()
+                );
+                return None;
+            }
+        };
+
+        self.slots[index] = Some(ProbeSlot {
+            probe,
+            baked: false,
+        });
+
+        Some(LightProbeHandle(index))
+    }
+
+    pub fn remove(&mut self, handle: LightProbeHandle) {
+        if self.slots[handle.0].take().is_some() {
+            self.upload()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.upload()
+    }
+
+    pub fn probe(&self, handle: LightProbeHandle) -> Option<&LightProbe> {
+        self.slots[handle.0].as_ref().map(|slot| &slot.probe)
+    }
+
+    // Moving the probe takes a bake to show, the other parameters apply immediately.
+    pub fn set_probe(&mut self, handle: LightProbeHandle, probe: LightProbe) {
+        if let Some(slot) = self.slots[handle.0].as_mut() {
+            if slot.probe.position != probe.position {
+                slot.baked = false;
+            }
+            slot.probe = probe;
+            self.upload()
+        }
+    }
+
+    pub fn handles(&self) -> Vec<LightProbeHandle> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_some())
+            .map(|(index, _)| LightProbeHandle(index))
+            .collect()
+    }
+
+    // Renders the surroundings of the probe into its cube maps. draw is called once per cube
+    // face and renders the scene, sky included, with the view of the face into the bound
+    // framebuffer. It may rewrite uniform buffers, the previous face has finished by then.
+    // No probe lights the scene while it is baked.
+    pub fn bake<F: FnMut(&ProbeView)>(&mut self, handle: LightProbeHandle, mut draw: F) {
+        let probe = match self.probe(handle) {
+            Some(probe) => *probe,
+            None => return,
+        };
+        let layer = handle.0 as i32;
+
+        self.probes_buffer.fill(0, &[0u32, 0, 0, 0]);
+
+        let projection = glm::perspective(1.0, 90.0f32.to_radians(), NEAR_PLANE, FAR_PLANE);
+
+        StateManager::bind_framebuffer(self.capture_framebuffer);
+
+        for (face, (direction, up)) in FACES.iter().enumerate() {
+            unsafe {
+                gl::NamedFramebufferTextureLayer(
+                    self.capture_framebuffer,
+                    gl::COLOR_ATTACHMENT0,
+                    self.capture.get_id(),
+                    0,
+                    face as i32,
+                );
+            }
+
+            // The clear needs depth writes, which the previous face may have left disabled.
+            StateManager::apply(&FixedFunctionState::default());
+            StateManager::set_viewport(0, 0, RADIANCE_SIZE as i32, RADIANCE_SIZE as i32);
+            unsafe {
+                gl::ClearNamedFramebufferfv(
+                    self.capture_framebuffer,
+                    gl::COLOR,
+                    0,
+                    [0.0f32, 0.0, 0.0, 1.0].as_ptr(),
+                );
+                gl::ClearNamedFramebufferfv(self.capture_framebuffer, gl::DEPTH, 0, &1.0f32);
+            }
+
+            GpuFence::new().wait();
+
+            let view = glm::look_at(
+                &probe.position,
+                &(probe.position + Vec3::from(*direction)),
+                &Vec3::from(*up),
+            );
+            draw(&ProbeView {
+                position: probe.position,
+                view,
+                projection,
+                view_projection: projection * view,
+            });
+        }
+
+        unsafe {
+            gl::GenerateTextureMipmap(self.capture.get_id());
+
+            for level in 0..RADIANCE_LEVELS {
+                let size = (RADIANCE_SIZE >> level).max(1) as i32;
+                gl::CopyImageSubData(
+                    self.capture.get_id(),
+                    gl::TEXTURE_CUBE_MAP,
+                    level,
+                    0,
+                    0,
+                    0,
+                    self.radiance.get_id(),
+                    gl::TEXTURE_CUBE_MAP_ARRAY,
+                    level,
+                    0,
+                    0,
+                    layer * 6,
+                    size,
+                    size,
+                    6,
+                );
+            }
+        }
+
+        self.convolve_irradiance(layer);
+
+        StateManager::bind_framebuffer(Framebuffer::default_id());
+        StateManager::apply(&FixedFunctionState::default());
+
+        // Callers restore their uniform buffers next, which the last face may still read.
+        GpuFence::new().wait();
+
+        if let Some(slot) = self.slots[handle.0].as_mut() {
+            slot.baked = true;
+        }
+        self.upload()
+    }
+
+    pub fn bake_all<F: FnMut(&ProbeView)>(&mut self, mut draw: F) {
+        for handle in self.handles() {
+            self.bake(handle, &mut draw)
+        }
+    }
+
+    // Binds the radiance array to the texture unit, the irradiance array to the next one and
+    // the probes to LIGHT_PROBES_BINDING.
+    pub fn bind(&self, program_pipeline: &ProgramPipeline, binding: u32) {
+        program_pipeline
+            .set_texture_cube_array(binding, &self.radiance, &self.sampler_linear)
+            .set_texture_cube_array(binding + 1, &self.irradiance, &self.sampler_linear);
+        self.probes_buffer.bind(LIGHT_PROBES_BINDING)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.upload()
+    }
+
+    // Baked probes, smallest volume first so nested probes take precedence.
+    fn upload(&self) {
+        let mut probes: Vec<(i32, &LightProbe)> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(layer, slot)| match slot {
+                Some(slot) if slot.baked && self.enabled => Some((layer as i32, &slot.probe)),
+                _ => None,
+            })
+            .collect();
+        probes.sort_by(|(_, a), (_, b)| a.volume().partial_cmp(&b.volume()).unwrap());
+
+        let data: Vec<LightProbeData> = probes
+            .into_iter()
+            .map(|(layer, probe)| {
+                let (extents, shape) = match probe.shape {
+                    ProbeShape::Box(half_extents) => (half_extents, PROBE_SHAPE_BOX),
+                    ProbeShape::Sphere(radius) => {
+                        (Vec3::new(radius, radius, radius), PROBE_SHAPE_SPHERE)
+                    }
+                };
+
+                LightProbeData {
+                    position: Vec4::new(
+                        probe.position.x,
+                        probe.position.y,
+                        probe.position.z,
+                        probe.intensity,
+                    ),
+                    extents: Vec4::new(extents.x, extents.y, extents.z, probe.blend_distance),
+                    info: [layer, shape, 0, 0],
+                }
+            })
+            .collect();
+
+        self.probes_buffer.fill(0, &[data.len() as u32, 0, 0, 0]);
+
+        if !data.is_empty() {
+            self.probes_buffer.fill_slice(16, &data);
+        }
+    }
+
+    fn convolve_irradiance(&self, layer: i32) {
+        let projection = glm::perspective(1.0, 90.0f32.to_radians(), 0.1, 10.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        self.irradiance_pipeline_state.bind();
+        self.irradiance_pipeline_state
+            .program_pipeline()
+            .set_texture_cube(0, &self.capture, &self.sampler_linear)
+            .set_float_all_stages("sampleLod", IRRADIANCE_SAMPLE_LOD);
+
+        StateManager::set_viewport(0, 0, IRRADIANCE_SIZE as i32, IRRADIANCE_SIZE as i32);
+
+        for (face, (direction, up)) in FACES.iter().enumerate() {
+            let inverse_view_projection = (projection
+                * glm::look_at(&origin, &Vec3::from(*direction), &Vec3::from(*up)))
+            .try_inverse()
+            .unwrap_or_else(Mat4::identity);
+
+            unsafe {
+                gl::NamedFramebufferTextureLayer(
+                    self.capture_framebuffer,
+                    gl::COLOR_ATTACHMENT0,
+                    self.irradiance.get_id(),
+                    0,
+                    layer * 6 + face as i32,
+                );
+            }
+
+            self.irradiance_pipeline_state
+                .program_pipeline()
+                .set_mat4_all_stages("inverseViewProjection", &inverse_view_projection);
+            FULLSCREEN_MESH.draw();
+        }
+
+        self.irradiance_pipeline_state.unbind()
+    }
+}
+
+impl Drop for LightProbes {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.capture_framebuffer);
+            gl::DeleteRenderbuffers(1, &self.capture_depth);
+        }
+    }
+}
+
+impl Gui for LightProbes {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            let mut enabled = self.enabled;
+            if ui.checkbox(im_str!("##light_probes"), &mut enabled) {
+                self.set_enabled(enabled)
+            }
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Light Probes"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    let mut changed = false;
+                    for (index, slot) in self.slots.iter_mut().enumerate() {
+                        if let Some(slot) = slot {
+                            let id = ui.push_id(index as i32);
+                            ui.text(format!(
+                                "Probe {}{}",
+                                index,
+                                if slot.baked { "" } else { " (not baked)" }
+                            ));
+                            changed |= imgui::Slider::new(im_str!("Intensity"))
+                                .range(RangeInclusive::new(0.0, 4.0))
+                                .display_format(im_str!("%.2f"))
+                                .build(&ui, &mut slot.probe.intensity);
+                            changed |= imgui::Slider::new(im_str!("Blend Distance"))
+                                .range(RangeInclusive::new(0.0, 4.0))
+                                .display_format(im_str!("%.2f"))
+                                .build(&ui, &mut slot.probe.blend_distance);
+                            id.pop(ui);
+                        }
+                    }
+
+                    if changed {
+                        self.upload()
+                    }
+
+                    ui.text(format!("{} of {} probes", self.len(), self.capacity()));
+
+                    ui.unindent()
+                });
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    core::{asset::Handle, math::Vec3},
+    rendering::mesh::Mesh,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodMetric {
+    // Thresholds are camera distances, ascending. A level is used while the distance is
+    // below its threshold.
+    Distance,
+    // Thresholds are projected bounding sphere sizes relative to the viewport height,
+    // descending. A level is used while the projected size is above its threshold.
+    ScreenSize,
+}
+
+pub struct LodLevel {
+    pub mesh: Handle<Mesh>,
+    pub threshold: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodView {
+    pub camera_position: Vec3,
+    // Radians.
+    pub vertical_fov: f32,
+}
+
+pub struct LodGroup {
+    levels: Vec<LodLevel>,
+    metric: LodMetric,
+    bounding_radius: f32,
+    bias: f32,
+}
+
+impl LodGroup {
+    // Index of the level to draw for an object at the given world position.
+    pub fn select(&self, world_position: &Vec3, view: &LodView) -> usize {
+        let distance = (world_position - view.camera_position).norm() * self.bias;
+
+        let selected = match self.metric {
+            LodMetric::Distance => self
+                .levels
+                .iter()
+                .position(|level| distance < level.threshold),
+            LodMetric::ScreenSize => {
+                let screen_size = if distance > 0.0 {
+                    self.bounding_radius / (distance * (view.vertical_fov * 0.5).tan())
+                } else {
+                    std::f32::MAX
+                };
+
+                self.levels
+                    .iter()
+                    .position(|level| screen_size >= level.threshold)
+            }
+        };
+
+        selected.unwrap_or(self.levels.len() - 1)
+    }
+
+    pub fn select_mesh(&self, world_position: &Vec3, view: &LodView) -> &Mesh {
+        &self.levels[self.select(world_position, view)].mesh
+    }
+
+    pub fn level(&self, index: usize) -> &LodLevel {
+        &self.levels[index]
+    }
+
+    pub fn levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    pub fn metric(&self) -> LodMetric {
+        self.metric
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    // Values above 1 switch to coarser levels earlier.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias.max(0.0)
+    }
+}
+
+pub struct LodGroupBuilder {
+    levels: Vec<LodLevel>,
+    metric: LodMetric,
+    bounding_radius: f32,
+    bias: f32,
+}
+
+impl LodGroupBuilder {
+    pub fn new(metric: LodMetric) -> Self {
+        Self {
+            levels: vec![],
+            metric,
+            bounding_radius: 1.0,
+            bias: 1.0,
+        }
+    }
+
+    // Levels are added from the most to the least detailed.
+    pub fn level(mut self, mesh: Handle<Mesh>, threshold: f32) -> Self {
+        self.levels.push(LodLevel { mesh, threshold });
+        self
+    }
+
+    // Generates the coarser levels by simplifying the given mesh. Each entry is the
+    // (triangle ratio, threshold) of a level. The full detail mesh becomes the first level.
+    #[cfg(feature = "meshopt")]
+    pub fn generate(
+        mut self,
+        mesh_data: &crate::geometry::MeshData,
+        threshold: f32,
+        levels: &[(f32, f32)],
+    ) -> Self {
+        use crate::geometry::simplify;
+
+        self.levels.push(LodLevel {
+            mesh: Handle::new(mesh_data.clone().into_mesh()),
+            threshold,
+        });
+
+        for &(ratio, threshold) in levels {
+            let simplified = simplify::simplify(mesh_data, ratio, 0.01);
+
+            self.levels.push(LodLevel {
+                mesh: Handle::new(simplified.into_mesh()),
+                threshold,
+            });
+        }
+
+        self
+    }
+
+    pub fn bounding_radius(mut self, bounding_radius: f32) -> Self {
+        self.bounding_radius = bounding_radius;
+        self
+    }
+
+    pub fn bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    pub fn build(self) -> LodGroup {
+        assert!(
+            !self.levels.is_empty(),
+            "A LOD group requires at least one level."
+        );
+
+        let ordered = self.levels.windows(2).all(|w| match self.metric {
+            LodMetric::Distance => w[0].threshold <= w[1].threshold,
+            LodMetric::ScreenSize => w[0].threshold >= w[1].threshold,
+        });
+
+        if !ordered {
+            log::warn!("LOD thresholds are not ordered. Some levels will never be selected.")
+        }
+
+        LodGroup {
+            levels: self.levels,
+            metric: self.metric,
+            bounding_radius: self.bounding_radius,
+            bias: self.bias.max(0.0),
+        }
+    }
+}
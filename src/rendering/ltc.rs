@@ -0,0 +1,448 @@
+use crate::{
+    core::math::{Vec3, Vec4},
+    rendering::{
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        texture::{SizedTextureFormat, Texture2D, TextureFormat},
+    },
+};
+use gl_bindings as gl;
+use nalgebra_glm as glm;
+use std::{f32::consts::PI, mem};
+
+// Generated with LtcTable::fit(64, 32). The matrices of all the texels followed by their
+// amplitudes, as little endian f32.
+const EMBEDDED_SIZE: usize = 64;
+static EMBEDDED_TABLE: &[u8] = include_bytes!("luts/ltc.bin");
+
+const MIN_ALPHA: f32 = 0.0001;
+
+// Linearly transformed cosines that approximate the GGX BRDF (Heitz et al., Real-Time Polygonal
+// Light Shading with Linearly Transformed Cosines, 2016). Indexed by perceptual roughness along
+// x and sqrt(1 - NdotV) along y.
+pub struct LtcTable {
+    size: usize,
+    // The 4 non trivial entries of the inverse transform, m00, m20, m02 and m22.
+    matrices: Vec<[f32; 4]>,
+    // Integral of the BRDF and of the Schlick Fresnel part of it.
+    amplitudes: Vec<[f32; 2]>,
+}
+
+impl LtcTable {
+    pub fn embedded() -> Self {
+        let floats: Vec<f32> = EMBEDDED_TABLE
+            .chunks_exact(mem::size_of::<f32>())
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+
+        let texel_count = EMBEDDED_SIZE * EMBEDDED_SIZE;
+        let (matrices, amplitudes) = floats.split_at(texel_count * 4);
+
+        Self {
+            size: EMBEDDED_SIZE,
+            matrices: matrices
+                .chunks_exact(4)
+                .map(|m| [m[0], m[1], m[2], m[3]])
+                .collect(),
+            amplitudes: amplitudes.chunks_exact(2).map(|a| [a[0], a[1]]).collect(),
+        }
+    }
+
+    // Fits the table with sample_count^2 samples per error estimate. Takes minutes for the size
+    // of the embedded table, mostly useful to regenerate it.
+    pub fn fit(size: usize, sample_count: usize) -> Self {
+        let mut matrices = vec![[0.0; 4]; size * size];
+        let mut amplitudes = vec![[0.0; 2]; size * size];
+
+        // Each fit starts from the previous one, going from rough to smooth and from normal to
+        // grazing incidence, where the lobes change the least.
+        let mut first_guess = [1.0, 1.0, 0.0];
+
+        for roughness_index in (0..size).rev() {
+            let perceptual_roughness = roughness_index as f32 / (size - 1) as f32;
+            let alpha = (perceptual_roughness * perceptual_roughness).max(MIN_ALPHA);
+
+            let mut guess = first_guess;
+
+            for view_index in 0..size {
+                let x = view_index as f32 / (size - 1) as f32;
+                let theta = (1.0 - x * x).acos().min(1.57);
+                let view = Vec3::new(theta.sin(), 0.0, theta.cos());
+
+                let (magnitude, fresnel, average_direction) =
+                    ggx_average_terms(&view, alpha, sample_count);
+
+                // At normal incidence the lobe is symmetric around the normal.
+                let isotropic = view_index == 0;
+                let basis = if isotropic {
+                    glm::Mat3::identity()
+                } else {
+                    let z = average_direction;
+                    glm::Mat3::from_columns(&[
+                        Vec3::new(z.z, 0.0, -z.x),
+                        Vec3::new(0.0, 1.0, 0.0),
+                        z,
+                    ])
+                };
+
+                let parameters = |p: &[f32; 3]| {
+                    let m11 = p[0].max(1e-7);
+                    if isotropic {
+                        [m11, m11, 0.0]
+                    } else {
+                        [m11, p[1].max(1e-7), p[2]]
+                    }
+                };
+
+                let fitted = nelder_mead(&guess, 0.05, 1e-5, 100, |p| {
+                    let ltc = Ltc::new(&basis, parameters(p), magnitude);
+                    ltc.error(&view, alpha, sample_count)
+                });
+
+                guess = parameters(&fitted);
+                if isotropic {
+                    first_guess = guess;
+                }
+
+                let ltc = Ltc::new(&basis, guess, magnitude);
+                // Scale doesn't change the directions, normalizing leaves 4 entries to store.
+                let inverse = ltc.inverse / ltc.inverse[(1, 1)];
+
+                let index = roughness_index + view_index * size;
+                matrices[index] = [
+                    inverse[(0, 0)],
+                    inverse[(2, 0)],
+                    inverse[(0, 2)],
+                    inverse[(2, 2)],
+                ];
+                amplitudes[index] = [magnitude, fresnel];
+            }
+        }
+
+        Self {
+            size,
+            matrices,
+            amplitudes,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn matrices(&self) -> &[[f32; 4]] {
+        &self.matrices
+    }
+
+    pub fn amplitudes(&self) -> &[[f32; 2]] {
+        &self.amplitudes
+    }
+}
+
+// The LTC table as textures, for area lights.
+pub struct LtcLuts {
+    matrix: Texture2D,
+    amplitude: Texture2D,
+    sampler_linear: Sampler,
+}
+
+impl LtcLuts {
+    pub fn new() -> Self {
+        Self::from_table(&LtcTable::embedded())
+    }
+
+    pub fn from_table(table: &LtcTable) -> Self {
+        let size = table.size() as u32;
+
+        Self {
+            matrix: Self::create_texture(
+                size,
+                SizedTextureFormat::Rgba32f,
+                TextureFormat::Rgba,
+                table.matrices().as_ptr() as *const _,
+            ),
+            amplitude: Self::create_texture(
+                size,
+                SizedTextureFormat::Rg16f,
+                TextureFormat::Rg,
+                table.amplitudes().as_ptr() as *const _,
+            ),
+            sampler_linear: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+        }
+    }
+
+    // Binds the matrices to the texture unit and the amplitudes to the one after it.
+    pub fn bind(&self, program_pipeline: &ProgramPipeline, binding: u32) {
+        program_pipeline
+            .set_texture_2d(binding, &self.matrix, &self.sampler_linear)
+            .set_texture_2d(binding + 1, &self.amplitude, &self.sampler_linear);
+    }
+
+    fn create_texture(
+        size: u32,
+        internal_format: SizedTextureFormat,
+        format: TextureFormat,
+        texels: *const gl::types::GLvoid,
+    ) -> Texture2D {
+        let texture = Texture2D::new_empty(size, size, internal_format, 1);
+
+        unsafe {
+            gl::TextureSubImage2D(
+                texture.get_id(),
+                0,
+                0,
+                0,
+                size as i32,
+                size as i32,
+                format as u32,
+                gl::FLOAT,
+                texels,
+            );
+        }
+
+        texture
+    }
+}
+
+impl Default for LtcLuts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A clamped cosine distribution transformed by m.
+struct Ltc {
+    m: glm::Mat3,
+    inverse: glm::Mat3,
+    determinant: f32,
+    magnitude: f32,
+}
+
+impl Ltc {
+    fn new(basis: &glm::Mat3, [m11, m22, m13]: [f32; 3], magnitude: f32) -> Self {
+        let m = basis * glm::Mat3::new(m11, 0.0, m13, 0.0, m22, 0.0, 0.0, 0.0, 1.0);
+
+        Self {
+            m,
+            inverse: m.try_inverse().unwrap_or_else(glm::Mat3::identity),
+            determinant: m.determinant().abs(),
+            magnitude,
+        }
+    }
+
+    fn eval(&self, l: &Vec3) -> f32 {
+        let original = (self.inverse * l).normalize();
+        let length = (self.m * original).norm();
+        let jacobian = self.determinant / (length * length * length);
+
+        self.magnitude * original.z.max(0.0) / PI / jacobian
+    }
+
+    fn sample(&self, u1: f32, u2: f32) -> Vec3 {
+        let cos_theta = u1.sqrt();
+        let sin_theta = (1.0 - u1).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        (self.m * Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)).normalize()
+    }
+
+    // Importance samples both distributions and weighs the cubed difference, as Heitz's fitting
+    // code does.
+    fn error(&self, view: &Vec3, alpha: f32, sample_count: usize) -> f32 {
+        let mut error = 0.0f64;
+
+        for (u1, u2) in stratified_samples(sample_count) {
+            for &l in &[self.sample(u1, u2), ggx_sample(view, alpha, u1, u2)] {
+                let (brdf, brdf_pdf) = ggx_eval(view, &l, alpha);
+                let ltc = self.eval(&l);
+                let ltc_pdf = ltc / self.magnitude;
+
+                if ltc_pdf + brdf_pdf > 0.0 {
+                    let difference = (brdf - ltc).abs();
+                    error += (difference.powi(3) / (ltc_pdf + brdf_pdf)) as f64;
+                }
+            }
+        }
+
+        (error / (sample_count * sample_count) as f64) as f32
+    }
+}
+
+fn stratified_samples(sample_count: usize) -> impl Iterator<Item = (f32, f32)> {
+    (0..sample_count * sample_count).map(move |i| {
+        (
+            ((i % sample_count) as f32 + 0.5) / sample_count as f32,
+            ((i / sample_count) as f32 + 0.5) / sample_count as f32,
+        )
+    })
+}
+
+// Smith masking of the GGX distribution.
+fn ggx_lambda(alpha: f32, cos_theta: f32) -> f32 {
+    if cos_theta >= 1.0 {
+        return 0.0;
+    }
+
+    let a = 1.0 / (alpha * cos_theta.acos().tan());
+    0.5 * (-1.0 + (1.0 + 1.0 / (a * a)).sqrt())
+}
+
+// The BRDF times the cosine of the light and the pdf of ggx_sample, in tangent space.
+fn ggx_eval(view: &Vec3, l: &Vec3, alpha: f32) -> (f32, f32) {
+    if view.z <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let lambda_view = ggx_lambda(alpha, view.z);
+    let g2 = if l.z <= 0.0 {
+        0.0
+    } else {
+        1.0 / (1.0 + lambda_view + ggx_lambda(alpha, l.z))
+    };
+
+    let h = (view + l).normalize();
+    let slope_x = h.x / h.z;
+    let slope_y = h.y / h.z;
+    let d = 1.0 / (1.0 + (slope_x * slope_x + slope_y * slope_y) / (alpha * alpha));
+    let d = d * d / (PI * alpha * alpha * h.z.powi(4));
+
+    let pdf = (d * h.z / 4.0 / view.dot(&h)).abs();
+    (d * g2 / 4.0 / view.z, pdf)
+}
+
+fn ggx_sample(view: &Vec3, alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let phi = 2.0 * PI * u1;
+    let r = alpha * (u2 / (1.0 - u2)).sqrt();
+    let n = Vec3::new(r * phi.cos(), r * phi.sin(), 1.0).normalize();
+
+    n * (2.0 * n.dot(view)) - view
+}
+
+// Directional albedo, its Fresnel part and the average direction of the lobe.
+fn ggx_average_terms(view: &Vec3, alpha: f32, sample_count: usize) -> (f32, f32, Vec3) {
+    let mut magnitude = 0.0;
+    let mut fresnel = 0.0;
+    let mut direction = Vec3::new(0.0, 0.0, 0.0);
+
+    for (u1, u2) in stratified_samples(sample_count) {
+        let l = ggx_sample(view, alpha, u1, u2);
+        let (brdf, pdf) = ggx_eval(view, &l, alpha);
+
+        if pdf > 0.0 {
+            let weight = brdf / pdf;
+            let h = (view + l).normalize();
+
+            magnitude += weight;
+            fresnel += weight * (1.0 - view.dot(&h).max(0.0)).powi(5);
+            direction += l * weight;
+        }
+    }
+
+    let count = (sample_count * sample_count) as f32;
+    // The lobe is symmetric about the plane of incidence.
+    direction.y = 0.0;
+
+    (magnitude / count, fresnel / count, direction.normalize())
+}
+
+// Minimizes a function of 3 parameters with the Nelder-Mead simplex method.
+fn nelder_mead<F: Fn(&[f32; 3]) -> f32>(
+    start: &[f32; 3],
+    delta: f32,
+    tolerance: f32,
+    max_iterations: usize,
+    function: F,
+) -> [f32; 3] {
+    let mut simplex = [*start; 4];
+    for (i, point) in simplex.iter_mut().skip(1).enumerate() {
+        point[i] += delta;
+    }
+
+    let mut values = [0.0; 4];
+    for (value, point) in values.iter_mut().zip(&simplex) {
+        *value = function(point);
+    }
+
+    for _ in 0..max_iterations {
+        let mut order = [0, 1, 2, 3];
+        order.sort_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap());
+        let (lowest, second_highest, highest) = (order[0], order[2], order[3]);
+
+        let range = (values[highest] - values[lowest]).abs();
+        if range <= tolerance * (values[highest].abs() + values[lowest].abs()).max(1e-12) {
+            break;
+        }
+
+        let mut centroid = [0.0; 3];
+        for &i in &order[..3] {
+            for (c, p) in centroid.iter_mut().zip(&simplex[i]) {
+                *c += p / 3.0;
+            }
+        }
+
+        // Points on the line from the centroid through the highest point.
+        let along = |t: f32| {
+            let mut point = [0.0; 3];
+            for d in 0..3 {
+                point[d] = centroid[d] + t * (simplex[highest][d] - centroid[d]);
+            }
+            point
+        };
+
+        let reflected = along(-1.0);
+        let reflected_value = function(&reflected);
+
+        if reflected_value < values[lowest] {
+            let expanded = along(-2.0);
+            let expanded_value = function(&expanded);
+
+            if expanded_value < reflected_value {
+                simplex[highest] = expanded;
+                values[highest] = expanded_value;
+            } else {
+                simplex[highest] = reflected;
+                values[highest] = reflected_value;
+            }
+        } else if reflected_value < values[second_highest] {
+            simplex[highest] = reflected;
+            values[highest] = reflected_value;
+        } else {
+            let contracted = if reflected_value < values[highest] {
+                along(-0.5)
+            } else {
+                along(0.5)
+            };
+            let contracted_value = function(&contracted);
+
+            if contracted_value < values[highest].min(reflected_value) {
+                simplex[highest] = contracted;
+                values[highest] = contracted_value;
+            } else {
+                let best = simplex[lowest];
+                for i in (0..4).filter(|i| *i != lowest) {
+                    for d in 0..3 {
+                        simplex[i][d] = best[d] + 0.5 * (simplex[i][d] - best[d]);
+                    }
+                    values[i] = function(&simplex[i]);
+                }
+            }
+        }
+    }
+
+    let mut best = 0;
+    for i in 1..4 {
+        if values[i] < values[best] {
+            best = i;
+        }
+    }
+
+    simplex[best]
+}
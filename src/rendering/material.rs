@@ -1,32 +1,270 @@
-use crate::core::asset::Asset;
+use crate::core::asset::{
+    embedded::EmbeddedAssets,
+    hot_reload::{AssetChanged, HotReload, ReloadStage},
+    Asset, AssetManager, Handle,
+};
 use crate::core::math::Vec2;
-use crate::rendering::buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags};
-use crate::rendering::texture::Texture2DLoadConfig;
+use crate::core::math::Vec3;
+use crate::rendering::buffer::BufferTarget;
+use crate::rendering::mesh::{Mesh, PrimitiveMode};
+use crate::rendering::sort_key::RenderLayer;
+use crate::rendering::state::{FixedFunctionState, StateManager};
+use crate::rendering::streaming_buffer::{StreamingAllocation, StreamingBuffer};
+use crate::rendering::vertex_layout::VertexAttribute;
 use crate::sampler::Anisotropy;
 use crate::{
     core::math::Vec4,
-    imgui::{im_str, ColorFormat, Gui, Ui},
+    imgui::{drag_drop, im_str, ColorFormat, Gui, Ui},
     rendering::{
+        async_pipeline::{fallback_pipeline, AsyncProgramPipeline},
+        error::RendererError,
         program_pipeline::ProgramPipeline,
+        render_texture::RenderTexture,
         sampler::{MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
-        shader::{Shader, ShaderStage},
+        shader::ShaderStage,
         texture::Texture2D,
     },
 };
-use std::{ops::RangeInclusive, path::Path, rc::Rc};
+use gl_bindings as gl;
+use std::{
+    cell::Cell,
+    fmt::Debug,
+    fs, mem,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-const MATERIAL_UBO_BINDING_INDEX: u32 = 4;
+pub(crate) const MATERIAL_UBO_BINDING_INDEX: u32 = 4;
 const ALBEDO_MAP_BINDING_INDEX: u32 = 0;
 const NORMAL_MAP_BINDING_INDEX: u32 = 1;
 // [Metalness (R), Roughness (G), AO (B)]
 const M_R_AO_MAP_BINDING_INDEX: u32 = 2;
 const BRDF_LUT_MAP_BINDING_INDEX: u32 = 3;
 const DISPLACEMENT_MAP_BINDING_INDEX: u32 = 6;
+pub(crate) const TESSELLATION_UBO_BINDING_INDEX: u32 = 6;
+pub(crate) const FOLIAGE_UBO_BINDING_INDEX: u32 = 10;
+const LIGHTMAP_BINDING_INDEX: u32 = 14;
+
+// What an externally baked lightmap, sampled with the second UV channel, contains.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightmapMode {
+    // Indirect diffuse light in RGB, replacing the irradiance of the environment, and ambient
+    // occlusion in alpha.
+    Irradiance = 1,
+    // Ambient occlusion in red, multiplied with the ambient occlusion of the material.
+    Occlusion = 2,
+}
+
+// Vertex shaders of the PbsMetallicRoughnessMaterial, depending on where the mesh gets its
+// transform from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PbsVertexVariant {
+    // The model matrix of the per draw block.
+    Static,
+    // The per instance model matrices, see Mesh::set_instance_buffer. Drawn with
+    // mesh.draw_instanced.
+    Instanced,
+    // Blended by the joints of the vertices, see Mesh::new_skinned. Reads the JointPalette,
+    // which has to be bound while drawing.
+    Skinned,
+}
+
+impl PbsVertexVariant {
+    // Instancing isn't part of the vertex format, so meshes without joints map to Static.
+    pub fn for_mesh(mesh: &Mesh) -> Self {
+        if mesh.layout().has_attribute(VertexAttribute::Joints) {
+            PbsVertexVariant::Skinned
+        } else {
+            PbsVertexVariant::Static
+        }
+    }
+
+    fn shader_file_name(self) -> &'static str {
+        match self {
+            PbsVertexVariant::Static => "pbs.vert",
+            PbsVertexVariant::Instanced => "pbs_instanced.vert",
+            PbsVertexVariant::Skinned => "pbs_skinned.vert",
+        }
+    }
+}
+
+// Serializable description of a PbsMetallicRoughnessMaterial. Loaded from simple
+// `key = value` files, texture paths are relative to the file:
+//
+//     albedo = bricks_albedo.png
+//     normals = bricks_normals.png
+//     base_color = 1.0 0.9 0.9 1.0
+//     roughness_scale = 0.8
+//     lightmap = bricks_lightmap.png
+//     lightmap_mode = irradiance
+//
+// Missing maps fall back to neutral 1x1 textures.
+#[derive(Debug, Clone)]
+pub struct MaterialTemplate {
+    pub albedo: Option<PathBuf>,
+    pub metallic_roughness_ao: Option<PathBuf>,
+    pub normals: Option<PathBuf>,
+    pub displacement: Option<PathBuf>,
+    pub lightmap: Option<PathBuf>,
+    pub lightmap_mode: LightmapMode,
+    pub lightmap_intensity: f32,
+    pub base_color: Vec4,
+    pub metallic_scale: f32,
+    pub roughness_scale: f32,
+}
+
+impl Default for MaterialTemplate {
+    fn default() -> Self {
+        Self {
+            albedo: None,
+            metallic_roughness_ao: None,
+            normals: None,
+            displacement: None,
+            lightmap: None,
+            lightmap_mode: LightmapMode::Irradiance,
+            lightmap_intensity: 1.0,
+            base_color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            metallic_scale: 1.0,
+            roughness_scale: 1.0,
+        }
+    }
+}
+
+impl MaterialTemplate {
+    pub fn parse(source: &str, directory: &Path) -> Result<Self, String> {
+        let mut template = Self::default();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| format!("Material line {}: {}", line_number + 1, message);
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts
+                .next()
+                .map(str::trim)
+                .ok_or_else(|| error("Expected `key = value`."))?;
+
+            let scalar = || {
+                value
+                    .parse::<f32>()
+                    .map_err(|_| error("Expected a number."))
+            };
+
+            match key {
+                "albedo" => template.albedo = Some(directory.join(value)),
+                "metallic_roughness_ao" => {
+                    template.metallic_roughness_ao = Some(directory.join(value))
+                }
+                "normals" => template.normals = Some(directory.join(value)),
+                "displacement" => template.displacement = Some(directory.join(value)),
+                "lightmap" => template.lightmap = Some(directory.join(value)),
+                "lightmap_mode" => {
+                    template.lightmap_mode = match value {
+                        "irradiance" => LightmapMode::Irradiance,
+                        "occlusion" => LightmapMode::Occlusion,
+                        _ => return Err(error("Expected `irradiance` or `occlusion`.")),
+                    }
+                }
+                "lightmap_intensity" => template.lightmap_intensity = scalar()?,
+                "base_color" => {
+                    let c = value
+                        .split_whitespace()
+                        .map(|v| v.parse::<f32>().ok())
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|c| c.len() == 4)
+                        .ok_or_else(|| error("Expected 4 numbers."))?;
+
+                    template.base_color = Vec4::new(c[0], c[1], c[2], c[3])
+                }
+                "metallic_scale" => template.metallic_scale = scalar()?,
+                "roughness_scale" => template.roughness_scale = scalar()?,
+                _ => return Err(error(&format!("Unknown key '{}'.", key))),
+            }
+        }
+
+        Ok(template)
+    }
+}
+
+impl Asset for MaterialTemplate {
+    type Output = Self;
+    type Error = String;
+    type LoadConfig = ();
+
+    fn load<P: AsRef<Path> + Debug>(
+        path: P,
+        _: Option<Self::LoadConfig>,
+    ) -> Result<Self::Output, Self::Error> {
+        let source = fs::read_to_string(path.as_ref()).map_err(|e| e.to_string())?;
+
+        Self::parse(
+            &source,
+            path.as_ref().parent().unwrap_or_else(|| Path::new("")),
+        )
+    }
+}
 
 pub trait Material: Gui {
     fn bind(&self);
     fn unbind(&self);
     fn program_pipeline(&self) -> &ProgramPipeline;
+
+    // Called once per frame before drawing, e.g. to advance the compilation of the shaders.
+    fn update(&mut self) {}
+
+    fn primitive_mode(&self) -> PrimitiveMode {
+        PrimitiveMode::Triangles
+    }
+
+    fn render_layer(&self) -> RenderLayer {
+        RenderLayer::Opaque
+    }
+}
+
+// The uniform block of a material, written to the region of the current frame of a streaming
+// buffer. The region is fenced when the next frame starts, so binding never waits on the frames
+// still in flight. Every bind within a frame overwrites the same region.
+pub(crate) struct MaterialUniforms {
+    buffer: StreamingBuffer,
+    allocation: Option<StreamingAllocation>,
+    size: isize,
+}
+
+impl MaterialUniforms {
+    pub(crate) fn new<T: Sized>(name: &str) -> Self {
+        let size = mem::size_of::<T>() as isize;
+        let mut buffer = StreamingBuffer::new(name, size, BufferTarget::Uniform);
+        let allocation = buffer.allocate(size);
+
+        Self {
+            buffer,
+            allocation,
+            size,
+        }
+    }
+
+    // Once per frame, from Material::update.
+    pub(crate) fn next_frame(&mut self) {
+        self.buffer.end_frame();
+        self.buffer.begin_frame();
+        self.allocation = self.buffer.allocate(self.size);
+    }
+
+    pub(crate) fn bind<T: Sized>(&self, binding_index: u32, block: &T) {
+        if let Some(allocation) = &self.allocation {
+            self.buffer
+                .get_buffer()
+                .fill_mapped(allocation.offset, block);
+            self.buffer.bind(binding_index, allocation);
+        }
+    }
 }
 
 #[repr(C)]
@@ -43,61 +281,54 @@ struct MaterialPropertyBlock {
     max_pom_layers: f32,
     displacement_scale: f32,
     parallax_mapping_method: i32,
-    _pad: Vec2,
+    lightmap_intensity: f32,
+    // 0 without a lightmap, LightmapMode otherwise.
+    lightmap_mode: i32,
 }
 
 pub struct PbsMetallicRoughnessMaterial {
-    albedo: Rc<Texture2D>,
-    metallic_roughness_ao: Rc<Texture2D>,
-    normals: Rc<Texture2D>,
-    displacement: Option<Rc<Texture2D>>,
-    ibl_brdf_lut: Texture2D,
+    albedo: Handle<Texture2D>,
+    metallic_roughness_ao: Handle<Texture2D>,
+    normals: Handle<Texture2D>,
+    displacement: Option<Handle<Texture2D>>,
+    lightmap: Option<Handle<Texture2D>>,
+    // Replaces the albedo map, e.g. for screens showing what a camera sees.
+    albedo_render_texture: Option<RenderTexture>,
+    ibl_brdf_lut: Handle<Texture2D>,
     sampler: Sampler,
+    // Lightmap charts are padded but not tiled.
+    lightmap_sampler: Sampler,
     property_block: MaterialPropertyBlock,
-    program_pipeline: ProgramPipeline,
-    material_ubo: Buffer,
+    // Draws with the fallback pipeline until the shaders are compiled and linked.
+    program_pipeline: AsyncProgramPipeline,
+    fallback_pipeline: Rc<ProgramPipeline>,
+    shader_paths: [PathBuf; 2],
+    uniforms: MaterialUniforms,
 }
 
 impl PbsMetallicRoughnessMaterial {
     pub fn new<P: AsRef<Path>>(
         asset_path: P,
-        albedo: Rc<Texture2D>,
-        metallic_roughness_ao: Rc<Texture2D>,
-        normals: Rc<Texture2D>,
-        displacement: Option<Rc<Texture2D>>,
-    ) -> Self {
-        let (vertex_shader, fragment_shader) = match displacement {
-            Some(_) => (
-                Shader::new(
-                    ShaderStage::Vertex,
-                    asset_path.as_ref().join("sdr/pbs_pom.vert"),
-                )
-                .unwrap(),
-                Shader::new(
-                    ShaderStage::Fragment,
-                    asset_path.as_ref().join("sdr/pbs_pom.frag"),
-                )
-                .unwrap(),
-            ),
-            None => (
-                Shader::new(
-                    ShaderStage::Vertex,
-                    asset_path.as_ref().join("sdr/pbs.vert"),
-                )
-                .unwrap(),
-                Shader::new(
-                    ShaderStage::Fragment,
-                    asset_path.as_ref().join("sdr/pbs.frag"),
-                )
-                .unwrap(),
-            ),
+        albedo: Handle<Texture2D>,
+        metallic_roughness_ao: Handle<Texture2D>,
+        normals: Handle<Texture2D>,
+        displacement: Option<Handle<Texture2D>>,
+    ) -> Result<Self, RendererError> {
+        let shader_paths = match displacement {
+            Some(_) => [
+                asset_path.as_ref().join("sdr/pbs_pom.vert"),
+                asset_path.as_ref().join("sdr/pbs_pom.frag"),
+            ],
+            None => [
+                asset_path.as_ref().join("sdr/pbs.vert"),
+                asset_path.as_ref().join("sdr/pbs.frag"),
+            ],
         };
 
-        let program_pipeline = ProgramPipeline::new()
-            .add_shader(&vertex_shader)
-            .add_shader(&fragment_shader)
-            .build()
-            .unwrap();
+        let program_pipeline = AsyncProgramPipeline::new(&[
+            (ShaderStage::Vertex, shader_paths[0].clone()),
+            (ShaderStage::Fragment, shader_paths[1].clone()),
+        ]);
 
         let sampler = Sampler::new(
             MinificationFilter::LinearMipmapLinear,
@@ -109,31 +340,26 @@ impl PbsMetallicRoughnessMaterial {
             Anisotropy::X4,
         );
 
-        let ibl_brdf_lut = Texture2D::load(
-            asset_path.as_ref().join("textures/pbs/ibl_brdf_lut.png"),
-            Some(Texture2DLoadConfig {
-                is_srgb: false,
-                generate_mipmap: false,
-            }),
-        )
-        .expect("Failed to load BRDF LUT texture");
-
-        let mut material_ubo = Buffer::new(
-            "MaterialPropertyBlock UBO",
-            std::mem::size_of::<MaterialPropertyBlock>() as isize,
-            BufferTarget::Uniform,
-            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
-        );
-        material_ubo.bind(MATERIAL_UBO_BINDING_INDEX);
-        material_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
+        let ibl_brdf_lut = EmbeddedAssets::brdf_lut()?;
 
-        Self {
+        Ok(Self {
             albedo,
             metallic_roughness_ao,
             normals,
             displacement,
+            lightmap: None,
+            albedo_render_texture: None,
             ibl_brdf_lut,
             sampler,
+            lightmap_sampler: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
             property_block: MaterialPropertyBlock {
                 base_color: Vec4::new(1.0, 1.0, 1.0, 1.0),
                 metallic_scale: 1.0,
@@ -146,26 +372,177 @@ impl PbsMetallicRoughnessMaterial {
                 max_pom_layers: 32.0,
                 displacement_scale: 0.018,
                 parallax_mapping_method: 4,
-                _pad: Vec2::new(0.0, 0.0),
+                lightmap_intensity: 1.0,
+                lightmap_mode: 0,
             },
             program_pipeline,
-            material_ubo,
+            fallback_pipeline: fallback_pipeline()?,
+            shader_paths,
+            uniforms: MaterialUniforms::new::<MaterialPropertyBlock>("MaterialPropertyBlock UBO"),
+        })
+    }
+
+    fn build_program_pipeline(
+        shader_paths: &[PathBuf; 2],
+    ) -> Result<ProgramPipeline, RendererError> {
+        let pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                &shader_paths[0],
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                &shader_paths[1],
+            )?)
+            .build()?;
+
+        Ok(pipeline)
+    }
+
+    // Shaders aren't loaded through the AssetManager, so they have to be watched explicitly.
+    pub fn watch(&self, hot_reload: &mut HotReload) {
+        for path in &self.shader_paths {
+            hot_reload.watch(path, ReloadStage::Shader);
+        }
+    }
+
+    // Swaps in textures the AssetManager reloaded and rebuilds the program pipeline when one of
+    // the shaders changed. Keeps the current state if the new shaders fail to compile.
+    pub fn reload(&mut self, change: &AssetChanged, asset_manager: &AssetManager) {
+        match change.stage {
+            ReloadStage::Shader
+                if self.shader_paths.contains(&change.path) && change.path.is_file() =>
+            {
+                match Self::build_program_pipeline(&self.shader_paths) {
+                    Ok(program_pipeline) => {
+                        self.program_pipeline =
+                            AsyncProgramPipeline::from_pipeline(program_pipeline)
+                    }
+                    Err(e) => log::warn!("Failed to reload material shaders: {}", e),
+                }
+            }
+            ReloadStage::Texture => {
+                let mut textures = vec![
+                    &mut self.albedo,
+                    &mut self.metallic_roughness_ao,
+                    &mut self.normals,
+                ];
+                textures.extend(self.displacement.as_mut());
+                textures.extend(self.lightmap.as_mut());
+
+                for texture in textures {
+                    if texture.get_path() == Some(change.path.as_path()) {
+                        if let Some(reloaded) = asset_manager.get_texture_2d(&change.path) {
+                            *texture = reloaded;
+                        }
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
-    pub fn set_program_pipeline(&mut self, program_pipeline: ProgramPipeline) {
+    pub fn set_program_pipeline(&mut self, program_pipeline: AsyncProgramPipeline) {
         self.program_pipeline = program_pipeline
     }
+
+    // Recompiles the material with the vertex shader of the variant, next to the current one.
+    // Parallax occlusion mapped materials keep their own vertex shader. Call before watch, so
+    // the new shader is the one that gets hot reloaded.
+    pub fn set_vertex_variant(&mut self, variant: PbsVertexVariant) {
+        if self.displacement.is_some() {
+            log::warn!(
+                "Parallax occlusion mapped materials don't support the {:?} vertex variant.",
+                variant
+            );
+            return;
+        }
+
+        if self.shader_paths[0].ends_with(variant.shader_file_name()) {
+            return;
+        }
+
+        self.shader_paths[0].set_file_name(variant.shader_file_name());
+        self.program_pipeline = AsyncProgramPipeline::new(&[
+            (ShaderStage::Vertex, self.shader_paths[0].clone()),
+            (ShaderStage::Fragment, self.shader_paths[1].clone()),
+        ]);
+    }
+
+    pub fn set_albedo(&mut self, albedo: Handle<Texture2D>) {
+        self.albedo = albedo
+    }
+
+    pub fn set_metallic_roughness_ao(&mut self, metallic_roughness_ao: Handle<Texture2D>) {
+        self.metallic_roughness_ao = metallic_roughness_ao
+    }
+
+    pub fn set_normals(&mut self, normals: Handle<Texture2D>) {
+        self.normals = normals
+    }
+
+    // Of the albedo, metallic roughness AO, normal and displacement maps.
+    pub fn set_anisotropy(&self, anisotropy: Anisotropy) {
+        self.sampler.set_anisotropy(anisotropy)
+    }
+
+    pub fn set_base_color(&mut self, base_color: Vec4) {
+        self.property_block.base_color = base_color
+    }
+
+    pub fn set_metallic_scale(&mut self, metallic_scale: f32) {
+        self.property_block.metallic_scale = metallic_scale
+    }
+
+    pub fn set_roughness_scale(&mut self, roughness_scale: f32) {
+        self.property_block.roughness_scale = roughness_scale
+    }
+
+    // Baked lighting of static geometry, sampled with the second UV channel of the mesh.
+    pub fn set_lightmap(&mut self, lightmap: Option<Handle<Texture2D>>, mode: LightmapMode) {
+        self.property_block.lightmap_mode = match lightmap {
+            Some(_) => mode as i32,
+            None => 0,
+        };
+        self.lightmap = lightmap
+    }
+
+    // Scales the irradiance of the lightmap, e.g. to the units of the scene.
+    pub fn set_lightmap_intensity(&mut self, lightmap_intensity: f32) {
+        self.property_block.lightmap_intensity = lightmap_intensity
+    }
+
+    // Samples the render texture instead of the albedo map. None restores the albedo map.
+    pub fn set_albedo_render_texture(&mut self, render_texture: Option<RenderTexture>) {
+        self.albedo_render_texture = render_texture
+    }
 }
 
 impl Material for PbsMetallicRoughnessMaterial {
     fn bind(&self) {
-        self.program_pipeline.bind();
+        let program_pipeline = self.program_pipeline();
 
-        self.material_ubo.fill_mapped(0, &self.property_block);
+        program_pipeline.bind();
 
-        self.program_pipeline
-            .set_texture_2d(ALBEDO_MAP_BINDING_INDEX, &self.albedo, &self.sampler)
+        self.uniforms
+            .bind(MATERIAL_UBO_BINDING_INDEX, &self.property_block);
+
+        match &self.albedo_render_texture {
+            Some(render_texture) => render_texture.bind_texture(
+                program_pipeline,
+                ALBEDO_MAP_BINDING_INDEX,
+                &self.sampler,
+            ),
+            None => {
+                program_pipeline.set_texture_2d(
+                    ALBEDO_MAP_BINDING_INDEX,
+                    &self.albedo,
+                    &self.sampler,
+                );
+            }
+        }
+
+        program_pipeline
             .set_texture_2d(
                 M_R_AO_MAP_BINDING_INDEX,
                 &self.metallic_roughness_ao,
@@ -179,20 +556,33 @@ impl Material for PbsMetallicRoughnessMaterial {
             );
 
         if let Some(displacement) = &self.displacement {
-            self.program_pipeline.set_texture_2d(
+            program_pipeline.set_texture_2d(
                 DISPLACEMENT_MAP_BINDING_INDEX,
                 &displacement,
                 &self.sampler,
             );
         }
+
+        if let Some(lightmap) = &self.lightmap {
+            program_pipeline.set_texture_2d(
+                LIGHTMAP_BINDING_INDEX,
+                &lightmap,
+                &self.lightmap_sampler,
+            );
+        }
     }
 
     fn unbind(&self) {
-        self.program_pipeline.unbind();
+        self.program_pipeline().unbind();
     }
 
     fn program_pipeline(&self) -> &ProgramPipeline {
-        &self.program_pipeline
+        self.program_pipeline.pipeline_or(&self.fallback_pipeline)
+    }
+
+    fn update(&mut self) {
+        self.program_pipeline.poll();
+        self.uniforms.next_frame();
     }
 }
 
@@ -210,6 +600,9 @@ impl Gui for PbsMetallicRoughnessMaterial {
                     ui.text(im_str!("Albedo Map"));
                     imgui::Image::new((self.albedo.get_id() as usize).into(), [128.0, 128.0])
                         .build(&ui);
+                    if let Some(albedo) = drag_drop::texture_target(ui) {
+                        self.albedo = albedo
+                    }
                     ui.spacing();
 
                     let mut albedo_color: [f32; 4] = self.property_block.base_color.into();
@@ -232,6 +625,9 @@ impl Gui for PbsMetallicRoughnessMaterial {
                         [128.0, 128.0],
                     )
                     .build(&ui);
+                    if let Some(metallic_roughness_ao) = drag_drop::texture_target(ui) {
+                        self.metallic_roughness_ao = metallic_roughness_ao
+                    }
                     ui.spacing();
                     imgui::Slider::new(im_str!("Metallic Scale"))
                         .range(RangeInclusive::new(0.0, 1.0))
@@ -267,10 +663,30 @@ impl Gui for PbsMetallicRoughnessMaterial {
                         ui.text(im_str!("Normal Map"));
                         imgui::Image::new((self.normals.get_id() as usize).into(), [128.0, 128.0])
                             .build(&ui);
+                        if let Some(normals) = drag_drop::texture_target(ui) {
+                            self.normals = normals
+                        }
                         ui.spacing();
                     });
                 });
 
+                if let Some(lightmap) = self.lightmap.as_ref() {
+                    ui.spacing();
+                    ui.spacing();
+
+                    ui.text(im_str!("Lightmap"));
+                    imgui::Image::new((lightmap.get_id() as usize).into(), [128.0, 128.0])
+                        .build(&ui);
+                    ui.spacing();
+
+                    if self.property_block.lightmap_mode == LightmapMode::Irradiance as i32 {
+                        imgui::Slider::new(im_str!("Lightmap Intensity"))
+                            .range(RangeInclusive::new(0.0, 10.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut self.property_block.lightmap_intensity);
+                    }
+                }
+
                 if let Some(displacement) = self.displacement.as_ref() {
                     ui.spacing();
                     ui.spacing();
@@ -343,3 +759,561 @@ impl Gui for PbsMetallicRoughnessMaterial {
         }
     }
 }
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct TessellationPropertyBlock {
+    min_tessellation_level: f32,
+    max_tessellation_level: f32,
+    min_distance: f32,
+    max_distance: f32,
+    displacement_scale: f32,
+    _pad: Vec3,
+}
+
+pub struct TessellatedPbsMaterial {
+    material: PbsMetallicRoughnessMaterial,
+    displacement: Handle<Texture2D>,
+    property_block: TessellationPropertyBlock,
+    uniforms: MaterialUniforms,
+}
+
+impl TessellatedPbsMaterial {
+    pub fn new<P: AsRef<Path>>(
+        asset_path: P,
+        albedo: Handle<Texture2D>,
+        metallic_roughness_ao: Handle<Texture2D>,
+        normals: Handle<Texture2D>,
+        displacement: Handle<Texture2D>,
+    ) -> Result<Self, RendererError> {
+        let mut material = PbsMetallicRoughnessMaterial::new(
+            asset_path.as_ref(),
+            albedo,
+            metallic_roughness_ao,
+            normals,
+            None,
+        )?;
+
+        material.set_program_pipeline(AsyncProgramPipeline::new(&[
+            (
+                ShaderStage::Vertex,
+                asset_path.as_ref().join("sdr/pbs_tess.vert"),
+            ),
+            (
+                ShaderStage::TesselationControl,
+                asset_path.as_ref().join("sdr/pbs_tess.tesc"),
+            ),
+            (
+                ShaderStage::TesselationEvaluation,
+                asset_path.as_ref().join("sdr/pbs_tess.tese"),
+            ),
+            (
+                ShaderStage::Fragment,
+                asset_path.as_ref().join("sdr/pbs.frag"),
+            ),
+        ]));
+
+        Ok(Self {
+            material,
+            displacement,
+            property_block: TessellationPropertyBlock {
+                min_tessellation_level: 1.0,
+                max_tessellation_level: 32.0,
+                min_distance: 10.0,
+                max_distance: 150.0,
+                displacement_scale: 0.5,
+                _pad: Vec3::new(0.0, 0.0, 0.0),
+            },
+            uniforms: MaterialUniforms::new::<TessellationPropertyBlock>(
+                "TessellationPropertyBlock UBO",
+            ),
+        })
+    }
+}
+
+impl Material for TessellatedPbsMaterial {
+    fn bind(&self) {
+        self.material.bind();
+
+        self.uniforms
+            .bind(TESSELLATION_UBO_BINDING_INDEX, &self.property_block);
+
+        self.material.program_pipeline().set_texture_2d(
+            DISPLACEMENT_MAP_BINDING_INDEX,
+            &self.displacement,
+            &self.material.sampler,
+        );
+
+        // Triangle patches.
+        StateManager::set_patch_vertices(3);
+    }
+
+    fn unbind(&self) {
+        self.material.unbind()
+    }
+
+    fn program_pipeline(&self) -> &ProgramPipeline {
+        self.material.program_pipeline()
+    }
+
+    fn update(&mut self) {
+        self.material.update();
+        self.uniforms.next_frame();
+    }
+
+    fn primitive_mode(&self) -> PrimitiveMode {
+        PrimitiveMode::Patches
+    }
+}
+
+impl Gui for TessellatedPbsMaterial {
+    fn gui(&mut self, ui: &Ui) {
+        self.material.gui(ui);
+
+        if imgui::CollapsingHeader::new(im_str!("Tessellation"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            ui.spacing();
+            ui.group(|| {
+                ui.text(im_str!("Displacement Map"));
+                imgui::Image::new((self.displacement.get_id() as usize).into(), [128.0, 128.0])
+                    .build(&ui);
+                ui.spacing();
+
+                imgui::DragRange::new(im_str!("Min/Max Level"))
+                    .range(RangeInclusive::new(1.0, 64.0))
+                    .display_format(im_str!("%.0f"))
+                    .build(
+                        &ui,
+                        &mut self.property_block.min_tessellation_level,
+                        &mut self.property_block.max_tessellation_level,
+                    );
+
+                imgui::DragRange::new(im_str!("Min/Max Distance"))
+                    .range(RangeInclusive::new(0.1, 1000.0))
+                    .display_format(im_str!("%.1f"))
+                    .build(
+                        &ui,
+                        &mut self.property_block.min_distance,
+                        &mut self.property_block.max_distance,
+                    );
+
+                imgui::Drag::new(im_str!("Displacement Scale"))
+                    .range(RangeInclusive::new(0.0, 10.0))
+                    .speed(0.01)
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.displacement_scale);
+            });
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct FoliagePropertyBlock {
+    // xyz: direction, w: strength.
+    wind: Vec4,
+    // w: translucency.
+    transmission_color: Vec4,
+    wind_frequency: f32,
+    flutter_strength: f32,
+    time: f32,
+    alpha_cutoff: f32,
+    transmission_power: f32,
+    alpha_to_coverage: i32,
+    _pad: Vec2,
+}
+
+// PBS material for leaves and grass, drawn instanced with mesh.draw_instanced. The wind sways
+// every instance with its own phase, bending the plants more the higher above their origin, and
+// leaves flutter by the red channel of the vertex colors. Cutouts take the alpha of the albedo:
+// on multisampled targets it turns into sample coverage, elsewhere it is tested against the
+// cutoff. Both faces are drawn and lit, and light passes through to the side facing away from
+// it.
+pub struct FoliageMaterial {
+    material: PbsMetallicRoughnessMaterial,
+    property_block: FoliagePropertyBlock,
+    alpha_to_coverage: bool,
+    uniforms: MaterialUniforms,
+    // The fixed function state before bind, restored by unbind.
+    previous_state: Cell<Option<FixedFunctionState>>,
+}
+
+impl FoliageMaterial {
+    pub fn new<P: AsRef<Path>>(
+        asset_path: P,
+        albedo: Handle<Texture2D>,
+        metallic_roughness_ao: Handle<Texture2D>,
+        normals: Handle<Texture2D>,
+    ) -> Result<Self, RendererError> {
+        let mut material = PbsMetallicRoughnessMaterial::new(
+            asset_path.as_ref(),
+            albedo,
+            metallic_roughness_ao,
+            normals,
+            None,
+        )?;
+
+        material.set_program_pipeline(AsyncProgramPipeline::new(&[
+            (
+                ShaderStage::Vertex,
+                asset_path.as_ref().join("sdr/foliage.vert"),
+            ),
+            (
+                ShaderStage::Fragment,
+                asset_path.as_ref().join("sdr/foliage.frag"),
+            ),
+        ]));
+
+        Ok(Self {
+            material,
+            property_block: FoliagePropertyBlock {
+                wind: Vec4::new(1.0, 0.0, 0.0, 0.02),
+                transmission_color: Vec4::new(0.8, 1.0, 0.4, 0.5),
+                wind_frequency: 1.5,
+                flutter_strength: 0.5,
+                time: 0.0,
+                alpha_cutoff: 0.5,
+                transmission_power: 4.0,
+                alpha_to_coverage: 0,
+                _pad: Vec2::new(0.0, 0.0),
+            },
+            alpha_to_coverage: true,
+            uniforms: MaterialUniforms::new::<FoliagePropertyBlock>("FoliagePropertyBlock UBO"),
+            previous_state: Cell::new(None),
+        })
+    }
+
+    pub fn set_base_color(&mut self, base_color: Vec4) {
+        self.material.set_base_color(base_color)
+    }
+
+    // Strength is the displacement per squared unit of height above the origin of an instance.
+    pub fn set_wind(&mut self, direction: Vec3, strength: f32) {
+        let direction = direction
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(Vec3::zeros);
+        self.property_block.wind = Vec4::new(direction.x, direction.y, direction.z, strength)
+    }
+
+    // Sways per second, times 2 pi.
+    pub fn set_wind_frequency(&mut self, wind_frequency: f32) {
+        self.property_block.wind_frequency = wind_frequency
+    }
+
+    pub fn set_flutter_strength(&mut self, flutter_strength: f32) {
+        self.property_block.flutter_strength = flutter_strength
+    }
+
+    // Drives the wind, in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.property_block.time = time
+    }
+
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.property_block.alpha_cutoff = alpha_cutoff
+    }
+
+    // Off tests the alpha against the cutoff on multisampled targets too.
+    pub fn set_alpha_to_coverage(&mut self, alpha_to_coverage: bool) {
+        self.alpha_to_coverage = alpha_to_coverage
+    }
+
+    // How much light passes through leaves, tinted by the color.
+    pub fn set_transmission(&mut self, color: Vec3, translucency: f32) {
+        self.property_block.transmission_color = Vec4::new(color.x, color.y, color.z, translucency)
+    }
+
+    fn is_target_multisampled() -> bool {
+        let mut sample_buffers = 0;
+        unsafe { gl::GetIntegerv(gl::SAMPLE_BUFFERS, &mut sample_buffers) };
+
+        sample_buffers > 0
+    }
+}
+
+impl Material for FoliageMaterial {
+    fn bind(&self) {
+        self.material.bind();
+
+        let alpha_to_coverage = self.alpha_to_coverage && Self::is_target_multisampled();
+
+        let mut property_block = self.property_block;
+        property_block.alpha_to_coverage = alpha_to_coverage as i32;
+        self.uniforms
+            .bind(FOLIAGE_UBO_BINDING_INDEX, &property_block);
+
+        let previous_state = StateManager::current_state();
+        let mut state = previous_state.unwrap_or_default();
+        state.rasterizer.face_culling = None;
+        StateManager::apply(&state);
+        self.previous_state.set(previous_state);
+
+        StateManager::set_alpha_to_coverage(alpha_to_coverage);
+    }
+
+    fn unbind(&self) {
+        StateManager::set_alpha_to_coverage(false);
+
+        if let Some(previous_state) = self.previous_state.take() {
+            StateManager::apply(&previous_state);
+        }
+
+        self.material.unbind()
+    }
+
+    fn program_pipeline(&self) -> &ProgramPipeline {
+        self.material.program_pipeline()
+    }
+
+    fn update(&mut self) {
+        self.material.update();
+        self.uniforms.next_frame();
+    }
+}
+
+impl Gui for FoliageMaterial {
+    fn gui(&mut self, ui: &Ui) {
+        self.material.gui(ui);
+
+        if imgui::CollapsingHeader::new(im_str!("Foliage"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            ui.spacing();
+            ui.group(|| {
+                imgui::Drag::new(im_str!("Wind Strength"))
+                    .range(RangeInclusive::new(0.0, 1.0))
+                    .speed(0.001)
+                    .display_format(im_str!("%.3f"))
+                    .build(&ui, &mut self.property_block.wind.w);
+                imgui::Slider::new(im_str!("Wind Frequency"))
+                    .range(RangeInclusive::new(0.0, 10.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.wind_frequency);
+                imgui::Slider::new(im_str!("Flutter Strength"))
+                    .range(RangeInclusive::new(0.0, 2.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.flutter_strength);
+                ui.spacing();
+
+                imgui::Slider::new(im_str!("Alpha Cutoff"))
+                    .range(RangeInclusive::new(0.0, 1.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.alpha_cutoff);
+                ui.checkbox(im_str!("Alpha to Coverage"), &mut self.alpha_to_coverage);
+                ui.spacing();
+
+                let mut transmission_color: [f32; 3] =
+                    self.property_block.transmission_color.xyz().into();
+                if imgui::ColorEdit::new(im_str!("Transmission Color"), &mut transmission_color)
+                    .format(ColorFormat::Float)
+                    .build(&ui)
+                {
+                    let translucency = self.property_block.transmission_color.w;
+                    self.property_block.transmission_color = Vec4::new(
+                        transmission_color[0],
+                        transmission_color[1],
+                        transmission_color[2],
+                        translucency,
+                    );
+                }
+                imgui::Slider::new(im_str!("Translucency"))
+                    .range(RangeInclusive::new(0.0, 1.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.transmission_color.w);
+                imgui::Slider::new(im_str!("Transmission Power"))
+                    .range(RangeInclusive::new(1.0, 16.0))
+                    .display_format(im_str!("%.1f"))
+                    .build(&ui, &mut self.property_block.transmission_power);
+            });
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct RefractionPropertyBlock {
+    tint: Vec4,
+    distortion_scroll: Vec2,
+    index_of_refraction: f32,
+    thickness: f32,
+    roughness: f32,
+    distortion_strength: f32,
+    time: f32,
+    has_distortion_map: i32,
+}
+
+// Transparent surface that refracts the opaque scene behind it, optionally distorted by a
+// scrolling map for heat haze. Draw it after SceneColor::capture, which binds the scene color.
+pub struct RefractiveMaterial {
+    distortion: Option<Handle<Texture2D>>,
+    sampler: Sampler,
+    property_block: RefractionPropertyBlock,
+    program_pipeline: ProgramPipeline,
+    uniforms: MaterialUniforms,
+}
+
+impl RefractiveMaterial {
+    // distortion is sampled as a tangent space normal map, only its xy is used.
+    pub fn new(distortion: Option<Handle<Texture2D>>) -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/refraction.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/refraction.frag",
+            )?)
+            .build()?;
+
+        let sampler = Sampler::new(
+            MinificationFilter::LinearMipmapLinear,
+            MagnificationFilter::Linear,
+            WrappingMode::Repeat,
+            WrappingMode::Repeat,
+            WrappingMode::Repeat,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::X4,
+        );
+
+        Ok(Self {
+            property_block: RefractionPropertyBlock {
+                tint: Vec4::new(1.0, 1.0, 1.0, 1.0),
+                distortion_scroll: Vec2::new(0.0, 0.1),
+                index_of_refraction: 1.33,
+                thickness: 0.5,
+                roughness: 0.0,
+                distortion_strength: 0.01,
+                time: 0.0,
+                has_distortion_map: distortion.is_some() as i32,
+            },
+            distortion,
+            sampler,
+            program_pipeline,
+            uniforms: MaterialUniforms::new::<RefractionPropertyBlock>(
+                "RefractionPropertyBlock UBO",
+            ),
+        })
+    }
+
+    pub fn set_tint(&mut self, tint: Vec4) {
+        self.property_block.tint = tint
+    }
+
+    pub fn set_index_of_refraction(&mut self, index_of_refraction: f32) {
+        self.property_block.index_of_refraction = index_of_refraction
+    }
+
+    // How far the refracted ray travels before the background is sampled, in world units.
+    pub fn set_thickness(&mut self, thickness: f32) {
+        self.property_block.thickness = thickness
+    }
+
+    // Blurs the background through the mip chain of the scene color.
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.property_block.roughness = roughness
+    }
+
+    // Screen space offset of the distortion, in UV units.
+    pub fn set_distortion_strength(&mut self, distortion_strength: f32) {
+        self.property_block.distortion_strength = distortion_strength
+    }
+
+    // Scroll speed of the distortion map, in UV units per second.
+    pub fn set_distortion_scroll(&mut self, distortion_scroll: Vec2) {
+        self.property_block.distortion_scroll = distortion_scroll
+    }
+
+    // Drives the scrolling of the distortion map, in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.property_block.time = time
+    }
+}
+
+impl Material for RefractiveMaterial {
+    fn bind(&self) {
+        self.program_pipeline.bind();
+
+        // Shares the material binding with the PBS materials.
+        self.uniforms
+            .bind(MATERIAL_UBO_BINDING_INDEX, &self.property_block);
+
+        if let Some(distortion) = &self.distortion {
+            self.program_pipeline.set_texture_2d(
+                NORMAL_MAP_BINDING_INDEX,
+                &distortion,
+                &self.sampler,
+            );
+        }
+    }
+
+    fn unbind(&self) {
+        self.program_pipeline.unbind();
+    }
+
+    fn program_pipeline(&self) -> &ProgramPipeline {
+        &self.program_pipeline
+    }
+
+    fn update(&mut self) {
+        self.uniforms.next_frame();
+    }
+
+    fn render_layer(&self) -> RenderLayer {
+        RenderLayer::Transparent
+    }
+}
+
+impl Gui for RefractiveMaterial {
+    fn gui(&mut self, ui: &Ui) {
+        if imgui::CollapsingHeader::new(im_str!("Refraction"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            ui.spacing();
+            ui.group(|| {
+                let mut tint: [f32; 4] = self.property_block.tint.into();
+                if imgui::ColorEdit::new(im_str!("Tint"), &mut tint)
+                    .format(ColorFormat::Float)
+                    .alpha(false)
+                    .build(&ui)
+                {
+                    self.property_block.tint = tint.into();
+                }
+
+                imgui::Slider::new(im_str!("Index of Refraction"))
+                    .range(RangeInclusive::new(1.0, 2.5))
+                    .display_format(im_str!("%.3f"))
+                    .build(&ui, &mut self.property_block.index_of_refraction);
+                imgui::Slider::new(im_str!("Thickness"))
+                    .range(RangeInclusive::new(0.0, 5.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.thickness);
+                imgui::Slider::new(im_str!("Roughness"))
+                    .range(RangeInclusive::new(0.0, 1.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.property_block.roughness);
+
+                if self.distortion.is_some() {
+                    imgui::Slider::new(im_str!("Distortion Strength"))
+                        .range(RangeInclusive::new(0.0, 0.1))
+                        .display_format(im_str!("%.3f"))
+                        .build(&ui, &mut self.property_block.distortion_strength);
+                    imgui::Drag::new(im_str!("Distortion Scroll"))
+                        .range(RangeInclusive::new(-1.0, 1.0))
+                        .speed(0.01)
+                        .build_array(&ui, self.property_block.distortion_scroll.as_mut_slice());
+                }
+            });
+        }
+    }
+}
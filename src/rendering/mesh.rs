@@ -3,161 +3,586 @@ use gl_bindings as gl;
 
 use crate::{
     core::{
-        asset::Asset,
+        asset::{self, meta::AssetMeta, Asset},
         math::{Vec2, Vec3, Vec4},
     },
+    geometry::{bounds::Aabb, shapes, MeshData},
     rendering::{
         buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        frame_stats::FrameStats,
+        indirect::IndirectDrawBuffer,
+        instancing::{InstanceBuffer, InstanceData, INSTANCE_BUFFER_BINDING_INDEX},
+        skinning::SkinnedVertex,
+        state::StateManager,
+        vertex_layout::{VertexAttribute, VertexFormat, VertexLayout},
         Draw,
     },
 };
-use std::{mem, path::Path, ptr};
+use std::{
+    cell::Cell,
+    hash::{Hash, Hasher},
+    mem,
+    path::Path,
+    ptr, slice,
+};
+
+pub static FULLSCREEN_MESH: FullscreenMesh = FullscreenMesh;
+
+thread_local! {
+    // The empty vertex array of the fullscreen triangle, created with the first draw in the
+    // context current on the thread.
+    static FULLSCREEN_VAO: Cell<GLuint> = Cell::new(0);
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrimitiveMode {
+    Points = gl::POINTS,
+    Lines = gl::LINES,
+    LineStrip = gl::LINE_STRIP,
+    Triangles = gl::TRIANGLES,
+    TriangleStrip = gl::TRIANGLE_STRIP,
+    Patches = gl::PATCHES,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexFormat {
+    UnsignedShort = gl::UNSIGNED_SHORT,
+    UnsignedInt = gl::UNSIGNED_INT,
+}
+
+impl IndexFormat {
+    // 16-bit indices are enough as long as every vertex can be addressed.
+    pub fn for_vertex_count(vertex_count: usize) -> Self {
+        if vertex_count <= u16::MAX as usize + 1 {
+            IndexFormat::UnsignedShort
+        } else {
+            IndexFormat::UnsignedInt
+        }
+    }
 
-lazy_static! {
-    pub static ref FULLSCREEN_MESH: FullscreenMesh = FullscreenMesh::new();
+    pub fn size(self) -> usize {
+        match self {
+            IndexFormat::UnsignedShort => mem::size_of::<u16>(),
+            IndexFormat::UnsignedInt => mem::size_of::<u32>(),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Vertex {
-    position: Vec3,
-    normal: Vec3,
-    tangent: Vec4,
-    tex_coord: Vec2,
-    color: Vec4,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    pub tex_coord: Vec2,
+    pub color: Vec4,
+    // Second UV channel, e.g. unique non overlapping coordinates of a lightmap.
+    pub tex_coord1: Vec2,
+}
+
+impl Vertex {
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at_offset(
+                VertexAttribute::Position,
+                VertexFormat::Float3,
+                offset_of!(Vertex, position) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Normal,
+                VertexFormat::Float3,
+                offset_of!(Vertex, normal) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Tangent,
+                VertexFormat::Float4,
+                offset_of!(Vertex, tangent) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord0,
+                VertexFormat::Float2,
+                offset_of!(Vertex, tex_coord) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Color,
+                VertexFormat::Float4,
+                offset_of!(Vertex, color) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord1,
+                VertexFormat::Float2,
+                offset_of!(Vertex, tex_coord1) as u32,
+            )
+            .with_stride(mem::size_of::<Vertex>() as u32)
+    }
+}
+
+// Compact vertex for imported meshes, 36 bytes instead of 72. Positions stay at full precision,
+// normals and tangents are snorm8, UVs are half floats and colors are unorm8. The normal's 4th
+// component is padding.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct QuantizedVertex {
+    pub position: [f32; 3],
+    pub normal: [i8; 4],
+    pub tangent: [i8; 4],
+    pub tex_coord: [u16; 2],
+    pub color: [u8; 4],
+    pub tex_coord1: [u16; 2],
+}
+
+impl QuantizedVertex {
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at_offset(
+                VertexAttribute::Position,
+                VertexFormat::Float3,
+                offset_of!(QuantizedVertex, position) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Normal,
+                VertexFormat::Byte4Normalized,
+                offset_of!(QuantizedVertex, normal) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Tangent,
+                VertexFormat::Byte4Normalized,
+                offset_of!(QuantizedVertex, tangent) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord0,
+                VertexFormat::Half2,
+                offset_of!(QuantizedVertex, tex_coord) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Color,
+                VertexFormat::UnsignedByte4Normalized,
+                offset_of!(QuantizedVertex, color) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord1,
+                VertexFormat::Half2,
+                offset_of!(QuantizedVertex, tex_coord1) as u32,
+            )
+            .with_stride(mem::size_of::<QuantizedVertex>() as u32)
+    }
+}
+
+// Processing applied to imported meshes before they are uploaded.
+#[derive(Debug, Clone, Copy)]
+pub struct MeshImportSettings {
+    // Reorders indices and vertices for better vertex cache, overdraw and fetch efficiency.
+    pub optimize: bool,
+    // Stores the mesh with the QuantizedVertex layout.
+    pub quantize: bool,
+    // Uniform scale applied to the positions, e.g. 0.01 for files authored in centimeters.
+    pub scale: f32,
+}
+
+impl Default for MeshImportSettings {
+    fn default() -> Self {
+        Self {
+            optimize: true,
+            quantize: false,
+            scale: 1.0,
+        }
+    }
+}
+
+impl MeshImportSettings {
+    // Overrides the defaults with the mesh's .meta sidecar, if it has one.
+    pub fn from_sidecar<P: AsRef<Path>>(path: P, defaults: Self) -> Result<Self, String> {
+        let meta = match AssetMeta::load_for(path)? {
+            Some(meta) => meta,
+            None => return Ok(defaults),
+        };
+
+        meta.check_keys(&["optimize", "quantize", "scale"]);
+
+        Ok(Self {
+            optimize: meta.get_bool("optimize")?.unwrap_or(defaults.optimize),
+            quantize: meta.get_bool("quantize")?.unwrap_or(defaults.quantize),
+            scale: meta.get_f32("scale")?.unwrap_or(defaults.scale),
+        })
+    }
+}
+
+// Compares the scale bit for bit so the settings can be part of a cache key.
+impl PartialEq for MeshImportSettings {
+    fn eq(&self, other: &Self) -> bool {
+        self.optimize == other.optimize
+            && self.quantize == other.quantize
+            && self.scale.to_bits() == other.scale.to_bits()
+    }
+}
+
+impl Eq for MeshImportSettings {}
+
+impl Hash for MeshImportSettings {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.optimize.hash(state);
+        self.quantize.hash(state);
+        self.scale.to_bits().hash(state);
+    }
 }
 
 pub struct Mesh {
     vao: GLuint,
-    vertices: Vec<Vertex>,
-    indices: Vec<u32>,
+    layout: VertexLayout,
+    vertex_count: usize,
+    index_count: usize,
+    index_format: IndexFormat,
+    bounds: Aabb,
     _vbo: Buffer,
-    _ibo: Buffer,
+    _ibo: Option<Buffer>,
 }
 
 impl Mesh {
     pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
+        Self::new_with_layout(&vertices, &indices, Vertex::layout())
+    }
+
+    // Creates a mesh from an interleaved vertex buffer described by the given layout. An empty
+    // index slice creates a non-indexed mesh.
+    pub fn new_with_layout<V>(vertices: &[V], indices: &[u32], layout: VertexLayout) -> Mesh {
+        assert_eq!(
+            mem::size_of::<V>() as u32,
+            layout.get_stride(),
+            "Vertex size does not match the stride of the vertex layout."
+        );
+
         //TODO: Check if dynamic buffer storage is needed here.
-        println!(
+        log::debug!(
             "Creating mesh with {} vertices and {} indices.",
             vertices.len(),
             indices.len()
         );
         let vbo = Buffer::new_from_slice(
             "Vertex Buffer",
-            &vertices,
+            vertices,
             BufferTarget::Array,
             BufferStorageFlags::DYNAMIC,
         );
 
-        let ibo = Buffer::new_from_slice(
-            "Index Buffer",
-            &indices,
-            BufferTarget::ElementArray,
-            BufferStorageFlags::DYNAMIC,
-        );
+        let index_format = IndexFormat::for_vertex_count(vertices.len());
+
+        let ibo = if indices.is_empty() {
+            None
+        } else {
+            Some(match index_format {
+                IndexFormat::UnsignedShort => {
+                    let indices = indices
+                        .iter()
+                        .map(|&index| index as u16)
+                        .collect::<Vec<_>>();
+
+                    Buffer::new_from_slice(
+                        "Index Buffer",
+                        &indices,
+                        BufferTarget::ElementArray,
+                        BufferStorageFlags::DYNAMIC,
+                    )
+                }
+                IndexFormat::UnsignedInt => Buffer::new_from_slice(
+                    "Index Buffer",
+                    indices,
+                    BufferTarget::ElementArray,
+                    BufferStorageFlags::DYNAMIC,
+                ),
+            })
+        };
 
         let mut vao: GLuint = 0;
         unsafe {
             gl::CreateVertexArrays(1, &mut vao);
 
-            gl::VertexArrayVertexBuffer(vao, 0, vbo.get_id(), 0, mem::size_of::<Vertex>() as i32);
-            gl::VertexArrayElementBuffer(vao, ibo.get_id());
+            // The mesh has only 1 interleaved VBO so it is located in binding 0.
+            gl::VertexArrayVertexBuffer(vao, 0, vbo.get_id(), 0, layout.get_stride() as i32);
+
+            if let Some(ibo) = ibo.as_ref() {
+                gl::VertexArrayElementBuffer(vao, ibo.get_id());
+            }
+        }
+
+        layout.apply(vao, 0);
 
-            gl::EnableVertexArrayAttrib(vao, 0); //positions
-            gl::EnableVertexArrayAttrib(vao, 1); //normals
-            gl::EnableVertexArrayAttrib(vao, 2); //tangents
-            gl::EnableVertexArrayAttrib(vao, 3); //texture coordinates
-            gl::EnableVertexArrayAttrib(vao, 4); //colors
+        let bounds = Self::compute_bounds(vertices, &layout);
 
-            // Specify format for the position attribute (0)
-            gl::VertexArrayAttribFormat(
-                vao,
+        Mesh {
+            vao,
+            layout,
+            vertex_count: vertices.len(),
+            index_count: indices.len(),
+            index_format,
+            bounds,
+            _vbo: vbo,
+            _ibo: ibo,
+        }
+    }
+
+    // Skinned meshes are drawn with a skinning capable vertex shader, see
+    // PbsVertexVariant::Skinned.
+    pub fn new_skinned(vertices: &[SkinnedVertex], indices: &[u32]) -> Mesh {
+        Self::new_with_layout(vertices, indices, SkinnedVertex::layout())
+    }
+
+    // Sources the per instance attributes from the given buffer. The buffer must outlive the
+    // draw calls that use it.
+    pub fn set_instance_buffer(&self, instances: &InstanceBuffer) {
+        let layout = InstanceData::layout();
+
+        unsafe {
+            gl::VertexArrayVertexBuffer(
+                self.vao,
+                INSTANCE_BUFFER_BINDING_INDEX,
+                instances.get_buffer().get_id(),
                 0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                offset_of!(Vertex, position) as u32,
+                layout.get_stride() as i32,
             );
+            gl::VertexArrayBindingDivisor(self.vao, INSTANCE_BUFFER_BINDING_INDEX, 1);
+        }
 
-            // Specify format for the normal attribute (1)
-            gl::VertexArrayAttribFormat(
-                vao,
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                offset_of!(Vertex, normal) as u32,
-            );
+        layout.apply(self.vao, INSTANCE_BUFFER_BINDING_INDEX);
+    }
 
-            // Specify format for the tangent attribute (2)
-            gl::VertexArrayAttribFormat(
-                vao,
-                2,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                offset_of!(Vertex, tangent) as u32,
-            );
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
 
-            // Specify format for the texture coordinate attribute (3)
-            gl::VertexArrayAttribFormat(
-                vao,
-                3,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                offset_of!(Vertex, tex_coord) as u32,
-            );
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
 
-            // Specify format for the color attribute (4)
-            gl::VertexArrayAttribFormat(
-                vao,
-                4,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                offset_of!(Vertex, color) as u32,
-            );
+    pub fn index_count(&self) -> usize {
+        self.index_count
+    }
 
-            // Associate attribute bindings with the VBO binding in the VAO.
-            // This VAO has only 1 VBO so it is located in binding 0.
-            gl::VertexArrayAttribBinding(vao, 0, 0);
-            gl::VertexArrayAttribBinding(vao, 1, 0);
-            gl::VertexArrayAttribBinding(vao, 2, 0);
-            gl::VertexArrayAttribBinding(vao, 3, 0);
-            gl::VertexArrayAttribBinding(vao, 4, 0);
-        }
+    pub fn index_format(&self) -> IndexFormat {
+        self.index_format
+    }
 
-        Mesh {
-            vao,
-            vertices,
-            indices,
-            _vbo: vbo,
-            _ibo: ibo,
-        }
+    // Object space bounds of the vertex positions. Empty when the layout has no Float3
+    // position.
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
     }
-}
 
-impl Draw for Mesh {
-    fn draw(&self) {
+    fn compute_bounds<V>(vertices: &[V], layout: &VertexLayout) -> Aabb {
+        let position = layout
+            .attributes()
+            .iter()
+            .find(|a| a.attribute == VertexAttribute::Position && a.format == VertexFormat::Float3);
+
+        let offset = match position {
+            Some(position) => position.offset as usize,
+            None => return Aabb::empty(),
+        };
+
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                vertices.len() * mem::size_of::<V>(),
+            )
+        };
+
+        Aabb::from_points(
+            bytes
+                .chunks_exact(layout.get_stride() as usize)
+                .map(|vertex| {
+                    let component = |i: usize| {
+                        let start = offset + i * mem::size_of::<f32>();
+                        let mut value = [0u8; 4];
+                        value.copy_from_slice(&vertex[start..start + 4]);
+                        f32::from_ne_bytes(value)
+                    };
+
+                    Vec3::new(component(0), component(1), component(2))
+                }),
+        )
+    }
+
+    // Submits every uploaded command of the buffer in a single call. The commands address
+    // ranges of this mesh's vertex and index buffers.
+    pub fn multi_draw_indirect(
+        &self,
+        commands: &IndirectDrawBuffer,
+        primitive_mode: PrimitiveMode,
+    ) {
+        assert!(
+            self.index_count > 0,
+            "Indirect draws require an indexed mesh."
+        );
+
+        if commands.draw_count() == 0 {
+            return;
+        }
+
+        StateManager::bind_vertex_array(self.vao);
+
         unsafe {
-            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, commands.get_buffer().get_id());
 
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
-                gl::UNSIGNED_INT,
+            gl::MultiDrawElementsIndirect(
+                primitive_mode as u32,
+                self.index_format as u32,
                 ptr::null(),
+                commands.draw_count() as i32,
+                0,
             );
 
-            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+
+        FrameStats::record_multi_draw(
+            primitive_mode,
+            commands.commands()[..commands.draw_count()]
+                .iter()
+                .map(|command| (command.count as usize, command.instance_count)),
+        )
+    }
+
+    pub fn draw_with_primitive_mode(&self, primitive_mode: PrimitiveMode) {
+        self.draw_instanced_with_primitive_mode(primitive_mode, 1)
+    }
+
+    pub fn draw_instanced(&self, instance_count: u32) {
+        self.draw_instanced_with_primitive_mode(PrimitiveMode::Triangles, instance_count)
+    }
+
+    pub fn draw_instanced_with_primitive_mode(
+        &self,
+        primitive_mode: PrimitiveMode,
+        instance_count: u32,
+    ) {
+        StateManager::bind_vertex_array(self.vao);
+
+        unsafe {
+            if self.index_count > 0 {
+                gl::DrawElementsInstanced(
+                    primitive_mode as u32,
+                    self.index_count as i32,
+                    self.index_format as u32,
+                    ptr::null(),
+                    instance_count as i32,
+                );
+            } else {
+                gl::DrawArraysInstanced(
+                    primitive_mode as u32,
+                    0,
+                    self.vertex_count as i32,
+                    instance_count as i32,
+                );
+            }
+        }
+
+        let vertex_count = if self.index_count > 0 {
+            self.index_count
+        } else {
+            self.vertex_count
+        };
+        FrameStats::record_draw(primitive_mode, vertex_count, instance_count)
+    }
+
+    // Reads the geometry of a mesh file without touching GL, so it can run on any thread.
+    pub(crate) fn read_mesh_data<P: AsRef<Path>>(path: P) -> Result<MeshData, String> {
+        if Self::is_obj(path.as_ref()) || Self::is_fbx(path.as_ref()) {
+            let source = std::fs::read(path.as_ref()).map_err(|e| e.to_string())?;
+            return Self::read_mesh_data_from_slice(&source, path);
+        }
+
+        match gltf::import(path) {
+            Ok((document, buffers, _)) => Self::read_gltf_mesh_data(&document, &buffers),
+            Err(_) => Err("Failed to load Gltf file".to_string()),
         }
     }
+
+    // Same as read_mesh_data for files that are already in memory. The path selects the format.
+    // glTF files have to be self contained, e.g. .glb.
+    pub(crate) fn read_mesh_data_from_slice<P: AsRef<Path>>(
+        data: &[u8],
+        path: P,
+    ) -> Result<MeshData, String> {
+        // All groups of an OBJ file are merged into a single mesh.
+        if Self::is_obj(path.as_ref()) {
+            let source = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+            let (groups, _) = asset::obj::parse_obj(source)?;
+
+            let mut mesh_data = MeshData::default();
+            for group in &groups {
+                mesh_data.append(&group.mesh_data);
+            }
+
+            return Ok(mesh_data);
+        }
+
+        if Self::is_fbx(path.as_ref()) {
+            return Self::read_fbx_mesh_data(data);
+        }
+
+        match gltf::import_slice(data) {
+            Ok((document, buffers, _)) => Self::read_gltf_mesh_data(&document, &buffers),
+            Err(_) => Err("Failed to load Gltf file".to_string()),
+        }
+    }
+
+    fn read_gltf_mesh_data(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<MeshData, String> {
+        let scene = document
+            .scenes()
+            .next()
+            .ok_or_else(|| "Gltf document has no scenes".to_string())?;
+        let node = scene
+            .nodes()
+            .next()
+            .ok_or_else(|| "Gltf scene has no nodes".to_string())?;
+        let mesh = node
+            .mesh()
+            .ok_or_else(|| "Gltf node has no mesh".to_string())?;
+        let primitive = mesh
+            .primitives()
+            .next()
+            .ok_or_else(|| "Gltf mesh has no primitives".to_string())?;
+
+        asset::gltf::read_primitive(&primitive, buffers)
+    }
+
+    #[cfg(feature = "fbx")]
+    fn read_fbx_mesh_data(data: &[u8]) -> Result<MeshData, String> {
+        asset::fbx::read_mesh_data(data)
+    }
+
+    #[cfg(not(feature = "fbx"))]
+    fn read_fbx_mesh_data(_: &[u8]) -> Result<MeshData, String> {
+        Err("FBX files can only be loaded with the fbx feature enabled.".to_string())
+    }
+
+    fn is_obj(path: &Path) -> bool {
+        Self::has_extension(path, "obj")
+    }
+
+    fn is_fbx(path: &Path) -> bool {
+        Self::has_extension(path, "fbx")
+    }
+
+    fn has_extension(path: &Path, extension: &str) -> bool {
+        path.extension()
+            .map_or(false, |e| e.eq_ignore_ascii_case(extension))
+    }
+}
+
+impl Draw for Mesh {
+    fn draw(&self) {
+        self.draw_with_primitive_mode(PrimitiveMode::Triangles)
+    }
 }
 
 impl Drop for Mesh {
     fn drop(&mut self) {
+        StateManager::vertex_array_deleted(self.vao);
         unsafe { gl::DeleteVertexArrays(1, &self.vao) }
     }
 }
@@ -165,83 +590,52 @@ impl Drop for Mesh {
 impl Asset for Mesh {
     type Output = Self;
     type Error = String;
-    type LoadConfig = ();
+    type LoadConfig = MeshImportSettings;
 
     fn load<P: AsRef<Path>>(
         path: P,
-        _: Option<Self::LoadConfig>,
+        load_config: Option<Self::LoadConfig>,
     ) -> Result<Self::Output, Self::Error> {
-        use gltf::buffer;
-
-        if let Ok((document, buffers, _)) = gltf::import(path) {
-            let scene = document
-                .scenes()
-                .next()
-                .expect("Gltf document has no scenes");
-            let node = scene.nodes().next().expect("Gltf scene has no nodes");
-            let mesh = node.mesh().expect("Gltf node has no mesh");
-            let primitive = mesh.primitives().next().unwrap();
-            let reader = primitive.reader(|buffer| {
-                buffers
-                    .get(buffer.index())
-                    .and_then(|buffer::Data(data)| data.as_slice().into())
-            });
-
-            let positions = reader.read_positions().expect("Mesh has no positions");
-            let normals = reader.read_normals().expect("Mesh has no normals");
-            let tangents = reader.read_tangents().expect("Mesh has no tangents");
-            let tex_coords = reader
-                .read_tex_coords(0)
-                .expect("Mesh has no UV channel 0.");
-
-            let vertices = positions
-                .zip(normals)
-                .zip(tangents)
-                .zip(tex_coords.into_f32())
-                .map(|(((v, n), t), tc)| Vertex {
-                    position: Vec3::new(v[0], v[1], v[2]),
-                    normal: Vec3::new(n[0], n[1], n[2]),
-                    tangent: t.into(),
-                    tex_coord: Vec2::new(tc[0], tc[1]),
-                    color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-                })
-                .collect::<Vec<_>>();
-
-            let indices = reader
-                .read_indices()
-                .expect("Mesh has no indices.")
-                .into_u32()
-                .map(|index| index)
-                .collect::<Vec<_>>();
-
-            Ok(Mesh::new(vertices, indices))
-        } else {
-            Err("Failed to load Gltf file".to_string())
-        }
+        // Without a load config the settings come from the mesh's .meta sidecar.
+        let settings = match load_config {
+            Some(settings) => settings,
+            None => MeshImportSettings::from_sidecar(path.as_ref(), Default::default())?,
+        };
+
+        let mesh_data = Mesh::read_mesh_data(path)?;
+
+        Ok(asset::gltf::process_mesh_data(mesh_data, settings))
     }
 }
 
-pub struct FullscreenMesh {
-    vao: GLuint,
-}
+// A triangle covering the viewport. The vertex shader derives the positions from the vertex ids.
+pub struct FullscreenMesh;
 
 impl FullscreenMesh {
-    pub fn new() -> Self {
-        let mut vao: GLuint = 0;
-
-        unsafe { gl::CreateVertexArrays(1, &mut vao) }
+    fn vao() -> GLuint {
+        FULLSCREEN_VAO.with(|vao| {
+            if vao.get() == 0 {
+                let mut id: GLuint = 0;
+                unsafe { gl::CreateVertexArrays(1, &mut id) }
+                vao.set(id)
+            }
+
+            vao.get()
+        })
+    }
 
-        FullscreenMesh { vao }
+    // The vertex array belonged to a lost context, the next draw creates another one.
+    pub(crate) fn forget() {
+        FULLSCREEN_VAO.with(|vao| vao.set(0))
     }
 }
 
 impl Draw for FullscreenMesh {
     fn draw(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
-            gl::BindVertexArray(0);
-        }
+        StateManager::bind_vertex_array(Self::vao());
+        unsafe { gl::DrawArrays(gl::TRIANGLES, 0, 3) }
+
+        FrameStats::record_draw(PrimitiveMode::Triangles, 3, 1)
     }
 }
 
@@ -249,208 +643,7 @@ pub struct MeshUtilities;
 
 impl MeshUtilities {
     pub fn generate_quadrilateral(dimensions: Vec3) -> Mesh {
-        let half_dimensions = dimensions * 0.5;
-
-        let vertices = vec![
-            // front
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, 1.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, 1.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, 1.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, 1.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            // right
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, -1.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, -1.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, -1.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, -1.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            // left
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(-1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, 1.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(-1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, 1.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(-1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, 1.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(-1.0, 0.0, 0.0),
-                tangent: Vec4::new(0.0, 0.0, 1.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            // back
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, -1.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, -1.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, -1.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 0.0, -1.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            // top
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 1.0, 0.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 1.0, 0.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, 1.0, 0.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, 1.0, 0.0),
-                tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            // bottom
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, -1.0, 0.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, -1.0, 0.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(0.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, half_dimensions.z),
-                normal: Vec3::new(0.0, -1.0, 0.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 0.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-            Vertex {
-                position: Vec3::new(-half_dimensions.x, -half_dimensions.y, -half_dimensions.z),
-                normal: Vec3::new(0.0, -1.0, 0.0),
-                tangent: Vec4::new(-1.0, 0.0, 0.0, 1.0),
-                tex_coord: Vec2::new(1.0, 1.0),
-                color: Vec4::new(1.0, 1.0, 1.0, 1.0),
-            },
-        ];
-
-        let index_count = vertices.len() / 2 * 3;
-
-        let mut indices = vec![];
-        indices.resize(index_count, 0);
-
-        let mut i = 0;
-        let mut j = 0;
-        while i < indices.len() {
-            indices[i] = j;
-
-            indices[i + 1] = j + 2;
-            indices[i + 4] = j + 2;
-
-            indices[i + 2] = j + 1;
-            indices[i + 3] = j + 1;
-
-            indices[i + 5] = j + 3;
-
-            i += 6;
-            j += 4;
-        }
-
-        Mesh::new(vertices, indices)
+        shapes::cuboid(dimensions).into_mesh()
     }
 
     pub fn generate_cube(size: f32) -> Mesh {
@@ -5,18 +5,63 @@ macro_rules! offset_of {
     };
 }
 
+pub mod async_pipeline;
+pub mod atmosphere;
 pub mod buffer;
+pub mod color_lut;
+pub mod command_buffer;
+pub mod context_loss;
+pub mod debug_draw;
+pub mod debug_view;
+pub mod draw_list;
+pub mod environment;
+pub mod error;
+pub mod fence;
+pub mod fog;
 pub mod format;
+pub mod frame_capture;
+pub mod frame_stats;
+pub mod gpu_capabilities;
+pub mod gpu_profiler;
+pub mod gizmo;
+pub mod graphics_settings;
 pub mod framebuffer;
+pub mod hiz;
+pub mod indirect;
+pub mod instancing;
 pub mod light;
+pub mod light_culling_debug;
+pub mod light_probe;
+pub mod lod;
+pub mod ltc;
 pub mod material;
 pub mod mesh;
+pub mod normal_visualizer;
+pub mod object_label;
+pub mod per_draw;
+pub mod picking;
+pub mod pipeline_state;
+pub mod pipeline_statistics;
 pub mod postprocess;
 pub mod program_pipeline;
+pub mod render_texture;
 pub mod sampler;
+pub mod scene_color;
 pub mod shader;
+pub mod shadow;
+pub mod shader_validation;
+pub mod skinning;
+pub mod sky;
+pub mod sort_key;
+pub mod sprite_batch;
+pub mod ssao;
 pub mod state;
+pub mod streaming_buffer;
+pub mod terrain;
 pub mod texture;
+pub mod transform_feedback;
+pub mod vertex_layout;
+pub mod viewport;
 
 pub trait Draw {
     fn draw(&self);
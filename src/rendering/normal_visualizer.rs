@@ -0,0 +1,124 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Vec3, Vec4},
+    imgui::{im_str, ColorFormat, Gui, Ui},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags},
+        error::RendererError,
+        mesh::{Mesh, PrimitiveMode},
+        program_pipeline::ProgramPipeline,
+        shader::ShaderStage,
+    },
+};
+use std::ops::RangeInclusive;
+
+pub(crate) const UBO_BINDING_INDEX: u32 = 7;
+
+#[repr(C)]
+struct NormalVisualizationUniforms {
+    color: Vec4,
+    normal_length: f32,
+    _pad: Vec3,
+}
+
+pub struct NormalVisualizer {
+    pipeline: ProgramPipeline,
+    ubo: Buffer,
+    color: [f32; 4],
+    normal_length: f32,
+    enabled: bool,
+}
+
+impl NormalVisualizer {
+    pub fn new() -> Result<Self, RendererError> {
+        let pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/normal_visualization.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Geometry,
+                "src/rendering/shaders/normal_visualization.geom",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/normal_visualization.frag",
+            )?)
+            .build()?;
+
+        let mut ubo = Buffer::new(
+            "Normal Visualization UBO",
+            std::mem::size_of::<NormalVisualizationUniforms>() as isize,
+            BufferTarget::Uniform,
+            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
+        );
+        ubo.bind(UBO_BINDING_INDEX);
+        ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
+
+        Ok(Self {
+            pipeline,
+            ubo,
+            color: [1.0, 1.0, 0.0, 1.0],
+            normal_length: 0.5,
+            enabled: false,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled
+    }
+
+    pub fn set_normal_length(&mut self, normal_length: f32) {
+        self.normal_length = normal_length
+    }
+
+    // Expects the per frame (binding 0) and per draw (binding 1) blocks of the mesh to be filled.
+    pub fn draw(&self, mesh: &Mesh) {
+        if !self.enabled {
+            return;
+        }
+
+        self.pipeline.bind();
+
+        self.ubo.fill_mapped(
+            0,
+            &NormalVisualizationUniforms {
+                color: self.color.into(),
+                normal_length: self.normal_length,
+                _pad: Vec3::new(0.0, 0.0, 0.0),
+            },
+        );
+
+        mesh.draw_with_primitive_mode(PrimitiveMode::Triangles);
+
+        self.pipeline.unbind()
+    }
+}
+
+impl Gui for NormalVisualizer {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Normal Visualization"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                ui.checkbox(im_str!("Enabled##normal_visualization"), &mut self.enabled);
+
+                imgui::Slider::new(im_str!("Length"))
+                    .range(RangeInclusive::new(0.01, 5.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.normal_length);
+
+                imgui::ColorEdit::new(im_str!("Color"), &mut self.color)
+                    .format(ColorFormat::Float)
+                    .alpha(false)
+                    .picker(true)
+                    .build(&ui);
+            });
+    }
+}
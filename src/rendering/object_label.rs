@@ -0,0 +1,21 @@
+use gl::types::*;
+use gl_bindings as gl;
+use std::ffi::CString;
+
+// Names a GL object for debuggers and for the messages of the GL debug output, e.g.
+// gl::TEXTURE, gl::FRAMEBUFFER, gl::PROGRAM_PIPELINE. Names longer than the driver allows are
+// truncated.
+pub fn set_object_label(identifier: GLenum, id: GLuint, name: &str) {
+    if id == 0 {
+        return;
+    }
+
+    let mut max_length: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_LABEL_LENGTH, &mut max_length) }
+
+    let name = name.replace('\0', "");
+    let length = name.len().min(max_length.max(1) as usize - 1);
+    let label = CString::new(&name.as_bytes()[..length]).unwrap();
+
+    unsafe { gl::ObjectLabel(identifier, id, length as i32, label.as_ptr()) }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    core::math::{inverse_transpose, Mat4},
+    rendering::{
+        buffer::{Buffer, BufferTarget},
+        streaming_buffer::{StreamingAllocation, StreamingBuffer},
+    },
+};
+
+// Uniform block binding of the PerDrawBlock.
+pub const PER_DRAW_BINDING: u32 = 1;
+
+// Layout of the PerDrawBlock. Shaders that batch materials declare a uint materialIndex after
+// normalMatrix, the others may leave it out.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PerDrawData {
+    pub model: Mat4,
+    pub normal_matrix: Mat4,
+    pub material_index: u32,
+    _pad: [u32; 3],
+}
+
+impl PerDrawData {
+    pub fn new(model: Mat4, material_index: u32) -> Self {
+        Self {
+            normal_matrix: inverse_transpose(model),
+            model,
+            material_index,
+            _pad: [0; 3],
+        }
+    }
+}
+
+// Identifies the per draw data of a single draw call within the current frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerDrawHandle {
+    allocation: StreamingAllocation,
+}
+
+// Per draw data of a whole frame, written to one persistently mapped, triple buffered uniform
+// buffer. Each draw binds its own range instead of updating a uniform buffer per object.
+pub struct PerDrawUniforms {
+    buffer: StreamingBuffer,
+    max_draws: usize,
+    draw_count: usize,
+}
+
+impl PerDrawUniforms {
+    pub fn new(max_draws: usize) -> Self {
+        let stride = Buffer::align_offset(
+            std::mem::size_of::<PerDrawData>() as isize,
+            BufferTarget::Uniform,
+        );
+
+        Self {
+            buffer: StreamingBuffer::new(
+                "Per Draw UBO",
+                stride * max_draws as isize,
+                BufferTarget::Uniform,
+            ),
+            max_draws,
+            draw_count: 0,
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.buffer.begin_frame();
+        self.draw_count = 0;
+    }
+
+    // Must be called after the last draw call of the frame.
+    pub fn end_frame(&mut self) {
+        self.buffer.end_frame()
+    }
+
+    pub fn push(&mut self, data: &PerDrawData) -> Option<PerDrawHandle> {
+        if self.draw_count == self.max_draws {
+            log::warn!(
+                "Per draw uniform capacity exceeded. Capacity: {}",
+                self.max_draws
+            );
+            return None;
+        }
+
+        let allocation = self.buffer.push(data)?;
+        self.draw_count += 1;
+
+        Some(PerDrawHandle { allocation })
+    }
+
+    // Convenience for push followed by bind.
+    pub fn push_and_bind(&mut self, data: &PerDrawData) -> Option<PerDrawHandle> {
+        let handle = self.push(data)?;
+        self.bind(&handle);
+
+        Some(handle)
+    }
+
+    pub fn bind(&self, handle: &PerDrawHandle) {
+        self.buffer.bind(PER_DRAW_BINDING, &handle.allocation)
+    }
+
+    pub fn draw_count(&self) -> usize {
+        self.draw_count
+    }
+
+    pub fn max_draws(&self) -> usize {
+        self.max_draws
+    }
+}
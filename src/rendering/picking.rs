@@ -0,0 +1,250 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::ecs::Entity,
+    core::math::{UVec2, Vec2},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        draw_list::DrawList,
+        error::RendererError,
+        fence::GpuFence,
+        framebuffer::{AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo},
+        per_draw::{PerDrawData, PerDrawUniforms},
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        shader::ShaderStage,
+        state::{DepthFunction, DepthStencilState, FixedFunctionState, StateManager},
+        texture::SizedTextureFormat,
+    },
+    Msaa,
+};
+use gl_bindings as gl;
+use std::{mem, ptr};
+
+// Picks in flight. Older ones are dropped when more are requested before the GPU catches up.
+const READBACK_COUNT: usize = 3;
+
+// Outcome of a pick. entity is None when the background was hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    pub position: Vec2,
+    pub entity: Option<Entity>,
+}
+
+struct Readback {
+    buffer: Buffer,
+    fence: Option<GpuFence>,
+    position: Vec2,
+    // The ids of the frame, id n belongs to entities[n - 1].
+    entities: Vec<Entity>,
+}
+
+// Mouse selection through an id buffer. Every draw with an entity writes its own id, the pixel
+// under the cursor is read back asynchronously and arrives a frame or two later.
+pub struct ObjectPicker {
+    framebuffer: Framebuffer,
+    pipeline_state: PipelineState,
+    readbacks: Vec<Readback>,
+    next_readback: usize,
+    pending: Option<Vec2>,
+}
+
+impl ObjectPicker {
+    // size is the size of the viewport that is picked from.
+    pub fn new(size: UVec2) -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/picking.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/picking.frag",
+            )?)
+            .build()?;
+
+        let pipeline_state = PipelineStateBuilder::new(program_pipeline)
+            .depth_stencil(DepthStencilState {
+                depth_function: DepthFunction::LessOrEqual,
+                ..Default::default()
+            })
+            .build();
+
+        let readbacks = (0..READBACK_COUNT)
+            .map(|_| Readback {
+                buffer: Buffer::new(
+                    "Picking Readback Buffer",
+                    mem::size_of::<u32>() as isize,
+                    BufferTarget::PixelPack,
+                    BufferStorageFlags::CLIENT_STORAGE,
+                ),
+                fence: None,
+                position: Vec2::new(0.0, 0.0),
+                entities: vec![],
+            })
+            .collect();
+
+        Ok(Self {
+            framebuffer: Self::create_framebuffer(size)?,
+            pipeline_state,
+            readbacks,
+            next_readback: 0,
+            pending: None,
+        })
+    }
+
+    // Keeps the current framebuffer if the new one can't be created.
+    pub fn resize(&mut self, size: UVec2) -> Result<(), RendererError> {
+        if size != self.framebuffer.size() {
+            self.framebuffer = Self::create_framebuffer(size)?
+        }
+
+        Ok(())
+    }
+
+    // Requests the entity under the position, in window pixels with the origin at the top left.
+    // The id buffer is only rendered on frames with a request.
+    pub fn pick(&mut self, position: Vec2) {
+        self.pending = Some(position)
+    }
+
+    // Renders the id buffer if a pick was requested. Expects the per frame block (binding 0)
+    // to be filled.
+    pub fn render(&mut self, draw_list: &DrawList, per_draw_uniforms: &mut PerDrawUniforms) {
+        let position = match self.pending.take() {
+            Some(position) => position,
+            None => return,
+        };
+
+        let size = self.framebuffer.size();
+        if position.x < 0.0
+            || position.y < 0.0
+            || position.x >= size.x as f32
+            || position.y >= size.y as f32
+        {
+            return;
+        }
+
+        self.framebuffer.bind();
+
+        unsafe {
+            let background: [u32; 4] = [0; 4];
+            let depth: f32 = 1.0;
+            gl::ClearNamedFramebufferuiv(self.framebuffer.id(), gl::COLOR, 0, background.as_ptr());
+            gl::ClearNamedFramebufferfv(self.framebuffer.id(), gl::DEPTH, 0, &depth);
+        }
+
+        self.pipeline_state.bind();
+
+        let readback = &mut self.readbacks[self.next_readback];
+        readback.entities.clear();
+        readback.position = position;
+        readback.fence = None;
+
+        for item in draw_list.items() {
+            let entity = match item.entity {
+                Some(entity) => entity,
+                None => continue,
+            };
+
+            if per_draw_uniforms
+                .push_and_bind(&PerDrawData::new(item.model, 0))
+                .is_none()
+            {
+                break;
+            }
+
+            readback.entities.push(entity);
+            self.pipeline_state
+                .program_pipeline()
+                .set_uint_all_stages("objectId", readback.entities.len() as u32);
+
+            item.mesh
+                .draw_with_primitive_mode(item.material.borrow().primitive_mode());
+        }
+
+        self.pipeline_state.unbind();
+
+        // The id buffer has its origin at the bottom left.
+        let x = position.x as i32;
+        let y = size.y as i32 - 1 - position.y as i32;
+
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, readback.buffer.get_id());
+            gl::GetTextureSubImage(
+                self.framebuffer.texture_attachments()[0].id(),
+                0,
+                x,
+                y,
+                0,
+                1,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                mem::size_of::<u32>() as i32,
+                ptr::null_mut(),
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        readback.fence = Some(GpuFence::new());
+        self.next_readback = (self.next_readback + 1) % READBACK_COUNT;
+
+        self.framebuffer.unbind(false);
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    // Returns the oldest pick that has arrived.
+    pub fn poll(&mut self) -> Option<PickResult> {
+        for offset in 0..READBACK_COUNT {
+            let index = (self.next_readback + offset) % READBACK_COUNT;
+            let readback = &mut self.readbacks[index];
+
+            if !readback.fence.as_ref().map_or(false, GpuFence::is_signaled) {
+                continue;
+            }
+
+            readback.fence = None;
+
+            let mut id: u32 = 0;
+            unsafe {
+                gl::GetNamedBufferSubData(
+                    readback.buffer.get_id(),
+                    0,
+                    mem::size_of::<u32>() as isize,
+                    &mut id as *mut u32 as *mut _,
+                );
+            }
+
+            let entity = match id {
+                0 => None,
+                id => readback.entities.get(id as usize - 1).cloned(),
+            };
+
+            return Some(PickResult {
+                position: readback.position,
+                entity,
+            });
+        }
+
+        None
+    }
+
+    fn create_framebuffer(size: UVec2) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::R32ui,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth32f,
+                    AttachmentType::Renderbuffer,
+                ),
+            ],
+        )
+        .map_err(RendererError::from)
+    }
+}
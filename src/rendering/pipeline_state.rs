@@ -0,0 +1,96 @@
+use crate::rendering::{
+    mesh::PrimitiveMode,
+    program_pipeline::ProgramPipeline,
+    state::{BlendState, DepthStencilState, FixedFunctionState, RasterizerState, StateManager},
+};
+
+pub struct PipelineState {
+    program_pipeline: ProgramPipeline,
+    fixed_function_state: FixedFunctionState,
+    primitive_mode: PrimitiveMode,
+    patch_vertices: u32,
+}
+
+impl PipelineState {
+    pub fn bind(&self) {
+        self.program_pipeline.bind();
+
+        StateManager::apply(&self.fixed_function_state);
+
+        if self.primitive_mode == PrimitiveMode::Patches {
+            StateManager::set_patch_vertices(self.patch_vertices)
+        }
+    }
+
+    pub fn unbind(&self) {
+        self.program_pipeline.unbind()
+    }
+
+    pub fn program_pipeline(&self) -> &ProgramPipeline {
+        &self.program_pipeline
+    }
+
+    pub fn fixed_function_state(&self) -> &FixedFunctionState {
+        &self.fixed_function_state
+    }
+
+    pub fn fixed_function_state_mut(&mut self) -> &mut FixedFunctionState {
+        &mut self.fixed_function_state
+    }
+
+    pub fn primitive_mode(&self) -> PrimitiveMode {
+        self.primitive_mode
+    }
+}
+
+pub struct PipelineStateBuilder {
+    program_pipeline: ProgramPipeline,
+    fixed_function_state: FixedFunctionState,
+    primitive_mode: PrimitiveMode,
+    patch_vertices: u32,
+}
+
+impl PipelineStateBuilder {
+    pub fn new(program_pipeline: ProgramPipeline) -> Self {
+        Self {
+            program_pipeline,
+            fixed_function_state: Default::default(),
+            primitive_mode: PrimitiveMode::Triangles,
+            patch_vertices: 3,
+        }
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: DepthStencilState) -> Self {
+        self.fixed_function_state.depth_stencil = depth_stencil;
+        self
+    }
+
+    pub fn blend(mut self, blend: Option<BlendState>) -> Self {
+        self.fixed_function_state.blend = blend;
+        self
+    }
+
+    pub fn rasterizer(mut self, rasterizer: RasterizerState) -> Self {
+        self.fixed_function_state.rasterizer = rasterizer;
+        self
+    }
+
+    pub fn primitive_mode(mut self, primitive_mode: PrimitiveMode) -> Self {
+        self.primitive_mode = primitive_mode;
+        self
+    }
+
+    pub fn patch_vertices(mut self, patch_vertices: u32) -> Self {
+        self.patch_vertices = patch_vertices;
+        self
+    }
+
+    pub fn build(self) -> PipelineState {
+        PipelineState {
+            program_pipeline: self.program_pipeline,
+            fixed_function_state: self.fixed_function_state,
+            primitive_mode: self.primitive_mode,
+            patch_vertices: self.patch_vertices,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    imgui::{im_str, Gui, Ui},
+    rendering::gpu_capabilities::GpuCapabilities,
+};
+use gl::types::*;
+use gl_bindings as gl;
+
+// Results are read this many frames later, so reading them never stalls.
+const FRAMES_IN_FLIGHT: usize = 4;
+
+const QUERY_TARGETS: [GLenum; 6] = [
+    gl::VERTICES_SUBMITTED_ARB,
+    gl::PRIMITIVES_SUBMITTED_ARB,
+    gl::VERTEX_SHADER_INVOCATIONS_ARB,
+    gl::CLIPPING_INPUT_PRIMITIVES_ARB,
+    gl::CLIPPING_OUTPUT_PRIMITIVES_ARB,
+    gl::FRAGMENT_SHADER_INVOCATIONS_ARB,
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineStatisticsResult {
+    pub vertices_submitted: u64,
+    pub primitives_submitted: u64,
+    pub vertex_shader_invocations: u64,
+    pub clipping_input_primitives: u64,
+    pub clipping_output_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+// Counts what the GPU actually processes between begin and end with pipeline statistics
+// queries, e.g. fragment shader invocations against the pixels of the target for overdraw.
+// Results arrive a few frames late.
+pub struct PipelineStatistics {
+    queries: Vec<[GLuint; 6]>,
+    // Whether the queries of the frame were issued and not read yet.
+    pending: Vec<bool>,
+    frame: usize,
+    active: bool,
+    result: PipelineStatisticsResult,
+    enabled: bool,
+}
+
+impl PipelineStatistics {
+    // Never counts without GL_ARB_pipeline_statistics_query.
+    pub fn new() -> Self {
+        let frames_in_flight = if GpuCapabilities::current().pipeline_statistics_query {
+            FRAMES_IN_FLIGHT
+        } else {
+            0
+        };
+
+        // One set of the six queries per frame in flight.
+        let queries = (0..frames_in_flight)
+            .map(|_| {
+                let mut set = [0; 6];
+                for (query, &target) in set.iter_mut().zip(QUERY_TARGETS.iter()) {
+                    unsafe { gl::CreateQueries(target, 1, query) }
+                }
+                set
+            })
+            .collect();
+
+        Self {
+            queries,
+            pending: vec![false; frames_in_flight],
+            frame: 0,
+            active: false,
+            result: PipelineStatisticsResult::default(),
+            enabled: false,
+        }
+    }
+
+    // Reads the oldest frame in flight if it is done and starts counting into its queries.
+    pub fn begin(&mut self) {
+        if !self.enabled || self.active {
+            return;
+        }
+
+        let queries = &self.queries[self.frame];
+
+        if self.pending[self.frame] && Self::is_available(queries[queries.len() - 1]) {
+            let mut values = [0u64; 6];
+            for (value, &query) in values.iter_mut().zip(queries.iter()) {
+                unsafe { gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, value) }
+            }
+
+            self.result = PipelineStatisticsResult {
+                vertices_submitted: values[0],
+                primitives_submitted: values[1],
+                vertex_shader_invocations: values[2],
+                clipping_input_primitives: values[3],
+                clipping_output_primitives: values[4],
+                fragment_shader_invocations: values[5],
+            };
+        }
+
+        for (&target, &query) in QUERY_TARGETS.iter().zip(queries.iter()) {
+            unsafe { gl::BeginQuery(target, query) }
+        }
+
+        self.active = true
+    }
+
+    pub fn end(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        for &target in QUERY_TARGETS.iter() {
+            unsafe { gl::EndQuery(target) }
+        }
+
+        self.pending[self.frame] = true;
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        self.active = false
+    }
+
+    pub fn result(&self) -> &PipelineStatisticsResult {
+        &self.result
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_supported(&self) -> bool {
+        !self.queries.is_empty()
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.is_supported() {
+            log::warn!("Pipeline statistics queries are not supported by the GPU.");
+            return;
+        }
+
+        if !enabled {
+            self.end();
+        }
+
+        self.enabled = enabled
+    }
+
+    fn is_available(query: GLuint) -> bool {
+        let mut available: GLint = 0;
+        unsafe { gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available) }
+
+        available != 0
+    }
+}
+
+impl Default for PipelineStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PipelineStatistics {
+    fn drop(&mut self) {
+        for queries in &self.queries {
+            unsafe { gl::DeleteQueries(queries.len() as i32, queries.as_ptr()) }
+        }
+    }
+}
+
+impl Gui for PipelineStatistics {
+    fn gui(&mut self, ui: &Ui) {
+        let mut enabled = self.enabled;
+
+        ui.group(|| {
+            if ui.checkbox(im_str!("##pipeline_statistics"), &mut enabled) {
+                self.set_enabled(enabled)
+            }
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Pipeline Statistics"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    let result = &self.result;
+                    ui.text(format!("Vertices: {}", result.vertices_submitted));
+                    ui.text(format!("Primitives: {}", result.primitives_submitted));
+                    ui.text(format!(
+                        "Vertex Shader Invocations: {}",
+                        result.vertex_shader_invocations
+                    ));
+                    ui.text(format!(
+                        "Clipping: {} in, {} out",
+                        result.clipping_input_primitives, result.clipping_output_primitives
+                    ));
+                    ui.text(format!(
+                        "Fragment Shader Invocations: {}",
+                        result.fragment_shader_invocations
+                    ));
+
+                    ui.unindent()
+                });
+        });
+    }
+}
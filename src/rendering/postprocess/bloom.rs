@@ -1,51 +1,89 @@
-use crate::core::math::{UVec2, Vec3, Vec4};
+use crate::core::math::{UVec2, Vec4};
 use crate::imgui::{im_str, Gui, Ui};
-use crate::rendering::buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags};
-use crate::rendering::framebuffer::{Framebuffer, TemporaryFramebufferPool};
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::Framebuffer;
 use crate::rendering::postprocess::{
-    AsAny, AsAnyMut, PostprocessingEffect, FULLSCREEN_VERTEX_SHADER,
+    fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut, PostprocessingEffect,
+};
+use crate::rendering::sampler::{
+    Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode,
+};
+use crate::rendering::state::{
+    BlendState, DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
 };
-use crate::rendering::program_pipeline::ProgramPipeline;
-use crate::rendering::shader::{Shader, ShaderStage};
 use crate::Context;
 use std::any::Any;
 use std::ops::RangeInclusive;
-use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-const UBO_BINDING_INDEX: u32 = 0;
-const EPSILON: f32 = 0.00001;
 const MIN_ITERATIONS: u32 = 1;
-const MAX_ITERATIONS: u32 = 16;
-const MIN_THRESHOLD: f32 = 0.1;
-const MAX_THRESHOLD: f32 = 10.0;
-const MIN_SMOOTH_FADE: f32 = 0.1;
-const MAX_SMOOTH_FADE: f32 = 1.0;
-const MIN_INTENSITY: f32 = 0.1;
-const MAX_INTENSITY: f32 = 10.0;
-
-#[repr(C)]
-#[derive(Default, Debug)]
-struct BloomUboData {
-    filter: Vec4,
-    intensity: f32,
-    _pad: Vec3,
-}
-
+const MAX_ITERATIONS: u32 = 10;
+const MIN_INTENSITY: f32 = 0.0;
+const MAX_INTENSITY: f32 = 1.0;
+const MIN_SCATTER: f32 = 0.0;
+const MAX_SCATTER: f32 = 1.0;
+const MIN_FILTER_RADIUS: f32 = 0.5;
+const MAX_FILTER_RADIUS: f32 = 4.0;
+
+// Threshold free bloom from Next Generation Post Processing in Call of Duty: Advanced Warfare.
+// The HDR color target is progressively downsampled and upsampled back, then blended over the
+// input in place so the tone mapper that follows picks it up.
 pub struct Bloom {
     iterations: u32,
-    threshold: f32,
-    smooth_fade: f32,
+    // Fraction of the final image that comes from the bloom.
     intensity: f32,
-    v_blur_program_pipeline: ProgramPipeline,
-    h_blur_program_pipeline: ProgramPipeline,
-    ubo_data: BloomUboData,
-    ubo: Buffer,
+    // How much of the light spreads into the wider, lower resolution mips.
+    scatter: f32,
+    // Tent filter radius of the upsample in texels of the lower mip.
+    filter_radius: f32,
+    downsample_pass: FullscreenPass,
+    upsample_pass: FullscreenPass,
+    composite_pass: FullscreenPass,
+    sampler_linear: Sampler,
     enabled: bool,
 }
 
 impl_as_any!(Bloom);
 
+impl Bloom {
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn scatter(&self) -> f32 {
+        self.scatter
+    }
+
+    pub fn filter_radius(&self) -> f32 {
+        self.filter_radius
+    }
+
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations.max(MIN_ITERATIONS).min(MAX_ITERATIONS)
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.max(MIN_INTENSITY).min(MAX_INTENSITY)
+    }
+
+    pub fn set_scatter(&mut self, scatter: f32) {
+        self.scatter = scatter.max(MIN_SCATTER).min(MAX_SCATTER)
+    }
+
+    pub fn set_filter_radius(&mut self, filter_radius: f32) {
+        self.filter_radius = filter_radius
+    }
+
+    fn set_viewport(framebuffer: &Framebuffer) {
+        let size = framebuffer.size();
+        StateManager::set_viewport(0, 0, size.x as i32, size.y as i32)
+    }
+}
+
 impl PostprocessingEffect for Bloom {
     fn name(&self) -> &str {
         "bloom"
@@ -67,9 +105,11 @@ impl PostprocessingEffect for Bloom {
         let Context {
             framebuffer_cache, ..
         } = context;
-        //TODO: Implement me
 
-        let attachment = input.texture_attachment(0);
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
 
         assert_eq!(
             attachment.is_depth_stencil(),
@@ -77,52 +117,140 @@ impl PostprocessingEffect for Bloom {
             "Bloom effect do not support depth texture attachments."
         );
 
-        let knee = self.threshold * self.smooth_fade;
-        self.ubo_data.filter = Vec4::new(
-            self.threshold,
-            self.threshold - knee,
-            2.0 * knee,
-            0.25 / (knee + EPSILON),
-        );
+        if self.intensity <= 0.0 {
+            return;
+        }
 
-        self.ubo.fill_mapped(0, &self.ubo_data);
+        let format = attachment.format();
 
-        // Blit to half resolution and filter bright pixels.
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        // Downsample chain, starting at half resolution.
         let mut size = UVec2::new(input.size().x / 2, input.size().y / 2);
-        let format = attachment.format();
+        let mut mips: Vec<Rc<Framebuffer>> = Vec::with_capacity(self.iterations as usize);
 
-        let mut current_destination = framebuffer_cache.get_temporary(size, format, None);
+        self.downsample_pass.bind();
 
-        let mut temporaries = Vec::with_capacity(self.iterations as usize);
+        for i in 0..self.iterations {
+            if size.x < 2 || size.y < 2 {
+                break;
+            }
 
-        temporaries.push(Rc::clone(&current_destination));
+            let destination = framebuffer_cache.get_temporary(size, format, None);
+            let source = mips
+                .last()
+                .map_or(attachment.id(), |mip| mip.texture_attachments()[0].id());
 
-        // TODO: Do a Box downsample blit here and filter brights
+            destination.bind();
+            Self::set_viewport(&destination);
 
-        let mut current_source = Rc::clone(&current_destination);
+            self.downsample_pass
+                .pipeline()
+                .set_int_all_stages("karisAverage", (i == 0) as i32);
+            self.downsample_pass
+                .set_texture("image", source, &self.sampler_linear)
+                .draw();
+
+            destination.unbind(false);
+            mips.push(destination);
 
-        for _ in 1..self.iterations {
             size.x /= 2;
             size.y /= 2;
+        }
 
-            if size.y < 2 {
-                break;
-            }
+        self.downsample_pass.unbind();
 
-            current_destination = framebuffer_cache.get_temporary(size, format, None);
-            temporaries.push(Rc::clone(&current_destination));
+        if mips.is_empty() {
+            StateManager::apply(&FixedFunctionState::default());
+            return;
+        }
 
-            //TODO: Do a downsample blit here
-            current_source = Rc::clone(&current_destination);
+        // Upsample chain. Each level mixes its downsampled image with the tent filtered level
+        // below it, so the last mip of the chain is used as is.
+        let mut upsampled = Rc::clone(mips.last().unwrap());
+
+        self.upsample_pass.bind();
+        self.upsample_pass
+            .pipeline()
+            .set_float_all_stages("scatter", self.scatter);
+        self.upsample_pass
+            .pipeline()
+            .set_float_all_stages("filterRadius", self.filter_radius);
+
+        for current in mips.iter().rev().skip(1) {
+            let destination = framebuffer_cache.get_temporary(current.size(), format, None);
+
+            destination.bind();
+            Self::set_viewport(&destination);
+
+            self.upsample_pass
+                .set_texture(
+                    "image",
+                    upsampled.texture_attachments()[0].id(),
+                    &self.sampler_linear,
+                )
+                .set_texture(
+                    "current",
+                    current.texture_attachments()[0].id(),
+                    &self.sampler_linear,
+                )
+                .draw();
+
+            destination.unbind(false);
+            upsampled = destination;
         }
 
-        temporaries.into_iter().rev().skip(1).for_each(|temporary| {
-            current_destination = temporary;
-            //TODO: Do an upsampling blit here
-            current_source = Rc::clone(&current_destination);
+        self.upsample_pass.unbind();
+
+        // Blend over the first color attachment of the input only.
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: Some(BlendState::alpha_blending()),
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
         });
 
-        //TODO: blit to add bloom tex to input render target
+        input.bind();
+        input.set_draw_buffer_count(Some(1));
+        Self::set_viewport(input);
+
+        self.composite_pass.bind();
+        self.composite_pass
+            .pipeline()
+            .set_float_all_stages("intensity", self.intensity);
+        self.composite_pass
+            .pipeline()
+            .set_float_all_stages("filterRadius", self.filter_radius);
+        self.composite_pass
+            .set_texture(
+                "image",
+                upsampled.texture_attachments()[0].id(),
+                &self.sampler_linear,
+            )
+            .draw();
+        self.composite_pass.unbind();
+
+        input.set_draw_buffer_count(None);
+        input.unbind(false);
+
+        StateManager::apply(&FixedFunctionState::default())
     }
 }
 
@@ -141,18 +269,18 @@ impl Gui for Bloom {
                     imgui::Slider::new(im_str!("Iterations"))
                         .range(RangeInclusive::new(MIN_ITERATIONS, MAX_ITERATIONS))
                         .build(&ui, &mut self.iterations);
-                    imgui::Slider::new(im_str!("Threshold"))
-                        .range(RangeInclusive::new(MIN_THRESHOLD, MAX_THRESHOLD))
-                        .display_format(im_str!("%.2f"))
-                        .build(&ui, &mut self.threshold);
-                    imgui::Slider::new(im_str!("Smooth Fade"))
-                        .range(RangeInclusive::new(MIN_SMOOTH_FADE, MAX_SMOOTH_FADE))
-                        .display_format(im_str!("%.2f"))
-                        .build(&ui, &mut self.smooth_fade);
                     imgui::Slider::new(im_str!("Intensity"))
                         .range(RangeInclusive::new(MIN_INTENSITY, MAX_INTENSITY))
-                        .display_format(im_str!("%.2f"))
+                        .display_format(im_str!("%.3f"))
                         .build(&ui, &mut self.intensity);
+                    imgui::Slider::new(im_str!("Scatter"))
+                        .range(RangeInclusive::new(MIN_SCATTER, MAX_SCATTER))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.scatter);
+                    imgui::Slider::new(im_str!("Filter Radius"))
+                        .range(RangeInclusive::new(MIN_FILTER_RADIUS, MAX_FILTER_RADIUS))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.filter_radius);
                     ui.unindent()
                 });
         });
@@ -160,22 +288,20 @@ impl Gui for Bloom {
 }
 
 pub struct BloomBuilder {
-    assets_path: PathBuf,
     iterations: u32,
-    threshold: f32,
-    smooth_fade: f32,
     intensity: f32,
+    scatter: f32,
+    filter_radius: f32,
     enabled: bool,
 }
 
 impl BloomBuilder {
-    pub fn new<P: AsRef<Path>>(assets_path: P) -> Self {
+    pub fn new() -> Self {
         Self {
-            assets_path: PathBuf::from(assets_path.as_ref()),
-            iterations: 5,
-            threshold: 1.0,
-            smooth_fade: 0.5,
-            intensity: 1.0,
+            iterations: 6,
+            intensity: 0.04,
+            scatter: 0.7,
+            filter_radius: 1.0,
             enabled: true,
         }
     }
@@ -185,18 +311,18 @@ impl BloomBuilder {
         self
     }
 
-    pub fn threshold(mut self, threshold: f32) -> Self {
-        self.threshold = threshold;
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
         self
     }
 
-    pub fn smooth_fade(mut self, smooth_fade: f32) -> Self {
-        self.smooth_fade = smooth_fade;
+    pub fn scatter(mut self, scatter: f32) -> Self {
+        self.scatter = scatter;
         self
     }
 
-    pub fn intensity(mut self, intensity: f32) -> Self {
-        self.intensity = intensity;
+    pub fn filter_radius(mut self, filter_radius: f32) -> Self {
+        self.filter_radius = filter_radius;
         self
     }
 
@@ -205,54 +331,40 @@ impl BloomBuilder {
         self
     }
 
-    pub fn build(self) -> Bloom {
-        let (v_blur_program_pipeline, h_blur_program_pipeline) = {
-            let v_blur_fs = Shader::new(
-                ShaderStage::Fragment,
-                self.assets_path.join("sdr/gaussian_blur_vertical.frag"),
-            )
-            .unwrap();
-
-            let h_blur_fs = Shader::new(
-                ShaderStage::Fragment,
-                self.assets_path.join("sdr/gaussian_blur_horizontal.frag"),
-            )
-            .unwrap();
-
-            let v_blur_pipeline = ProgramPipeline::new()
-                .add_shader(&FULLSCREEN_VERTEX_SHADER)
-                .add_shader(&v_blur_fs)
-                .build()
-                .unwrap();
-
-            let h_blur_pipeline = ProgramPipeline::new()
-                .add_shader(&FULLSCREEN_VERTEX_SHADER)
-                .add_shader(&h_blur_fs)
-                .build()
-                .unwrap();
+    pub fn build(self) -> Result<Bloom, RendererError> {
+        let downsample_pass =
+            FullscreenPass::new("src/rendering/postprocess/shaders/bloom_downsample.frag")?;
+        let upsample_pass =
+            FullscreenPass::new("src/rendering/postprocess/shaders/bloom_upsample.frag")?;
+        let composite_pass =
+            FullscreenPass::new("src/rendering/postprocess/shaders/bloom_composite.frag")?;
+
+        let sampler_linear = Sampler::new(
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::None,
+        );
 
-            (v_blur_pipeline, h_blur_pipeline)
+        let mut bloom = Bloom {
+            iterations: 0,
+            intensity: 0.0,
+            scatter: 0.0,
+            filter_radius: self.filter_radius,
+            downsample_pass,
+            upsample_pass,
+            composite_pass,
+            sampler_linear,
+            enabled: self.enabled,
         };
 
-        let mut ubo = Buffer::new(
-            "Bloom UBO",
-            std::mem::size_of::<BloomUboData>() as isize,
-            BufferTarget::Uniform,
-            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
-        );
-        ubo.bind(UBO_BINDING_INDEX);
-        ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
-
-        Bloom {
-            iterations: self.iterations,
-            threshold: self.threshold,
-            smooth_fade: self.smooth_fade,
-            intensity: self.intensity,
-            v_blur_program_pipeline,
-            h_blur_program_pipeline,
-            ubo_data: Default::default(),
-            ubo,
-            enabled: self.enabled,
-        }
+        bloom.set_iterations(self.iterations);
+        bloom.set_intensity(self.intensity);
+        bloom.set_scatter(self.scatter);
+
+        Ok(bloom)
     }
 }
@@ -0,0 +1,321 @@
+use crate::core::math::{Vec3, Vec4};
+use crate::imgui::{im_str, Gui, Ui};
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::Framebuffer;
+use crate::rendering::postprocess::{
+    fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut, PostprocessingEffect,
+};
+use crate::rendering::sampler::{
+    Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode,
+};
+use crate::rendering::state::{
+    DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
+};
+use crate::Context;
+use gl_bindings as gl;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrainSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+    // Grain size in pixels.
+    pub size: f32,
+    // How much the grain fades out in bright areas, 0 is uniform grain.
+    pub luminance_response: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    // Linear color the corners fade to.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub smoothness: f32,
+    // 1 is a circle, lower values follow the aspect ratio of the screen.
+    pub roundness: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChromaticAberrationSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+}
+
+// Parameters of the post processing a scene is authored with, saved along with the scene file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostprocessingSettings {
+    pub grain: GrainSettings,
+    pub vignette: VignetteSettings,
+    pub chromatic_aberration: ChromaticAberrationSettings,
+}
+
+impl Default for GrainSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 0.15,
+            size: 1.5,
+            luminance_response: 0.8,
+        }
+    }
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: [0.0, 0.0, 0.0],
+            intensity: 0.45,
+            smoothness: 0.2,
+            roundness: 1.0,
+        }
+    }
+}
+
+impl Default for ChromaticAberrationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 0.5,
+        }
+    }
+}
+
+// Film grain, vignette and chromatic aberration in a single pass over the HDR image. Add it
+// right before the tone mapper so the other effects work on the clean image.
+pub struct CameraImperfections {
+    settings: PostprocessingSettings,
+    pass: FullscreenPass,
+    sampler_linear: Sampler,
+    enabled: bool,
+}
+
+impl_as_any!(CameraImperfections);
+
+impl CameraImperfections {
+    pub fn new(settings: PostprocessingSettings) -> Result<Self, RendererError> {
+        let pass =
+            FullscreenPass::new("src/rendering/postprocess/shaders/camera_imperfections.frag")?;
+
+        let sampler_linear = Sampler::new(
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::None,
+        );
+
+        Ok(Self {
+            settings,
+            pass,
+            sampler_linear,
+            enabled: true,
+        })
+    }
+
+    pub fn settings(&self) -> &PostprocessingSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut PostprocessingSettings {
+        &mut self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: PostprocessingSettings) {
+        self.settings = settings
+    }
+}
+
+impl PostprocessingEffect for CameraImperfections {
+    fn name(&self) -> &str {
+        "camera_imperfections"
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(&mut self, input: &Framebuffer, context: Context) {
+        let Context {
+            framebuffer_cache,
+            timer,
+            ..
+        } = context;
+
+        let PostprocessingSettings {
+            grain,
+            vignette,
+            chromatic_aberration,
+        } = &self.settings;
+
+        if !grain.enabled && !vignette.enabled && !chromatic_aberration.enabled {
+            return;
+        }
+
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        assert_eq!(
+            attachment.is_depth_stencil(),
+            false,
+            "Camera imperfections do not support depth texture attachments."
+        );
+
+        let size = input.size();
+        let temporary = framebuffer_cache.get_temporary(size, attachment.format(), None);
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        temporary.bind();
+        StateManager::set_viewport(0, 0, size.x as i32, size.y as i32);
+
+        self.pass.bind();
+        self.pass
+            .pipeline()
+            .set_int_all_stages("chromaticAberration", chromatic_aberration.enabled as i32)
+            .set_float_all_stages(
+                "chromaticAberrationIntensity",
+                chromatic_aberration.intensity,
+            )
+            .set_int_all_stages("vignette", vignette.enabled as i32)
+            .set_vec3_all_stages("vignetteColor", &Vec3::from(vignette.color))
+            .set_float_all_stages("vignetteIntensity", vignette.intensity)
+            .set_float_all_stages("vignetteSmoothness", vignette.smoothness)
+            .set_float_all_stages("vignetteRoundness", vignette.roundness)
+            .set_float_all_stages("aspect", size.x as f32 / size.y.max(1) as f32)
+            .set_int_all_stages("grain", grain.enabled as i32)
+            .set_float_all_stages("grainIntensity", grain.intensity)
+            .set_float_all_stages("grainSize", grain.size.max(1.0))
+            .set_float_all_stages("grainResponse", grain.luminance_response)
+            .set_float_all_stages("time", timer.get_elapsed_time());
+        self.pass
+            .set_texture("image", attachment.id(), &self.sampler_linear)
+            .draw();
+        self.pass.unbind();
+
+        temporary.unbind(false);
+
+        unsafe {
+            gl::CopyImageSubData(
+                temporary.texture_attachments()[0].id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                attachment.id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                size.x as i32,
+                size.y as i32,
+                1,
+            );
+        }
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+}
+
+impl Gui for CameraImperfections {
+    fn gui(&mut self, ui: &Ui) {
+        let enabled = &mut self.enabled;
+        let PostprocessingSettings {
+            grain,
+            vignette,
+            chromatic_aberration,
+        } = &mut self.settings;
+
+        ui.group(|| {
+            ui.checkbox(im_str!("##camera_imperfections"), enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Camera Imperfections"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    ui.checkbox(
+                        im_str!("Chromatic Aberration"),
+                        &mut chromatic_aberration.enabled,
+                    );
+                    if chromatic_aberration.enabled {
+                        imgui::Slider::new(im_str!("Intensity##chromatic_aberration"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut chromatic_aberration.intensity);
+                    }
+
+                    ui.checkbox(im_str!("Vignette"), &mut vignette.enabled);
+                    if vignette.enabled {
+                        imgui::ColorEdit::new(im_str!("Color##vignette"), &mut vignette.color)
+                            .build(&ui);
+                        imgui::Slider::new(im_str!("Intensity##vignette"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut vignette.intensity);
+                        imgui::Slider::new(im_str!("Smoothness##vignette"))
+                            .range(RangeInclusive::new(0.01, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut vignette.smoothness);
+                        imgui::Slider::new(im_str!("Roundness##vignette"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut vignette.roundness);
+                    }
+
+                    ui.checkbox(im_str!("Grain"), &mut grain.enabled);
+                    if grain.enabled {
+                        imgui::Slider::new(im_str!("Intensity##grain"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut grain.intensity);
+                        imgui::Slider::new(im_str!("Size##grain"))
+                            .range(RangeInclusive::new(1.0, 4.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut grain.size);
+                        imgui::Slider::new(im_str!("Luminance Response##grain"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut grain.luminance_response);
+                    }
+
+                    ui.unindent()
+                });
+        });
+    }
+}
@@ -0,0 +1,308 @@
+use crate::core::math::{UVec2, Vec2, Vec4};
+use crate::imgui::{im_str, Gui, Ui};
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::Framebuffer;
+use crate::rendering::postprocess::{
+    fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut, PostprocessingEffect,
+};
+use crate::rendering::sampler::{
+    Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode,
+};
+use crate::rendering::state::{
+    DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
+};
+use crate::Context;
+use std::any::Any;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsrQuality {
+    UltraQuality,
+    Quality,
+    Balanced,
+    Performance,
+}
+
+impl FsrQuality {
+    // Render size relative to the output size, per axis, as in the FSR 1.0 presets.
+    pub fn render_scale(self) -> f32 {
+        match self {
+            FsrQuality::UltraQuality => 1.0 / 1.3,
+            FsrQuality::Quality => 1.0 / 1.5,
+            FsrQuality::Balanced => 1.0 / 1.7,
+            FsrQuality::Performance => 1.0 / 2.0,
+        }
+    }
+}
+
+// AMD FidelityFX Super Resolution 1.0. Upsamples an image rendered below the window size with
+// the edge adaptive EASU pass and sharpens it with RCAS, for dynamic resolution. Scenes render
+// at render_size and the effects after this one work at the window size. Images already at the
+// window size are only sharpened. Only the first color attachment is passed on. Belongs after
+// the anti aliasing and before the tone mapper.
+pub struct Fsr {
+    render_scale: f32,
+    // In stops, 0 is the strongest.
+    sharpness: f32,
+    easu: FullscreenPass,
+    rcas: FullscreenPass,
+    sampler_nearest: Sampler,
+    // Until the stack takes it. Temporary framebuffers return to the cache once released.
+    output: Option<Rc<Framebuffer>>,
+    enabled: bool,
+}
+
+impl_as_any!(Fsr);
+
+impl Fsr {
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale = render_scale.max(0.25).min(1.0)
+    }
+
+    pub fn set_quality(&mut self, quality: FsrQuality) {
+        self.set_render_scale(quality.render_scale())
+    }
+
+    pub fn sharpness(&self) -> f32 {
+        self.sharpness
+    }
+
+    pub fn set_sharpness(&mut self, sharpness: f32) {
+        self.sharpness = sharpness.max(0.0).min(2.0)
+    }
+
+    // The size to render the scene at for the output size. The full output size while disabled.
+    pub fn render_size(&self, output_size: UVec2) -> UVec2 {
+        if !self.enabled {
+            return output_size;
+        }
+
+        UVec2::new(
+            ((output_size.x as f32 * self.render_scale).round() as u32).max(1),
+            ((output_size.y as f32 * self.render_scale).round() as u32).max(1),
+        )
+    }
+}
+
+impl PostprocessingEffect for Fsr {
+    fn name(&self) -> &str {
+        "fsr"
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(&mut self, input: &Framebuffer, context: Context) {
+        let output_size = context.window_size();
+        let Context {
+            framebuffer_cache, ..
+        } = context;
+
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        assert_eq!(
+            attachment.is_depth_stencil(),
+            false,
+            "FSR does not support depth texture attachments."
+        );
+        assert!(input.samples() <= 1, "FSR expects a resolved framebuffer.");
+
+        let input_size = input.size();
+        let upsample = input_size != output_size;
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        let upsampled = if upsample {
+            let upsampled = framebuffer_cache.get_temporary(output_size, attachment.format(), None);
+
+            upsampled.bind();
+
+            self.easu.bind();
+            self.easu
+                .pipeline()
+                .set_vec2_all_stages(
+                    "inputSize",
+                    &Vec2::new(input_size.x as f32, input_size.y as f32),
+                )
+                .set_vec2_all_stages(
+                    "outputSize",
+                    &Vec2::new(output_size.x as f32, output_size.y as f32),
+                );
+            self.easu
+                .set_texture("image", attachment.id(), &self.sampler_nearest)
+                .draw();
+            self.easu.unbind();
+
+            upsampled.unbind(false);
+
+            Some(upsampled)
+        } else {
+            None
+        };
+
+        let output = framebuffer_cache.get_temporary(output_size, attachment.format(), None);
+
+        output.bind();
+
+        self.rcas.bind();
+        self.rcas
+            .pipeline()
+            .set_float_all_stages("sharpness", 2.0f32.powf(-self.sharpness))
+            .set_int_all_stages("compressed", upsample as i32);
+        self.rcas
+            .set_texture(
+                "image",
+                upsampled.as_ref().map_or(attachment.id(), |upsampled| {
+                    upsampled.texture_attachments()[0].id()
+                }),
+                &self.sampler_nearest,
+            )
+            .draw();
+        self.rcas.unbind();
+
+        output.unbind(false);
+
+        self.output = Some(output);
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    fn take_output(&mut self) -> Option<Rc<Framebuffer>> {
+        self.output.take()
+    }
+}
+
+impl Gui for Fsr {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            ui.checkbox(im_str!("##fsr"), &mut self.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("FSR 1.0"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+                    let mut preset = 0;
+                    if imgui::ComboBox::new(im_str!("Preset")).build_simple_string(
+                        &ui,
+                        &mut preset,
+                        &[
+                            im_str!("Custom"),
+                            im_str!("Ultra Quality"),
+                            im_str!("Quality"),
+                            im_str!("Balanced"),
+                            im_str!("Performance"),
+                        ],
+                    ) {
+                        match preset {
+                            1 => self.set_quality(FsrQuality::UltraQuality),
+                            2 => self.set_quality(FsrQuality::Quality),
+                            3 => self.set_quality(FsrQuality::Balanced),
+                            4 => self.set_quality(FsrQuality::Performance),
+                            _ => {}
+                        }
+                    }
+                    imgui::Slider::new(im_str!("Render Scale"))
+                        .range(RangeInclusive::new(0.25, 1.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.render_scale);
+                    imgui::Slider::new(im_str!("Sharpness (stops)"))
+                        .range(RangeInclusive::new(0.0, 2.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.sharpness);
+                    ui.unindent()
+                });
+        });
+    }
+}
+
+pub struct FsrBuilder {
+    quality: FsrQuality,
+    sharpness: f32,
+    enabled: bool,
+}
+
+impl FsrBuilder {
+    pub fn new() -> Self {
+        Self {
+            quality: FsrQuality::Quality,
+            sharpness: 0.2,
+            enabled: true,
+        }
+    }
+
+    pub fn quality(mut self, quality: FsrQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn sharpness(mut self, sharpness: f32) -> Self {
+        self.sharpness = sharpness;
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<Fsr, RendererError> {
+        let easu = FullscreenPass::new("src/rendering/postprocess/shaders/fsr_easu.frag")?;
+        let rcas = FullscreenPass::new("src/rendering/postprocess/shaders/fsr_rcas.frag")?;
+
+        let sampler_nearest = Sampler::new(
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::None,
+        );
+
+        let mut fsr = Fsr {
+            render_scale: 1.0,
+            sharpness: 0.0,
+            easu,
+            rcas,
+            sampler_nearest,
+            output: None,
+            enabled: self.enabled,
+        };
+
+        fsr.set_quality(self.quality);
+        fsr.set_sharpness(self.sharpness);
+
+        Ok(fsr)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::core::asset::embedded::EmbeddedAssets;
+use crate::rendering::{
+    error::RendererError,
+    mesh::FULLSCREEN_MESH,
+    postprocess::fullscreen_vertex_shader,
+    program_pipeline::ProgramPipeline,
+    sampler::Sampler,
+    shader::{Shader, ShaderStage},
+    state::{FrontFace, StateManager},
+    Draw,
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::{fmt::Debug, path::Path};
+
+// A fragment shader drawn over the whole render target with the shared fullscreen triangle.
+pub struct FullscreenPass {
+    pipeline: ProgramPipeline,
+}
+
+impl FullscreenPass {
+    pub fn new<P: AsRef<Path> + Debug>(fragment_shader_path: P) -> Result<Self, RendererError> {
+        let fragment_shader =
+            EmbeddedAssets::load_shader(ShaderStage::Fragment, fragment_shader_path)?;
+
+        Self::from_shader(&fragment_shader)
+    }
+
+    pub fn from_shader(fragment_shader: &Shader) -> Result<Self, RendererError> {
+        let vertex_shader = fullscreen_vertex_shader()?;
+
+        let pipeline = ProgramPipeline::new()
+            .add_shader(&vertex_shader)
+            .add_shader(fragment_shader)
+            .build()?;
+
+        Ok(Self { pipeline })
+    }
+
+    pub fn pipeline(&self) -> &ProgramPipeline {
+        &self.pipeline
+    }
+
+    pub fn bind(&self) -> &Self {
+        self.pipeline.bind();
+        self
+    }
+
+    pub fn unbind(&self) {
+        self.pipeline.unbind()
+    }
+
+    // Binds the texture to the unit of the named sampler uniform of the fragment shader.
+    pub fn set_texture(&self, name: &str, texture_id: GLuint, sampler: &Sampler) -> &Self {
+        match self.pipeline.texture_unit(name) {
+            Some(unit) => {
+                self.pipeline
+                    .set_texture_2d_with_id(unit, texture_id, sampler);
+            }
+            None => log::warn!("Sampler '{}' does not exist in the fullscreen pass.", name),
+        }
+
+        self
+    }
+
+    // Draws with the pipeline bound by bind().
+    pub fn draw(&self) {
+        // The fullscreen triangle is wound clockwise.
+        StateManager::set_front_face(FrontFace::Clockwise);
+        FULLSCREEN_MESH.draw();
+        StateManager::set_front_face(FrontFace::CounterClockwise);
+    }
+
+    // Binds, draws and unbinds in one go for passes without extra state.
+    pub fn execute(&self) {
+        self.bind();
+        self.draw();
+        self.unbind()
+    }
+}
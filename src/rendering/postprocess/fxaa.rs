@@ -0,0 +1,272 @@
+use crate::core::math::Vec4;
+use crate::imgui::{im_str, Gui, Ui};
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::Framebuffer;
+use crate::rendering::postprocess::{
+    fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut, PostprocessingEffect,
+};
+use crate::rendering::sampler::{
+    Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode,
+};
+use crate::rendering::state::{
+    DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
+};
+use crate::Context;
+use gl_bindings as gl;
+use std::any::Any;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FxaaQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl FxaaQuality {
+    // Subpixel, edge threshold and minimum edge threshold, as in the FXAA 3.11 presets.
+    fn settings(self) -> (f32, f32, f32) {
+        match self {
+            FxaaQuality::Low => (0.5, 0.25, 0.0833),
+            FxaaQuality::Medium => (0.75, 0.166, 0.0833),
+            FxaaQuality::High => (0.75, 0.125, 0.0625),
+        }
+    }
+}
+
+// Cheap post process anti aliasing for when MSAA or temporal anti aliasing are not an option.
+// Runs on the HDR color target in place, so it belongs before the tone mapper.
+pub struct Fxaa {
+    subpixel: f32,
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    pass: FullscreenPass,
+    sampler_linear: Sampler,
+    enabled: bool,
+}
+
+impl_as_any!(Fxaa);
+
+impl Fxaa {
+    pub fn set_quality(&mut self, quality: FxaaQuality) {
+        let (subpixel, edge_threshold, edge_threshold_min) = quality.settings();
+
+        self.subpixel = subpixel;
+        self.edge_threshold = edge_threshold;
+        self.edge_threshold_min = edge_threshold_min;
+    }
+
+    pub fn subpixel(&self) -> f32 {
+        self.subpixel
+    }
+
+    pub fn edge_threshold(&self) -> f32 {
+        self.edge_threshold
+    }
+
+    pub fn edge_threshold_min(&self) -> f32 {
+        self.edge_threshold_min
+    }
+
+    pub fn set_subpixel(&mut self, subpixel: f32) {
+        self.subpixel = subpixel
+    }
+
+    pub fn set_edge_threshold(&mut self, edge_threshold: f32) {
+        self.edge_threshold = edge_threshold
+    }
+
+    pub fn set_edge_threshold_min(&mut self, edge_threshold_min: f32) {
+        self.edge_threshold_min = edge_threshold_min
+    }
+}
+
+impl PostprocessingEffect for Fxaa {
+    fn name(&self) -> &str {
+        "fxaa"
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(&mut self, input: &Framebuffer, context: Context) {
+        let Context {
+            framebuffer_cache, ..
+        } = context;
+
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        assert_eq!(
+            attachment.is_depth_stencil(),
+            false,
+            "FXAA does not support depth texture attachments."
+        );
+        assert!(input.samples() <= 1, "FXAA expects a resolved framebuffer.");
+
+        let size = input.size();
+        let temporary = framebuffer_cache.get_temporary(size, attachment.format(), None);
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        temporary.bind();
+        StateManager::set_viewport(0, 0, size.x as i32, size.y as i32);
+
+        self.pass.bind();
+        self.pass
+            .pipeline()
+            .set_float_all_stages("subpixel", self.subpixel)
+            .set_float_all_stages("edgeThreshold", self.edge_threshold)
+            .set_float_all_stages("edgeThresholdMin", self.edge_threshold_min);
+        self.pass
+            .set_texture("image", attachment.id(), &self.sampler_linear)
+            .draw();
+        self.pass.unbind();
+
+        temporary.unbind(false);
+
+        // The pass can't read and write the same texture, so the result is copied back.
+        unsafe {
+            gl::CopyImageSubData(
+                temporary.texture_attachments()[0].id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                attachment.id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                size.x as i32,
+                size.y as i32,
+                1,
+            );
+        }
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+}
+
+impl Gui for Fxaa {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            ui.checkbox(im_str!("##fxaa"), &mut self.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("FXAA"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+                    let mut preset = 0;
+                    if imgui::ComboBox::new(im_str!("Preset")).build_simple_string(
+                        &ui,
+                        &mut preset,
+                        &[
+                            im_str!("Custom"),
+                            im_str!("Low"),
+                            im_str!("Medium"),
+                            im_str!("High"),
+                        ],
+                    ) {
+                        match preset {
+                            1 => self.set_quality(FxaaQuality::Low),
+                            2 => self.set_quality(FxaaQuality::Medium),
+                            3 => self.set_quality(FxaaQuality::High),
+                            _ => {}
+                        }
+                    }
+                    imgui::Slider::new(im_str!("Subpixel"))
+                        .range(RangeInclusive::new(0.0, 1.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.subpixel);
+                    imgui::Slider::new(im_str!("Edge Threshold"))
+                        .range(RangeInclusive::new(0.063, 0.333))
+                        .display_format(im_str!("%.3f"))
+                        .build(&ui, &mut self.edge_threshold);
+                    imgui::Slider::new(im_str!("Edge Threshold Min"))
+                        .range(RangeInclusive::new(0.0, 0.0833))
+                        .display_format(im_str!("%.4f"))
+                        .build(&ui, &mut self.edge_threshold_min);
+                    ui.unindent()
+                });
+        });
+    }
+}
+
+pub struct FxaaBuilder {
+    quality: FxaaQuality,
+    enabled: bool,
+}
+
+impl FxaaBuilder {
+    pub fn new() -> Self {
+        Self {
+            quality: FxaaQuality::Medium,
+            enabled: true,
+        }
+    }
+
+    pub fn quality(mut self, quality: FxaaQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<Fxaa, RendererError> {
+        let pass = FullscreenPass::new("src/rendering/postprocess/shaders/fxaa.frag")?;
+
+        let sampler_linear = Sampler::new(
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::None,
+        );
+
+        let mut fxaa = Fxaa {
+            subpixel: 0.0,
+            edge_threshold: 0.0,
+            edge_threshold_min: 0.0,
+            pass,
+            sampler_linear,
+            enabled: self.enabled,
+        };
+
+        fxaa.set_quality(self.quality);
+
+        Ok(fxaa)
+    }
+}
@@ -1,19 +1,42 @@
+use crate::core::asset::embedded::EmbeddedAssets;
 use crate::imgui::{im_str, Gui, Ui};
-use crate::rendering::framebuffer::Framebuffer;
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::{Framebuffer, FramebufferAttachment};
 use crate::rendering::shader::{Shader, ShaderStage};
 use crate::{AsAny, AsAnyMut, Context};
+use std::{cell::RefCell, rc::Rc};
 
 pub mod bloom;
+pub mod camera_imperfections;
+pub mod fsr;
+pub mod fullscreen_pass;
+pub mod fxaa;
+pub mod motion_blur;
 pub mod tone_mapper;
 
-lazy_static! {
-    pub static ref FULLSCREEN_VERTEX_SHADER: Shader = {
-        Shader::new(
-            ShaderStage::Vertex,
-            "src/rendering/postprocess/shaders/fullscreen.vert",
-        )
-        .unwrap()
-    };
+thread_local! {
+    // Shared by every fullscreen pass, loaded with the first one.
+    static FULLSCREEN_VERTEX_SHADER: RefCell<Option<Rc<Shader>>> = RefCell::new(None);
+}
+
+pub fn fullscreen_vertex_shader() -> Result<Rc<Shader>, RendererError> {
+    FULLSCREEN_VERTEX_SHADER.with(|shader| {
+        let mut shader = shader.borrow_mut();
+
+        if shader.is_none() {
+            *shader = Some(Rc::new(EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/postprocess/shaders/fullscreen.vert",
+            )?));
+        }
+
+        Ok(Rc::clone(shader.as_ref().unwrap()))
+    })
+}
+
+// Drops the shader of a lost context, the next fullscreen pass loads it again.
+pub(crate) fn release_fullscreen_vertex_shader() {
+    FULLSCREEN_VERTEX_SHADER.with(|shader| shader.borrow_mut().take());
 }
 
 pub trait PostprocessingEffect: Gui + AsAny + AsAnyMut {
@@ -26,6 +49,21 @@ pub trait PostprocessingEffect: Gui + AsAny + AsAnyMut {
     fn enabled(&self) -> bool;
 
     fn apply(&mut self, input: &Framebuffer, context: Context);
+
+    // The framebuffer the effect rendered into when it did not write to its input, e.g. at a
+    // different size. Taken right after apply, the effects after it take it as their input.
+    fn take_output(&mut self) -> Option<Rc<Framebuffer>> {
+        None
+    }
+}
+
+// The color attachment an effect reads from its input. Logs and returns None if the input has
+// none, the effect is skipped then.
+pub(crate) fn input_attachment(input: &Framebuffer, effect: &str) -> Option<FramebufferAttachment> {
+    input
+        .texture_attachment(0)
+        .map_err(|e| log::error!("Skipping {}: {}", effect, e))
+        .ok()
 }
 
 pub struct PostprocessingStack {
@@ -52,15 +90,22 @@ impl PostprocessingStack {
         } = context;
 
         if self.enabled {
-            self.post_effects
+            let mut output: Option<Rc<Framebuffer>> = None;
+
+            for effect in self
+                .post_effects
                 .iter_mut()
                 .filter(|effect| effect.enabled())
-                .for_each(|effect| {
-                    effect.apply(
-                        &input,
-                        Context::new(window, asset_manager, timer, framebuffer_cache, settings),
-                    )
-                });
+            {
+                effect.apply(
+                    output.as_deref().unwrap_or(input),
+                    Context::new(window, asset_manager, timer, framebuffer_cache, settings),
+                );
+
+                if let Some(effect_output) = effect.take_output() {
+                    output = Some(effect_output)
+                }
+            }
         }
     }
 
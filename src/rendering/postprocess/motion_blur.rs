@@ -0,0 +1,517 @@
+use crate::core::asset::embedded::EmbeddedAssets;
+use crate::core::camera::Camera;
+use crate::core::ecs::Entity;
+use crate::core::math::{Mat4, UVec2, Vec2, Vec4};
+use crate::imgui::{im_str, Gui, Ui};
+use crate::rendering::draw_list::DrawList;
+use crate::rendering::error::RendererError;
+use crate::rendering::framebuffer::{AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo};
+use crate::rendering::per_draw::{PerDrawData, PerDrawUniforms};
+use crate::rendering::pipeline_state::{PipelineState, PipelineStateBuilder};
+use crate::rendering::postprocess::{
+    fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut, PostprocessingEffect,
+};
+use crate::rendering::program_pipeline::ProgramPipeline;
+use crate::rendering::sampler::{
+    Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode,
+};
+use crate::rendering::shader::ShaderStage;
+use crate::rendering::state::{
+    DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
+};
+use crate::rendering::texture::SizedTextureFormat;
+use crate::{Context, Msaa};
+use gl_bindings as gl;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+const MIN_SAMPLE_COUNT: i32 = 5;
+const MAX_SAMPLE_COUNT: i32 = 31;
+const MIN_MAX_BLUR_RADIUS: u32 = 4;
+const MAX_MAX_BLUR_RADIUS: u32 = 64;
+// Background of the velocity buffer: no motion and the largest half float depth.
+const VELOCITY_CLEAR: [f32; 4] = [0.0, 0.0, 65504.0, 0.0];
+
+// Camera and per object motion blur with the tile max velocity reconstruction filter of
+// A Reconstruction Filter for Plausible Motion Blur (McGuire et al. 2012).
+//
+// render_velocity writes the screen space motion of every draw since the previous frame, then
+// apply reduces it to the largest velocity per tile and its neighbourhood and gathers along it.
+// Previous transforms are tracked per entity, draws without one only get camera motion.
+pub struct MotionBlur {
+    velocity_framebuffer: Framebuffer,
+    tile_max_framebuffer: Framebuffer,
+    neighbor_max_framebuffer: Framebuffer,
+    velocity_pipeline_state: PipelineState,
+    tile_max_pass: FullscreenPass,
+    neighbor_max_pass: FullscreenPass,
+    reconstruction_pass: FullscreenPass,
+    sampler_nearest: Sampler,
+    sampler_linear: Sampler,
+    previous_view_projection: Option<Mat4>,
+    previous_models: HashMap<Entity, Mat4>,
+    // Fraction of the frame the shutter is open, 0.5 is a 180 degree shutter.
+    shutter_fraction: f32,
+    // Longest blur in pixels, which is also the tile size.
+    max_blur_radius: u32,
+    sample_count: i32,
+    // Whether the camera of the last velocity pass has motion blur on.
+    active: bool,
+    enabled: bool,
+}
+
+impl_as_any!(MotionBlur);
+
+impl MotionBlur {
+    // Keeps the current framebuffers if the new ones can't be created.
+    pub fn resize(&mut self, size: UVec2) -> Result<(), RendererError> {
+        if size == self.velocity_framebuffer.size() {
+            return Ok(());
+        }
+
+        let velocity_framebuffer = Self::create_velocity_framebuffer(size)?;
+        let tile_max_framebuffer = Self::create_tile_framebuffer(size, self.max_blur_radius)?;
+        let neighbor_max_framebuffer = Self::create_tile_framebuffer(size, self.max_blur_radius)?;
+
+        self.velocity_framebuffer = velocity_framebuffer;
+        self.tile_max_framebuffer = tile_max_framebuffer;
+        self.neighbor_max_framebuffer = neighbor_max_framebuffer;
+
+        Ok(())
+    }
+
+    // Renders the velocity buffer of the frame. Call it once per frame before the post
+    // processing stack, with the camera that renders the frame.
+    pub fn render_velocity(
+        &mut self,
+        draw_list: &DrawList,
+        per_draw_uniforms: &mut PerDrawUniforms,
+        camera: &Camera,
+    ) {
+        // The jitter is not motion, so it stays out of the velocities.
+        let view_projection = camera.unjittered_projection_matrix() * camera.transform();
+        let previous_view_projection = self
+            .previous_view_projection
+            .replace(view_projection)
+            .unwrap_or(view_projection);
+
+        self.active = camera.motion_blur();
+
+        if !self.enabled || !self.active {
+            self.previous_models.clear();
+            return;
+        }
+
+        let size = self.velocity_framebuffer.size();
+
+        self.velocity_framebuffer.bind();
+        unsafe {
+            let depth: f32 = 1.0;
+            gl::ClearNamedFramebufferfv(
+                self.velocity_framebuffer.id(),
+                gl::COLOR,
+                0,
+                VELOCITY_CLEAR.as_ptr(),
+            );
+            gl::ClearNamedFramebufferfv(self.velocity_framebuffer.id(), gl::DEPTH, 0, &depth);
+        }
+
+        self.velocity_pipeline_state.bind();
+        self.velocity_pipeline_state
+            .program_pipeline()
+            .set_mat4_all_stages("viewProjection", &view_projection)
+            .set_mat4_all_stages("previousViewProjection", &previous_view_projection)
+            .set_vec2_all_stages("viewportSize", &Vec2::new(size.x as f32, size.y as f32));
+
+        let mut models = HashMap::with_capacity(self.previous_models.len());
+
+        for item in draw_list.items() {
+            if per_draw_uniforms
+                .push_and_bind(&PerDrawData::new(item.model, 0))
+                .is_none()
+            {
+                break;
+            }
+
+            let previous_model = item
+                .entity
+                .and_then(|entity| {
+                    models.insert(entity, item.model);
+                    self.previous_models.get(&entity).cloned()
+                })
+                .unwrap_or(item.model);
+
+            self.velocity_pipeline_state
+                .program_pipeline()
+                .set_mat4_all_stages("previousModel", &previous_model);
+
+            item.mesh
+                .draw_with_primitive_mode(item.material.borrow().primitive_mode());
+        }
+
+        self.velocity_pipeline_state.unbind();
+        self.previous_models = models;
+
+        self.velocity_framebuffer.unbind(false);
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    pub fn shutter_fraction(&self) -> f32 {
+        self.shutter_fraction
+    }
+
+    pub fn max_blur_radius(&self) -> u32 {
+        self.max_blur_radius
+    }
+
+    pub fn sample_count(&self) -> i32 {
+        self.sample_count
+    }
+
+    pub fn set_shutter_fraction(&mut self, shutter_fraction: f32) {
+        self.shutter_fraction = shutter_fraction.max(0.0).min(1.0)
+    }
+
+    // Keeps the current radius if the tile framebuffers for the new one can't be created.
+    pub fn set_max_blur_radius(&mut self, max_blur_radius: u32) -> Result<(), RendererError> {
+        let max_blur_radius = max_blur_radius
+            .max(MIN_MAX_BLUR_RADIUS)
+            .min(MAX_MAX_BLUR_RADIUS);
+
+        if max_blur_radius != self.max_blur_radius {
+            let size = self.velocity_framebuffer.size();
+            let tile_max_framebuffer = Self::create_tile_framebuffer(size, max_blur_radius)?;
+            let neighbor_max_framebuffer = Self::create_tile_framebuffer(size, max_blur_radius)?;
+
+            self.max_blur_radius = max_blur_radius;
+            self.tile_max_framebuffer = tile_max_framebuffer;
+            self.neighbor_max_framebuffer = neighbor_max_framebuffer
+        }
+
+        Ok(())
+    }
+
+    // Odd counts keep the center tap on the pixel itself.
+    pub fn set_sample_count(&mut self, sample_count: i32) {
+        self.sample_count = (sample_count | 1)
+            .max(MIN_SAMPLE_COUNT)
+            .min(MAX_SAMPLE_COUNT)
+    }
+
+    fn set_common_uniforms(&self, pass: &FullscreenPass) {
+        // The filter blurs over the velocity in both directions, hence the half.
+        pass.pipeline()
+            .set_int_all_stages("tileSize", self.max_blur_radius as i32)
+            .set_float_all_stages("velocityScale", 0.5 * self.shutter_fraction)
+            .set_float_all_stages("maxBlurRadius", self.max_blur_radius as f32);
+    }
+
+    fn create_velocity_framebuffer(size: UVec2) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Rgba16f,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth32f,
+                    AttachmentType::Renderbuffer,
+                ),
+            ],
+        )
+        .map_err(RendererError::from)
+    }
+
+    fn create_tile_framebuffer(size: UVec2, tile_size: u32) -> Result<Framebuffer, RendererError> {
+        let tiles = UVec2::new(
+            ((size.x + tile_size - 1) / tile_size).max(1),
+            ((size.y + tile_size - 1) / tile_size).max(1),
+        );
+
+        Framebuffer::new(
+            tiles,
+            Msaa::None,
+            vec![FramebufferAttachmentCreateInfo::new(
+                SizedTextureFormat::Rg16f,
+                AttachmentType::Texture,
+            )],
+        )
+        .map_err(RendererError::from)
+    }
+}
+
+impl PostprocessingEffect for MotionBlur {
+    fn name(&self) -> &str {
+        "motion_blur"
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(&mut self, input: &Framebuffer, context: Context) {
+        let Context {
+            framebuffer_cache, ..
+        } = context;
+
+        if !self.active || self.shutter_fraction <= 0.0 {
+            return;
+        }
+
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        assert_eq!(
+            attachment.is_depth_stencil(),
+            false,
+            "Motion blur does not support depth texture attachments."
+        );
+        assert_eq!(
+            input.size(),
+            self.velocity_framebuffer.size(),
+            "Motion blur must be resized along with its input."
+        );
+
+        let size = input.size();
+        let velocity = self.velocity_framebuffer.texture_attachments()[0];
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        self.tile_max_framebuffer.bind();
+        self.tile_max_pass.bind();
+        self.set_common_uniforms(&self.tile_max_pass);
+        self.tile_max_pass
+            .set_texture("velocityMap", velocity.id(), &self.sampler_nearest)
+            .draw();
+        self.tile_max_pass.unbind();
+        self.tile_max_framebuffer.unbind(false);
+
+        self.neighbor_max_framebuffer.bind();
+        self.neighbor_max_pass.bind();
+        self.neighbor_max_pass
+            .set_texture(
+                "tileMax",
+                self.tile_max_framebuffer.texture_attachments()[0].id(),
+                &self.sampler_nearest,
+            )
+            .draw();
+        self.neighbor_max_pass.unbind();
+        self.neighbor_max_framebuffer.unbind(false);
+
+        let temporary = framebuffer_cache.get_temporary(size, attachment.format(), None);
+
+        temporary.bind();
+        StateManager::set_viewport(0, 0, size.x as i32, size.y as i32);
+
+        self.reconstruction_pass.bind();
+        self.set_common_uniforms(&self.reconstruction_pass);
+        self.reconstruction_pass
+            .pipeline()
+            .set_int_all_stages("sampleCount", self.sample_count);
+        self.reconstruction_pass
+            .set_texture("image", attachment.id(), &self.sampler_linear)
+            .set_texture("velocityMap", velocity.id(), &self.sampler_nearest)
+            .set_texture(
+                "neighborMax",
+                self.neighbor_max_framebuffer.texture_attachments()[0].id(),
+                &self.sampler_nearest,
+            )
+            .draw();
+        self.reconstruction_pass.unbind();
+
+        temporary.unbind(false);
+
+        unsafe {
+            gl::CopyImageSubData(
+                temporary.texture_attachments()[0].id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                attachment.id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                size.x as i32,
+                size.y as i32,
+                1,
+            );
+        }
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+}
+
+impl Gui for MotionBlur {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            ui.checkbox(im_str!("##motion_blur"), &mut self.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Motion Blur"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+                    imgui::Slider::new(im_str!("Shutter Fraction"))
+                        .range(RangeInclusive::new(0.0, 1.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.shutter_fraction);
+
+                    let mut max_blur_radius = self.max_blur_radius;
+                    if imgui::Slider::new(im_str!("Max Blur Radius"))
+                        .range(RangeInclusive::new(
+                            MIN_MAX_BLUR_RADIUS,
+                            MAX_MAX_BLUR_RADIUS,
+                        ))
+                        .build(&ui, &mut max_blur_radius)
+                    {
+                        if let Err(e) = self.set_max_blur_radius(max_blur_radius) {
+                            log::error!("Failed to change the max blur radius: {}", e);
+                        }
+                    }
+
+                    let mut sample_count = self.sample_count;
+                    if imgui::Slider::new(im_str!("Samples"))
+                        .range(RangeInclusive::new(MIN_SAMPLE_COUNT, MAX_SAMPLE_COUNT))
+                        .build(&ui, &mut sample_count)
+                    {
+                        self.set_sample_count(sample_count);
+                    }
+                    ui.unindent()
+                });
+        });
+    }
+}
+
+pub struct MotionBlurBuilder {
+    size: UVec2,
+    shutter_fraction: f32,
+    max_blur_radius: u32,
+    sample_count: i32,
+    enabled: bool,
+}
+
+impl MotionBlurBuilder {
+    // size is the size of the frames the effect is applied to.
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            shutter_fraction: 0.5,
+            max_blur_radius: 20,
+            sample_count: 15,
+            enabled: true,
+        }
+    }
+
+    pub fn shutter_fraction(mut self, shutter_fraction: f32) -> Self {
+        self.shutter_fraction = shutter_fraction;
+        self
+    }
+
+    pub fn max_blur_radius(mut self, max_blur_radius: u32) -> Self {
+        self.max_blur_radius = max_blur_radius;
+        self
+    }
+
+    pub fn sample_count(mut self, sample_count: i32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<MotionBlur, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/velocity.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/velocity.frag",
+            )?)
+            .build()?;
+
+        let sampler = |minification: MinificationFilter, magnification: MagnificationFilter| {
+            Sampler::new(
+                minification,
+                magnification,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            )
+        };
+
+        let max_blur_radius = self
+            .max_blur_radius
+            .max(MIN_MAX_BLUR_RADIUS)
+            .min(MAX_MAX_BLUR_RADIUS);
+
+        let mut motion_blur = MotionBlur {
+            velocity_framebuffer: MotionBlur::create_velocity_framebuffer(self.size)?,
+            tile_max_framebuffer: MotionBlur::create_tile_framebuffer(self.size, max_blur_radius)?,
+            neighbor_max_framebuffer: MotionBlur::create_tile_framebuffer(
+                self.size,
+                max_blur_radius,
+            )?,
+            velocity_pipeline_state: PipelineStateBuilder::new(program_pipeline).build(),
+            tile_max_pass: FullscreenPass::new(
+                "src/rendering/postprocess/shaders/motion_blur_tile_max.frag",
+            )?,
+            neighbor_max_pass: FullscreenPass::new(
+                "src/rendering/postprocess/shaders/motion_blur_neighbor_max.frag",
+            )?,
+            reconstruction_pass: FullscreenPass::new(
+                "src/rendering/postprocess/shaders/motion_blur.frag",
+            )?,
+            sampler_nearest: sampler(MinificationFilter::Nearest, MagnificationFilter::Nearest),
+            sampler_linear: sampler(MinificationFilter::Linear, MagnificationFilter::Linear),
+            previous_view_projection: None,
+            previous_models: HashMap::new(),
+            shutter_fraction: 0.0,
+            max_blur_radius,
+            sample_count: 0,
+            active: false,
+            enabled: self.enabled,
+        };
+
+        motion_blur.set_shutter_fraction(self.shutter_fraction);
+        motion_blur.set_sample_count(self.sample_count);
+
+        Ok(motion_blur)
+    }
+}
@@ -0,0 +1,32 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Blended over the scene as scene * (1 - intensity) + bloom * intensity, so no energy is added.
+layout(binding = 0) uniform sampler2D image;
+
+uniform float intensity;
+uniform float filterRadius;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    vec2 radius = filterRadius / textureSize(image, 0);
+    vec2 uv = fsIn.texcoord;
+
+    vec3 color = texture(image, uv).rgb * 4.0;
+    color += texture(image, uv + radius * vec2(-1.0,  0.0)).rgb * 2.0;
+    color += texture(image, uv + radius * vec2( 1.0,  0.0)).rgb * 2.0;
+    color += texture(image, uv + radius * vec2( 0.0, -1.0)).rgb * 2.0;
+    color += texture(image, uv + radius * vec2( 0.0,  1.0)).rgb * 2.0;
+    color += texture(image, uv + radius * vec2(-1.0, -1.0)).rgb;
+    color += texture(image, uv + radius * vec2( 1.0, -1.0)).rgb;
+    color += texture(image, uv + radius * vec2(-1.0,  1.0)).rgb;
+    color += texture(image, uv + radius * vec2( 1.0,  1.0)).rgb;
+
+    outColor = vec4(color / 16.0, intensity);
+}
@@ -0,0 +1,66 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// 13 tap downsample from Next Generation Post Processing in Call of Duty: Advanced Warfare
+// (Jimenez 2014). The first pass weighs each 2x2 box by its inverse luma (Karis average) to
+// keep fireflies from flickering.
+layout(binding = 0) uniform sampler2D image;
+
+uniform int karisAverage;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+float KarisWeight(vec3 color)
+{
+    return 1.0 / (1.0 + dot(color, vec3(0.2126, 0.7152, 0.0722)));
+}
+
+vec3 Box(vec3 a, vec3 b, vec3 c, vec3 d, float weight)
+{
+    vec3 average = (a + b + c + d) * 0.25;
+    return average * weight * (karisAverage != 0 ? KarisWeight(average) : 1.0);
+}
+
+void main()
+{
+    vec2 texel = 1.0 / textureSize(image, 0);
+    vec2 uv = fsIn.texcoord;
+
+    vec3 a = texture(image, uv + texel * vec2(-2.0,  2.0)).rgb;
+    vec3 b = texture(image, uv + texel * vec2( 0.0,  2.0)).rgb;
+    vec3 c = texture(image, uv + texel * vec2( 2.0,  2.0)).rgb;
+    vec3 d = texture(image, uv + texel * vec2(-2.0,  0.0)).rgb;
+    vec3 e = texture(image, uv).rgb;
+    vec3 f = texture(image, uv + texel * vec2( 2.0,  0.0)).rgb;
+    vec3 g = texture(image, uv + texel * vec2(-2.0, -2.0)).rgb;
+    vec3 h = texture(image, uv + texel * vec2( 0.0, -2.0)).rgb;
+    vec3 i = texture(image, uv + texel * vec2( 2.0, -2.0)).rgb;
+    vec3 j = texture(image, uv + texel * vec2(-1.0,  1.0)).rgb;
+    vec3 k = texture(image, uv + texel * vec2( 1.0,  1.0)).rgb;
+    vec3 l = texture(image, uv + texel * vec2(-1.0, -1.0)).rgb;
+    vec3 m = texture(image, uv + texel * vec2( 1.0, -1.0)).rgb;
+
+    // The center box has half of the weight, the 4 overlapping corner boxes share the rest.
+    vec3 color = Box(j, k, l, m, 0.5)
+               + Box(a, b, d, e, 0.125)
+               + Box(b, c, e, f, 0.125)
+               + Box(d, e, g, h, 0.125)
+               + Box(e, f, h, i, 0.125);
+
+    // Normalize the Karis weights so the average stays energy preserving.
+    if (karisAverage != 0)
+    {
+        float weightSum = KarisWeight((j + k + l + m) * 0.25) * 0.5
+                        + KarisWeight((a + b + d + e) * 0.25) * 0.125
+                        + KarisWeight((b + c + e + f) * 0.25) * 0.125
+                        + KarisWeight((d + e + g + h) * 0.25) * 0.125
+                        + KarisWeight((e + f + h + i) * 0.25) * 0.125;
+        color /= weightSum;
+    }
+
+    outColor = vec4(max(color, 0.0), 1.0);
+}
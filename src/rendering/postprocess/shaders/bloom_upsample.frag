@@ -0,0 +1,41 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Upsamples the lower mip with a 3x3 tent filter and blends it with the current mip. scatter
+// decides how much of the light spreads into the wider, lower resolution mips.
+layout(binding = 0) uniform sampler2D image;
+layout(binding = 1) uniform sampler2D current;
+
+uniform float scatter;
+uniform float filterRadius;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 Tent(sampler2D source, vec2 uv, vec2 radius)
+{
+    vec3 color = texture(source, uv).rgb * 4.0;
+
+    color += texture(source, uv + radius * vec2(-1.0,  0.0)).rgb * 2.0;
+    color += texture(source, uv + radius * vec2( 1.0,  0.0)).rgb * 2.0;
+    color += texture(source, uv + radius * vec2( 0.0, -1.0)).rgb * 2.0;
+    color += texture(source, uv + radius * vec2( 0.0,  1.0)).rgb * 2.0;
+
+    color += texture(source, uv + radius * vec2(-1.0, -1.0)).rgb;
+    color += texture(source, uv + radius * vec2( 1.0, -1.0)).rgb;
+    color += texture(source, uv + radius * vec2(-1.0,  1.0)).rgb;
+    color += texture(source, uv + radius * vec2( 1.0,  1.0)).rgb;
+
+    return color / 16.0;
+}
+
+void main()
+{
+    vec2 radius = filterRadius / textureSize(image, 0);
+    vec3 upsampled = Tent(image, fsIn.texcoord, radius);
+
+    outColor = vec4(mix(texture(current, fsIn.texcoord).rgb, upsampled, scatter), 1.0);
+}
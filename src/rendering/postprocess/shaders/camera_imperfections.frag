@@ -0,0 +1,94 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Lens and film artifacts composited over the HDR image right before tone mapping.
+layout(binding = 0) uniform sampler2D image;
+
+uniform int chromaticAberration;
+uniform float chromaticAberrationIntensity;
+
+uniform int vignette;
+uniform vec3 vignetteColor;
+uniform float vignetteIntensity;
+uniform float vignetteSmoothness;
+uniform float vignetteRoundness;
+uniform float aspect;
+
+uniform int grain;
+uniform float grainIntensity;
+uniform float grainSize;
+uniform float grainResponse;
+uniform float time;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+// Reference: https://www.shadertoy.com/view/4djSRW
+float Hash(vec3 p)
+{
+    p = fract(p * 0.1031);
+    p += dot(p, p.zyx + 31.32);
+    return fract((p.x + p.y) * p.z);
+}
+
+vec3 ChromaticAberration(vec2 uv)
+{
+    // Lateral aberration, growing with the square of the distance to the center.
+    vec2 fromCenter = uv - 0.5;
+    vec2 offset = fromCenter * dot(fromCenter, fromCenter) * chromaticAberrationIntensity * 0.1;
+
+    return vec3(texture(image, uv - offset).r,
+                texture(image, uv).g,
+                texture(image, uv + offset).b);
+}
+
+// The parameterization of the vignette in Unity's post processing stack.
+vec3 Vignette(vec3 color, vec2 uv)
+{
+    float roundness = (1.0 - vignetteRoundness) * 6.0 + vignetteRoundness;
+
+    vec2 d = abs(uv - 0.5) * vignetteIntensity;
+    d.x *= mix(1.0, aspect, vignetteRoundness);
+    d = pow(clamp(d, 0.0, 1.0), vec2(roundness));
+
+    float factor = pow(clamp(1.0 - dot(d, d), 0.0, 1.0), vignetteSmoothness * 5.0);
+
+    return color * mix(vignetteColor, vec3(1.0), factor);
+}
+
+vec3 Grain(vec3 color)
+{
+    // A new pattern every 1 / 24 s, like film running through a projector.
+    vec2 cell = floor(gl_FragCoord.xy / grainSize);
+    float noise = Hash(vec3(cell, floor(time * 24.0))) - 0.5;
+
+    // Less grain in the highlights, the luminance is compressed to keep HDR values in range.
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    float response = 1.0 - grainResponse * luminance / (1.0 + luminance);
+
+    return max(color * (1.0 + noise * grainIntensity * response), 0.0);
+}
+
+void main()
+{
+    vec2 uv = fsIn.texcoord;
+    vec4 source = texture(image, uv);
+    vec3 color = source.rgb;
+
+    if (chromaticAberration != 0) {
+        color = ChromaticAberration(uv);
+    }
+
+    if (vignette != 0) {
+        color = Vignette(color, uv);
+    }
+
+    if (grain != 0) {
+        color = Grain(color);
+    }
+
+    outColor = vec4(color, source.a);
+}
@@ -0,0 +1,160 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// FidelityFX Super Resolution 1.0 edge adaptive spatial upsampling (EASU), the fp32 path.
+// Runs before the tone mapper, so the taps are compressed with a reversible tone map and the
+// result is left compressed for the sharpening pass to expand.
+// Reference: https://github.com/GPUOpen-Effects/FidelityFX-FSR/blob/master/ffx-fsr/ffx_fsr1.h
+layout(binding = 0) uniform sampler2D image;
+
+// In pixels.
+uniform vec2 inputSize;
+uniform vec2 outputSize;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 Compress(vec3 color)
+{
+    return color / (1.0 + max(max(color.r, color.g), color.b));
+}
+
+vec3 Fetch(ivec2 position)
+{
+    return Compress(texelFetch(image, clamp(position, ivec2(0), ivec2(inputSize) - 1), 0).rgb);
+}
+
+// Luma times 2, as in the reference.
+float Luma(vec3 color)
+{
+    return color.b * 0.5 + (color.r * 0.5 + color.g);
+}
+
+// Accumulates the direction and length of the gradient around c, weighted by the bilinear
+// weight of c.
+//    a
+//  b c d
+//    e
+void SetDirection(inout vec2 dir, inout float len, float w, float a, float b, float c, float d, float e)
+{
+    float dc = d - c;
+    float cb = c - b;
+    float lenX = max(abs(dc), abs(cb));
+    lenX = 1.0 / max(lenX, 1.0e-5);
+    float dirX = d - b;
+    dir.x += dirX * w;
+    lenX = clamp(abs(dirX) * lenX, 0.0, 1.0);
+    lenX *= lenX;
+    len += lenX * w;
+
+    float ec = e - c;
+    float ca = c - a;
+    float lenY = max(abs(ec), abs(ca));
+    lenY = 1.0 / max(lenY, 1.0e-5);
+    float dirY = e - a;
+    dir.y += dirY * w;
+    lenY = clamp(abs(dirY) * lenY, 0.0, 1.0);
+    lenY *= lenY;
+    len += lenY * w;
+}
+
+// Adds a tap of the approximated, direction stretched lanczos2 kernel.
+void Tap(inout vec3 aC, inout float aW, vec2 offset, vec2 dir, vec2 len, float lob, float clp, vec3 c)
+{
+    vec2 v = vec2(offset.x * dir.x + offset.y * dir.y, offset.x * -dir.y + offset.y * dir.x);
+    v *= len;
+
+    float d2 = min(v.x * v.x + v.y * v.y, clp);
+
+    float wB = 2.0 / 5.0 * d2 - 1.0;
+    float wA = lob * d2 - 1.0;
+    wB *= wB;
+    wA *= wA;
+    wB = 25.0 / 16.0 * wB - (25.0 / 16.0 - 1.0);
+
+    float w = wB * wA;
+    aC += c * w;
+    aW += w;
+}
+
+void main()
+{
+    vec2 pp = gl_FragCoord.xy * (inputSize / outputSize) - 0.5;
+    vec2 fp = floor(pp);
+    pp -= fp;
+    ivec2 p = ivec2(fp);
+
+    // The 12 taps around the output pixel.
+    //    b c
+    //  e f g h
+    //  i j k l
+    //    n o
+    vec3 b = Fetch(p + ivec2(0, -1));
+    vec3 c = Fetch(p + ivec2(1, -1));
+    vec3 e = Fetch(p + ivec2(-1, 0));
+    vec3 f = Fetch(p);
+    vec3 g = Fetch(p + ivec2(1, 0));
+    vec3 h = Fetch(p + ivec2(2, 0));
+    vec3 i = Fetch(p + ivec2(-1, 1));
+    vec3 j = Fetch(p + ivec2(0, 1));
+    vec3 k = Fetch(p + ivec2(1, 1));
+    vec3 l = Fetch(p + ivec2(2, 1));
+    vec3 n = Fetch(p + ivec2(0, 2));
+    vec3 o = Fetch(p + ivec2(1, 2));
+
+    float bL = Luma(b);
+    float cL = Luma(c);
+    float eL = Luma(e);
+    float fL = Luma(f);
+    float gL = Luma(g);
+    float hL = Luma(h);
+    float iL = Luma(i);
+    float jL = Luma(j);
+    float kL = Luma(k);
+    float lL = Luma(l);
+    float nL = Luma(n);
+    float oL = Luma(o);
+
+    vec2 dir = vec2(0.0);
+    float len = 0.0;
+    SetDirection(dir, len, (1.0 - pp.x) * (1.0 - pp.y), bL, eL, fL, gL, jL);
+    SetDirection(dir, len, pp.x * (1.0 - pp.y), cL, fL, gL, hL, kL);
+    SetDirection(dir, len, (1.0 - pp.x) * pp.y, fL, iL, jL, kL, nL);
+    SetDirection(dir, len, pp.x * pp.y, gL, jL, kL, lL, oL);
+
+    // Normalize the direction, flat areas get an arbitrary one.
+    vec2 dir2 = dir * dir;
+    float dirR = dir2.x + dir2.y;
+    bool zero = dirR < 1.0 / 32768.0;
+    dirR = zero ? 1.0 : inversesqrt(dirR);
+    dir.x = zero ? 1.0 : dir.x;
+    dir *= dirR;
+
+    // Stretch the kernel along edges and shape the lobe by how strong they are.
+    len = len * 0.5;
+    len *= len;
+    float stretch = (dir.x * dir.x + dir.y * dir.y) / max(abs(dir.x), abs(dir.y));
+    vec2 len2 = vec2(1.0 + (stretch - 1.0) * len, 1.0 - 0.5 * len);
+    float lob = 0.5 + ((1.0 / 4.0 - 0.04) - 0.5) * len;
+    float clp = 1.0 / lob;
+
+    vec3 aC = vec3(0.0);
+    float aW = 0.0;
+    Tap(aC, aW, vec2(0.0, -1.0) - pp, dir, len2, lob, clp, b);
+    Tap(aC, aW, vec2(1.0, -1.0) - pp, dir, len2, lob, clp, c);
+    Tap(aC, aW, vec2(-1.0, 1.0) - pp, dir, len2, lob, clp, i);
+    Tap(aC, aW, vec2(0.0, 1.0) - pp, dir, len2, lob, clp, j);
+    Tap(aC, aW, vec2(0.0, 0.0) - pp, dir, len2, lob, clp, f);
+    Tap(aC, aW, vec2(-1.0, 0.0) - pp, dir, len2, lob, clp, e);
+    Tap(aC, aW, vec2(1.0, 1.0) - pp, dir, len2, lob, clp, k);
+    Tap(aC, aW, vec2(2.0, 1.0) - pp, dir, len2, lob, clp, l);
+    Tap(aC, aW, vec2(2.0, 0.0) - pp, dir, len2, lob, clp, h);
+    Tap(aC, aW, vec2(1.0, 0.0) - pp, dir, len2, lob, clp, g);
+    Tap(aC, aW, vec2(1.0, 2.0) - pp, dir, len2, lob, clp, o);
+    Tap(aC, aW, vec2(0.0, 2.0) - pp, dir, len2, lob, clp, n);
+
+    // Removes the ringing of the negative lobes.
+    vec3 min4 = min(min(f, g), min(j, k));
+    vec3 max4 = max(max(f, g), max(j, k));
+
+    outColor = vec4(clamp(aC / aW, min4, max4), 1.0);
+}
@@ -0,0 +1,74 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// FidelityFX Super Resolution 1.0 robust contrast adaptive sharpening (RCAS), the fp32 path
+// with noise removal. Sharpens the output of the upsampling, or the image itself when it is
+// already at the output size, and expands the reversible tone map back to HDR.
+// Reference: https://github.com/GPUOpen-Effects/FidelityFX-FSR/blob/master/ffx-fsr/ffx_fsr1.h
+layout(binding = 0) uniform sampler2D image;
+
+// 1 at full strength, halved by every stop of reduction.
+uniform float sharpness;
+// The image is already compressed by the upsampling pass.
+uniform bool compressed;
+
+layout(location = 0) out vec4 outColor;
+
+// Limits the lobe so the sharpening cannot clip, 0.25 - 1/16 as in the reference.
+const float RCAS_LIMIT = 0.25 - 1.0 / 16.0;
+
+vec3 Fetch(ivec2 position)
+{
+    vec3 color = texelFetch(image, clamp(position, ivec2(0), textureSize(image, 0) - 1), 0).rgb;
+
+    return compressed ? color : color / (1.0 + max(max(color.r, color.g), color.b));
+}
+
+vec3 Expand(vec3 color)
+{
+    return color / max(1.0 - max(max(color.r, color.g), color.b), 1.0e-4);
+}
+
+float Luma(vec3 color)
+{
+    return color.b * 0.5 + (color.r * 0.5 + color.g);
+}
+
+void main()
+{
+    ivec2 p = ivec2(gl_FragCoord.xy);
+
+    //    b
+    //  d e f
+    //    h
+    vec3 b = Fetch(p + ivec2(0, -1));
+    vec3 d = Fetch(p + ivec2(-1, 0));
+    vec3 e = Fetch(p);
+    vec3 f = Fetch(p + ivec2(1, 0));
+    vec3 h = Fetch(p + ivec2(0, 1));
+
+    float bL = Luma(b);
+    float dL = Luma(d);
+    float eL = Luma(e);
+    float fL = Luma(f);
+    float hL = Luma(h);
+
+    // Less sharpening where the center stands out of its neighbors, which is likely noise.
+    float nz = 0.25 * (bL + dL + fL + hL) - eL;
+    float range = max(max(max(bL, dL), max(eL, fL)), hL) - min(min(min(bL, dL), min(eL, fL)), hL);
+    nz = clamp(abs(nz) / max(range, 1.0e-5), 0.0, 1.0);
+    nz = -0.5 * nz + 1.0;
+
+    // The strongest lobe that neither clips to black nor to white.
+    vec3 min4 = min(min(b, d), min(f, h));
+    vec3 max4 = max(max(b, d), max(f, h));
+    vec3 hitMin = min(min4, e) / (4.0 * max4 + 1.0e-5);
+    vec3 hitMax = (1.0 - max(max4, e)) / min(4.0 * min4 - 4.0, vec3(-1.0e-5));
+    vec3 lobeRgb = max(-hitMin, hitMax);
+    float lobe = max(-RCAS_LIMIT, min(max(max(lobeRgb.r, lobeRgb.g), lobeRgb.b), 0.0)) * sharpness;
+    lobe *= nz;
+
+    vec3 color = (lobe * (b + d + f + h) + e) / (4.0 * lobe + 1.0);
+
+    outColor = vec4(Expand(color), 1.0);
+}
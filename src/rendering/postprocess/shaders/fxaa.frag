@@ -0,0 +1,182 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// FXAA 3.11 quality (Lottes 2011). Runs before the tone mapper, so edges are found on the luma
+// of a Reinhard compressed color, which is close to what the tone mapped image will show.
+// Reference: https://catlikecoding.com/unity/tutorials/advanced-rendering/fxaa/
+layout(binding = 0) uniform sampler2D image;
+
+// Amount of sub pixel aliasing removal.
+uniform float subpixel;
+// Minimum local contrast, relative to the brightest neighbour, that counts as an edge.
+uniform float edgeThreshold;
+// Lower bound of the contrast threshold so dark areas are left alone.
+uniform float edgeThresholdMin;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const int SEARCH_STEPS = 10;
+const float STEP_SIZES[SEARCH_STEPS] = float[](1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 4.0);
+// Assumed distance past the last step when the end of the edge was not found.
+const float LAST_EDGE_GUESS = 8.0;
+
+float Luma(vec3 color)
+{
+    color = color / (1.0 + color);
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+float LumaAt(vec2 uv)
+{
+    return Luma(textureLod(image, uv, 0.0).rgb);
+}
+
+float LumaAt(vec2 uv, ivec2 offset)
+{
+    return Luma(textureLodOffset(image, uv, 0.0, offset).rgb);
+}
+
+void main()
+{
+    vec2 texel = 1.0 / textureSize(image, 0);
+    vec2 uv = fsIn.texcoord;
+
+    vec4 center = textureLod(image, uv, 0.0);
+
+    float lumaM = Luma(center.rgb);
+    float lumaN = LumaAt(uv, ivec2( 0,  1));
+    float lumaS = LumaAt(uv, ivec2( 0, -1));
+    float lumaE = LumaAt(uv, ivec2( 1,  0));
+    float lumaW = LumaAt(uv, ivec2(-1,  0));
+
+    float maxLuma = max(lumaM, max(max(lumaN, lumaS), max(lumaE, lumaW)));
+    float minLuma = min(lumaM, min(min(lumaN, lumaS), min(lumaE, lumaW)));
+    float contrast = maxLuma - minLuma;
+
+    if (contrast < max(edgeThresholdMin, maxLuma * edgeThreshold))
+    {
+        outColor = center;
+        return;
+    }
+
+    float lumaNE = LumaAt(uv, ivec2( 1,  1));
+    float lumaNW = LumaAt(uv, ivec2(-1,  1));
+    float lumaSE = LumaAt(uv, ivec2( 1, -1));
+    float lumaSW = LumaAt(uv, ivec2(-1, -1));
+
+    // Sub pixel blend from the difference between the pixel and its low passed neighbourhood.
+    float average = (2.0 * (lumaN + lumaS + lumaE + lumaW) + lumaNE + lumaNW + lumaSE + lumaSW) / 12.0;
+    float subpixelBlend = smoothstep(0.0, 1.0, clamp(abs(average - lumaM) / contrast, 0.0, 1.0));
+    subpixelBlend = subpixelBlend * subpixelBlend * subpixel;
+
+    float horizontal = 2.0 * abs(lumaN + lumaS - 2.0 * lumaM)
+                     + abs(lumaNE + lumaSE - 2.0 * lumaE)
+                     + abs(lumaNW + lumaSW - 2.0 * lumaW);
+    float vertical = 2.0 * abs(lumaE + lumaW - 2.0 * lumaM)
+                   + abs(lumaNE + lumaNW - 2.0 * lumaN)
+                   + abs(lumaSE + lumaSW - 2.0 * lumaS);
+    bool isHorizontal = horizontal >= vertical;
+
+    // Blend towards the neighbour across the edge with the larger gradient.
+    float stepLength = isHorizontal ? texel.y : texel.x;
+    float positiveLuma = isHorizontal ? lumaN : lumaE;
+    float negativeLuma = isHorizontal ? lumaS : lumaW;
+    float positiveGradient = abs(positiveLuma - lumaM);
+    float negativeGradient = abs(negativeLuma - lumaM);
+
+    float gradient = positiveGradient;
+    float oppositeLuma = positiveLuma;
+
+    if (positiveGradient < negativeGradient)
+    {
+        stepLength = -stepLength;
+        gradient = negativeGradient;
+        oppositeLuma = negativeLuma;
+    }
+
+    // Walk along the edge in both directions until its luma changes.
+    vec2 edgeUv = uv;
+    vec2 edgeStep;
+
+    if (isHorizontal)
+    {
+        edgeUv.y += stepLength * 0.5;
+        edgeStep = vec2(texel.x, 0.0);
+    }
+    else
+    {
+        edgeUv.x += stepLength * 0.5;
+        edgeStep = vec2(0.0, texel.y);
+    }
+
+    float edgeLuma = (lumaM + oppositeLuma) * 0.5;
+    float gradientThreshold = gradient * 0.25;
+
+    vec2 positiveUv = edgeUv + edgeStep * STEP_SIZES[0];
+    float positiveLumaDelta = LumaAt(positiveUv) - edgeLuma;
+    bool positiveAtEnd = abs(positiveLumaDelta) >= gradientThreshold;
+
+    for (int i = 1; i < SEARCH_STEPS && !positiveAtEnd; ++i)
+    {
+        positiveUv += edgeStep * STEP_SIZES[i];
+        positiveLumaDelta = LumaAt(positiveUv) - edgeLuma;
+        positiveAtEnd = abs(positiveLumaDelta) >= gradientThreshold;
+    }
+
+    if (!positiveAtEnd)
+    {
+        positiveUv += edgeStep * LAST_EDGE_GUESS;
+    }
+
+    vec2 negativeUv = edgeUv - edgeStep * STEP_SIZES[0];
+    float negativeLumaDelta = LumaAt(negativeUv) - edgeLuma;
+    bool negativeAtEnd = abs(negativeLumaDelta) >= gradientThreshold;
+
+    for (int i = 1; i < SEARCH_STEPS && !negativeAtEnd; ++i)
+    {
+        negativeUv -= edgeStep * STEP_SIZES[i];
+        negativeLumaDelta = LumaAt(negativeUv) - edgeLuma;
+        negativeAtEnd = abs(negativeLumaDelta) >= gradientThreshold;
+    }
+
+    if (!negativeAtEnd)
+    {
+        negativeUv -= edgeStep * LAST_EDGE_GUESS;
+    }
+
+    float positiveDistance = isHorizontal ? positiveUv.x - uv.x : positiveUv.y - uv.y;
+    float negativeDistance = isHorizontal ? uv.x - negativeUv.x : uv.y - negativeUv.y;
+
+    float shortestDistance = positiveDistance;
+    bool deltaSign = positiveLumaDelta >= 0.0;
+
+    if (negativeDistance < positiveDistance)
+    {
+        shortestDistance = negativeDistance;
+        deltaSign = negativeLumaDelta >= 0.0;
+    }
+
+    // Only the side of the edge that moves away from the pixel's luma is blended.
+    float edgeBlend = 0.0;
+    if (deltaSign != (lumaM - edgeLuma >= 0.0))
+    {
+        edgeBlend = 0.5 - shortestDistance / (positiveDistance + negativeDistance);
+    }
+
+    float blend = max(subpixelBlend, edgeBlend);
+
+    if (isHorizontal)
+    {
+        uv.y += stepLength * blend;
+    }
+    else
+    {
+        uv.x += stepLength * blend;
+    }
+
+    outColor = vec4(textureLod(image, uv, 0.0).rgb, center.a);
+}
@@ -0,0 +1,111 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Reconstruction filter from A Reconstruction Filter for Plausible Motion Blur (McGuire et al.
+// 2012). Samples along the dominant velocity of the neighbourhood and weighs every sample by
+// whether it is in front of the pixel and whether its own blur covers the pixel.
+layout(binding = 0) uniform sampler2D image;
+layout(binding = 1) uniform sampler2D velocityMap;
+layout(binding = 2) uniform sampler2D neighborMax;
+
+uniform int sampleCount;
+uniform int tileSize;
+uniform float velocityScale;
+uniform float maxBlurRadius;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+// Depth range over which samples blend between in front and behind, in view units.
+const float SOFT_Z_EXTENT = 0.1;
+
+float SoftDepthCompare(float a, float b)
+{
+    return clamp(1.0 - (a - b) / SOFT_Z_EXTENT, 0.0, 1.0);
+}
+
+float Cone(float distance, float velocityLength)
+{
+    return clamp(1.0 - distance / max(velocityLength, 0.0001), 0.0, 1.0);
+}
+
+float Cylinder(float distance, float velocityLength)
+{
+    return 1.0 - smoothstep(0.95 * velocityLength, 1.05 * velocityLength, distance);
+}
+
+vec3 ScaledVelocity(vec2 uv)
+{
+    vec3 velocityAndDepth = texture(velocityMap, uv).xyz;
+    vec2 velocity = velocityAndDepth.xy * velocityScale;
+    float length = length(velocity);
+
+    if (length > maxBlurRadius)
+    {
+        velocity *= maxBlurRadius / length;
+    }
+
+    return vec3(velocity, velocityAndDepth.z);
+}
+
+// Reference: http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+float InterleavedGradientNoise(vec2 position)
+{
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
+void main()
+{
+    vec2 size = textureSize(image, 0);
+    vec2 uv = fsIn.texcoord;
+    vec4 center = texture(image, uv);
+
+    vec2 dominantVelocity = texelFetch(neighborMax, ivec2(gl_FragCoord.xy) / tileSize, 0).xy;
+
+    if (length(dominantVelocity) < 0.5)
+    {
+        outColor = center;
+        return;
+    }
+
+    vec3 centerVelocity = ScaledVelocity(uv);
+    float centerLength = max(length(centerVelocity.xy), 0.5);
+    float centerDepth = centerVelocity.z;
+
+    float weight = 1.0 / centerLength;
+    vec3 color = center.rgb * weight;
+
+    float jitter = InterleavedGradientNoise(gl_FragCoord.xy) - 0.5;
+
+    for (int i = 0; i < sampleCount; ++i)
+    {
+        if (i == (sampleCount - 1) / 2)
+        {
+            continue;
+        }
+
+        float t = mix(-1.0, 1.0, (float(i) + jitter + 1.0) / float(sampleCount + 1));
+        vec2 offset = dominantVelocity * t;
+        vec2 sampleUv = uv + offset / size;
+
+        vec3 sampleVelocity = ScaledVelocity(sampleUv);
+        float sampleLength = length(sampleVelocity.xy);
+        float sampleDepth = sampleVelocity.z;
+        float distance = length(offset);
+
+        float inFront = SoftDepthCompare(sampleDepth, centerDepth);
+        float behind = SoftDepthCompare(centerDepth, sampleDepth);
+
+        float sampleWeight = inFront * Cone(distance, sampleLength)
+                           + behind * Cone(distance, centerLength)
+                           + Cylinder(distance, sampleLength) * Cylinder(distance, centerLength) * 2.0;
+
+        color += texture(image, sampleUv).rgb * sampleWeight;
+        weight += sampleWeight;
+    }
+
+    outColor = vec4(color / weight, center.a);
+}
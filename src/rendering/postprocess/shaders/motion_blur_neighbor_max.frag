@@ -0,0 +1,33 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Largest tile velocity of the 3x3 tile neighbourhood, so blur can spill over tile borders.
+layout(binding = 0) uniform sampler2D tileMax;
+
+layout(location = 0) out vec4 outVelocity;
+
+void main()
+{
+    ivec2 size = textureSize(tileMax, 0);
+    ivec2 tile = ivec2(gl_FragCoord.xy);
+
+    vec2 maxVelocity = vec2(0.0);
+    float maxLength = 0.0;
+
+    for (int y = -1; y <= 1; ++y)
+    {
+        for (int x = -1; x <= 1; ++x)
+        {
+            vec2 velocity = texelFetch(tileMax, clamp(tile + ivec2(x, y), ivec2(0), size - 1), 0).xy;
+            float length = dot(velocity, velocity);
+
+            if (length > maxLength)
+            {
+                maxLength = length;
+                maxVelocity = velocity;
+            }
+        }
+    }
+
+    outVelocity = vec4(maxVelocity, 0.0, 0.0);
+}
@@ -0,0 +1,46 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Largest velocity of each tileSize x tileSize tile.
+layout(binding = 0) uniform sampler2D velocityMap;
+
+uniform int tileSize;
+uniform float velocityScale;
+uniform float maxBlurRadius;
+
+layout(location = 0) out vec4 outVelocity;
+
+vec2 ScaledVelocity(ivec2 texel)
+{
+    vec2 velocity = texelFetch(velocityMap, texel, 0).xy * velocityScale;
+    float length = length(velocity);
+
+    return length > maxBlurRadius ? velocity * (maxBlurRadius / length) : velocity;
+}
+
+void main()
+{
+    ivec2 size = textureSize(velocityMap, 0);
+    ivec2 origin = ivec2(gl_FragCoord.xy) * tileSize;
+
+    vec2 maxVelocity = vec2(0.0);
+    float maxLength = 0.0;
+
+    for (int y = 0; y < tileSize; ++y)
+    {
+        for (int x = 0; x < tileSize; ++x)
+        {
+            ivec2 texel = min(origin + ivec2(x, y), size - 1);
+            vec2 velocity = ScaledVelocity(texel);
+            float length = dot(velocity, velocity);
+
+            if (length > maxLength)
+            {
+                maxLength = length;
+                maxVelocity = velocity;
+            }
+        }
+    }
+
+    outVelocity = vec4(maxVelocity, 0.0, 0.0);
+}
@@ -7,6 +7,19 @@ layout(std140, binding = 3) uniform ToneMappingBlock
     int tonemappingOperator;
     float whiteThreshold;
     float exposure;
+    int autoExposure;
+    int colorGrading;
+    float lutContribution;
+    float lutSize;
+};
+
+// sRGB to sRGB color grading LUT, applied after tone mapping when colorGrading is set.
+layout(binding = 1) uniform sampler3D colorLut;
+
+// Written by luminance_average.comp when auto exposure is enabled.
+layout(std430, binding = 2) readonly buffer LuminanceBlock
+{
+    float adaptedLuminance;
 };
 
 layout(location = 0) in VsOut {
@@ -121,26 +134,135 @@ vec3 RomBinDaHouse(vec3 color)
     return exp(-1.0 / (2.72 * color + 0.15));
 }
 
+// Reference: https://iolite-engine.com/blog_posts/minimal_agx_implementation
+vec3 AgXContrastApproximation(vec3 x)
+{
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+
+    return 15.5 * x4 * x2
+         - 40.14 * x4 * x
+         + 31.96 * x4
+         - 6.868 * x2 * x
+         + 0.4298 * x2
+         + 0.1191 * x
+         - 0.00232;
+}
+
+vec3 AgX(vec3 color)
+{
+    const mat3 inset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+
+    const mat3 outset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+
+    const float minEv = -12.47393;
+    const float maxEv = 4.026069;
+
+    color = inset * color;
+    color = clamp(log2(max(color, 1e-10)), minEv, maxEv);
+    color = (color - minEv) / (maxEv - minEv);
+    color = AgXContrastApproximation(color);
+    color = outset * color;
+
+    // The curve outputs display encoded values, the framebuffer expects linear ones.
+    return pow(max(color, 0.0), vec3(2.2));
+}
+
+// Gran Turismo tone mapper.
+// Reference: https://www.slideshare.net/nikuque/hdr-theory-and-practicce-jp
+// Reference: https://www.shadertoy.com/view/WdjSW3
+vec3 Uchimura(vec3 x)
+{
+    const float P = 1.0;  // Max brightness
+    const float a = 1.0;  // Contrast
+    const float m = 0.22; // Linear section start
+    const float l = 0.4;  // Linear section length
+    const float c = 1.33; // Black tightness
+    const float b = 0.0;  // Pedestal
+
+    float l0 = ((P - m) * l) / a;
+    float S0 = m + l0;
+    float S1 = m + a * l0;
+    float C2 = (a * P) / (P - S1);
+    float CP = -C2 / P;
+
+    vec3 w0 = vec3(1.0 - smoothstep(0.0, m, x));
+    vec3 w2 = vec3(step(m + l0, x));
+    vec3 w1 = vec3(1.0 - w0 - w2);
+
+    vec3 T = m * pow(x / m, vec3(c)) + b;
+    vec3 S = P - (P - S1) * exp(CP * (x - S0));
+    vec3 L = m + a * (x - m);
+
+    return T * w0 + L * w1 + S * w2;
+}
+
+// COLOR GRADING --------------------------------------------------------
+vec3 LinearToSrgb(vec3 color)
+{
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
+vec3 SrgbToLinear(vec3 color)
+{
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
+vec3 ApplyColorLut(vec3 color)
+{
+    vec3 encoded = LinearToSrgb(clamp(color, 0.0, 1.0));
+
+    // Sample the texel centers so the ends of the range land on the first and last entries.
+    vec3 uvw = encoded * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+    vec3 graded = SrgbToLinear(texture(colorLut, uvw).rgb);
+
+    return mix(color, graded, lutContribution);
+}
+
 void main()
 {
-    vec3 color = texture(image, fsIn.texcoord).rgb * exposure;
+    // With auto exposure the exposure uniform only holds the compensation. The key of 1 / 9.6
+    // maps the average luminance to middle grey.
+    float finalExposure = exposure;
+    if (autoExposure != 0) {
+        finalExposure /= 9.6 * max(adaptedLuminance, 0.0001);
+    }
+
+    vec3 color = texture(image, fsIn.texcoord).rgb * finalExposure;
 
     if (tonemappingOperator == 0) {
-        outColor = vec4(ACESFitted(color), 1.0);
+        color = ACESFitted(color);
     } else if (tonemappingOperator == 1) {
-        outColor = vec4(ACESFilm(color), 1.0);
+        color = ACESFilm(color);
     } else if (tonemappingOperator == 2) {
-        outColor = vec4(Reinhard(color), 1.0);
+        color = Reinhard(color);
     } else if (tonemappingOperator == 3) {
-        outColor = vec4(LumaBasedReinhard(color), 1.0);
+        color = LumaBasedReinhard(color);
     } else if (tonemappingOperator == 4) {
-        outColor = vec4(WhitePreservingLumaBasedReinhard(color), 1.0);
+        color = WhitePreservingLumaBasedReinhard(color);
     } else if (tonemappingOperator == 5) {
-        outColor = vec4(Uncharted2(color), 1.0);
+        color = Uncharted2(color);
     } else if (tonemappingOperator == 6) {
-        outColor = vec4(RomBinDaHouse(color), 1.0);
+        color = RomBinDaHouse(color);
+    } else if (tonemappingOperator == 7) {
+        color = AgX(color);
+    } else if (tonemappingOperator == 8) {
+        color = Uchimura(color);
     } else {
-//        outColor = vec4(ACESFitted(color), 1.0);
+//        color = ACESFitted(color);
         outColor = vec4(1.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    if (colorGrading != 0) {
+        color = ApplyColorLut(color);
     }
+
+    outColor = vec4(color, 1.0);
 }
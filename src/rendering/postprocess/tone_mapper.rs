@@ -1,56 +1,115 @@
 use crate::{
     core::application::clear_default_framebuffer,
+    core::asset::embedded::EmbeddedAssets,
     framebuffer::Framebuffer,
     imgui::{im_str, Gui, Ui},
     math::Vec4,
-    mesh::FULLSCREEN_MESH,
     rendering::{
         buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags},
-        postprocess::{AsAny, AsAnyMut, PostprocessingEffect, FULLSCREEN_VERTEX_SHADER},
+        color_lut::ColorLut,
+        error::RendererError,
+        frame_stats::FrameStats,
+        postprocess::{
+            fullscreen_pass::FullscreenPass, input_attachment, AsAny, AsAnyMut,
+            PostprocessingEffect,
+        },
         program_pipeline::ProgramPipeline,
         sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
-        shader::{Shader, ShaderStage},
-        state::{FrontFace, StateManager},
-        Draw,
+        shader::ShaderStage,
+        state::StateManager,
     },
     Context,
 };
+use gl_bindings as gl;
 
-use std::{any::Any, ops::RangeInclusive};
+use std::{any::Any, ops::RangeInclusive, path::Path};
+
+pub(crate) const UBO_BINDING_INDEX: u32 = 3;
+const HISTOGRAM_BINDING_INDEX: u32 = 1;
+const LUMINANCE_BINDING_INDEX: u32 = 2;
+const COLOR_LUT_BINDING_INDEX: u32 = 1;
+const HISTOGRAM_BIN_COUNT: usize = 256;
+// Used as the delta time of the first auto exposure frame so it starts fully adapted.
+const SNAP_DELTA_TIME: f32 = 1000.0;
+
+// The order matches the operator index of tonemap.frag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMappingOperator {
+    AcesFitted,
+    AcesFilmic,
+    Reinhard,
+    LumaBasedReinhard,
+    WhitePreservingLumaBasedReinhard,
+    Uncharted2,
+    RomBinDaHouse,
+    AgX,
+    Uchimura,
+}
+
+const OPERATORS: [ToneMappingOperator; 9] = [
+    ToneMappingOperator::AcesFitted,
+    ToneMappingOperator::AcesFilmic,
+    ToneMappingOperator::Reinhard,
+    ToneMappingOperator::LumaBasedReinhard,
+    ToneMappingOperator::WhitePreservingLumaBasedReinhard,
+    ToneMappingOperator::Uncharted2,
+    ToneMappingOperator::RomBinDaHouse,
+    ToneMappingOperator::AgX,
+    ToneMappingOperator::Uchimura,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExposureMode {
+    // The exposure set through set_exposure or set_ev100, e.g. from the camera.
+    Manual,
+    // Derived from a luminance histogram of the frame and adapted over time.
+    Automatic,
+}
 
 #[repr(C)]
 struct ToneMappingPerFrameUniforms {
     operator: i32,
     white_threshold: f32,
     exposure: f32,
+    auto_exposure: i32,
+    color_grading: i32,
+    lut_contribution: f32,
+    lut_size: f32,
     _pad: f32,
 }
 
 pub struct ToneMapper {
-    pipeline: ProgramPipeline,
+    pass: FullscreenPass,
     tone_mapper_ubo: Buffer,
     sampler_nearest: Sampler,
+    histogram_pipeline: ProgramPipeline,
+    average_pipeline: ProgramPipeline,
+    histogram_buffer: Buffer,
+    luminance_buffer: Buffer,
     operator: usize,
     white_threshold: f32,
     exposure: f32,
+    exposure_mode: ExposureMode,
+    // In EV, applied on top of both exposure modes.
+    exposure_compensation: f32,
+    min_log_luminance: f32,
+    max_log_luminance: f32,
+    adaptation_speed: f32,
+    last_adaptation_time: Option<f32>,
+    // Graded after tone mapping, in the same pass.
+    color_lut: Option<ColorLut>,
+    lut_contribution: f32,
+    sampler_lut: Sampler,
+    lut_path: imgui::ImString,
+    lut_error: Option<String>,
     enabled: bool,
 }
 
 impl_as_any!(ToneMapper);
 
 impl ToneMapper {
-    pub fn new() -> Self {
-        let pipeline = ProgramPipeline::new()
-            .add_shader(&FULLSCREEN_VERTEX_SHADER)
-            .add_shader(
-                &Shader::new(
-                    ShaderStage::Fragment,
-                    "src/rendering/postprocess/shaders/tonemap.frag",
-                )
-                .unwrap(),
-            )
-            .build()
-            .unwrap();
+    pub fn new() -> Result<Self, RendererError> {
+        let pass = FullscreenPass::new("src/rendering/postprocess/shaders/tonemap.frag")?;
 
         let mut tone_mapper_ubo = Buffer::new(
             "Tonemapping Fragment UBO",
@@ -58,7 +117,7 @@ impl ToneMapper {
             BufferTarget::Uniform,
             BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
         );
-        tone_mapper_ubo.bind(3);
+        tone_mapper_ubo.bind(UBO_BINDING_INDEX);
         tone_mapper_ubo.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
 
         let sampler_nearest = Sampler::new(
@@ -71,20 +130,176 @@ impl ToneMapper {
             Anisotropy::None,
         );
 
-        ToneMapper {
-            pipeline,
+        let sampler_lut = Sampler::new(
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(0.0, 0.0, 0.0, 0.0),
+            Anisotropy::None,
+        );
+
+        let load = |path: &str| -> Result<ProgramPipeline, RendererError> {
+            let program_pipeline = ProgramPipeline::new()
+                .add_shader(&EmbeddedAssets::load_shader(ShaderStage::Compute, path)?)
+                .build()?;
+
+            Ok(program_pipeline)
+        };
+
+        let histogram_buffer = Buffer::new_with_data(
+            "Luminance Histogram SSBO",
+            &[0u32; HISTOGRAM_BIN_COUNT],
+            BufferTarget::ShaderStorage,
+            BufferStorageFlags::empty(),
+        );
+
+        let luminance_buffer = Buffer::new_with_data(
+            "Adapted Luminance SSBO",
+            &1.0f32,
+            BufferTarget::ShaderStorage,
+            BufferStorageFlags::empty(),
+        );
+
+        Ok(ToneMapper {
+            pass,
             tone_mapper_ubo,
             sampler_nearest,
+            histogram_pipeline: load("src/rendering/postprocess/shaders/luminance_histogram.comp")?,
+            average_pipeline: load("src/rendering/postprocess/shaders/luminance_average.comp")?,
+            histogram_buffer,
+            luminance_buffer,
             operator: 0,
             white_threshold: 2.0,
             exposure: 1.5,
+            exposure_mode: ExposureMode::Manual,
+            exposure_compensation: 0.0,
+            min_log_luminance: -8.0,
+            max_log_luminance: 4.0,
+            adaptation_speed: 1.5,
+            last_adaptation_time: None,
+            color_lut: None,
+            lut_contribution: 1.0,
+            sampler_lut,
+            lut_path: imgui::ImString::with_capacity(256),
+            lut_error: None,
             enabled: true,
-        }
+        })
+    }
+
+    pub fn operator(&self) -> ToneMappingOperator {
+        OPERATORS[self.operator]
+    }
+
+    pub fn set_operator(&mut self, operator: ToneMappingOperator) {
+        self.operator = operator as usize
     }
 
+    // Exposure multiplier used in manual mode.
     pub fn set_exposure(&mut self, exposure: f32) {
         self.exposure = exposure
     }
+
+    // Sets the manual exposure from an exposure value at ISO 100, the same way the camera does.
+    pub fn set_ev100(&mut self, ev100: f32) {
+        self.exposure = 1.0 / 2.0f32.powf(ev100) * 1.2
+    }
+
+    pub fn exposure_mode(&self) -> ExposureMode {
+        self.exposure_mode
+    }
+
+    pub fn set_exposure_mode(&mut self, exposure_mode: ExposureMode) {
+        self.exposure_mode = exposure_mode
+    }
+
+    pub fn exposure_compensation(&self) -> f32 {
+        self.exposure_compensation
+    }
+
+    pub fn set_exposure_compensation(&mut self, exposure_compensation: f32) {
+        self.exposure_compensation = exposure_compensation
+    }
+
+    // The log2 luminance range the histogram covers.
+    pub fn set_luminance_range(&mut self, min_log_luminance: f32, max_log_luminance: f32) {
+        self.min_log_luminance = min_log_luminance;
+        self.max_log_luminance = max_log_luminance.max(min_log_luminance + 0.1)
+    }
+
+    // Higher is faster. The adapted luminance covers 1 - e^(-speed) of the way per second.
+    pub fn set_adaptation_speed(&mut self, adaptation_speed: f32) {
+        self.adaptation_speed = adaptation_speed
+    }
+
+    pub fn color_lut(&self) -> Option<&ColorLut> {
+        self.color_lut.as_ref()
+    }
+
+    // None turns color grading off.
+    pub fn set_color_lut(&mut self, color_lut: Option<ColorLut>) {
+        self.color_lut = color_lut
+    }
+
+    // Loads a strip image or .cube LUT and grades with it. The current LUT stays on failure.
+    pub fn load_color_lut<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        self.color_lut = Some(ColorLut::new_from_file(path)?);
+        Ok(())
+    }
+
+    pub fn lut_contribution(&self) -> f32 {
+        self.lut_contribution
+    }
+
+    // Blend between the tone mapped and the graded color.
+    pub fn set_lut_contribution(&mut self, lut_contribution: f32) {
+        self.lut_contribution = lut_contribution.max(0.0).min(1.0)
+    }
+
+    fn compute_adapted_luminance(&mut self, input: &Framebuffer, image: u32, time: f32) {
+        let delta_time = self
+            .last_adaptation_time
+            .map_or(SNAP_DELTA_TIME, |last_time| time - last_time);
+        self.last_adaptation_time = Some(time);
+
+        let size = input.size();
+        let range = self.max_log_luminance - self.min_log_luminance;
+
+        self.histogram_buffer.bind(HISTOGRAM_BINDING_INDEX);
+        self.luminance_buffer.bind(LUMINANCE_BINDING_INDEX);
+
+        self.histogram_pipeline.bind();
+        self.histogram_pipeline
+            .set_texture_2d_with_id(0, image, &self.sampler_nearest)
+            .set_float_all_stages("minLogLuminance", self.min_log_luminance)
+            .set_float_all_stages("inverseLogLuminanceRange", 1.0 / range);
+
+        unsafe {
+            // Matches the 16x16 local size of the shader.
+            gl::DispatchCompute((size.x + 15) / 16, (size.y + 15) / 16, 1);
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+        FrameStats::record_dispatch();
+
+        self.histogram_pipeline.unbind();
+
+        self.average_pipeline.bind();
+        self.average_pipeline
+            .set_float_all_stages("minLogLuminance", self.min_log_luminance)
+            .set_float_all_stages("logLuminanceRange", range)
+            .set_uint_all_stages("pixelCount", size.x * size.y)
+            .set_float_all_stages("deltaTime", delta_time)
+            .set_float_all_stages("adaptationSpeed", self.adaptation_speed);
+
+        unsafe {
+            gl::DispatchCompute(1, 1, 1);
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+        FrameStats::record_dispatch();
+
+        self.average_pipeline.unbind();
+    }
 }
 
 impl PostprocessingEffect for ToneMapper {
@@ -103,42 +318,58 @@ impl PostprocessingEffect for ToneMapper {
     }
 
     fn apply(&mut self, input: &Framebuffer, context: Context) {
-        let Context { window, .. } = context;
+        let window_size = context.window_size();
+        let Context { timer, .. } = context;
+
+        let attachment = match input_attachment(input, self.name()) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        let auto_exposure = self.exposure_mode == ExposureMode::Automatic;
+
+        if auto_exposure {
+            self.compute_adapted_luminance(input, attachment.id(), timer.get_elapsed_time());
+        } else {
+            self.last_adaptation_time = None;
+        }
 
-        let width = window.inner_size().width;
-        let height = window.inner_size().height;
         clear_default_framebuffer(&Vec4::new(0.0, 1.0, 0.0, 1.0));
 
-        StateManager::set_viewport(0, 0, width as i32, height as i32);
+        StateManager::set_viewport(0, 0, window_size.x as i32, window_size.y as i32);
 
-        self.pipeline.bind();
+        self.pass.bind();
+
+        // In automatic mode the shader derives the exposure from the adapted luminance.
+        let exposure = if auto_exposure { 1.0 } else { self.exposure };
 
         let tone_mapping_uniforms = ToneMappingPerFrameUniforms {
             operator: self.operator as i32,
             white_threshold: self.white_threshold,
-            exposure: self.exposure,
+            exposure: exposure * 2.0f32.powf(self.exposure_compensation),
+            auto_exposure: auto_exposure as i32,
+            color_grading: self.color_lut.is_some() as i32,
+            lut_contribution: self.lut_contribution,
+            lut_size: self.color_lut.as_ref().map_or(1, ColorLut::size) as f32,
             _pad: 0.0,
         };
 
         self.tone_mapper_ubo.fill_mapped(0, &tone_mapping_uniforms);
+        self.luminance_buffer.bind(LUMINANCE_BINDING_INDEX);
 
-        self.pipeline.set_texture_2d_with_id(
-            0,
-            input.texture_attachment(0).id(),
-            &self.sampler_nearest,
-        );
-
-        StateManager::set_front_face(FrontFace::Clockwise);
-        FULLSCREEN_MESH.draw();
-        StateManager::set_front_face(FrontFace::CounterClockwise);
+        if let Some(color_lut) = &self.color_lut {
+            self.pass.pipeline().set_texture_2d_with_id(
+                COLOR_LUT_BINDING_INDEX,
+                color_lut.get_id(),
+                &self.sampler_lut,
+            );
+        }
 
-        self.pipeline.unbind()
-    }
-}
+        self.pass
+            .set_texture("image", attachment.id(), &self.sampler_nearest)
+            .draw();
 
-impl Default for ToneMapper {
-    fn default() -> Self {
-        ToneMapper::new()
+        self.pass.unbind()
     }
 }
 
@@ -161,6 +392,8 @@ impl Gui for ToneMapper {
                         im_str!("White-Preserving Luma-Based Reinhard"),
                         im_str!("Uncharted 2"),
                         im_str!("RomBinDaHouse"),
+                        im_str!("AgX"),
+                        im_str!("Uchimura"),
                     ],
                 );
 
@@ -171,6 +404,83 @@ impl Gui for ToneMapper {
                         .build(&ui, &mut self.white_threshold);
                 }
 
+                let mut mode = self.exposure_mode as usize;
+                if imgui::ComboBox::new(im_str!("Exposure")).build_simple_string(
+                    &ui,
+                    &mut mode,
+                    &[im_str!("Manual"), im_str!("Automatic")],
+                ) {
+                    self.exposure_mode = if mode == 0 {
+                        ExposureMode::Manual
+                    } else {
+                        ExposureMode::Automatic
+                    };
+                }
+
+                imgui::Slider::new(im_str!("Compensation (EV)"))
+                    .range(RangeInclusive::new(-5.0, 5.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut self.exposure_compensation);
+
+                if self.exposure_mode == ExposureMode::Automatic {
+                    let mut min_log_luminance = self.min_log_luminance;
+                    let mut max_log_luminance = self.max_log_luminance;
+
+                    let min_changed = imgui::Slider::new(im_str!("Min Log Luminance"))
+                        .range(RangeInclusive::new(-16.0, 0.0))
+                        .display_format(im_str!("%.1f"))
+                        .build(&ui, &mut min_log_luminance);
+                    let max_changed = imgui::Slider::new(im_str!("Max Log Luminance"))
+                        .range(RangeInclusive::new(0.0, 16.0))
+                        .display_format(im_str!("%.1f"))
+                        .build(&ui, &mut max_log_luminance);
+
+                    if min_changed || max_changed {
+                        self.set_luminance_range(min_log_luminance, max_log_luminance);
+                    }
+
+                    imgui::Slider::new(im_str!("Adaptation Speed"))
+                        .range(RangeInclusive::new(0.1, 10.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.adaptation_speed);
+                }
+
+                imgui::TreeNode::new(im_str!("Color Grading"))
+                    .default_open(false)
+                    .open_on_arrow(true)
+                    .open_on_double_click(true)
+                    .framed(false)
+                    .build(ui, || {
+                        ui.input_text(im_str!("LUT"), &mut self.lut_path).build();
+
+                        if ui.button(im_str!("Load"), [0.0, 0.0]) {
+                            let path = self.lut_path.to_str().to_string();
+                            self.lut_error = self.load_color_lut(&path).err();
+                        }
+
+                        ui.same_line(0.0);
+
+                        if ui.button(im_str!("Clear"), [0.0, 0.0]) {
+                            self.color_lut = None;
+                            self.lut_error = None;
+                        }
+
+                        if let Some(error) = &self.lut_error {
+                            ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+                        } else if let Some(color_lut) = &self.color_lut {
+                            ui.text(format!(
+                                "{} ({}^3)",
+                                color_lut.path().display(),
+                                color_lut.size()
+                            ));
+                        }
+
+                        imgui::Slider::new(im_str!("Contribution"))
+                            .range(RangeInclusive::new(0.0, 1.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut self.lut_contribution);
+                    });
+
                 ui.new_line()
             });
     }
@@ -1,18 +1,227 @@
 use gl::types::*;
 use gl_bindings as gl;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fmt;
+use std::path::PathBuf;
 use std::ptr;
 
+use crate::core::math::{utilities, Mat4, Vec2, Vec3, Vec4};
 use crate::rendering::{
+    object_label::set_object_label,
     sampler::Sampler,
-    shader::{Shader, ShaderStage},
-    texture::{Texture2D, TextureCube},
+    shader::{self, Shader, ShaderStage},
+    state::StateManager,
+    texture::{Texture2D, Texture2DArray, TextureCube, TextureCubeArray},
+    transform_feedback::TransformFeedbackBufferMode,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogLocation {
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+impl LogLocation {
+    // Understands the common driver log formats:
+    //   NVIDIA:       "0(12) : error C0000: ..."
+    //   Mesa:         "0:12(5): error: ..."
+    //   AMD/Intel:    "ERROR: 0:12: ..."
+    pub fn parse(log: &str) -> Option<LogLocation> {
+        log.lines().find_map(|line| Self::parse_line(line.trim()))
+    }
+
+    fn parse_line(line: &str) -> Option<LogLocation> {
+        let line = line
+            .strip_prefix("ERROR: ")
+            .or_else(|| line.strip_prefix("WARNING: "))
+            .unwrap_or(line);
+
+        let (_, rest) = Self::parse_number(line)?;
+
+        if let Some(rest) = rest.strip_prefix(':') {
+            let (line_number, rest) = Self::parse_number(rest)?;
+
+            let column = rest
+                .strip_prefix('(')
+                .and_then(Self::parse_number)
+                .filter(|(_, rest)| rest.starts_with(')'))
+                .map(|(column, _)| column);
+
+            if column.is_some() || rest.starts_with(':') {
+                return Some(LogLocation {
+                    line: line_number,
+                    column,
+                });
+            }
+        } else if let Some(rest) = rest.strip_prefix('(') {
+            let (line_number, rest) = Self::parse_number(rest)?;
+
+            if rest.starts_with(')') {
+                return Some(LogLocation {
+                    line: line_number,
+                    column: None,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn parse_number(s: &str) -> Option<(u32, &str)> {
+        let end = s
+            .char_indices()
+            .find(|(_, c)| !c.is_ascii_digit())
+            .map_or(s.len(), |(i, _)| i);
+
+        s[..end].parse().ok().map(|number| (number, &s[end..]))
+    }
+}
+
+impl fmt::Display for LogLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "{}:{}", self.line, column),
+            None => write!(f, "{}", self.line),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Empty,
+    Compile {
+        stage: ShaderStage,
+        path: PathBuf,
+        location: Option<LogLocation>,
+        log: String,
+    },
+    ProgramCreation {
+        stage: ShaderStage,
+        path: PathBuf,
+    },
+    Link {
+        stage: ShaderStage,
+        path: PathBuf,
+        location: Option<LogLocation>,
+        log: String,
+    },
+    MissingStage(ShaderStage),
+    ComputeMixedWithGraphics,
+    InterfaceMismatch {
+        producer: ShaderStage,
+        consumer: ShaderStage,
+        name: String,
+        location: i32,
+    },
+    Validation(String),
+}
+
+impl std::error::Error for PipelineError {}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Empty => write!(
+                f,
+                "Program pipeline has no shaders. Add at least one shader before building."
+            ),
+            PipelineError::Compile {
+                stage,
+                path,
+                location: Some(location),
+                log,
+            } => write!(
+                f,
+                "Failed to compile {:?} shader {:?} at {}:\n{}",
+                stage, path, location, log
+            ),
+            PipelineError::Compile {
+                stage,
+                path,
+                location: None,
+                log,
+            } => write!(
+                f,
+                "Failed to compile {:?} shader {:?}:\n{}",
+                stage, path, log
+            ),
+            PipelineError::ProgramCreation { stage, path } => write!(
+                f,
+                "Failed to create a program object for {:?} shader {:?}.",
+                stage, path
+            ),
+            PipelineError::Link {
+                stage,
+                path,
+                location: Some(location),
+                log,
+            } => write!(
+                f,
+                "Failed to link {:?} shader {:?} at {}:\n{}",
+                stage, path, location, log
+            ),
+            PipelineError::Link {
+                stage,
+                path,
+                location: None,
+                log,
+            } => write!(f, "Failed to link {:?} shader {:?}:\n{}", stage, path, log),
+            PipelineError::MissingStage(stage) => write!(
+                f,
+                "Program pipeline is missing a {:?} shader. Graphics pipelines require at least \
+                 a vertex and a fragment shader.",
+                stage
+            ),
+            PipelineError::ComputeMixedWithGraphics => write!(
+                f,
+                "Program pipeline mixes a compute shader with graphics stages. \
+                 Use a separate pipeline for compute."
+            ),
+            PipelineError::InterfaceMismatch {
+                producer,
+                consumer,
+                name,
+                location,
+            } => write!(
+                f,
+                "{:?} shader input '{}' (location = {}) has no matching output in the {:?} shader.",
+                consumer, name, location, producer
+            ),
+            PipelineError::Validation(log) => {
+                write!(f, "Program pipeline validation failed:\n{}", log)
+            }
+        }
+    }
+}
+
+struct InterfaceVariable {
+    name: String,
+    location: i32,
+    variable_type: GLenum,
+}
+
+struct TransformFeedbackVaryings {
+    stage: ShaderStage,
+    varyings: Vec<CString>,
+    buffer_mode: TransformFeedbackBufferMode,
+}
+
+struct AttachedShader {
+    stage: ShaderStage,
+    id: GLuint,
+    path: PathBuf,
+}
+
 pub struct ProgramPipeline {
     id: GLuint,
-    shaders: [Option<(ShaderStage, GLuint)>; 6],
+    shaders: [Option<AttachedShader>; 6],
     shader_programs: [Option<GLuint>; 6],
+    validated: Cell<bool>,
+    // uniform name -> (program, location) for every stage the uniform is active in.
+    uniform_locations: RefCell<HashMap<String, Vec<(GLuint, GLint)>>>,
+    transform_feedback_varyings: Option<TransformFeedbackVaryings>,
 }
 
 impl ProgramPipeline {
@@ -25,84 +234,215 @@ impl ProgramPipeline {
 
         ProgramPipeline {
             id,
-            shaders: [None; 6],
+            shaders: Default::default(),
             shader_programs: [None; 6],
+            validated: Cell::new(false),
+            uniform_locations: Default::default(),
+            transform_feedback_varyings: None,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    // The file names of the shaders, e.g. "pbs.vert + pbs.frag". Names the pipeline in messages
+    // and debuggers.
+    pub fn name(&self) -> String {
+        let name = self
+            .shaders
+            .iter()
+            .flatten()
+            .map(|shader| {
+                shader.path.file_name().map_or_else(
+                    || shader.path.to_string_lossy(),
+                    |name| name.to_string_lossy(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" + ");
+
+        if name.is_empty() {
+            format!("{}", self.id)
+        } else {
+            name
         }
     }
 
     pub fn add_shader(mut self, shader: &Shader) -> Self {
         let idx = Self::shader_stage_to_array_index(shader.get_stage());
 
-        if let Some(_) = self.shaders[idx] {
-            println!(
-                "Shader of type {:?} already exists in the program pipeline... Replacing...",
-                shader.get_stage()
+        if let Some(ref existing) = self.shaders[idx] {
+            log::warn!(
+                "Replacing {:?} shader {:?} of program pipeline {} with {:?}.",
+                existing.stage,
+                existing.path,
+                self.name(),
+                shader.get_path()
             )
         }
 
-        self.shaders[idx] = Some((shader.get_stage(), shader.get_id()));
+        self.shaders[idx] = Some(AttachedShader {
+            stage: shader.get_stage(),
+            id: shader.get_id(),
+            path: shader.get_path().to_owned(),
+        });
+
+        self
+    }
+
+    // The outputs of the given stage to capture while transform feedback is active. The stage
+    // must be the last vertex processing stage of the pipeline.
+    pub fn transform_feedback_varyings(
+        mut self,
+        stage: ShaderStage,
+        varyings: &[&str],
+        buffer_mode: TransformFeedbackBufferMode,
+    ) -> Self {
+        self.transform_feedback_varyings = Some(TransformFeedbackVaryings {
+            stage,
+            varyings: varyings
+                .iter()
+                .map(|&varying| CString::new(varying).unwrap())
+                .collect(),
+            buffer_mode,
+        });
 
         self
     }
 
-    pub fn build(mut self) -> Result<Self, String> {
+    pub fn build(self) -> Result<Self, PipelineError> {
+        self.link()?.finish_link()
+    }
+
+    // Issues the link of every stage program without waiting for the result.
+    pub fn link(mut self) -> Result<Self, PipelineError> {
+        if self.shaders.iter().all(Option::is_none) {
+            return Err(PipelineError::Empty);
+        }
+
         unsafe {
-            for option in self.shaders.iter() {
-                if let Some(shader) = option {
-                    let program_id = gl::CreateProgram();
+            for shader in self.shaders.iter().flatten() {
+                let program_id = gl::CreateProgram();
+
+                if program_id == 0 {
+                    return Err(PipelineError::ProgramCreation {
+                        stage: shader.stage,
+                        path: shader.path.clone(),
+                    });
+                }
 
-                    //must be called before linking
-                    gl::ProgramParameteri(program_id, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+                //must be called before linking
+                gl::ProgramParameteri(program_id, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
 
-                    gl::AttachShader(program_id, shader.1);
+                if let Some(ref feedback) = self.transform_feedback_varyings {
+                    if Self::shader_stage_to_array_index(feedback.stage)
+                        == Self::shader_stage_to_array_index(shader.stage)
+                    {
+                        let varyings: Vec<*const GLchar> =
+                            feedback.varyings.iter().map(|v| v.as_ptr()).collect();
 
-                    gl::LinkProgram(program_id);
+                        gl::TransformFeedbackVaryings(
+                            program_id,
+                            varyings.len() as i32,
+                            varyings.as_ptr(),
+                            feedback.buffer_mode as u32,
+                        );
+                    }
+                }
 
-                    let mut link_status: GLint = 0;
-                    gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut link_status);
+                gl::AttachShader(program_id, shader.id);
 
-                    if link_status != gl::TRUE as i32 {
-                        let mut message_size = 0;
+                gl::LinkProgram(program_id);
 
-                        gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut message_size);
+                let idx = Self::shader_stage_to_array_index(shader.stage);
+                self.shader_programs[idx] = Some(program_id);
+            }
+        }
 
-                        //+1 for nul termination
-                        let mut buffer = Vec::with_capacity(message_size as usize + 1);
+        Ok(self)
+    }
 
-                        buffer.extend([b' '].iter().cycle().take(message_size as usize));
+    pub fn is_link_complete(&self) -> bool {
+        if !shader::check_parallel_shader_compile_support() {
+            return true;
+        }
 
-                        let message = CString::from_vec_unchecked(buffer);
+        self.shader_programs.iter().flatten().all(|&program_id| {
+            let mut completion_status: GLint = 0;
 
-                        gl::GetProgramInfoLog(
-                            program_id,
-                            message_size as i32,
-                            ptr::null_mut(),
-                            message.as_ptr() as *mut GLchar,
-                        );
+            unsafe {
+                gl::GetProgramiv(
+                    program_id,
+                    gl::COMPLETION_STATUS_KHR,
+                    &mut completion_status,
+                )
+            }
 
-                        return Err(message.to_string_lossy().into_owned());
-                    }
+            completion_status == gl::TRUE as i32
+        })
+    }
+
+    // Checks the link status of every stage program and assembles the pipeline.
+    pub fn finish_link(self) -> Result<Self, PipelineError> {
+        unsafe {
+            for shader in self.shaders.iter().flatten() {
+                let idx = Self::shader_stage_to_array_index(shader.stage);
+                let program_id = self.shader_programs[idx].unwrap();
 
-                    let idx = Self::shader_stage_to_array_index(shader.0);
-                    self.shader_programs[idx] = Some(program_id);
+                let mut link_status: GLint = 0;
+                gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut link_status);
 
-                    gl::UseProgramStages(
-                        self.id,
-                        Self::shader_stage_to_gl_bitfield(shader.0),
+                if link_status != gl::TRUE as i32 {
+                    let mut message_size = 0;
+
+                    gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut message_size);
+
+                    //+1 for nul termination
+                    let mut buffer = Vec::with_capacity(message_size as usize + 1);
+
+                    buffer.extend([b' '].iter().cycle().take(message_size as usize));
+
+                    let message = CString::from_vec_unchecked(buffer);
+
+                    gl::GetProgramInfoLog(
                         program_id,
-                    )
+                        message_size as i32,
+                        ptr::null_mut(),
+                        message.as_ptr() as *mut GLchar,
+                    );
+
+                    let log = message.to_string_lossy().into_owned();
+
+                    return Err(PipelineError::Link {
+                        stage: shader.stage,
+                        path: shader.path.clone(),
+                        location: LogLocation::parse(&log),
+                        log,
+                    });
                 }
+
+                gl::UseProgramStages(
+                    self.id,
+                    Self::shader_stage_to_gl_bitfield(shader.stage),
+                    program_id,
+                );
+
+                set_object_label(gl::PROGRAM, program_id, &shader.path.to_string_lossy());
             }
         }
 
+        let name = self.name();
+        set_object_label(gl::PROGRAM_PIPELINE, self.id, &name);
+
+        log::debug!("Linked program pipeline {}.", name);
+
         Ok(self)
     }
 
     pub fn set_texture_2d(&self, location: u32, texture: &Texture2D, sampler: &Sampler) -> &Self {
-        unsafe {
-            gl::BindTextureUnit(location as GLuint, texture.get_id());
-            gl::BindSampler(location as GLuint, sampler.id)
-        }
+        StateManager::bind_texture_unit(location, texture.get_id());
+        StateManager::bind_sampler(location, sampler.id);
 
         self
     }
@@ -113,10 +453,20 @@ impl ProgramPipeline {
         texture_id: u32,
         sampler: &Sampler,
     ) -> &Self {
-        unsafe {
-            gl::BindTextureUnit(binding_location as GLuint, texture_id);
-            gl::BindSampler(binding_location as GLuint, sampler.id)
-        }
+        StateManager::bind_texture_unit(binding_location, texture_id);
+        StateManager::bind_sampler(binding_location, sampler.id);
+
+        self
+    }
+
+    pub fn set_texture_2d_array(
+        &self,
+        binding_location: u32,
+        texture: &Texture2DArray,
+        sampler: &Sampler,
+    ) -> &Self {
+        StateManager::bind_texture_unit(binding_location, texture.get_id());
+        StateManager::bind_sampler(binding_location, sampler.id);
 
         self
     }
@@ -127,24 +477,315 @@ impl ProgramPipeline {
         texture: &TextureCube,
         sampler: &Sampler,
     ) -> &Self {
+        StateManager::bind_texture_unit(binding_location, texture.get_id());
+        StateManager::bind_sampler(binding_location, sampler.id);
+
+        self
+    }
+
+    pub fn set_texture_cube_array(
+        &self,
+        binding_location: u32,
+        texture: &TextureCubeArray,
+        sampler: &Sampler,
+    ) -> &Self {
+        StateManager::bind_texture_unit(binding_location, texture.get_id());
+        StateManager::bind_sampler(binding_location, sampler.id);
+
+        self
+    }
+
+    pub fn set_int_all_stages(&self, name: &str, value: i32) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform1i(program, location, value)
+        })
+    }
+
+    pub fn set_uint_all_stages(&self, name: &str, value: u32) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform1ui(program, location, value)
+        })
+    }
+
+    pub fn set_float_all_stages(&self, name: &str, value: f32) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform1f(program, location, value)
+        })
+    }
+
+    pub fn set_vec2_all_stages(&self, name: &str, value: &Vec2) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform2fv(program, location, 1, utilities::value_ptr(value))
+        })
+    }
+
+    pub fn set_vec3_all_stages(&self, name: &str, value: &Vec3) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform3fv(program, location, 1, utilities::value_ptr(value))
+        })
+    }
+
+    pub fn set_vec4_all_stages(&self, name: &str, value: &Vec4) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniform4fv(program, location, 1, utilities::value_ptr(value))
+        })
+    }
+
+    pub fn set_mat4_all_stages(&self, name: &str, value: &Mat4) -> &Self {
+        self.for_each_uniform_location(name, |program, location| unsafe {
+            gl::ProgramUniformMatrix4fv(
+                program,
+                location,
+                1,
+                gl::FALSE,
+                utilities::value_ptr(value),
+            )
+        })
+    }
+
+    // Looks up the texture unit a sampler uniform is bound to, i.e. its layout(binding = N).
+    pub fn texture_unit(&self, name: &str) -> Option<u32> {
+        self.uniform_locations(name)
+            .first()
+            .map(|&(program, location)| {
+                let mut unit: GLint = 0;
+                unsafe { gl::GetUniformiv(program, location, &mut unit) }
+                unit as u32
+            })
+    }
+
+    pub fn has_uniform(&self, name: &str) -> bool {
+        !self.uniform_locations(name).is_empty()
+    }
+
+    pub fn has_stage(&self, stage: ShaderStage) -> bool {
+        self.shaders[Self::shader_stage_to_array_index(stage)].is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), PipelineError> {
+        let has_compute = self.has_stage(ShaderStage::Compute);
+
+        if has_compute {
+            let has_graphics = self
+                .shaders
+                .iter()
+                .flatten()
+                .any(|shader| shader.stage != ShaderStage::Compute);
+
+            if has_graphics {
+                return Err(PipelineError::ComputeMixedWithGraphics);
+            }
+        } else {
+            if !self.has_stage(ShaderStage::Vertex) {
+                return Err(PipelineError::MissingStage(ShaderStage::Vertex));
+            }
+
+            if !self.has_stage(ShaderStage::Fragment) {
+                return Err(PipelineError::MissingStage(ShaderStage::Fragment));
+            }
+
+            if self.has_stage(ShaderStage::TesselationControl)
+                && !self.has_stage(ShaderStage::TesselationEvaluation)
+            {
+                return Err(PipelineError::MissingStage(
+                    ShaderStage::TesselationEvaluation,
+                ));
+            }
+
+            self.validate_interfaces()?;
+        }
+
         unsafe {
-            gl::BindTextureUnit(binding_location as GLuint, texture.get_id());
-            gl::BindSampler(binding_location as GLuint, sampler.id)
+            gl::ValidateProgramPipeline(self.id);
+
+            let mut status: GLint = 0;
+            gl::GetProgramPipelineiv(self.id, gl::VALIDATE_STATUS, &mut status);
+
+            if status != gl::TRUE as i32 {
+                let mut message_size = 0;
+                gl::GetProgramPipelineiv(self.id, gl::INFO_LOG_LENGTH, &mut message_size);
+
+                //+1 for nul termination
+                let mut buffer = Vec::with_capacity(message_size as usize + 1);
+
+                buffer.extend([b' '].iter().cycle().take(message_size as usize));
+
+                let message = CString::from_vec_unchecked(buffer);
+
+                gl::GetProgramPipelineInfoLog(
+                    self.id,
+                    message_size as i32,
+                    ptr::null_mut(),
+                    message.as_ptr() as *mut GLchar,
+                );
+
+                return Err(PipelineError::Validation(
+                    message.to_string_lossy().into_owned(),
+                ));
+            }
         }
 
-        self
+        Ok(())
     }
 
     pub fn bind(&self) {
-        unsafe {
-            gl::BindProgramPipeline(self.id);
+        StateManager::bind_program_pipeline(self.id);
+
+        if cfg!(debug_assertions) && !self.validated.get() {
+            self.validated.set(true);
+
+            if let Err(e) = self.validate() {
+                log::error!("Program pipeline {}: {}", self.name(), e)
+            }
         }
     }
 
     pub fn unbind(&self) {
+        StateManager::bind_program_pipeline(0)
+    }
+
+    fn for_each_uniform_location<F>(&self, name: &str, f: F) -> &Self
+    where
+        F: Fn(GLuint, GLint),
+    {
+        let locations = self.uniform_locations(name);
+
+        if cfg!(debug_assertions) && locations.is_empty() {
+            log::warn!(
+                "Uniform '{}' does not exist in any stage of program pipeline {}.",
+                name,
+                self.name()
+            )
+        }
+
+        locations
+            .iter()
+            .for_each(|&(program, location)| f(program, location));
+
+        self
+    }
+
+    fn uniform_locations(&self, name: &str) -> Vec<(GLuint, GLint)> {
+        self.uniform_locations
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let c_name = CString::new(name).unwrap();
+
+                self.shader_programs
+                    .iter()
+                    .flatten()
+                    .filter_map(|&program| {
+                        let location = unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) };
+
+                        if location >= 0 {
+                            Some((program, location))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .clone()
+    }
+
+    fn validate_interfaces(&self) -> Result<(), PipelineError> {
+        // Graphics stages in pipeline order. Compute is handled separately.
+        let stages = self.shaders[..5]
+            .iter()
+            .zip(self.shader_programs[..5].iter())
+            .filter_map(|(shader, program)| match (shader, program) {
+                (Some(shader), Some(program)) => Some((shader.stage, *program)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for pair in stages.windows(2) {
+            let (producer, producer_program) = pair[0];
+            let (consumer, consumer_program) = pair[1];
+
+            let outputs = Self::interface_variables(producer_program, gl::PROGRAM_OUTPUT);
+
+            let unmatched_input = Self::interface_variables(consumer_program, gl::PROGRAM_INPUT)
+                .into_iter()
+                .find(|input| {
+                    !outputs.iter().any(|output| {
+                        output.location == input.location
+                            && output.variable_type == input.variable_type
+                    })
+                });
+
+            if let Some(input) = unmatched_input {
+                return Err(PipelineError::InterfaceMismatch {
+                    producer,
+                    consumer,
+                    name: input.name,
+                    location: input.location,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn interface_variables(program: GLuint, interface: GLenum) -> Vec<InterfaceVariable> {
+        let mut count: GLint = 0;
+        let mut max_name_length: GLint = 0;
+
         unsafe {
-            gl::BindProgramPipeline(0);
+            gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+            gl::GetProgramInterfaceiv(
+                program,
+                interface,
+                gl::MAX_NAME_LENGTH,
+                &mut max_name_length,
+            );
         }
+
+        let properties = [gl::LOCATION, gl::TYPE];
+
+        (0..count as GLuint)
+            .filter_map(|index| {
+                let mut values: [GLint; 2] = [0; 2];
+                let mut name = vec![0u8; max_name_length as usize + 1];
+                let mut name_length: GLsizei = 0;
+
+                unsafe {
+                    gl::GetProgramResourceiv(
+                        program,
+                        interface,
+                        index,
+                        properties.len() as i32,
+                        properties.as_ptr(),
+                        values.len() as i32,
+                        ptr::null_mut(),
+                        values.as_mut_ptr(),
+                    );
+
+                    gl::GetProgramResourceName(
+                        program,
+                        interface,
+                        index,
+                        name.len() as i32,
+                        &mut name_length,
+                        name.as_mut_ptr() as *mut GLchar,
+                    );
+                }
+
+                name.truncate(name_length as usize);
+
+                // Built-ins (gl_Position etc.) have no location.
+                if values[0] < 0 {
+                    return None;
+                }
+
+                Some(InterfaceVariable {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    location: values[0],
+                    variable_type: values[1] as GLenum,
+                })
+            })
+            .collect()
     }
 
     fn shader_stage_to_array_index(shader_type: ShaderStage) -> usize {
@@ -172,6 +813,73 @@ impl ProgramPipeline {
 
 impl Drop for ProgramPipeline {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgramPipelines(1, &self.id) }
+        StateManager::program_pipeline_deleted(self.id);
+
+        unsafe {
+            self.shader_programs
+                .iter()
+                .flatten()
+                .for_each(|&program_id| gl::DeleteProgram(program_id));
+
+            gl::DeleteProgramPipelines(1, &self.id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogLocation;
+
+    fn location(line: u32, column: Option<u32>) -> Option<LogLocation> {
+        Some(LogLocation { line, column })
+    }
+
+    #[test]
+    fn parses_nvidia_logs() {
+        let log = "0(12) : error C0000: syntax error, unexpected identifier";
+
+        assert_eq!(LogLocation::parse(log), location(12, None));
+    }
+
+    #[test]
+    fn parses_mesa_logs() {
+        let log = "0:12(5): error: `albedo' undeclared";
+
+        assert_eq!(LogLocation::parse(log), location(12, Some(5)));
+    }
+
+    #[test]
+    fn parses_amd_and_intel_logs() {
+        assert_eq!(
+            LogLocation::parse("ERROR: 0:12: 'albedo' : undeclared identifier"),
+            location(12, None)
+        );
+        assert_eq!(
+            LogLocation::parse("WARNING: 0:3: extension not supported"),
+            location(3, None)
+        );
+    }
+
+    #[test]
+    fn takes_the_first_line_with_a_location() {
+        let log = "Fragment shader failed to compile with the following errors:\n\
+                   ERROR: 0:7: 'x' : undeclared identifier\n\
+                   ERROR: 0:9: '' : compilation terminated";
+
+        assert_eq!(LogLocation::parse(log), location(7, None));
+    }
+
+    #[test]
+    fn ignores_logs_without_a_location() {
+        assert_eq!(LogLocation::parse(""), None);
+        assert_eq!(LogLocation::parse("error: linking failed"), None);
+        assert_eq!(LogLocation::parse("0(12 : error"), None);
+        assert_eq!(LogLocation::parse("0:12 error"), None);
+    }
+
+    #[test]
+    fn displays_line_and_column() {
+        assert_eq!(location(12, Some(5)).unwrap().to_string(), "12:5");
+        assert_eq!(location(12, None).unwrap().to_string(), "12");
     }
 }
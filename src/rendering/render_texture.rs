@@ -0,0 +1,68 @@
+use crate::{
+    core::{camera::CameraTarget, math::UVec2},
+    rendering::{
+        framebuffer::{AttachmentType, Framebuffer, FramebufferAttachmentCreateInfo},
+        program_pipeline::ProgramPipeline,
+        sampler::Sampler,
+        texture::SizedTextureFormat,
+    },
+    Msaa,
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::rc::Rc;
+
+// A color texture with a depth buffer that cameras render into and materials sample, e.g. for
+// mirrors, in-world screens and minimaps. Clones share the same framebuffer, so a camera and
+// the materials that show its image can each hold one.
+#[derive(Debug, Clone)]
+pub struct RenderTexture {
+    framebuffer: Rc<Framebuffer>,
+}
+
+impl RenderTexture {
+    pub fn new(size: UVec2, format: SizedTextureFormat) -> Result<Self, String> {
+        let framebuffer = Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![
+                FramebufferAttachmentCreateInfo::new(format, AttachmentType::Texture),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth24Stencil8,
+                    AttachmentType::Renderbuffer,
+                ),
+            ],
+        )
+        .map_err(|e| format!("Failed to create render texture: {}", e))?;
+
+        Ok(Self {
+            framebuffer: Rc::new(framebuffer),
+        })
+    }
+
+    // The target of cameras rendering into the texture.
+    pub fn target(&self) -> CameraTarget {
+        CameraTarget::Texture(Rc::clone(&self.framebuffer))
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn texture_id(&self) -> GLuint {
+        self.framebuffer.texture_attachments()[0].id()
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.framebuffer.size()
+    }
+
+    pub fn bind_texture(
+        &self,
+        program_pipeline: &ProgramPipeline,
+        location: u32,
+        sampler: &Sampler,
+    ) {
+        program_pipeline.set_texture_2d_with_id(location, self.texture_id(), sampler);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::core::math::utilities;
 use crate::core::math::Vec4;
+use crate::rendering::gpu_capabilities::GpuCapabilities;
+use crate::rendering::state::{DepthFunction, StateManager};
 use gl_bindings as gl;
 
 use gl::types::GLuint;
@@ -30,7 +32,7 @@ pub enum WrappingMode {
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Anisotropy {
     None = 1,
     X2 = 2,
@@ -75,10 +77,9 @@ impl Sampler {
                 gl::TEXTURE_BORDER_COLOR,
                 utilities::value_ptr(&border_color),
             );
-            gl::SamplerParameterf(id, gl::TEXTURE_MAX_ANISOTROPY, anisotropy as u32 as f32)
         }
 
-        Sampler {
+        let sampler = Sampler {
             id,
             min_filter,
             mag_filter,
@@ -86,12 +87,38 @@ impl Sampler {
             wrap_t,
             wrap_r,
             border_color,
+        };
+        sampler.set_anisotropy(anisotropy);
+
+        sampler
+    }
+
+    // Clamped to the maximum of the GPU, ignored without anisotropic filtering.
+    pub fn set_anisotropy(&self, anisotropy: Anisotropy) {
+        let capabilities = GpuCapabilities::current();
+        if capabilities.anisotropic_filtering {
+            let anisotropy = (anisotropy as u32 as f32).min(capabilities.max_anisotropy);
+            unsafe { gl::SamplerParameterf(self.id, gl::TEXTURE_MAX_ANISOTROPY, anisotropy) }
+        }
+    }
+
+    // Makes lookups compare the reference value against the depth texture instead of returning
+    // the depth, as sampler2DShadow expects.
+    pub fn set_depth_comparison(&self, function: DepthFunction) {
+        unsafe {
+            gl::SamplerParameteri(
+                self.id,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::SamplerParameteri(self.id, gl::TEXTURE_COMPARE_FUNC, function as i32)
         }
     }
 }
 
 impl Drop for Sampler {
     fn drop(&mut self) {
+        StateManager::sampler_deleted(self.id);
         unsafe { gl::DeleteSamplers(1, &self.id) }
     }
 }
@@ -0,0 +1,141 @@
+use crate::{
+    core::math::{UVec2, Vec4},
+    rendering::{
+        framebuffer::Framebuffer,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        state::StateManager,
+        texture::{SizedTextureFormat, Texture2D},
+    },
+};
+use gl_bindings as gl;
+
+// The texture unit the captured color is bound to. Shaders of refractive and distorting
+// materials sample it as `layout(binding = 8) uniform sampler2D sceneColor;`.
+pub const SCENE_COLOR_BINDING_INDEX: u32 = 8;
+
+// A copy of the opaque scene color for materials that need to see what is behind them, e.g.
+// for refraction or heat haze. The copy happens at most once per frame, no matter how many
+// transparent draws ask for it. The mip chain approximates blur for rough refraction.
+pub struct SceneColor {
+    texture: Texture2D,
+    size: UVec2,
+    format: SizedTextureFormat,
+    level_count: u32,
+    sampler: Sampler,
+    captured: bool,
+}
+
+impl SceneColor {
+    pub fn new(size: UVec2, format: SizedTextureFormat) -> Self {
+        let level_count = Self::level_count_for(size);
+
+        Self {
+            texture: Texture2D::new_empty(size.x, size.y, format, level_count as i32),
+            size,
+            format,
+            level_count,
+            sampler: Sampler::new(
+                MinificationFilter::LinearMipmapLinear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            captured: false,
+        }
+    }
+
+    pub fn resize(&mut self, size: UVec2) {
+        if size == self.size {
+            return;
+        }
+
+        self.level_count = Self::level_count_for(size);
+        self.texture = Texture2D::new_empty(size.x, size.y, self.format, self.level_count as i32);
+        self.size = size;
+        self.captured = false
+    }
+
+    // Call once per frame before the opaque geometry.
+    pub fn begin_frame(&mut self) {
+        self.captured = false
+    }
+
+    // Copies the first color attachment of the framebuffer and binds the copy. Call after the
+    // opaque geometry and before the first draw that samples it. Returns false when the frame
+    // was already captured or the framebuffer has no color attachment.
+    pub fn capture(&mut self, framebuffer: &Framebuffer) -> bool {
+        if self.captured {
+            self.bind();
+            return false;
+        }
+
+        let attachment = match framebuffer.texture_attachment(0) {
+            Ok(attachment) => attachment,
+            Err(e) => {
+                log::error!("Failed to capture the scene color: {}", e);
+                return false;
+            }
+        };
+
+        assert!(
+            framebuffer.samples() <= 1,
+            "The scene color is captured from a resolved framebuffer."
+        );
+        assert_eq!(
+            framebuffer.size(),
+            self.size,
+            "The scene color must be the size of the framebuffer it is captured from."
+        );
+
+        unsafe {
+            gl::CopyImageSubData(
+                attachment.id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                self.texture.get_id(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                self.size.x as i32,
+                self.size.y as i32,
+                1,
+            );
+            gl::GenerateTextureMipmap(self.texture.get_id());
+        }
+
+        self.captured = true;
+        self.bind();
+
+        true
+    }
+
+    // Binds the last capture to SCENE_COLOR_BINDING_INDEX, e.g. after another pass used the unit.
+    pub fn bind(&self) {
+        StateManager::bind_texture_unit(SCENE_COLOR_BINDING_INDEX, self.texture.get_id());
+        StateManager::bind_sampler(SCENE_COLOR_BINDING_INDEX, self.sampler.id)
+    }
+
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+
+    pub fn texture(&self) -> &Texture2D {
+        &self.texture
+    }
+
+    pub fn level_count(&self) -> u32 {
+        self.level_count
+    }
+
+    fn level_count_for(size: UVec2) -> u32 {
+        32 - size.x.max(size.y).max(1).leading_zeros()
+    }
+}
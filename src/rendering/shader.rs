@@ -1,36 +1,33 @@
-use crate::core::asset::Asset;
+use crate::core::asset::{embedded::EmbeddedAssets, Asset};
+use crate::rendering::error::RendererError;
+use crate::rendering::gpu_capabilities::GpuCapabilities;
+use crate::rendering::object_label::set_object_label;
+use crate::rendering::program_pipeline::{LogLocation, PipelineError};
+use crate::rendering::shader_validation::{self, BindingLayout};
 use gl::types::*;
 use gl_bindings as gl;
-use std::{ffi::CString, fmt::Debug, fs::File, io::Read, path::Path, ptr};
+use std::{
+    ffi::CString,
+    fmt::Debug,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+// Deeper includes are taken for a file that includes itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
 
 pub fn check_spirv_support() -> bool {
-    let mut format_count: GLint = 0;
-
-    unsafe {
-        gl::GetIntegerv(gl::NUM_SHADER_BINARY_FORMATS, &mut format_count);
-    }
-
-    if format_count > 0 {
-        let mut formats = vec![0; format_count as usize];
-
-        unsafe {
-            gl::GetIntegerv(gl::SHADER_BINARY_FORMATS, formats.as_mut_ptr());
-        }
-
-        let opt = formats
-            .iter()
-            .find(|&m| *m == gl::SHADER_BINARY_FORMAT_SPIR_V_ARB as i32);
-        match opt {
-            Some(_) => return true,
-            _ => false,
-        };
-    }
+    GpuCapabilities::current().spirv
+}
 
-    false
+pub fn check_parallel_shader_compile_support() -> bool {
+    GpuCapabilities::current().parallel_shader_compile
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ShaderStage {
     Vertex = gl::VERTEX_SHADER,
     TesselationControl = gl::TESS_CONTROL_SHADER,
@@ -43,22 +40,23 @@ pub enum ShaderStage {
 pub struct Shader {
     id: GLuint,
     stage: ShaderStage,
+    path: PathBuf,
 }
 
 impl Shader {
-    pub fn new<P: AsRef<Path> + Debug>(stage: ShaderStage, path: P) -> Result<Shader, String> {
-        assert!(
-            path.as_ref().is_file(),
-            "Path: {:?} is not a file!",
-            path.as_ref()
-        );
+    pub fn new<P: AsRef<Path> + Debug>(
+        stage: ShaderStage,
+        path: P,
+    ) -> Result<Shader, RendererError> {
+        if !path.as_ref().is_file() {
+            return Err(RendererError::Shader {
+                stage,
+                path: path.as_ref().to_owned(),
+                log: String::from("The shader is not a file."),
+            });
+        }
 
         if cfg!(feature = "use-spirv") {
-            assert!(
-                path.as_ref().is_file(),
-                "Path: {:?} is not a file!",
-                path.as_ref()
-            );
             let mut spv_path = path.as_ref().to_owned();
             let mut file_name = spv_path.file_name().unwrap().to_owned();
             file_name.push(".spv");
@@ -73,22 +71,27 @@ impl Shader {
     fn new_from_spirv<P: AsRef<Path> + Debug>(
         stage: ShaderStage,
         path: P,
-    ) -> Result<Shader, String> {
+    ) -> Result<Shader, RendererError> {
         let mut spir_v = Vec::new();
 
-        {
-            let mut file = File::open(path.as_ref()).unwrap();
-
-            let file_size_in_bytes = file.metadata().unwrap().len() as usize;
-
-            let bytes_read = file.read_to_end(&mut spir_v).unwrap();
+        File::open(path.as_ref())
+            .and_then(|mut file| file.read_to_end(&mut spir_v))
+            .map_err(|e| RendererError::Shader {
+                stage,
+                path: path.as_ref().to_owned(),
+                log: format!("Failed to read the shader: {}", e),
+            })?;
 
-            assert_eq!(
-                bytes_read, file_size_in_bytes,
-                "Could not read the entirety of the file."
-            );
-        }
+        Self::new_from_spirv_binary(stage, path, &spir_v)
+    }
 
+    // The path only identifies the shader, e.g. in error messages. Used for shaders that don't
+    // live in a loose file, like the ones in asset packs.
+    pub fn new_from_spirv_binary<P: AsRef<Path> + Debug>(
+        stage: ShaderStage,
+        path: P,
+        spir_v: &[u8],
+    ) -> Result<Shader, RendererError> {
         let id: GLuint;
 
         unsafe {
@@ -112,75 +115,104 @@ impl Shader {
                 ptr::null_mut(), // no specialization constants
                 ptr::null_mut(),
             ); // no specialization constants
+        }
 
-            let mut compilation_status: GLint = 0;
-
-            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut compilation_status);
-
-            if compilation_status != gl::TRUE as i32 {
-                let mut message_size = 0;
-
-                gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut message_size);
-
-                let mut buffer = Vec::with_capacity(message_size as usize + 1); //+1 for nul termination
-
-                buffer.extend([b' '].iter().cycle().take(message_size as usize));
+        set_object_label(gl::SHADER, id, &path.as_ref().to_string_lossy());
 
-                let message = CString::from_vec_unchecked(buffer);
+        let shader = Shader {
+            id,
+            stage,
+            path: path.as_ref().to_owned(),
+        };
 
-                gl::GetShaderInfoLog(
-                    id,
-                    message_size as i32,
-                    ptr::null_mut(),
-                    message.as_ptr() as *mut GLchar,
-                );
+        shader.check_compile_status()?;
 
-                return Err(message.to_string_lossy().into_owned());
-            }
-        }
+        log::debug!("Loaded {:?} shader {:?}.", stage, shader.path);
 
-        Ok(Shader { id, stage })
+        Ok(shader)
     }
 
     fn new_from_text<P: AsRef<Path> + Debug>(
         stage: ShaderStage,
         path: P,
-    ) -> Result<Shader, String> {
-        let mut text_source = String::new();
+    ) -> Result<Shader, RendererError> {
+        let source = Self::read_source(path.as_ref()).map_err(|log| RendererError::Shader {
+            stage,
+            path: path.as_ref().to_owned(),
+            log,
+        })?;
+
+        Self::new_from_source(stage, path, source)
+    }
 
-        {
-            let mut file = match File::open(path.as_ref()) {
-                Err(why) => panic!("couldn't open {:?}: {}", path, why),
-                Ok(file) => file,
-            };
+    // The path only identifies the shader, e.g. in error messages.
+    pub fn new_from_source<P: AsRef<Path> + Debug>(
+        stage: ShaderStage,
+        path: P,
+        source: String,
+    ) -> Result<Shader, RendererError> {
+        if cfg!(feature = "validate-shaders") {
+            shader_validation::validate(&source, &BindingLayout::engine()).map_err(|e| {
+                RendererError::Shader {
+                    stage,
+                    path: path.as_ref().to_owned(),
+                    log: e.to_string(),
+                }
+            })?;
+        }
 
-            let size = file.read_to_string(&mut text_source).unwrap();
+        let shader = Self::compile_from_source(stage, path, source);
 
-            assert_eq!(
-                size,
-                text_source.len(),
-                "Could not read the entirety of the file."
-            );
-        }
+        shader.check_compile_status()?;
 
-        let id: GLuint;
-        let c_string_source = CString::new(text_source).unwrap();
+        log::debug!("Compiled {:?} shader {:?}.", stage, shader.path);
 
-        unsafe {
-            id = gl::CreateShader(stage as u32);
+        Ok(shader)
+    }
 
-            gl::ShaderSource(id, 1, &c_string_source.as_ptr(), ptr::null());
+    // Issues the compilation without waiting for its result. With KHR_parallel_shader_compile
+    // the driver compiles on its own threads and is_compile_complete() can be polled.
+    // Falls back to the embedded shader with the same file name, like
+    // EmbeddedAssets::load_shader. A source that fails to read compiles as an empty one, so the
+    // error surfaces with the compile status.
+    pub fn new_async<P: AsRef<Path> + Debug>(stage: ShaderStage, path: P) -> Shader {
+        let source = Self::read_source(path.as_ref())
+            .or_else(|e| {
+                path.as_ref()
+                    .file_name()
+                    .and_then(|name| EmbeddedAssets::shader_source(&name.to_string_lossy()))
+                    .ok_or(e)
+                    .and_then(|source| expand_includes(source, &EmbeddedAssets::shader_include))
+            })
+            .unwrap_or_else(|e| {
+                log::error!("{}", e);
+                String::new()
+            });
+
+        Self::compile_from_source(stage, path, source)
+    }
 
-            gl::CompileShader(id);
+    pub fn is_compile_complete(&self) -> bool {
+        let mut completion_status: GLint = gl::TRUE as i32;
+
+        if check_parallel_shader_compile_support() {
+            unsafe { gl::GetShaderiv(self.id, gl::COMPLETION_STATUS_KHR, &mut completion_status) }
+        }
 
+        completion_status == gl::TRUE as i32
+    }
+
+    // Fails with the info log and the location of its first error, when the driver reports one.
+    pub fn check_compile_status(&self) -> Result<(), PipelineError> {
+        unsafe {
             let mut compilation_status: GLint = 0;
 
-            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut compilation_status);
+            gl::GetShaderiv(self.id, gl::COMPILE_STATUS, &mut compilation_status);
 
             if compilation_status != gl::TRUE as i32 {
                 let mut message_size = 0;
 
-                gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut message_size);
+                gl::GetShaderiv(self.id, gl::INFO_LOG_LENGTH, &mut message_size);
 
                 let mut buffer = Vec::with_capacity(message_size as usize + 1); //+1 for nul termination
 
@@ -189,16 +221,64 @@ impl Shader {
                 let message = CString::from_vec_unchecked(buffer);
 
                 gl::GetShaderInfoLog(
-                    id,
+                    self.id,
                     message_size as i32,
                     ptr::null_mut(),
                     message.as_ptr() as *mut GLchar,
                 );
 
-                return Err(message.to_string_lossy().into_owned());
+                let log = message.to_string_lossy().into_owned();
+
+                return Err(PipelineError::Compile {
+                    stage: self.stage,
+                    path: self.path.clone(),
+                    location: LogLocation::parse(&log),
+                    log,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Includes are read from the directory of the shader, or taken from the embedded shaders if
+    // they are not there.
+    fn read_source(path: &Path) -> Result<String, String> {
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        expand_includes(&read_file(path)?, &|name| {
+            let include_path = directory.join(name);
+
+            if include_path.is_file() {
+                read_file(&include_path)
+            } else {
+                EmbeddedAssets::shader_include(name)
             }
+        })
+    }
+
+    fn compile_from_source<P: AsRef<Path> + Debug>(
+        stage: ShaderStage,
+        path: P,
+        text_source: String,
+    ) -> Shader {
+        let id: GLuint;
+        let c_string_source = CString::new(text_source).unwrap();
+
+        unsafe {
+            id = gl::CreateShader(stage as u32);
+
+            gl::ShaderSource(id, 1, &c_string_source.as_ptr(), ptr::null());
+
+            gl::CompileShader(id);
+        }
+
+        set_object_label(gl::SHADER, id, &path.as_ref().to_string_lossy());
 
-            Ok(Shader { id, stage })
+        Shader {
+            id,
+            stage,
+            path: path.as_ref().to_owned(),
         }
     }
 
@@ -209,6 +289,10 @@ impl Shader {
     pub fn get_stage(&self) -> ShaderStage {
         self.stage
     }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
 }
 
 impl Asset for Shader {
@@ -220,8 +304,74 @@ impl Asset for Shader {
         path: P,
         load_config: Option<Self::LoadConfig>,
     ) -> Result<Self::Output, Self::Error> {
-        Shader::new(load_config.unwrap(), path)
+        Shader::new(load_config.unwrap(), path).map_err(String::from)
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut text_source = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text_source))
+        .map_err(|e| format!("Failed to read shader {:?}: {}", path, e))?;
+
+    Ok(text_source)
+}
+
+// Replaces the #include "name" lines of the source with the sources read_include returns for
+// the names, expanded in turn. The GL_GOOGLE_include_directive extension, which glslangValidator
+// needs to compile the files to SPIR-V, is dropped since drivers do not know it. A #line
+// directive after each include keeps the lines of the info log those of the including file.
+pub fn expand_includes<F>(source: &str, read_include: &F) -> Result<String, String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
+    expand_includes_at_depth(source, read_include, 0)
+}
+
+fn expand_includes_at_depth<F>(
+    source: &str,
+    read_include: &F,
+    depth: usize,
+) -> Result<String, String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
+    let mut expanded = String::with_capacity(source.len());
+
+    for (i, line) in source.lines().enumerate() {
+        let directive = line.trim_start();
+
+        if directive.starts_with("#extension GL_GOOGLE_include_directive") {
+            expanded.push('\n');
+            continue;
+        }
+
+        let name = match directive.strip_prefix("#include") {
+            Some(name) => name.trim().trim_matches('"'),
+            None => {
+                expanded.push_str(line);
+                expanded.push('\n');
+                continue;
+            }
+        };
+
+        if depth == MAX_INCLUDE_DEPTH {
+            return Err(format!(
+                "Shader includes nest deeper than {} levels at {}.",
+                MAX_INCLUDE_DEPTH, name
+            ));
+        }
+
+        expanded.push_str(&expand_includes_at_depth(
+            &read_include(name)?,
+            read_include,
+            depth + 1,
+        )?);
+        expanded.push_str(&format!("#line {}\n", i + 2));
     }
+
+    Ok(expanded)
 }
 
 impl Drop for Shader {
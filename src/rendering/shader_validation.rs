@@ -0,0 +1,375 @@
+use crate::rendering::{
+    fog::FOG_BINDING,
+    light_culling_debug::LIGHT_CULLING_DEBUG_BINDING,
+    material, normal_visualizer,
+    per_draw::PER_DRAW_BINDING,
+    postprocess::tone_mapper,
+    sky::SKYBOX_MATRICES_BINDING,
+    terrain,
+    viewport::{FRAGMENT_PER_FRAME_BINDING, PER_VIEW_BINDING},
+};
+use std::{collections::HashMap, fmt, ops::RangeInclusive};
+
+// The texture units every stage is guaranteed to have, GL_MAX_TEXTURE_IMAGE_UNITS is at least 16.
+const ENGINE_TEXTURE_UNITS: RangeInclusive<u32> = 0..=15;
+
+#[derive(Debug, Clone)]
+pub struct BindingLayout {
+    // Materials reuse a binding for their own block, so a binding may accept several names.
+    uniform_blocks: HashMap<u32, Vec<String>>,
+    texture_units: RangeInclusive<u32>,
+}
+
+impl BindingLayout {
+    pub fn new(texture_units: RangeInclusive<u32>) -> Self {
+        Self {
+            uniform_blocks: HashMap::new(),
+            texture_units,
+        }
+    }
+
+    pub fn with_uniform_block(mut self, binding: u32, name: &str) -> Self {
+        self.uniform_blocks
+            .entry(binding)
+            .or_insert_with(Vec::new)
+            .push(name.to_string());
+        self
+    }
+
+    // The bindings the engine fills in, taken from the constants the uniform buffers are bound to.
+    pub fn engine() -> Self {
+        Self::new(ENGINE_TEXTURE_UNITS)
+            .with_uniform_block(PER_VIEW_BINDING, "PerFrameBlock")
+            .with_uniform_block(PER_VIEW_BINDING, "VertexPerFrameBlock")
+            .with_uniform_block(PER_DRAW_BINDING, "PerDrawBlock")
+            .with_uniform_block(FRAGMENT_PER_FRAME_BINDING, "PerFrameBlock")
+            .with_uniform_block(tone_mapper::UBO_BINDING_INDEX, "ToneMappingBlock")
+            .with_uniform_block(material::MATERIAL_UBO_BINDING_INDEX, "MaterialBlock")
+            .with_uniform_block(material::MATERIAL_UBO_BINDING_INDEX, "RefractionBlock")
+            .with_uniform_block(
+                terrain::material::MATERIAL_UBO_BINDING_INDEX,
+                "TerrainMaterialBlock",
+            )
+            .with_uniform_block(SKYBOX_MATRICES_BINDING, "MatricesBlock")
+            .with_uniform_block(
+                material::TESSELLATION_UBO_BINDING_INDEX,
+                "TessellationBlock",
+            )
+            .with_uniform_block(
+                normal_visualizer::UBO_BINDING_INDEX,
+                "NormalVisualizationBlock",
+            )
+            .with_uniform_block(FOG_BINDING, "FogBlock")
+            .with_uniform_block(LIGHT_CULLING_DEBUG_BINDING, "LightCullingDebugBlock")
+            .with_uniform_block(material::FOLIAGE_UBO_BINDING_INDEX, "FoliageBlock")
+    }
+
+    pub fn uniform_blocks(&self, binding: u32) -> Option<&[String]> {
+        self.uniform_blocks.get(&binding).map(Vec::as_slice)
+    }
+
+    pub fn texture_units(&self) -> &RangeInclusive<u32> {
+        &self.texture_units
+    }
+}
+
+impl Default for BindingLayout {
+    fn default() -> Self {
+        BindingLayout::engine()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderValidationError {
+    MissingVersion,
+    MissingUniformBlockBinding {
+        name: String,
+        line: u32,
+    },
+    UnknownUniformBlockBinding {
+        name: String,
+        binding: u32,
+        line: u32,
+    },
+    UniformBlockNameMismatch {
+        expected: String,
+        found: String,
+        binding: u32,
+        line: u32,
+    },
+    TextureUnitOutOfRange {
+        name: String,
+        unit: u32,
+        line: u32,
+    },
+    TextureUnitCollision {
+        first: String,
+        second: String,
+        unit: u32,
+        line: u32,
+    },
+}
+
+impl fmt::Display for ShaderValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderValidationError::MissingVersion => {
+                write!(f, "Shader does not start with a #version directive.")
+            }
+            ShaderValidationError::MissingUniformBlockBinding { name, line } => write!(
+                f,
+                "line {}: Uniform block {} has no explicit binding.",
+                line, name
+            ),
+            ShaderValidationError::UnknownUniformBlockBinding {
+                name,
+                binding,
+                line,
+            } => write!(
+                f,
+                "line {}: Uniform block {} uses binding {} which is not part of the engine binding layout.",
+                line, name, binding
+            ),
+            ShaderValidationError::UniformBlockNameMismatch {
+                expected,
+                found,
+                binding,
+                line,
+            } => write!(
+                f,
+                "line {}: Uniform block binding {} is reserved for {}, found {}.",
+                line, binding, expected, found
+            ),
+            ShaderValidationError::TextureUnitOutOfRange { name, unit, line } => write!(
+                f,
+                "line {}: Sampler {} uses texture unit {} which is not part of the engine binding layout.",
+                line, name, unit
+            ),
+            ShaderValidationError::TextureUnitCollision {
+                first,
+                second,
+                unit,
+                line,
+            } => write!(
+                f,
+                "line {}: Samplers {} and {} are both bound to texture unit {}.",
+                line, first, second, unit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderValidationError {}
+
+// Replaces comments with whitespace. Newlines are kept so line numbers still match the original
+// source.
+pub fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                while let Some(next) = chars.next() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    if next == '\n' {
+                        stripped.push('\n');
+                    }
+                    previous = next;
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+pub fn validate(source: &str, layout: &BindingLayout) -> Result<(), ShaderValidationError> {
+    let source = strip_comments(source);
+
+    if !source.trim_start().starts_with("#version") {
+        return Err(ShaderValidationError::MissingVersion);
+    }
+
+    let tokens = tokenize(&source);
+    let mut texture_units: HashMap<u32, String> = HashMap::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let declaration_start = i;
+        let mut binding = None;
+
+        if tokens[i].text == "layout" {
+            let (layout_binding, next) = parse_layout_qualifier(&tokens, i + 1);
+            binding = layout_binding;
+            i = next;
+        }
+
+        let mut is_uniform = false;
+        while i < tokens.len() && is_qualifier(tokens[i].text) {
+            is_uniform |= tokens[i].text == "uniform";
+            i += 1;
+        }
+
+        if !is_uniform || i + 1 >= tokens.len() {
+            i = declaration_start + 1;
+            continue;
+        }
+
+        let type_or_block = &tokens[i];
+        let next = &tokens[i + 1];
+
+        if next.text == "{" {
+            let name = type_or_block.text.to_string();
+            let line = type_or_block.line;
+
+            match binding {
+                None => {
+                    return Err(ShaderValidationError::MissingUniformBlockBinding { name, line })
+                }
+                Some(binding) => match layout.uniform_blocks(binding) {
+                    None => {
+                        return Err(ShaderValidationError::UnknownUniformBlockBinding {
+                            name,
+                            binding,
+                            line,
+                        })
+                    }
+                    Some(expected) if !expected.contains(&name) => {
+                        return Err(ShaderValidationError::UniformBlockNameMismatch {
+                            expected: expected.join(" or "),
+                            found: name,
+                            binding,
+                            line,
+                        })
+                    }
+                    _ => {}
+                },
+            }
+        } else if is_sampler_type(type_or_block.text) {
+            let name = next.text.to_string();
+            let line = next.line;
+
+            // Samplers without an explicit binding default to unit 0.
+            let unit = binding.unwrap_or(0);
+
+            if !layout.texture_units().contains(&unit) {
+                return Err(ShaderValidationError::TextureUnitOutOfRange { name, unit, line });
+            }
+
+            if let Some(first) = texture_units.get(&unit) {
+                return Err(ShaderValidationError::TextureUnitCollision {
+                    first: first.clone(),
+                    second: name,
+                    unit,
+                    line,
+                });
+            }
+
+            texture_units.insert(unit, name);
+        }
+
+        i += 1;
+    }
+
+    Ok(())
+}
+
+struct Token<'a> {
+    text: &'a str,
+    line: u32,
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index as u32 + 1;
+
+        // Preprocessor directives carry no declarations we care about.
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut start = None;
+
+        for (i, c) in line.char_indices() {
+            if c.is_alphanumeric() || c == '_' {
+                if start.is_none() {
+                    start = Some(i);
+                }
+                continue;
+            }
+
+            if let Some(s) = start.take() {
+                tokens.push(Token {
+                    text: &line[s..i],
+                    line: line_number,
+                });
+            }
+
+            if !c.is_whitespace() {
+                tokens.push(Token {
+                    text: &line[i..i + c.len_utf8()],
+                    line: line_number,
+                });
+            }
+        }
+
+        if let Some(s) = start {
+            tokens.push(Token {
+                text: &line[s..],
+                line: line_number,
+            });
+        }
+    }
+
+    tokens
+}
+
+// Parses "( ..., binding = N, ... )" starting at the opening parenthesis. Returns the binding, if
+// any, and the index of the first token after the closing parenthesis.
+fn parse_layout_qualifier(tokens: &[Token<'_>], mut i: usize) -> (Option<u32>, usize) {
+    let mut binding = None;
+
+    if tokens.get(i).map(|t| t.text) != Some("(") {
+        return (None, i);
+    }
+
+    while i < tokens.len() && tokens[i].text != ")" {
+        if tokens[i].text == "binding" && tokens.get(i + 1).map(|t| t.text) == Some("=") {
+            binding = tokens.get(i + 2).and_then(|t| t.text.parse().ok());
+        }
+        i += 1;
+    }
+
+    (binding, i + 1)
+}
+
+fn is_qualifier(token: &str) -> bool {
+    match token {
+        "uniform" | "buffer" | "in" | "out" | "inout" | "const" | "shared" | "readonly"
+        | "writeonly" | "coherent" | "volatile" | "restrict" | "highp" | "mediump" | "lowp"
+        | "flat" | "smooth" | "noperspective" | "centroid" | "sample" | "patch" | "invariant"
+        | "precise" => true,
+        _ => false,
+    }
+}
+
+fn is_sampler_type(token: &str) -> bool {
+    token.starts_with("sampler") || token.starts_with("isampler") || token.starts_with("usampler")
+}
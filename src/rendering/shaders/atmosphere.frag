@@ -0,0 +1,302 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// The look up tables of A Scalable and Production Ready Sky and Atmosphere Rendering Technique
+// (Hillaire 2020). Distances are in km, the planet center is at the origin and up is +Y.
+const int TRANSMITTANCE_LUT = 0;
+const int MULTIPLE_SCATTERING_LUT = 1;
+const int SKY_VIEW_LUT = 2;
+
+layout(binding = 0) uniform sampler2D transmittanceLut;
+layout(binding = 1) uniform sampler2D multipleScatteringLut;
+
+uniform int lut;
+
+uniform float bottomRadius;
+uniform float topRadius;
+uniform vec3 rayleighScattering;
+uniform float rayleighDensityHeight;
+uniform float mieScattering;
+uniform float mieAbsorption;
+uniform float mieDensityHeight;
+uniform float mieG;
+uniform vec3 ozoneAbsorption;
+uniform vec3 groundAlbedo;
+uniform float viewHeight;
+uniform vec3 sunDirection;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+const int TRANSMITTANCE_STEPS = 40;
+const int MULTIPLE_SCATTERING_STEPS = 20;
+const int MULTIPLE_SCATTERING_DIRECTIONS = 8;
+const int SKY_VIEW_STEPS = 32;
+
+// Distance to the sphere around the planet center along the ray, -1 if it is missed.
+float RaySphere(vec3 origin, vec3 direction, float radius)
+{
+    float b = dot(origin, direction);
+    float c = dot(origin, origin) - radius * radius;
+    if (c > 0.0 && b > 0.0) {
+        return -1.0;
+    }
+
+    float discriminant = b * b - c;
+    if (discriminant < 0.0) {
+        return -1.0;
+    }
+
+    float root = sqrt(discriminant);
+    return -b - root >= 0.0 ? -b - root : -b + root;
+}
+
+void Medium(vec3 position, out vec3 rayleigh, out float mie, out vec3 extinction)
+{
+    float height = max(length(position) - bottomRadius, 0.0);
+
+    rayleigh = rayleighScattering * exp(-height / rayleighDensityHeight);
+    mie = mieScattering * exp(-height / mieDensityHeight);
+
+    float mieExtinction = (mieScattering + mieAbsorption) * exp(-height / mieDensityHeight);
+    vec3 ozone = ozoneAbsorption * max(0.0, 1.0 - abs(height - 25.0) / 15.0);
+
+    extinction = rayleigh + mieExtinction + ozone;
+}
+
+float RayleighPhase(float cosTheta)
+{
+    return 3.0 / (16.0 * PI) * (1.0 + cosTheta * cosTheta);
+}
+
+// Cornette-Shanks.
+float MiePhase(float cosTheta)
+{
+    float g2 = mieG * mieG;
+    float denominator = 1.0 + g2 - 2.0 * mieG * cosTheta;
+    return 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + cosTheta * cosTheta))
+         / ((2.0 + g2) * denominator * sqrt(denominator));
+}
+
+// The parameterization of Bruneton and Neyret 2008, rays are cut off at the horizon.
+void TransmittanceUvToHeightAndCosZenith(vec2 uv, out float r, out float mu)
+{
+    float H = sqrt(topRadius * topRadius - bottomRadius * bottomRadius);
+    float rho = H * uv.y;
+    r = sqrt(rho * rho + bottomRadius * bottomRadius);
+
+    float dMin = topRadius - r;
+    float dMax = rho + H;
+    float d = dMin + uv.x * (dMax - dMin);
+
+    mu = d == 0.0 ? 1.0 : (H * H - rho * rho - d * d) / (2.0 * r * d);
+    mu = clamp(mu, -1.0, 1.0);
+}
+
+vec2 HeightAndCosZenithToTransmittanceUv(float r, float mu)
+{
+    float H = sqrt(topRadius * topRadius - bottomRadius * bottomRadius);
+    float rho = sqrt(max(r * r - bottomRadius * bottomRadius, 0.0));
+
+    float discriminant = r * r * (mu * mu - 1.0) + topRadius * topRadius;
+    float d = max(-r * mu + sqrt(max(discriminant, 0.0)), 0.0);
+
+    float dMin = topRadius - r;
+    float dMax = rho + H;
+
+    return vec2((d - dMin) / (dMax - dMin), rho / H);
+}
+
+// Transmittance towards the sun, 0 in the shadow of the planet.
+vec3 SunTransmittance(vec3 position, vec3 sun)
+{
+    if (RaySphere(position, sun, bottomRadius) > 0.0) {
+        return vec3(0.0);
+    }
+
+    float r = length(position);
+    vec2 uv = HeightAndCosZenithToTransmittanceUv(r, dot(position / r, sun));
+    return texture(transmittanceLut, uv).rgb;
+}
+
+vec3 MultipleScattering(vec3 position, vec3 sun)
+{
+    float r = length(position);
+    vec2 uv = vec2(dot(position / r, sun) * 0.5 + 0.5,
+                   (r - bottomRadius) / (topRadius - bottomRadius));
+    return texture(multipleScatteringLut, uv).rgb;
+}
+
+vec3 Transmittance()
+{
+    float r;
+    float mu;
+    TransmittanceUvToHeightAndCosZenith(fsIn.texcoord, r, mu);
+
+    vec3 origin = vec3(0.0, r, 0.0);
+    vec3 direction = vec3(sqrt(1.0 - mu * mu), mu, 0.0);
+    float distance = RaySphere(origin, direction, topRadius);
+
+    vec3 opticalDepth = vec3(0.0);
+    float dt = distance / float(TRANSMITTANCE_STEPS);
+
+    for (int i = 0; i < TRANSMITTANCE_STEPS; ++i) {
+        vec3 rayleigh;
+        float mie;
+        vec3 extinction;
+        Medium(origin + (float(i) + 0.5) * dt * direction, rayleigh, mie, extinction);
+        opticalDepth += extinction * dt;
+    }
+
+    return exp(-opticalDepth);
+}
+
+// The isotropic second order scattering and the transfer factor of every order after it,
+// summed as a geometric series.
+vec3 MultipleScatteringTransfer()
+{
+    float cosSunZenith = fsIn.texcoord.x * 2.0 - 1.0;
+    vec3 sun = vec3(sqrt(clamp(1.0 - cosSunZenith * cosSunZenith, 0.0, 1.0)), cosSunZenith, 0.0);
+    vec3 origin = vec3(0.0, mix(bottomRadius + 0.001, topRadius - 0.001, fsIn.texcoord.y), 0.0);
+
+    const float isotropicPhase = 1.0 / (4.0 * PI);
+    const float directionCount = float(MULTIPLE_SCATTERING_DIRECTIONS * MULTIPLE_SCATTERING_DIRECTIONS);
+
+    vec3 secondOrder = vec3(0.0);
+    vec3 transfer = vec3(0.0);
+
+    for (int i = 0; i < MULTIPLE_SCATTERING_DIRECTIONS; ++i) {
+        for (int j = 0; j < MULTIPLE_SCATTERING_DIRECTIONS; ++j) {
+            // Uniformly distributed over the sphere.
+            float theta = 2.0 * PI * (float(i) + 0.5) / float(MULTIPLE_SCATTERING_DIRECTIONS);
+            float phi = acos(1.0 - 2.0 * (float(j) + 0.5) / float(MULTIPLE_SCATTERING_DIRECTIONS));
+            vec3 direction = vec3(cos(theta) * sin(phi), cos(phi), sin(theta) * sin(phi));
+
+            float groundDistance = RaySphere(origin, direction, bottomRadius);
+            float distance = groundDistance > 0.0 ? groundDistance
+                                                  : RaySphere(origin, direction, topRadius);
+
+            vec3 luminance = vec3(0.0);
+            vec3 luminanceFactor = vec3(0.0);
+            vec3 throughput = vec3(1.0);
+            float t = 0.0;
+
+            for (int step = 0; step < MULTIPLE_SCATTERING_STEPS; ++step) {
+                float nextT = distance * (float(step) + 0.3) / float(MULTIPLE_SCATTERING_STEPS);
+                float dt = nextT - t;
+                t = nextT;
+
+                vec3 position = origin + t * direction;
+
+                vec3 rayleigh;
+                float mie;
+                vec3 extinction;
+                Medium(position, rayleigh, mie, extinction);
+
+                vec3 sampleTransmittance = exp(-dt * extinction);
+                vec3 scattering = rayleigh + mie;
+
+                // Energy conserving integration over the step, Hillaire 2015.
+                vec3 scatteringIntegral = (scattering - scattering * sampleTransmittance) / extinction;
+                luminanceFactor += throughput * scatteringIntegral;
+
+                vec3 inScattering = scattering * isotropicPhase * SunTransmittance(position, sun);
+                luminance += throughput * (inScattering - inScattering * sampleTransmittance) / extinction;
+
+                throughput *= sampleTransmittance;
+            }
+
+            if (groundDistance > 0.0) {
+                vec3 ground = normalize(origin + groundDistance * direction) * bottomRadius;
+                float cosSun = clamp(dot(normalize(ground), sun), 0.0, 1.0);
+                luminance += throughput * groundAlbedo / PI * cosSun * SunTransmittance(ground * 1.0001, sun);
+            }
+
+            secondOrder += luminance / directionCount;
+            transfer += luminanceFactor / directionCount;
+        }
+    }
+
+    return secondOrder / (1.0 - transfer);
+}
+
+// Luminance of the sky for a unit sun illuminance, by view zenith and azimuth from the sun.
+vec3 SkyView()
+{
+    vec3 origin = vec3(0.0, viewHeight, 0.0);
+
+    // Half of the texture covers the sky above the horizon, more precise towards it.
+    float horizonCos = sqrt(max(viewHeight * viewHeight - bottomRadius * bottomRadius, 0.0)) / viewHeight;
+    float beta = acos(horizonCos);
+    float zenithHorizonAngle = PI - beta;
+
+    float viewZenith;
+    if (fsIn.texcoord.y < 0.5) {
+        float coordinate = 1.0 - 2.0 * fsIn.texcoord.y;
+        viewZenith = zenithHorizonAngle * (1.0 - coordinate * coordinate);
+    } else {
+        float coordinate = fsIn.texcoord.y * 2.0 - 1.0;
+        viewZenith = zenithHorizonAngle + beta * coordinate * coordinate;
+    }
+
+    float azimuth = fsIn.texcoord.x * PI;
+
+    vec3 direction = vec3(sin(viewZenith) * cos(azimuth), cos(viewZenith), sin(viewZenith) * sin(azimuth));
+    vec3 sun = normalize(sunDirection);
+    sun = vec3(length(sun.xz), sun.y, 0.0);
+
+    float groundDistance = RaySphere(origin, direction, bottomRadius);
+    float distance = groundDistance > 0.0 ? groundDistance : RaySphere(origin, direction, topRadius);
+
+    float cosTheta = dot(direction, sun);
+    float rayleighPhase = RayleighPhase(cosTheta);
+    float miePhase = MiePhase(cosTheta);
+
+    vec3 luminance = vec3(0.0);
+    vec3 throughput = vec3(1.0);
+    float t = 0.0;
+
+    for (int step = 0; step < SKY_VIEW_STEPS; ++step) {
+        float nextT = distance * (float(step) + 0.3) / float(SKY_VIEW_STEPS);
+        float dt = nextT - t;
+        t = nextT;
+
+        vec3 position = origin + t * direction;
+
+        vec3 rayleigh;
+        float mie;
+        vec3 extinction;
+        Medium(position, rayleigh, mie, extinction);
+
+        vec3 sampleTransmittance = exp(-dt * extinction);
+        vec3 sunTransmittance = SunTransmittance(position, sun);
+        vec3 multipleScattering = MultipleScattering(position, sun);
+
+        vec3 inScattering = rayleigh * (rayleighPhase * sunTransmittance + multipleScattering)
+                          + mie * (miePhase * sunTransmittance + multipleScattering);
+
+        luminance += throughput * (inScattering - inScattering * sampleTransmittance) / extinction;
+        throughput *= sampleTransmittance;
+    }
+
+    return luminance;
+}
+
+void main()
+{
+    vec3 color;
+
+    if (lut == TRANSMITTANCE_LUT) {
+        color = Transmittance();
+    } else if (lut == MULTIPLE_SCATTERING_LUT) {
+        color = MultipleScatteringTransfer();
+    } else {
+        color = SkyView();
+    }
+
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in VsOut {
+    vec4 color;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    outColor = fsIn.color;
+}
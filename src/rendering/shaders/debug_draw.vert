@@ -0,0 +1,26 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 4) in vec4 inColor;
+
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(location = 0) out VsOut {
+    vec4 color;
+} vsOut;
+
+void main()
+{
+    // Debug primitives are submitted in world space.
+    gl_Position = view_projection * vec4(inPosition, 1.0);
+    vsOut.color = inColor;
+}
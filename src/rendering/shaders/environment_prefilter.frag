@@ -0,0 +1,84 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// GGX convolution of an environment cube map for a level of the radiance map of the specular
+// IBL term, with the view along the normal.
+layout(binding = 0) uniform samplerCube environmentMap;
+
+uniform mat4 inverseViewProjection;
+uniform float perceptualRoughness;
+// Of the faces of the environment map at level 0.
+uniform float environmentSize;
+
+layout(location = 0) in VsOut {
+    vec2 ndc;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 256u;
+
+vec2 Hammersley(uint i)
+{
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(SAMPLE_COUNT), float(bits) * 2.3283064365386963e-10);
+}
+
+float DistributionGGX(float NdotH, float alpha)
+{
+    float alpha2 = alpha * alpha;
+    float denominator = NdotH * NdotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * denominator * denominator);
+}
+
+vec3 ImportanceSampleGGX(vec2 xi, vec3 normal, float alpha)
+{
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+
+    return normalize(tangent * sinTheta * cos(phi) + bitangent * sinTheta * sin(phi)
+                   + normal * cosTheta);
+}
+
+void main()
+{
+    vec4 position = inverseViewProjection * vec4(fsIn.ndc, 1.0, 1.0);
+    vec3 normal = normalize(position.xyz / position.w);
+
+    if (perceptualRoughness == 0.0) {
+        outColor = vec4(textureLod(environmentMap, normal, 0.0).rgb, 1.0);
+        return;
+    }
+
+    float alpha = perceptualRoughness * perceptualRoughness;
+    // Solid angle of a texel of the environment map.
+    float texelSolidAngle = 4.0 * PI / (6.0 * environmentSize * environmentSize);
+
+    vec3 radiance = vec3(0.0);
+    float totalWeight = 0.0;
+
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 halfway = ImportanceSampleGGX(Hammersley(i), normal, alpha);
+        vec3 light = normalize(2.0 * dot(normal, halfway) * halfway - normal);
+
+        float NdotL = dot(normal, light);
+        if (NdotL > 0.0) {
+            // Samples of a wide lobe read lower mips, which hides the undersampling.
+            float NdotH = max(dot(normal, halfway), 0.0);
+            float pdf = DistributionGGX(NdotH, alpha) * 0.25 + 0.0001;
+            float sampleSolidAngle = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+            float lod = 0.5 * log2(sampleSolidAngle / texelSolidAngle);
+
+            radiance += textureLod(environmentMap, light, max(lod, 0.0)).rgb * NdotL;
+            totalWeight += NdotL;
+        }
+    }
+
+    outColor = vec4(radiance / max(totalWeight, 0.0001), 1.0);
+}
@@ -0,0 +1,34 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Projects an equirectangular panorama onto the faces of a cube map.
+layout(binding = 0) uniform sampler2D equirectangularMap;
+
+uniform mat4 inverseViewProjection;
+// Of the panorama around the vertical axis, in radians.
+uniform float rotation;
+uniform float intensity;
+
+layout(location = 0) in VsOut {
+    vec2 ndc;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+
+void main()
+{
+    vec4 position = inverseViewProjection * vec4(fsIn.ndc, 1.0, 1.0);
+    vec3 direction = normalize(position.xyz / position.w);
+
+    float c = cos(rotation);
+    float s = sin(rotation);
+    direction = vec3(c * direction.x - s * direction.z, direction.y, s * direction.x + c * direction.z);
+
+    // The first row of the image is the top of the panorama.
+    vec2 uv = vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5,
+                   0.5 - asin(clamp(direction.y, -1.0, 1.0)) / PI);
+
+    outColor = vec4(intensity * textureLod(equirectangularMap, uv, 0.0).rgb, 1.0);
+}
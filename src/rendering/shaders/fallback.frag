@@ -0,0 +1,20 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in VsOut {
+    vec3 wViewDirection;
+    vec3 wNormal;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    // Flat grey headlight shading. Used while the real pipeline is still compiling.
+    vec3 n = normalize(fsIn.wNormal);
+    vec3 v = normalize(fsIn.wViewDirection);
+
+    float nDotV = max(dot(n, v), 0.0);
+
+    outColor = vec4(vec3(0.1 + 0.6 * nDotV), 1.0);
+}
@@ -0,0 +1,36 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+layout(std140, binding = 1) uniform PerDrawBlock
+{
+    mat4 model;
+    mat4 normalMatrix;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(location = 0) out VsOut {
+    vec3 wViewDirection;
+    vec3 wNormal;
+} vsOut;
+
+void main()
+{
+    vec4 wPosition = model * vec4(inPosition, 1.0);
+
+    vsOut.wViewDirection = eyePosition.xyz - wPosition.xyz;
+    vsOut.wNormal = mat3(normalMatrix) * inNormal;
+
+    gl_Position = view_projection * wPosition;
+}
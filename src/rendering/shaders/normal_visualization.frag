@@ -0,0 +1,15 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(std140, binding = 7) uniform NormalVisualizationBlock
+{
+    vec4 color;
+    float normalLength;
+};
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    outColor = color;
+}
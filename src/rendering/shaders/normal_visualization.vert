@@ -0,0 +1,26 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+
+layout(std140, binding = 1) uniform PerDrawBlock
+{
+    mat4 model;
+    mat4 normalMatrix;
+};
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(location = 0) out VsOut {
+    vec3 wNormal;
+} vsOut;
+
+void main()
+{
+    // World space position. Projection is applied in the geometry stage.
+    gl_Position = model * vec4(inPosition, 1.0);
+    vsOut.wNormal = normalize(mat3(normalMatrix) * inNormal);
+}
@@ -0,0 +1,12 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// 0 is reserved for the background.
+uniform uint objectId;
+
+layout(location = 0) out uint outId;
+
+void main()
+{
+    outId = objectId;
+}
@@ -0,0 +1,64 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Refracts the captured opaque scene color. The refracted ray is followed for thickness world
+// units and projected back to the screen to find the background texel. The distortion map
+// adds a scrolling screen space offset on top, e.g. for heat haze.
+layout(std140, binding = 0) uniform PerFrameBlock
+{
+    mat4 view_projection;
+    vec4 eyePosition;
+};
+
+layout(std140, binding = 4) uniform RefractionBlock
+{
+    vec4 tint;
+    vec2 distortionScroll;
+    float indexOfRefraction;
+    float thickness;
+    float roughness;
+    float distortionStrength;
+    float time;
+    int hasDistortionMap;
+};
+
+layout(binding = 1) uniform sampler2D distortionMap;
+layout(binding = 8) uniform sampler2D sceneColor;
+
+layout(location = 0) in VsOut {
+    vec3 wPosition;
+    vec3 wNormal;
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    vec3 n = normalize(fsIn.wNormal);
+    vec3 v = normalize(eyePosition.xyz - fsIn.wPosition);
+
+    if (!gl_FrontFacing) {
+        n = -n;
+    }
+
+    vec3 r = refract(-v, n, 1.0 / max(indexOfRefraction, 0.001));
+
+    // Total internal reflection, look straight through.
+    if (dot(r, r) == 0.0) {
+        r = -v;
+    }
+
+    vec4 clip = view_projection * vec4(fsIn.wPosition + r * thickness, 1.0);
+    vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+
+    if (hasDistortionMap == 1) {
+        vec2 distortion = texture(distortionMap, fsIn.texcoord + distortionScroll * time).xy * 2.0 - 1.0;
+        uv += distortion * distortionStrength;
+    }
+
+    float lod = roughness * float(textureQueryLevels(sceneColor) - 1);
+    vec3 color = textureLod(sceneColor, clamp(uv, 0.0, 1.0), lod).rgb;
+
+    outColor = vec4(color * tint.rgb, 1.0);
+}
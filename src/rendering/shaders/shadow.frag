@@ -0,0 +1,7 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Shadow maps only need the depth of the occluders.
+void main()
+{
+}
@@ -0,0 +1,169 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+const int SOURCE_CUBEMAP = 0;
+const int SOURCE_PREETHAM = 1;
+const int SOURCE_ATMOSPHERE = 2;
+
+layout(binding = 0) uniform samplerCube environmentMap;
+layout(binding = 1) uniform sampler2D skyViewLut;
+layout(binding = 2) uniform sampler2D transmittanceLut;
+
+// Of the view projection without the camera translation.
+uniform mat4 inverseViewProjection;
+uniform int source;
+
+// Preetham sky parameters. sunDirection points towards the sun.
+uniform vec3 sunDirection;
+uniform float turbidity;
+uniform float intensity;
+uniform int sunDisk;
+
+// Atmosphere parameters, in km. See atmosphere.frag for the look up tables.
+uniform float bottomRadius;
+uniform float topRadius;
+uniform float viewHeight;
+uniform vec3 sunIlluminance;
+
+layout(location = 0) in VsOut {
+    vec2 ndc;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+// Angular radius of the sun in radians.
+const float SUN_ANGULAR_RADIUS = 0.00465;
+
+// A Practical Analytic Model for Daylight (Preetham et al. 1999).
+float Perez(float theta, float gamma, float A, float B, float C, float D, float E)
+{
+    float cosGamma = cos(gamma);
+    return (1.0 + A * exp(B / cos(theta))) * (1.0 + C * exp(D * gamma) + E * cosGamma * cosGamma);
+}
+
+float PerezRatio(float theta, float gamma, float thetaSun, vec3 abc, vec2 de)
+{
+    return Perez(theta, gamma, abc.x, abc.y, abc.z, de.x, de.y)
+         / Perez(0.0, thetaSun, abc.x, abc.y, abc.z, de.x, de.y);
+}
+
+vec3 Preetham(vec3 direction)
+{
+    vec3 sun = normalize(sunDirection);
+    float T = turbidity;
+
+    // The model is undefined below the horizon, it is continued with the horizon color.
+    float theta = acos(clamp(direction.y, 0.001, 1.0));
+    float thetaSun = acos(clamp(sun.y, 0.0, 1.0));
+    float gamma = acos(clamp(dot(direction, sun), -1.0, 1.0));
+
+    float chi = (4.0 / 9.0 - T / 120.0) * (PI - 2.0 * thetaSun);
+    float zenithY = (4.0453 * T - 4.9710) * tan(chi) - 0.2155 * T + 2.4192;
+
+    vec4 thetas = vec4(thetaSun * thetaSun * thetaSun, thetaSun * thetaSun, thetaSun, 1.0);
+    float zenithX = T * T * dot(vec4(0.00166, -0.00375, 0.00209, 0.0), thetas)
+                  + T * dot(vec4(-0.02903, 0.06377, -0.03202, 0.00394), thetas)
+                  + dot(vec4(0.11693, -0.21196, 0.06052, 0.25886), thetas);
+    float zenithy = T * T * dot(vec4(0.00275, -0.00610, 0.00317, 0.0), thetas)
+                  + T * dot(vec4(-0.04214, 0.08970, -0.04153, 0.00516), thetas)
+                  + dot(vec4(0.15346, -0.26756, 0.06670, 0.26688), thetas);
+
+    float Y = zenithY * PerezRatio(theta, gamma, thetaSun,
+        vec3(0.1787 * T - 1.4630, -0.3554 * T + 0.4275, -0.0227 * T + 5.3251),
+        vec2(0.1206 * T - 2.5771, -0.0670 * T + 0.3703));
+    float x = zenithX * PerezRatio(theta, gamma, thetaSun,
+        vec3(-0.0193 * T - 0.2592, -0.0665 * T + 0.0008, -0.0004 * T + 0.2125),
+        vec2(-0.0641 * T - 0.8989, -0.0033 * T + 0.0452));
+    float y = zenithy * PerezRatio(theta, gamma, thetaSun,
+        vec3(-0.0167 * T - 0.2608, -0.0950 * T + 0.0092, -0.0079 * T + 0.2102),
+        vec2(-0.0441 * T - 1.6537, -0.0109 * T + 0.0529));
+
+    // xyY to XYZ to linear sRGB. Y is in kcd/m^2.
+    vec3 XYZ = vec3(x / y * Y, Y, (1.0 - x - y) / y * Y);
+    vec3 color = mat3(3.2406, -0.9689, 0.0557,
+                      -1.5372, 1.8758, -0.2040,
+                      -0.4986, 0.0415, 1.0570) * XYZ;
+
+    if (sunDisk != 0 && gamma < SUN_ANGULAR_RADIUS && direction.y > 0.0) {
+        color *= 100.0;
+    }
+
+    // Darker ground below the horizon.
+    if (direction.y < 0.0) {
+        color *= mix(1.0, 0.3, clamp(-direction.y * 8.0, 0.0, 1.0));
+    }
+
+    return max(color, 0.0) * intensity;
+}
+
+// The inverse of the sky view parameterization of atmosphere.frag.
+vec2 SkyViewUv(vec3 direction, vec3 sun)
+{
+    float horizonCos = sqrt(max(viewHeight * viewHeight - bottomRadius * bottomRadius, 0.0)) / viewHeight;
+    float beta = acos(horizonCos);
+    float zenithHorizonAngle = PI - beta;
+    float viewZenith = acos(clamp(direction.y, -1.0, 1.0));
+
+    float v;
+    if (viewZenith < zenithHorizonAngle) {
+        float coordinate = sqrt(1.0 - viewZenith / zenithHorizonAngle);
+        v = (1.0 - coordinate) * 0.5;
+    } else {
+        float coordinate = sqrt((viewZenith - zenithHorizonAngle) / beta);
+        v = (coordinate + 1.0) * 0.5;
+    }
+
+    // The azimuth from the sun, the sky is symmetric around the plane of the sun.
+    vec2 horizontal = direction.xz;
+    vec2 sunHorizontal = sun.xz;
+    float cosAzimuth = 1.0;
+    if (dot(horizontal, horizontal) > 1e-8 && dot(sunHorizontal, sunHorizontal) > 1e-8) {
+        cosAzimuth = dot(normalize(horizontal), normalize(sunHorizontal));
+    }
+
+    return vec2(acos(clamp(cosAzimuth, -1.0, 1.0)) / PI, v);
+}
+
+vec2 TransmittanceUv(float r, float mu)
+{
+    float H = sqrt(topRadius * topRadius - bottomRadius * bottomRadius);
+    float rho = sqrt(max(r * r - bottomRadius * bottomRadius, 0.0));
+
+    float discriminant = r * r * (mu * mu - 1.0) + topRadius * topRadius;
+    float d = max(-r * mu + sqrt(max(discriminant, 0.0)), 0.0);
+
+    float dMin = topRadius - r;
+    float dMax = rho + H;
+
+    return vec2((d - dMin) / (dMax - dMin), rho / H);
+}
+
+vec3 Atmosphere(vec3 direction)
+{
+    vec3 sun = normalize(sunDirection);
+    vec3 color = texture(skyViewLut, SkyViewUv(direction, sun)).rgb;
+
+    // Not the luminance of the actual sun, which would swamp the captured lighting.
+    float horizonCos = -sqrt(max(viewHeight * viewHeight - bottomRadius * bottomRadius, 0.0)) / viewHeight;
+    if (sunDisk != 0 && acos(clamp(dot(direction, sun), -1.0, 1.0)) < SUN_ANGULAR_RADIUS
+        && direction.y > horizonCos) {
+        color += texture(transmittanceLut, TransmittanceUv(viewHeight, direction.y)).rgb * 100.0;
+    }
+
+    return color * sunIlluminance;
+}
+
+void main()
+{
+    vec4 position = inverseViewProjection * vec4(fsIn.ndc, 1.0, 1.0);
+    vec3 direction = normalize(position.xyz / position.w);
+
+    if (source == SOURCE_PREETHAM) {
+        outColor = vec4(Preetham(direction), 1.0);
+    } else if (source == SOURCE_ATMOSPHERE) {
+        outColor = vec4(Atmosphere(direction), 1.0);
+    } else {
+        outColor = vec4(texture(environmentMap, direction).rgb, 1.0);
+    }
+}
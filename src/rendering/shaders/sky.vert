@@ -0,0 +1,19 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(location = 0) out VsOut {
+    vec2 ndc;
+} vsOut;
+
+void main()
+{
+    vec2 texcoord = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    vsOut.ndc = texcoord * 2.0 - 1.0;
+
+    // On the far plane, so only pixels nothing was drawn to pass a less or equal depth test.
+    gl_Position = vec4(vsOut.ndc, 1.0, 1.0);
+}
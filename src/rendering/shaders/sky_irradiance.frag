@@ -0,0 +1,45 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Cosine convolution of a sky capture into an irradiance map for the diffuse IBL term.
+layout(binding = 0) uniform samplerCube environmentMap;
+
+uniform mat4 inverseViewProjection;
+// Lower mips hide the undersampling of the convolution.
+uniform float sampleLod;
+
+layout(location = 0) in VsOut {
+    vec2 ndc;
+} fsIn;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.05;
+
+void main()
+{
+    vec4 position = inverseViewProjection * vec4(fsIn.ndc, 1.0, 1.0);
+    vec3 normal = normalize(position.xyz / position.w);
+
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    vec3 irradiance = vec3(0.0);
+    float sampleCount = 0.0;
+
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangentSample.x * right + tangentSample.y * up + tangentSample.z * normal;
+
+            irradiance += textureLod(environmentMap, direction, sampleLod).rgb
+                        * cos(theta) * sin(theta);
+            sampleCount += 1.0;
+        }
+    }
+
+    // Irradiance over pi, the way the prefiltered irradiance maps of the examples store it.
+    outColor = vec4(PI * irradiance / sampleCount, 1.0);
+}
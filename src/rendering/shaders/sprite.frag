@@ -0,0 +1,16 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+    vec4 color;
+} fsIn;
+
+layout(binding = 0) uniform sampler2D spriteTexture;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    outColor = texture(spriteTexture, fsIn.texcoord) * fsIn.color;
+}
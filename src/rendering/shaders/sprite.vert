@@ -0,0 +1,28 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 3) in vec2 inTexcoord;
+layout(location = 4) in vec4 inColor;
+
+// In pixels.
+uniform vec2 screenSize;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(location = 0) out VsOut {
+    vec2 texcoord;
+    vec4 color;
+} vsOut;
+
+void main()
+{
+    // Sprites are submitted in pixels from the top left corner of the screen.
+    vec2 ndc = inPosition / screenSize * 2.0 - 1.0;
+    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+
+    vsOut.texcoord = inTexcoord;
+    vsOut.color = inColor;
+}
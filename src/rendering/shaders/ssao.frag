@@ -0,0 +1,89 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Normal oriented hemisphere SSAO. Works in view space, with the position reconstructed from
+// depth and the world space normals of the prepass rotated into view space.
+layout(binding = 0) uniform sampler2D depthMap;
+layout(binding = 1) uniform sampler2D normalMap;
+
+uniform mat4 projection;
+uniform mat4 inverseProjection;
+uniform mat4 view;
+uniform float radius;
+uniform float bias;
+uniform float intensity;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out float outOcclusion;
+
+const int SAMPLE_COUNT = 16;
+const float GOLDEN_ANGLE = 2.39996323;
+
+vec3 ViewPosition(vec2 uv, float depth)
+{
+    vec4 position = inverseProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+// Reference: http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+float InterleavedGradientNoise(vec2 position)
+{
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
+void main()
+{
+    float depth = texture(depthMap, fsIn.texcoord).r;
+
+    if (depth >= 1.0)
+    {
+        outOcclusion = 1.0;
+        return;
+    }
+
+    vec3 position = ViewPosition(fsIn.texcoord, depth);
+    vec3 normal = normalize(mat3(view) * texture(normalMap, fsIn.texcoord).xyz);
+
+    // A per pixel rotation of the kernel trades banding for noise that the blur removes.
+    float noise = InterleavedGradientNoise(gl_FragCoord.xy);
+    float angle = noise * 2.0 * 3.14159265;
+    vec3 randomVector = vec3(cos(angle), sin(angle), 0.0);
+
+    vec3 tangent = randomVector - normal * dot(randomVector, normal);
+    if (dot(tangent, tangent) < 0.0001)
+    {
+        tangent = abs(normal.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0);
+        tangent -= normal * dot(tangent, normal);
+    }
+    tangent = normalize(tangent);
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+
+    for (int i = 0; i < SAMPLE_COUNT; ++i)
+    {
+        // Spiral over the hemisphere, with more samples close to the pixel.
+        float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+        float cosTheta = sqrt(1.0 - t);
+        float sinTheta = sqrt(t);
+        float phi = float(i) * GOLDEN_ANGLE;
+        vec3 direction = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+        float scale = mix(0.1, 1.0, t * t);
+        vec3 samplePosition = position + tbn * direction * radius * scale;
+
+        vec4 clip = projection * vec4(samplePosition, 1.0);
+        vec2 sampleUv = clip.xy / clip.w * 0.5 + 0.5;
+
+        float sceneDepth = ViewPosition(sampleUv, texture(depthMap, sampleUv).r).z;
+
+        // Occluders far outside the radius, e.g. across depth discontinuities, fade out.
+        float rangeCheck = smoothstep(0.0, 1.0, radius / abs(position.z - sceneDepth));
+        occlusion += (sceneDepth >= samplePosition.z + bias ? 1.0 : 0.0) * rangeCheck;
+    }
+
+    outOcclusion = pow(1.0 - occlusion / float(SAMPLE_COUNT), intensity);
+}
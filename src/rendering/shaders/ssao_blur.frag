@@ -0,0 +1,51 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// One direction of a separable bilateral blur. Taps at a different depth get less weight, so
+// the occlusion does not bleed across silhouettes.
+layout(binding = 0) uniform sampler2D image;
+layout(binding = 1) uniform sampler2D depthMap;
+
+uniform mat4 inverseProjection;
+uniform vec2 direction;
+uniform float sharpness;
+
+layout(location = 0) in VsOut {
+    vec2 texcoord;
+} fsIn;
+
+layout(location = 0) out float outOcclusion;
+
+const int RADIUS = 4;
+const float WEIGHTS[RADIUS + 1] = float[](0.2270270270, 0.1945945946, 0.1216216216, 0.0540540541, 0.0162162162);
+
+float ViewDepth(vec2 uv)
+{
+    float depth = texture(depthMap, uv).r * 2.0 - 1.0;
+    vec4 position = inverseProjection * vec4(0.0, 0.0, depth, 1.0);
+    return position.z / position.w;
+}
+
+void main()
+{
+    vec2 texel = direction / textureSize(image, 0);
+    float centerDepth = ViewDepth(fsIn.texcoord);
+
+    float occlusion = texture(image, fsIn.texcoord).r * WEIGHTS[0];
+    float weightSum = WEIGHTS[0];
+
+    for (int i = 1; i <= RADIUS; ++i)
+    {
+        for (int side = -1; side <= 1; side += 2)
+        {
+            vec2 uv = fsIn.texcoord + texel * float(i * side);
+            float depthDifference = abs(ViewDepth(uv) - centerDepth);
+            float weight = WEIGHTS[i] * exp(-depthDifference * sharpness);
+
+            occlusion += texture(image, uv).r * weight;
+            weightSum += weight;
+        }
+    }
+
+    outOcclusion = occlusion / weightSum;
+}
@@ -0,0 +1,13 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in VsOut {
+    vec3 wNormal;
+} fsIn;
+
+layout(location = 0) out vec4 outNormal;
+
+void main()
+{
+    outNormal = vec4(normalize(fsIn.wNormal), 1.0);
+}
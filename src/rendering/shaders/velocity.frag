@@ -0,0 +1,21 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+// Screen space motion in pixels since the previous frame (xy) and view depth (z).
+uniform vec2 viewportSize;
+
+layout(location = 0) in VsOut {
+    vec4 clipPosition;
+    vec4 previousClipPosition;
+} fsIn;
+
+layout(location = 0) out vec4 outVelocity;
+
+void main()
+{
+    vec2 current = fsIn.clipPosition.xy / fsIn.clipPosition.w;
+    vec2 previous = fsIn.previousClipPosition.xy / fsIn.previousClipPosition.w;
+
+    // w is the view depth for perspective projections.
+    outVelocity = vec4((current - previous) * 0.5 * viewportSize, fsIn.clipPosition.w, 0.0);
+}
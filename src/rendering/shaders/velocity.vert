@@ -0,0 +1,33 @@
+#version 450 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+
+layout(std140, binding = 1) uniform PerDrawBlock
+{
+    mat4 model;
+    mat4 normalMatrix;
+};
+
+uniform mat4 viewProjection;
+uniform mat4 previousViewProjection;
+uniform mat4 previousModel;
+
+layout(location = 0) out VsOut {
+    vec4 clipPosition;
+    vec4 previousClipPosition;
+} vsOut;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main()
+{
+    vec4 position = vec4(inPosition, 1.0);
+
+    vsOut.clipPosition = viewProjection * model * position;
+    vsOut.previousClipPosition = previousViewProjection * previousModel * position;
+
+    gl_Position = vsOut.clipPosition;
+}
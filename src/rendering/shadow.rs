@@ -0,0 +1,630 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::math::{Mat4, UVec2, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        draw_list::DrawList,
+        error::RendererError,
+        framebuffer::{
+            AttachmentType, Framebuffer, FramebufferAttachment, FramebufferAttachmentCreateInfo,
+        },
+        per_draw::{PerDrawData, PerDrawUniforms},
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{DepthFunction, FixedFunctionState, StateManager},
+        texture::SizedTextureFormat,
+    },
+    Msaa,
+};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+use std::{mem, ops::RangeInclusive};
+
+// Storage block binding of the shadow views, read by the lighting shaders.
+pub const SHADOW_VIEWS_BINDING: u32 = 3;
+
+const MAX_SHADOW_VIEWS: usize = 256;
+const MIN_TILE_SIZE: u32 = 64;
+const NEAR_PLANE: f32 = 0.05;
+
+// A square region of the shadow atlas, in texels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowTile {
+    pub offset: UVec2,
+    pub size: u32,
+}
+
+// Hands out power of two tiles of one shadow map by splitting it as a quadtree.
+pub struct ShadowAtlas {
+    size: u32,
+    min_tile_size: u32,
+    // Free tiles by level. Level 0 is the whole atlas, every level halves the tile size.
+    free: Vec<Vec<UVec2>>,
+}
+
+impl ShadowAtlas {
+    pub fn new(size: u32, min_tile_size: u32) -> Self {
+        assert!(
+            size.is_power_of_two() && min_tile_size.is_power_of_two() && min_tile_size <= size,
+            "Shadow atlas and tile sizes must be powers of two."
+        );
+
+        let levels = (size / min_tile_size).trailing_zeros() as usize + 1;
+        let mut atlas = Self {
+            size,
+            min_tile_size,
+            free: vec![vec![]; levels],
+        };
+
+        atlas.clear();
+        atlas
+    }
+
+    // Rounds the size up to a power of two. None if there is no free tile that large.
+    pub fn allocate(&mut self, size: u32) -> Option<ShadowTile> {
+        let size = size
+            .max(self.min_tile_size)
+            .min(self.size)
+            .next_power_of_two();
+        let level = (self.size / size).trailing_zeros() as usize;
+
+        let source = (0..=level).rev().find(|&l| !self.free[l].is_empty())?;
+        let offset = self.free[source].pop().unwrap();
+
+        // Keeps splitting off the first quadrant, the other three become free.
+        for l in source..level {
+            let half = self.size >> (l + 1);
+            self.free[l + 1].extend_from_slice(&[
+                offset + UVec2::new(half, 0),
+                offset + UVec2::new(0, half),
+                offset + UVec2::new(half, half),
+            ]);
+        }
+
+        Some(ShadowTile { offset, size })
+    }
+
+    pub fn free(&mut self, tile: ShadowTile) {
+        let mut level = (self.size / tile.size).trailing_zeros() as usize;
+        let mut offset = tile.offset;
+
+        // Merges the tile with its siblings for as long as all of them are free.
+        while level > 0 {
+            let size = self.size >> level;
+            let parent = UVec2::new(
+                offset.x / (size * 2) * (size * 2),
+                offset.y / (size * 2) * (size * 2),
+            );
+            let siblings = [
+                parent,
+                parent + UVec2::new(size, 0),
+                parent + UVec2::new(0, size),
+                parent + UVec2::new(size, size),
+            ];
+
+            let free = &mut self.free[level];
+            if !siblings
+                .iter()
+                .filter(|&&sibling| sibling != offset)
+                .all(|sibling| free.contains(sibling))
+            {
+                break;
+            }
+
+            free.retain(|tile| !siblings.contains(tile));
+            offset = parent;
+            level -= 1;
+        }
+
+        self.free[level].push(offset)
+    }
+
+    // Frees every tile.
+    pub fn clear(&mut self) {
+        self.free.iter_mut().for_each(|free| free.clear());
+        self.free[0].push(UVec2::new(0, 0))
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn min_tile_size(&self) -> u32 {
+        self.min_tile_size
+    }
+}
+
+// How the edges of the shadow of a light are filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShadowFilter {
+    // A fixed 3x3 kernel, the same softness everywhere.
+    Pcf,
+    // Percentage-closer soft shadows. Sharp where the occluder touches the receiver and softer
+    // further away, like the penumbra of a light of that radius in world units.
+    Pcss { light_radius: f32 },
+}
+
+impl Default for ShadowFilter {
+    fn default() -> Self {
+        ShadowFilter::Pcf
+    }
+}
+
+// Too little bias gives shadow acne, too much detaches the shadows from their casters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowBias {
+    // Subtracted from the depth of the receiver, in [0, 1] depth units.
+    pub depth: f32,
+    // Pushes the occluders away from the light by their depth slope while rendering.
+    pub slope_scale: f32,
+    // Moves the receiver along its normal, in texels of the shadow map.
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowBias {
+    fn default() -> Self {
+        Self {
+            depth: 0.0005,
+            slope_scale: 1.0,
+            normal_offset: 1.5,
+        }
+    }
+}
+
+impl Gui for ShadowBias {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::Slider::new(im_str!("Depth Bias"))
+            .range(RangeInclusive::new(0.0, 0.01))
+            .display_format(im_str!("%.5f"))
+            .build(&ui, &mut self.depth);
+        imgui::Slider::new(im_str!("Slope Scaled Bias"))
+            .range(RangeInclusive::new(0.0, 8.0))
+            .display_format(im_str!("%.2f"))
+            .build(&ui, &mut self.slope_scale);
+        imgui::Slider::new(im_str!("Normal Offset"))
+            .range(RangeInclusive::new(0.0, 4.0))
+            .display_format(im_str!("%.2f"))
+            .build(&ui, &mut self.normal_offset);
+    }
+}
+
+// How a light casts its shadow. Missing fields take their default values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    // Tile size. None uses the resolution of LocalShadows.
+    pub resolution: Option<u32>,
+    pub filter: ShadowFilter,
+    // None uses the bias of LocalShadows.
+    pub bias: Option<ShadowBias>,
+}
+
+// Layout of the ShadowView struct of the lighting shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct ShadowViewData {
+    view_projection: Mat4,
+    // Offset and scale of the tile in texture coordinates of the atlas.
+    atlas_rect: Vec4,
+    // x: depth bias, y: normal offset per unit of distance from the light, z: slope scaled bias
+    // of the occluders, only used while rendering.
+    params: Vec4,
+    // x: size of the light on the near plane in texture coordinates of the tile, 0 for PCF.
+    // y, z: near and far plane.
+    pcss: Vec4,
+}
+
+// The shadow of a light for this frame. Lights pass index() to the shaders, point lights add
+// the cube face (+X, -X, +Y, -Y, +Z, -Z) to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowHandle(u32);
+
+impl ShadowHandle {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+// Shadow maps of point and spot lights, all rendered into tiles of a single depth texture so
+// any number of shadowed lights takes one texture unit. Point lights render their six cube
+// faces into six tiles, spot lights a single one.
+pub struct LocalShadows {
+    atlas: ShadowAtlas,
+    framebuffer: Framebuffer,
+    pipeline_state: PipelineState,
+    sampler: Sampler,
+    // Reads the depths themselves, for the blocker search of PCSS.
+    depth_sampler: Sampler,
+    views: Vec<ShadowViewData>,
+    tiles: Vec<ShadowTile>,
+    views_buffer: Buffer,
+    // Tile size and bias for lights added without them.
+    resolution: u32,
+    bias: ShadowBias,
+    enabled: bool,
+}
+
+impl LocalShadows {
+    pub fn new(atlas_size: u32) -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/shadow.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/shadow.frag",
+            )?)
+            .build()?;
+
+        let sampler = Sampler::new(
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(1.0, 1.0, 1.0, 1.0),
+            Anisotropy::None,
+        );
+        sampler.set_depth_comparison(DepthFunction::LessOrEqual);
+
+        let depth_sampler = Sampler::new(
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            WrappingMode::ClampToEdge,
+            Vec4::new(1.0, 1.0, 1.0, 1.0),
+            Anisotropy::None,
+        );
+
+        Ok(Self {
+            atlas: ShadowAtlas::new(atlas_size, MIN_TILE_SIZE),
+            framebuffer: Self::atlas_framebuffer(atlas_size)?,
+            pipeline_state: PipelineStateBuilder::new(program_pipeline).build(),
+            sampler,
+            depth_sampler,
+            views: Vec::with_capacity(MAX_SHADOW_VIEWS),
+            tiles: Vec::with_capacity(MAX_SHADOW_VIEWS),
+            views_buffer: Buffer::new(
+                "Shadow Views",
+                (MAX_SHADOW_VIEWS * mem::size_of::<ShadowViewData>()) as isize,
+                BufferTarget::ShaderStorage,
+                BufferStorageFlags::DYNAMIC,
+            ),
+            resolution: 512,
+            bias: ShadowBias::default(),
+            enabled: true,
+        })
+    }
+
+    // Forgets the lights of the previous frame.
+    pub fn begin_frame(&mut self) {
+        self.atlas.clear();
+        self.views.clear();
+        self.tiles.clear()
+    }
+
+    // None while shadows are disabled. Lights added first get the resolution they ask for, once
+    // the atlas fills up the next ones get smaller tiles and finally None. Add the most
+    // important lights first.
+    pub fn add_point_light(
+        &mut self,
+        position: &Vec3,
+        range: f32,
+        settings: &ShadowSettings,
+    ) -> Option<ShadowHandle> {
+        let tiles = self.allocate_tiles(6, settings.resolution)?;
+        let projection = glm::perspective(1.0, 90.0f32.to_radians(), NEAR_PLANE, range);
+
+        let faces = [
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, -1.0, 0.0)),
+        ];
+
+        let handle = ShadowHandle(self.views.len() as u32);
+
+        for (tile, (direction, up)) in tiles.into_iter().zip(faces.iter()) {
+            let view = glm::look_at(position, &(position + direction), up);
+            self.push_view(
+                tile,
+                projection * view,
+                90.0f32.to_radians(),
+                range,
+                settings,
+            );
+        }
+
+        Some(handle)
+    }
+
+    // outer_angle is the half angle of the cone in radians.
+    pub fn add_spot_light(
+        &mut self,
+        position: &Vec3,
+        direction: &Vec3,
+        outer_angle: f32,
+        range: f32,
+        settings: &ShadowSettings,
+    ) -> Option<ShadowHandle> {
+        let tile = self.allocate_tiles(1, settings.resolution)?.pop().unwrap();
+
+        // A little wider than the cone, so its edge doesn't sample the border of the tile.
+        let fov = (outer_angle * 2.0 + 2.0f32.to_radians()).min(179.0f32.to_radians());
+        let projection = glm::perspective(1.0, fov, NEAR_PLANE, range);
+
+        let direction = direction.normalize();
+        let up = if direction.y.abs() > 0.99 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let view = glm::look_at(position, &(position + direction), &up);
+
+        let handle = ShadowHandle(self.views.len() as u32);
+        self.push_view(tile, projection * view, fov, range, settings);
+
+        Some(handle)
+    }
+
+    // Renders every shadow view added this frame. draw issues the occluders, it is called once
+    // per view with its view projection, e.g. for culling.
+    pub fn render<F: FnMut(&Mat4)>(&mut self, mut draw: F) {
+        if self.views.is_empty() {
+            return;
+        }
+
+        self.views_buffer.fill_slice(0, &self.views);
+
+        self.framebuffer.bind();
+        self.framebuffer.clear(&Vec4::new(0.0, 0.0, 0.0, 0.0));
+
+        self.pipeline_state.bind();
+
+        for (view, tile) in self.views.iter().zip(&self.tiles) {
+            StateManager::set_viewport(
+                tile.offset.x as i32,
+                tile.offset.y as i32,
+                tile.size as i32,
+                tile.size as i32,
+            );
+
+            self.pipeline_state
+                .program_pipeline()
+                .set_mat4_all_stages("viewProjection", &view.view_projection);
+            StateManager::set_polygon_offset(view.params.z, 0.0);
+            draw(&view.view_projection);
+        }
+
+        StateManager::set_polygon_offset(0.0, 0.0);
+        self.pipeline_state.unbind();
+
+        self.framebuffer.unbind(false);
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    pub fn render_draw_list(
+        &mut self,
+        draw_list: &DrawList,
+        per_draw_uniforms: &mut PerDrawUniforms,
+    ) {
+        // Pushed once and bound for every view.
+        let mut handles = Vec::with_capacity(draw_list.len());
+        for item in draw_list.items() {
+            match per_draw_uniforms.push(&PerDrawData::new(item.model, 0)) {
+                Some(handle) => handles.push(handle),
+                None => break,
+            }
+        }
+
+        self.render(|_| {
+            for (item, handle) in draw_list.items().iter().zip(&handles) {
+                per_draw_uniforms.bind(handle);
+                item.mesh
+                    .draw_with_primitive_mode(item.material.borrow().primitive_mode());
+            }
+        })
+    }
+
+    // Binds the atlas with a comparison sampler to the texture unit and the shadow views to
+    // SHADOW_VIEWS_BINDING.
+    pub fn bind(&self, program_pipeline: &ProgramPipeline, binding: u32) {
+        program_pipeline.set_texture_2d_with_id(binding, self.atlas_texture().id(), &self.sampler);
+        self.views_buffer.bind(SHADOW_VIEWS_BINDING)
+    }
+
+    // Binds the atlas without comparison, which the blocker search of PCSS reads.
+    pub fn bind_depth(&self, program_pipeline: &ProgramPipeline, binding: u32) {
+        program_pipeline.set_texture_2d_with_id(
+            binding,
+            self.atlas_texture().id(),
+            &self.depth_sampler,
+        );
+    }
+
+    pub fn atlas_texture(&self) -> FramebufferAttachment {
+        self.framebuffer.texture_attachments()[0]
+    }
+
+    pub fn atlas_size(&self) -> u32 {
+        self.atlas.size()
+    }
+
+    // Recreates the atlas, between frames. Forgets the lights added so far. Keeps the current
+    // atlas if the new framebuffer can't be created.
+    pub fn set_atlas_size(&mut self, atlas_size: u32) -> Result<(), RendererError> {
+        if atlas_size == self.atlas.size() {
+            return Ok(());
+        }
+
+        self.framebuffer = Self::atlas_framebuffer(atlas_size)?;
+        self.atlas = ShadowAtlas::new(atlas_size, MIN_TILE_SIZE);
+        self.begin_frame();
+
+        Ok(())
+    }
+
+    pub fn view_count(&self) -> usize {
+        self.views.len()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    pub fn set_resolution(&mut self, resolution: u32) {
+        self.resolution = resolution
+    }
+
+    pub fn bias(&self) -> &ShadowBias {
+        &self.bias
+    }
+
+    pub fn set_bias(&mut self, bias: ShadowBias) {
+        self.bias = bias
+    }
+
+    // Tries smaller tiles until all of them fit. Nothing is allocated on failure.
+    fn allocate_tiles(&mut self, count: usize, resolution: Option<u32>) -> Option<Vec<ShadowTile>> {
+        if !self.enabled {
+            return None;
+        }
+
+        if self.views.len() + count > MAX_SHADOW_VIEWS {
+            log::warn!(
+                "Shadow view capacity exceeded. Capacity: {}",
+                MAX_SHADOW_VIEWS
+            );
+            return None;
+        }
+
+        let mut size = resolution
+            .unwrap_or(self.resolution)
+            .max(self.atlas.min_tile_size())
+            .next_power_of_two();
+
+        while size >= self.atlas.min_tile_size() {
+            let mut tiles = Vec::with_capacity(count);
+
+            while tiles.len() < count {
+                match self.atlas.allocate(size) {
+                    Some(tile) => tiles.push(tile),
+                    None => break,
+                }
+            }
+
+            if tiles.len() == count {
+                return Some(tiles);
+            }
+
+            tiles.into_iter().for_each(|tile| self.atlas.free(tile));
+            size /= 2;
+        }
+
+        None
+    }
+
+    fn push_view(
+        &mut self,
+        tile: ShadowTile,
+        view_projection: Mat4,
+        fov: f32,
+        far: f32,
+        settings: &ShadowSettings,
+    ) {
+        let bias = settings.bias.unwrap_or(self.bias);
+        let atlas_size = self.atlas.size() as f32;
+        let texel_angle = 2.0 * (fov * 0.5).tan() / tile.size as f32;
+
+        // The diameter of the light over the width of the near plane.
+        let light_size = match settings.filter {
+            ShadowFilter::Pcf => 0.0,
+            ShadowFilter::Pcss { light_radius } => {
+                light_radius * 2.0 / (2.0 * NEAR_PLANE * (fov * 0.5).tan())
+            }
+        };
+
+        self.views.push(ShadowViewData {
+            view_projection,
+            atlas_rect: Vec4::new(
+                tile.offset.x as f32 / atlas_size,
+                tile.offset.y as f32 / atlas_size,
+                tile.size as f32 / atlas_size,
+                tile.size as f32 / atlas_size,
+            ),
+            params: Vec4::new(
+                bias.depth,
+                bias.normal_offset * texel_angle,
+                bias.slope_scale,
+                0.0,
+            ),
+            pcss: Vec4::new(light_size, NEAR_PLANE, far, 0.0),
+        });
+        self.tiles.push(tile)
+    }
+
+    fn atlas_framebuffer(atlas_size: u32) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            UVec2::new(atlas_size, atlas_size),
+            Msaa::None,
+            vec![FramebufferAttachmentCreateInfo::new(
+                SizedTextureFormat::Depth32f,
+                AttachmentType::Texture,
+            )],
+        )
+        .map_err(RendererError::from)
+    }
+}
+
+impl Gui for LocalShadows {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            ui.checkbox(im_str!("##local_shadows"), &mut self.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Local Light Shadows"))
+                .default_open(false)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+
+                    let mut resolution = self.resolution as i32;
+                    if imgui::Slider::new(im_str!("Resolution"))
+                        .range(RangeInclusive::new(
+                            MIN_TILE_SIZE as i32,
+                            self.atlas.size() as i32 / 2,
+                        ))
+                        .build(&ui, &mut resolution)
+                    {
+                        self.resolution = (resolution as u32).next_power_of_two()
+                    }
+                    self.bias.gui(ui);
+                    ui.text(format!(
+                        "{} views in a {}x{} atlas",
+                        self.views.len(),
+                        self.atlas.size(),
+                        self.atlas.size()
+                    ));
+
+                    ui.unindent()
+                });
+        });
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    core::math::{Mat4, Vec2, Vec3, Vec4},
+    rendering::{
+        buffer::{Buffer, BufferStorageFlags, BufferTarget},
+        mesh::Vertex,
+        vertex_layout::{VertexAttribute, VertexFormat, VertexLayout},
+    },
+};
+use std::mem;
+
+// Shader storage binding of the joint palette. Storage blocks have their own binding points so
+// this does not collide with the uniform blocks.
+pub const JOINT_PALETTE_BINDING: u32 = 0;
+
+// Vertices are influenced by at most this many joints.
+pub const MAX_JOINT_INFLUENCES: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    pub tex_coord: Vec2,
+    pub color: Vec4,
+    pub joints: [u16; MAX_JOINT_INFLUENCES],
+    pub weights: [f32; MAX_JOINT_INFLUENCES],
+}
+
+impl SkinnedVertex {
+    // Weights are normalized so that they sum up to one.
+    pub fn new(
+        vertex: &Vertex,
+        joints: [u16; MAX_JOINT_INFLUENCES],
+        weights: [f32; MAX_JOINT_INFLUENCES],
+    ) -> Self {
+        let weight_sum: f32 = weights.iter().sum();
+        let weights = if weight_sum > std::f32::EPSILON {
+            [
+                weights[0] / weight_sum,
+                weights[1] / weight_sum,
+                weights[2] / weight_sum,
+                weights[3] / weight_sum,
+            ]
+        } else {
+            // Unweighted vertices follow the first joint.
+            [1.0, 0.0, 0.0, 0.0]
+        };
+
+        Self {
+            position: vertex.position,
+            normal: vertex.normal,
+            tangent: vertex.tangent,
+            tex_coord: vertex.tex_coord,
+            color: vertex.color,
+            joints,
+            weights,
+        }
+    }
+
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at_offset(
+                VertexAttribute::Position,
+                VertexFormat::Float3,
+                offset_of!(SkinnedVertex, position) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Normal,
+                VertexFormat::Float3,
+                offset_of!(SkinnedVertex, normal) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Tangent,
+                VertexFormat::Float4,
+                offset_of!(SkinnedVertex, tangent) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord0,
+                VertexFormat::Float2,
+                offset_of!(SkinnedVertex, tex_coord) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Color,
+                VertexFormat::Float4,
+                offset_of!(SkinnedVertex, color) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Joints,
+                VertexFormat::UnsignedShort4,
+                offset_of!(SkinnedVertex, joints) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Weights,
+                VertexFormat::Float4,
+                offset_of!(SkinnedVertex, weights) as u32,
+            )
+            .with_stride(mem::size_of::<SkinnedVertex>() as u32)
+    }
+}
+
+// The joints of a skinned mesh and the matrices that bring the mesh from its bind pose to the
+// local space of each joint.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    pub fn new(inverse_bind_matrices: Vec<Mat4>) -> Self {
+        Self {
+            inverse_bind_matrices,
+        }
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.inverse_bind_matrices.len()
+    }
+
+    pub fn inverse_bind_matrices(&self) -> &[Mat4] {
+        &self.inverse_bind_matrices
+    }
+
+    // Combines the current joint transforms (in mesh space) with the inverse bind matrices into
+    // the skinning matrices expected by the vertex shader.
+    pub fn compute_palette(&self, joint_transforms: &[Mat4], palette: &mut Vec<Mat4>) {
+        assert_eq!(
+            joint_transforms.len(),
+            self.joint_count(),
+            "Joint transform count does not match the joint count of the skin."
+        );
+
+        palette.clear();
+        palette.extend(
+            joint_transforms
+                .iter()
+                .zip(&self.inverse_bind_matrices)
+                .map(|(joint, inverse_bind)| joint * inverse_bind),
+        );
+    }
+}
+
+// GPU copy of the skinning matrices, read by the skinning vertex shader through a storage
+// block at JOINT_PALETTE_BINDING.
+pub struct JointPalette {
+    buffer: Buffer,
+    capacity: usize,
+    joint_count: usize,
+}
+
+impl JointPalette {
+    pub fn new(capacity: usize) -> Self {
+        let buffer = Buffer::new(
+            "Joint Palette",
+            (capacity * mem::size_of::<Mat4>()) as isize,
+            BufferTarget::ShaderStorage,
+            BufferStorageFlags::DYNAMIC,
+        );
+
+        Self {
+            buffer,
+            capacity,
+            joint_count: 0,
+        }
+    }
+
+    pub fn update(&mut self, palette: &[Mat4]) {
+        assert!(
+            palette.len() <= self.capacity,
+            "Joint palette capacity exceeded. Capacity: {}, Requested: {}",
+            self.capacity,
+            palette.len()
+        );
+
+        self.buffer.fill_slice(0, palette);
+        self.joint_count = palette.len();
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind(JOINT_PALETTE_BINDING)
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joint_count
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
@@ -0,0 +1,422 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::camera::Camera,
+    core::math::{Mat4, Vec3, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        atmosphere::{Atmosphere, AtmosphereSettings},
+        error::RendererError,
+        framebuffer::Framebuffer,
+        mesh::FULLSCREEN_MESH,
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{
+            DepthFunction, DepthStencilState, FixedFunctionState, RasterizerState, StateManager,
+        },
+        texture::{SizedTextureFormat, TextureCube},
+        Draw,
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use nalgebra_glm as glm;
+use std::ops::RangeInclusive;
+
+const RADIANCE_SIZE: u32 = 128;
+// Enough levels for the roughness lookup of the specular IBL term.
+const RADIANCE_LEVELS: i32 = 8;
+const IRRADIANCE_SIZE: u32 = 32;
+const IRRADIANCE_SAMPLE_LOD: f32 = 2.0;
+
+// Uniform block binding of the MatricesBlock of the cube map skybox shaders of the applications.
+pub const SKYBOX_MATRICES_BINDING: u32 = 5;
+
+// What the sky pass draws.
+pub enum SkySource<'a> {
+    // An environment cube map, the same one that is bound for image based lighting.
+    Cubemap(&'a TextureCube),
+    // The procedural sky of the pass. Use radiance() and irradiance() for its lighting.
+    Procedural,
+}
+
+// The model the procedural sky is computed with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkyModel {
+    // Analytic and cheap, but only defined above the horizon and for a sun over it.
+    Preetham,
+    // Physically based scattering in an atmosphere around the planet, including sunsets and
+    // the sky after them.
+    Atmosphere,
+}
+
+const SOURCE_CUBEMAP: i32 = 0;
+const SOURCE_PREETHAM: i32 = 1;
+const SOURCE_ATMOSPHERE: i32 = 2;
+
+// Parameters of the analytic daylight model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreethamSky {
+    // Haziness of the atmosphere, from 2 for a clear to 10 for a hazy sky.
+    pub turbidity: f32,
+    // Scales the luminance of the model (kcd/m^2) to the units of the scene.
+    pub intensity: f32,
+    pub sun_disk: bool,
+}
+
+impl Default for PreethamSky {
+    fn default() -> Self {
+        Self {
+            turbidity: 3.0,
+            intensity: 0.05,
+            sun_disk: true,
+        }
+    }
+}
+
+// Draws the background of the scene at far depth, after the opaque geometry, so it only
+// shades the pixels nothing else covers. The procedural sky is also captured into cube maps
+// that stand in for loaded environment maps in the image based lighting.
+pub struct SkyPass {
+    pipeline_state: PipelineState,
+    irradiance_pipeline_state: PipelineState,
+    sampler_linear: Sampler,
+    model: SkyModel,
+    // Towards the sun, e.g. the direction of the directional light.
+    sun_direction: Vec3,
+    // Of the atmosphere, e.g. the color and intensity of the directional light.
+    sun_illuminance: Vec3,
+    preetham: PreethamSky,
+    atmosphere: Atmosphere,
+    radiance: TextureCube,
+    irradiance: TextureCube,
+    // The parameters the cube maps were captured with.
+    captured: Option<CaptureKey>,
+    capture_framebuffer: GLuint,
+}
+
+impl SkyPass {
+    pub fn new() -> Result<Self, RendererError> {
+        let load = |fragment_shader: &str| -> Result<ProgramPipeline, RendererError> {
+            let program_pipeline = ProgramPipeline::new()
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Vertex,
+                    "src/rendering/shaders/sky.vert",
+                )?)
+                .add_shader(&EmbeddedAssets::load_shader(
+                    ShaderStage::Fragment,
+                    fragment_shader,
+                )?)
+                .build()?;
+
+            Ok(program_pipeline)
+        };
+
+        let pipeline_state = |program_pipeline: ProgramPipeline| {
+            PipelineStateBuilder::new(program_pipeline)
+                .depth_stencil(DepthStencilState {
+                    depth_write: false,
+                    depth_function: DepthFunction::LessOrEqual,
+                    ..Default::default()
+                })
+                .rasterizer(RasterizerState {
+                    face_culling: None,
+                    ..Default::default()
+                })
+                .build()
+        };
+
+        let sky_pipeline = load("src/rendering/shaders/sky.frag")?;
+        let irradiance_pipeline = load("src/rendering/shaders/sky_irradiance.frag")?;
+
+        let mut capture_framebuffer: GLuint = 0;
+        unsafe { gl::CreateFramebuffers(1, &mut capture_framebuffer) }
+
+        Ok(Self {
+            pipeline_state: pipeline_state(sky_pipeline),
+            irradiance_pipeline_state: pipeline_state(irradiance_pipeline),
+            sampler_linear: Sampler::new(
+                MinificationFilter::LinearMipmapLinear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            model: SkyModel::Preetham,
+            sun_direction: Vec3::new(0.3, 0.6, -0.7).normalize(),
+            sun_illuminance: Vec3::new(1.0, 1.0, 1.0),
+            preetham: PreethamSky::default(),
+            atmosphere: Atmosphere::new(AtmosphereSettings::default())?,
+            radiance: TextureCube::new_empty(
+                RADIANCE_SIZE,
+                SizedTextureFormat::Rgba16f,
+                RADIANCE_LEVELS,
+            ),
+            irradiance: TextureCube::new_empty(IRRADIANCE_SIZE, SizedTextureFormat::Rgba16f, 1),
+            captured: None,
+            capture_framebuffer,
+        })
+    }
+
+    // Draws the sky into the bound framebuffer, which needs the depth of the scene.
+    pub fn render(&self, camera: &Camera, source: SkySource) {
+        self.render_view(camera.transform(), &camera.projection_matrix(), source)
+    }
+
+    // Like render, for a view that is not a camera, e.g. the face of a light probe.
+    pub fn render_view(&self, view: &Mat4, projection: &Mat4, source: SkySource) {
+        let mut view = view.clone_owned();
+        view.m14 = 0.0;
+        view.m24 = 0.0;
+        view.m34 = 0.0;
+
+        let inverse_view_projection = (projection * view)
+            .try_inverse()
+            .unwrap_or_else(Mat4::identity);
+
+        self.pipeline_state.bind();
+        self.pipeline_state
+            .program_pipeline()
+            .set_mat4_all_stages("inverseViewProjection", &inverse_view_projection);
+        self.draw(&source);
+        self.pipeline_state.unbind();
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    // Captures the procedural sky into the radiance and irradiance maps if its parameters
+    // changed, e.g. every time the sun moves. Call it before the sky is drawn or its maps are
+    // used for lighting.
+    pub fn update_environment(&mut self) {
+        if self.model == SkyModel::Atmosphere {
+            self.atmosphere.update(&self.sun_direction);
+        }
+
+        let key = self.capture_key();
+        if self.captured.as_ref() == Some(&key) {
+            return;
+        }
+
+        self.pipeline_state.bind();
+        self.capture(&self.radiance, RADIANCE_SIZE, &self.pipeline_state, || {
+            self.draw(&SkySource::Procedural)
+        });
+        self.pipeline_state.unbind();
+
+        unsafe { gl::GenerateTextureMipmap(self.radiance.get_id()) }
+
+        self.irradiance_pipeline_state.bind();
+        self.irradiance_pipeline_state
+            .program_pipeline()
+            .set_texture_cube(0, &self.radiance, &self.sampler_linear)
+            .set_float_all_stages("sampleLod", IRRADIANCE_SAMPLE_LOD);
+        self.capture(
+            &self.irradiance,
+            IRRADIANCE_SIZE,
+            &self.irradiance_pipeline_state,
+            || FULLSCREEN_MESH.draw(),
+        );
+        self.irradiance_pipeline_state.unbind();
+
+        StateManager::apply(&FixedFunctionState::default());
+        self.captured = Some(key)
+    }
+
+    // The captured procedural sky, mipmapped for the specular IBL term.
+    pub fn radiance(&self) -> &TextureCube {
+        &self.radiance
+    }
+
+    // The captured procedural sky, convolved for the diffuse IBL term.
+    pub fn irradiance(&self) -> &TextureCube {
+        &self.irradiance
+    }
+
+    pub fn preetham(&self) -> &PreethamSky {
+        &self.preetham
+    }
+
+    pub fn set_preetham(&mut self, preetham: PreethamSky) {
+        self.preetham = preetham
+    }
+
+    pub fn atmosphere(&self) -> &Atmosphere {
+        &self.atmosphere
+    }
+
+    pub fn atmosphere_mut(&mut self) -> &mut Atmosphere {
+        &mut self.atmosphere
+    }
+
+    pub fn model(&self) -> SkyModel {
+        self.model
+    }
+
+    pub fn set_model(&mut self, model: SkyModel) {
+        self.model = model
+    }
+
+    pub fn sun_direction(&self) -> &Vec3 {
+        &self.sun_direction
+    }
+
+    pub fn set_sun_direction(&mut self, sun_direction: Vec3) {
+        self.sun_direction = sun_direction
+    }
+
+    pub fn set_sun_illuminance(&mut self, sun_illuminance: Vec3) {
+        self.sun_illuminance = sun_illuminance
+    }
+
+    fn capture_key(&self) -> CaptureKey {
+        CaptureKey {
+            model: self.model,
+            sun_direction: self.sun_direction,
+            sun_illuminance: self.sun_illuminance,
+            preetham: self.preetham.clone(),
+            atmosphere: self.atmosphere.settings().clone(),
+        }
+    }
+
+    fn draw(&self, source: &SkySource) {
+        let program_pipeline = self.pipeline_state.program_pipeline();
+
+        match source {
+            SkySource::Cubemap(environment_map) => {
+                program_pipeline
+                    .set_int_all_stages("source", SOURCE_CUBEMAP)
+                    .set_texture_cube(0, environment_map, &self.sampler_linear);
+            }
+            SkySource::Procedural => {
+                program_pipeline
+                    .set_vec3_all_stages("sunDirection", &self.sun_direction)
+                    .set_int_all_stages("sunDisk", self.preetham.sun_disk as i32);
+
+                match self.model {
+                    SkyModel::Preetham => {
+                        program_pipeline
+                            .set_int_all_stages("source", SOURCE_PREETHAM)
+                            .set_float_all_stages("turbidity", self.preetham.turbidity)
+                            .set_float_all_stages("intensity", self.preetham.intensity);
+                    }
+                    SkyModel::Atmosphere => {
+                        let atmosphere = &self.atmosphere;
+                        program_pipeline
+                            .set_int_all_stages("source", SOURCE_ATMOSPHERE)
+                            .set_float_all_stages(
+                                "bottomRadius",
+                                atmosphere.settings().bottom_radius,
+                            )
+                            .set_float_all_stages("topRadius", atmosphere.settings().top_radius)
+                            .set_float_all_stages("viewHeight", atmosphere.view_height())
+                            .set_vec3_all_stages("sunIlluminance", &self.sun_illuminance)
+                            .set_texture_2d_with_id(
+                                1,
+                                atmosphere.sky_view_lut().id(),
+                                atmosphere.sampler(),
+                            )
+                            .set_texture_2d_with_id(
+                                2,
+                                atmosphere.transmittance_lut().id(),
+                                atmosphere.sampler(),
+                            );
+                    }
+                }
+            }
+        }
+
+        FULLSCREEN_MESH.draw()
+    }
+
+    // Renders every face of the cube map with the matrix of the face.
+    fn capture<F: Fn()>(
+        &self,
+        target: &TextureCube,
+        size: u32,
+        pipeline_state: &PipelineState,
+        draw: F,
+    ) {
+        let projection = glm::perspective(1.0, 90.0f32.to_radians(), 0.1, 10.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        // The +X, -X, +Y, -Y, +Z, -Z faces of the GL cube map layout.
+        let faces = [
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, -1.0, 0.0)),
+        ];
+
+        StateManager::bind_framebuffer(self.capture_framebuffer);
+        StateManager::set_viewport(0, 0, size as i32, size as i32);
+
+        for (face, (direction, up)) in faces.iter().enumerate() {
+            let inverse_view_projection = (projection * glm::look_at(&origin, direction, up))
+                .try_inverse()
+                .unwrap_or_else(Mat4::identity);
+
+            unsafe {
+                gl::NamedFramebufferTextureLayer(
+                    self.capture_framebuffer,
+                    gl::COLOR_ATTACHMENT0,
+                    target.get_id(),
+                    0,
+                    face as i32,
+                );
+            }
+
+            pipeline_state
+                .program_pipeline()
+                .set_mat4_all_stages("inverseViewProjection", &inverse_view_projection);
+            draw();
+        }
+
+        StateManager::bind_framebuffer(Framebuffer::default_id());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CaptureKey {
+    model: SkyModel,
+    sun_direction: Vec3,
+    sun_illuminance: Vec3,
+    preetham: PreethamSky,
+    atmosphere: AtmosphereSettings,
+}
+
+impl Drop for SkyPass {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.capture_framebuffer) }
+    }
+}
+
+impl Gui for SkyPass {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Procedural Sky"))
+            .default_open(false)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .framed(false)
+            .build(ui, || {
+                match self.model {
+                    SkyModel::Preetham => {
+                        imgui::Slider::new(im_str!("Turbidity"))
+                            .range(RangeInclusive::new(2.0, 10.0))
+                            .display_format(im_str!("%.2f"))
+                            .build(&ui, &mut self.preetham.turbidity);
+                        imgui::Slider::new(im_str!("Intensity"))
+                            .range(RangeInclusive::new(0.001, 1.0))
+                            .display_format(im_str!("%.3f"))
+                            .build(&ui, &mut self.preetham.intensity);
+                    }
+                    SkyModel::Atmosphere => self.atmosphere.gui(ui),
+                }
+                ui.checkbox(im_str!("Sun Disk"), &mut self.preetham.sun_disk);
+            });
+    }
+}
@@ -0,0 +1,135 @@
+const LAYER_SHIFT: u32 = 56;
+const DEPTH_BITS: u32 = 24;
+const DEPTH_MASK: u64 = (1 << DEPTH_BITS) - 1;
+const INDEX_MASK: u64 = 0xFFFF;
+
+// Layers are submitted in order, every layer after the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RenderLayer {
+    Opaque = 0,
+    // Blended over the opaque scene, back to front.
+    Transparent = 1,
+    // Drawn over everything else, e.g. gizmos, back to front.
+    Overlay = 2,
+}
+
+impl RenderLayer {
+    pub fn sorts_back_to_front(&self) -> bool {
+        *self != RenderLayer::Opaque
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        match bits {
+            0 => RenderLayer::Opaque,
+            1 => RenderLayer::Transparent,
+            _ => RenderLayer::Overlay,
+        }
+    }
+}
+
+impl Default for RenderLayer {
+    fn default() -> Self {
+        RenderLayer::Opaque
+    }
+}
+
+// Orders the draws of a command buffer. The layer takes the top 8 bits. Opaque draws follow with
+// 16 bits of pipeline, 16 bits of material and 24 bits of depth, so that the fewest pipelines and
+// materials get bound and each run of them is drawn front to back for early depth rejection.
+// The other layers blend, and need the depth first, inverted for back to front, followed by 16
+// bits each of pipeline and material.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(u64);
+
+impl SortKey {
+    // Pipeline and material are indices, of which the lower 16 bits are kept. The depth is in
+    // [0, 1], 0 at the near plane.
+    pub fn new(layer: RenderLayer, pipeline: u32, material: u32, depth: f32) -> Self {
+        let pipeline = pipeline as u64 & INDEX_MASK;
+        let material = material as u64 & INDEX_MASK;
+        let depth = (depth.max(0.0).min(1.0) as f64 * DEPTH_MASK as f64) as u64;
+
+        let key = if layer.sorts_back_to_front() {
+            (DEPTH_MASK - depth) << 32 | pipeline << 16 | material
+        } else {
+            pipeline << 40 | material << DEPTH_BITS | depth
+        };
+
+        SortKey((layer as u64) << LAYER_SHIFT | key)
+    }
+
+    pub fn layer(&self) -> RenderLayer {
+        RenderLayer::from_bits(self.0 >> LAYER_SHIFT)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RenderLayer, SortKey};
+
+    #[test]
+    fn keeps_the_layer() {
+        for &layer in [
+            RenderLayer::Opaque,
+            RenderLayer::Transparent,
+            RenderLayer::Overlay,
+        ]
+        .iter()
+        {
+            assert_eq!(SortKey::new(layer, 0xFFFF, 0xFFFF, 0.5).layer(), layer);
+        }
+    }
+
+    #[test]
+    fn sorts_by_layer_first() {
+        let opaque = SortKey::new(RenderLayer::Opaque, 0xFFFF, 0xFFFF, 1.0);
+        let transparent = SortKey::new(RenderLayer::Transparent, 0, 0, 1.0);
+        let overlay = SortKey::new(RenderLayer::Overlay, 0, 0, 1.0);
+
+        assert!(opaque < transparent);
+        assert!(transparent < overlay);
+    }
+
+    #[test]
+    fn sorts_opaque_draws_by_pipeline_material_and_front_to_back() {
+        let key = |pipeline, material, depth| {
+            SortKey::new(RenderLayer::Opaque, pipeline, material, depth)
+        };
+
+        assert!(key(0, 5, 1.0) < key(1, 0, 0.0));
+        assert!(key(0, 0, 1.0) < key(0, 1, 0.0));
+        assert!(key(0, 0, 0.1) < key(0, 0, 0.9));
+    }
+
+    #[test]
+    fn sorts_blended_draws_back_to_front_first() {
+        let key = |pipeline, material, depth| {
+            SortKey::new(RenderLayer::Transparent, pipeline, material, depth)
+        };
+
+        assert!(key(0, 0, 0.9) < key(0, 0, 0.1));
+        assert!(key(5, 5, 0.9) < key(0, 0, 0.1));
+        assert!(key(0, 5, 0.5) < key(1, 0, 0.5));
+        assert!(key(0, 0, 0.5) < key(0, 1, 0.5));
+    }
+
+    #[test]
+    fn keeps_the_lower_16_bits_of_the_indices() {
+        let key = |pipeline, material| SortKey::new(RenderLayer::Opaque, pipeline, material, 0.5);
+
+        assert_eq!(key(0x1_0001, 0), key(1, 0));
+        assert_eq!(key(0, 0x2_0003), key(0, 3));
+    }
+
+    #[test]
+    fn clamps_the_depth() {
+        let key = |depth| SortKey::new(RenderLayer::Opaque, 0, 0, depth);
+
+        assert_eq!(key(-1.0), key(0.0));
+        assert_eq!(key(2.0), key(1.0));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    core::asset::{embedded::EmbeddedAssets, Handle},
+    core::math::{Vec2, Vec4},
+    core::Rectangle,
+    rendering::{
+        buffer::BufferTarget,
+        error::RendererError,
+        frame_stats::FrameStats,
+        mesh::PrimitiveMode,
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{BlendState, DepthStencilState, RasterizerState, StateManager},
+        streaming_buffer::StreamingBuffer,
+        texture::Texture2D,
+        vertex_layout::{VertexAttribute, VertexFormat, VertexLayout},
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use std::mem;
+
+// Per frame vertex budget, 6 vertices per sprite. Sprites past it are dropped with a warning.
+const FRAME_BUFFER_SIZE: isize = 1024 * 1024;
+const TEXTURE_BINDING_INDEX: u32 = 0;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct SpriteVertex {
+    pub position: Vec2,
+    pub tex_coord: Vec2,
+    pub color: Vec4,
+}
+
+impl SpriteVertex {
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at_offset(
+                VertexAttribute::Position,
+                VertexFormat::Float2,
+                offset_of!(SpriteVertex, position) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::TexCoord0,
+                VertexFormat::Float2,
+                offset_of!(SpriteVertex, tex_coord) as u32,
+            )
+            .attribute_at_offset(
+                VertexAttribute::Color,
+                VertexFormat::Float4,
+                offset_of!(SpriteVertex, color) as u32,
+            )
+            .with_stride(mem::size_of::<SpriteVertex>() as u32)
+    }
+}
+
+// A screen space quad in pixels, from the top left corner of the screen. The texture is
+// multiplied with the color, untextured sprites take the color alone. Higher layers are drawn
+// over lower ones, sprites of the same layer in the order they were added.
+#[derive(Clone)]
+pub struct Sprite {
+    pub position: Vec2,
+    pub size: Vec2,
+    pub color: Vec4,
+    pub texture: Option<Handle<Texture2D>>,
+    // The region of the texture, e.g. a frame of a sprite sheet.
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub layer: i32,
+}
+
+impl Sprite {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            position,
+            size,
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            texture: None,
+            uv_min: Vec2::new(0.0, 0.0),
+            uv_max: Vec2::new(1.0, 1.0),
+            layer: 0,
+        }
+    }
+
+    pub fn color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn texture(mut self, texture: Handle<Texture2D>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn uv(mut self, uv_min: Vec2, uv_max: Vec2) -> Self {
+        self.uv_min = uv_min;
+        self.uv_max = uv_max;
+        self
+    }
+
+    pub fn layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+}
+
+struct QueuedSprite {
+    sprite: Sprite,
+    scissor: Option<Rectangle>,
+}
+
+// Consecutive sprites with the same texture and scissor rectangle, drawn with one call.
+struct SpriteDraw {
+    texture: GLuint,
+    scissor: Option<Rectangle>,
+    first: usize,
+    vertex_count: usize,
+}
+
+// Immediate mode 2D layer for HUDs, loading screens and crosshairs. Everything added during a
+// frame is drawn by render() and discarded afterwards. Render it into the final image after
+// post-processing, it is blended over whatever is bound without depth testing.
+pub struct SpriteBatch {
+    pipeline_state: PipelineState,
+    vertex_buffer: StreamingBuffer,
+    vao: GLuint,
+    sampler: Sampler,
+    white: Texture2D,
+    sprites: Vec<QueuedSprite>,
+    scissor: Option<Rectangle>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/sprite.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/sprite.frag",
+            )?)
+            .build()?;
+
+        let pipeline_state = PipelineStateBuilder::new(program_pipeline)
+            .depth_stencil(DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            })
+            .blend(Some(BlendState::alpha_blending()))
+            .rasterizer(RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            })
+            .build();
+
+        let vertex_buffer = StreamingBuffer::new(
+            "Sprite Batch Buffer",
+            FRAME_BUFFER_SIZE,
+            BufferTarget::Array,
+        );
+
+        let mut vao: GLuint = 0;
+        unsafe { gl::CreateVertexArrays(1, &mut vao) }
+        SpriteVertex::layout().apply(vao, 0);
+
+        Ok(Self {
+            pipeline_state,
+            vertex_buffer,
+            vao,
+            sampler: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            white: Texture2D::new_from_color([255, 255, 255]),
+            sprites: Vec::new(),
+            scissor: None,
+        })
+    }
+
+    // Clips the sprites added afterwards to the rectangle, in pixels from the top left corner.
+    // None stops clipping.
+    pub fn set_scissor(&mut self, scissor: Option<Rectangle>) {
+        self.scissor = scissor
+    }
+
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(QueuedSprite {
+            sprite,
+            scissor: self.scissor,
+        })
+    }
+
+    pub fn quad(&mut self, position: Vec2, size: Vec2, color: Vec4, layer: i32) {
+        self.draw(Sprite::new(position, size).color(color).layer(layer))
+    }
+
+    // Draws the sprites added this frame over the whole of the bound framebuffer.
+    pub fn render(&mut self, screen_size: Vec2) {
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        // Stable, sprites of a layer keep their order.
+        self.sprites.sort_by_key(|queued| queued.sprite.layer);
+
+        let mut vertices = Vec::with_capacity(self.sprites.len() * 6);
+        let mut draws: Vec<SpriteDraw> = vec![];
+
+        for QueuedSprite { sprite, scissor } in &self.sprites {
+            let texture = sprite
+                .texture
+                .as_ref()
+                .map_or(self.white.get_id(), |texture| texture.get_id());
+
+            match draws.last_mut() {
+                Some(draw) if draw.texture == texture && draw.scissor == *scissor => {
+                    draw.vertex_count += 6
+                }
+                _ => draws.push(SpriteDraw {
+                    texture,
+                    scissor: *scissor,
+                    first: vertices.len(),
+                    vertex_count: 6,
+                }),
+            }
+
+            let min = sprite.position;
+            let max = sprite.position + sprite.size;
+            let vertex = |x: f32, y: f32, u: f32, v: f32| SpriteVertex {
+                position: Vec2::new(x, y),
+                tex_coord: Vec2::new(u, v),
+                color: sprite.color,
+            };
+
+            // Images are uploaded top row first, v runs down the screen as y does.
+            let (u0, u1) = (sprite.uv_min.x, sprite.uv_max.x);
+            let (v0, v1) = (sprite.uv_min.y, sprite.uv_max.y);
+
+            vertices.extend_from_slice(&[
+                vertex(min.x, min.y, u0, v0),
+                vertex(min.x, max.y, u0, v1),
+                vertex(max.x, min.y, u1, v0),
+                vertex(max.x, min.y, u1, v0),
+                vertex(min.x, max.y, u0, v1),
+                vertex(max.x, max.y, u1, v1),
+            ]);
+        }
+
+        self.sprites.clear();
+
+        self.vertex_buffer.begin_frame();
+
+        let allocation = match self.vertex_buffer.push_slice(&vertices) {
+            Some(allocation) => allocation,
+            None => {
+                log::warn!(
+                    "Sprite batch budget exceeded, dropping {} sprites",
+                    vertices.len() / 6
+                );
+                self.vertex_buffer.end_frame();
+                return;
+            }
+        };
+
+        self.pipeline_state.bind();
+        self.pipeline_state
+            .program_pipeline()
+            .set_vec2_all_stages("screenSize", &screen_size);
+
+        StateManager::set_viewport(0, 0, screen_size.x as i32, screen_size.y as i32);
+        StateManager::bind_vertex_array(self.vao);
+
+        unsafe {
+            gl::VertexArrayVertexBuffer(
+                self.vao,
+                0,
+                self.vertex_buffer.get_buffer().get_id(),
+                allocation.offset,
+                mem::size_of::<SpriteVertex>() as i32,
+            )
+        }
+
+        for draw in &draws {
+            // GL counts rows from the bottom of the screen.
+            StateManager::set_scissor(draw.scissor.map(|scissor| {
+                Rectangle::new(
+                    scissor.x,
+                    screen_size.y as i32 - scissor.y - scissor.height,
+                    scissor.width,
+                    scissor.height,
+                )
+            }));
+
+            self.pipeline_state
+                .program_pipeline()
+                .set_texture_2d_with_id(TEXTURE_BINDING_INDEX, draw.texture, &self.sampler);
+
+            unsafe { gl::DrawArrays(gl::TRIANGLES, draw.first as i32, draw.vertex_count as i32) }
+
+            FrameStats::record_draw(PrimitiveMode::Triangles, draw.vertex_count, 1)
+        }
+
+        StateManager::set_scissor(None);
+
+        self.pipeline_state.unbind();
+        self.vertex_buffer.end_frame();
+    }
+}
+
+impl Drop for SpriteBatch {
+    fn drop(&mut self) {
+        StateManager::vertex_array_deleted(self.vao);
+        unsafe { gl::DeleteVertexArrays(1, &self.vao) }
+    }
+}
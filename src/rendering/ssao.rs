@@ -0,0 +1,354 @@
+use crate::{
+    core::asset::embedded::EmbeddedAssets,
+    core::camera::Camera,
+    core::math::{Mat4, UVec2, Vec2, Vec4},
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        draw_list::DrawList,
+        error::RendererError,
+        framebuffer::{
+            AttachmentType, Framebuffer, FramebufferAttachment, FramebufferAttachmentCreateInfo,
+        },
+        per_draw::{PerDrawData, PerDrawUniforms},
+        pipeline_state::{PipelineState, PipelineStateBuilder},
+        postprocess::fullscreen_pass::FullscreenPass,
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        state::{DepthStencilState, FixedFunctionState, RasterizerState, StateManager},
+        texture::SizedTextureFormat,
+    },
+    Msaa,
+};
+use std::ops::RangeInclusive;
+
+// Screen space ambient occlusion. A depth and normal prepass feeds a hemisphere occlusion pass
+// whose result is blurred and sampled by the ambient term of the lighting, which adds the
+// contact occlusion between objects that baked AO maps can't have.
+pub struct Ssao {
+    prepass_framebuffer: Framebuffer,
+    occlusion_framebuffer: Framebuffer,
+    blur_framebuffer: Framebuffer,
+    prepass_pipeline_state: PipelineState,
+    occlusion_pass: FullscreenPass,
+    blur_pass: FullscreenPass,
+    sampler_nearest: Sampler,
+    sampler_linear: Sampler,
+    // World space radius of the sampled hemisphere.
+    radius: f32,
+    bias: f32,
+    // Exponent of the occlusion term.
+    intensity: f32,
+    blur: bool,
+    // How quickly blur taps lose weight with their depth difference.
+    blur_sharpness: f32,
+    enabled: bool,
+}
+
+impl Ssao {
+    pub fn new(size: UVec2) -> Result<Self, RendererError> {
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                "src/rendering/shaders/ssao_prepass.vert",
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                "src/rendering/shaders/ssao_prepass.frag",
+            )?)
+            .build()?;
+
+        let sampler = |filter: MinificationFilter, magnification: MagnificationFilter| {
+            Sampler::new(
+                filter,
+                magnification,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            )
+        };
+
+        Ok(Self {
+            prepass_framebuffer: Self::create_prepass_framebuffer(size)?,
+            occlusion_framebuffer: Self::create_occlusion_framebuffer(size)?,
+            blur_framebuffer: Self::create_occlusion_framebuffer(size)?,
+            prepass_pipeline_state: PipelineStateBuilder::new(program_pipeline).build(),
+            occlusion_pass: FullscreenPass::new("src/rendering/shaders/ssao.frag")?,
+            blur_pass: FullscreenPass::new("src/rendering/shaders/ssao_blur.frag")?,
+            sampler_nearest: sampler(MinificationFilter::Nearest, MagnificationFilter::Nearest),
+            sampler_linear: sampler(MinificationFilter::Linear, MagnificationFilter::Linear),
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.5,
+            blur: true,
+            blur_sharpness: 4.0,
+            enabled: true,
+        })
+    }
+
+    // Keeps the current framebuffers if the new ones can't be created.
+    pub fn resize(&mut self, size: UVec2) -> Result<(), RendererError> {
+        if size == self.prepass_framebuffer.size() {
+            return Ok(());
+        }
+
+        let prepass_framebuffer = Self::create_prepass_framebuffer(size)?;
+        let occlusion_framebuffer = Self::create_occlusion_framebuffer(size)?;
+        let blur_framebuffer = Self::create_occlusion_framebuffer(size)?;
+
+        self.prepass_framebuffer = prepass_framebuffer;
+        self.occlusion_framebuffer = occlusion_framebuffer;
+        self.blur_framebuffer = blur_framebuffer;
+
+        Ok(())
+    }
+
+    // Renders depth and normals of whatever draw issues, e.g. meshes with their per draw block
+    // pushed. Expects the per frame block (binding 0) to be filled.
+    pub fn prepass<F: FnOnce()>(&self, draw: F) {
+        if !self.enabled {
+            return;
+        }
+
+        self.prepass_framebuffer.bind();
+        self.prepass_framebuffer
+            .clear(&Vec4::new(0.0, 0.0, 0.0, 0.0));
+
+        self.prepass_pipeline_state.bind();
+        draw();
+        self.prepass_pipeline_state.unbind();
+
+        self.prepass_framebuffer.unbind(false);
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    pub fn prepass_draw_list(&self, draw_list: &DrawList, per_draw_uniforms: &mut PerDrawUniforms) {
+        self.prepass(|| {
+            for item in draw_list.items() {
+                if per_draw_uniforms
+                    .push_and_bind(&PerDrawData::new(item.model, 0))
+                    .is_none()
+                {
+                    break;
+                }
+
+                item.mesh
+                    .draw_with_primitive_mode(item.material.borrow().primitive_mode());
+            }
+        })
+    }
+
+    // Computes the occlusion from the prepass. camera must be the one the prepass was rendered
+    // with.
+    pub fn compute(&self, camera: &Camera) {
+        if !self.enabled {
+            return;
+        }
+
+        let projection = camera.projection_matrix();
+        let inverse_projection = projection.try_inverse().unwrap_or_else(Mat4::identity);
+        let depth = self.prepass_framebuffer.texture_attachments()[1];
+        let normals = self.prepass_framebuffer.texture_attachments()[0];
+
+        StateManager::apply(&FixedFunctionState {
+            depth_stencil: DepthStencilState {
+                depth_test: false,
+                depth_write: false,
+                ..Default::default()
+            },
+            blend: None,
+            rasterizer: RasterizerState {
+                face_culling: None,
+                ..Default::default()
+            },
+        });
+
+        self.occlusion_framebuffer.bind();
+
+        self.occlusion_pass.bind();
+        self.occlusion_pass
+            .pipeline()
+            .set_mat4_all_stages("projection", &projection)
+            .set_mat4_all_stages("inverseProjection", &inverse_projection)
+            .set_mat4_all_stages("view", camera.transform())
+            .set_float_all_stages("radius", self.radius)
+            .set_float_all_stages("bias", self.bias)
+            .set_float_all_stages("intensity", self.intensity);
+        self.occlusion_pass
+            .set_texture("depthMap", depth.id(), &self.sampler_nearest)
+            .set_texture("normalMap", normals.id(), &self.sampler_nearest)
+            .draw();
+        self.occlusion_pass.unbind();
+
+        self.occlusion_framebuffer.unbind(false);
+
+        if self.blur {
+            self.blur_pass.bind();
+            self.blur_pass
+                .pipeline()
+                .set_mat4_all_stages("inverseProjection", &inverse_projection)
+                .set_float_all_stages("sharpness", self.blur_sharpness);
+
+            self.blur_pass
+                .set_texture("depthMap", depth.id(), &self.sampler_nearest);
+
+            // Horizontally into the blur target, then vertically back.
+            let passes = [
+                (
+                    &self.occlusion_framebuffer,
+                    &self.blur_framebuffer,
+                    Vec2::new(1.0, 0.0),
+                ),
+                (
+                    &self.blur_framebuffer,
+                    &self.occlusion_framebuffer,
+                    Vec2::new(0.0, 1.0),
+                ),
+            ];
+
+            for (source, destination, direction) in passes.iter() {
+                destination.bind();
+
+                self.blur_pass
+                    .pipeline()
+                    .set_vec2_all_stages("direction", direction);
+                self.blur_pass
+                    .set_texture(
+                        "image",
+                        source.texture_attachments()[0].id(),
+                        &self.sampler_linear,
+                    )
+                    .draw();
+
+                destination.unbind(false);
+            }
+
+            self.blur_pass.unbind();
+        }
+
+        StateManager::apply(&FixedFunctionState::default())
+    }
+
+    // R8 occlusion, 1 is unoccluded. Sample it with the fragment position over its size.
+    pub fn occlusion(&self) -> FramebufferAttachment {
+        self.occlusion_framebuffer.texture_attachments()[0]
+    }
+
+    // Binds the occlusion with a linear sampler to the texture unit of the pipeline.
+    pub fn bind_occlusion(&self, program_pipeline: &ProgramPipeline, binding: u32) {
+        program_pipeline.set_texture_2d_with_id(
+            binding,
+            self.occlusion().id(),
+            &self.sampler_linear,
+        );
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity
+    }
+
+    pub fn set_blur(&mut self, blur: bool) {
+        self.blur = blur
+    }
+
+    pub fn set_blur_sharpness(&mut self, blur_sharpness: f32) {
+        self.blur_sharpness = blur_sharpness
+    }
+
+    fn create_prepass_framebuffer(size: UVec2) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Rgba16f,
+                    AttachmentType::Texture,
+                ),
+                FramebufferAttachmentCreateInfo::new(
+                    SizedTextureFormat::Depth32f,
+                    AttachmentType::Texture,
+                ),
+            ],
+        )
+        .map_err(RendererError::from)
+    }
+
+    fn create_occlusion_framebuffer(size: UVec2) -> Result<Framebuffer, RendererError> {
+        Framebuffer::new(
+            size,
+            Msaa::None,
+            vec![FramebufferAttachmentCreateInfo::new(
+                SizedTextureFormat::R8,
+                AttachmentType::Texture,
+            )],
+        )
+        .map_err(RendererError::from)
+    }
+}
+
+impl Gui for Ssao {
+    fn gui(&mut self, ui: &Ui) {
+        ui.group(|| {
+            ui.checkbox(im_str!("##ssao"), &mut self.enabled);
+            ui.same_line(20.0);
+            imgui::TreeNode::new(im_str!("Ambient Occlusion"))
+                .default_open(true)
+                .open_on_arrow(true)
+                .open_on_double_click(true)
+                .framed(false)
+                .build(ui, || {
+                    ui.indent();
+                    imgui::Slider::new(im_str!("Radius"))
+                        .range(RangeInclusive::new(0.05, 5.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.radius);
+                    imgui::Slider::new(im_str!("Bias"))
+                        .range(RangeInclusive::new(0.0, 0.2))
+                        .display_format(im_str!("%.3f"))
+                        .build(&ui, &mut self.bias);
+                    imgui::Slider::new(im_str!("Intensity"))
+                        .range(RangeInclusive::new(0.1, 8.0))
+                        .display_format(im_str!("%.2f"))
+                        .build(&ui, &mut self.intensity);
+                    ui.checkbox(im_str!("Bilateral Blur"), &mut self.blur);
+                    if self.blur {
+                        imgui::Slider::new(im_str!("Blur Sharpness"))
+                            .range(RangeInclusive::new(0.0, 32.0))
+                            .display_format(im_str!("%.1f"))
+                            .build(&ui, &mut self.blur_sharpness);
+                    }
+                    ui.unindent()
+                });
+        });
+    }
+}
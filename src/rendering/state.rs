@@ -1,8 +1,15 @@
+use crate::{core::Rectangle, rendering::frame_stats::FrameStats};
+use gl::types::*;
 use gl_bindings as gl;
+use std::{cell::RefCell, collections::HashMap};
+
+// Texture units above are bound without tracking.
+const TRACKED_TEXTURE_UNITS: usize = 32;
 
 pub struct StateManager;
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlendFactor {
     Zero = gl::ZERO,
     One = gl::ONE,
@@ -26,44 +33,540 @@ pub enum BlendFactor {
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DepthFunction {
+    Never = gl::NEVER,
+    Less = gl::LESS,
+    Equal = gl::EQUAL,
+    LessOrEqual = gl::LEQUAL,
+    Greater = gl::GREATER,
+    NotEqual = gl::NOTEQUAL,
+    GreaterOrEqual = gl::GEQUAL,
+    Always = gl::ALWAYS,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StencilFunction {
+    Never = gl::NEVER,
     Less = gl::LESS,
+    Equal = gl::EQUAL,
     LessOrEqual = gl::LEQUAL,
+    Greater = gl::GREATER,
+    NotEqual = gl::NOTEQUAL,
+    GreaterOrEqual = gl::GEQUAL,
+    Always = gl::ALWAYS,
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StencilOperation {
+    Keep = gl::KEEP,
+    Zero = gl::ZERO,
+    Replace = gl::REPLACE,
+    Increment = gl::INCR,
+    IncrementWrap = gl::INCR_WRAP,
+    Decrement = gl::DECR,
+    DecrementWrap = gl::DECR_WRAP,
+    Invert = gl::INVERT,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FaceCulling {
     Front = gl::FRONT,
     Back = gl::BACK,
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FrontFace {
     Clockwise = gl::CW,
     CounterClockwise = gl::CCW,
 }
 
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolygonMode {
+    Point = gl::POINT,
+    Line = gl::LINE,
+    Fill = gl::FILL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StencilState {
+    pub function: StencilFunction,
+    pub reference: i32,
+    pub mask: u32,
+    pub stencil_fail: StencilOperation,
+    pub depth_fail: StencilOperation,
+    pub pass: StencilOperation,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self {
+            function: StencilFunction::Always,
+            reference: 0,
+            mask: 0xFF,
+            stencil_fail: StencilOperation::Keep,
+            depth_fail: StencilOperation::Keep,
+            pass: StencilOperation::Keep,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthStencilState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_function: DepthFunction,
+    pub stencil: Option<StencilState>,
+}
+
+impl Default for DepthStencilState {
+    fn default() -> Self {
+        Self {
+            depth_test: true,
+            depth_write: true,
+            depth_function: DepthFunction::Less,
+            stencil: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlendState {
+    pub source_factor: BlendFactor,
+    pub destination_factor: BlendFactor,
+}
+
+impl BlendState {
+    pub fn alpha_blending() -> Self {
+        Self {
+            source_factor: BlendFactor::SourceAlpha,
+            destination_factor: BlendFactor::OneMinusSourceAlpha,
+        }
+    }
+
+    pub fn additive() -> Self {
+        Self {
+            source_factor: BlendFactor::One,
+            destination_factor: BlendFactor::One,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterizerState {
+    pub face_culling: Option<FaceCulling>,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+}
+
+impl Default for RasterizerState {
+    fn default() -> Self {
+        Self {
+            face_culling: Some(FaceCulling::Back),
+            front_face: FrontFace::CounterClockwise,
+            polygon_mode: PolygonMode::Fill,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FixedFunctionState {
+    pub depth_stencil: DepthStencilState,
+    // None disables blending.
+    pub blend: Option<BlendState>,
+    pub rasterizer: RasterizerState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BufferBinding {
+    buffer: GLuint,
+    offset: isize,
+    size: isize,
+}
+
+// The objects last bound through the StateManager. None where it does not know, binding then
+// always issues the GL call.
+#[derive(Debug, Default)]
+struct BoundObjects {
+    framebuffer: Option<GLuint>,
+    viewport: Option<[i32; 4]>,
+    // None inside disables the scissor test.
+    scissor: Option<Option<[i32; 4]>>,
+    program_pipeline: Option<GLuint>,
+    vertex_array: Option<GLuint>,
+    textures: [Option<GLuint>; TRACKED_TEXTURE_UNITS],
+    samplers: [Option<GLuint>; TRACKED_TEXTURE_UNITS],
+    // (target, binding index) -> buffer range
+    buffers: HashMap<(GLenum, GLuint), BufferBinding>,
+}
+
+thread_local! {
+    // Shadow copy of the fixed function state last applied through the StateManager.
+    // None until the first full apply, since the initial GL state is not tracked.
+    static CURRENT_STATE: RefCell<Option<FixedFunctionState>> = RefCell::new(None);
+    static BOUND_OBJECTS: RefCell<BoundObjects> = RefCell::new(BoundObjects::default());
+}
+
+// Every bind of the engine goes through the StateManager, which keeps a shadow copy of the GL
+// state and skips the calls that would not change it. GL code outside of the engine, e.g. the UI
+// renderer, has to be followed by StateManager::invalidate.
 impl StateManager {
     pub fn set_viewport(x: i32, y: i32, width: i32, height: i32) {
-        unsafe { gl::Viewport(x, y, width, height) }
+        if Self::update_binding(|bound| &mut bound.viewport, [x, y, width, height]) {
+            unsafe { gl::Viewport(x, y, width, height) }
+        }
+    }
+
+    // Limits drawing and clearing to the rectangle. None disables the scissor test.
+    pub fn set_scissor(rectangle: Option<Rectangle>) {
+        let scissor = rectangle.map(|r| [r.x, r.y, r.width, r.height]);
+
+        if Self::update_binding(|bound| &mut bound.scissor, scissor) {
+            unsafe {
+                match scissor {
+                    Some([x, y, width, height]) => {
+                        gl::Enable(gl::SCISSOR_TEST);
+                        gl::Scissor(x, y, width, height)
+                    }
+                    None => gl::Disable(gl::SCISSOR_TEST),
+                }
+            }
+        }
+    }
+
+    // Binds to both the draw and the read framebuffer.
+    pub fn bind_framebuffer(framebuffer: GLuint) {
+        if Self::update_binding(|bound| &mut bound.framebuffer, framebuffer) {
+            unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer) }
+        }
+    }
+
+    pub fn bind_program_pipeline(program_pipeline: GLuint) {
+        if Self::update_binding(|bound| &mut bound.program_pipeline, program_pipeline) {
+            unsafe { gl::BindProgramPipeline(program_pipeline) }
+            FrameStats::record_pipeline_bind()
+        }
+    }
+
+    pub fn bind_vertex_array(vertex_array: GLuint) {
+        if Self::update_binding(|bound| &mut bound.vertex_array, vertex_array) {
+            unsafe { gl::BindVertexArray(vertex_array) }
+        }
+    }
+
+    pub fn bind_texture_unit(unit: u32, texture: GLuint) {
+        let bind = (unit as usize) >= TRACKED_TEXTURE_UNITS
+            || Self::update_binding(|bound| &mut bound.textures[unit as usize], texture);
+
+        if bind {
+            unsafe { gl::BindTextureUnit(unit, texture) }
+            FrameStats::record_texture_bind()
+        }
+    }
+
+    pub fn bind_sampler(unit: u32, sampler: GLuint) {
+        let bind = (unit as usize) >= TRACKED_TEXTURE_UNITS
+            || Self::update_binding(|bound| &mut bound.samplers[unit as usize], sampler);
+
+        if bind {
+            unsafe { gl::BindSampler(unit, sampler) }
+        }
+    }
+
+    // Binds a range of the buffer to an indexed target, e.g. gl::UNIFORM_BUFFER.
+    pub fn bind_buffer_range(
+        target: GLenum,
+        index: GLuint,
+        buffer: GLuint,
+        offset: isize,
+        size: isize,
+    ) {
+        let binding = BufferBinding {
+            buffer,
+            offset,
+            size,
+        };
+
+        let changed = BOUND_OBJECTS.with(|bound| {
+            bound.borrow_mut().buffers.insert((target, index), binding) != Some(binding)
+        });
+
+        if changed {
+            unsafe { gl::BindBufferRange(target, index, buffer, offset, size) }
+        } else {
+            FrameStats::record_redundant_bind()
+        }
+    }
+
+    // GL unbinds deleted objects, the shadow copy has to follow. Otherwise an object created
+    // later with the same name would be taken as bound.
+    pub(crate) fn texture_deleted(texture: GLuint) {
+        Self::forget_binding(|bound| {
+            for unit in bound.textures.iter_mut() {
+                if *unit == Some(texture) {
+                    *unit = Some(0)
+                }
+            }
+        })
+    }
+
+    pub(crate) fn sampler_deleted(sampler: GLuint) {
+        Self::forget_binding(|bound| {
+            for unit in bound.samplers.iter_mut() {
+                if *unit == Some(sampler) {
+                    *unit = Some(0)
+                }
+            }
+        })
+    }
+
+    pub(crate) fn buffer_deleted(buffer: GLuint) {
+        Self::forget_binding(|bound| bound.buffers.retain(|_, binding| binding.buffer != buffer))
+    }
+
+    pub(crate) fn framebuffer_deleted(framebuffer: GLuint) {
+        Self::forget_binding(|bound| {
+            if bound.framebuffer == Some(framebuffer) {
+                bound.framebuffer = Some(0)
+            }
+        })
+    }
+
+    pub(crate) fn program_pipeline_deleted(program_pipeline: GLuint) {
+        Self::forget_binding(|bound| {
+            if bound.program_pipeline == Some(program_pipeline) {
+                bound.program_pipeline = Some(0)
+            }
+        })
+    }
+
+    pub(crate) fn vertex_array_deleted(vertex_array: GLuint) {
+        Self::forget_binding(|bound| {
+            if bound.vertex_array == Some(vertex_array) {
+                bound.vertex_array = Some(0)
+            }
+        })
     }
 
     pub fn set_blend_function(source_factor: BlendFactor, destination_factor: BlendFactor) {
         unsafe { gl::BlendFunc(source_factor as u32, destination_factor as u32) }
+
+        Self::update_current_state(|state| {
+            if let Some(blend) = state.blend.as_mut() {
+                blend.source_factor = source_factor;
+                blend.destination_factor = destination_factor;
+            }
+        })
     }
 
     pub fn set_depth_function(depth_function: DepthFunction) {
         unsafe { gl::DepthFunc(depth_function as u32) }
+
+        Self::update_current_state(|state| state.depth_stencil.depth_function = depth_function)
     }
 
     pub fn set_face_culling(culling: FaceCulling) {
         unsafe { gl::CullFace(culling as u32) }
+
+        Self::update_current_state(|state| {
+            if state.rasterizer.face_culling.is_some() {
+                state.rasterizer.face_culling = Some(culling)
+            }
+        })
     }
 
     pub fn set_front_face(front_face: FrontFace) {
         unsafe { gl::FrontFace(front_face as u32) }
+
+        Self::update_current_state(|state| state.rasterizer.front_face = front_face)
+    }
+
+    pub fn set_patch_vertices(count: u32) {
+        assert!(count > 0, "Patch vertex count must be > 0.");
+        unsafe { gl::PatchParameteri(gl::PATCH_VERTICES, count as i32) }
+    }
+
+    // Skips rasterization entirely. Useful when only capturing vertex outputs with
+    // transform feedback.
+    pub fn set_rasterizer_discard(enabled: bool) {
+        unsafe { Self::set_capability(gl::RASTERIZER_DISCARD, enabled) }
+    }
+
+    // Turns the alpha of the fragments into the coverage of the samples of multisampled targets,
+    // order independent transparency for cutouts like foliage. No effect on other targets.
+    pub fn set_alpha_to_coverage(enabled: bool) {
+        unsafe { Self::set_capability(gl::SAMPLE_ALPHA_TO_COVERAGE, enabled) }
+    }
+
+    // Offsets the depth of filled polygons by factor times their depth slope plus units times the
+    // smallest resolvable depth difference. Zero disables it.
+    pub fn set_polygon_offset(factor: f32, units: f32) {
+        unsafe {
+            Self::set_capability(gl::POLYGON_OFFSET_FILL, factor != 0.0 || units != 0.0);
+            gl::PolygonOffset(factor, units)
+        }
+    }
+
+    pub fn apply(state: &FixedFunctionState) {
+        let current = CURRENT_STATE.with(|current| *current.borrow());
+
+        match current {
+            Some(current) => {
+                if current.depth_stencil != state.depth_stencil {
+                    Self::apply_depth_stencil_state(
+                        &state.depth_stencil,
+                        Some(&current.depth_stencil),
+                    );
+                    FrameStats::record_state_change()
+                }
+
+                if current.blend != state.blend {
+                    Self::apply_blend_state(state.blend.as_ref(), Some(current.blend.as_ref()));
+                    FrameStats::record_state_change()
+                }
+
+                if current.rasterizer != state.rasterizer {
+                    Self::apply_rasterizer_state(&state.rasterizer, Some(&current.rasterizer));
+                    FrameStats::record_state_change()
+                }
+            }
+            None => {
+                Self::apply_depth_stencil_state(&state.depth_stencil, None);
+                Self::apply_blend_state(state.blend.as_ref(), None);
+                Self::apply_rasterizer_state(&state.rasterizer, None);
+            }
+        }
+
+        CURRENT_STATE.with(|current| *current.borrow_mut() = Some(*state))
+    }
+
+    pub fn current_state() -> Option<FixedFunctionState> {
+        CURRENT_STATE.with(|current| *current.borrow())
+    }
+
+    // Forgets the shadow state. The next apply and every next bind will issue the GL calls.
+    pub fn invalidate() {
+        CURRENT_STATE.with(|current| *current.borrow_mut() = None);
+        BOUND_OBJECTS.with(|bound| *bound.borrow_mut() = BoundObjects::default())
+    }
+
+    // True if the value changed and the GL call has to be issued.
+    fn update_binding<T, F>(f: F, value: T) -> bool
+    where
+        T: Copy + PartialEq,
+        F: FnOnce(&mut BoundObjects) -> &mut Option<T>,
+    {
+        let changed = BOUND_OBJECTS.with(|bound| {
+            let mut bound = bound.borrow_mut();
+            let current = f(&mut *bound);
+
+            current.replace(value) != Some(value)
+        });
+
+        if !changed {
+            FrameStats::record_redundant_bind()
+        }
+
+        changed
+    }
+
+    fn forget_binding<F: FnOnce(&mut BoundObjects)>(f: F) {
+        // Objects dropped during thread teardown outlive the shadow state.
+        let _ = BOUND_OBJECTS.try_with(|bound| f(&mut *bound.borrow_mut()));
+    }
+
+    fn update_current_state<F: FnOnce(&mut FixedFunctionState)>(f: F) {
+        CURRENT_STATE.with(|current| {
+            if let Some(state) = current.borrow_mut().as_mut() {
+                f(state)
+            }
+        })
+    }
+
+    fn apply_depth_stencil_state(state: &DepthStencilState, current: Option<&DepthStencilState>) {
+        unsafe {
+            if current.map_or(true, |c| c.depth_test != state.depth_test) {
+                Self::set_capability(gl::DEPTH_TEST, state.depth_test)
+            }
+
+            if current.map_or(true, |c| c.depth_write != state.depth_write) {
+                gl::DepthMask(if state.depth_write {
+                    gl::TRUE
+                } else {
+                    gl::FALSE
+                })
+            }
+
+            if current.map_or(true, |c| c.depth_function != state.depth_function) {
+                gl::DepthFunc(state.depth_function as u32)
+            }
+
+            if current.map_or(true, |c| c.stencil != state.stencil) {
+                match state.stencil {
+                    Some(stencil) => {
+                        Self::set_capability(gl::STENCIL_TEST, true);
+                        gl::StencilFunc(stencil.function as u32, stencil.reference, stencil.mask);
+                        gl::StencilOp(
+                            stencil.stencil_fail as u32,
+                            stencil.depth_fail as u32,
+                            stencil.pass as u32,
+                        );
+                    }
+                    None => Self::set_capability(gl::STENCIL_TEST, false),
+                }
+            }
+        }
+    }
+
+    fn apply_blend_state(state: Option<&BlendState>, current: Option<Option<&BlendState>>) {
+        unsafe {
+            match state {
+                Some(blend) => {
+                    if current.map_or(true, |c| c.is_none()) {
+                        Self::set_capability(gl::BLEND, true)
+                    }
+
+                    if current.map_or(true, |c| c != Some(blend)) {
+                        gl::BlendFunc(blend.source_factor as u32, blend.destination_factor as u32)
+                    }
+                }
+                None => Self::set_capability(gl::BLEND, false),
+            }
+        }
+    }
+
+    fn apply_rasterizer_state(state: &RasterizerState, current: Option<&RasterizerState>) {
+        unsafe {
+            if current.map_or(true, |c| c.face_culling != state.face_culling) {
+                match state.face_culling {
+                    Some(culling) => {
+                        Self::set_capability(gl::CULL_FACE, true);
+                        gl::CullFace(culling as u32)
+                    }
+                    None => Self::set_capability(gl::CULL_FACE, false),
+                }
+            }
+
+            if current.map_or(true, |c| c.front_face != state.front_face) {
+                gl::FrontFace(state.front_face as u32)
+            }
+
+            if current.map_or(true, |c| c.polygon_mode != state.polygon_mode) {
+                gl::PolygonMode(gl::FRONT_AND_BACK, state.polygon_mode as u32)
+            }
+        }
+    }
+
+    unsafe fn set_capability(capability: u32, enabled: bool) {
+        if enabled {
+            gl::Enable(capability)
+        } else {
+            gl::Disable(capability)
+        }
     }
 }
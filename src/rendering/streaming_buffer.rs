@@ -0,0 +1,121 @@
+use crate::rendering::{
+    buffer::{Buffer, BufferStorageFlags, BufferTarget, MapModeFlags},
+    fence::{FenceWaitResult, GpuFence},
+};
+use std::mem;
+
+// Number of frame regions in the ring. The CPU writes one region while the GPU may still be
+// reading the other two.
+pub const FRAMES_IN_FLIGHT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingAllocation {
+    pub offset: isize,
+    pub size: isize,
+}
+
+// A persistently mapped ring buffer for transient per-frame data. Each frame sub-allocates
+// linearly from its own region which is reused FRAMES_IN_FLIGHT frames later, once the GPU is
+// done with it.
+pub struct StreamingBuffer {
+    buffer: Buffer,
+    frame_size: isize,
+    frame_index: usize,
+    head: isize,
+    alignment: isize,
+    fences: [Option<GpuFence>; FRAMES_IN_FLIGHT],
+}
+
+impl StreamingBuffer {
+    pub fn new(name: &str, frame_size: isize, target: BufferTarget) -> Self {
+        let alignment = Buffer::offset_alignment(target);
+        let frame_size = Buffer::align_offset(frame_size, target);
+
+        let mut buffer = Buffer::new(
+            name,
+            frame_size * FRAMES_IN_FLIGHT as isize,
+            target,
+            BufferStorageFlags::MAP_WRITE_PERSISTENT_COHERENT,
+        );
+        buffer.map(MapModeFlags::MAP_WRITE_PERSISTENT_COHERENT);
+
+        Self {
+            buffer,
+            frame_size,
+            frame_index: 0,
+            head: 0,
+            alignment,
+            fences: Default::default(),
+        }
+    }
+
+    // Moves to the next frame region, waiting for the GPU if it is still reading it.
+    pub fn begin_frame(&mut self) {
+        self.frame_index = (self.frame_index + 1) % FRAMES_IN_FLIGHT;
+        self.head = 0;
+
+        if let Some(fence) = self.fences[self.frame_index].take() {
+            if fence.wait() == FenceWaitResult::Failed {
+                log::error!("Waiting on streaming buffer fence failed.");
+            }
+        }
+    }
+
+    // Must be called after the last draw call that reads from this frame's allocations.
+    pub fn end_frame(&mut self) {
+        self.fences[self.frame_index] = Some(GpuFence::new())
+    }
+
+    pub fn allocate(&mut self, size: isize) -> Option<StreamingAllocation> {
+        let head = Self::align(self.head, self.alignment);
+
+        if head + size > self.frame_size {
+            log::warn!(
+                "Streaming buffer out of space. Frame size: {}, Requested: {}",
+                self.frame_size,
+                head + size
+            );
+            return None;
+        }
+
+        self.head = head + size;
+
+        Some(StreamingAllocation {
+            offset: self.frame_index as isize * self.frame_size + head,
+            size,
+        })
+    }
+
+    pub fn push<T: Sized>(&mut self, data: &T) -> Option<StreamingAllocation> {
+        self.push_slice(std::slice::from_ref(data))
+    }
+
+    pub fn push_slice<T: Sized>(&mut self, data: &[T]) -> Option<StreamingAllocation> {
+        let allocation = self.allocate((data.len() * mem::size_of::<T>()) as isize)?;
+
+        self.buffer.fill_mapped_slice(allocation.offset, data);
+
+        Some(allocation)
+    }
+
+    pub fn bind(&self, binding_index: u32, allocation: &StreamingAllocation) {
+        self.buffer
+            .bind_range(binding_index, allocation.offset, allocation.size)
+    }
+
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn get_frame_size(&self) -> isize {
+        self.frame_size
+    }
+
+    pub fn bytes_used(&self) -> isize {
+        self.head
+    }
+
+    fn align(value: isize, alignment: isize) -> isize {
+        (value + alignment - 1) / alignment * alignment
+    }
+}
@@ -0,0 +1,395 @@
+pub mod heightmap;
+pub mod material;
+pub mod quadtree;
+
+use crate::{
+    core::camera::Camera,
+    core::math::{Vec2, Vec3, Vec4},
+    geometry::bounds::Aabb,
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        instancing::{InstanceBuffer, InstanceData},
+        material::Material,
+        mesh::{Mesh, Vertex},
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        texture::{SizedTextureFormat, Texture2D},
+    },
+};
+use gl::types::*;
+use gl_bindings as gl;
+use nalgebra_glm as glm;
+use std::ops::RangeInclusive;
+
+use self::{
+    heightmap::Heightmap,
+    material::TerrainMaterial,
+    quadtree::{Quadtree, SelectionView, TerrainPatch},
+};
+
+const HEIGHT_MAP_BINDING_INDEX: u32 = 6;
+const NORMAL_MAP_BINDING_INDEX: u32 = 8;
+// The size of the morphRanges array of terrain.vert.
+pub const MAX_LOD_COUNT: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainSettings {
+    // The corner of the terrain at the lowest x and z.
+    pub origin: Vec3,
+    // The x and z extents of the terrain and the height of a heightmap sample of 1.
+    pub size: Vec3,
+    pub lod_count: u32,
+    // Quads along the side of the grid every patch is drawn with.
+    pub patch_resolution: u32,
+    // Up to which distance the finest level is used. Every level reaches twice as far as the
+    // previous one.
+    pub detail_distance: f32,
+    // Where in the range of a level its vertices start to morph into the ones of the next
+    // level, from 0 to 1.
+    pub morph_start: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            origin: Vec3::new(-512.0, 0.0, -512.0),
+            size: Vec3::new(1024.0, 128.0, 1024.0),
+            lod_count: 6,
+            patch_resolution: 32,
+            detail_distance: 32.0,
+            morph_start: 0.7,
+        }
+    }
+}
+
+// A heightmap terrain. Drawn with continuous distance-dependent levels of detail: every frame
+// the quadtree selects patches of the terrain by their distance to the camera, which are drawn
+// as instances of one grid. The vertex shader displaces the grid by the heightmap and morphs the
+// vertices of each level into the ones of the next, so levels meet without cracks or popping.
+// Normals are generated from the heightmap. Shaded like the PBS materials, with the lights, the
+// shadows and the environment of the scene bound.
+pub struct Terrain {
+    settings: TerrainSettings,
+    heightmap: Heightmap,
+    quadtree: Quadtree,
+    height_map: Texture2D,
+    normal_map: Texture2D,
+    sampler: Sampler,
+    patch_mesh: Mesh,
+    instances: InstanceBuffer,
+    patches: Vec<TerrainPatch>,
+    material: TerrainMaterial,
+}
+
+impl Terrain {
+    pub fn new(heightmap: Heightmap, settings: TerrainSettings, material: TerrainMaterial) -> Self {
+        let mut settings = settings;
+        settings.lod_count = settings.lod_count.max(1).min(MAX_LOD_COUNT);
+        settings.patch_resolution = settings.patch_resolution.max(2) / 2 * 2;
+
+        let quadtree = Quadtree::new(
+            &heightmap,
+            settings.origin,
+            settings.size,
+            settings.lod_count,
+        );
+
+        let (height_map, normal_map) = Self::create_textures(&heightmap, &settings.size);
+
+        let patch_mesh = Self::create_patch_mesh(settings.patch_resolution);
+        let instances = InstanceBuffer::new(64);
+        patch_mesh.set_instance_buffer(&instances);
+
+        Self {
+            settings,
+            heightmap,
+            quadtree,
+            height_map,
+            normal_map,
+            sampler: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            patch_mesh,
+            instances,
+            patches: vec![],
+            material,
+        }
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    pub fn heightmap(&self) -> &Heightmap {
+        &self.heightmap
+    }
+
+    pub fn material(&self) -> &TerrainMaterial {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut TerrainMaterial {
+        &mut self.material
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.quadtree.bounds()
+    }
+
+    // The patches selected by the last update.
+    pub fn patches(&self) -> &[TerrainPatch] {
+        &self.patches
+    }
+
+    pub fn set_detail_distance(&mut self, detail_distance: f32) {
+        self.settings.detail_distance = detail_distance.max(1.0)
+    }
+
+    // The height of the surface at the world position, None outside of the terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let (u, v) = self.uv(x, z)?;
+
+        Some(self.settings.origin.y + self.heightmap.sample(u, v) * self.settings.size.y)
+    }
+
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vec3> {
+        let (u, v) = self.uv(x, z)?;
+
+        let step = Vec2::new(
+            self.settings.size.x / (self.heightmap.width() - 1) as f32,
+            self.settings.size.z / (self.heightmap.depth() - 1) as f32,
+        );
+        let du = 1.0 / (self.heightmap.width() - 1) as f32;
+        let dv = 1.0 / (self.heightmap.depth() - 1) as f32;
+
+        let dx = (self.heightmap.sample(u + du, v) - self.heightmap.sample(u - du, v))
+            * self.settings.size.y
+            / (2.0 * step.x);
+        let dz = (self.heightmap.sample(u, v + dv) - self.heightmap.sample(u, v - dv))
+            * self.settings.size.y
+            / (2.0 * step.y);
+
+        Some(Vec3::new(-dx, 1.0, -dz).normalize())
+    }
+
+    // Selects the patches for the camera and updates the material. Call it once per frame before
+    // draw.
+    pub fn update(&mut self, camera: &Camera) {
+        self.material.update();
+
+        let ranges = self.lod_ranges();
+
+        self.quadtree.select(
+            &SelectionView {
+                eye: camera.position(),
+                view_projection: &camera.view_projection_matrix(),
+                ranges: &ranges,
+            },
+            &mut self.patches,
+        );
+
+        let instances = self
+            .patches
+            .iter()
+            .map(|patch| {
+                let model = glm::translation(&Vec3::new(
+                    patch.offset.x,
+                    self.settings.origin.y,
+                    patch.offset.y,
+                )) * glm::scaling(&Vec3::new(patch.size.x, 1.0, patch.size.y));
+
+                InstanceData::new(model, patch.lod)
+            })
+            .collect::<Vec<_>>();
+
+        if instances.len() > self.instances.capacity() {
+            self.instances = InstanceBuffer::new(instances.len().next_power_of_two());
+            self.patch_mesh.set_instance_buffer(&self.instances);
+        }
+
+        self.instances.update(&instances);
+    }
+
+    // Draws the selected patches into the bound framebuffer, with the per frame data, the
+    // lights and the environment maps of the scene bound as for the other materials.
+    pub fn draw(&self) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        self.material.bind();
+
+        let program_pipeline = self.material.program_pipeline();
+        program_pipeline
+            .set_texture_2d(HEIGHT_MAP_BINDING_INDEX, &self.height_map, &self.sampler)
+            .set_texture_2d(NORMAL_MAP_BINDING_INDEX, &self.normal_map, &self.sampler)
+            .set_vec3_all_stages("terrainOrigin", &self.settings.origin)
+            .set_vec3_all_stages("terrainSize", &self.settings.size)
+            .set_float_all_stages("gridResolution", self.settings.patch_resolution as f32);
+
+        for (lod, range) in self.morph_ranges().iter().enumerate() {
+            program_pipeline.set_vec2_all_stages(&format!("morphRanges[{}]", lod), range);
+        }
+
+        self.patch_mesh.draw_instanced(self.instances.len() as u32);
+
+        self.material.unbind();
+    }
+
+    // The finest level first. The coarsest level covers the whole terrain.
+    fn lod_ranges(&self) -> Vec<f32> {
+        let mut ranges = (0..self.settings.lod_count)
+            .map(|lod| self.settings.detail_distance * 2.0f32.powi(lod as i32))
+            .collect::<Vec<_>>();
+
+        if let Some(last) = ranges.last_mut() {
+            *last = std::f32::MAX;
+        }
+
+        ranges
+    }
+
+    // The distances between which the vertices of each level morph, ending at its range.
+    fn morph_ranges(&self) -> Vec<Vec2> {
+        let ranges = self.lod_ranges();
+
+        ranges
+            .iter()
+            .enumerate()
+            .map(|(lod, &end)| {
+                let start = if lod == 0 { 0.0 } else { ranges[lod - 1] };
+                Vec2::new(start + (end - start) * self.settings.morph_start, end)
+            })
+            .collect()
+    }
+
+    fn uv(&self, x: f32, z: f32) -> Option<(f32, f32)> {
+        let u = (x - self.settings.origin.x) / self.settings.size.x;
+        let v = (z - self.settings.origin.z) / self.settings.size.z;
+
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some((u, v))
+        } else {
+            None
+        }
+    }
+
+    fn create_textures(heightmap: &Heightmap, size: &Vec3) -> (Texture2D, Texture2D) {
+        let (width, depth) = (heightmap.width(), heightmap.depth());
+
+        let height_map = Texture2D::new_empty(width, depth, SizedTextureFormat::R32f, 1);
+        height_map.set_label("Terrain Height Map");
+
+        let normals = heightmap
+            .generate_normals(size)
+            .iter()
+            .flat_map(|normal| vec![normal.x, normal.y, normal.z])
+            .collect::<Vec<_>>();
+
+        let normal_map = Texture2D::new_empty(width, depth, SizedTextureFormat::Rgb16f, 1);
+        normal_map.set_label("Terrain Normal Map");
+
+        unsafe {
+            gl::TextureSubImage2D(
+                height_map.get_id(),
+                0,
+                0,
+                0,
+                width as i32,
+                depth as i32,
+                gl::RED,
+                gl::FLOAT,
+                heightmap.heights().as_ptr() as *const GLvoid,
+            );
+            gl::TextureSubImage2D(
+                normal_map.get_id(),
+                0,
+                0,
+                0,
+                width as i32,
+                depth as i32,
+                gl::RGB,
+                gl::FLOAT,
+                normals.as_ptr() as *const GLvoid,
+            );
+        }
+
+        (height_map, normal_map)
+    }
+
+    // A flat grid from 0 to 1 along x and z. The patches place and scale it.
+    fn create_patch_mesh(resolution: u32) -> Mesh {
+        let mut vertices = vec![];
+
+        for z in 0..=resolution {
+            for x in 0..=resolution {
+                let uv = Vec2::new(x as f32, z as f32) / resolution as f32;
+
+                vertices.push(Vertex {
+                    position: Vec3::new(uv.x, 0.0, uv.y),
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                    tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+                    tex_coord: uv,
+                    color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+                    tex_coord1: uv,
+                });
+            }
+        }
+
+        let row = resolution + 1;
+        let mut indices = vec![];
+
+        for z in 0..resolution {
+            for x in 0..resolution {
+                let corner = z * row + x;
+
+                indices.extend_from_slice(&[
+                    corner,
+                    corner + row,
+                    corner + 1,
+                    corner + 1,
+                    corner + row,
+                    corner + row + 1,
+                ]);
+            }
+        }
+
+        Mesh::new(vertices, indices)
+    }
+}
+
+impl Gui for Terrain {
+    fn gui(&mut self, ui: &Ui) {
+        if imgui::CollapsingHeader::new(im_str!("Terrain"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            ui.spacing();
+            ui.text(format!("Patches: {}", self.patches.len()));
+
+            let mut detail_distance = self.settings.detail_distance;
+            if imgui::Slider::new(im_str!("Detail Distance"))
+                .range(RangeInclusive::new(4.0, 256.0))
+                .display_format(im_str!("%.0f"))
+                .build(&ui, &mut detail_distance)
+            {
+                self.set_detail_distance(detail_distance)
+            }
+
+            imgui::Slider::new(im_str!("Morph Start"))
+                .range(RangeInclusive::new(0.0, 0.95))
+                .display_format(im_str!("%.2f"))
+                .build(&ui, &mut self.settings.morph_start);
+
+            ui.spacing();
+            self.material.gui(ui);
+        }
+    }
+}
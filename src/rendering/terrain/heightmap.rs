@@ -0,0 +1,151 @@
+use crate::core::math::Vec3;
+use image::{DynamicImage, GenericImageView};
+use std::{fmt::Debug, fs, path::Path};
+
+// Heights from 0 to 1 on a regular grid of samples, row by row from the -Z edge of the terrain.
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    width: u32,
+    depth: u32,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: u32, depth: u32, heights: Vec<f32>) -> Result<Self, String> {
+        if width < 2 || depth < 2 {
+            return Err(format!(
+                "A heightmap needs at least 2x2 samples, got {}x{}",
+                width, depth
+            ));
+        }
+
+        if heights.len() != (width * depth) as usize {
+            return Err(format!(
+                "A {}x{} heightmap needs {} heights, got {}",
+                width,
+                depth,
+                width * depth,
+                heights.len()
+            ));
+        }
+
+        Ok(Self {
+            width,
+            depth,
+            heights,
+        })
+    }
+
+    // The luminance of the image, 8 bits per sample. Use load_raw16 for more precision.
+    pub fn from_image(image: &DynamicImage) -> Result<Self, String> {
+        let (width, depth) = image.dimensions();
+
+        let heights = image
+            .to_luma()
+            .into_raw()
+            .into_iter()
+            .map(|height| height as f32 / 255.0)
+            .collect();
+
+        Self::new(width, depth, heights)
+    }
+
+    pub fn load<P: AsRef<Path> + Debug>(path: P) -> Result<Self, String> {
+        let image = image::open(path.as_ref())
+            .map_err(|e| format!("Failed to load heightmap {:?}: {}", path, e))?;
+
+        Self::from_image(&image)
+    }
+
+    // Headerless 16 bit little endian samples, as terrain tools export them.
+    pub fn load_raw16<P: AsRef<Path> + Debug>(
+        path: P,
+        width: u32,
+        depth: u32,
+    ) -> Result<Self, String> {
+        let data = fs::read(path.as_ref())
+            .map_err(|e| format!("Failed to load heightmap {:?}: {}", path, e))?;
+
+        let heights = data
+            .chunks_exact(2)
+            .map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as f32 / 65535.0)
+            .collect();
+
+        Self::new(width, depth, heights)
+            .map_err(|e| format!("Failed to load heightmap {:?}: {}", path, e))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    // Samples outside of the grid take the height of the closest edge.
+    pub fn height(&self, x: i64, z: i64) -> f32 {
+        let x = x.max(0).min(self.width as i64 - 1) as usize;
+        let z = z.max(0).min(self.depth as i64 - 1) as usize;
+
+        self.heights[z * self.width as usize + x]
+    }
+
+    // Bilinear, u and v from 0 to 1 over the grid.
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        let x = u.max(0.0).min(1.0) * (self.width - 1) as f32;
+        let z = v.max(0.0).min(1.0) * (self.depth - 1) as f32;
+
+        let (x0, z0) = (x.floor() as i64, z.floor() as i64);
+        let (tx, tz) = (x.fract(), z.fract());
+
+        let top = self.height(x0, z0) * (1.0 - tx) + self.height(x0 + 1, z0) * tx;
+        let bottom = self.height(x0, z0 + 1) * (1.0 - tx) + self.height(x0 + 1, z0 + 1) * tx;
+
+        top * (1.0 - tz) + bottom * tz
+    }
+
+    // The lowest and highest sample of the grid rectangle, both corners included.
+    pub fn range(&self, x0: u32, z0: u32, x1: u32, z1: u32) -> (f32, f32) {
+        let mut range = (std::f32::MAX, std::f32::MIN);
+
+        for z in z0..=z1.min(self.depth - 1) {
+            for x in x0..=x1.min(self.width - 1) {
+                let height = self.heights[(z * self.width + x) as usize];
+                range = (range.0.min(height), range.1.max(height));
+            }
+        }
+
+        range
+    }
+
+    // A normal per sample of a terrain of the size (x and z extents, y for a height of 1), from
+    // the central differences of the neighbouring samples.
+    pub fn generate_normals(&self, size: &Vec3) -> Vec<Vec3> {
+        let spacing_x = size.x / (self.width - 1) as f32;
+        let spacing_z = size.z / (self.depth - 1) as f32;
+
+        let mut normals = Vec::with_capacity(self.heights.len());
+
+        for z in 0..self.depth as i64 {
+            for x in 0..self.width as i64 {
+                // One sided at the edges.
+                let (left, right) = ((x - 1).max(0), (x + 1).min(self.width as i64 - 1));
+                let (back, front) = ((z - 1).max(0), (z + 1).min(self.depth as i64 - 1));
+
+                let dx = (self.height(right, z) - self.height(left, z)) * size.y
+                    / ((right - left) as f32 * spacing_x);
+                let dz = (self.height(x, front) - self.height(x, back)) * size.y
+                    / ((front - back) as f32 * spacing_z);
+
+                normals.push(Vec3::new(-dx, 1.0, -dz).normalize());
+            }
+        }
+
+        normals
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    core::asset::{embedded::EmbeddedAssets, Handle},
+    core::math::Vec4,
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        error::RendererError,
+        material::{Material, MaterialUniforms},
+        program_pipeline::ProgramPipeline,
+        sampler::{Anisotropy, MagnificationFilter, MinificationFilter, Sampler, WrappingMode},
+        shader::ShaderStage,
+        texture::{Texture2D, Texture2DArray},
+    },
+};
+use image::{DynamicImage, RgbaImage};
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+// Every splat map weighs four layers, one per channel.
+pub const MAX_LAYERS: usize = 8;
+
+pub(crate) const MATERIAL_UBO_BINDING_INDEX: u32 = 4;
+const ALBEDO_MAPS_BINDING_INDEX: u32 = 0;
+const NORMAL_MAPS_BINDING_INDEX: u32 = 1;
+// [Metalness (R), Roughness (G), AO (B)]
+const M_R_AO_MAPS_BINDING_INDEX: u32 = 2;
+const BRDF_LUT_MAP_BINDING_INDEX: u32 = 3;
+const SPLAT_MAP_BINDING_INDICES: [u32; 2] = [12, 13];
+
+// One PBS material of the terrain, tiled over it. Missing maps fall back to a flat normal and a
+// rough dielectric without occlusion.
+#[derive(Debug, Clone)]
+pub struct TerrainLayer {
+    pub albedo: PathBuf,
+    pub normals: Option<PathBuf>,
+    pub metallic_roughness_ao: Option<PathBuf>,
+    // World units one repetition of the maps covers.
+    pub tile_size: f32,
+    // Projects the maps along the three axes instead of from above, for steep slopes and
+    // cliffs that would stretch them.
+    pub triplanar: bool,
+    pub normal_strength: f32,
+}
+
+impl TerrainLayer {
+    pub fn new<P: AsRef<Path>>(albedo: P) -> Self {
+        Self {
+            albedo: albedo.as_ref().to_path_buf(),
+            normals: None,
+            metallic_roughness_ao: None,
+            tile_size: 4.0,
+            triplanar: false,
+            normal_strength: 1.0,
+        }
+    }
+
+    pub fn normals<P: AsRef<Path>>(mut self, normals: P) -> Self {
+        self.normals = Some(normals.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn metallic_roughness_ao<P: AsRef<Path>>(mut self, metallic_roughness_ao: P) -> Self {
+        self.metallic_roughness_ao = Some(metallic_roughness_ao.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: f32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    pub fn triplanar(mut self, triplanar: bool) -> Self {
+        self.triplanar = triplanar;
+        self
+    }
+
+    pub fn normal_strength(mut self, normal_strength: f32) -> Self {
+        self.normal_strength = normal_strength;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct TerrainPropertyBlock {
+    // x: tile size, y: 1 for triplanar projection, z: normal strength.
+    layers: [Vec4; MAX_LAYERS],
+    layer_count: i32,
+    triplanar_sharpness: f32,
+    _pad: [f32; 2],
+}
+
+// Blends up to MAX_LAYERS PBS materials by the weights of the splat maps, which span the whole
+// terrain. The first splat map weighs layers 0 to 3 in its RGBA channels, the second layers 4
+// to 7. The weights are normalized, where they are all 0 the first layer shows. The maps of the
+// layers are resized to the size of the maps of the first layer. Light probes are not blended
+// on terrain, it takes the environment maps.
+pub struct TerrainMaterial {
+    layers: Vec<TerrainLayer>,
+    albedo_maps: Texture2DArray,
+    normal_maps: Texture2DArray,
+    metallic_roughness_ao_maps: Texture2DArray,
+    splat_maps: [Handle<Texture2D>; 2],
+    ibl_brdf_lut: Handle<Texture2D>,
+    sampler: Sampler,
+    splat_sampler: Sampler,
+    property_block: TerrainPropertyBlock,
+    program_pipeline: ProgramPipeline,
+    uniforms: MaterialUniforms,
+}
+
+impl TerrainMaterial {
+    // Splat maps have to be linear, one for up to four layers.
+    pub fn new<P: AsRef<Path>>(
+        asset_path: P,
+        layers: Vec<TerrainLayer>,
+        splat_maps: Vec<Handle<Texture2D>>,
+    ) -> Result<Self, RendererError> {
+        if layers.is_empty() || layers.len() > MAX_LAYERS {
+            return Err(RendererError::TerrainLayerCount {
+                count: layers.len(),
+                max: MAX_LAYERS,
+            });
+        }
+
+        let load = |path: &Path| {
+            image::open(path).map_err(|e| RendererError::Texture {
+                name: format!("{:?}", path),
+                log: e.to_string(),
+            })
+        };
+
+        let color = |color: [u8; 4]| {
+            DynamicImage::ImageRgba8(RgbaImage::from_raw(1, 1, color.to_vec()).unwrap())
+        };
+
+        let mut albedo_images = vec![];
+        let mut normal_images = vec![];
+        let mut metallic_roughness_ao_images = vec![];
+
+        for layer in &layers {
+            albedo_images.push(load(&layer.albedo)?);
+            normal_images.push(match &layer.normals {
+                Some(normals) => load(normals)?,
+                None => color([128, 128, 255, 255]),
+            });
+            metallic_roughness_ao_images.push(match &layer.metallic_roughness_ao {
+                Some(metallic_roughness_ao) => load(metallic_roughness_ao)?,
+                None => color([0, 255, 255, 255]),
+            });
+        }
+
+        let mut splat_maps = splat_maps.into_iter();
+        let mut splat_map = |weights: [u8; 4]| match splat_maps.next() {
+            Some(splat_map) => Ok(splat_map),
+            None => Texture2D::new_from_image(color(weights), false, false)
+                .map(Handle::new)
+                .map_err(|log| RendererError::Texture {
+                    name: String::from("default splat map"),
+                    log,
+                }),
+        };
+
+        let program_pipeline = ProgramPipeline::new()
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Vertex,
+                asset_path.as_ref().join("sdr/terrain.vert"),
+            )?)
+            .add_shader(&EmbeddedAssets::load_shader(
+                ShaderStage::Fragment,
+                asset_path.as_ref().join("sdr/terrain.frag"),
+            )?)
+            .build()?;
+
+        let texture_array = |name: &str, images: &[DynamicImage], is_srgb: bool| {
+            Texture2DArray::new_from_images(images, is_srgb).map_err(|log| RendererError::Texture {
+                name: name.to_string(),
+                log,
+            })
+        };
+
+        let mut material = Self {
+            albedo_maps: texture_array("terrain albedo maps", &albedo_images, true)?,
+            normal_maps: texture_array("terrain normal maps", &normal_images, false)?,
+            metallic_roughness_ao_maps: texture_array(
+                "terrain metallic roughness AO maps",
+                &metallic_roughness_ao_images,
+                false,
+            )?,
+            splat_maps: [splat_map([255, 0, 0, 0])?, splat_map([0, 0, 0, 0])?],
+            layers,
+            ibl_brdf_lut: EmbeddedAssets::brdf_lut()?,
+            sampler: Sampler::new(
+                MinificationFilter::LinearMipmapLinear,
+                MagnificationFilter::Linear,
+                WrappingMode::Repeat,
+                WrappingMode::Repeat,
+                WrappingMode::Repeat,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::X8,
+            ),
+            splat_sampler: Sampler::new(
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                WrappingMode::ClampToEdge,
+                Vec4::new(0.0, 0.0, 0.0, 0.0),
+                Anisotropy::None,
+            ),
+            property_block: TerrainPropertyBlock {
+                layers: [Vec4::new(0.0, 0.0, 0.0, 0.0); MAX_LAYERS],
+                layer_count: 0,
+                triplanar_sharpness: 4.0,
+                _pad: [0.0; 2],
+            },
+            program_pipeline,
+            uniforms: MaterialUniforms::new::<TerrainPropertyBlock>("TerrainPropertyBlock UBO"),
+        };
+
+        material.update_property_block();
+
+        Ok(material)
+    }
+
+    pub fn layers(&self) -> &[TerrainLayer] {
+        &self.layers
+    }
+
+    pub fn set_tile_size(&mut self, layer: usize, tile_size: f32) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.tile_size = tile_size.max(0.01);
+        }
+        self.update_property_block()
+    }
+
+    pub fn set_triplanar(&mut self, layer: usize, triplanar: bool) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.triplanar = triplanar;
+        }
+        self.update_property_block()
+    }
+
+    // Exponent of the blend weights of the three projections, higher blends over shorter
+    // distances.
+    pub fn set_triplanar_sharpness(&mut self, sharpness: f32) {
+        self.property_block.triplanar_sharpness = sharpness.max(1.0)
+    }
+
+    pub fn set_splat_map(&mut self, index: usize, splat_map: Handle<Texture2D>) {
+        if let Some(slot) = self.splat_maps.get_mut(index) {
+            *slot = splat_map
+        }
+    }
+
+    fn update_property_block(&mut self) {
+        for (properties, layer) in self.property_block.layers.iter_mut().zip(&self.layers) {
+            *properties = Vec4::new(
+                layer.tile_size,
+                layer.triplanar as i32 as f32,
+                layer.normal_strength,
+                0.0,
+            );
+        }
+
+        self.property_block.layer_count = self.layers.len() as i32;
+    }
+}
+
+impl Material for TerrainMaterial {
+    fn bind(&self) {
+        self.program_pipeline.bind();
+
+        self.uniforms
+            .bind(MATERIAL_UBO_BINDING_INDEX, &self.property_block);
+
+        self.program_pipeline
+            .set_texture_2d_array(ALBEDO_MAPS_BINDING_INDEX, &self.albedo_maps, &self.sampler)
+            .set_texture_2d_array(NORMAL_MAPS_BINDING_INDEX, &self.normal_maps, &self.sampler)
+            .set_texture_2d_array(
+                M_R_AO_MAPS_BINDING_INDEX,
+                &self.metallic_roughness_ao_maps,
+                &self.sampler,
+            )
+            .set_texture_2d(
+                BRDF_LUT_MAP_BINDING_INDEX,
+                &self.ibl_brdf_lut,
+                &self.splat_sampler,
+            );
+
+        for (binding, splat_map) in SPLAT_MAP_BINDING_INDICES.iter().zip(&self.splat_maps) {
+            self.program_pipeline
+                .set_texture_2d(*binding, splat_map, &self.splat_sampler);
+        }
+    }
+
+    fn unbind(&self) {
+        self.program_pipeline.unbind();
+    }
+
+    fn program_pipeline(&self) -> &ProgramPipeline {
+        &self.program_pipeline
+    }
+
+    fn update(&mut self) {
+        self.uniforms.next_frame();
+    }
+}
+
+impl Gui for TerrainMaterial {
+    fn gui(&mut self, ui: &Ui) {
+        if imgui::CollapsingHeader::new(im_str!("Terrain Material"))
+            .default_open(true)
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .build(ui)
+        {
+            ui.spacing();
+
+            let mut changed = false;
+
+            for (i, layer) in self.layers.iter_mut().enumerate() {
+                let id = ui.push_id(i as i32);
+
+                ui.text(format!("Layer {}", i));
+                changed |= imgui::Slider::new(im_str!("Tile Size"))
+                    .range(RangeInclusive::new(0.1, 64.0))
+                    .display_format(im_str!("%.1f"))
+                    .build(&ui, &mut layer.tile_size);
+                changed |= imgui::Slider::new(im_str!("Normal Strength"))
+                    .range(RangeInclusive::new(0.0, 2.0))
+                    .display_format(im_str!("%.2f"))
+                    .build(&ui, &mut layer.normal_strength);
+                changed |= ui.checkbox(im_str!("Triplanar"), &mut layer.triplanar);
+                ui.spacing();
+
+                id.pop(ui);
+            }
+
+            imgui::Slider::new(im_str!("Triplanar Sharpness"))
+                .range(RangeInclusive::new(1.0, 16.0))
+                .display_format(im_str!("%.1f"))
+                .build(&ui, &mut self.property_block.triplanar_sharpness);
+
+            if changed {
+                self.update_property_block()
+            }
+        }
+    }
+}
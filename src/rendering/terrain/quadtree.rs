@@ -0,0 +1,235 @@
+use crate::{
+    core::math::{Mat4, Vec2, Vec3, Vec4},
+    geometry::bounds::Aabb,
+    rendering::terrain::heightmap::Heightmap,
+};
+
+// A rectangle of the terrain drawn with the patch grid at a level of detail, 0 the finest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainPatch {
+    // The corner of the patch at the lowest x and z, in world space.
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub lod: u32,
+}
+
+// A square area of the grid, from 0 to 1 over the terrain, and the range of its heights.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    x: f32,
+    z: f32,
+    size: f32,
+    min_height: f32,
+    max_height: f32,
+    // The four children are consecutive, in the order -x -z, +x -z, -x +z, +x +z.
+    first_child: Option<usize>,
+}
+
+// What the patches are selected for.
+pub struct SelectionView<'a> {
+    pub eye: &'a Vec3,
+    pub view_projection: &'a Mat4,
+    // Up to which distance each level of detail is used, finest first.
+    pub ranges: &'a [f32],
+}
+
+// The quadtree of a continuous distance-dependent level of detail (CDLOD) terrain. Every level
+// of the tree halves the size of its nodes, the leaves are drawn at the finest level. Nodes are
+// bounded by the heights below them for culling and the distance to the eye.
+// Reference: https://github.com/fstrugar/CDLOD/blob/master/cdlod_paper_latest.pdf
+pub struct Quadtree {
+    nodes: Vec<Node>,
+    lod_count: u32,
+    origin: Vec3,
+    size: Vec3,
+}
+
+impl Quadtree {
+    // origin is the corner of the terrain at the lowest x and z, size its extents with y the
+    // height of a sample of 1.
+    pub fn new(heightmap: &Heightmap, origin: Vec3, size: Vec3, lod_count: u32) -> Self {
+        let mut quadtree = Self {
+            nodes: vec![Node {
+                x: 0.0,
+                z: 0.0,
+                size: 1.0,
+                min_height: 0.0,
+                max_height: 0.0,
+                first_child: None,
+            }],
+            lod_count: lod_count.max(1),
+            origin,
+            size,
+        };
+
+        quadtree.build(heightmap, 0, quadtree.lod_count - 1);
+
+        quadtree
+    }
+
+    pub fn lod_count(&self) -> u32 {
+        self.lod_count
+    }
+
+    // Bounds of the whole terrain.
+    pub fn bounds(&self) -> Aabb {
+        self.node_bounds(&self.nodes[0])
+    }
+
+    // The patches to draw for the view. Nodes are drawn at the finest level whose range they
+    // are in, nodes outside of the view frustum are skipped. The coarsest level covers the
+    // rest of the terrain whatever its range.
+    pub fn select(&self, view: &SelectionView, patches: &mut Vec<TerrainPatch>) {
+        patches.clear();
+
+        let root = &self.nodes[0];
+        let lod = self.lod_count - 1;
+
+        if !self.select_node(0, lod, view, patches)
+            && is_in_frustum(&self.node_bounds(root), view.view_projection)
+        {
+            patches.push(self.patch(root, lod));
+        }
+    }
+
+    fn build(&mut self, heightmap: &Heightmap, index: usize, levels: u32) {
+        let node = self.nodes[index];
+
+        let (min_height, max_height) = if levels == 0 {
+            let (width, depth) = (
+                (heightmap.width() - 1) as f32,
+                (heightmap.depth() - 1) as f32,
+            );
+
+            heightmap.range(
+                (node.x * width).floor() as u32,
+                (node.z * depth).floor() as u32,
+                ((node.x + node.size) * width).ceil() as u32,
+                ((node.z + node.size) * depth).ceil() as u32,
+            )
+        } else {
+            let first_child = self.nodes.len();
+            let half = node.size * 0.5;
+
+            for i in 0..4 {
+                self.nodes.push(Node {
+                    x: node.x + (i % 2) as f32 * half,
+                    z: node.z + (i / 2) as f32 * half,
+                    size: half,
+                    min_height: 0.0,
+                    max_height: 0.0,
+                    first_child: None,
+                });
+            }
+
+            self.nodes[index].first_child = Some(first_child);
+
+            for child in first_child..first_child + 4 {
+                self.build(heightmap, child, levels - 1);
+            }
+
+            self.nodes[first_child..first_child + 4]
+                .iter()
+                .fold((std::f32::MAX, std::f32::MIN), |range, child| {
+                    (range.0.min(child.min_height), range.1.max(child.max_height))
+                })
+        };
+
+        self.nodes[index].min_height = min_height;
+        self.nodes[index].max_height = max_height;
+    }
+
+    // False if the node is out of the range of the level, so its parent has to cover it.
+    fn select_node(
+        &self,
+        index: usize,
+        lod: u32,
+        view: &SelectionView,
+        patches: &mut Vec<TerrainPatch>,
+    ) -> bool {
+        let node = &self.nodes[index];
+        let bounds = self.node_bounds(node);
+
+        if !intersects_sphere(&bounds, view.eye, view.ranges[lod as usize]) {
+            return false;
+        }
+
+        if !is_in_frustum(&bounds, view.view_projection) {
+            return true;
+        }
+
+        let first_child = match node.first_child {
+            Some(first_child)
+                if lod > 0
+                    && intersects_sphere(&bounds, view.eye, view.ranges[lod as usize - 1]) =>
+            {
+                first_child
+            }
+            _ => {
+                patches.push(self.patch(node, lod));
+                return true;
+            }
+        };
+
+        for child in first_child..first_child + 4 {
+            if !self.select_node(child, lod - 1, view, patches) {
+                let child = &self.nodes[child];
+
+                if is_in_frustum(&self.node_bounds(child), view.view_projection) {
+                    patches.push(self.patch(child, lod));
+                }
+            }
+        }
+
+        true
+    }
+
+    fn node_bounds(&self, node: &Node) -> Aabb {
+        Aabb::new(
+            Vec3::new(
+                self.origin.x + node.x * self.size.x,
+                self.origin.y + node.min_height * self.size.y,
+                self.origin.z + node.z * self.size.z,
+            ),
+            Vec3::new(
+                self.origin.x + (node.x + node.size) * self.size.x,
+                self.origin.y + node.max_height * self.size.y,
+                self.origin.z + (node.z + node.size) * self.size.z,
+            ),
+        )
+    }
+
+    fn patch(&self, node: &Node, lod: u32) -> TerrainPatch {
+        TerrainPatch {
+            offset: Vec2::new(
+                self.origin.x + node.x * self.size.x,
+                self.origin.z + node.z * self.size.z,
+            ),
+            size: Vec2::new(node.size * self.size.x, node.size * self.size.z),
+            lod,
+        }
+    }
+}
+
+fn intersects_sphere(bounds: &Aabb, center: &Vec3, radius: f32) -> bool {
+    let closest = center.sup(&bounds.min).inf(&bounds.max);
+
+    (closest - center).norm_squared() <= radius * radius
+}
+
+// False if all corners of the bounds are outside of the same clip plane.
+fn is_in_frustum(bounds: &Aabb, view_projection: &Mat4) -> bool {
+    let corners = bounds
+        .corners()
+        .iter()
+        .map(|corner| view_projection * Vec4::new(corner.x, corner.y, corner.z, 1.0))
+        .collect::<Vec<_>>();
+
+    for axis in 0..3 {
+        if corners.iter().all(|c| c[axis] < -c.w) || corners.iter().all(|c| c[axis] > c.w) {
+            return false;
+        }
+    }
+
+    true
+}
@@ -1,19 +1,23 @@
 use image;
-use image::{ColorType, DynamicImage, GenericImageView};
+use image::{hdr::HDRDecoder, ColorType, DynamicImage, FilterType, GenericImageView, Pixel};
 
 use gli::GliTexture;
 use gli_rs as gli;
 
-use crate::core::asset::Asset;
+use crate::core::asset::{meta::AssetMeta, Asset};
+use crate::rendering::object_label::set_object_label;
+use crate::rendering::state::StateManager;
 use gl::types::*;
 use gl_bindings as gl;
-use std::path::Path;
+use std::{fs::File, io::BufReader, path::Path, str::FromStr};
 
 #[repr(u32)]
 #[derive(Debug, Clone, Copy)]
 pub enum SizedTextureFormat {
     R8 = gl::R8,
     R16 = gl::R16,
+    R32f = gl::R32F,
+    R32ui = gl::R32UI,
     Rg8 = gl::RG8,
     Rgb8 = gl::RGB8,
     Srgb8 = gl::SRGB8,
@@ -24,6 +28,10 @@ pub enum SizedTextureFormat {
     Rgba16f = gl::RGBA16F,
     Rgb32f = gl::RGB32F,
     Rgba32f = gl::RGBA32F,
+    CompressedRedRgtc1 = gl::COMPRESSED_RED_RGTC1,
+    CompressedRgRgtc2 = gl::COMPRESSED_RG_RGTC2,
+    CompressedRgbaBptc = gl::COMPRESSED_RGBA_BPTC_UNORM,
+    CompressedSrgbAlphaBptc = gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
     Depth16 = gl::DEPTH_COMPONENT16,
     Depth24 = gl::DEPTH_COMPONENT24,
     Depth32 = gl::DEPTH_COMPONENT32,
@@ -76,6 +84,34 @@ impl Utils {
             _ => Err(String::from("Unsupported texture format.")),
         }
     }
+
+    fn compressed_format(
+        format: SizedTextureFormat,
+        compression: TextureCompression,
+        is_srgb: bool,
+    ) -> Result<SizedTextureFormat, String> {
+        match (compression, format) {
+            (TextureCompression::Bptc, SizedTextureFormat::Rgb8)
+            | (TextureCompression::Bptc, SizedTextureFormat::Srgb8)
+            | (TextureCompression::Bptc, SizedTextureFormat::Rgba8)
+            | (TextureCompression::Bptc, SizedTextureFormat::Srgb8A8) => Ok(if is_srgb {
+                SizedTextureFormat::CompressedSrgbAlphaBptc
+            } else {
+                SizedTextureFormat::CompressedRgbaBptc
+            }),
+            (TextureCompression::Rgtc, SizedTextureFormat::R8) => {
+                Ok(SizedTextureFormat::CompressedRedRgtc1)
+            }
+            (TextureCompression::Rgtc, SizedTextureFormat::Rg8) => {
+                Ok(SizedTextureFormat::CompressedRgRgtc2)
+            }
+            (TextureCompression::None, format) => Ok(format),
+            (compression, format) => Err(format!(
+                "{:?} compression doesn't support {:?} images.",
+                compression, format
+            )),
+        }
+    }
 }
 
 pub struct Texture2D {
@@ -83,9 +119,68 @@ pub struct Texture2D {
     image: DynamicImage,
 }
 
+// GPU block compression. The driver compresses the image on upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureCompression {
+    None,
+    // BC7. RGB(A) images.
+    Bptc,
+    // BC4/BC5. One and two channel images.
+    Rgtc,
+}
+
+impl FromStr for TextureCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(TextureCompression::None),
+            "bptc" | "bc7" => Ok(TextureCompression::Bptc),
+            "rgtc" | "bc4" | "bc5" => Ok(TextureCompression::Rgtc),
+            _ => Err(format!("Unknown texture compression '{}'.", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Texture2DLoadConfig {
     pub is_srgb: bool,
     pub generate_mipmap: bool,
+    pub compression: TextureCompression,
+}
+
+impl Default for Texture2DLoadConfig {
+    fn default() -> Self {
+        Self {
+            is_srgb: false,
+            generate_mipmap: false,
+            compression: TextureCompression::None,
+        }
+    }
+}
+
+impl Texture2DLoadConfig {
+    // Overrides the defaults with the texture's .meta sidecar, if it has one.
+    pub fn from_sidecar<P: AsRef<Path>>(path: P, defaults: Self) -> Result<Self, String> {
+        let meta = match AssetMeta::load_for(path)? {
+            Some(meta) => meta,
+            None => return Ok(defaults),
+        };
+
+        meta.check_keys(&["srgb", "mipmaps", "compression"]);
+
+        Ok(Self {
+            is_srgb: meta.get_bool("srgb")?.unwrap_or(defaults.is_srgb),
+            generate_mipmap: meta
+                .get_bool("mipmaps")?
+                .unwrap_or(defaults.generate_mipmap),
+            compression: meta
+                .get_str("compression")
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or(defaults.compression),
+        })
+    }
 }
 
 impl Asset for Texture2D {
@@ -93,22 +188,31 @@ impl Asset for Texture2D {
     type Error = String;
     type LoadConfig = Texture2DLoadConfig;
 
+    // Without a load config the settings come from the texture's .meta sidecar.
     fn load<P: AsRef<Path>>(
         path: P,
         load_config: Option<Self::LoadConfig>,
     ) -> Result<Self::Output, Self::Error> {
-        let mut is_srgb = false;
-        let mut generate_mipmap = false;
+        let config = match load_config {
+            Some(config) => config,
+            None => Texture2DLoadConfig::from_sidecar(path.as_ref(), Default::default())?,
+        };
 
-        if let Some(config) = load_config {
-            is_srgb = config.is_srgb;
-            generate_mipmap = config.generate_mipmap;
-        }
+        let image = Utils::open_image_file(path.as_ref())?;
 
-        match Utils::open_image_file(path.as_ref()) {
-            Ok(img) => Ok(Self::new_from_image(img, generate_mipmap, is_srgb)?),
-            Err(e) => Err(e.to_string()),
-        }
+        let texture = Self::new_from_image_with_config(image, config)?;
+
+        texture.set_label(&path.as_ref().to_string_lossy());
+
+        log::debug!(
+            "Loaded texture {:?} ({}x{}, {:?}).",
+            path.as_ref(),
+            texture.image.width(),
+            texture.image.height(),
+            config
+        );
+
+        Ok(texture)
     }
 }
 
@@ -117,17 +221,33 @@ impl Texture2D {
         image: DynamicImage,
         generate_mipmap: bool,
         is_srgb: bool,
+    ) -> Result<Self, String> {
+        Self::new_from_image_with_config(
+            image,
+            Texture2DLoadConfig {
+                is_srgb,
+                generate_mipmap,
+                compression: TextureCompression::None,
+            },
+        )
+    }
+
+    pub fn new_from_image_with_config(
+        image: DynamicImage,
+        config: Texture2DLoadConfig,
     ) -> Result<Self, String> {
         let (width, height) = image.dimensions();
 
-        let formats;
-        match Utils::color_type_to_texture_formats(image.color(), is_srgb) {
-            Ok(res) => formats = res,
-            Err(e) => return Err(e),
+        let (mut internal_format, format) =
+            Utils::color_type_to_texture_formats(image.color(), config.is_srgb)?;
+
+        if config.compression != TextureCompression::None {
+            internal_format =
+                Utils::compressed_format(internal_format, config.compression, config.is_srgb)?;
         }
 
         let mut mip_levels = 1;
-        if generate_mipmap {
+        if config.generate_mipmap {
             mip_levels =
                 (f32::floor(f32::log2(f32::max(width as f32, height as f32))) + 1.0) as i32;
         }
@@ -139,7 +259,7 @@ impl Texture2D {
             gl::TextureStorage2D(
                 id,
                 mip_levels,
-                formats.0 as u32,
+                internal_format as u32,
                 width as i32,
                 height as i32,
             );
@@ -151,23 +271,112 @@ impl Texture2D {
                 0,
                 width as i32,
                 height as i32,
-                formats.1 as u32,
+                format as u32,
                 gl::UNSIGNED_BYTE,
                 image.raw_pixels().as_ptr() as *const GLvoid,
             );
+        }
 
-            if generate_mipmap {
-                gl::GenerateTextureMipmap(id)
+        if config.generate_mipmap {
+            if config.compression == TextureCompression::None {
+                unsafe { gl::GenerateTextureMipmap(id) }
+            } else {
+                // Compressed formats aren't renderable, so the GL can't generate their mips.
+                for level in 1..mip_levels {
+                    let level_width = (width >> level).max(1);
+                    let level_height = (height >> level).max(1);
+                    let level_image =
+                        image.resize_exact(level_width, level_height, FilterType::Triangle);
+
+                    unsafe {
+                        gl::TextureSubImage2D(
+                            id,
+                            level,
+                            0,
+                            0,
+                            level_width as i32,
+                            level_height as i32,
+                            format as u32,
+                            gl::UNSIGNED_BYTE,
+                            level_image.raw_pixels().as_ptr() as *const GLvoid,
+                        );
+                    }
+                }
             }
         }
 
         Ok(Self { id, image })
     }
 
+    // 1x1 linear RGB texture. Stands in for missing material maps.
+    pub fn new_from_color(color: [u8; 3]) -> Self {
+        let image =
+            DynamicImage::ImageRgb8(image::RgbImage::from_raw(1, 1, color.to_vec()).unwrap());
+
+        Self::new_from_image(image, false, false).unwrap()
+    }
+
+    // Floating point RGB texture of a Radiance HDR file, e.g. an equirectangular environment.
+    // Without mips, since the filtering across the seam of a panorama breaks them. Its image is
+    // empty.
+    pub fn new_from_hdr<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path.as_ref()).map_err(|e| e.to_string())?;
+        let decoder = HDRDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()
+            .map_err(|e| e.to_string())?
+            .iter()
+            .flat_map(|pixel| pixel.channels().to_vec())
+            .collect::<Vec<f32>>();
+
+        let texture = Self::new_empty(
+            metadata.width,
+            metadata.height,
+            SizedTextureFormat::Rgb32f,
+            1,
+        );
+        unsafe {
+            gl::TextureSubImage2D(
+                texture.id,
+                0,
+                0,
+                0,
+                metadata.width as i32,
+                metadata.height as i32,
+                TextureFormat::Rgb as u32,
+                gl::FLOAT,
+                pixels.as_ptr() as *const GLvoid,
+            );
+        }
+
+        Ok(texture)
+    }
+
+    // Uninitialized texture to be filled on the GPU, e.g. by a compute shader. Its image is
+    // empty.
+    pub fn new_empty(width: u32, height: u32, format: SizedTextureFormat, mip_levels: i32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut id);
+            gl::TextureStorage2D(id, mip_levels, format as u32, width as i32, height as i32);
+        }
+
+        Self {
+            id,
+            image: DynamicImage::new_rgb8(0, 0),
+        }
+    }
+
     pub fn get_id(&self) -> GLuint {
         self.id
     }
 
+    // Names the texture for debuggers and the messages of the GL debug output.
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::TEXTURE, self.id, label)
+    }
+
     pub fn get_image(&self) -> &DynamicImage {
         &self.image
     }
@@ -175,6 +384,7 @@ impl Texture2D {
 
 impl Drop for Texture2D {
     fn drop(&mut self) {
+        StateManager::texture_deleted(self.id);
         unsafe { gl::DeleteTextures(1, &self.id) }
     }
 }
@@ -195,21 +405,18 @@ impl Asset for TextureCube {
         let result: gli::Result<gli::TextureCube> = gli::load(path.as_ref());
         match result {
             Ok(tex) => {
-                println!("Cube load ok!");
-                println!("KTX Texture info:");
-                println!(
-                    "\tExtent: ({}, {})",
+                log::debug!(
+                    "Loaded cube map {:?} ({}x{}, {} faces, {} layers, {} levels, {} bytes, \
+                     format {}).",
+                    path.as_ref(),
                     tex.extent(0).width,
-                    tex.extent(0).height
+                    tex.extent(0).height,
+                    tex.faces(),
+                    tex.layers(),
+                    tex.levels(),
+                    tex.size(),
+                    tex.format()
                 );
-                println!("\tFaces  count: {}", tex.faces());
-                println!("\tLayers count: {}", tex.layers());
-                println!("\tLevels count: {}", tex.levels());
-                println!("\tSize: {}", tex.size());
-                println!("\tAddress: {:?}", tex.data());
-                println!("\tFormat: {}", tex.format());
-                println!("\tTarget: {}", tex.target());
-                println!();
 
                 let (internal_format, external_format, data_type) =
                     Self::translate_gli_format_info(tex.format());
@@ -253,9 +460,15 @@ impl Asset for TextureCube {
                     }
                 }
 
+                set_object_label(gl::TEXTURE, id, &path.as_ref().to_string_lossy());
+
                 Ok(TextureCube { id })
             }
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(format!(
+                "Failed to load cube map {:?}: {}",
+                path.as_ref(),
+                e
+            )),
         }
     }
 }
@@ -266,21 +479,18 @@ impl TextureCube {
         let result: gli::Result<gli::TextureCube> = gli::load(path.as_ref());
         match result {
             Ok(tex) => {
-                println!("Cube load ok!");
-                println!("KTX Texture info:");
-                println!(
-                    "\tExtent: ({}, {})",
+                log::debug!(
+                    "Loaded cube map {:?} ({}x{}, {} faces, {} layers, {} levels, {} bytes, \
+                     format {}).",
+                    path.as_ref(),
                     tex.extent(0).width,
-                    tex.extent(0).height
+                    tex.extent(0).height,
+                    tex.faces(),
+                    tex.layers(),
+                    tex.levels(),
+                    tex.size(),
+                    tex.format()
                 );
-                println!("\tFaces  count: {}", tex.faces());
-                println!("\tLayers count: {}", tex.layers());
-                println!("\tLevels count: {}", tex.levels());
-                println!("\tSize: {}", tex.size());
-                println!("\tAddress: {:?}", tex.data());
-                println!("\tFormat: {}", tex.format());
-                println!("\tTarget: {}", tex.target());
-                println!();
 
                 let (internal_format, external_format, data_type) =
                     Self::translate_gli_format_info(tex.format());
@@ -322,16 +532,82 @@ impl TextureCube {
                     }
                 }
 
+                set_object_label(gl::TEXTURE, id, &path.as_ref().to_string_lossy());
+
                 Ok(TextureCube { id })
             }
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(format!(
+                "Failed to load cube map {:?}: {}",
+                path.as_ref(),
+                e
+            )),
+        }
+    }
+
+    // An uninitialized cube map, e.g. to render an environment into.
+    pub fn new_empty(size: u32, format: SizedTextureFormat, mip_levels: i32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_CUBE_MAP, 1, &mut id);
+            gl::TextureStorage2D(id, mip_levels, format as u32, size as i32, size as i32);
         }
+
+        Self { id }
     }
 
     pub fn get_id(&self) -> GLuint {
         self.id
     }
 
+    // A copy of a face at the largest mip level that fits the size, e.g. to preview the cube map
+    // in the UI. Faces are ordered +X, -X, +Y, -Y, +Z, -Z.
+    pub fn face_thumbnail(&self, face: u32, max_size: u32) -> Texture2D {
+        let (mut levels, mut format, mut size) = (0, 0, 0);
+        unsafe {
+            gl::GetTextureParameteriv(self.id, gl::TEXTURE_IMMUTABLE_LEVELS, &mut levels);
+            gl::GetTextureLevelParameteriv(self.id, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+            gl::GetTextureLevelParameteriv(self.id, 0, gl::TEXTURE_WIDTH, &mut size);
+        }
+
+        let level = (0..levels.max(1))
+            .find(|&level| (size >> level).max(1) as u32 <= max_size)
+            .unwrap_or(levels.max(1) - 1);
+        let level_size = (size >> level).max(1);
+
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut id);
+            gl::TextureStorage2D(id, 1, format as u32, level_size, level_size);
+            gl::CopyImageSubData(
+                self.id,
+                gl::TEXTURE_CUBE_MAP,
+                level,
+                0,
+                0,
+                face as i32,
+                id,
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                level_size,
+                level_size,
+                1,
+            );
+        }
+
+        Texture2D {
+            id,
+            image: DynamicImage::new_rgb8(0, 0),
+        }
+    }
+
+    // Names the texture for debuggers and the messages of the GL debug output.
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::TEXTURE, self.id, label)
+    }
+
     fn translate_gli_format_info(
         format: gli::Format,
     ) -> (SizedTextureFormat, TextureFormat, GLenum) {
@@ -368,6 +644,136 @@ impl TextureCube {
 
 impl Drop for TextureCube {
     fn drop(&mut self) {
+        StateManager::texture_deleted(self.id);
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+// Cube maps of the same size and format in one texture, indexed by layer in the shaders.
+pub struct TextureCubeArray {
+    id: GLuint,
+    layers: u32,
+}
+
+impl TextureCubeArray {
+    // An uninitialized array of layers cube maps. Face f of layer l is layer-face l * 6 + f.
+    pub fn new_empty(size: u32, layers: u32, format: SizedTextureFormat, mip_levels: i32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_CUBE_MAP_ARRAY, 1, &mut id);
+            gl::TextureStorage3D(
+                id,
+                mip_levels,
+                format as u32,
+                size as i32,
+                size as i32,
+                (layers * 6) as i32,
+            );
+        }
+
+        Self { id, layers }
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+}
+
+impl Drop for TextureCubeArray {
+    fn drop(&mut self) {
+        StateManager::texture_deleted(self.id);
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+// 2D textures of the same size in one texture, indexed by layer in the shaders, e.g. the
+// material layers of a terrain.
+pub struct Texture2DArray {
+    id: GLuint,
+    layers: u32,
+}
+
+impl Texture2DArray {
+    // RGBA8 with mipmaps. The images are resized to the size of the first one.
+    pub fn new_from_images(images: &[DynamicImage], is_srgb: bool) -> Result<Self, String> {
+        let (width, height) = images
+            .first()
+            .ok_or_else(|| String::from("A texture array needs at least one image."))?
+            .dimensions();
+
+        let format = if is_srgb {
+            SizedTextureFormat::Srgb8A8
+        } else {
+            SizedTextureFormat::Rgba8
+        };
+
+        let mip_levels =
+            (f32::floor(f32::log2(f32::max(width as f32, height as f32))) + 1.0) as i32;
+
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D_ARRAY, 1, &mut id);
+            gl::TextureStorage3D(
+                id,
+                mip_levels,
+                format as u32,
+                width as i32,
+                height as i32,
+                images.len() as i32,
+            );
+        }
+
+        for (layer, image) in images.iter().enumerate() {
+            let pixels = if image.dimensions() == (width, height) {
+                image.to_rgba().into_raw()
+            } else {
+                image
+                    .resize_exact(width, height, FilterType::Triangle)
+                    .to_rgba()
+                    .into_raw()
+            };
+
+            unsafe {
+                gl::TextureSubImage3D(
+                    id,
+                    0,
+                    0,
+                    0,
+                    layer as i32,
+                    width as i32,
+                    height as i32,
+                    1,
+                    TextureFormat::Rgba as u32,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_ptr() as *const GLvoid,
+                );
+            }
+        }
+
+        unsafe { gl::GenerateTextureMipmap(id) }
+
+        Ok(Self {
+            id,
+            layers: images.len() as u32,
+        })
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+}
+
+impl Drop for Texture2DArray {
+    fn drop(&mut self) {
+        StateManager::texture_deleted(self.id);
         unsafe { gl::DeleteTextures(1, &self.id) }
     }
 }
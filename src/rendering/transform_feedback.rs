@@ -0,0 +1,172 @@
+use crate::rendering::{
+    buffer::Buffer, frame_stats::FrameStats, mesh::PrimitiveMode, object_label::set_object_label,
+};
+use gl::types::*;
+use gl_bindings as gl;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformFeedbackBufferMode {
+    // All varyings are written to the buffer bound at index 0.
+    Interleaved = gl::INTERLEAVED_ATTRIBS,
+    // Each varying is written to the buffer bound at its own index.
+    Separate = gl::SEPARATE_ATTRIBS,
+}
+
+pub struct TransformFeedback {
+    id: GLuint,
+    primitive_query: GLuint,
+    active_primitive_mode: Option<PrimitiveMode>,
+}
+
+impl TransformFeedback {
+    pub fn new(name: &str) -> Self {
+        let mut id: GLuint = 0;
+        let mut primitive_query: GLuint = 0;
+
+        unsafe {
+            gl::CreateTransformFeedbacks(1, &mut id);
+            gl::CreateQueries(
+                gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN,
+                1,
+                &mut primitive_query,
+            );
+        }
+
+        set_object_label(gl::TRANSFORM_FEEDBACK, id, name);
+
+        Self {
+            id,
+            primitive_query,
+            active_primitive_mode: None,
+        }
+    }
+
+    pub fn bind_buffer(&self, index: u32, buffer: &Buffer) {
+        unsafe { gl::TransformFeedbackBufferBase(self.id, index, buffer.get_id()) }
+    }
+
+    pub fn bind_buffer_range(&self, index: u32, buffer: &Buffer, offset: isize, size: isize) {
+        assert!(
+            offset + size <= buffer.get_size(),
+            "Transform feedback buffer range out of buffer range. Buffer size: {}, Requested offset: {}, Requested size: {}",
+            buffer.get_size(),
+            offset,
+            size
+        );
+        assert_eq!(
+            offset % 4,
+            0,
+            "Transform feedback buffer offset must be a multiple of 4."
+        );
+
+        unsafe { gl::TransformFeedbackBufferRange(self.id, index, buffer.get_id(), offset, size) }
+    }
+
+    // Captures the outputs of the currently bound pipeline until end() is called. Draw calls
+    // issued in between must use a primitive mode compatible with the given one.
+    pub fn begin(&mut self, primitive_mode: PrimitiveMode) {
+        assert!(
+            self.active_primitive_mode.is_none(),
+            "Transform feedback is already active."
+        );
+
+        let capture_mode = match primitive_mode {
+            PrimitiveMode::Points => gl::POINTS,
+            PrimitiveMode::Lines | PrimitiveMode::LineStrip => gl::LINES,
+            PrimitiveMode::Triangles | PrimitiveMode::TriangleStrip => gl::TRIANGLES,
+            PrimitiveMode::Patches => {
+                panic!("Patches cannot be captured. Capture the output of the tessellation evaluation stage instead.")
+            }
+        };
+
+        unsafe {
+            gl::BindTransformFeedback(gl::TRANSFORM_FEEDBACK, self.id);
+            gl::BeginQuery(
+                gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN,
+                self.primitive_query,
+            );
+            gl::BeginTransformFeedback(capture_mode);
+        }
+
+        self.active_primitive_mode = Some(primitive_mode);
+    }
+
+    pub fn pause(&self) {
+        assert!(self.is_active(), "Transform feedback is not active.");
+        unsafe { gl::PauseTransformFeedback() }
+    }
+
+    pub fn resume(&self) {
+        assert!(self.is_active(), "Transform feedback is not active.");
+        unsafe { gl::ResumeTransformFeedback() }
+    }
+
+    pub fn end(&mut self) {
+        assert!(self.is_active(), "Transform feedback is not active.");
+
+        unsafe {
+            gl::EndTransformFeedback();
+            gl::EndQuery(gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN);
+            gl::BindTransformFeedback(gl::TRANSFORM_FEEDBACK, 0);
+        }
+
+        self.active_primitive_mode = None;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_primitive_mode.is_some()
+    }
+
+    // Number of primitives written during the last capture. Stalls until the capture completes.
+    pub fn primitives_written(&self) -> u32 {
+        let mut primitives_written: GLuint = 0;
+
+        unsafe {
+            gl::GetQueryObjectuiv(
+                self.primitive_query,
+                gl::QUERY_RESULT,
+                &mut primitives_written,
+            )
+        }
+
+        primitives_written
+    }
+
+    // Draws the vertices captured during the last capture without reading the count back to
+    // the CPU. A vertex array describing the capture buffers must be bound.
+    pub fn draw(&self, primitive_mode: PrimitiveMode) {
+        self.draw_instanced(primitive_mode, 1)
+    }
+
+    pub fn draw_instanced(&self, primitive_mode: PrimitiveMode, instance_count: u32) {
+        assert!(
+            !self.is_active(),
+            "Cannot draw a transform feedback object while it is capturing."
+        );
+
+        unsafe {
+            gl::DrawTransformFeedbackInstanced(
+                primitive_mode as u32,
+                self.id,
+                instance_count as i32,
+            )
+        }
+
+        // The vertex count stays on the GPU.
+        FrameStats::record_draw(primitive_mode, 0, instance_count)
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for TransformFeedback {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.primitive_query);
+            gl::DeleteTransformFeedbacks(1, &self.id)
+        }
+    }
+}
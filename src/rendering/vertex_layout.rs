@@ -0,0 +1,234 @@
+use gl::types::*;
+use gl_bindings as gl;
+
+// Attribute locations are fixed so that every shader can rely on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    Tangent,
+    TexCoord0,
+    Color,
+    TexCoord1,
+    Joints,
+    Weights,
+    // Per instance attributes. The model matrix takes one location per column.
+    InstanceModel0,
+    InstanceModel1,
+    InstanceModel2,
+    InstanceModel3,
+    InstanceMaterialIndex,
+}
+
+impl VertexAttribute {
+    pub fn location(self) -> u32 {
+        match self {
+            VertexAttribute::Position => 0,
+            VertexAttribute::Normal => 1,
+            VertexAttribute::Tangent => 2,
+            VertexAttribute::TexCoord0 => 3,
+            VertexAttribute::Color => 4,
+            VertexAttribute::TexCoord1 => 5,
+            VertexAttribute::Joints => 6,
+            VertexAttribute::Weights => 7,
+            VertexAttribute::InstanceModel0 => 8,
+            VertexAttribute::InstanceModel1 => 9,
+            VertexAttribute::InstanceModel2 => 10,
+            VertexAttribute::InstanceModel3 => 11,
+            VertexAttribute::InstanceMaterialIndex => 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexFormat {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    // Half precision floats. Read as vec2 in the shader.
+    Half2,
+    // Integer attributes. Read as uint/uvec4 in the shader.
+    UnsignedInt,
+    UnsignedByte4,
+    UnsignedShort4,
+    // Normalized to [0, 1]. Read as vec4 in the shader.
+    UnsignedByte4Normalized,
+    UnsignedShort4Normalized,
+    // Normalized to [-1, 1]. Read as vec4 in the shader.
+    Byte4Normalized,
+}
+
+impl VertexFormat {
+    pub fn component_count(self) -> i32 {
+        match self {
+            VertexFormat::Float | VertexFormat::UnsignedInt => 1,
+            VertexFormat::Float2 | VertexFormat::Half2 => 2,
+            VertexFormat::Float3 => 3,
+            VertexFormat::Float4
+            | VertexFormat::UnsignedByte4
+            | VertexFormat::UnsignedShort4
+            | VertexFormat::UnsignedByte4Normalized
+            | VertexFormat::UnsignedShort4Normalized
+            | VertexFormat::Byte4Normalized => 4,
+        }
+    }
+
+    pub fn size(self) -> u32 {
+        let component_size = match self.data_type() {
+            gl::BYTE | gl::UNSIGNED_BYTE => 1,
+            gl::HALF_FLOAT | gl::UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+
+        component_size * self.component_count() as u32
+    }
+
+    fn data_type(self) -> GLenum {
+        match self {
+            VertexFormat::Float
+            | VertexFormat::Float2
+            | VertexFormat::Float3
+            | VertexFormat::Float4 => gl::FLOAT,
+            VertexFormat::Half2 => gl::HALF_FLOAT,
+            VertexFormat::UnsignedInt => gl::UNSIGNED_INT,
+            VertexFormat::UnsignedByte4 | VertexFormat::UnsignedByte4Normalized => {
+                gl::UNSIGNED_BYTE
+            }
+            VertexFormat::UnsignedShort4 | VertexFormat::UnsignedShort4Normalized => {
+                gl::UNSIGNED_SHORT
+            }
+            VertexFormat::Byte4Normalized => gl::BYTE,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        match self {
+            VertexFormat::UnsignedInt
+            | VertexFormat::UnsignedByte4
+            | VertexFormat::UnsignedShort4 => true,
+            _ => false,
+        }
+    }
+
+    fn is_normalized(self) -> bool {
+        match self {
+            VertexFormat::UnsignedByte4Normalized
+            | VertexFormat::UnsignedShort4Normalized
+            | VertexFormat::Byte4Normalized => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexAttributeDescriptor {
+    pub attribute: VertexAttribute,
+    pub format: VertexFormat,
+    pub offset: u32,
+}
+
+// Describes a single interleaved vertex buffer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttributeDescriptor>,
+    stride: u32,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Appends a tightly packed attribute after the previous one.
+    pub fn attribute(self, attribute: VertexAttribute, format: VertexFormat) -> Self {
+        let offset = self.stride;
+        self.attribute_at_offset(attribute, format, offset)
+    }
+
+    // Places an attribute at an explicit offset. Use together with offset_of! for #[repr(C)]
+    // vertex structs.
+    pub fn attribute_at_offset(
+        mut self,
+        attribute: VertexAttribute,
+        format: VertexFormat,
+        offset: u32,
+    ) -> Self {
+        assert!(
+            !self.has_attribute(attribute),
+            "Vertex attribute {:?} is already part of the layout.",
+            attribute
+        );
+
+        self.attributes.push(VertexAttributeDescriptor {
+            attribute,
+            format,
+            offset,
+        });
+        self.stride = self.stride.max(offset + format.size());
+
+        self
+    }
+
+    // Overrides the computed stride, e.g. to account for trailing padding.
+    pub fn with_stride(mut self, stride: u32) -> Self {
+        assert!(
+            self.attributes
+                .iter()
+                .all(|a| a.offset + a.format.size() <= stride),
+            "Vertex stride {} is smaller than the attributes of the layout.",
+            stride
+        );
+
+        self.stride = stride;
+        self
+    }
+
+    pub fn get_stride(&self) -> u32 {
+        self.stride
+    }
+
+    pub fn attributes(&self) -> &[VertexAttributeDescriptor] {
+        &self.attributes
+    }
+
+    pub fn has_attribute(&self, attribute: VertexAttribute) -> bool {
+        self.attributes.iter().any(|a| a.attribute == attribute)
+    }
+
+    pub(crate) fn apply(&self, vao: GLuint, binding_index: u32) {
+        unsafe {
+            for descriptor in &self.attributes {
+                let location = descriptor.attribute.location();
+                let format = descriptor.format;
+
+                gl::EnableVertexArrayAttrib(vao, location);
+
+                if format.is_integer() {
+                    gl::VertexArrayAttribIFormat(
+                        vao,
+                        location,
+                        format.component_count(),
+                        format.data_type(),
+                        descriptor.offset,
+                    );
+                } else {
+                    gl::VertexArrayAttribFormat(
+                        vao,
+                        location,
+                        format.component_count(),
+                        format.data_type(),
+                        if format.is_normalized() {
+                            gl::TRUE
+                        } else {
+                            gl::FALSE
+                        },
+                        descriptor.offset,
+                    );
+                }
+
+                gl::VertexArrayAttribBinding(vao, location, binding_index);
+            }
+        }
+    }
+}
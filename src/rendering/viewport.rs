@@ -0,0 +1,375 @@
+use crate::{
+    core::{
+        camera::Camera,
+        math::{Mat4, UVec2, Vec4},
+        Rectangle,
+    },
+    imgui::{im_str, Gui, Ui},
+    rendering::{
+        buffer::{Buffer, BufferTarget},
+        framebuffer::Framebuffer,
+        state::StateManager,
+        streaming_buffer::StreamingBuffer,
+    },
+};
+use gl_bindings as gl;
+use std::ops::RangeInclusive;
+
+// Uniform block binding of the per view data, the PerFrameBlock of the vertex shaders.
+pub const PER_VIEW_BINDING: u32 = 0;
+
+// Uniform block binding of the PerFrameBlock of the fragment shaders, filled in by the scene.
+pub const FRAGMENT_PER_FRAME_BINDING: u32 = 2;
+
+// Layout of the PerFrameBlock of the vertex shaders. Shaders that need the pixel rectangle of
+// the view declare a vec4 viewport after eyePosition, the others may leave it out.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PerViewData {
+    pub view_projection: Mat4,
+    pub eye_position: Vec4,
+    // x, y, width and height in pixels.
+    pub viewport: Vec4,
+}
+
+impl PerViewData {
+    pub fn new(camera: &Camera, viewport: &Rectangle) -> Self {
+        let position = camera.position();
+
+        Self {
+            view_projection: camera.view_projection_matrix(),
+            eye_position: Vec4::new(position.x, position.y, position.z, 1.0),
+            viewport: Vec4::new(
+                viewport.x as f32,
+                viewport.y as f32,
+                viewport.width as f32,
+                viewport.height as f32,
+            ),
+        }
+    }
+}
+
+// A rectangle of a render target in [0, 1], relative to its size so layouts follow resizes.
+// The origin is the bottom left corner, as in GL.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ViewportRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    // Side by side columns, the first on the left.
+    pub fn split_horizontal(count: u32) -> Vec<Self> {
+        Self::grid(count, 1)
+    }
+
+    // Stacked rows, the first at the top.
+    pub fn split_vertical(count: u32) -> Vec<Self> {
+        Self::grid(1, count)
+    }
+
+    // Row by row from the top left cell, e.g. 2 x 2 for four player split screen.
+    pub fn grid(columns: u32, rows: u32) -> Vec<Self> {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let width = 1.0 / columns as f32;
+        let height = 1.0 / rows as f32;
+
+        (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| {
+                    Self::new(
+                        column as f32 * width,
+                        1.0 - (row + 1) as f32 * height,
+                        width,
+                        height,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    // The rectangle in pixels of a target of the given size. Neighboring rectangles share
+    // their edges without gaps or overlap.
+    pub fn pixels(&self, target_size: UVec2) -> Rectangle {
+        let x0 = (self.x * target_size.x as f32).round() as i32;
+        let y0 = (self.y * target_size.y as f32).round() as i32;
+        let x1 = ((self.x + self.width) * target_size.x as f32).round() as i32;
+        let y1 = ((self.y + self.height) * target_size.y as f32).round() as i32;
+
+        Rectangle::new(x0, y0, (x1 - x0).max(0), (y1 - y0).max(0))
+    }
+}
+
+impl Default for ViewportRect {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+// A camera rendering into a rectangle of the target.
+pub struct View {
+    pub name: String,
+    pub camera: Camera,
+    pub rect: ViewportRect,
+    // The rectangle is cleared to it before drawing the view. None keeps what is below, e.g.
+    // for views drawn over the whole target by an earlier one.
+    pub clear_color: Option<Vec4>,
+    pub enabled: bool,
+}
+
+impl View {
+    pub fn new(name: &str, camera: Camera, rect: ViewportRect) -> Self {
+        Self {
+            name: name.to_string(),
+            camera,
+            rect,
+            clear_color: Some(Vec4::new(0.0, 0.0, 0.0, 1.0)),
+            enabled: true,
+        }
+    }
+
+    pub fn clear_color(mut self, clear_color: Option<Vec4>) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+}
+
+impl Gui for View {
+    fn gui(&mut self, ui: &Ui) {
+        let rect = &mut self.rect;
+
+        ui.text(&self.name);
+        ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+
+        let mut position = [rect.x, rect.y];
+        let mut size = [rect.width, rect.height];
+
+        if imgui::Drag::new(im_str!("Position"))
+            .range(RangeInclusive::new(0.0, 1.0))
+            .speed(0.005)
+            .build_array(&ui, &mut position)
+        {
+            rect.x = position[0];
+            rect.y = position[1];
+        }
+
+        if imgui::Drag::new(im_str!("Size"))
+            .range(RangeInclusive::new(0.0, 1.0))
+            .speed(0.005)
+            .build_array(&ui, &mut size)
+        {
+            rect.width = size[0];
+            rect.height = size[1];
+        }
+    }
+}
+
+// Several cameras rendering into rectangles of the same target, for split screen and preview
+// panes. Each view gets its own per view uniforms, written to a streaming buffer and bound
+// before its draws, so the passes of a view only draw and never touch the camera uniforms.
+// Views render in order, later ones over the earlier.
+pub struct MultiView {
+    views: Vec<View>,
+    uniforms: StreamingBuffer,
+    max_views: usize,
+}
+
+impl MultiView {
+    pub fn new(max_views: usize) -> Self {
+        let stride = Buffer::align_offset(
+            std::mem::size_of::<PerViewData>() as isize,
+            BufferTarget::Uniform,
+        );
+
+        Self {
+            views: vec![],
+            uniforms: StreamingBuffer::new(
+                "Per View UBO",
+                stride * max_views.max(1) as isize,
+                BufferTarget::Uniform,
+            ),
+            max_views: max_views.max(1),
+        }
+    }
+
+    // The index of the view.
+    pub fn add(&mut self, view: View) -> usize {
+        if self.views.len() >= self.max_views {
+            log::warn!(
+                "View capacity exceeded, '{}' is not rendered. Capacity: {}",
+                view.name,
+                self.max_views
+            );
+        }
+
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> View {
+        self.views.remove(index)
+    }
+
+    pub fn view(&self, index: usize) -> &View {
+        &self.views[index]
+    }
+
+    pub fn view_mut(&mut self, index: usize) -> &mut View {
+        &mut self.views[index]
+    }
+
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    pub fn views_mut(&mut self) -> &mut [View] {
+        &mut self.views
+    }
+
+    pub fn len(&self) -> usize {
+        self.views.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.views.is_empty()
+    }
+
+    // Lays the views out in the rectangles, in order. Views without a rectangle are disabled.
+    pub fn set_layout(&mut self, rects: &[ViewportRect]) {
+        for (i, view) in self.views.iter_mut().enumerate() {
+            match rects.get(i) {
+                Some(rect) => {
+                    view.rect = *rect;
+                    view.enabled = true;
+                }
+                None => view.enabled = false,
+            }
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.uniforms.begin_frame()
+    }
+
+    // Must be called after the last draw call of the frame.
+    pub fn end_frame(&mut self) {
+        self.uniforms.end_frame()
+    }
+
+    // Renders every enabled view into its rectangle of the target. For each view the viewport,
+    // the scissor rectangle and the per view uniforms are set, the rectangle is cleared if clear
+    // is set and the view has a clear color, and draw is called with the index of the view. The
+    // camera of the view takes the aspect ratio of its rectangle. Passes of the same frame, e.g.
+    // the geometry and then the sky, render one after the other between begin_frame and
+    // end_frame.
+    pub fn render<F>(&mut self, target: &Framebuffer, clear: bool, mut draw: F)
+    where
+        F: FnMut(usize, &View),
+    {
+        let target_size = target.size();
+
+        target.bind();
+
+        for (i, view) in self.views.iter_mut().enumerate().take(self.max_views) {
+            let rect = view.rect.pixels(target_size);
+
+            if !view.enabled || rect.width == 0 || rect.height == 0 {
+                continue;
+            }
+
+            view.camera
+                .set_viewport_size(UVec2::new(rect.width as u32, rect.height as u32));
+
+            let allocation = match self.uniforms.push(&PerViewData::new(&view.camera, &rect)) {
+                Some(allocation) => allocation,
+                None => break,
+            };
+            self.uniforms.bind(PER_VIEW_BINDING, &allocation);
+
+            StateManager::set_viewport(rect.x, rect.y, rect.width, rect.height);
+            StateManager::set_scissor(Some(rect));
+
+            if let Some(clear_color) = view.clear_color.as_ref().filter(|_| clear) {
+                target.clear(clear_color)
+            }
+
+            draw(i, view);
+        }
+
+        StateManager::set_scissor(None);
+        StateManager::set_viewport(0, 0, target_size.x as i32, target_size.y as i32)
+    }
+
+    // Composites views rendered to separate framebuffers, source i into the rectangle of view
+    // i of the destination. None composites into the default framebuffer of the given size.
+    pub fn composite(
+        &self,
+        sources: &[&Framebuffer],
+        destination: Option<&Framebuffer>,
+        destination_size: UVec2,
+    ) {
+        let destination_id = destination.map_or(Framebuffer::default_id(), |d| d.id());
+
+        for (view, source) in self.views.iter().zip(sources) {
+            let rect = view.rect.pixels(destination_size);
+
+            if !view.enabled || rect.width == 0 || rect.height == 0 {
+                continue;
+            }
+
+            let source_size = source.size();
+            let filter = if source_size == UVec2::new(rect.width as u32, rect.height as u32) {
+                gl::NEAREST
+            } else {
+                gl::LINEAR
+            };
+
+            unsafe {
+                gl::BlitNamedFramebuffer(
+                    source.id(),
+                    destination_id,
+                    0,
+                    0,
+                    source_size.x as i32,
+                    source_size.y as i32,
+                    rect.x,
+                    rect.y,
+                    rect.x + rect.width,
+                    rect.y + rect.height,
+                    gl::COLOR_BUFFER_BIT,
+                    filter,
+                )
+            }
+        }
+    }
+}
+
+impl Gui for MultiView {
+    fn gui(&mut self, ui: &Ui) {
+        imgui::TreeNode::new(im_str!("Views"))
+            .default_open(false)
+            .build(ui, || {
+                for (index, view) in self.views.iter_mut().enumerate() {
+                    let id = ui.push_id(index as i32);
+                    view.gui(ui);
+                    id.pop(ui);
+                }
+            });
+    }
+}
@@ -0,0 +1,99 @@
+use engine::{
+    config::EngineConfig,
+    golden_image::GoldenImageTest,
+    headless::HeadlessApplication,
+    math::vector::{Vec2, Vec4},
+    rendering::sprite_batch::SpriteBatch,
+    scene::Scene,
+    Context, Settings, Version,
+};
+use std::env;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 32;
+
+// Pixel aligned quads in saturated colors, which every driver renders to the same bytes. A red,
+// green, blue and white quadrant under a black quad in the center, one layer higher.
+struct QuadrantScene {
+    sprite_batch: SpriteBatch,
+}
+
+impl QuadrantScene {
+    fn new(_: Context) -> Self {
+        Self {
+            sprite_batch: SpriteBatch::new()
+                .unwrap_or_else(|error| panic!("Sprite batch creation error: {}", error)),
+        }
+    }
+}
+
+impl Scene for QuadrantScene {
+    fn draw(&mut self, _: Context) {
+        let screen_size = Vec2::new(WIDTH as f32, HEIGHT as f32);
+        let quadrant = screen_size * 0.5;
+
+        let quadrants = [
+            (Vec2::new(0.0, 0.0), Vec4::new(1.0, 0.0, 0.0, 1.0)),
+            (Vec2::new(quadrant.x, 0.0), Vec4::new(0.0, 1.0, 0.0, 1.0)),
+            (Vec2::new(0.0, quadrant.y), Vec4::new(0.0, 0.0, 1.0, 1.0)),
+            (quadrant, Vec4::new(1.0, 1.0, 1.0, 1.0)),
+        ];
+
+        for (position, color) in quadrants.iter() {
+            self.sprite_batch.quad(*position, quadrant, *color, 0);
+        }
+
+        self.sprite_batch
+            .quad(quadrant * 0.5, quadrant, Vec4::new(0.0, 0.0, 0.0, 1.0), 1);
+
+        self.sprite_batch.render(screen_size)
+    }
+}
+
+fn settings() -> Settings {
+    let config = EngineConfig {
+        window_size: [WIDTH, HEIGHT],
+        ..Default::default()
+    };
+
+    Settings::from_config(
+        "Golden image test",
+        Version {
+            major: 0,
+            minor: 1,
+            patch: 0,
+        },
+        &config,
+    )
+}
+
+// The event loop the headless context is created with still needs a display server on Linux.
+fn has_display() -> bool {
+    !cfg!(target_os = "linux")
+        || env::var_os("DISPLAY").is_some()
+        || env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+#[test]
+fn sprite_quadrants_match_reference() {
+    if !has_display() {
+        eprintln!("No display server, skipping the golden image test.");
+        return;
+    }
+
+    let mut application = HeadlessApplication::new(settings())
+        .unwrap_or_else(|e| panic!("Failed to create a headless context: {}", e));
+
+    let result = GoldenImageTest::new("sprite_quadrants", "tests/golden")
+        .frames(1)
+        .pixel_tolerance(0.0)
+        .max_differing_pixels(0.0)
+        .run(&mut application, QuadrantScene::new)
+        .unwrap_or_else(|e| panic!("Golden image test failed: {}", e));
+
+    assert!(
+        result.passed,
+        "{} of {} pixels differ, mean delta E {:.3}, max {:.3}.",
+        result.differing_pixels, result.pixel_count, result.mean_difference, result.max_difference
+    );
+}